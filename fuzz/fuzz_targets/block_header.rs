@@ -0,0 +1,10 @@
+#![no_main]
+
+use bitcoin::{parser::BtcEncode, structs::block_header::BlockHeader};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = BlockHeader::parse(data.to_vec()) {
+        assert_eq!(header.serialize(), data[..80]);
+    }
+});
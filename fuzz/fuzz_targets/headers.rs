@@ -0,0 +1,8 @@
+#![no_main]
+
+use bitcoin::{messages::headers::Headers, parser::BtcEncode};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Headers::parse(data.to_vec());
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use bitcoin::{parser::BtcEncode, structs::inventory::Inventory};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(inventory) = Inventory::parse(data.to_vec()) {
+        assert_eq!(inventory.serialize(), data);
+    }
+});
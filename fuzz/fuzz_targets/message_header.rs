@@ -0,0 +1,24 @@
+#![no_main]
+
+use bitcoin::{
+    message::MessageHeader,
+    messages::{block::Block, headers::Headers},
+    parser::BtcEncode,
+};
+use libfuzzer_sys::fuzz_target;
+
+// Los bytes son el stream de un peer: se lee el header y luego su payload como lo hace PeerStreamLoop,
+// parseando headers y block a medida que se leen
+fuzz_target!(|data: &[u8]| {
+    let mut stream = data;
+    let Ok(header) = MessageHeader::read(&mut stream) else {
+        return;
+    };
+    let _ = match header.command.as_str() {
+        "headers" => {
+            header.read_streamed(&mut stream, |parser| Headers::decode(parser).map(|_| ()))
+        }
+        "block" => header.read_streamed(&mut stream, |parser| Block::decode(parser).map(|_| ())),
+        _ => header.read_payload(&mut stream).map(|_| ()),
+    };
+});
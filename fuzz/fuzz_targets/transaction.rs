@@ -0,0 +1,15 @@
+#![no_main]
+
+use bitcoin::{messages::transaction::Transaction, parser::BtcEncode};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transaction) = Transaction::parse(data.to_vec()) {
+        let _ = transaction.hash();
+        let _ = transaction.weight();
+        let _ = transaction.check_standard();
+        for output in &transaction.outputs {
+            let _ = output.address();
+        }
+    }
+});
@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::error::CustomError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Subnet representa una direccion IP o una subred, las IPv4 se guardan mapeadas a IPv6 como las direcciones de los peers.
+/// - address: direccion de la subred.
+/// - prefix: cantidad de bits de la direccion que deben coincidir, 128 para una unica direccion.
+pub struct Subnet {
+    address: Ipv6Addr,
+    prefix: u8,
+}
+
+impl Subnet {
+    /// Indica si la direccion pertenece a la subred.
+    pub fn contains(&self, address: &Ipv6Addr) -> bool {
+        let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+        u128::from(self.address) & mask == u128::from(*address) & mask
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = CustomError;
+
+    /// Parsea una direccion IP (ej: 10.0.0.1) o una subred en notacion CIDR (ej: 10.0.0.0/8, 2001:db8::/32).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let address =
+            IpAddr::from_str(address).map_err(|_| CustomError::ConfigErrorReadingValue)?;
+        let (address, max_prefix, offset) = match address {
+            IpAddr::V4(address) => (address.to_ipv6_mapped(), 32, 96),
+            IpAddr::V6(address) => (address, 128, 0),
+        };
+        let prefix = match prefix {
+            Some(prefix) => {
+                u8::from_str(prefix).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(CustomError::ConfigErrorReadingValue);
+        }
+        Ok(Self {
+            address,
+            prefix: prefix + offset,
+        })
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.address.to_ipv4_mapped() {
+            Some(address) if self.prefix >= 96 => write!(f, "{}/{}", address, self.prefix - 96),
+            _ => write!(f, "{}/{}", self.address, self.prefix),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// AccessList contiene las direcciones con las que el nodo siempre acepta o siempre rechaza conexiones,
+/// tanto entrantes como salientes.
+/// - whitelist: subredes permitidas, sus peers no se banean ni se desalojan y se aceptan aunque no haya lugar.
+/// - blacklist: subredes rechazadas, no se aceptan sus conexiones ni se las llama. Tiene prioridad sobre la whitelist.
+pub struct AccessList {
+    pub whitelist: Vec<Subnet>,
+    pub blacklist: Vec<Subnet>,
+}
+
+impl AccessList {
+    /// Indica si la direccion esta en la whitelist y no en la blacklist.
+    pub fn is_whitelisted(&self, address: &Ipv6Addr) -> bool {
+        !self.is_blacklisted(address) && self.whitelist.iter().any(|s| s.contains(address))
+    }
+
+    /// Indica si la direccion esta en la blacklist.
+    pub fn is_blacklisted(&self, address: &Ipv6Addr) -> bool {
+        self.blacklist.iter().any(|subnet| subnet.contains(address))
+    }
+}
+
+/// Parsea una lista de direcciones o subredes separadas por coma.
+pub fn parse_subnets(value: &str) -> Result<Vec<Subnet>, CustomError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|subnet| !subnet.is_empty())
+        .map(Subnet::from_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subnet_contains_addresses() -> Result<(), CustomError> {
+        let subnet = Subnet::from_str("10.0.0.0/8")?;
+        assert!(subnet.contains(&"::ffff:10.20.30.40".parse().unwrap()));
+        assert!(!subnet.contains(&"::ffff:11.0.0.1".parse().unwrap()));
+        assert_eq!(subnet.to_string(), "10.0.0.0/8");
+
+        let subnet = Subnet::from_str("2001:db8::/32")?;
+        assert!(subnet.contains(&"2001:db8:1::1".parse().unwrap()));
+        assert!(!subnet.contains(&"2001:db9::1".parse().unwrap()));
+
+        let subnet = Subnet::from_str("127.0.0.1")?;
+        assert!(subnet.contains(&"::ffff:127.0.0.1".parse().unwrap()));
+        assert!(!subnet.contains(&"::ffff:127.0.0.2".parse().unwrap()));
+
+        assert!(Subnet::from_str("0.0.0.0/0")?.contains(&"::ffff:1.2.3.4".parse().unwrap()));
+        assert!(Subnet::from_str("10.0.0.0/33").is_err());
+        assert!(Subnet::from_str("localhost").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn blacklist_has_priority_over_whitelist() -> Result<(), CustomError> {
+        let access_list = AccessList {
+            whitelist: parse_subnets("10.0.0.0/8")?,
+            blacklist: parse_subnets("10.0.0.1, 192.168.0.0/16")?,
+        };
+        assert!(access_list.is_whitelisted(&"::ffff:10.0.0.2".parse().unwrap()));
+        assert!(!access_list.is_whitelisted(&"::ffff:10.0.0.1".parse().unwrap()));
+        assert!(access_list.is_blacklisted(&"::ffff:10.0.0.1".parse().unwrap()));
+        assert!(access_list.is_blacklisted(&"::ffff:192.168.1.1".parse().unwrap()));
+        assert!(!access_list.is_blacklisted(&"::ffff:10.0.0.2".parse().unwrap()));
+        Ok(())
+    }
+}
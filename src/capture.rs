@@ -0,0 +1,208 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Write},
+    net::SocketAddrV6,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::CustomError,
+    message::{MessageHeader, MESSAGE_HEADER_SIZE},
+    parser::{BtcEncode, BufferParser},
+    utils::open_new_file,
+};
+
+#[derive(Debug, Clone)]
+/// Capture es el archivo en el que se guardan los mensajes recibidos de los peers tal como llegaron,
+/// para poder reproducirlos luego sin conectarse a la red (ver ReplayLoop).
+/// Cada mensaje se agrega al final del archivo como un CapturedMessage.
+/// Los elementos son:
+/// - path: Path del archivo de la captura.
+/// - file: Archivo de la captura, compartido por los threads de todos los peers.
+pub struct Capture {
+    pub path: String,
+    file: Arc<Mutex<File>>,
+}
+
+impl PartialEq for Capture {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Capture {
+    /// Abre el archivo de la captura, agregando los mensajes a los de capturas anteriores si ya existe.
+    pub fn open(path: String) -> Result<Self, CustomError> {
+        let file = open_new_file(path.clone(), true)?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Agrega al archivo los bytes de un mensaje recibido del peer en el timestamp en milisegundos recibido.
+    pub fn record(
+        &self,
+        timestamp: u64,
+        address: SocketAddrV6,
+        bytes: &[u8],
+    ) -> Result<(), CustomError> {
+        let message = CapturedMessage {
+            timestamp,
+            address,
+            bytes: bytes.to_vec(),
+        };
+        self.file.lock()?.write_all(&message.serialize())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// CapturedMessage es un mensaje recibido de un peer, guardado en una captura.
+/// Se serializa como el timestamp (u64 little endian), la direccion del peer (IPv6 y puerto big endian),
+/// el largo de los bytes (u32 little endian) y los bytes.
+/// Los elementos son:
+/// - timestamp: Timestamp en milisegundos en el que se recibio el header del mensaje.
+/// - address: Direccion del peer que lo envio, identifica al peer en la captura.
+/// - bytes: Header y payload del mensaje tal como se leyeron del stream. Si la lectura fallo,
+///   el mensaje esta incompleto y contiene solo lo que se llego a leer.
+pub struct CapturedMessage {
+    pub timestamp: u64,
+    pub address: SocketAddrV6,
+    pub bytes: Vec<u8>,
+}
+
+impl CapturedMessage {
+    /// Devuelve el header del mensaje, o None si no se llego a leer completo o es invalido.
+    pub fn header(&self) -> Option<MessageHeader> {
+        let header = self.bytes.get(..MESSAGE_HEADER_SIZE)?.try_into().ok()?;
+        MessageHeader::parse(header).ok()
+    }
+
+    /// Indica si se leyeron todos los bytes del mensaje, es decir su header y todo su payload.
+    pub fn is_complete(&self) -> bool {
+        self.header().is_some_and(|header| {
+            self.bytes.len() >= MESSAGE_HEADER_SIZE + header.payload_size as usize
+        })
+    }
+}
+
+impl BtcEncode for CapturedMessage {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.timestamp.to_le_bytes());
+        buffer.extend(self.address.ip().octets());
+        buffer.extend(self.address.port().to_be_bytes());
+        buffer.extend((self.bytes.len() as u32).to_le_bytes());
+        buffer.extend(&self.bytes);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let timestamp = parser.extract_u64()?;
+        let address = parser.extract_address()?;
+        let size = parser.extract_u32()? as usize;
+        let bytes = parser.extract_buffer(size)?.to_vec();
+        Ok(Self {
+            timestamp,
+            address,
+            bytes,
+        })
+    }
+}
+
+/// Lee todos los mensajes de una captura, en el orden en que se recibieron.
+/// Devuelve CustomError si no se puede leer el archivo o si un mensaje esta cortado.
+pub fn read_capture(path: &str) -> Result<Vec<CapturedMessage>, CustomError> {
+    let mut buffer = vec![];
+    File::open(path)?.read_to_end(&mut buffer)?;
+    let mut parser = BufferParser::new(buffer);
+
+    let mut messages = vec![];
+    while !parser.is_empty() {
+        messages.push(CapturedMessage::decode(&mut parser)?);
+    }
+    Ok(messages)
+}
+
+/// Devuelve las direcciones de los peers de la captura, en el orden en que enviaron su primer mensaje.
+pub fn captured_peers(messages: &[CapturedMessage]) -> Vec<SocketAddrV6> {
+    let mut seen = HashSet::new();
+    messages
+        .iter()
+        .map(|message| message.address)
+        .filter(|address| seen.insert(*address))
+        .collect()
+}
+
+/// CaptureReader lee de un stream guardando los bytes leidos, si se estan capturando los mensajes.
+/// Los elementos son:
+/// - stream: Stream del que se lee.
+/// - captured: Bytes leidos hasta el momento, None si no se capturan.
+pub struct CaptureReader<'a, R: Read> {
+    stream: &'a mut R,
+    captured: Option<&'a mut Vec<u8>>,
+}
+
+impl<'a, R: Read> CaptureReader<'a, R> {
+    pub fn new(stream: &'a mut R, captured: Option<&'a mut Vec<u8>>) -> Self {
+        Self { stream, captured }
+    }
+}
+
+impl<R: Read> Read for CaptureReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.stream.read(buf)?;
+        if let Some(captured) = self.captured.as_mut() {
+            captured.extend(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_file, net::Ipv6Addr};
+
+    use super::*;
+    use crate::{messages::ping_pong::Ping, network::Network};
+
+    #[test]
+    fn captured_messages_are_read_in_order() -> Result<(), CustomError> {
+        let path = String::from("tests/test_capture.bin");
+        let first_peer = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 18333, 0, 0);
+        let second_peer = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 18444, 0, 0);
+        let ping = Ping { nonce: 7 };
+        let mut bytes = MessageHeader::new(&ping, Network::Testnet).serialize();
+        bytes.extend(ping.serialize());
+
+        remove_file(&path).ok();
+        let capture = Capture::open(path.clone())?;
+        capture.record(1, second_peer, &bytes)?;
+        capture.record(2, first_peer, &bytes[..30])?;
+        capture.record(3, second_peer, &bytes)?;
+        let messages = read_capture(&path)?;
+        remove_file(path)?;
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].timestamp, 1);
+        assert_eq!(messages[0].bytes, bytes);
+        assert!(messages[0].is_complete());
+        assert_eq!(messages[0].header().unwrap().command, "ping");
+        assert!(!messages[1].is_complete());
+        assert_eq!(captured_peers(&messages), vec![second_peer, first_peer]);
+        Ok(())
+    }
+
+    #[test]
+    fn capture_reader_keeps_read_bytes() -> Result<(), CustomError> {
+        let bytes = vec![1, 2, 3, 4];
+        let mut captured = vec![];
+        let mut stream = bytes.as_slice();
+        let mut reader = CaptureReader::new(&mut stream, Some(&mut captured));
+        let mut buffer = [0; 3];
+        reader.read_exact(&mut buffer)?;
+
+        assert_eq!(captured, vec![1, 2, 3]);
+        Ok(())
+    }
+}
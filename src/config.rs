@@ -2,26 +2,105 @@ use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
+use std::time::Duration;
 
+use crate::access_list::{parse_subnets, AccessList, Subnet};
+use crate::capture::Capture;
 use crate::error::CustomError;
+use crate::logger::{LogFilter, LogFormat, LogLevel};
+use crate::message::MAX_USER_AGENT_SIZE;
+use crate::network::Network;
+use crate::notify::NotifyCommands;
+use crate::peer::{default_user_agent, PeerConfig, PeerTimeouts};
+use crate::price::{HttpPriceSource, PriceCache};
+use crate::proxy::Proxy;
+use crate::services::{parse_services, DEFAULT_SERVICES};
+use crate::states::pending_txs_state::{DEFAULT_MAX_MEMPOOL_SIZE, DEFAULT_MIN_RELAY_FEE};
+use crate::structs::hash32::Hash32;
+
+/// Cantidad maxima de peers entrantes por defecto.
+const DEFAULT_MAX_INBOUND: usize = 16;
+/// Cantidad de peers mas rapidos entre los que se reparte la descarga por defecto.
+const DEFAULT_SYNC_PEERS: usize = 3;
+/// Moneda en la que se muestran los valores aproximados por defecto.
+const DEFAULT_FIAT_CURRENCY: &str = "USD";
 
 #[derive(Debug)]
 
 /// Config es una estructura que contiene los valores de configuracion del nodo.
-/// Estos valores se leen de un archivo de configuracion.
+/// Estos valores se leen de un archivo de configuracion, y pueden pisarse desde la linea de comandos.
 /// Los valores son:
-/// - seed: semilla DNS para obtener direcciones IP.
+/// - seed: semillas DNS para obtener direcciones IP, separadas por coma.
 /// - protocol_version: version del protocolo.
+/// - user_agent: user agent con el que el nodo se presenta a los peers (USER_AGENT), con el formato /nombre:version/ de BIP14.
+/// - services: servicios que anuncia el nodo y que atiende (SERVICES, ej: NODE_NETWORK,NODE_WITNESS), se ignoran si es solo cliente.
 /// - port: puerto en el que escucha el nodo.
+/// - bind: interfaces en las que escucha el nodo (BIND, ej: 0.0.0.0,[::1]:18444), si no se indica puerto se usa port.
+///   Si esta vacio se escucha en todas las interfaces IPv6, que en la mayoria de los sistemas tambien aceptan IPv4.
+/// - network: red a la que se conecta el nodo (mainnet, testnet o regtest), por defecto testnet.
+/// - rest_port: puerto de la API REST de solo lectura, si es 0 la API queda deshabilitada.
+/// - rpc_port: puerto del servidor JSON-RPC (RPC_PORT), si es 0 queda deshabilitado.
+/// - publisher_port: puerto donde se publican los eventos de bloques y transacciones, si es 0 queda deshabilitado.
+/// - log_filter: nivel minimo de los logs (LOG_LEVEL) y de cada modulo (LOG_FILTER, ej: sync:warn,peer:debug).
+/// - log_format: formato de los logs en el archivo y la consola (text o json), por defecto text.
+/// - proxy: proxy SOCKS5 (host:port) por el que se abren las conexiones salientes, por ejemplo Tor.
+/// - proxy_isolate: si es true se usan credenciales distintas por peer en el proxy (stream isolation).
+/// - whitelist: IPs o subredes (WHITELIST, ej: 10.0.0.0/8,::1) con las que siempre se aceptan conexiones.
+/// - blacklist: IPs o subredes (BLACKLIST) con las que nunca se aceptan ni se abren conexiones.
+/// - max_inbound: cantidad maxima de peers entrantes (MAX_INBOUND), al alcanzarla se desaloja al menos util.
+/// - blocks_only: si es true el nodo pide a los peers que no le anuncien transacciones (BLOCKS_ONLY), solo envia las propias.
+/// - max_mempool: tamaño maximo de la mempool en megabytes (MAX_MEMPOOL), al superarlo se descartan las transacciones de menor fee rate.
+/// - min_relay_fee: fee rate minimo en satoshis por kilobyte virtual (MIN_RELAY_FEE) de las transacciones que se aceptan y retransmiten.
+/// - assume_valid: hash de un bloque (ASSUME_VALID) cuyos ancestros se asumen validos, sin verificar sus scripts.
+/// - spv: si es true el nodo funciona como cliente SPV (SPV), descargando solo headers y los bloques filtrados por las wallets.
+/// - compress_blocks: si es true los bloques que se guardan se comprimen con zstd (COMPRESS_BLOCKS).
+/// - sync_peers: cantidad de peers mas rapidos entre los que se reparten los pedidos de headers y bloques (SYNC_PEERS).
+/// - peer_timeouts: timeouts en milisegundos de conexion (CONNECT_TIMEOUT), handshake (HANDSHAKE_TIMEOUT), lectura (READ_TIMEOUT) y respuesta a los pings (PING_TIMEOUT) con los peers.
+/// - fiat_display: si es true se muestran los montos aproximados en fiat (FIAT_DISPLAY), por defecto false,
+///   ya que consultar el precio revela a la fuente de precios que se usa bitcoin (la consulta sale por el proxy si hay uno).
+/// - fiat_currency: codigo de la moneda fiat en la que se muestran los montos (FIAT_CURRENCY), por defecto USD.
+/// - price_source: URL http de la fuente de precios (PRICE_SOURCE, ej: http://localhost:8080/price/{currency}).
+/// - block_notify: comando que se ejecuta con cada bloque nuevo una vez sincronizado (BLOCK_NOTIFY), %s se reemplaza por su hash.
+/// - wallet_notify: comando que se ejecuta con cada transaccion de las wallets (WALLET_NOTIFY), %s se reemplaza por su hash.
+/// - capture: archivo en el que se guardan los mensajes recibidos de los peers (CAPTURE), para reproducirlos con --replay.
 pub struct Config {
     pub seed: String,
     pub protocol_version: i32,
+    pub user_agent: String,
+    pub services: u64,
     pub port: u16,
+    pub bind: Vec<(IpAddr, Option<u16>)>,
     pub log_file: String,
     pub npeers: u8,
+    pub max_inbound: usize,
+    pub sync_peers: usize,
+    pub whitelist: Vec<Subnet>,
+    pub blacklist: Vec<Subnet>,
     pub client_only: bool,
+    pub blocks_only: bool,
+    pub max_mempool: usize,
+    pub min_relay_fee: u64,
+    pub assume_valid: Option<Hash32>,
+    pub spv: bool,
+    pub compress_blocks: bool,
     pub store_path: String,
+    pub rest_port: u16,
+    pub rpc_port: u16,
+    pub publisher_port: u16,
+    pub network: Network,
+    pub log_filter: LogFilter,
+    pub log_format: LogFormat,
+    pub peer_timeouts: PeerTimeouts,
+    pub proxy: Option<String>,
+    pub proxy_isolate: bool,
+    pub fiat_display: bool,
+    pub fiat_currency: String,
+    pub price_source: Option<HttpPriceSource>,
+    pub block_notify: Option<String>,
+    pub wallet_notify: Option<String>,
+    pub capture: Option<String>,
 }
 
 impl Config {
@@ -34,8 +113,28 @@ impl Config {
     /// - El archivo tiene un formato invalido.
     /// - El archivo no contiene todos los valores requeridos.
     pub fn from_file(path: &str) -> Result<Self, CustomError> {
-        let file = File::open(path).map_err(|_| CustomError::ConfigMissingFile)?;
-        Self::from_reader(file)
+        Self::from_file_with_overrides(Some(path), vec![])
+    }
+
+    /// Crea un config a partir de un archivo de configuracion opcional, pisando sus valores con los recibidos.
+    /// Los overrides tienen la forma ({NOMBRE}, {VALOR}), con los mismos nombres que el archivo de configuracion.
+    /// Devuelve CustomError en los mismos casos que from_file, verificando los valores requeridos luego de aplicar los overrides.
+    pub fn from_file_with_overrides(
+        path: Option<&str>,
+        overrides: Vec<(&str, String)>,
+    ) -> Result<Self, CustomError> {
+        let mut config = Self::default();
+
+        if let Some(path) = path {
+            let file = File::open(path).map_err(|_| CustomError::ConfigMissingFile)?;
+            config.load_reader(file)?;
+        }
+        for (name, value) in overrides {
+            config.load_setting(name, &value)?;
+        }
+
+        Self::check_required_values(&config)?;
+        Ok(config)
     }
 
     /// Crea un config a partir de cualquier implementacion del trait Read
@@ -44,39 +143,34 @@ impl Config {
     /// - El contenido tiene un formato invalido.
     /// - El contenido no contiene todos los valores requeridos.
     /// - No se pudo leer el contenido.
-    fn from_reader<T: Read>(content: T) -> Result<Config, CustomError> {
-        let reader = BufReader::new(content);
+    pub fn from_reader<T: Read>(content: T) -> Result<Config, CustomError> {
+        let mut config = Self::default();
+        config.load_reader(content)?;
 
-        let mut config = Self {
-            seed: String::new(),
-            protocol_version: 0,
-            port: 0,
-            log_file: String::new(),
-            npeers: 0,
-            client_only: false,
-            store_path: String::from("store"),
-        };
+        Self::check_required_values(&config)?;
+
+        Ok(config)
+    }
+
+    /// Carga en el config los valores leidos del contenido recibido, sin verificar los valores requeridos.
+    fn load_reader<T: Read>(&mut self, content: T) -> Result<(), CustomError> {
+        let reader = BufReader::new(content);
 
         for line in reader.lines() {
             let current_line = line.map_err(|_| CustomError::ConfigInvalid)?;
 
-            let setting: Vec<&str> = current_line.split('=').collect();
-
-            // ['KEY', 'VALUE'].len() == 2
-            if setting.len() != 2 {
-                return Err(CustomError::ConfigInvalid);
-            }
-            Self::load_setting(&mut config, setting[0], setting[1])?;
+            // el valor puede contener '=', por ejemplo los comandos de BLOCK_NOTIFY y WALLET_NOTIFY
+            let (name, value) = current_line
+                .split_once('=')
+                .ok_or(CustomError::ConfigInvalid)?;
+            self.load_setting(name, value)?;
         }
-
-        Self::check_required_values(&config)?;
-
-        Ok(config)
+        Ok(())
     }
 
     /// Verifica que todos los valores requeridos esten cargados en el config.
     fn check_required_values(config: &Config) -> Result<(), CustomError> {
-        if config.seed.is_empty() {
+        if config.get_seeds().is_empty() {
             return Err(CustomError::ConfigMissingValue);
         }
         if config.protocol_version == 0 {
@@ -105,26 +199,264 @@ impl Config {
                 self.protocol_version =
                     i32::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
             }
+            "USER_AGENT" => self.user_agent = parse_user_agent(value)?,
+            "SERVICES" => self.services = parse_services(value)?,
             "PORT" => {
                 self.port =
                     u16::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
             }
+            "BIND" => self.bind = parse_bind(value)?,
             "LOG" => self.log_file = String::from(value),
             "NPEERS" => {
                 self.npeers =
                     u8::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
             }
+            "MAX_INBOUND" => {
+                self.max_inbound =
+                    usize::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "SYNC_PEERS" => {
+                self.sync_peers =
+                    usize::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "WHITELIST" => self.whitelist = parse_subnets(value)?,
+            "BLACKLIST" => self.blacklist = parse_subnets(value)?,
             "STORE_PATH" => self.store_path = String::from(value),
             "CLIENT_ONLY" => self.client_only = value == "true",
+            "BLOCKS_ONLY" => self.blocks_only = value == "true",
+            "MAX_MEMPOOL" => {
+                self.max_mempool =
+                    usize::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "MIN_RELAY_FEE" => {
+                self.min_relay_fee =
+                    u64::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "ASSUME_VALID" => {
+                self.assume_valid = match value.is_empty() {
+                    true => None,
+                    false => Some(
+                        Hash32::from_str(value)
+                            .map_err(|_| CustomError::ConfigErrorReadingValue)?,
+                    ),
+                }
+            }
+            "SPV" => self.spv = value == "true",
+            "COMPRESS_BLOCKS" => self.compress_blocks = value == "true",
+            "REST_PORT" => {
+                self.rest_port =
+                    u16::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "RPC_PORT" => {
+                self.rpc_port =
+                    u16::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "PUBLISHER_PORT" => {
+                self.publisher_port =
+                    u16::from_str(value).map_err(|_| CustomError::ConfigErrorReadingValue)?
+            }
+            "NETWORK" => self.network = Network::from_str(value)?,
+            "LOG_LEVEL" => self.log_filter.level = LogLevel::from_str(value)?,
+            "LOG_FILTER" => self.log_filter.set_targets(value)?,
+            "LOG_FORMAT" => self.log_format = LogFormat::from_str(value)?,
+            "CONNECT_TIMEOUT" => self.peer_timeouts.connect = parse_millis(value)?,
+            "HANDSHAKE_TIMEOUT" => self.peer_timeouts.handshake = parse_millis(value)?,
+            "READ_TIMEOUT" => self.peer_timeouts.read = parse_millis(value)?,
+            "PING_TIMEOUT" => self.peer_timeouts.ping = parse_millis(value)?,
+            "PROXY" => self.proxy = (!value.is_empty()).then(|| String::from(value)),
+            "PROXY_ISOLATE" => self.proxy_isolate = value == "true",
+            "FIAT_DISPLAY" => self.fiat_display = value == "true",
+            "FIAT_CURRENCY" => self.fiat_currency = parse_currency(value)?,
+            "PRICE_SOURCE" => {
+                self.price_source = match value.is_empty() {
+                    true => None,
+                    false => Some(HttpPriceSource::from_url(value)?),
+                }
+            }
+            "BLOCK_NOTIFY" => self.block_notify = (!value.is_empty()).then(|| String::from(value)),
+            "WALLET_NOTIFY" => {
+                self.wallet_notify = (!value.is_empty()).then(|| String::from(value))
+            }
+            "CAPTURE" => self.capture = (!value.is_empty()).then(|| String::from(value)),
             _ => (),
         }
         Ok(())
     }
+
+    /// Devuelve las semillas DNS configuradas.
+    pub fn get_seeds(&self) -> Vec<String> {
+        self.seed
+            .split(',')
+            .map(str::trim)
+            .filter(|seed| !seed.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Devuelve las direcciones en las que escucha el nodo, completando el puerto de las que no lo indican.
+    pub fn get_bind_addresses(&self) -> Vec<SocketAddr> {
+        if self.bind.is_empty() {
+            return vec![SocketAddr::from((Ipv6Addr::UNSPECIFIED, self.port))];
+        }
+        self.bind
+            .iter()
+            .map(|(ip, port)| SocketAddr::new(*ip, port.unwrap_or(self.port)))
+            .collect()
+    }
+
+    /// Devuelve las listas de direcciones permitidas y rechazadas.
+    pub fn get_access_list(&self) -> AccessList {
+        AccessList {
+            whitelist: self.whitelist.clone(),
+            blacklist: self.blacklist.clone(),
+        }
+    }
+
+    /// Devuelve el proxy SOCKS5 configurado, si lo hay.
+    pub fn get_proxy(&self) -> Option<Proxy> {
+        self.proxy.as_ref().map(|address| Proxy {
+            address: address.clone(),
+            isolate: self.proxy_isolate,
+        })
+    }
+
+    /// Devuelve la configuracion de las conexiones con los peers, abriendo el archivo de la captura si se configuro.
+    /// Un cliente SPV o solo cliente no atiende conexiones entrantes, ya que no tiene los bloques completos, por lo que no anuncia servicios.
+    /// Devuelve CustomError si no se puede abrir el archivo de la captura.
+    pub fn get_peer_config(&self) -> Result<PeerConfig, CustomError> {
+        Ok(PeerConfig {
+            network: self.network,
+            services: match self.client_only || self.spv {
+                true => 0x00,
+                false => self.services,
+            },
+            timeouts: self.peer_timeouts,
+            user_agent: self.user_agent.clone(),
+            blocks_only: self.blocks_only,
+            spv: self.spv,
+            proxy: self.get_proxy(),
+            capture: self.capture.clone().map(Capture::open).transpose()?,
+        })
+    }
+
+    /// Devuelve la cache de precios fiat, o None si no se habilito FIAT_DISPLAY o no se configuro una fuente de precios.
+    /// La fuente se consulta a traves del proxy configurado, si lo hay.
+    pub fn get_price_cache(&self) -> Option<PriceCache<HttpPriceSource>> {
+        if !self.fiat_display {
+            return None;
+        }
+        let source = self.price_source.clone()?.with_proxy(self.get_proxy());
+        Some(PriceCache::new(source, &self.fiat_currency))
+    }
+
+    /// Devuelve los comandos externos que se ejecutan con cada bloque nuevo y transaccion de las wallets.
+    pub fn get_notify_commands(&self) -> NotifyCommands {
+        NotifyCommands {
+            block_notify: self.block_notify.clone(),
+            wallet_notify: self.wallet_notify.clone(),
+        }
+    }
+}
+
+/// Parsea el codigo de una moneda fiat, que debe tener 3 letras (ISO 4217).
+fn parse_currency(value: &str) -> Result<String, CustomError> {
+    match value.len() == 3 && value.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => Ok(value.to_uppercase()),
+        false => Err(CustomError::ConfigErrorReadingValue),
+    }
+}
+
+/// Parsea una lista de direcciones separadas por coma, cada una con la forma ip o ip:port (IPv6 entre corchetes).
+fn parse_bind(value: &str) -> Result<Vec<(IpAddr, Option<u16>)>, CustomError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            if let Ok(address) = SocketAddr::from_str(entry) {
+                return Ok((address.ip(), Some(address.port())));
+            }
+            let ip = IpAddr::from_str(entry).map_err(|_| CustomError::ConfigErrorReadingValue)?;
+            Ok((ip, None))
+        })
+        .collect()
+}
+
+/// Verifica que el user agent tenga el formato de BIP14: uno o mas /nombre:version/, cada version con comentarios opcionales.
+fn parse_user_agent(value: &str) -> Result<String, CustomError> {
+    let Some(components) = value
+        .strip_prefix('/')
+        .and_then(|value| value.strip_suffix('/'))
+    else {
+        return Err(CustomError::ConfigErrorReadingValue);
+    };
+    let valid = value.len() <= MAX_USER_AGENT_SIZE as usize
+        && components.split('/').all(|component| {
+            component
+                .split_once(':')
+                .is_some_and(|(name, version)| !name.is_empty() && !version.is_empty())
+        });
+    match valid {
+        true => Ok(String::from(value)),
+        false => Err(CustomError::ConfigErrorReadingValue),
+    }
+}
+
+/// Parsea una duracion en milisegundos, que debe ser mayor a 0 para poder usarse como timeout.
+fn parse_millis(value: &str) -> Result<Duration, CustomError> {
+    match u64::from_str(value) {
+        Ok(millis) if millis > 0 => Ok(Duration::from_millis(millis)),
+        _ => Err(CustomError::ConfigErrorReadingValue),
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: String::new(),
+            protocol_version: 0,
+            user_agent: default_user_agent(),
+            services: DEFAULT_SERVICES,
+            port: 0,
+            bind: vec![],
+            log_file: String::new(),
+            npeers: 0,
+            max_inbound: DEFAULT_MAX_INBOUND,
+            sync_peers: DEFAULT_SYNC_PEERS,
+            whitelist: vec![],
+            blacklist: vec![],
+            client_only: false,
+            blocks_only: false,
+            max_mempool: DEFAULT_MAX_MEMPOOL_SIZE,
+            min_relay_fee: DEFAULT_MIN_RELAY_FEE,
+            assume_valid: None,
+            spv: false,
+            compress_blocks: false,
+            store_path: String::from("store"),
+            rest_port: 0,
+            rpc_port: 0,
+            publisher_port: 0,
+            network: Network::Testnet,
+            log_filter: LogFilter::default(),
+            log_format: LogFormat::Text,
+            peer_timeouts: PeerTimeouts::default(),
+            proxy: None,
+            proxy_isolate: false,
+            fiat_display: false,
+            fiat_currency: String::from(DEFAULT_FIAT_CURRENCY),
+            price_source: None,
+            block_notify: None,
+            wallet_notify: None,
+            capture: None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::logger::LogTarget;
+    use crate::services::{NODE_NETWORK, NODE_WITNESS};
 
     #[test]
     fn config_con_formato_invalido() {
@@ -165,6 +497,8 @@ mod tests {
         NPEERS=5\n\
         PORT=4321\n\
         CLIENT_ONLY=true\n\
+        BLOCKS_ONLY=true\n\
+        COMPRESS_BLOCKS=true\n\
         STORE_PATH=custom"
             .as_bytes();
         let config = Config::from_reader(content)?;
@@ -174,6 +508,11 @@ mod tests {
         assert_eq!("log.txt", config.log_file);
         assert_eq!(4321, config.port);
         assert_eq!(true, config.client_only);
+        assert!(config.blocks_only);
+        assert!(config.compress_blocks);
+        assert_eq!(DEFAULT_MAX_MEMPOOL_SIZE, config.max_mempool);
+        assert_eq!(DEFAULT_MIN_RELAY_FEE, config.min_relay_fee);
+        assert_eq!(None, config.assume_valid);
         assert_eq!("custom", config.store_path);
 
         let content = "SEED=seed.test\n\
@@ -189,8 +528,475 @@ mod tests {
         assert_eq!("log.txt", config.log_file);
         assert_eq!(4321, config.port);
         assert_eq!(false, config.client_only);
+        assert!(!config.blocks_only);
+        assert!(!config.compress_blocks);
         assert_eq!("store", config.store_path);
+        assert_eq!(0, config.rest_port);
+        assert_eq!(0, config.publisher_port);
+        assert_eq!(Network::Testnet, config.network);
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_rpc_port() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        RPC_PORT=18443"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(18443, config.rpc_port);
+        assert_eq!(0, config.rest_port);
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_rest_port() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        REST_PORT=8332"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(8332, config.rest_port);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        REST_PORT=abc"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_publisher_port() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        PUBLISHER_PORT=28332"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(28332, config.publisher_port);
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_comandos_de_notificacion() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        BLOCK_NOTIFY=curl -s http://localhost/block?hash=%s\n\
+        WALLET_NOTIFY=echo %s >> wallet_txs.txt"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            Some(String::from("curl -s http://localhost/block?hash=%s")),
+            config.block_notify
+        );
+        assert_eq!(
+            Some(String::from("echo %s >> wallet_txs.txt")),
+            config.wallet_notify
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_captura() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        CAPTURE=capture.bin"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(Some(String::from("capture.bin")), config.capture);
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_precio_fiat() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        FIAT_CURRENCY=eur\n\
+        PRICE_SOURCE=http://localhost:8080/price/{currency}"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!("EUR", config.fiat_currency);
+        assert!(config.get_price_cache().is_none());
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        FIAT_DISPLAY=true\n\
+        PRICE_SOURCE=http://localhost:8080/price/{currency}"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!("USD", config.fiat_currency);
+        assert!(config.get_price_cache().is_some());
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        PRICE_SOURCE=https://localhost/price"
+            .as_bytes();
+        assert!(Config::from_reader(content).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_network() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        NETWORK=regtest"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(Network::Regtest, config.network);
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_peer_timeouts() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        HANDSHAKE_TIMEOUT=2000\n\
+        READ_TIMEOUT=30000\n\
+        PING_TIMEOUT=60000"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(Duration::from_millis(500), config.peer_timeouts.connect);
+        assert_eq!(Duration::from_secs(2), config.peer_timeouts.handshake);
+        assert_eq!(Duration::from_secs(30), config.peer_timeouts.read);
+        assert_eq!(Duration::from_secs(60), config.peer_timeouts.ping);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        READ_TIMEOUT=0"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_max_inbound() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(DEFAULT_MAX_INBOUND, config.max_inbound);
+        assert_eq!(DEFAULT_SYNC_PEERS, config.sync_peers);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        MAX_INBOUND=4\n\
+        SYNC_PEERS=2\n\
+        MAX_MEMPOOL=50\n\
+        MIN_RELAY_FEE=2000\n\
+        ASSUME_VALID=000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(4, config.max_inbound);
+        assert_eq!(2, config.sync_peers);
+        assert_eq!(50, config.max_mempool);
+        assert_eq!(2000, config.min_relay_fee);
+        assert_eq!(
+            Some(Hash32::from_str(
+                "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+            )?),
+            config.assume_valid
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_varias_semillas() -> Result<(), CustomError> {
+        let content = "SEED=seed.test, other.seed.test,\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            config.get_seeds(),
+            vec![String::from("seed.test"), String::from("other.seed.test")]
+        );
+
+        let content = "SEED=,\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigMissingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_listas_de_acceso() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        WHITELIST=10.0.0.0/8,::1\n\
+        BLACKLIST=192.168.0.1"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        let access_list = config.get_access_list();
+        assert_eq!(access_list.whitelist.len(), 2);
+        assert!(access_list.is_whitelisted(&"::ffff:10.1.2.3".parse().unwrap()));
+        assert!(access_list.is_blacklisted(&"::ffff:192.168.0.1".parse().unwrap()));
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        BLACKLIST=10.0.0.0/40"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_bind() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            config.get_bind_addresses(),
+            vec![SocketAddr::from((Ipv6Addr::UNSPECIFIED, 4321))]
+        );
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        BIND=0.0.0.0, [::1]:18444\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            config.get_bind_addresses(),
+            vec![
+                SocketAddr::from(([0, 0, 0, 0], 4321)),
+                SocketAddr::from((Ipv6Addr::LOCALHOST, 18444)),
+            ]
+        );
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        BIND=localhost"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_proxy() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        PROXY_ISOLATE=true\n\
+        PROXY=127.0.0.1:9050"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            config.get_proxy(),
+            Some(Proxy {
+                address: String::from("127.0.0.1:9050"),
+                isolate: true
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_user_agent() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(default_user_agent(), config.user_agent);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        USER_AGENT=/rust-eze:1.0(testnet)/bitcoin-node:0.1.0/"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(
+            "/rust-eze:1.0(testnet)/bitcoin-node:0.1.0/",
+            config.user_agent
+        );
+
+        for user_agent in ["rust-eze", "/rust-eze/", "/rust-eze:1.0", "//"] {
+            let content = format!(
+                "SEED=seed.test\nPROTOCOL_VERSION=7000\nLOG=log.txt\nNPEERS=5\nPORT=4321\nUSER_AGENT={user_agent}"
+            );
+            let config = Config::from_reader(content.as_bytes());
+            assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_services() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(DEFAULT_SERVICES, config.services);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        SERVICES=NODE_NETWORK,NODE_WITNESS"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(NODE_NETWORK | NODE_WITNESS, config.services);
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        SERVICES=NODE_GETUTXO"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
 
+    #[test]
+    fn config_con_log_filter() -> Result<(), CustomError> {
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        LOG_LEVEL=warn\n\
+        LOG_FILTER=sync:error,peer:debug\n\
+        LOG_FORMAT=json"
+            .as_bytes();
+        let config = Config::from_reader(content)?;
+        assert_eq!(LogLevel::Warn, config.log_filter.level);
+        assert_eq!(LogFormat::Json, config.log_format);
+        assert_eq!(
+            Some(&LogLevel::Error),
+            config.log_filter.targets.get(&LogTarget::Sync)
+        );
+        assert_eq!(
+            Some(&LogLevel::Debug),
+            config.log_filter.targets.get(&LogTarget::Peer)
+        );
+
+        let content = "SEED=seed.test\n\
+        PROTOCOL_VERSION=7000\n\
+        LOG=log.txt\n\
+        NPEERS=5\n\
+        PORT=4321\n\
+        LOG_LEVEL=verbose"
+            .as_bytes();
+        let config = Config::from_reader(content);
+        assert!(matches!(config, Err(CustomError::ConfigErrorReadingValue)));
+        Ok(())
+    }
+
+    #[test]
+    fn config_con_overrides() -> Result<(), CustomError> {
+        let config = Config::from_file_with_overrides(
+            Some("example-config.txt"),
+            vec![
+                ("PORT", String::from("1234")),
+                ("STORE_PATH", String::from("other-store")),
+                ("CLIENT_ONLY", String::from("true")),
+                ("SPV", String::from("true")),
+            ],
+        )?;
+        assert_eq!(1234, config.port);
+        assert_eq!("other-store", config.store_path);
+        assert!(config.client_only);
+        assert!(config.spv);
+        Ok(())
+    }
+
+    #[test]
+    fn config_sin_archivo_con_overrides() -> Result<(), CustomError> {
+        let config = Config::from_file_with_overrides(
+            None,
+            vec![
+                ("SEED", String::from("seed.test")),
+                ("PROTOCOL_VERSION", String::from("7000")),
+                ("PORT", String::from("4321")),
+                ("LOG", String::from("log.txt")),
+                ("NPEERS", String::from("5")),
+            ],
+        )?;
+        assert_eq!("seed.test", config.seed);
+        assert_eq!(4321, config.port);
+
+        let config = Config::from_file_with_overrides(None, vec![("PORT", String::from("4321"))]);
+        assert!(matches!(config, Err(CustomError::ConfigMissingValue)));
         Ok(())
     }
 
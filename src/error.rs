@@ -51,6 +51,23 @@ pub enum CustomError {
     InvalidFee,
     InvalidTransferFields,
     PeerNotSynced,
+    InvalidChecksum,
+    MessageTooLarge,
+    SelfConnection,
+    PeerTimeout,
+    CannotConnectThroughProxy,
+    PeerWithoutBloomFilters,
+    InvalidBalance,
+    NonStandardTransaction,
+    BlockExceedsLimits,
+    NonFinalTransaction,
+    UnknownParentHeader,
+    BlockNotFound,
+    StoreFileIncompatible,
+    StoreFileCorrupt,
+    CannotFetchPrice,
+    NonCanonicalVarint,
+    NodeBusy,
 }
 
 impl CustomError {
@@ -96,6 +113,27 @@ impl CustomError {
             Self::InvalidFee => "invalid fee",
             Self::InvalidTransferFields => "invalid transfer fields",
             Self::PeerNotSynced => "peer not synced",
+            Self::InvalidChecksum => "message payload does not match its checksum",
+            Self::MessageTooLarge => "message payload exceeds the maximum size for its command",
+            Self::SelfConnection => "connection to ourselves detected by version nonce",
+            Self::PeerTimeout => "peer did not answer in time",
+            Self::CannotConnectThroughProxy => "cannot connect through the SOCKS5 proxy",
+            Self::PeerWithoutBloomFilters => "peer does not support bloom filters",
+            Self::InvalidBalance => {
+                "transaction spends more than its inputs or coinbase exceeds subsidy and fees"
+            }
+            Self::NonStandardTransaction => "transaction is not standard",
+            Self::BlockExceedsLimits => "block exceeds the weight or sigops limit",
+            Self::NonFinalTransaction => "transaction locktime or sequence locks are not satisfied",
+            Self::UnknownParentHeader => "header's parent is unknown",
+            Self::BlockNotFound => "block not found in the block files",
+            Self::StoreFileIncompatible => {
+                "store file has an unknown type or a newer format version"
+            }
+            Self::StoreFileCorrupt => "store file is corrupt",
+            Self::CannotFetchPrice => "cannot fetch the exchange rate from the price source",
+            Self::NonCanonicalVarint => "varint is not encoded in its shortest form",
+            Self::NodeBusy => "the node is busy processing messages, try again in a moment",
         }
     }
 }
@@ -108,6 +146,7 @@ impl From<Error> for CustomError {
             ErrorKind::AlreadyExists => CustomError::CannotOpenFile,
             ErrorKind::InvalidInput => CustomError::CannotOpenFile,
             ErrorKind::Interrupted => CustomError::FileOperationInterrupted,
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => CustomError::PeerTimeout,
             _ => CustomError::UnknownError,
         }
     }
@@ -0,0 +1,34 @@
+use crate::{logger::Log, price::FiatPrice, structs::sync_progress::SyncProgress};
+
+/// GUIEvents es un enum que contiene los eventos que emite el nodo por el canal de eventos.
+/// Los consume la interfaz grafica, o cualquier otro proyecto que utilice el nodo como biblioteca.
+/// Los eventos son:
+/// - Log: Recibe un Log y lo muestra en la lista de logs.
+/// - WalletChanged: Se cambio la wallet activa.
+/// - WalletsUpdated: Se Actualizo alguna de las wallets cargadas.
+/// - NewPendingTx: Alguna de las wallets cargadas recibio una pending transaction.
+/// - PendingTxConflict: Una pending transaction de alguna de las wallets cargadas gasta las mismas salidas que otra,
+///   y se reemplazo una de ellas o se rechazo la nueva como doble gasto.
+/// - NodeStateReady: El node state ya se sincronizo y se puede mostrar la informacion.
+/// - NewBlock: Llego un nuevo bloque.
+/// - TransactionSent: Se envio una transaccion del usuario.
+/// - NewHeaders: Hay nuevos Headers.
+/// - SyncProgress: Progreso de la sincronizacion con la red, se emite mientras el nodo no esta sincronizado.
+/// - AddressBookChanged: Se agrego, modifico o quito una etiqueta de la libreta de direcciones o de un movimiento.
+/// - FiatPrice: Cambio el precio de bitcoin en fiat, o None si ya no hay un precio reciente. Solo se emite si se habilito FIAT_DISPLAY.
+/// - Terminate: Se solicito cerrar la aplicacion (por ejemplo con Ctrl-C).
+pub enum GUIEvents {
+    Log(Log),
+    WalletChanged,
+    WalletsUpdated,
+    NewPendingTx,
+    PendingTxConflict,
+    NodeStateReady,
+    NewBlock,
+    TransactionSent,
+    NewHeaders,
+    SyncProgress(SyncProgress),
+    AddressBookChanged,
+    FiatPrice(Option<FiatPrice>),
+    Terminate,
+}
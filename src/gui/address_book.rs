@@ -0,0 +1,171 @@
+use std::sync::{mpsc::Sender, Arc};
+
+use gtk::{
+    prelude::GtkListStoreExtManual,
+    traits::{ButtonExt, ContainerExt, EntryExt, GtkListStoreExt, LabelExt, WidgetExt},
+    ListBox,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    node_state::NodeState,
+};
+
+use super::init::{get_gui_element, GUIEvents};
+
+#[derive(Clone)]
+/// GUIAddressBook es una estructura que contiene los elementos de la interfaz grafica
+/// relacionados con la libreta de direcciones y la lista (etiqueta y direccion).
+/// Permite guardar una direccion con su etiqueta y quitarla, y mantiene las etiquetas en el modelo "contacts-store"
+/// que usa el formulario de envio para autocompletar los destinatarios.
+/// Los elementos son:
+/// - builder: Builder de gtk.
+/// - node_state_ref: Referencia al estado del nodo.
+/// - logger_sender: Sender para enviar logs al logger.
+pub struct GUIAddressBook {
+    pub logger_sender: Sender<Log>,
+    pub builder: gtk::Builder,
+    pub node_state_ref: Arc<NodeState>,
+}
+
+impl GUIAddressBook {
+    /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
+    /// Para AddressBookChanged: Actualiza la lista de contactos y las etiquetas para autocompletar.
+    pub fn handle_events(&mut self, message: &GUIEvents) {
+        let result = match message {
+            GUIEvents::AddressBookChanged => self.update_contacts(),
+            _ => Ok(()),
+        };
+
+        if let Err(error) = result {
+            send_log(&self.logger_sender, Log::Error(error));
+        }
+    }
+
+    /// Lista los contactos y establece los callbacks de los elementos de la interfaz grafica.
+    /// Para el boton de guardar: Guarda la direccion ingresada con su etiqueta y limpia los campos
+    /// (o abre una ventana de error en caso de estar mal ingresada).
+    pub fn handle_interactivity(&self) -> Result<(), CustomError> {
+        self.update_contacts()?;
+
+        let add_button: gtk::Button = get_gui_element(&self.builder, "address-book-add")?;
+        let address_book = self.clone();
+        add_button.connect_clicked(move |_| {
+            if let Err(error) = address_book.save_contact() {
+                send_log(&address_book.logger_sender, Log::Error(error));
+            }
+        });
+        Ok(())
+    }
+
+    fn save_contact(&self) -> Result<(), CustomError> {
+        let label_entry: gtk::Entry = get_gui_element(&self.builder, "address-book-label")?;
+        let address_entry: gtk::Entry = get_gui_element(&self.builder, "address-book-address")?;
+
+        self.node_state_ref.set_contact(
+            address_entry.text().trim().to_string(),
+            label_entry.text().to_string(),
+        )?;
+        label_entry.set_text("");
+        address_entry.set_text("");
+        Ok(())
+    }
+
+    fn update_contacts(&self) -> Result<(), CustomError> {
+        let contacts_list_box: gtk::ListBox = get_gui_element(&self.builder, "address-book-list")?;
+        let contacts_store: gtk::ListStore = get_gui_element(&self.builder, "contacts-store")?;
+        let contacts = self.node_state_ref.get_contacts()?;
+
+        reset_table(&contacts_list_box);
+        contacts_store.clear();
+        for (label, address) in contacts {
+            let contact_row = gtk::ListBoxRow::new();
+            let contact_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            contact_box.set_margin_top(8);
+            contact_box.set_margin_bottom(8);
+
+            contact_box.add(&contact_label(&label));
+            contact_box.add(&address_label(&address));
+            contact_box.add(&remove_contact_button(
+                address,
+                self.node_state_ref.clone(),
+                self.logger_sender.clone(),
+            ));
+
+            contact_row.add(&contact_box);
+            contact_row.show_all();
+            contacts_list_box.add(&contact_row);
+            contacts_store.insert_with_values(None, &[(0, &label)]);
+        }
+        Ok(())
+    }
+}
+
+/// Genera un label con la etiqueta del contacto.
+fn contact_label(label: &str) -> gtk::Label {
+    let contact_label = gtk::Label::new(Some(label));
+
+    contact_label.set_width_request(220);
+    contact_label.set_xalign(0.0);
+
+    contact_label
+}
+
+/// Genera un label con la direccion del contacto, que ocupa el espacio libre de la fila.
+fn address_label(address: &str) -> gtk::Label {
+    let address_label = gtk::Label::new(Some(address));
+
+    address_label.set_expand(true);
+    address_label.set_xalign(0.0);
+    address_label.set_selectable(true);
+
+    address_label
+}
+
+/// Genera un boton que al hacerle click quita la direccion de la libreta.
+fn remove_contact_button(
+    address: String,
+    node_state_ref: Arc<NodeState>,
+    logger_sender: Sender<Log>,
+) -> gtk::Button {
+    let button = gtk::Button::new();
+
+    button.set_label("Remove");
+    button.connect_clicked(move |_| {
+        if let Err(error) = node_state_ref.remove_contact(&address) {
+            send_log(&logger_sender, Log::Error(error));
+        }
+    });
+
+    button
+}
+
+fn reset_table(list_box: &ListBox) {
+    list_box.foreach(|child| {
+        list_box.remove(child);
+    });
+    let header_row = gtk::ListBoxRow::new();
+    let header_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    let label_label = gtk::Label::new(None);
+    let address_label = gtk::Label::new(None);
+    let actions_label = gtk::Label::new(None);
+
+    label_label.set_width_request(220);
+    label_label.set_xalign(0.0);
+    label_label.set_markup("<b>Label</b>");
+
+    address_label.set_expand(true);
+    address_label.set_xalign(0.0);
+    address_label.set_markup("<b>Address</b>");
+
+    actions_label.set_width_request(80);
+
+    header_box.add(&label_label);
+    header_box.add(&address_label);
+    header_box.add(&actions_label);
+
+    header_row.add(&header_box);
+    header_row.show_all();
+    list_box.add(&header_row);
+}
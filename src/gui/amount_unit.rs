@@ -0,0 +1,102 @@
+use std::{
+    cell::{Cell, RefCell},
+    sync::mpsc,
+};
+
+use gtk::traits::{ComboBoxExt, ComboBoxTextExt};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    price::FiatPrice,
+    structs::amount_unit::AmountUnit,
+};
+
+use super::init::{get_gui_element, GUIEvents};
+
+thread_local! {
+    /// Unidad en la que la interfaz grafica muestra e ingresa los montos.
+    /// Solo se accede desde el thread de gtk, por lo que no necesita sincronizacion.
+    static CURRENT_UNIT: Cell<AmountUnit> = const { Cell::new(AmountUnit::Btc) };
+    /// Ultimo precio fiat recibido del nodo, None si no se habilito FIAT_DISPLAY o no hay un precio reciente.
+    static FIAT_PRICE: RefCell<Option<FiatPrice>> = const { RefCell::new(None) };
+}
+
+/// Devuelve la unidad seleccionada para mostrar e ingresar montos.
+pub fn current_unit() -> AmountUnit {
+    CURRENT_UNIT.with(|unit| unit.get())
+}
+
+/// Formatea un valor en satoshis en la unidad seleccionada, seguido de su valor aproximado en fiat si hay un precio.
+pub fn format_amount(value: i64) -> String {
+    let amount = current_unit().format(value);
+    FIAT_PRICE.with(|price| match price.borrow().as_ref() {
+        Some(price) => format!("{amount} ({})", price.format(value)),
+        None => amount,
+    })
+}
+
+/// Parsea un monto ingresado en la unidad seleccionada y lo devuelve en satoshis.
+pub fn parse_amount(value: &str) -> Result<u64, CustomError> {
+    current_unit().parse(value)
+}
+
+#[derive(Clone)]
+/// GUIAmountUnit es una estructura que contiene el selector de la unidad en la que se muestran los montos
+/// (balances, historial, UTXO y formulario de envio) y en la que se ingresan los montos a enviar.
+/// Los elementos son:
+/// - builder: Builder de gtk.
+/// - logger_sender: Sender para enviar logs al logger.
+pub struct GUIAmountUnit {
+    pub builder: gtk::Builder,
+    pub logger_sender: mpsc::Sender<Log>,
+}
+
+impl GUIAmountUnit {
+    /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
+    /// Para FiatPrice: Guarda el precio con el que se muestran los valores aproximados en fiat.
+    /// Debe recibir los eventos antes que el resto de los elementos, para que muestren los montos con el precio nuevo.
+    pub fn handle_events(&self, message: &GUIEvents) {
+        if let GUIEvents::FiatPrice(fiat_price) = message {
+            FIAT_PRICE.with(|price| price.replace(fiat_price.clone()));
+        }
+    }
+
+    /// Carga las unidades disponibles en el combobox y selecciona la actual.
+    pub fn initialize(&self) -> Result<(), CustomError> {
+        let unit_cb: gtk::ComboBoxText = get_gui_element(&self.builder, "amount-unit-combo-box")?;
+
+        for unit in AmountUnit::ALL {
+            let name = unit.to_string();
+            unit_cb.append(Some(&name), &name);
+        }
+        unit_cb.set_active_id(Some(&current_unit().to_string()));
+
+        Ok(())
+    }
+
+    /// Establece el callback del combobox: al elegir una unidad la guarda como la actual
+    /// y llama a on_change con la unidad anterior, para que se actualicen los montos mostrados.
+    pub fn handle_interactivity(
+        &self,
+        on_change: impl Fn(AmountUnit) -> Result<(), CustomError> + 'static,
+    ) -> Result<(), CustomError> {
+        let unit_cb: gtk::ComboBoxText = get_gui_element(&self.builder, "amount-unit-combo-box")?;
+        let logger_sender = self.logger_sender.clone();
+
+        unit_cb.connect_changed(move |unit_cb| {
+            let Some(Ok(unit)) = unit_cb.active_id().map(|id| id.parse::<AmountUnit>()) else {
+                return;
+            };
+            let previous_unit = CURRENT_UNIT.with(|current| current.replace(unit));
+            if previous_unit == unit {
+                return;
+            }
+            on_change(previous_unit).unwrap_or_else(|error| {
+                send_log(&logger_sender, Log::Error(error));
+            });
+        });
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc};
 
 use gtk::{
     traits::{BoxExt, ContainerExt, LabelExt, WidgetExt},
@@ -12,6 +12,7 @@ use crate::{
 };
 
 use super::{
+    amount_unit::format_amount,
     init::{get_gui_element, GUIEvents},
     table_cells::{side_label, value_label},
 };
@@ -28,7 +29,7 @@ use super::{
 /// - pending_balance: Balance pendiente de la billetera.
 pub struct GUIBalance {
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
     pub logger_sender: mpsc::Sender<Log>,
     pub available_balance: f64,
     pub pending_balance: f64,
@@ -40,11 +41,14 @@ impl GUIBalance {
     /// Para WalletChanged: Actualiza el balance pendiente y disponible y las transacciones pendientes.
     /// Para WalletsUpdated: Actualiza el balance pendiente y disponible y las transacciones pendientes.
     /// Para NewPendingTx: Actualiza las transacciones pendientes y el balance pendinente.
+    /// Para PendingTxConflict: Actualiza las transacciones pendientes, ya que alguna pudo ser reemplazada.
+    /// Para FiatPrice: Vuelve a mostrar los balances y las transacciones pendientes con el precio nuevo.
     pub fn handle_events(&mut self, message: &GUIEvents) {
         let result = match message {
             GUIEvents::WalletChanged => self.handle_wallet_changed(),
-            GUIEvents::NewPendingTx => self.handle_new_pending_tx(),
+            GUIEvents::NewPendingTx | GUIEvents::PendingTxConflict => self.handle_new_pending_tx(),
             GUIEvents::WalletsUpdated => self.handle_wallet_updated(),
+            GUIEvents::FiatPrice(_) => self.refresh_amounts(),
             _ => Ok(()),
         };
 
@@ -53,6 +57,14 @@ impl GUIBalance {
         }
     }
 
+    /// Vuelve a mostrar los balances y las transacciones pendientes con el formato actual de los montos (unidad y precio fiat).
+    pub fn refresh_amounts(&mut self) -> Result<(), CustomError> {
+        if self.node_state_ref.get_active_wallet()?.is_none() {
+            return Ok(());
+        }
+        self.handle_wallet_changed()
+    }
+
     fn handle_wallet_changed(&mut self) -> Result<(), CustomError> {
         self.update_available_balance()?;
         self.update_pending_txs()
@@ -68,9 +80,7 @@ impl GUIBalance {
     }
 
     fn update_available_balance(&mut self) -> Result<(), CustomError> {
-        let node_state = self.node_state_ref.lock()?;
-
-        match node_state.get_active_wallet_balance() {
+        match self.node_state_ref.get_active_wallet_balance() {
             Ok(balance) => {
                 self.available_balance = balance as f64;
             }
@@ -78,7 +88,6 @@ impl GUIBalance {
                 send_log(&self.logger_sender, Log::Error(error));
             }
         }
-        drop(node_state);
 
         self.update_balances()?;
 
@@ -88,12 +97,10 @@ impl GUIBalance {
     fn update_pending_txs(&mut self) -> Result<(), CustomError> {
         let pending_tx_list_box: gtk::ListBox =
             get_gui_element(&self.builder, "pending-transactions-list")?;
-        let node_state_ref_clone = self.node_state_ref.clone();
-        let node_state = node_state_ref_clone.lock()?;
-        if node_state.get_active_wallet().is_none() {
+        if self.node_state_ref.get_active_wallet()?.is_none() {
             return Ok(());
         }
-        let pending_transactions = node_state.get_active_wallet_pending_txs()?;
+        let pending_transactions = self.node_state_ref.get_active_wallet_pending_txs()?;
 
         self.pending_balance = 0.0;
         reset_table(&pending_tx_list_box);
@@ -112,7 +119,6 @@ impl GUIBalance {
             pending_tx_row.show_all();
             pending_tx_list_box.add(&pending_tx_row);
         }
-        drop(node_state);
 
         self.update_balances()?;
 
@@ -127,19 +133,15 @@ impl GUIBalance {
         let transfer_balance: gtk::Label =
             get_gui_element(&self.builder, "label-transfer-balance")?;
 
-        let available_btc = self.available_balance / 100_000_000.0;
-        available_balance.set_text(format!("Balance:    {:.8} BTC", available_btc).as_str());
-
-        let pending_btc = self.pending_balance / 100_000_000.0;
-        pending_balance.set_text(format!("Pending:    {:.8} BTC", pending_btc).as_str());
+        let available = format_amount(self.available_balance as i64);
+        available_balance.set_text(format!("Balance:    {}", available).as_str());
 
-        let total_satoshi = self.available_balance + self.pending_balance;
-        let total_btc = total_satoshi / 100_000_000.0;
-        let total_balance_string = format!("Total:	     {:.8} BTC", total_btc);
-        let total_balance_string_satoshi = format!("Total:  {:.0} Sat", total_satoshi);
+        let pending = format_amount(self.pending_balance as i64);
+        pending_balance.set_text(format!("Pending:    {}", pending).as_str());
 
-        total_balance.set_text(total_balance_string.as_str());
-        transfer_balance.set_text(total_balance_string_satoshi.as_str());
+        let total = format_amount((self.available_balance + self.pending_balance) as i64);
+        total_balance.set_text(format!("Total:	     {}", total).as_str());
+        transfer_balance.set_text(format!("Total:  {}", total).as_str());
 
         Ok(())
     }
@@ -0,0 +1,152 @@
+use std::sync::{mpsc::Sender, Arc};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use gtk::traits::{ContainerExt, GtkWindowExt, ListBoxExt, ListBoxRowExt, WidgetExt};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    messages::block::Block,
+    node_state::NodeState,
+    parser::BtcEncode,
+    structs::{block_header::BlockHeader, hash32::Hash32},
+};
+
+use super::{
+    amount_unit::format_amount,
+    table_cells::{tx_hash_label, value_label},
+    tx_details::{detail_label, section_label, show_transaction_details},
+};
+
+/// Abre una ventana con los detalles de un bloque: los campos de su header y, si esta descargado,
+/// su tamaño, su peso y el listado de sus transacciones. Al hacer click en una transaccion se abren sus detalles.
+pub fn show_block_details(
+    node_state_ref: Arc<NodeState>,
+    logger_sender: Sender<Log>,
+    height: usize,
+    header: &BlockHeader,
+    block: Option<Block>,
+) {
+    let block_hash = *header.hash();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+
+    content.add(&detail_label(&format!("Block Hash: {block_hash}")));
+    content.add(&detail_label(&format!("Height: {height}")));
+    content.add(&detail_label(&format!(
+        "Previous Block: {}",
+        header.prev_block_hash
+    )));
+    content.add(&detail_label(&format!(
+        "Merkle Root: {}",
+        header.merkle_root
+    )));
+    content.add(&detail_label(&format!(
+        "Time: {}",
+        format_timestamp(header.timestamp)
+    )));
+    content.add(&detail_label(&format!("Version: {:#010x}", header.version)));
+    content.add(&detail_label(&format!("nBits: {:#010x}", header.bits)));
+    content.add(&detail_label(&format!("Nonce: {}", header.nonce)));
+
+    let Some(block) = block else {
+        content.add(&section_label("Block not downloaded"));
+        show_window(&format!("Block {height}"), &content);
+        return;
+    };
+
+    content.add(&detail_label(&format!(
+        "Size: {} bytes ({} WU)",
+        block.serialize().len(),
+        block.weight()
+    )));
+    let outputs_value: u64 = block
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.outputs.iter())
+        .map(|output| output.value)
+        .sum();
+    content.add(&detail_label(&format!(
+        "Total Output: {}",
+        format_amount(outputs_value as i64)
+    )));
+
+    content.add(&section_label(&format!(
+        "Transactions ({})",
+        block.transactions.len()
+    )));
+    let transactions_list_box = gtk::ListBox::new();
+    let tx_hashes: Vec<Hash32> = block.transactions.iter().map(|tx| tx.hash()).collect();
+    for (transaction, tx_hash) in block.transactions.iter().zip(&tx_hashes) {
+        let tx_row = gtk::ListBoxRow::new();
+        let tx_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        tx_box.set_margin_top(4);
+        tx_box.set_margin_bottom(4);
+
+        tx_box.add(&tx_hash_label(*tx_hash));
+        tx_box.add(&value_label(
+            transaction
+                .outputs
+                .iter()
+                .map(|output| output.value as i64)
+                .sum(),
+        ));
+
+        tx_row.add(&tx_box);
+        transactions_list_box.add(&tx_row);
+    }
+    transactions_list_box.connect_row_activated(move |_, row| {
+        let Some(tx_hash) = tx_hashes.get(row.index() as usize) else {
+            return;
+        };
+        show_block_transaction_details(&node_state_ref, tx_hash, block_hash).unwrap_or_else(
+            |error| {
+                send_log(&logger_sender, Log::Error(error));
+            },
+        );
+    });
+    content.add(&transactions_list_box);
+
+    show_window(&format!("Block {height}"), &content);
+}
+
+/// Muestra los detalles de la transaccion con el hash recibido, incluida en el bloque recibido.
+fn show_block_transaction_details(
+    node_state_ref: &NodeState,
+    tx_hash: &Hash32,
+    block_hash: Hash32,
+) -> Result<(), CustomError> {
+    let Some(details) = node_state_ref.get_transaction_details(tx_hash, Some(block_hash))? else {
+        return Err(CustomError::Validation(format!(
+            "Transaction {tx_hash} not found"
+        )));
+    };
+    show_transaction_details(&details, node_state_ref.get_network());
+    Ok(())
+}
+
+/// Formatea un timestamp en segundos como fecha y hora local.
+fn format_timestamp(timestamp: u32) -> String {
+    match NaiveDateTime::from_timestamp_millis(timestamp as i64 * 1000) {
+        Some(datetime) => DateTime::<Local>::from_utc(datetime, *Local::now().offset())
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+fn show_window(title: &str, content: &gtk::Box) {
+    let scrolled_window =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled_window.add(content);
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(title);
+    window.set_default_size(760, 520);
+    window.add(&scrolled_window);
+    window.show_all();
+}
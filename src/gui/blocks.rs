@@ -1,7 +1,7 @@
-use std::sync::{mpsc::Sender, Arc, Mutex};
+use std::sync::{mpsc::Sender, Arc};
 
 use gtk::{
-    traits::{ContainerExt, LabelExt, WidgetExt},
+    traits::{ContainerExt, LabelExt, ListBoxExt, ListBoxRowExt, WidgetExt},
     ListBox,
 };
 
@@ -9,16 +9,22 @@ use crate::{
     error::CustomError,
     logger::{send_log, Log},
     node_state::NodeState,
+    parser::BtcEncode,
 };
 
 use super::{
+    block_details::show_block_details,
     init::{get_gui_element, GUIEvents},
     table_cells::{number_label, time_label, tx_hash_label},
 };
 
+/// Cantidad de bloques que se listan, del mas reciente al mas viejo.
+const BLOCKS_TO_LIST: usize = 100;
+
 #[derive(Clone)]
 /// GUIBlocks es una estructura que contiene los elementos de la interfaz grafica
-/// relacionados con los bloques. Muestra un listado de los ultimos 100 bloques (fecha de creacion, tx hash, version y nbits).
+/// relacionados con los bloques. Muestra un listado de los ultimos 100 bloques (altura, hash, fecha de creacion, cantidad de transacciones y tamaño).
+/// Al hacer click en un bloque se abre una ventana con su header y sus transacciones.
 /// Los elementos son:
 /// - builder: Builder de gtk.
 /// - node_state_ref: Referencia al estado del nodo.
@@ -26,7 +32,7 @@ use super::{
 pub struct GUIBlocks {
     pub logger_sender: Sender<Log>,
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
     pub node_state_ready: bool,
 }
 
@@ -51,64 +57,134 @@ impl GUIBlocks {
         self.update_blocks()
     }
 
+    /// Establece los callbacks de los elementos de la interfaz grafica.
+    /// Para la lista de bloques: Abre los detalles del bloque seleccionado.
+    pub fn handle_interactivity(&self) -> Result<(), CustomError> {
+        let blocks_list_box: gtk::ListBox = get_gui_element(&self.builder, "blocks-list")?;
+
+        let node_state_ref = self.node_state_ref.clone();
+        let logger_sender = self.logger_sender.clone();
+
+        blocks_list_box.connect_row_activated(move |_, row| {
+            // la primera fila es el encabezado de la tabla
+            let Some(position) = (row.index() as usize).checked_sub(1) else {
+                return;
+            };
+            open_block_details(&node_state_ref, &logger_sender, position).unwrap_or_else(|error| {
+                send_log(&logger_sender, Log::Error(error));
+            });
+        });
+
+        Ok(())
+    }
+
     fn update_blocks(&self) -> Result<(), CustomError> {
         if !self.node_state_ready {
             return Ok(());
         }
         let blocks_list_box: gtk::ListBox = get_gui_element(&self.builder, "blocks-list")?;
-        let node_state_ref_clone = self.node_state_ref.clone();
-        let node_state = node_state_ref_clone.lock()?;
-        let headers = node_state.get_last_headers(100);
+        let headers = self.node_state_ref.get_last_headers(BLOCKS_TO_LIST)?;
 
         reset_table(&blocks_list_box);
         for (height, header) in headers.into_iter() {
-            let utxo_row = gtk::ListBoxRow::new();
-            let utxo_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
-            utxo_box.set_margin_top(8);
-            utxo_box.set_margin_bottom(8);
-
-            utxo_box.add(&time_label(header.timestamp));
-            utxo_box.add(&tx_hash_label(header.hash().clone()));
-            utxo_box.add(&number_label(height as i64));
-            utxo_box.add(&number_label(header.bits as i64));
-
-            utxo_row.add(&utxo_box);
-            utxo_row.show_all();
-            blocks_list_box.add(&utxo_row);
+            let block_row = gtk::ListBoxRow::new();
+            let block_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            block_box.set_margin_top(8);
+            block_box.set_margin_bottom(8);
+
+            block_box.add(&number_label(height as i64));
+            block_box.add(&tx_hash_label(*header.hash()));
+            block_box.add(&time_label(header.timestamp));
+
+            // los bloques que todavia no se descargaron solo muestran los datos del header
+            if self.node_state_ref.has_block(header.hash())? {
+                let block = self.node_state_ref.get_block(header.hash())?;
+                block_box.add(&number_label(block.transactions.len() as i64));
+                block_box.add(&number_label(block.serialize().len() as i64));
+            } else {
+                block_box.add(&pending_label());
+                block_box.add(&pending_label());
+            }
+
+            block_row.add(&block_box);
+            block_row.show_all();
+            blocks_list_box.add(&block_row);
         }
         Ok(())
     }
 }
 
+/// Abre los detalles del bloque en la posicion recibida de la tabla, que lista los ultimos bloques en el orden
+/// en que los devuelve get_last_headers. Si el bloque no esta descargado solo se muestra su header.
+fn open_block_details(
+    node_state_ref: &Arc<NodeState>,
+    logger_sender: &Sender<Log>,
+    position: usize,
+) -> Result<(), CustomError> {
+    let Some((height, header)) = node_state_ref
+        .get_last_headers(BLOCKS_TO_LIST)?
+        .into_iter()
+        .nth(position)
+    else {
+        return Ok(());
+    };
+    let block = match node_state_ref.has_block(header.hash())? {
+        true => Some(node_state_ref.get_block(header.hash())?),
+        false => None,
+    };
+    show_block_details(
+        node_state_ref.clone(),
+        logger_sender.clone(),
+        height,
+        &header,
+        block,
+    );
+    Ok(())
+}
+
+/// Genera un label para los datos de un bloque que todavia no se descargo.
+fn pending_label() -> gtk::Label {
+    let pending_label = gtk::Label::new(Some("-"));
+
+    pending_label.set_width_request(100);
+
+    pending_label
+}
+
 fn reset_table(list_box: &ListBox) {
     list_box.foreach(|child| {
         list_box.remove(child);
     });
-    let utxo_row = gtk::ListBoxRow::new();
-    let utxo_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
-    let tx_hash_label = gtk::Label::new(None);
-    let time_label = gtk::Label::new(None);
-    let nbits_label = gtk::Label::new(None);
+    let header_row = gtk::ListBoxRow::new();
+    let header_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
     let height_label = gtk::Label::new(None);
+    let block_hash_label = gtk::Label::new(None);
+    let time_label = gtk::Label::new(None);
+    let tx_count_label = gtk::Label::new(None);
+    let size_label = gtk::Label::new(None);
+
+    height_label.set_width_request(100);
+    height_label.set_markup("<b>Height</b>");
+
+    block_hash_label.set_expand(true);
+    block_hash_label.set_markup("<b>Block Hash</b>");
 
     time_label.set_width_request(92);
     time_label.set_markup("<b>Time</b>");
 
-    tx_hash_label.set_expand(true);
-    tx_hash_label.set_markup("<b>Block Hash</b>");
-
-    nbits_label.set_width_request(100);
-    nbits_label.set_markup("<b>nBits</b>");
+    tx_count_label.set_width_request(100);
+    tx_count_label.set_markup("<b>Txs</b>");
 
-    height_label.set_width_request(100);
-    height_label.set_markup("<b>Height</b>");
+    size_label.set_width_request(100);
+    size_label.set_markup("<b>Size (bytes)</b>");
 
-    utxo_box.add(&time_label);
-    utxo_box.add(&tx_hash_label);
-    utxo_box.add(&height_label);
-    utxo_box.add(&nbits_label);
+    header_box.add(&height_label);
+    header_box.add(&block_hash_label);
+    header_box.add(&time_label);
+    header_box.add(&tx_count_label);
+    header_box.add(&size_label);
 
-    utxo_row.add(&utxo_box);
-    utxo_row.show_all();
-    list_box.add(&utxo_row);
+    header_row.add(&header_box);
+    header_row.show_all();
+    list_box.add(&header_row);
 }
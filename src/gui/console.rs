@@ -0,0 +1,84 @@
+use std::sync::mpsc;
+
+use gtk::{
+    prelude::WidgetExtManual,
+    traits::{ButtonExt, EntryExt, TextBufferExt, TextViewExt, WidgetExt},
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    rpc::{error_message, RpcHandler},
+};
+
+use super::init::get_gui_element;
+
+#[derive(Clone)]
+/// GUIConsole es una estructura que contiene los elementos de la interfaz grafica
+/// relacionados con la consola de depuracion. Ejecuta comandos estilo RPC (getblock, getpeerinfo, sendrawtransaction, etc.)
+/// mediante el RpcHandler y muestra sus resultados.
+/// Los elementos son:
+/// - builder: Builder de gtk.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - rpc_handler: Capa de comandos RPC que ejecuta los comandos ingresados.
+pub struct GUIConsole {
+    pub builder: gtk::Builder,
+    pub logger_sender: mpsc::Sender<Log>,
+    pub rpc_handler: RpcHandler,
+}
+
+impl GUIConsole {
+    /// Establece la interactividad de la consola: el boton que abre la ventana,
+    /// ocultarla al cerrarla para conservar la salida, y ejecutar el comando al presionar enter.
+    pub fn handle_interactivity(&self) -> Result<(), CustomError> {
+        let console_button: gtk::Button = get_gui_element(&self.builder, "console-button")?;
+        let console_window: gtk::Window = get_gui_element(&self.builder, "console-window")?;
+        let console_input: gtk::Entry = get_gui_element(&self.builder, "console-input")?;
+
+        let window = console_window.clone();
+        let input = console_input.clone();
+        console_button.connect_clicked(move |_| {
+            window.show_all();
+            input.grab_focus();
+        });
+
+        console_window.connect_delete_event(|window, _| window.hide_on_delete());
+
+        let console = self.clone();
+        console_input.connect_activate(move |input| {
+            let line = input.text().to_string();
+            if line.trim().is_empty() {
+                return;
+            }
+            input.set_text("");
+            if let Err(error) = console.run_command(&line) {
+                send_log(&console.logger_sender, Log::Error(error));
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Ejecuta el comando ingresado y agrega a la salida el comando junto a su resultado, o el error si fallo.
+    fn run_command(&self, line: &str) -> Result<(), CustomError> {
+        let result = match self.rpc_handler.execute_line(line) {
+            Ok(result) => result,
+            Err(error) => error_message(&error),
+        };
+        self.append_output(&format!("> {}\n{result}\n\n", line.trim()))
+    }
+
+    fn append_output(&self, text: &str) -> Result<(), CustomError> {
+        let console_output: gtk::TextView = get_gui_element(&self.builder, "console-output")?;
+        let buffer = console_output
+            .buffer()
+            .ok_or(CustomError::MissingGUIElement)?;
+
+        buffer.insert(&mut buffer.end_iter(), text);
+        if let Some(end) = buffer.create_mark(None, &buffer.end_iter(), false) {
+            console_output.scroll_mark_onscreen(&end);
+            buffer.delete_mark(&end);
+        }
+        Ok(())
+    }
+}
@@ -1,42 +1,78 @@
-use std::sync::{mpsc::Sender, Arc, Mutex};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{mpsc::Sender, Arc},
+};
 
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
 use gtk::{
-    traits::{ContainerExt, LabelExt, WidgetExt},
+    prelude::{EditableSignals, ObjectExt},
+    traits::{
+        ButtonExt, ComboBoxExt, ContainerExt, DialogExt, EntryExt, LabelExt, ListBoxExt,
+        ListBoxRowExt, MessageDialogExt, SearchEntryExt, WidgetExt,
+    },
     ListBox,
 };
 
 use crate::{
     error::CustomError,
     logger::{send_log, Log},
+    loops::node_action_loop::{NodeAction, NodeActionSender},
     node_state::NodeState,
+    structs::{
+        hash32::Hash32,
+        history_query::{HistoryQuery, MovementFilter},
+        movement::Movement,
+    },
 };
 
 use super::{
+    amount_unit::{format_amount, parse_amount},
     init::{get_gui_element, GUIEvents},
-    table_cells::{merkle_proof_button, side_label, tx_hash_label, value_label},
+    table_cells::{
+        conflicted_label, merkle_proof_button, movement_label, side_label, time_label, value_label,
+    },
+    tx_details::show_transaction_details,
 };
 
+/// Cantidad de movimientos que se cargan al abrir el historial y cada vez que se piden mas.
+const HISTORY_PAGE_SIZE: usize = 50;
+
 #[derive(Clone)]
 /// GUIHistory es una estructura que contiene los elementos de la interfaz grafica
-/// relacionados con el historial de movimientos de una wallet y los lista (tx hash, enviado o recibido, valor y pedir el merkle proof de esa tx).
+/// relacionados con el historial de movimientos de una wallet y los lista (tx hash, fecha, enviado o recibido, valor y pedir el merkle proof de esa tx).
+/// Permite buscar por tx hash o monto, filtrar por tipo de movimiento y rango de fechas, y carga los movimientos de a HISTORY_PAGE_SIZE.
+/// Al hacer click en un movimiento se abre una ventana con los detalles de su transaccion.
+/// Los movimientos entrantes pendientes tienen un boton para acelerarlos con una transaccion hija (CPFP).
 /// Los elementos son:
 /// - builder: Builder de gtk.
 /// - node_state_ref: Referencia al estado del nodo.
 /// - logger_sender: Sender para enviar logs al logger.
+/// - node_action_sender: Sender para enviar acciones al nodo.
+/// - shown: Movimientos listados, en el orden de la tabla.
 pub struct GUIHistory {
     pub logger_sender: Sender<Log>,
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
+    pub node_action_sender: NodeActionSender,
+    pub shown: Rc<RefCell<Vec<Movement>>>,
 }
 
 impl GUIHistory {
     /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
-    /// Para WalletChanged: Actualiza la lista de movimientos.
-    /// Para WalletsUpdated: Actualiza la lista de movimientos.
+    /// Para WalletChanged: Lista la primera pagina de movimientos.
+    /// Para WalletsUpdated: Actualiza la lista de movimientos, manteniendo la cantidad cargada.
+    /// Para NewPendingTx: Actualiza la lista de movimientos, ya que incluye los pendientes.
+    /// Para PendingTxConflict: Actualiza la lista de movimientos, ya que algun pendiente pudo ser reemplazado.
+    /// Para FiatPrice: Vuelve a mostrar la lista de movimientos con el precio nuevo.
+    /// Para AddressBookChanged: Vuelve a mostrar la lista de movimientos con las etiquetas actualizadas.
     pub fn handle_events(&mut self, message: &GUIEvents) {
         let result = match message {
-            GUIEvents::WalletChanged => self.update_txs(),
-            GUIEvents::WalletsUpdated => self.update_txs(),
+            GUIEvents::WalletChanged => self.update_txs(HISTORY_PAGE_SIZE),
+            GUIEvents::WalletsUpdated | GUIEvents::NewPendingTx | GUIEvents::PendingTxConflict => {
+                self.update_txs(self.loaded_count())
+            }
+            GUIEvents::FiatPrice(_) | GUIEvents::AddressBookChanged => self.refresh_amounts(),
             _ => Ok(()),
         };
 
@@ -45,35 +81,280 @@ impl GUIHistory {
         }
     }
 
-    fn update_txs(&self) -> Result<(), CustomError> {
+    /// Establece los callbacks de los elementos de la interfaz grafica.
+    /// Para la lista de movimientos: Abre los detalles de la transaccion del movimiento seleccionado.
+    /// Para la busqueda, el filtro y las fechas: Vuelve a listar la primera pagina de movimientos que cumplen la consulta.
+    /// Para el boton de cargar mas: Agrega a la lista la siguiente pagina de movimientos.
+    pub fn handle_interactivity(&self) -> Result<(), CustomError> {
+        let history_list_box: gtk::ListBox = get_gui_element(&self.builder, "history-list")?;
+        let search_entry: gtk::SearchEntry = get_gui_element(&self.builder, "history-search")?;
+        let filter_cb: gtk::ComboBoxText = get_gui_element(&self.builder, "history-filter")?;
+        let from_entry: gtk::Entry = get_gui_element(&self.builder, "history-from")?;
+        let to_entry: gtk::Entry = get_gui_element(&self.builder, "history-to")?;
+        let load_more_button: gtk::Button = get_gui_element(&self.builder, "history-load-more")?;
+
+        let history = self.clone();
+        history_list_box.connect_row_activated(move |_, row| {
+            // la primera fila es el encabezado de la tabla
+            let Some(position) = (row.index() as usize).checked_sub(1) else {
+                return;
+            };
+            let movement = history.shown.borrow().get(position).cloned();
+            if let Some(movement) = movement {
+                show_movement_details(&history.node_state_ref, &movement).unwrap_or_else(|error| {
+                    send_log(&history.logger_sender, Log::Error(error));
+                });
+            }
+        });
+
+        let history = self.clone();
+        search_entry.connect_search_changed(move |_| history.reload());
+        let history = self.clone();
+        filter_cb.connect_changed(move |_| history.reload());
+        let history = self.clone();
+        from_entry.connect_changed(move |_| history.reload());
+        let history = self.clone();
+        to_entry.connect_changed(move |_| history.reload());
+
+        let history = self.clone();
+        load_more_button.connect_clicked(move |_| {
+            history.load_more().unwrap_or_else(|error| {
+                send_log(&history.logger_sender, Log::Error(error));
+            });
+        });
+
+        Ok(())
+    }
+
+    /// Vuelve a mostrar la lista de movimientos con el formato actual de los montos (unidad y precio fiat).
+    pub fn refresh_amounts(&self) -> Result<(), CustomError> {
+        self.update_txs(self.loaded_count())
+    }
+
+    fn reload(&self) {
+        self.update_txs(HISTORY_PAGE_SIZE)
+            .unwrap_or_else(|error| send_log(&self.logger_sender, Log::Error(error)));
+    }
+
+    /// Cantidad de movimientos a volver a listar para no perder los que ya se cargaron.
+    fn loaded_count(&self) -> usize {
+        self.shown.borrow().len().max(HISTORY_PAGE_SIZE)
+    }
+
+    /// Vuelve a listar los primeros limit movimientos que cumplen la consulta.
+    fn update_txs(&self, limit: usize) -> Result<(), CustomError> {
         let history_list_box: gtk::ListBox = get_gui_element(&self.builder, "history-list")?;
-        let node_state_ref_clone = self.node_state_ref.clone();
-        let node_state = node_state_ref_clone.lock()?;
-        let Some(active_wallet) = node_state.get_active_wallet() else { return Ok(()) };
-        let history = active_wallet.get_history();
+        if self.node_state_ref.get_active_wallet()?.is_none() {
+            return Ok(());
+        }
+        // si alguna fecha esta a medio escribir se mantiene la lista actual
+        let Some(query) = self.read_query(0, limit)? else {
+            return Ok(());
+        };
+
         reset_table(&history_list_box);
+        self.shown.borrow_mut().clear();
+        self.append_page(&history_list_box, &query)
+    }
+
+    /// Agrega a la lista la siguiente pagina de movimientos que cumplen la consulta.
+    fn load_more(&self) -> Result<(), CustomError> {
+        let history_list_box: gtk::ListBox = get_gui_element(&self.builder, "history-list")?;
+        let offset = self.shown.borrow().len();
+        let Some(query) = self.read_query(offset, HISTORY_PAGE_SIZE)? else {
+            return Ok(());
+        };
+        self.append_page(&history_list_box, &query)
+    }
+
+    fn append_page(
+        &self,
+        history_list_box: &gtk::ListBox,
+        query: &HistoryQuery,
+    ) -> Result<(), CustomError> {
+        let page = self.node_state_ref.query_active_wallet_history(query)?;
 
-        for movement in history.iter().rev() {
+        for (movement, timestamp) in page.movements {
             let history_row = gtk::ListBoxRow::new();
             let history_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
 
-            history_box.add(&tx_hash_label(movement.tx_hash.clone()));
+            history_box.add(&movement_label(
+                movement.tx_hash,
+                self.node_state_ref.get_movement_label(&movement.tx_hash)?,
+            ));
+            history_box.add(&match timestamp {
+                Some(timestamp) => time_label(timestamp),
+                None => no_block_time_label(movement.conflicted),
+            });
             history_box.add(&side_label(movement.value));
             history_box.add(&value_label(movement.value));
-            history_box.add(&merkle_proof_button(
-                movement.block_hash.clone(),
-                movement.tx_hash.clone(),
-                self.logger_sender.clone(),
-                self.node_state_ref.clone(),
-            ));
+            match movement.conflicted {
+                true => history_box.add(&conflicted_label()),
+                false if movement.block_hash.is_none() && movement.value > 0 => {
+                    history_box.add(&self.accelerate_button(movement.tx_hash))
+                }
+                false => history_box.add(&merkle_proof_button(
+                    movement.block_hash,
+                    movement.tx_hash,
+                    self.logger_sender.clone(),
+                    self.node_state_ref.clone(),
+                )),
+            }
 
             history_row.add(&history_box);
             history_row.show_all();
             history_list_box.add(&history_row);
+            self.shown.borrow_mut().push(movement);
         }
-        drop(node_state);
+
+        let shown = self.shown.borrow().len();
+        let count_label: gtk::Label = get_gui_element(&self.builder, "history-count")?;
+        count_label.set_text(&format!("Showing {} of {} movements", shown, page.total));
+        let load_more_button: gtk::Button = get_gui_element(&self.builder, "history-load-more")?;
+        load_more_button.set_sensitive(shown < page.total);
         Ok(())
     }
+
+    /// Genera un boton que al hacerle click ofrece acelerar la pending tx con una transaccion hija.
+    fn accelerate_button(&self, tx_hash: Hash32) -> gtk::Box {
+        let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let button = gtk::Button::with_label("Accelerate");
+
+        let history = self.clone();
+        button.connect_clicked(move |_| {
+            history
+                .confirm_and_accelerate(tx_hash)
+                .unwrap_or_else(|error| {
+                    send_log(&history.logger_sender, Log::Error(error));
+                });
+        });
+
+        button_box.add(&button);
+        button_box.set_width_request(128);
+        button_box
+    }
+
+    /// Muestra el resumen de la transaccion hija con el fee rate ingresado, actualizandolo mientras se edita,
+    /// y si el usuario lo acepta se la pide al nodo.
+    fn confirm_and_accelerate(&self, tx_hash: Hash32) -> Result<(), CustomError> {
+        let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "accelerate-dialog")?;
+        let fee_rate_entry: gtk::Entry = get_gui_element(&self.builder, "accelerate-fee-rate")?;
+
+        let node_state_ref = self.node_state_ref.clone();
+        let preview_dialog = dialog.clone();
+        let update_preview = move |entry: &gtk::Entry| {
+            let preview = parse_fee_rate(entry)
+                .and_then(|fee_rate| node_state_ref.preview_acceleration(tx_hash, fee_rate));
+            preview_dialog.set_secondary_text(Some(&match preview {
+                Ok(preview) => preview.describe(format_amount),
+                Err(error) => error.to_string(),
+            }));
+        };
+        update_preview(&fee_rate_entry);
+        let handler = fee_rate_entry.connect_changed(update_preview);
+        let response = dialog.run();
+        dialog.hide();
+        fee_rate_entry.disconnect(handler);
+        if response != gtk::ResponseType::Ok {
+            return Ok(());
+        }
+
+        self.node_action_sender
+            .try_send(NodeAction::AccelerateTransaction(
+                tx_hash,
+                parse_fee_rate(&fee_rate_entry)?,
+            ))
+    }
+
+    /// Arma la consulta del historial con la busqueda, el filtro y las fechas ingresadas.
+    /// La busqueda tambien se interpreta como un monto en la unidad seleccionada.
+    /// Devuelve None si alguna de las fechas no tiene el formato YYYY-MM-DD.
+    fn read_query(&self, offset: usize, limit: usize) -> Result<Option<HistoryQuery>, CustomError> {
+        let search_entry: gtk::SearchEntry = get_gui_element(&self.builder, "history-search")?;
+        let filter_cb: gtk::ComboBoxText = get_gui_element(&self.builder, "history-filter")?;
+        let from_entry: gtk::Entry = get_gui_element(&self.builder, "history-from")?;
+        let to_entry: gtk::Entry = get_gui_element(&self.builder, "history-to")?;
+
+        let (Ok(from), Ok(to)) = (
+            parse_date(&from_entry.text(), NaiveTime::MIN),
+            parse_date(
+                &to_entry.text(),
+                NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default(),
+            ),
+        ) else {
+            return Ok(None);
+        };
+        let search = search_entry.text().to_string();
+        let filter = filter_cb
+            .active_id()
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(MovementFilter::All);
+
+        Ok(Some(HistoryQuery {
+            amount: parse_amount(&search).ok(),
+            search,
+            filter,
+            from,
+            to,
+            offset,
+            limit,
+        }))
+    }
+}
+
+/// Parsea una fecha YYYY-MM-DD y devuelve el timestamp de la hora recibida de ese dia en la zona horaria local.
+/// Devuelve None si la fecha esta vacia.
+fn parse_date(date: &str, time: NaiveTime) -> Result<Option<u32>, CustomError> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+    let date =
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| CustomError::InvalidValue)?;
+    let datetime = Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or(CustomError::InvalidValue)?;
+    Ok(Some(datetime.timestamp().clamp(0, u32::MAX as i64) as u32))
+}
+
+/// Parsea el fee rate en satoshis por byte virtual ingresado.
+fn parse_fee_rate(entry: &gtk::Entry) -> Result<u64, CustomError> {
+    entry
+        .text()
+        .trim()
+        .parse()
+        .map_err(|_| CustomError::InvalidFee)
+}
+
+/// Genera un label para la fecha de un movimiento que no esta en un bloque:
+/// todavia pendiente, o sin fecha si quedo en conflicto y nunca va a estar en uno.
+fn no_block_time_label(conflicted: bool) -> gtk::Label {
+    let time_label = gtk::Label::new(match conflicted {
+        true => Some("-"),
+        false => Some("Pending"),
+    });
+
+    time_label.set_width_request(92);
+
+    time_label
+}
+
+/// Muestra los detalles de la transaccion del movimiento.
+fn show_movement_details(
+    node_state_ref: &NodeState,
+    movement: &Movement,
+) -> Result<(), CustomError> {
+    let Some(details) =
+        node_state_ref.get_transaction_details(&movement.tx_hash, movement.block_hash)?
+    else {
+        return Err(CustomError::Validation(format!(
+            "Transaction {} not found",
+            movement.tx_hash
+        )));
+    };
+    show_transaction_details(&details, node_state_ref.get_network());
+    Ok(())
 }
 
 fn reset_table(list_box: &ListBox) {
@@ -83,12 +364,16 @@ fn reset_table(list_box: &ListBox) {
     let utxo_row = gtk::ListBoxRow::new();
     let utxo_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
     let tx_hash_label = gtk::Label::new(None);
+    let time_label = gtk::Label::new(None);
     let side_label = gtk::Label::new(None);
     let value_label = gtk::Label::new(None);
     let action_label = gtk::Label::new(None);
 
     tx_hash_label.set_expand(true);
-    tx_hash_label.set_markup("<b>Tx Hash</b>");
+    tx_hash_label.set_markup("<b>Tx Hash / Label</b>");
+
+    time_label.set_width_request(92);
+    time_label.set_markup("<b>Date</b>");
 
     side_label.set_width_request(92);
     side_label.set_markup("<b>Side</b>");
@@ -100,6 +385,7 @@ fn reset_table(list_box: &ListBox) {
     action_label.set_markup("<b>Action</b>");
 
     utxo_box.add(&tx_hash_label);
+    utxo_box.add(&time_label);
     utxo_box.add(&side_label);
     utxo_box.add(&value_label);
     utxo_box.add(&action_label);
@@ -1,60 +1,54 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{mpsc, Arc},
+};
 
 use gtk::{
     glib::{self, Object, Receiver},
     prelude::{BuilderExtManual, IsA},
 };
 
+pub use crate::events::GUIEvents;
 use crate::{
-    error::CustomError, logger::Log, loops::node_action_loop::NodeAction, node_state::NodeState,
+    error::CustomError, logger::Log, loops::node_action_loop::NodeActionSender,
+    node_state::NodeState, rpc::RpcHandler,
 };
 
 use super::{
-    balance::GUIBalance, blocks::GUIBlocks, history::GUIHistory, logs::GUILogs,
+    address_book::GUIAddressBook, amount_unit::GUIAmountUnit, balance::GUIBalance,
+    blocks::GUIBlocks, console::GUIConsole, history::GUIHistory, logs::GUILogs, peers::GUIPeers,
     transfer::GUITransfer, utxo::GUIUtxo, wallet::GUIWallet, window::GUIWindow,
 };
 
-/// GUIEvents es un enum que contiene los eventos que se pueden recibir en el canal de eventos de la interfaz grafica.
-/// Los eventos son:
-/// - Log: Recibe un Log y lo muestra en la lista de logs.
-/// - WalletChanged: Se cambio la wallet activa.
-/// - WalletsUpdated: Se Actualizo alguna de las wallets cargadas.
-/// - NewPendingTx: Alguna de las wallets cargadas recibio una pending transaction.
-/// - NodeStateReady: El node state ya se sincronizo y se puede mostrar la informacion.
-/// - NewBlock: Llego un nuevo bloque.
-/// - TransactionSent: Se envio una transaccion del usuario.
-/// - NewHeaders: Hay nuevos Headers.
-pub enum GUIEvents {
-    Log(Log),
-    WalletChanged,
-    WalletsUpdated,
-    NewPendingTx,
-    NodeStateReady,
-    NewBlock,
-    TransactionSent,
-    NewHeaders,
-}
-
 /// GUI es una estructura que contiene los elementos que manejan la interfaz grafica
 /// Contiene y les maneja el ciclo de vida a cada uno de los elementos de la interfaz grafica.
 /// Los elementos son:
 /// - node_action_sender: Sender para enviar acciones al nodo.
 /// - wallet: GUIWallet.
+/// - amount_unit: GUIAmountUnit.
 /// - balance: GUIBalance.
 /// - logs: GUILogs.
 /// - history: GUIHistory.
 /// - utxo: GUIUtxo.
 /// - blocks: GUIBlocks.
+/// - peers: GUIPeers.
+/// - console: GUIConsole.
+/// - address_book: GUIAddressBook.
 /// - transfer: GUITransfer.
 /// - window: GUIWindow.
 pub struct GUI {
-    node_action_sender: mpsc::Sender<NodeAction>,
+    node_action_sender: NodeActionSender,
     wallet: GUIWallet,
+    amount_unit: GUIAmountUnit,
     balance: GUIBalance,
     logs: GUILogs,
     history: GUIHistory,
     utxo: GUIUtxo,
     blocks: GUIBlocks,
+    peers: GUIPeers,
+    console: GUIConsole,
+    address_book: GUIAddressBook,
     transfer: GUITransfer,
     window: GUIWindow,
 }
@@ -65,9 +59,9 @@ impl GUI {
     /// Inicializa el ciclo de vida de la interfaz grafica (escuchar los GUIEvents).
     pub fn start(
         gui_receiver: Receiver<GUIEvents>,
-        node_state_ref: Arc<Mutex<NodeState>>,
+        node_state_ref: Arc<NodeState>,
         logger_sender: mpsc::Sender<Log>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
     ) -> Result<(), CustomError> {
         if gtk::init().is_err() {
             return Err(CustomError::CannotInitGUI);
@@ -82,6 +76,11 @@ impl GUI {
             logger_sender: logger_sender.clone(),
         };
 
+        let amount_unit = GUIAmountUnit {
+            builder: builder.clone(),
+            logger_sender: logger_sender.clone(),
+        };
+
         let balance = GUIBalance {
             builder: builder.clone(),
             node_state_ref: node_state_ref.clone(),
@@ -99,12 +98,15 @@ impl GUI {
             builder: builder.clone(),
             logger_sender: logger_sender.clone(),
             node_state_ref: node_state_ref.clone(),
+            node_action_sender: node_action_sender.clone(),
+            shown: Rc::new(RefCell::new(vec![])),
         };
 
         let utxo = GUIUtxo {
             builder: builder.clone(),
             logger_sender: logger_sender.clone(),
             node_state_ref: node_state_ref.clone(),
+            node_action_sender: node_action_sender.clone(),
         };
 
         let blocks = GUIBlocks {
@@ -114,10 +116,33 @@ impl GUI {
             node_state_ready: false,
         };
 
+        let peers = GUIPeers {
+            builder: builder.clone(),
+            logger_sender: logger_sender.clone(),
+            node_state_ref: node_state_ref.clone(),
+            node_action_sender: node_action_sender.clone(),
+        };
+
+        let console = GUIConsole {
+            builder: builder.clone(),
+            logger_sender: logger_sender.clone(),
+            rpc_handler: RpcHandler {
+                node_state_ref: node_state_ref.clone(),
+                node_action_sender: node_action_sender.clone(),
+            },
+        };
+
+        let address_book = GUIAddressBook {
+            builder: builder.clone(),
+            logger_sender: logger_sender.clone(),
+            node_state_ref: node_state_ref.clone(),
+        };
+
         let transfer = GUITransfer {
             builder: builder.clone(),
             logger_sender: logger_sender.clone(),
             node_state_ref,
+            outputs: Rc::new(RefCell::new(vec![])),
         };
 
         let window = GUIWindow {
@@ -128,11 +153,15 @@ impl GUI {
         let gui = Self {
             node_action_sender,
             wallet,
+            amount_unit,
             balance,
             logs,
             history,
             utxo,
             blocks,
+            peers,
+            console,
+            address_book,
             transfer,
             window,
         };
@@ -150,17 +179,40 @@ impl GUI {
     pub fn handle_interactivity(&self) -> Result<(), CustomError> {
         // initialize
         self.wallet.initialize()?;
+        self.amount_unit.initialize()?;
         self.window.initialize()?;
 
         // interactivity
         self.wallet.handle_interactivity()?;
+        self.history.handle_interactivity()?;
+        self.utxo.handle_interactivity()?;
+        self.blocks.handle_interactivity()?;
+        self.console.handle_interactivity()?;
+        self.address_book.handle_interactivity()?;
         self.transfer
             .handle_interactivity(&self.node_action_sender)?;
+        self.handle_amount_unit_interactivity()?;
 
         Ok(())
     }
 
+    /// Al cambiar la unidad de los montos, vuelve a mostrar los balances, el historial, el UTXO y el formulario de envio en la nueva unidad.
+    fn handle_amount_unit_interactivity(&self) -> Result<(), CustomError> {
+        let balance = RefCell::new(self.balance.clone());
+        let history = self.history.clone();
+        let utxo = self.utxo.clone();
+        let transfer = self.transfer.clone();
+
+        self.amount_unit.handle_interactivity(move |previous_unit| {
+            balance.borrow_mut().refresh_amounts()?;
+            history.refresh_amounts()?;
+            utxo.refresh_amounts()?;
+            transfer.handle_amount_unit_changed(previous_unit)
+        })
+    }
+
     fn gui_actions_loop(&self, gui_receiver: Receiver<GUIEvents>) -> Result<(), CustomError> {
+        let amount_unit = self.amount_unit.clone();
         let mut balance = self.balance.clone();
         let logs = self.logs.clone();
         let mut transactions = self.history.clone();
@@ -168,8 +220,11 @@ impl GUI {
         let mut transfer = self.transfer.clone();
         let mut utxo = self.utxo.clone();
         let mut blocks = self.blocks.clone();
+        let mut peers = self.peers.clone();
+        let mut address_book = self.address_book.clone();
 
         gui_receiver.attach(None, move |message| {
+            amount_unit.handle_events(&message);
             balance.handle_events(&message);
             logs.handle_events(&message);
             transactions.handle_events(&message);
@@ -177,6 +232,8 @@ impl GUI {
             transfer.handle_events(&message);
             utxo.handle_events(&message);
             blocks.handle_events(&message);
+            peers.handle_events(&message);
+            address_book.handle_events(&message);
 
             glib::Continue(true)
         });
@@ -41,10 +41,10 @@ impl GUILogs {
         let dialog_error: gtk::MessageDialog = get_gui_element(&self.builder, "error-dialog")?;
 
         match log {
-            Log::Message(string) => {
+            Log::Message(_) | Log::Debug(..) | Log::Info(..) | Log::Warn(..) | Log::Peer(..) => {
                 let current_time = Local::now();
                 let formatted_time = current_time.format("%Y-%m-%d %H:%M:%S");
-                let formatted_string = format!("[{}] {}", formatted_time, string);
+                let formatted_string = format!("[{}] {}", formatted_time, log);
                 logs.set_text(formatted_string.as_str());
                 load_screen_logs.set_text(formatted_string.as_str());
             }
@@ -1,10 +1,16 @@
+pub mod address_book;
+pub mod amount_unit;
 pub mod balance;
+pub mod block_details;
 pub mod blocks;
+pub mod console;
 pub mod history;
 pub mod init;
 pub mod logs;
+pub mod peers;
 pub mod table_cells;
 pub mod transfer;
+pub mod tx_details;
 pub mod utxo;
 pub mod wallet;
 pub mod window;
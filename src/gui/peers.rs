@@ -0,0 +1,236 @@
+use std::{
+    net::SocketAddrV6,
+    sync::{mpsc::Sender, Arc},
+};
+
+use gtk::{
+    glib,
+    traits::{ButtonExt, ContainerExt, LabelExt, WidgetExt},
+    ListBox,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    loops::node_action_loop::{NodeAction, NodeActionSender},
+    node_state::NodeState,
+    structs::peer_info::PeerInfo,
+};
+
+use super::{
+    init::{get_gui_element, GUIEvents},
+    table_cells::number_label,
+};
+
+/// Cada cuantos segundos se actualiza la lista de peers, para mostrar su latencia y trafico al dia.
+const PEERS_REFRESH_INTERVAL: u32 = 2;
+
+#[derive(Clone)]
+/// GUIPeers es una estructura que contiene los elementos de la interfaz grafica
+/// relacionados con los peers conectados y los lista (direccion, user agent, version, latencia, bytes recibidos y enviados y si es entrante o saliente).
+/// Cada peer tiene un boton para desconectarlo y otro para banearlo.
+/// Los elementos son:
+/// - builder: Builder de gtk.
+/// - node_state_ref: Referencia al estado del nodo.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - node_action_sender: Sender para enviar acciones al nodo.
+pub struct GUIPeers {
+    pub logger_sender: Sender<Log>,
+    pub builder: gtk::Builder,
+    pub node_state_ref: Arc<NodeState>,
+    pub node_action_sender: NodeActionSender,
+}
+
+impl GUIPeers {
+    /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
+    /// Para NodeStateReady: Lista los peers y empieza a actualizarlos periodicamente.
+    pub fn handle_events(&mut self, message: &GUIEvents) {
+        let result = match message {
+            GUIEvents::NodeStateReady => self.initialize(),
+            _ => Ok(()),
+        };
+
+        if let Err(error) = result {
+            send_log(&self.logger_sender, Log::Error(error));
+        }
+    }
+
+    /// Lista los peers y programa su actualizacion cada PEERS_REFRESH_INTERVAL segundos,
+    /// ya que los peers se conectan y desconectan sin emitir GUIEvents.
+    fn initialize(&self) -> Result<(), CustomError> {
+        self.update_peers()?;
+
+        let peers = self.clone();
+        glib::timeout_add_seconds_local(PEERS_REFRESH_INTERVAL, move || {
+            if let Err(error) = peers.update_peers() {
+                send_log(&peers.logger_sender, Log::Error(error));
+            }
+            glib::Continue(true)
+        });
+        Ok(())
+    }
+
+    fn update_peers(&self) -> Result<(), CustomError> {
+        let peers_list_box: gtk::ListBox = get_gui_element(&self.builder, "peers-list")?;
+        let mut peers = self.node_state_ref.get_peers_info()?;
+        peers.sort_by_key(|peer| (peer.inbound, peer.connected_at));
+
+        reset_table(&peers_list_box);
+        for peer in peers.iter() {
+            let peer_row = gtk::ListBoxRow::new();
+            let peer_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            peer_box.set_margin_top(8);
+            peer_box.set_margin_bottom(8);
+
+            peer_box.add(&address_label(peer));
+            peer_box.add(&user_agent_label(peer));
+            peer_box.add(&number_label(peer.version as i64));
+            peer_box.add(&number_label(peer.latency));
+            peer_box.add(&bytes_label(peer.bytes_received));
+            peer_box.add(&bytes_label(peer.bytes_sent));
+            peer_box.add(&direction_label(peer.inbound));
+            peer_box.add(&peer_action_button(
+                "Disconnect",
+                NodeAction::DisconnectPeer,
+                peer.address,
+                self.node_action_sender.clone(),
+                self.logger_sender.clone(),
+            ));
+            peer_box.add(&peer_action_button(
+                "Ban",
+                NodeAction::BanPeer,
+                peer.address,
+                self.node_action_sender.clone(),
+                self.logger_sender.clone(),
+            ));
+
+            peer_row.add(&peer_box);
+            peer_row.show_all();
+            peers_list_box.add(&peer_row);
+        }
+        Ok(())
+    }
+}
+
+/// Genera un label con la direccion del peer, mostrando las IPv4 sin el prefijo de IPv4 mapeada en IPv6.
+fn address_label(peer: &PeerInfo) -> gtk::Label {
+    let address = match peer.address.ip().to_ipv4_mapped() {
+        Some(ipv4) => format!("{}:{}", ipv4, peer.address.port()),
+        None => peer.address.to_string(),
+    };
+    let address_label = gtk::Label::new(Some(address.as_str()));
+
+    address_label.set_width_request(220);
+    address_label.set_xalign(0.0);
+
+    address_label
+}
+
+/// Genera un label con el user agent del peer, que ocupa el espacio libre de la fila.
+fn user_agent_label(peer: &PeerInfo) -> gtk::Label {
+    let user_agent_label = gtk::Label::new(Some(peer.user_agent.as_str()));
+
+    user_agent_label.set_expand(true);
+
+    user_agent_label
+}
+
+/// Genera un label con una cantidad de bytes, en la unidad mas grande que no la deje por debajo de 1.
+fn bytes_label(bytes: u64) -> gtk::Label {
+    let text = match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    };
+    let bytes_label = gtk::Label::new(Some(text.as_str()));
+
+    bytes_label.set_width_request(100);
+
+    bytes_label
+}
+
+/// Genera un label que indica si la conexion la inicio el peer (entrante) o el nodo (saliente).
+fn direction_label(inbound: bool) -> gtk::Label {
+    let direction_label = gtk::Label::new(if inbound {
+        Some("Inbound")
+    } else {
+        Some("Outbound")
+    });
+
+    direction_label.set_width_request(92);
+
+    direction_label
+}
+
+/// Genera un boton que al hacerle click envia al nodo la accion sobre el peer.
+fn peer_action_button(
+    label: &str,
+    action: fn(SocketAddrV6) -> NodeAction,
+    address: SocketAddrV6,
+    node_action_sender: NodeActionSender,
+    logger_sender: Sender<Log>,
+) -> gtk::Button {
+    let button = gtk::Button::new();
+
+    button.set_label(label);
+    button.connect_clicked(move |_| {
+        if let Err(error) = node_action_sender.try_send(action(address)) {
+            send_log(&logger_sender, Log::Error(error));
+        }
+    });
+
+    button
+}
+
+fn reset_table(list_box: &ListBox) {
+    list_box.foreach(|child| {
+        list_box.remove(child);
+    });
+    let header_row = gtk::ListBoxRow::new();
+    let header_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    let address_label = gtk::Label::new(None);
+    let user_agent_label = gtk::Label::new(None);
+    let version_label = gtk::Label::new(None);
+    let latency_label = gtk::Label::new(None);
+    let received_label = gtk::Label::new(None);
+    let sent_label = gtk::Label::new(None);
+    let direction_label = gtk::Label::new(None);
+    let actions_label = gtk::Label::new(None);
+
+    address_label.set_width_request(220);
+    address_label.set_xalign(0.0);
+    address_label.set_markup("<b>Address</b>");
+
+    user_agent_label.set_expand(true);
+    user_agent_label.set_markup("<b>User Agent</b>");
+
+    version_label.set_width_request(100);
+    version_label.set_markup("<b>Version</b>");
+
+    latency_label.set_width_request(100);
+    latency_label.set_markup("<b>Latency (ms)</b>");
+
+    received_label.set_width_request(100);
+    received_label.set_markup("<b>Received</b>");
+
+    sent_label.set_width_request(100);
+    sent_label.set_markup("<b>Sent</b>");
+
+    direction_label.set_width_request(92);
+    direction_label.set_markup("<b>Direction</b>");
+
+    actions_label.set_width_request(160);
+
+    header_box.add(&address_label);
+    header_box.add(&user_agent_label);
+    header_box.add(&version_label);
+    header_box.add(&latency_label);
+    header_box.add(&received_label);
+    header_box.add(&sent_label);
+    header_box.add(&direction_label);
+    header_box.add(&actions_label);
+
+    header_row.add(&header_box);
+    header_row.show_all();
+    list_box.add(&header_row);
+}
@@ -1,29 +1,40 @@
-use std::sync::{mpsc::Sender, Arc, Mutex};
+use std::sync::{mpsc::Sender, Arc};
 
 use chrono::{DateTime, Local, NaiveDateTime};
 use gtk::traits::{ButtonExt, ContainerExt, LabelExt, WidgetExt};
 
 use crate::{
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogTarget},
     node_state::NodeState,
-    structs::block_header::hash_as_string,
+    structs::hash32::Hash32,
 };
 
+use super::amount_unit::format_amount;
+
 /// Genera un label formateado para un hash en formato hexadecimal y lo devuelve.
-pub fn tx_hash_label(mut tx_hash: Vec<u8>) -> gtk::Label {
+pub fn tx_hash_label(tx_hash: Hash32) -> gtk::Label {
     let tx_hash_label = gtk::Label::new(None);
 
-    tx_hash.reverse();
-    let mut tx_hash_string = hash_as_string(tx_hash.clone());
-    tx_hash_string.make_ascii_lowercase();
-
-    tx_hash_label.set_text(tx_hash_string.as_str());
+    tx_hash_label.set_text(tx_hash.to_string().as_str());
 
     tx_hash_label.set_expand(true);
 
     tx_hash_label
 }
 
+/// Genera un label para un movimiento con su etiqueta y el hash de la transaccion como tooltip,
+/// o con el hash si el movimiento no tiene etiqueta.
+pub fn movement_label(tx_hash: Hash32, label: Option<String>) -> gtk::Label {
+    let movement_label = tx_hash_label(tx_hash);
+
+    if let Some(label) = label {
+        movement_label.set_text(&label);
+        movement_label.set_tooltip_text(Some(tx_hash.to_string().as_str()));
+    }
+
+    movement_label
+}
+
 /// Genera un label formateado para una fecha y lo devuelve.
 /// Si la fecha es de hoy, muestra la hora, sino muestra la fecha.
 pub fn time_label(timestamp: u32) -> gtk::Label {
@@ -45,9 +56,9 @@ pub fn time_label(timestamp: u32) -> gtk::Label {
 }
 
 /// Genera un label formateado para un valor en satoshis y lo devuelve.
-/// El valor se muestra en BTC.
+/// El valor se muestra en la unidad seleccionada.
 pub fn value_label(value: i64) -> gtk::Label {
-    let value_string = format!("{:.8} BTC", (value as f64) / 100_000_000.0);
+    let value_string = format_amount(value);
     let value_label = gtk::Label::new(Some(value_string.as_str()));
 
     value_label.set_width_request(128);
@@ -55,13 +66,23 @@ pub fn value_label(value: i64) -> gtk::Label {
     value_label
 }
 
+/// Genera un label que indica que la transaccion quedo en conflicto con otra incluida en un bloque y lo devuelve.
+/// Reemplaza al boton del merkle proof, ya que la transaccion nunca va a estar en un bloque.
+pub fn conflicted_label() -> gtk::Label {
+    let conflicted_label = gtk::Label::new(Some("Conflicted"));
+
+    conflicted_label.set_width_request(128);
+
+    conflicted_label
+}
+
 /// Genera un boton para pedir el merkle proof de una transaccion y lo devuelve.
 /// Si el bloque no esta en la base de datos, no se muestra el boton.
 pub fn merkle_proof_button(
-    block_hash: Option<Vec<u8>>,
-    tx_hash: Vec<u8>,
+    block_hash: Option<Hash32>,
+    tx_hash: Hash32,
     logger_sender: Sender<Log>,
-    node_state_ref: Arc<Mutex<NodeState>>,
+    node_state_ref: Arc<NodeState>,
 ) -> gtk::Box {
     let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
 
@@ -69,17 +90,15 @@ pub fn merkle_proof_button(
         let button = gtk::Button::new();
 
         button.set_label("Merkle Proof");
-        let block_hash_string = hash_as_string(block_hash);
         button.connect_clicked(move |_| {
-            let node_state = node_state_ref.lock().unwrap();
-            let block = match node_state.get_block(block_hash_string.clone()) {
+            let block = match node_state_ref.get_block(&block_hash) {
                 Ok(block) => block,
                 Err(error) => {
                     send_log(&logger_sender, Log::Error(error));
                     return;
                 }
             };
-            let (mp_flags, mp_hashes) = match block.generate_merkle_path(tx_hash.to_vec()) {
+            let (mp_flags, mp_hashes) = match block.generate_merkle_path(tx_hash) {
                 Ok((mp_flags, mp_hashes)) => (mp_flags, mp_hashes),
                 Err(error) => {
                     send_log(&logger_sender, Log::Error(error));
@@ -88,11 +107,11 @@ pub fn merkle_proof_button(
             };
             send_log(
                 &logger_sender,
-                Log::Message(format!("Merkle Flags: {:?}", mp_flags)),
+                Log::Debug(LogTarget::Gui, format!("Merkle Flags: {:?}", mp_flags)),
             );
             send_log(
                 &logger_sender,
-                Log::Message(format!("Merkle Hashes: {:?}", mp_hashes)),
+                Log::Debug(LogTarget::Gui, format!("Merkle Hashes: {:?}", mp_hashes)),
             );
         });
 
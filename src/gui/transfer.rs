@@ -1,32 +1,58 @@
 use std::{
-    collections::HashMap,
-    sync::{mpsc::Sender, Arc, Mutex},
+    cell::RefCell,
+    rc::Rc,
+    sync::{mpsc::Sender, Arc},
 };
 
-use gtk::traits::{ButtonExt, DialogExt, EntryExt, LabelExt, WidgetExt};
+use gtk::{
+    prelude::Cast,
+    traits::{
+        BoxExt, ButtonExt, ContainerExt, DialogExt, EntryCompletionExt, EntryExt, LabelExt,
+        MessageDialogExt, WidgetExt,
+    },
+};
 
 use crate::{
     error::CustomError,
     logger::{send_log, Log},
-    loops::node_action_loop::NodeAction,
+    loops::node_action_loop::{NodeAction, NodeActionSender},
     node_state::NodeState,
+    structs::amount_unit::AmountUnit,
+    wallet::merge_recipients,
 };
 
-use super::init::{get_gui_element, GUIEvents};
+use super::{
+    amount_unit::{current_unit, format_amount, parse_amount},
+    init::{get_gui_element, GUIEvents},
+};
 
-const TRANSFER_OUTPUTS: u8 = 3;
+#[derive(Clone)]
+/// Fila del formulario de envio con la direccion y el monto de un destinatario.
+/// - row: Box que contiene los elementos de la fila.
+/// - address: Entry de la direccion del destinatario.
+/// - value: Entry del monto que recibe el destinatario.
+pub struct OutputRow {
+    row: gtk::Box,
+    address: gtk::Entry,
+    value: gtk::Entry,
+}
 
 #[derive(Clone)]
 /// GUITransfer es una estructura que contiene los elementos de la interfaz grafica
 /// relacionados con el envio de transacciones. Permite enviar transacciones a una o mas direcciones ingresando para cada una la pubkey y el monto, ademas del fee.
+/// Los destinatarios se agregan y quitan de a uno, y antes de enviar la transaccion se muestra su resumen para confirmarla.
+/// En lugar de la pubkey se puede ingresar la etiqueta de una direccion de la libreta, que se autocompleta.
+/// En las opciones avanzadas se puede ingresar el lock time de la transaccion.
 /// Los elementos son:
 /// - builder: Builder de gtk.
 /// - node_state_ref: Referencia al estado del nodo.
 /// - logger_sender: Sender para enviar logs al logger.
+/// - outputs: Filas de los destinatarios, en el orden en que se muestran.
 pub struct GUITransfer {
     pub logger_sender: Sender<Log>,
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
+    pub outputs: Rc<RefCell<Vec<OutputRow>>>,
 }
 
 impl GUITransfer {
@@ -45,90 +71,176 @@ impl GUITransfer {
         }
     }
     /// Establece los callbacks de los elementos de la interfaz grafica.
-    /// Para el boton de enviar transaccion: Envia la transaccion al nodo (o abre una ventana de error en caso de estar mal ingresada) con los valores leidos de la interfaz.
+    /// Para el boton de agregar destinatario: Agrega una fila vacia al formulario.
+    /// Para el boton de enviar transaccion: Muestra el resumen de la transaccion con los valores leidos de la interfaz
+    /// y, si el usuario la confirma, la envia al nodo (o abre una ventana de error en caso de estar mal ingresada).
     pub fn handle_interactivity(
         &self,
-        node_action_sender: &Sender<NodeAction>,
+        node_action_sender: &NodeActionSender,
     ) -> Result<(), CustomError> {
-        let send_button: gtk::Button = get_gui_element(&self.builder, "send-tx")?;
+        self.reset_tx_fields()?;
 
-        let node_action_sender_clone = node_action_sender.clone();
-        let builder = self.builder.clone();
-        let logger_sender = self.logger_sender.clone();
+        let add_button: gtk::Button = get_gui_element(&self.builder, "add-output")?;
+        let transfer = self.clone();
+        add_button.connect_clicked(move |_| {
+            if let Err(error) = transfer.add_output_row() {
+                send_log(&transfer.logger_sender, Log::Error(error));
+            }
+        });
 
+        let send_button: gtk::Button = get_gui_element(&self.builder, "send-tx")?;
+        let transfer = self.clone();
+        let node_action_sender = node_action_sender.clone();
         send_button.connect_clicked(move |_| {
-            let mut outputs = HashMap::new();
-            for i in 0..TRANSFER_OUTPUTS {
-                match get_output(&builder, i) {
-                    Ok(Some((pubkey, value))) => outputs.insert(pubkey, value),
-                    Ok(None) => continue,
-                    Err(error) => {
-                        send_log(&logger_sender, Log::Error(error));
-                        return;
-                    }
-                };
+            if let Err(error) = transfer.confirm_and_send(&node_action_sender) {
+                send_log(&transfer.logger_sender, Log::Error(error));
             }
+        });
+        Ok(())
+    }
+
+    /// Pasa los montos ingresados de la unidad anterior a la nueva unidad seleccionada y actualiza los placeholders.
+    /// Los montos que no se pueden parsear en la unidad anterior se dejan como estan.
+    pub fn handle_amount_unit_changed(&self, previous_unit: AmountUnit) -> Result<(), CustomError> {
+        let unit = current_unit();
+        let mut value_entries = vec![get_gui_element::<gtk::Entry>(&self.builder, "tx-fee")?];
+        for output in self.outputs.borrow().iter() {
+            value_entries.push(output.value.clone());
+        }
+
+        for entry in value_entries {
+            entry.set_placeholder_text(Some(&format!("Value ({unit})")));
+            if let Ok(value) = previous_unit.parse(&entry.text()) {
+                entry.set_text(&unit.format_value(value as i64));
+            }
+        }
+        Ok(())
+    }
 
-            let fee_entry: gtk::Entry = match get_gui_element(&builder, "tx-fee") {
-                Ok(fee_entry) => fee_entry,
-                Err(error) => {
-                    send_log(&logger_sender, Log::Error(error));
-                    return;
-                }
-            };
-
-            match fee_entry.text().to_string().parse::<u64>() {
-                Ok(fee) => {
-                    if fee == 0 {
-                        send_log(&logger_sender, Log::Error(CustomError::InvalidFee));
-                        return;
-                    }
-                    if node_action_sender_clone
-                        .send(NodeAction::MakeTransaction((outputs, fee)))
-                        .is_err()
-                    {
-                        send_log(
-                            &logger_sender,
-                            Log::Error(CustomError::CannotSendMessageToChannel),
-                        );
-                    };
-                }
-                Err(_) => {
-                    send_log(&logger_sender, Log::Error(CustomError::InvalidFee));
-                }
-            };
+    /// Lee los destinatarios y el fee, muestra el resumen de la transaccion y si el usuario lo acepta se la pide al nodo.
+    fn confirm_and_send(&self, node_action_sender: &NodeActionSender) -> Result<(), CustomError> {
+        let outputs = merge_recipients(
+            &self
+                .node_state_ref
+                .resolve_recipients(self.read_outputs()?)?,
+        )?;
+
+        let fee_entry: gtk::Entry = get_gui_element(&self.builder, "tx-fee")?;
+        let fee = parse_amount(&fee_entry.text()).map_err(|_| CustomError::InvalidFee)?;
+        if fee == 0 {
+            return Err(CustomError::InvalidFee);
+        }
+        let lock_time = self.read_lock_time()?;
+
+        let summary = self
+            .node_state_ref
+            .summarize_transaction(outputs.clone(), fee, lock_time)?;
+        let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "confirm-tx-dialog")?;
+        dialog.set_secondary_text(Some(&summary.describe(format_amount)));
+        let response = dialog.run();
+        dialog.hide();
+        if response != gtk::ResponseType::Ok {
+            return Ok(());
+        }
+
+        node_action_sender.try_send(NodeAction::MakeTransaction((outputs, fee, lock_time)))
+    }
+
+    /// Agrega al formulario una fila vacia para un destinatario, con un boton para quitarla.
+    /// La direccion se autocompleta con las etiquetas de la libreta de direcciones.
+    fn add_output_row(&self) -> Result<(), CustomError> {
+        let outputs_box: gtk::Box = get_gui_element(&self.builder, "transfer-outputs")?;
+        let contacts_store: gtk::ListStore = get_gui_element(&self.builder, "contacts-store")?;
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+
+        let completion = gtk::EntryCompletion::new();
+        completion.set_model(Some(&contacts_store));
+        completion.set_text_column(0);
+        let address = gtk::Entry::new();
+        address.set_placeholder_text(Some("PubKey or label"));
+        address.set_hexpand(true);
+        address.set_completion(Some(&completion));
+        let value = gtk::Entry::new();
+        value.set_placeholder_text(Some(&format!("Value ({})", current_unit())));
+        value.set_input_purpose(gtk::InputPurpose::Number);
+        let remove_button = gtk::Button::with_label("Remove");
+
+        let outputs = self.outputs.clone();
+        let container = outputs_box.clone();
+        remove_button.connect_clicked(move |button| {
+            let Some(row) = button.parent() else { return };
+            container.remove(&row);
+            outputs
+                .borrow_mut()
+                .retain(|output| output.row.upcast_ref::<gtk::Widget>() != &row);
+        });
+
+        row.pack_start(&address, true, true, 0);
+        row.pack_start(&value, false, true, 0);
+        row.pack_start(&remove_button, false, false, 0);
+        row.show_all();
+        outputs_box.add(&row);
+        self.outputs.borrow_mut().push(OutputRow {
+            row,
+            address,
+            value,
         });
         Ok(())
     }
 
+    /// Devuelve la direccion y el monto de las filas completas, en el orden del formulario, salteando las vacias.
+    fn read_outputs(&self) -> Result<Vec<(String, u64)>, CustomError> {
+        let mut outputs = vec![];
+        for output in self.outputs.borrow().iter() {
+            if let Some(output) = get_output(output)? {
+                outputs.push(output);
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Devuelve el lock time ingresado en las opciones avanzadas, None si esta vacio.
+    fn read_lock_time(&self) -> Result<Option<u32>, CustomError> {
+        let lock_time_entry: gtk::Entry = get_gui_element(&self.builder, "tx-locktime")?;
+        match lock_time_entry.text().trim() {
+            "" => Ok(None),
+            lock_time => Ok(Some(
+                lock_time.parse().map_err(|_| CustomError::InvalidValue)?,
+            )),
+        }
+    }
+
+    /// Deja el formulario con una unica fila vacia, el fee en 0 y sin lock time.
     fn reset_tx_fields(&self) -> Result<(), CustomError> {
         let fee_entry: gtk::Entry = get_gui_element(&self.builder, "tx-fee")?;
         fee_entry.set_text("0");
+        let lock_time_entry: gtk::Entry = get_gui_element(&self.builder, "tx-locktime")?;
+        lock_time_entry.set_text("");
 
-        for i in 0..TRANSFER_OUTPUTS {
-            let receiver_pubkey: gtk::Entry =
-                get_gui_element(&self.builder, &format!("output-{}-pubkey", i))?;
-            receiver_pubkey.set_text("");
-            let receiver_value: gtk::Entry =
-                get_gui_element(&self.builder, &format!("output-{}-value", i))?;
-            receiver_value.set_text("");
-            let label: gtk::Label =
-                get_gui_element(&self.builder, &format!("tx-information-label{}", i))?;
-            label.set_text("");
+        let outputs_box: gtk::Box = get_gui_element(&self.builder, "transfer-outputs")?;
+        for output in self.outputs.borrow_mut().drain(..) {
+            outputs_box.remove(&output.row);
         }
-        Ok(())
+        self.add_output_row()
     }
 
     fn handle_sent_transaction(&self) -> Result<(), CustomError> {
         let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "successful-tx-dialog")?;
+        let label: gtk::Label = get_gui_element(&self.builder, "tx-information-label")?;
 
-        for i in 0..TRANSFER_OUTPUTS {
-            let label: gtk::Label =
-                get_gui_element(&self.builder, &format!("tx-information-label{}", i))?;
-            if let Ok(Some((pubkey, value))) = get_output(&self.builder, i) {
-                label.set_text(&format!("Transaction of {} sent to: {}", value, pubkey));
-            };
-        }
+        let sent: Vec<String> = self
+            .read_outputs()
+            .unwrap_or_default()
+            .iter()
+            .map(|(pubkey, value)| {
+                format!(
+                    "Transaction of {} sent to: {}",
+                    format_amount(*value as i64),
+                    pubkey
+                )
+            })
+            .collect();
+        label.set_text(&sent.join("\n"));
         dialog.run();
         dialog.hide();
 
@@ -137,22 +249,18 @@ impl GUITransfer {
     }
 }
 
-fn get_output(builder: &gtk::Builder, i: u8) -> Result<Option<(String, u64)>, CustomError> {
-    let pubkey: gtk::Entry = get_gui_element(builder, &format!("output-{}-pubkey", i))?;
-    let value: gtk::Entry = get_gui_element(builder, &format!("output-{}-value", i))?;
+fn get_output(output: &OutputRow) -> Result<Option<(String, u64)>, CustomError> {
+    let pubkey = output.address.text().to_string();
+    let value = output.value.text().to_string();
 
-    if pubkey.text().to_string().is_empty() && value.text().to_string().is_empty() {
+    if pubkey.is_empty() && value.is_empty() {
         return Ok(None);
     }
-    if pubkey.text().to_string().len() != 34 || value.text().to_string().is_empty() {
+    if pubkey.is_empty() || value.is_empty() {
         return Err(CustomError::InvalidTransferFields);
     }
 
-    let value = value
-        .text()
-        .to_string()
-        .parse::<u64>()
-        .map_err(|_| CustomError::InvalidValue)?;
+    let value = parse_amount(&value)?;
 
-    Ok(Some((pubkey.text().to_string(), value)))
+    Ok(Some((pubkey, value)))
 }
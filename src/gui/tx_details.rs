@@ -0,0 +1,112 @@
+use gtk::{
+    pango::WrapMode,
+    traits::{ContainerExt, GtkWindowExt, LabelExt, WidgetExt},
+};
+
+use crate::{
+    network::Network,
+    parser::BtcEncode,
+    structs::{hash32::Hash32, transaction_details::TransactionDetails},
+    utils::bytes_to_hex,
+};
+
+use super::amount_unit::format_amount;
+
+/// Abre una ventana con los detalles de una transaccion: sus entradas y salidas con sus direcciones,
+/// el fee, el tamaño, las confirmaciones y la transaccion serializada en hexadecimal.
+/// Las direcciones se muestran con el formato de la red recibida.
+pub fn show_transaction_details(details: &TransactionDetails, network: Network) {
+    let transaction = &details.transaction;
+    let tx_hash = transaction.hash();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+
+    content.add(&detail_label(&format!("Tx Hash: {tx_hash}")));
+    content.add(&detail_label(&match details.block_hash {
+        Some(block_hash) => format!("Block: {block_hash}"),
+        None => String::from("Block: Pending"),
+    }));
+    content.add(&detail_label(&format!(
+        "Confirmations: {}",
+        details.confirmations
+    )));
+    content.add(&detail_label(&format!(
+        "Size: {} bytes ({} vbytes)",
+        transaction.serialize().len(),
+        transaction.vsize()
+    )));
+    content.add(&detail_label(&match details.fee() {
+        Some(fee) => format!("Fee: {}", format_amount(fee as i64)),
+        None => String::from("Fee: Unknown"),
+    }));
+
+    content.add(&section_label("Inputs"));
+    for (input, spent_output) in transaction.inputs.iter().zip(&details.spent_outputs) {
+        let previous_output = &input.previous_output;
+        let text = match spent_output {
+            _ if previous_output.hash == Hash32::default() => String::from("Coinbase"),
+            Some(output) => format!(
+                "{}:{}  {}  {}",
+                previous_output.hash,
+                previous_output.index,
+                output
+                    .address(network)
+                    .unwrap_or_else(|| String::from("Unknown address")),
+                format_amount(output.value as i64)
+            ),
+            None => format!(
+                "{}:{}  Unknown output",
+                previous_output.hash, previous_output.index
+            ),
+        };
+        content.add(&detail_label(&text));
+    }
+
+    content.add(&section_label("Outputs"));
+    for (index, output) in transaction.outputs.iter().enumerate() {
+        content.add(&detail_label(&format!(
+            "#{index}  {}  {}",
+            output
+                .address(network)
+                .unwrap_or_else(|| String::from("Unknown address")),
+            format_amount(output.value as i64)
+        )));
+    }
+
+    content.add(&section_label("Raw Transaction"));
+    let raw_label = detail_label(&bytes_to_hex(&transaction.serialize()));
+    raw_label.set_line_wrap(true);
+    raw_label.set_line_wrap_mode(WrapMode::Char);
+    content.add(&raw_label);
+
+    let scrolled_window =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled_window.add(&content);
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&format!("Transaction {tx_hash}"));
+    window.set_default_size(760, 520);
+    window.add(&scrolled_window);
+    window.show_all();
+}
+
+/// Genera un label seleccionable alineado a la izquierda con el texto recibido.
+pub fn detail_label(text: &str) -> gtk::Label {
+    let label = gtk::Label::new(Some(text));
+    label.set_xalign(0.0);
+    label.set_selectable(true);
+    label
+}
+
+/// Genera un label en negrita para el titulo de una seccion.
+pub fn section_label(title: &str) -> gtk::Label {
+    let label = gtk::Label::new(None);
+    label.set_xalign(0.0);
+    label.set_margin_top(8);
+    label.set_markup(&format!("<b>{title}</b>"));
+    label
+}
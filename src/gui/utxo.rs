@@ -1,19 +1,21 @@
-use std::sync::{mpsc::Sender, Arc, Mutex, MutexGuard};
+use std::sync::{mpsc::Sender, Arc};
 
 use gtk::{
-    traits::{ContainerExt, LabelExt, WidgetExt},
+    traits::{ButtonExt, ContainerExt, DialogExt, EntryExt, LabelExt, MessageDialogExt, WidgetExt},
     ListBox,
 };
 
 use crate::{
     error::CustomError,
     logger::{send_log, Log},
+    loops::node_action_loop::{NodeAction, NodeActionSender},
     node_state::NodeState,
     states::utxo_state::UTXOValue,
     structs::outpoint::OutPoint,
 };
 
 use super::{
+    amount_unit::{current_unit, format_amount, parse_amount},
     init::{get_gui_element, GUIEvents},
     table_cells::{merkle_proof_button, time_label, tx_hash_label, value_label},
 };
@@ -21,24 +23,29 @@ use super::{
 #[derive(Clone)]
 /// GUIUtxo es una estructura que contiene los elementos de la interfaz grafica
 /// relacionados con los UTXO de una wallet y los lista (tx hash, fecha de creacion, valor y pedir el merkle proof de esa tx).
+/// Permite juntar los UTXO de hasta un valor en una sola salida con un fee rate bajo, mostrando antes las entradas y el fee.
 /// Los elementos son:
 /// - builder: Builder de gtk.
 /// - node_state_ref: Referencia al estado del nodo.
 /// - logger_sender: Sender para enviar logs al logger.
+/// - node_action_sender: Sender para enviar acciones al nodo.
 pub struct GUIUtxo {
     pub logger_sender: Sender<Log>,
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
+    pub node_action_sender: NodeActionSender,
 }
 
 impl GUIUtxo {
     /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
     /// Para WalletChanged: Actualiza la lista de UTXO.
     /// Para WalletsUpdated: Actualiza la lista de UTXO.
+    /// Para FiatPrice: Vuelve a mostrar la lista de UTXO con el precio nuevo.
     pub fn handle_events(&mut self, message: &GUIEvents) {
         let result = match message {
             GUIEvents::WalletChanged => self.update_utxo(),
             GUIEvents::WalletsUpdated => self.update_utxo(),
+            GUIEvents::FiatPrice(_) => self.refresh_amounts(),
             _ => Ok(()),
         };
 
@@ -47,23 +54,45 @@ impl GUIUtxo {
         }
     }
 
+    /// Establece los callbacks de los elementos de la interfaz grafica.
+    /// Para el boton de consolidar: Muestra el resumen de la consolidacion con los valores ingresados
+    /// y, si el usuario la confirma, se la pide al nodo (o abre una ventana de error si no se puede armar).
+    pub fn handle_interactivity(&self) -> Result<(), CustomError> {
+        let consolidate_button: gtk::Button = get_gui_element(&self.builder, "consolidate-utxo")?;
+        let utxo = self.clone();
+        consolidate_button.connect_clicked(move |_| {
+            if let Err(error) = utxo.confirm_and_consolidate() {
+                send_log(&utxo.logger_sender, Log::Error(error));
+            }
+        });
+        Ok(())
+    }
+
+    /// Vuelve a mostrar la lista de UTXO con el formato actual de los montos (unidad y precio fiat).
+    pub fn refresh_amounts(&self) -> Result<(), CustomError> {
+        let max_value_entry: gtk::Entry = get_gui_element(&self.builder, "consolidate-max-value")?;
+        max_value_entry.set_placeholder_text(Some(&format!("Max value ({})", current_unit())));
+        if self.node_state_ref.get_active_wallet()?.is_none() {
+            return Ok(());
+        }
+        self.update_utxo()
+    }
+
     fn update_utxo(&self) -> Result<(), CustomError> {
         let utxo_list_box: gtk::ListBox = get_gui_element(&self.builder, "utxo-list")?;
-        let node_state_ref_clone = self.node_state_ref.clone();
-        let node_state = node_state_ref_clone.lock()?;
-        let wallet_utxo = get_wallet_sorted_utxo(node_state)?;
+        let wallet_utxo = get_wallet_sorted_utxo(&self.node_state_ref)?;
 
         reset_table(&utxo_list_box);
         for (out_point, utxo_value) in wallet_utxo.iter() {
             let utxo_row = gtk::ListBoxRow::new();
             let utxo_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
 
-            utxo_box.add(&tx_hash_label(out_point.hash.clone()));
+            utxo_box.add(&tx_hash_label(out_point.hash));
             utxo_box.add(&time_label(utxo_value.block_timestamp));
             utxo_box.add(&value_label(utxo_value.tx_out.value as i64));
             utxo_box.add(&merkle_proof_button(
-                Some(utxo_value.block_hash.clone()),
-                out_point.hash.clone(),
+                Some(utxo_value.block_hash),
+                out_point.hash,
                 self.logger_sender.clone(),
                 self.node_state_ref.clone(),
             ));
@@ -74,10 +103,40 @@ impl GUIUtxo {
         }
         Ok(())
     }
+
+    /// Lee el valor maximo y el fee rate, muestra el resumen de la consolidacion y si el usuario lo acepta se la pide al nodo.
+    /// Si no se ingresa un valor maximo se juntan todos los UTXO.
+    fn confirm_and_consolidate(&self) -> Result<(), CustomError> {
+        let max_value_entry: gtk::Entry = get_gui_element(&self.builder, "consolidate-max-value")?;
+        let fee_rate_entry: gtk::Entry = get_gui_element(&self.builder, "consolidate-fee-rate")?;
+        let max_value = match max_value_entry.text().trim() {
+            "" => None,
+            max_value => Some(parse_amount(max_value)?),
+        };
+        let fee_rate = fee_rate_entry
+            .text()
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| CustomError::InvalidFee)?;
+
+        let preview = self
+            .node_state_ref
+            .preview_consolidation(max_value, fee_rate)?;
+        let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "confirm-tx-dialog")?;
+        dialog.set_secondary_text(Some(&preview.describe(format_amount)));
+        let response = dialog.run();
+        dialog.hide();
+        if response != gtk::ResponseType::Ok {
+            return Ok(());
+        }
+
+        self.node_action_sender
+            .try_send(NodeAction::ConsolidateUtxos(max_value, fee_rate))
+    }
 }
 
 fn get_wallet_sorted_utxo(
-    node_state: MutexGuard<'_, NodeState>,
+    node_state: &NodeState,
 ) -> Result<Vec<(OutPoint, UTXOValue)>, CustomError> {
     let mut wallet_utxo = node_state.get_active_wallet_utxo()?;
     wallet_utxo.sort_by(|a, b| {
@@ -1,11 +1,18 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc};
 
-use gtk::traits::{ButtonExt, ComboBoxExt, ComboBoxTextExt, DialogExt, EntryExt, WidgetExt};
+use gtk::{
+    prelude::Cast,
+    traits::{
+        BinExt, ButtonExt, ComboBoxExt, ComboBoxTextExt, ContainerExt, DialogExt, EntryExt,
+        FileChooserExt, GtkWindowExt, LabelExt, MessageDialogExt, ToggleButtonExt, WidgetExt,
+    },
+};
 
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogTarget},
     node_state::NodeState,
+    wallet::Wallet,
 };
 
 use super::init::get_gui_element;
@@ -13,9 +20,11 @@ use super::init::get_gui_element;
 #[derive(Clone)]
 /// GUIWallet es una estructura que contiene los elementos de la interfaz grafica
 /// relacionados con la billetera. Permite agregar y cambiar de wallet y muestra la wallet activa.
+/// Tambien permite guardar todas las wallets en un backup cifrado con una passphrase y restaurarlo,
+/// y en el dialogo de administracion renombrarlas, cambiar su orden en el selector y quitarlas.
 pub struct GUIWallet {
     pub builder: gtk::Builder,
-    pub node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_state_ref: Arc<NodeState>,
     pub logger_sender: mpsc::Sender<Log>,
 }
 
@@ -36,12 +45,18 @@ impl GUIWallet {
     /// - handle_add_wallet_submit: Agrega la wallet ingresada a la lista de wallets.
     /// - cancel_add_wallet: Cancela el agregado de una wallet.
     /// - handle_change_wallet: Cambia la wallet activa.
+    /// - handle_backup_wallets: Guarda las wallets en el backup cifrado que elija el usuario.
+    /// - handle_restore_wallets: Restaura las wallets del backup cifrado que elija el usuario.
+    /// - handle_manage_wallets: Muestra el dialogo para renombrar, ordenar y quitar wallets.
     ///
     pub fn handle_interactivity(&self) -> Result<(), CustomError> {
         self.handle_add_wallet_trigger()?;
         self.handle_add_wallet_submit()?;
         self.cancel_add_wallet()?;
         self.handle_change_wallet()?;
+        self.handle_backup_wallets()?;
+        self.handle_restore_wallets()?;
+        self.handle_manage_wallets()?;
 
         Ok(())
     }
@@ -63,6 +78,247 @@ impl GUIWallet {
         Ok(())
     }
 
+    fn handle_backup_wallets(&self) -> Result<(), CustomError> {
+        let button: gtk::Button = get_gui_element(&self.builder, "backup-wallets-button")?;
+        let wallet = self.clone();
+
+        button.connect_clicked(move |_| {
+            wallet.backup_wallets().unwrap_or_else(|error| {
+                send_log(&wallet.logger_sender, Log::Error(error));
+            });
+        });
+
+        Ok(())
+    }
+
+    fn handle_restore_wallets(&self) -> Result<(), CustomError> {
+        let button: gtk::Button = get_gui_element(&self.builder, "restore-wallets-button")?;
+        let wallet = self.clone();
+
+        button.connect_clicked(move |_| {
+            wallet.restore_wallets().unwrap_or_else(|error| {
+                send_log(&wallet.logger_sender, Log::Error(error));
+            });
+        });
+
+        Ok(())
+    }
+
+    fn handle_manage_wallets(&self) -> Result<(), CustomError> {
+        let button: gtk::Button = get_gui_element(&self.builder, "manage-wallets-button")?;
+        let dialog: gtk::Dialog = get_gui_element(&self.builder, "manage-wallets-dialog")?;
+        let wallet = self.clone();
+
+        button.connect_clicked(move |_| {
+            if let Err(error) = wallet.update_manage_wallets_list() {
+                send_log(&wallet.logger_sender, Log::Error(error));
+                return;
+            }
+            dialog.run();
+            dialog.hide();
+        });
+
+        Ok(())
+    }
+
+    /// Lista las wallets en el dialogo de administracion, en el orden del selector, cada una con su nombre editable
+    /// y botones para renombrarla, subirla, bajarla y quitarla.
+    fn update_manage_wallets_list(&self) -> Result<(), CustomError> {
+        let list_box: gtk::ListBox = get_gui_element(&self.builder, "manage-wallets-list")?;
+        list_box.foreach(|child| {
+            list_box.remove(child);
+        });
+
+        let wallets = self.node_state_ref.get_wallets()?;
+        let last = wallets.len().saturating_sub(1);
+        for (index, wallet) in wallets.into_iter().enumerate() {
+            let row = gtk::ListBoxRow::new();
+            let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row_box.set_margin_top(8);
+            row_box.set_margin_bottom(8);
+
+            let name = gtk::Entry::new();
+            name.set_text(&wallet.name);
+            name.set_hexpand(true);
+            let pubkey_label = gtk::Label::new(Some(&wallet.pubkey));
+            pubkey_label.set_selectable(true);
+            row_box.add(&name);
+            row_box.add(&pubkey_label);
+
+            let pubkey = wallet.pubkey.clone();
+            row_box.add(
+                &self.wallet_action_button("Rename", true, move |node_state| {
+                    node_state.rename_wallet(&pubkey, &name.text())
+                }),
+            );
+            let pubkey = wallet.pubkey.clone();
+            row_box.add(
+                &self.wallet_action_button("Up", index > 0, move |node_state| {
+                    node_state.move_wallet(&pubkey, index - 1)
+                }),
+            );
+            let pubkey = wallet.pubkey.clone();
+            row_box.add(
+                &self.wallet_action_button("Down", index < last, move |node_state| {
+                    node_state.move_wallet(&pubkey, index + 1)
+                }),
+            );
+
+            let remove_button = gtk::Button::with_label("Remove");
+            let gui_wallet = self.clone();
+            remove_button.connect_clicked(move |_| {
+                gui_wallet
+                    .confirm_and_remove(&wallet)
+                    .unwrap_or_else(|error| {
+                        send_log(&gui_wallet.logger_sender, Log::Error(error));
+                    });
+            });
+            row_box.add(&remove_button);
+
+            row.add(&row_box);
+            row.show_all();
+            list_box.add(&row);
+        }
+        Ok(())
+    }
+
+    /// Genera un boton que al hacerle click aplica el cambio recibido a las wallets y actualiza la lista y el selector.
+    fn wallet_action_button(
+        &self,
+        label: &str,
+        sensitive: bool,
+        action: impl Fn(&NodeState) -> Result<(), CustomError> + 'static,
+    ) -> gtk::Button {
+        let button = gtk::Button::with_label(label);
+        button.set_sensitive(sensitive);
+
+        let gui_wallet = self.clone();
+        button.connect_clicked(move |_| {
+            let result = action(gui_wallet.node_state_ref.as_ref())
+                .and_then(|_| gui_wallet.refresh_wallets());
+            if let Err(error) = result {
+                send_log(&gui_wallet.logger_sender, Log::Error(error));
+            }
+        });
+
+        button
+    }
+
+    /// Pide confirmacion para quitar la wallet y, si se eligio guardar sus claves, el archivo y la passphrase del backup.
+    fn confirm_and_remove(&self, wallet: &Wallet) -> Result<(), CustomError> {
+        let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "remove-wallet-dialog")?;
+        let export: gtk::CheckButton = get_gui_element(&self.builder, "remove-wallet-export")?;
+        dialog.set_secondary_text(Some(&format!(
+            "The keys of {} ({}) will be deleted from this node.",
+            wallet.name, wallet.pubkey
+        )));
+        let response = dialog.run();
+        dialog.hide();
+        if response != gtk::ResponseType::Ok {
+            return Ok(());
+        }
+
+        let key_backup = match export.is_active() {
+            true => match self.ask_backup_file(gtk::FileChooserAction::Save)? {
+                Some(key_backup) => Some(key_backup),
+                None => return Ok(()),
+            },
+            false => None,
+        };
+        self.node_state_ref.remove_wallet(
+            &wallet.pubkey,
+            key_backup
+                .as_ref()
+                .map(|(path, passphrase)| (path.as_str(), passphrase.as_str())),
+        )?;
+        self.refresh_wallets()
+    }
+
+    /// Actualiza el selector de wallets y la lista del dialogo de administracion.
+    fn refresh_wallets(&self) -> Result<(), CustomError> {
+        self.initialize()?;
+        self.update_manage_wallets_list()
+    }
+
+    fn backup_wallets(&self) -> Result<(), CustomError> {
+        let Some((path, passphrase)) = self.ask_backup_file(gtk::FileChooserAction::Save)? else {
+            return Ok(());
+        };
+        let count = self
+            .node_state_ref
+            .export_wallets_backup(&path, &passphrase)?;
+        send_log(
+            &self.logger_sender,
+            Log::Info(
+                LogTarget::Wallet,
+                format!("Backup of {count} wallets saved to {path}"),
+            ),
+        );
+        Ok(())
+    }
+
+    fn restore_wallets(&self) -> Result<(), CustomError> {
+        let Some((path, passphrase)) = self.ask_backup_file(gtk::FileChooserAction::Open)? else {
+            return Ok(());
+        };
+        let added = self
+            .node_state_ref
+            .restore_wallets_backup(&path, &passphrase)?;
+        self.initialize()?;
+        send_log(
+            &self.logger_sender,
+            Log::Info(
+                LogTarget::Wallet,
+                format!("Backup restored from {path}, {added} wallets added"),
+            ),
+        );
+        Ok(())
+    }
+
+    /// Pide al usuario el archivo del backup y su passphrase. Devuelve None si cancela alguno de los dos dialogos.
+    fn ask_backup_file(
+        &self,
+        action: gtk::FileChooserAction,
+    ) -> Result<Option<(String, String)>, CustomError> {
+        let window: gtk::Window = get_gui_element(&self.builder, "main-window")?;
+        let (title, accept_label) = match action {
+            gtk::FileChooserAction::Save => ("Backup wallets", "Save"),
+            _ => ("Restore wallets", "Open"),
+        };
+        let file_chooser = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&window),
+            action,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                (accept_label, gtk::ResponseType::Accept),
+            ],
+        );
+        file_chooser.set_do_overwrite_confirmation(true);
+        if action == gtk::FileChooserAction::Save {
+            file_chooser.set_current_name("wallets.backup");
+        }
+        let response = file_chooser.run();
+        let path = file_chooser.filename();
+        file_chooser.close();
+        let (gtk::ResponseType::Accept, Some(path)) = (response, path) else {
+            return Ok(None);
+        };
+
+        let dialog: gtk::MessageDialog = get_gui_element(&self.builder, "wallets-backup-dialog")?;
+        let passphrase_entry: gtk::Entry =
+            get_gui_element(&self.builder, "wallets-backup-passphrase")?;
+        dialog.set_text(Some(title));
+        let response = dialog.run();
+        dialog.hide();
+        let passphrase = passphrase_entry.text().to_string();
+        passphrase_entry.set_text("");
+        if response != gtk::ResponseType::Ok {
+            return Ok(None);
+        }
+        Ok(Some((path.to_string_lossy().to_string(), passphrase)))
+    }
+
     fn handle_add_wallet_trigger(&self) -> Result<(), CustomError> {
         let trigger: gtk::Button = get_gui_element(&self.builder, "add-wallet-button")?;
         let dialog: gtk::Dialog = get_gui_element(&self.builder, "add-wallet-dialog")?;
@@ -87,32 +343,20 @@ impl GUIWallet {
         let logger_sender = self.logger_sender.clone();
 
         action.connect_clicked(move |_| {
-            let mut node_state = match node_state_ref
-                .lock()
-                .map_err(|_| CustomError::CannotLockGuard)
-            {
-                Ok(node_state) => node_state,
-                Err(error) => {
-                    send_log(&logger_sender, Log::Error(error));
-                    return;
-                }
-            };
-            if let Err(error) = node_state.append_wallet(
+            if let Err(error) = node_state_ref.append_wallet(
                 name.text().to_string(),
                 pubkey.text().to_string(),
                 privkey.text().to_string(),
             ) {
                 send_log(&logger_sender, Log::Error(error));
-                drop(node_state);
                 return;
             }
-            drop(node_state);
 
             update_wallet_combo_box(node_state_ref.clone(), wallet_combobox.clone())
                 .unwrap_or_else(|_| {
                     send_log(
                         &logger_sender,
-                        Log::Message("Error updating combo box".to_string()),
+                        Log::Warn(LogTarget::Gui, "Error updating combo box".to_string()),
                     )
                 });
             name.set_text("");
@@ -143,34 +387,50 @@ impl GUIWallet {
 }
 
 fn switch_active_wallet(
-    node_state_ref: &Arc<Mutex<NodeState>>,
+    node_state_ref: &Arc<NodeState>,
     builder: &gtk::Builder,
 ) -> Result<(), CustomError> {
     let select_wallet_cb: gtk::ComboBoxText = get_gui_element(builder, "select-wallet-combo-box")?;
 
     if let Some(active_pubkey) = select_wallet_cb.active_id() {
-        let mut node_state = node_state_ref.lock()?;
-        node_state.change_wallet(active_pubkey.to_string())?;
-        if let Some(active_wallet) = node_state.get_active_wallet() {
+        if node_state_ref
+            .get_active_wallet()?
+            .is_some_and(|wallet| wallet.pubkey == active_pubkey.as_str())
+        {
+            return Ok(());
+        }
+        node_state_ref.change_wallet(active_pubkey.to_string())?;
+        if let Some(active_wallet) = node_state_ref.get_active_wallet()? {
             select_wallet_cb.set_active_id(Some(active_wallet.pubkey.as_str()));
         } else {
             select_wallet_cb.set_active_id(None);
         }
-        drop(node_state);
     }
 
     Ok(())
 }
 
 fn update_wallet_combo_box(
-    node_state_ref: Arc<Mutex<NodeState>>,
+    node_state_ref: Arc<NodeState>,
     select_wallet_cb: gtk::ComboBoxText,
 ) -> Result<(), CustomError> {
-    let node_state = node_state_ref.lock()?;
     select_wallet_cb.remove_all();
-    for wallet in node_state.get_wallets() {
+    for wallet in node_state_ref.get_wallets()? {
         select_wallet_cb.append(Some(wallet.pubkey.as_str()), wallet.name.as_str());
     }
-    drop(node_state);
+    // El selector muestra el nombre de la wallet activa, que puede haber cambiado, o ninguna si se quito
+    match node_state_ref.get_active_wallet()? {
+        Some(active_wallet) => {
+            select_wallet_cb.set_active_id(Some(active_wallet.pubkey.as_str()));
+        }
+        None => {
+            if let Some(entry) = select_wallet_cb
+                .child()
+                .and_then(|child| child.downcast::<gtk::Entry>().ok())
+            {
+                entry.set_text("- select wallet -");
+            }
+        }
+    }
     Ok(())
 }
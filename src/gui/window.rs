@@ -1,15 +1,16 @@
-use gtk::traits::{GtkWindowExt, WidgetExt};
+use gtk::traits::{GtkWindowExt, LabelExt, ProgressBarExt, WidgetExt};
 use std::sync::mpsc;
 
 use super::init::{get_gui_element, GUIEvents};
 use crate::{
     error::CustomError,
     logger::{send_log, Log},
+    structs::sync_progress::SyncProgress,
 };
 
 #[derive(Clone)]
 /// GUIWindow es una estructura que contiene los elementos de la interfaz grafica
-/// relacionados con la ventana principal. Muestra la ventana principal y la ventana de carga.
+/// relacionados con la ventana principal. Muestra la ventana principal y la ventana de carga, con el progreso de la sincronizacion.
 /// Los elementos son:
 /// - builder: Builder de gtk.
 /// - logger_sender: Sender para enviar logs al logger.
@@ -34,10 +35,17 @@ impl GUIWindow {
     }
 
     /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada envento.
+    /// Para SyncProgress: Actualiza la barra de progreso de la ventana de carga.
     /// Para NodeStateReady: Muestra la ventana principal y oculta la de carga.
+    /// Para Terminate: Cierra la interfaz grafica.
     pub fn handle_events(&self, message: &GUIEvents) {
         let result = match message {
+            GUIEvents::SyncProgress(progress) => self.update_sync_progress(progress),
             GUIEvents::NodeStateReady => self.handle_node_state_ready(),
+            GUIEvents::Terminate => {
+                gtk::main_quit();
+                Ok(())
+            }
             _ => Ok(()),
         };
 
@@ -46,6 +54,39 @@ impl GUIWindow {
         }
     }
 
+    fn update_sync_progress(&self, progress: &SyncProgress) -> Result<(), CustomError> {
+        let progress_bar: gtk::ProgressBar = get_gui_element(&self.builder, "sync-progress-bar")?;
+        let details: gtk::Label = get_gui_element(&self.builder, "sync-progress-details")?;
+
+        progress_bar.set_fraction(progress.fraction());
+        if progress.headers_synced {
+            progress_bar.set_text(Some(&format!(
+                "Downloading blocks: {} / {}",
+                progress.blocks_downloaded, progress.blocks_total
+            )));
+        } else {
+            progress_bar.set_text(Some(&format!(
+                "Downloading headers: {} / ~{}",
+                progress.header_height, progress.network_height
+            )));
+        }
+
+        let eta = match progress.eta {
+            Some(eta) => format_duration(eta),
+            None => String::from("calculating..."),
+        };
+        details.set_text(&format!(
+            "Headers: {} of ~{}    Blocks: {} of {}\n{:.1} blocks/s    ETA: {}",
+            progress.header_height,
+            progress.network_height,
+            progress.blocks_downloaded,
+            progress.blocks_total,
+            progress.blocks_per_second,
+            eta
+        ));
+        Ok(())
+    }
+
     fn handle_node_state_ready(&self) -> Result<(), CustomError> {
         self.show_main_window()?;
         Ok(())
@@ -62,3 +103,12 @@ impl GUIWindow {
         Ok(())
     }
 }
+
+/// Formatea una duracion en segundos en horas, minutos y segundos.
+fn format_duration(seconds: u64) -> String {
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, seconds) => format!("{seconds}s"),
+        (0, minutes, seconds) => format!("{minutes}m {seconds}s"),
+        (hours, minutes, _) => format!("{hours}h {minutes}m"),
+    }
+}
@@ -1,15 +1,77 @@
+//! Nodo de Bitcoin (testnet) utilizable como biblioteca.
+//!
+//! El binario `bitcoin` es una interfaz grafica en GTK construida sobre esta biblioteca,
+//! la cual se puede compilar sin la interfaz deshabilitando el feature `gui`.
+//!
+//! Los elementos principales que expone son:
+//! - [`Config`]: Configuracion del nodo, leida de un archivo `{NOMBRE}={VALOR}`.
+//! - [`NodeState`]: Estado del nodo (headers, bloques, UTXO, wallets y peers), compartido mediante un `Arc<_>`, con un lock independiente por componente.
+//! - [`Node`]: Nodo que se conecta a la red, descarga la cadena y atiende a los peers.
+//! - [`GUIEvents`]: Eventos que emite el nodo, por ejemplo al recibir nuevos headers o transacciones.
+//! - [`messages`]: Mensajes del protocolo de Bitcoin, que implementan el trait [`message::Message`] y se serializan con [`parser::BtcEncode`].
+//!
+//! Ejemplo de uso:
+//!
+//! ```no_run
+//! use std::sync::mpsc;
+//!
+//! use bitcoin::{logger::Logger, utils::get_seed_addresses, Config, Node, NodeState};
+//!
+//! let config = Config::from_file("config.txt")?;
+//! let (events_sender, events_receiver) = mpsc::channel();
+//! let logger = Logger::new(&config.log_file, events_sender.clone())?;
+//! let node_state_ref = NodeState::new(
+//!     logger.get_sender(),
+//!     events_sender.clone(),
+//!     &config.store_path,
+//!     config.network,
+//! )?;
+//!
+//! let node = Node::new(&config, &logger, node_state_ref.clone())?;
+//! let addresses = get_seed_addresses(
+//!     &config.get_seeds(),
+//!     config.network.dns_seeds(),
+//!     config.port,
+//!     config.npeers as usize,
+//! )?;
+//! let _node_thread = node.spawn(addresses, events_sender)?;
+//!
+//! while let Ok(_event) = events_receiver.recv() {
+//!     // reaccionar a los eventos del nodo
+//! }
+//! # Ok::<(), bitcoin::CustomError>(())
+//! ```
+
+pub mod access_list;
+pub mod capture;
 pub mod config;
 pub mod error;
+pub mod events;
+#[cfg(feature = "gui")]
 pub mod gui;
 pub mod logger;
 pub mod loops;
 pub mod message;
 pub mod messages;
+pub mod network;
 pub mod node;
 pub mod node_state;
+pub mod notify;
 pub mod parser;
 pub mod peer;
+pub mod price;
+pub mod proxy;
+pub mod rpc;
+pub mod services;
+pub mod spv;
 pub mod states;
 pub mod structs;
+pub mod tui;
 pub mod utils;
 pub mod wallet;
+
+pub use config::Config;
+pub use error::CustomError;
+pub use events::GUIEvents;
+pub use node::Node;
+pub use node_state::NodeState;
@@ -1,29 +1,282 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::Path;
+use std::str::FromStr;
 use std::{
     fs::OpenOptions,
     sync::mpsc::{self, Sender},
     thread,
 };
 
-use chrono::Local;
-use gtk::glib;
+use chrono::{DateTime, Local};
 
 use crate::error::CustomError;
-use crate::gui::init::GUIEvents;
+use crate::events::GUIEvents;
+use crate::utils::get_socket_address;
 
 #[derive(Debug, Clone)]
 /// Log es el tipo de dato que se envia al logger.
-/// Puede ser un Message o un Error.
+/// Puede ser un Message, un Error, un log con nivel (Debug, Info o Warn) asociado a un LogTarget o un log con nivel sobre un peer.
 /// Los Message reciben un String y esos seran los logs que se guarden en el archivo, impriman en consola y se muestren en la interfaz.
 /// Los Error reciben un CustomError y haran los mismo que los Message, y ademas muestran una ventana popup con el error.
+/// Los Debug, Info y Warn se manejan como los Message, pero se descartan si su nivel es menor al configurado para su LogTarget.
+/// Los Peer se manejan como los anteriores con el LogTarget::Peer, e incluyen la direccion del peer.
 pub enum Log {
     Message(String),
+    Debug(LogTarget, String),
+    Info(LogTarget, String),
+    Warn(LogTarget, String),
+    Peer(LogLevel, SocketAddr, String),
     Error(CustomError),
     Terminate,
 }
 
+impl Log {
+    /// Devuelve el nivel del log, los Message son de nivel Info.
+    pub fn level(&self) -> LogLevel {
+        match self {
+            Log::Debug(..) => LogLevel::Debug,
+            Log::Message(_) | Log::Info(..) | Log::Terminate => LogLevel::Info,
+            Log::Warn(..) => LogLevel::Warn,
+            Log::Error(_) => LogLevel::Error,
+            Log::Peer(level, ..) => *level,
+        }
+    }
+
+    /// Devuelve el modulo que genero el log, los Message y Error son de LogTarget::General.
+    pub fn target(&self) -> LogTarget {
+        match self {
+            Log::Debug(target, _) | Log::Info(target, _) | Log::Warn(target, _) => *target,
+            Log::Peer(..) => LogTarget::Peer,
+            _ => LogTarget::General,
+        }
+    }
+
+    /// Devuelve la direccion del peer al que se refiere el log, si tiene una.
+    /// Las direcciones IPv4 mapeadas a IPv6 se devuelven como IPv4.
+    pub fn peer_address(&self) -> Option<SocketAddr> {
+        match self {
+            Log::Peer(_, SocketAddr::V6(address), _) => Some(get_socket_address(*address)),
+            Log::Peer(_, address, _) => Some(*address),
+            _ => None,
+        }
+    }
+
+    /// Devuelve el mensaje del log, sin nivel ni modulo.
+    pub fn message(&self) -> String {
+        match self {
+            Log::Message(string)
+            | Log::Debug(_, string)
+            | Log::Info(_, string)
+            | Log::Warn(_, string)
+            | Log::Peer(_, _, string) => string.clone(),
+            Log::Error(error) => error.to_string(),
+            Log::Terminate => String::new(),
+        }
+    }
+
+    /// Devuelve el log como un objeto JSON de una linea, con los campos timestamp, level, module, peer y message.
+    pub fn to_json(&self, timestamp: &DateTime<Local>) -> String {
+        let peer = match self.peer_address() {
+            Some(address) => json_string(&address.to_string()),
+            None => String::from("null"),
+        };
+        format!(
+            "{{\"timestamp\":{},\"level\":{},\"module\":{},\"peer\":{},\"message\":{}}}",
+            json_string(&timestamp.to_rfc3339()),
+            json_string(&self.level().to_string()),
+            json_string(&self.target().to_string()),
+            peer,
+            json_string(&self.message())
+        )
+    }
+}
+
+impl fmt::Display for Log {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Log::Message(string) => write!(f, "{}", string),
+            Log::Debug(target, string) | Log::Info(target, string) | Log::Warn(target, string) => {
+                write!(f, "[{}] [{}] {}", self.level(), target, string)
+            }
+            Log::Peer(level, address, string) => {
+                write!(
+                    f,
+                    "[{}] [{}] [{}] {}",
+                    level,
+                    self.target(),
+                    self.peer_address().unwrap_or(*address),
+                    string
+                )
+            }
+            Log::Error(error) => write!(f, "[{}] {}", self.level(), error),
+            Log::Terminate => Ok(()),
+        }
+    }
+}
+
+/// Devuelve el string entre comillas, escapando los caracteres que no pueden aparecer en un string JSON.
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for char in value.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char.is_control() => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+            char => escaped.push(char),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// LogFormat es el formato con el que se escriben los logs en el archivo y en consola.
+/// - Text: Una linea legible con el formato "[fecha] [NIVEL] [modulo] mensaje".
+/// - Json: Un objeto JSON por linea, para ser procesado por herramientas como journald o ELK.
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = CustomError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(CustomError::ConfigErrorReadingValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// LogLevel es el nivel de importancia de un log, ordenado de menor a mayor.
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl FromStr for LogLevel {
+    type Err = CustomError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(CustomError::ConfigErrorReadingValue),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        };
+        write!(f, "{}", level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// LogTarget es el modulo del nodo que genera un log, permite filtrar los logs de cada modulo por separado.
+/// Los modulos son:
+/// - General: Logs sin un modulo especifico.
+/// - Peer: Conexiones y mensajes con los peers.
+/// - Sync: Descarga de headers, bloques y generacion del UTXO.
+/// - Wallet: Wallets y transacciones del usuario.
+/// - Gui: Interfaz grafica.
+pub enum LogTarget {
+    General,
+    Peer,
+    Sync,
+    Wallet,
+    Gui,
+}
+
+impl FromStr for LogTarget {
+    type Err = CustomError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "general" => Ok(LogTarget::General),
+            "peer" => Ok(LogTarget::Peer),
+            "sync" => Ok(LogTarget::Sync),
+            "wallet" => Ok(LogTarget::Wallet),
+            "gui" => Ok(LogTarget::Gui),
+            _ => Err(CustomError::ConfigErrorReadingValue),
+        }
+    }
+}
+
+impl fmt::Display for LogTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = match self {
+            LogTarget::General => "general",
+            LogTarget::Peer => "peer",
+            LogTarget::Sync => "sync",
+            LogTarget::Wallet => "wallet",
+            LogTarget::Gui => "gui",
+        };
+        write!(f, "{}", target)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// LogFilter decide que logs se escriben segun su nivel y su LogTarget.
+/// Los elementos son:
+/// - level: Nivel minimo de los logs que se escriben.
+/// - targets: Nivel minimo de cada LogTarget, pisa al nivel general.
+pub struct LogFilter {
+    pub level: LogLevel,
+    pub targets: HashMap<LogTarget, LogLevel>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Info,
+            targets: HashMap::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    /// Carga los niveles de cada LogTarget a partir de un string con el formato {TARGET}:{NIVEL},{TARGET}:{NIVEL}.
+    /// Por ejemplo: "sync:warn,peer:debug".
+    /// Devuelve CustomError si el formato es invalido o algun target o nivel no existe.
+    pub fn set_targets(&mut self, value: &str) -> Result<(), CustomError> {
+        for directive in value.split(',').filter(|directive| !directive.is_empty()) {
+            let (target, level) = directive
+                .split_once(':')
+                .ok_or(CustomError::ConfigErrorReadingValue)?;
+            self.targets
+                .insert(LogTarget::from_str(target)?, LogLevel::from_str(level)?);
+        }
+        Ok(())
+    }
+
+    /// Devuelve true si el log debe escribirse.
+    pub fn enabled(&self, log: &Log) -> bool {
+        let level = self.targets.get(&log.target()).unwrap_or(&self.level);
+        log.level() >= *level
+    }
+}
+
 /// Logger es una estructura que contiene los elementos necesarios para manejar los logs.
 /// Los elementos son:
 /// - tx: Sender para enviar logs al logger.
@@ -33,14 +286,30 @@ pub struct Logger {
 }
 
 impl Logger {
+    /// Inicializa el logger en formato texto con el LogFilter por defecto (nivel Info para todos los modulos).
+    pub fn new(filename: &String, gui_sender: Sender<GUIEvents>) -> Result<Self, CustomError> {
+        Self::with_options(
+            filename,
+            gui_sender,
+            LogFilter::default(),
+            LogFormat::Text,
+            true,
+        )
+    }
+
     /// Inicializa el logger.
     /// Si el archivo donde se guardan los logs existe, lo borra.
     /// Crea el archivo de logs en la ubicacion recibida
     /// Inicializa el thread que escucha los Logs recibidos por un channel que crea.
-    /// Los logs se manejan como se comenta en el enum Log.
-    pub fn new(
+    /// Los logs que no pasan el filtro se descartan, el resto se manejan como se comenta en el enum Log.
+    /// El archivo y la consola reciben los logs en el formato recibido, la interfaz siempre los muestra como texto.
+    /// Si console es false no se imprimen en la consola, por ejemplo cuando la ocupa la interfaz de terminal.
+    pub fn with_options(
         filename: &String,
-        gui_sender: glib::Sender<GUIEvents>,
+        gui_sender: Sender<GUIEvents>,
+        filter: LogFilter,
+        format: LogFormat,
+        console: bool,
     ) -> Result<Self, CustomError> {
         let (tx, rx) = mpsc::channel::<Log>();
 
@@ -56,26 +325,26 @@ impl Logger {
 
         let thread = thread::spawn(move || -> Result<(), CustomError> {
             while let Ok(message) = rx.recv() {
-                match message {
-                    Log::Message(ref string) => {
-                        let current_time = Local::now();
-                        let formatted_time = current_time.format("%Y-%m-%d %H:%M:%S");
-                        println!("[{}] {}", formatted_time, string);
-                        writeln!(file, "[{}] {}", formatted_time, string)?;
-                        if let Err(error) = gui_sender.send(GUIEvents::Log(message)) {
-                            println!("Error sending log message to gui: {}", error);
-                        }
-                    }
-                    Log::Error(ref error) => {
-                        let current_time = Local::now();
+                if let Log::Terminate = message {
+                    break;
+                }
+                if !filter.enabled(&message) {
+                    continue;
+                }
+                let current_time = Local::now();
+                let line = match format {
+                    LogFormat::Text => {
                         let formatted_time = current_time.format("%Y-%m-%d %H:%M:%S");
-                        println!("[{}] [ERROR] {}", formatted_time, error);
-                        writeln!(file, "[{}] [ERROR] {}", formatted_time, error)?;
-                        if let Err(error) = gui_sender.send(GUIEvents::Log(message)) {
-                            println!("Error sending log error to gui: {}", error);
-                        }
+                        format!("[{}] {}", formatted_time, message)
                     }
-                    Log::Terminate => break,
+                    LogFormat::Json => message.to_json(&current_time),
+                };
+                if console {
+                    println!("{}", line);
+                }
+                writeln!(file, "{}", line)?;
+                if let Err(error) = gui_sender.send(GUIEvents::Log(message)) {
+                    println!("Error sending log to gui: {}", error);
                 }
             }
             Ok(())
@@ -103,13 +372,12 @@ pub fn send_log(logger_sender: &Sender<Log>, message: Log) {
 mod tests {
     use std::time;
 
-    use gtk::glib::Priority;
-
     use super::*;
+    use crate::utils::get_address_v6;
 
     #[test]
     fn log_file_gets_written() {
-        let (tx, _rx) = glib::MainContext::channel(Priority::default());
+        let (tx, _rx) = mpsc::channel();
 
         let logger = Logger::new(&String::from("test1.txt"), tx).unwrap();
         let sender = logger.get_sender();
@@ -133,7 +401,7 @@ mod tests {
 
     #[test]
     fn log_error_gets_written() {
-        let (tx, _rx) = glib::MainContext::channel(Priority::default());
+        let (tx, _rx) = mpsc::channel();
 
         let logger = Logger::new(&String::from("test2.txt"), tx).unwrap();
         let sender = logger.get_sender();
@@ -157,7 +425,7 @@ mod tests {
 
     #[test]
     fn log_file_gets_written_by_two_senders() {
-        let (tx, _rx) = glib::MainContext::channel(Priority::default());
+        let (tx, _rx) = mpsc::channel();
 
         let logger = Logger::new(&String::from("test3.txt"), tx).unwrap();
         let sender1 = logger.get_sender();
@@ -182,7 +450,7 @@ mod tests {
 
     #[test]
     fn log_file_gets_written_by_two_threads() {
-        let (tx, _rx) = glib::MainContext::channel(Priority::default());
+        let (tx, _rx) = mpsc::channel();
 
         let logger = Logger::new(&String::from("test4.txt"), tx).unwrap();
         let sender1 = logger.get_sender();
@@ -212,4 +480,69 @@ mod tests {
         assert_eq!(fs::read_to_string("test4.txt").unwrap(), result);
         fs::remove_file("test4.txt").unwrap();
     }
+
+    #[test]
+    fn log_levels_get_written() {
+        let (tx, _rx) = mpsc::channel();
+
+        let logger = Logger::new(&String::from("test5.txt"), tx).unwrap();
+        let sender = logger.get_sender();
+        let timestamp_string = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        sender
+            .send(Log::Warn(LogTarget::Peer, String::from("Sender test")))
+            .unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let result = format!("[{}] [WARN] [peer] Sender test\n", timestamp_string);
+        assert_eq!(fs::read_to_string("test5.txt").unwrap(), result);
+        fs::remove_file("test5.txt").unwrap();
+    }
+
+    #[test]
+    fn log_json_format() {
+        let timestamp = Local::now();
+        let address = SocketAddr::from(([127, 0, 0, 1], 18333));
+        let log = Log::Peer(LogLevel::Warn, address, String::from("Invalid \"ping\"\n"));
+
+        let result = format!(
+            "{{\"timestamp\":\"{}\",\"level\":\"WARN\",\"module\":\"peer\",\"peer\":\"127.0.0.1:18333\",\"message\":\"Invalid \\\"ping\\\"\\n\"}}",
+            timestamp.to_rfc3339()
+        );
+        assert_eq!(log.to_json(&timestamp), result);
+
+        let mapped_address = SocketAddr::from(get_address_v6(address));
+        let log = Log::Peer(
+            LogLevel::Warn,
+            mapped_address,
+            String::from("Invalid \"ping\"\n"),
+        );
+        assert_eq!(log.to_json(&timestamp), result);
+
+        let log = Log::Message(String::from("Sender test"));
+        assert!(log.to_json(&timestamp).ends_with(
+            "\"level\":\"INFO\",\"module\":\"general\",\"peer\":null,\"message\":\"Sender test\"}"
+        ));
+    }
+
+    #[test]
+    fn log_filter_by_level_and_target() {
+        let mut filter = LogFilter::default();
+        filter.set_targets("sync:warn,peer:debug").unwrap();
+
+        assert!(!filter.enabled(&Log::Debug(LogTarget::Wallet, String::new())));
+        assert!(filter.enabled(&Log::Info(LogTarget::Wallet, String::new())));
+        assert!(filter.enabled(&Log::Message(String::new())));
+        assert!(!filter.enabled(&Log::Info(LogTarget::Sync, String::new())));
+        assert!(filter.enabled(&Log::Warn(LogTarget::Sync, String::new())));
+        assert!(filter.enabled(&Log::Debug(LogTarget::Peer, String::new())));
+        assert!(filter.enabled(&Log::Error(CustomError::CannotRemoveFile)));
+    }
+
+    #[test]
+    fn log_filter_invalid_targets() {
+        let mut filter = LogFilter::default();
+        assert!(filter.set_targets("sync").is_err());
+        assert!(filter.set_targets("mempool:warn").is_err());
+        assert!(filter.set_targets("sync:verbose").is_err());
+    }
 }
@@ -0,0 +1,147 @@
+use std::{
+    net::{SocketAddr, SocketAddrV6},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log, LogLevel, LogTarget},
+    node_state::NodeState,
+    peer::Peer,
+    utils::get_socket_address,
+};
+
+/// Cada cuanto se verifica la cantidad de peers salientes.
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Cantidad maxima de direcciones que se intentan en cada verificacion, para no demorar el cierre del nodo.
+const MAX_ATTEMPTS_PER_CHECK: usize = 8;
+
+/// Funcion que llama a un peer y realiza el handshake, con los datos del nodo ya capturados.
+pub type PeerConnector = Box<dyn Fn(SocketAddr) -> Result<Peer, CustomError> + Send>;
+
+/// ConnectionLoop es el loop que mantiene la cantidad de peers salientes del nodo.
+/// Periodicamente cuenta los peers salientes y, si se desconecto alguno, se conecta con nuevas
+/// direcciones del address manager hasta volver a la cantidad buscada.
+/// Los elementos son:
+/// - logger_sender: Sender para enviar logs al logger.
+/// - node_state_ref: Referencia al estado del nodo.
+/// - connector: Funcion para llamar a un peer.
+/// - npeers: Cantidad de peers salientes que se buscan mantener.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
+pub struct ConnectionLoop {
+    logger_sender: mpsc::Sender<Log>,
+    node_state_ref: Arc<NodeState>,
+    connector: PeerConnector,
+    npeers: u8,
+    terminate_receiver: mpsc::Receiver<()>,
+}
+
+impl ConnectionLoop {
+    #[must_use]
+    /// Inicializa el loop en un thread.
+    /// Devuelve el thread junto al sender para indicarle que termine.
+    pub fn spawn(
+        logger_sender: mpsc::Sender<Log>,
+        node_state_ref: Arc<NodeState>,
+        connector: PeerConnector,
+        npeers: u8,
+    ) -> (JoinHandle<Result<(), CustomError>>, mpsc::Sender<()>) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        let thread = thread::spawn(move || -> Result<(), CustomError> {
+            let mut thread = Self {
+                logger_sender,
+                node_state_ref,
+                connector,
+                npeers,
+                terminate_receiver,
+            };
+            thread.event_loop()
+        });
+        (thread, terminate_sender)
+    }
+
+    fn event_loop(&mut self) -> Result<(), CustomError> {
+        while self
+            .terminate_receiver
+            .recv_timeout(CONNECTION_CHECK_INTERVAL)
+            == Err(RecvTimeoutError::Timeout)
+        {
+            let outbound_peers = self.node_state_ref.count_outbound_peers()?;
+            let missing_peers = (self.npeers as usize).saturating_sub(outbound_peers);
+            if missing_peers == 0 {
+                continue;
+            }
+
+            send_log(
+                &self.logger_sender,
+                Log::Info(
+                    LogTarget::Peer,
+                    format!(
+                        "{} outbound peers connected, looking for {} more",
+                        outbound_peers, missing_peers
+                    ),
+                ),
+            );
+            let peers = connect_to_peers(
+                &self.node_state_ref,
+                &self.logger_sender,
+                &self.connector,
+                self.node_state_ref.get_address_candidates()?,
+                missing_peers,
+                MAX_ATTEMPTS_PER_CHECK,
+            )?;
+            self.node_state_ref.append_peers(peers)?;
+        }
+        Ok(())
+    }
+}
+
+/// Se conecta con hasta number_of_peers de las direcciones candidatas, en orden, intentando como maximo max_attempts.
+/// Se saltean las direcciones baneadas y las de peers ya conectados.
+/// Registra en el address manager el resultado de cada intento y devuelve los peers conectados.
+pub fn connect_to_peers(
+    node_state_ref: &NodeState,
+    logger_sender: &mpsc::Sender<Log>,
+    connector: &PeerConnector,
+    candidates: Vec<SocketAddrV6>,
+    number_of_peers: usize,
+    max_attempts: usize,
+) -> Result<Vec<Peer>, CustomError> {
+    let mut peers = vec![];
+    let mut attempts = 0;
+
+    for address in candidates {
+        if peers.len() >= number_of_peers || attempts >= max_attempts {
+            break;
+        }
+        if node_state_ref.is_banned(address)? || node_state_ref.is_connected(address)? {
+            continue;
+        }
+
+        attempts += 1;
+        match connector(get_socket_address(address)) {
+            Ok(peer) => {
+                node_state_ref.address_connected(address)?;
+                peers.push(peer);
+            }
+            Err(error) => {
+                node_state_ref.address_failed(address)?;
+                send_log(
+                    logger_sender,
+                    Log::Peer(
+                        LogLevel::Warn,
+                        get_socket_address(address),
+                        format!("Error connecting to peer: {:?}", error),
+                    ),
+                );
+            }
+        };
+    }
+
+    Ok(peers)
+}
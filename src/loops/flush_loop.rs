@@ -0,0 +1,40 @@
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    node_state::NodeState,
+};
+
+/// Cada cuanto se guardan en disco los headers, las UTXO y las wallets que cambiaron.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// flush_loop es una funcion que genera un loop que guarda periodicamente en disco los headers, las UTXO y las wallets
+/// que cambiaron, para que el procesamiento de headers y bloques no tenga que esperar a que se escriban los archivos.
+/// Al terminar el nodo se guardan los cambios pendientes, por lo que ante un cierre inesperado solo se pierden
+/// los cambios del ultimo intervalo, que se vuelven a descargar.
+/// Los elementos son:
+/// - node_state_ref: Referencia al estado del nodo.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
+pub fn flush_loop(
+    node_state_ref: Arc<NodeState>,
+    logger_sender: mpsc::Sender<Log>,
+    terminate_receiver: mpsc::Receiver<()>,
+) -> thread::JoinHandle<Result<(), CustomError>> {
+    thread::spawn(move || -> Result<(), CustomError> {
+        while terminate_receiver.recv_timeout(FLUSH_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+            if let Err(error) = node_state_ref.flush_state() {
+                send_log(&logger_sender, Log::Error(error));
+            }
+        }
+        Ok(())
+    })
+}
@@ -1,5 +1,14 @@
+pub mod connection_loop;
+pub mod flush_loop;
 pub mod node_action_loop;
 pub mod peer_action_loop;
 pub mod peer_stream_loop;
 pub mod pending_blocks_loop;
+pub mod ping_loop;
+pub mod price_loop;
+pub mod publisher_loop;
+pub mod rebroadcast_loop;
+pub mod replay_loop;
+pub mod rest_api_loop;
+pub mod rpc_server_loop;
 pub mod tcp_listener_loop;
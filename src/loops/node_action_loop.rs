@@ -1,58 +1,214 @@
 use std::{
     collections::HashMap,
     net::SocketAddrV6,
-    sync::{mpsc, Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
 };
 
-use gtk::glib;
+use tracing::instrument;
 
 use crate::{
     error::CustomError,
-    gui::init::GUIEvents,
-    logger::{send_log, Log},
-    message::Message,
+    events::GUIEvents,
+    logger::{send_log, Log, LogLevel, LogTarget},
+    message::{Message, MAX_INVENTORIES},
     messages::{
-        block::Block, get_data::GetData, get_headers::GetHeaders, headers::Headers, inv::Inv,
-        not_found::NotFound, transaction::Transaction,
+        addr::{Addr, MAX_ADDR_COUNT},
+        addr_v2::AddrV2,
+        block::Block,
+        cfcheckpt::{CFCheckpt, GetCFCheckpt},
+        cfheaders::{CFHeaders, GetCFHeaders},
+        cfilters::{CFilter, GetCFilters},
+        get_data::GetData,
+        get_headers::GetHeaders,
+        headers::Headers,
+        inv::Inv,
+        not_found::NotFound,
+        transaction::Transaction,
     },
-    node_state::NodeState,
+    node_state::{remove_peer, NodeState},
+    parser::BtcEncode,
+    peer::{Misbehavior, BAN_SCORE},
+    services::{offers_service, serves_block_at_depth, NODE_COMPACT_FILTERS, NODE_WITNESS},
+    spv::block_inventory,
+    states::bans_state::DEFAULT_BAN_TIME,
     structs::{
-        block_header::{hash_as_string, BlockHeader},
+        block_filter::BASIC_FILTER_TYPE,
+        block_header::BlockHeader,
+        bloom_filter::BloomFilter,
+        hash32::Hash32,
+        headers_round::{HeadersRound, HEADERS_CROSS_CHECK_PEERS},
         inventory::{Inventory, InventoryType},
+        net_address::{NetAddress, NetAddressV2},
     },
+    utils::get_current_timestamp,
 };
 
-use super::peer_action_loop::PeerAction;
+use super::{peer_action_loop::PeerAction, publisher_loop::PublisherEvent};
 
 /// NodeAction es una enumeracion de las acciones que puede realizar el nodo.
 /// Las acciones son:
 /// - PeerError: Peer tiene comportamiento no esperado.
-/// - NewHeaders: Recibe nuevos headers.
-/// - GetHeadersError: Error al solicitar headers.
-/// - Block: Recibe un bloque.
+/// - PeerMisbehaving: Peer envio un mensaje invalido, se suma el puntaje del mal comportamiento.
+/// - NewHeaders: Recibe nuevos headers de un peer.
+/// - GetHeadersError: Error al solicitar headers a un peer.
+/// - Block: Recibe un bloque de un peer.
+/// - LocalBlock: Bloque agregado a la cadena por el propio nodo (por ejemplo minado con generate), para anunciarlo a los peers.
 /// - GetDataError: Error al solicitar data.
+/// - BlocksRequested: Bloques que se le pidieron a un peer.
 /// - PendingTransaction: Recibe una transaccion.
-/// - MakeTransaction: Solicitar una transaccion.
+/// - MakeTransaction: Solicitar una transaccion, con los outputs, el fee y opcionalmente el lock time.
+/// - AccelerateTransaction: Acelerar una pending tx de la wallet activa con una transaccion hija (CPFP), con el fee rate del paquete en satoshis por byte virtual.
+/// - ConsolidateUtxos: Juntar los UTXO de la wallet activa de hasta un valor (todos si es None) en una salida, con un fee rate en satoshis por byte virtual.
+/// - SendRawTransaction: Transaccion ya firmada enviada por el usuario (por ejemplo desde la consola) para agregar a la mempool y retransmitir.
 /// - SendHeaders: Habilita el envio directo de headers a un peer.
 /// - GetHeaders: Solicitud de headers de parte de un peer.
 /// - GetData: Solicitud de data de parte de un peer.
+/// - GetCFilters: Solicitud de filtros de bloques de parte de un peer (BIP157).
+/// - GetCFHeaders: Solicitud de headers de filtros de parte de un peer (BIP157).
+/// - GetCFCheckpt: Solicitud de checkpoints de headers de filtros de parte de un peer (BIP157).
+/// - Mempool: Solicitud de las transacciones pendientes de parte de un peer.
+/// - FeeFilter: Fee minimo de las transacciones que un peer quiere que le anuncien (BIP133).
+/// - BloomFilter: Bloom filter cargado por un peer, o None si lo descarto (BIP37).
+/// - NewAddresses: Direcciones de otros nodos anunciadas por un peer.
+/// - GetAddr: Solicitud de direcciones de otros nodos de parte de un peer.
+/// - Pong: Respuesta de un peer a un ping, con su nonce.
+/// - RebroadcastTransactions: Reenviar a los peers las transacciones locales que todavia no se confirmaron.
+/// - DisconnectPeer: Desconectar a un peer a pedido del usuario.
+/// - BanPeer: Banear y desconectar a un peer a pedido del usuario.
 /// - Terminate: Termina el nodo.
 pub enum NodeAction {
     PeerError(SocketAddrV6),
-    NewHeaders(Headers),
-    GetHeadersError,
-    Block((Vec<u8>, Block)),
+    PeerMisbehaving(SocketAddrV6, Misbehavior),
+    NewHeaders(SocketAddrV6, Headers),
+    GetHeadersError(SocketAddrV6),
+    Block(SocketAddrV6, Hash32, Block),
+    LocalBlock(Block),
     GetDataError(Vec<Inventory>),
+    BlocksRequested(SocketAddrV6, Vec<Hash32>),
     PendingTransaction(Transaction),
-    MakeTransaction((HashMap<String, u64>, u64)),
+    MakeTransaction((HashMap<String, u64>, u64, Option<u32>)),
+    ConsolidateUtxos(Option<u64>, u64),
+    AccelerateTransaction(Hash32, u64),
+    SendRawTransaction(Transaction),
     SendHeaders(SocketAddrV6),
     GetHeaders(SocketAddrV6, GetHeaders),
     GetData(SocketAddrV6, GetData),
+    GetCFilters(SocketAddrV6, GetCFilters),
+    GetCFHeaders(SocketAddrV6, GetCFHeaders),
+    GetCFCheckpt(SocketAddrV6, GetCFCheckpt),
+    Mempool(SocketAddrV6),
+    FeeFilter(SocketAddrV6, u64),
+    BloomFilter(SocketAddrV6, Option<BloomFilter>),
+    NewAddresses(Vec<NetAddress>),
+    GetAddr(SocketAddrV6),
+    Pong(SocketAddrV6, u64),
+    RebroadcastTransactions,
+    DisconnectPeer(SocketAddrV6),
+    BanPeer(SocketAddrV6),
     Terminate,
 }
 
+/// Cantidad maxima de NodeAction encolados antes de que los senders se bloqueen.
+/// Evita que un peer rapido durante la IBD acumule bloques en memoria mas rapido de lo que se procesan.
+pub const NODE_ACTION_QUEUE_SIZE: usize = 128;
+
+/// Crea el channel acotado por el que los peers, la GUI y el nodo envian acciones al NodeActionLoop.
+pub fn node_action_channel(capacity: usize) -> (NodeActionSender, NodeActionReceiver) {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let queued = Arc::new(AtomicUsize::new(0));
+    (
+        NodeActionSender {
+            sender,
+            queued: queued.clone(),
+            capacity,
+        },
+        NodeActionReceiver { receiver, queued },
+    )
+}
+
+#[derive(Clone)]
+/// NodeActionSender es el extremo de envio del channel acotado de NodeAction.
+/// Si la cola esta llena, send se bloquea hasta que el NodeActionLoop la vacie (backpressure),
+/// mientras que try_send falla en el momento, para las interfaces y el RPC.
+/// Los elementos son:
+/// - sender: SyncSender del channel.
+/// - queued: Cantidad de acciones encoladas sin procesar.
+/// - capacity: Capacidad maxima de la cola.
+pub struct NodeActionSender {
+    sender: mpsc::SyncSender<NodeAction>,
+    queued: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl NodeActionSender {
+    /// Encola una accion, bloqueandose mientras la cola este llena.
+    /// Se usa en los loops de los peers, que se pausan hasta que el nodo procese lo recibido.
+    /// Devuelve CustomError si el NodeActionLoop ya termino.
+    pub fn send(&self, action: NodeAction) -> Result<(), CustomError> {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        if let Err(error) = self.sender.send(action) {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    /// Encola una accion sin bloquearse, para que la GUI, la TUI y el RPC no se congelen mientras el nodo esta ocupado.
+    /// Devuelve CustomError::NodeBusy si la cola esta llena, para que el usuario lo vuelva a intentar,
+    /// y CustomError si el NodeActionLoop ya termino.
+    pub fn try_send(&self, action: NodeAction) -> Result<(), CustomError> {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        if let Err(error) = self.sender.try_send(action) {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(match error {
+                mpsc::TrySendError::Full(_) => CustomError::NodeBusy,
+                mpsc::TrySendError::Disconnected(_) => CustomError::CannotSendMessageToChannel,
+            });
+        }
+        Ok(())
+    }
+
+    /// Devuelve si la cola alcanzo su capacidad maxima.
+    /// Se usa para pausar los pedidos de getdata hasta que el nodo procese lo recibido.
+    pub fn is_full(&self) -> bool {
+        self.queued.load(Ordering::SeqCst) >= self.capacity
+    }
+}
+
+/// NodeActionReceiver es el extremo de recepcion del channel acotado de NodeAction.
+/// Al dropearse vacia el contador de acciones encoladas para que ningun peer quede pausado esperando.
+pub struct NodeActionReceiver {
+    receiver: mpsc::Receiver<NodeAction>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl NodeActionReceiver {
+    /// Espera la siguiente accion de la cola.
+    pub fn recv(&self) -> Result<NodeAction, mpsc::RecvError> {
+        let action = self.receiver.recv()?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        Ok(action)
+    }
+}
+
+impl Drop for NodeActionReceiver {
+    fn drop(&mut self) {
+        self.queued.store(0, Ordering::SeqCst);
+    }
+}
+
 const START_DATE_IBD: u32 = 1681095630;
 
+/// Timestamp minimo que se acepta en una direccion anunciada (marzo de 1973, igual que Bitcoin Core).
+const ADDR_MIN_TIMESTAMP: u64 = 100_000_000;
+/// Segundos en el futuro que se aceptan en una direccion anunciada.
+const ADDR_MAX_FUTURE: u64 = 10 * 60;
+/// Antiguedad en segundos que se asigna a las direcciones con timestamp invalido.
+const ADDR_PENALTY: u64 = 5 * 24 * 60 * 60;
+
 /// NodeActionLoop es una estructura que contiene los elementos necesarios para manejar los mensajes recibidos por el nodo.
 /// Genera el loop de eventos alrededor de los NodeAction recibidoe por node_action_receiver.
 /// Los elementos son:
@@ -61,22 +217,34 @@ const START_DATE_IBD: u32 = 1681095630;
 /// - peer_action_sender: Sender para enviar acciones al los peers.
 /// - logger_sender: Sender para enviar logs al logger.
 /// - node_state_ref: Referencia al estado del nodo.
+/// - publisher_sender: Sender para publicar eventos de bloques y transacciones, si el publisher esta habilitado.
+/// - sync_peers: Cantidad de peers mas rapidos entre los que se reparten los pedidos de headers y bloques.
+/// - headers_round: Cantidad de pedidos de headers realizados, para rotar entre los peers mas rapidos.
+/// - pending_headers: Lote de headers pedido a varios peers cuyas respuestas todavia se estan esperando.
+/// - mempool_requested: Indica si ya se les pidio su mempool a los peers, lo que se hace al terminar la sincronizacion.
 pub struct NodeActionLoop {
-    gui_sender: glib::Sender<GUIEvents>,
-    node_action_receiver: mpsc::Receiver<NodeAction>,
+    gui_sender: mpsc::Sender<GUIEvents>,
+    node_action_receiver: NodeActionReceiver,
     peer_action_sender: mpsc::Sender<PeerAction>,
     logger_sender: mpsc::Sender<Log>,
-    node_state_ref: Arc<Mutex<NodeState>>,
+    node_state_ref: Arc<NodeState>,
+    publisher_sender: Option<mpsc::Sender<PublisherEvent>>,
+    sync_peers: usize,
+    headers_round: usize,
+    pending_headers: Option<HeadersRound>,
+    mempool_requested: bool,
 }
 
 impl NodeActionLoop {
     /// Inicializa el loop de eventos.
     pub fn start(
-        gui_sender: glib::Sender<GUIEvents>,
-        node_action_receiver: mpsc::Receiver<NodeAction>,
+        gui_sender: mpsc::Sender<GUIEvents>,
+        node_action_receiver: NodeActionReceiver,
         peer_action_sender: mpsc::Sender<PeerAction>,
         logger_sender: mpsc::Sender<Log>,
-        node_state_ref: Arc<Mutex<NodeState>>,
+        node_state_ref: Arc<NodeState>,
+        publisher_sender: Option<mpsc::Sender<PublisherEvent>>,
+        sync_peers: usize,
     ) {
         let mut node_thread = Self {
             gui_sender,
@@ -84,7 +252,15 @@ impl NodeActionLoop {
             peer_action_sender,
             logger_sender,
             node_state_ref,
+            publisher_sender,
+            sync_peers,
+            headers_round: 0,
+            pending_headers: None,
+            mempool_requested: false,
         };
+        if let Err(error) = node_thread.start_headers_download() {
+            send_log(&node_thread.logger_sender, Log::Error(error));
+        }
         node_thread.event_loop();
     }
 
@@ -92,21 +268,63 @@ impl NodeActionLoop {
         while let Ok(message) = self.node_action_receiver.recv() {
             let response = match message {
                 NodeAction::PeerError(address) => self.handle_peer_error(address),
-                NodeAction::Block((block_hash, block)) => self.handle_block(block_hash, block),
-                NodeAction::NewHeaders(new_headers) => self.handle_new_headers(new_headers),
-                NodeAction::GetHeadersError => self.handle_get_headers_error(),
+                NodeAction::PeerMisbehaving(address, misbehavior) => {
+                    self.handle_peer_misbehaving(address, misbehavior)
+                }
+                NodeAction::Block(address, block_hash, block) => {
+                    self.handle_block(address, block_hash, block)
+                }
+                NodeAction::LocalBlock(block) => self.handle_local_block(block),
+                NodeAction::NewHeaders(address, new_headers) => {
+                    self.handle_new_headers(address, new_headers)
+                }
+                NodeAction::GetHeadersError(address) => self.handle_get_headers_error(address),
                 NodeAction::GetDataError(inventory) => self.handle_get_data_error(inventory),
-                NodeAction::MakeTransaction((outputs, fee)) => {
-                    self.handle_make_transaction(outputs, fee)
+                NodeAction::BlocksRequested(address, block_hashes) => self
+                    .node_state_ref
+                    .request_pending_blocks(address, block_hashes),
+                NodeAction::MakeTransaction((outputs, fee, lock_time)) => {
+                    self.handle_make_transaction(outputs, fee, lock_time)
+                }
+                NodeAction::ConsolidateUtxos(max_value, fee_rate) => {
+                    self.handle_consolidate_utxos(max_value, fee_rate)
+                }
+                NodeAction::AccelerateTransaction(tx_hash, fee_rate) => {
+                    self.handle_accelerate_transaction(tx_hash, fee_rate)
                 }
                 NodeAction::PendingTransaction(transaction) => {
                     self.handle_pending_transaction(transaction)
                 }
+                NodeAction::SendRawTransaction(transaction) => {
+                    self.handle_send_raw_transaction(transaction)
+                }
                 NodeAction::SendHeaders(address) => self.handle_send_headers(address),
                 NodeAction::GetHeaders(address, getheaders) => {
                     self.handle_get_headers(address, getheaders)
                 }
                 NodeAction::GetData(address, getdata) => self.handle_get_data(address, getdata),
+                NodeAction::GetCFilters(address, getcfilters) => {
+                    self.handle_get_cfilters(address, getcfilters)
+                }
+                NodeAction::GetCFHeaders(address, getcfheaders) => {
+                    self.handle_get_cfheaders(address, getcfheaders)
+                }
+                NodeAction::GetCFCheckpt(address, getcfcheckpt) => {
+                    self.handle_get_cfcheckpt(address, getcfcheckpt)
+                }
+                NodeAction::Mempool(address) => self.handle_mempool(address),
+                NodeAction::FeeFilter(address, fee_rate) => {
+                    self.node_state_ref.peer_fee_filter(address, fee_rate)
+                }
+                NodeAction::BloomFilter(address, bloom_filter) => {
+                    self.node_state_ref.peer_bloom_filter(address, bloom_filter)
+                }
+                NodeAction::NewAddresses(addresses) => self.handle_new_addresses(addresses),
+                NodeAction::GetAddr(address) => self.handle_get_addr(address),
+                NodeAction::Pong(address, nonce) => self.handle_pong(address, nonce),
+                NodeAction::RebroadcastTransactions => self.handle_rebroadcast_transactions(),
+                NodeAction::DisconnectPeer(address) => self.handle_disconnect_peer(address),
+                NodeAction::BanPeer(address) => self.handle_ban_peer(address),
                 NodeAction::Terminate => break,
             };
 
@@ -120,12 +338,81 @@ impl NodeActionLoop {
     }
 
     fn handle_peer_error(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
         send_log(
             &self.logger_sender,
-            Log::Message(format!("Deleting Peer {} from list...", address)),
+            Log::Peer(
+                LogLevel::Info,
+                address.into(),
+                String::from("Deleting peer from list..."),
+            ),
+        );
+        self.node_state_ref.remove_peer(address)?;
+        self.stop_waiting_headers(address)
+    }
+
+    fn handle_disconnect_peer(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Info,
+                address.into(),
+                String::from("Disconnecting peer by user request"),
+            ),
+        );
+        self.node_state_ref.remove_peer(address)?;
+        self.stop_waiting_headers(address)
+    }
+
+    /// Banea la direccion IP del peer por DEFAULT_BAN_TIME, lo que tambien desconecta a los peers conectados desde ella.
+    fn handle_ban_peer(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Warn,
+                address.into(),
+                String::from("Banning and disconnecting peer by user request"),
+            ),
+        );
+        self.node_state_ref.set_ban(
+            *address.ip(),
+            String::from("banned by user"),
+            DEFAULT_BAN_TIME,
+        )?;
+        self.stop_waiting_headers(address)
+    }
+
+    fn handle_peer_misbehaving(
+        &mut self,
+        address: SocketAddrV6,
+        misbehavior: Misbehavior,
+    ) -> Result<(), CustomError> {
+        let Some(score) = self.node_state_ref.peer_misbehaving(address, misbehavior)? else {
+            return Ok(());
+        };
+
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Warn,
+                address.into(),
+                format!("Misbehaving ({misbehavior}), score {score}"),
+            ),
         );
-        node_state.remove_peer(address);
+        if score >= BAN_SCORE && !self.node_state_ref.is_whitelisted(address.ip())? {
+            send_log(
+                &self.logger_sender,
+                Log::Peer(
+                    LogLevel::Warn,
+                    address.into(),
+                    String::from("Banning and disconnecting peer"),
+                ),
+            );
+            self.node_state_ref.set_ban(
+                *address.ip(),
+                misbehavior.to_string(),
+                DEFAULT_BAN_TIME,
+            )?;
+        }
         Ok(())
     }
 
@@ -133,35 +420,122 @@ impl NodeActionLoop {
         &mut self,
         outputs: HashMap<String, u64>,
         fee: u64,
+        lock_time: Option<u32>,
     ) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        let transaction = match node_state.make_transaction(outputs, fee) {
+        let transaction = match self
+            .node_state_ref
+            .make_transaction(outputs, fee, lock_time)
+        {
             Ok(transaction) => transaction,
             Err(error) => {
                 send_log(&self.logger_sender, Log::Error(error));
                 return Ok(());
             }
         };
-        drop(node_state);
 
-        self.broadcast(transaction.clone())?;
+        self.broadcast_local_transaction(&transaction)?;
+
+        self.node_state_ref.label_sent_transaction(&transaction)?;
+        self.node_state_ref.append_local_tx(transaction)?;
+        self.gui_sender.send(GUIEvents::TransactionSent)?;
+
+        Ok(())
+    }
+
+    fn handle_consolidate_utxos(
+        &mut self,
+        max_value: Option<u64>,
+        fee_rate: u64,
+    ) -> Result<(), CustomError> {
+        let transaction = match self.node_state_ref.make_consolidation(max_value, fee_rate) {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                send_log(&self.logger_sender, Log::Error(error));
+                return Ok(());
+            }
+        };
+
+        self.broadcast_local_transaction(&transaction)?;
+        self.node_state_ref.append_local_tx(transaction)?;
+
+        Ok(())
+    }
+
+    fn handle_accelerate_transaction(
+        &mut self,
+        tx_hash: Hash32,
+        fee_rate: u64,
+    ) -> Result<(), CustomError> {
+        let transaction = match self.node_state_ref.make_acceleration(tx_hash, fee_rate) {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                send_log(&self.logger_sender, Log::Error(error));
+                return Ok(());
+            }
+        };
+
+        self.broadcast_local_transaction(&transaction)?;
+        self.node_state_ref.append_local_tx(transaction)?;
+
+        Ok(())
+    }
+
+    /// Envia a los peers y publica una transaccion creada por una wallet del nodo.
+    fn broadcast_local_transaction(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<(), CustomError> {
+        self.broadcast_transaction(transaction)?;
+        self.publish(PublisherEvent::Transaction(
+            transaction.hash(),
+            transaction.serialize(),
+        ));
 
         send_log(
             &self.logger_sender,
-            Log::Message("Transaction broadcasted!".to_string()),
+            Log::Info(LogTarget::Wallet, "Transaction broadcasted!".to_string()),
         );
+        Ok(())
+    }
 
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.append_pending_tx(transaction)?;
-        self.gui_sender.send(GUIEvents::TransactionSent)?;
+    /// Agrega a la mempool una transaccion enviada por el usuario y, si se acepta, la retransmite a los peers.
+    /// Se marca como local para que se siga reenviando hasta que se confirme.
+    fn handle_send_raw_transaction(&mut self, transaction: Transaction) -> Result<(), CustomError> {
+        let tx_hash = transaction.hash();
+        if !self.node_state_ref.append_local_tx(transaction.clone())? {
+            send_log(
+                &self.logger_sender,
+                Log::Warn(
+                    LogTarget::Wallet,
+                    format!("Raw transaction {tx_hash} was not accepted to the mempool"),
+                ),
+            );
+            return Ok(());
+        }
 
+        self.broadcast_transaction(&transaction)?;
+        self.publish(PublisherEvent::Transaction(
+            tx_hash,
+            transaction.serialize(),
+        ));
+
+        send_log(
+            &self.logger_sender,
+            Log::Info(
+                LogTarget::Wallet,
+                format!("Raw transaction {tx_hash} broadcasted!"),
+            ),
+        );
         Ok(())
     }
 
     fn handle_get_data_error(&mut self, inventory: Vec<Inventory>) -> Result<(), CustomError> {
         send_log(
             &self.logger_sender,
-            Log::Message("Error requesting data,trying with another peer...".to_string()),
+            Log::Warn(
+                LogTarget::Sync,
+                "Error requesting data,trying with another peer...".to_string(),
+            ),
         );
 
         self.peer_action_sender
@@ -169,14 +543,27 @@ impl NodeActionLoop {
         Ok(())
     }
 
-    fn handle_get_headers_error(&mut self) -> Result<(), CustomError> {
-        let node_state = self.node_state_ref.lock()?;
-        let last_header = node_state.get_last_header_hash();
-        drop(node_state);
+    fn handle_get_headers_error(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        if self
+            .pending_headers
+            .as_ref()
+            .is_some_and(|round| round.is_pending(address))
+        {
+            return self.stop_waiting_headers(address);
+        }
+        self.request_headers_from_any_peer()
+    }
+
+    /// Pide los headers siguientes al ultimo a cualquier peer, cuando fallo el pedido a los peers mas rapidos.
+    fn request_headers_from_any_peer(&mut self) -> Result<(), CustomError> {
+        let last_header = self.node_state_ref.get_last_header_hash()?;
 
         send_log(
             &self.logger_sender,
-            Log::Message("Error requesting headers,trying with another peer...".to_string()),
+            Log::Warn(
+                LogTarget::Sync,
+                "Error requesting headers,trying with another peer...".to_string(),
+            ),
         );
 
         self.peer_action_sender
@@ -184,181 +571,586 @@ impl NodeActionLoop {
         Ok(())
     }
 
-    fn handle_new_headers(&mut self, new_headers: Headers) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.append_headers(&new_headers)?;
-        drop(node_state);
+    /// Si los headers responden al lote pedido a varios peers, se guardan hasta que respondan todos.
+    /// Los headers que llegan sin formar parte de un lote pedido (ej: anuncios de bloques nuevos) se agregan directamente.
+    #[instrument(skip_all, fields(count = new_headers.headers.len()))]
+    fn handle_new_headers(
+        &mut self,
+        address: SocketAddrV6,
+        new_headers: Headers,
+    ) -> Result<(), CustomError> {
+        let Some(round) = self
+            .pending_headers
+            .as_mut()
+            .filter(|round| round.is_pending(address))
+        else {
+            return self.append_new_headers(new_headers);
+        };
+        if round.add_response(address, new_headers) {
+            return self.resolve_headers_round();
+        }
+        Ok(())
+    }
+
+    /// Deja de esperar el lote de headers de un peer que no va a responder, y resuelve la ronda si era el ultimo.
+    fn stop_waiting_headers(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let Some(round) = self.pending_headers.as_mut() else {
+            return Ok(());
+        };
+        if round.is_pending(address) && round.remove_peer(address) {
+            return self.resolve_headers_round();
+        }
+        Ok(())
+    }
 
-        let headers_after_timestamp = &new_headers
+    /// Elige el lote de headers de la ronda, señala a los peers que enviaron una cadena divergente y agrega el lote elegido.
+    /// Si ningun peer respondio se vuelven a pedir los headers.
+    fn resolve_headers_round(&mut self) -> Result<(), CustomError> {
+        let Some(round) = self.pending_headers.take() else {
+            return Ok(());
+        };
+        let Some((new_headers, divergent)) = round.resolve() else {
+            return self.request_headers_from_any_peer();
+        };
+        for address in divergent {
+            self.handle_peer_misbehaving(address, Misbehavior::DivergentHeaders)?;
+        }
+        self.append_new_headers(new_headers)
+    }
+
+    /// Agrega los headers y encola los bloques de los que no se conocian.
+    /// Si el lote esta completo pide el siguiente, aunque todos sus headers ya se conocieran.
+    fn append_new_headers(&mut self, new_headers: Headers) -> Result<(), CustomError> {
+        let appended = self.node_state_ref.append_headers(&new_headers)?;
+
+        if new_headers.headers.len() == 2000 {
+            let last_header = new_headers.headers.last().map(BlockHeader::hash).cloned();
+            self.request_next_headers(last_header)?;
+        }
+
+        let headers_after_timestamp = &appended
             .headers
             .iter()
             .filter(|header| header.timestamp > START_DATE_IBD)
             .collect::<Vec<_>>();
-        let chunks: Vec<&[&BlockHeader]> = headers_after_timestamp.chunks(5).collect();
-        for chunk in chunks {
-            self.request_block(chunk)?;
+        for header in headers_after_timestamp {
+            if self.node_state_ref.is_before_snapshot(header.hash())? {
+                continue;
+            }
+            self.node_state_ref.append_pending_block(*header.hash())?;
         }
 
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.verify_sync()?;
+        // Si no hay peers conectados, los bloques se encolan para el primer peer que se conecte
+        if self
+            .node_state_ref
+            .schedule_block_requests(self.sync_peers)?
+            .is_none()
+        {
+            let chunks: Vec<&[&BlockHeader]> = headers_after_timestamp.chunks(5).collect();
+            for chunk in chunks {
+                self.request_block(chunk)?;
+            }
+        }
 
-        Ok(())
+        self.node_state_ref.verify_sync()?;
+        self.request_mempools_if_synced()
     }
 
-    fn request_block(&mut self, headers: &[&BlockHeader]) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
+    /// La primera vez que el nodo queda sincronizado les pide su mempool a los peers,
+    /// para no arrancar sin transacciones pendientes. Las transacciones llegan como inv y tx.
+    fn request_mempools_if_synced(&mut self) -> Result<(), CustomError> {
+        if self.mempool_requested
+            || self.node_state_ref.is_blocks_only()
+            || !self.node_state_ref.is_synced()?
+        {
+            return Ok(());
+        }
+        self.mempool_requested = true;
 
-        let mut inventories = vec![];
-        for header in headers {
-            node_state.append_pending_block(header.hash().clone())?;
-            inventories.push(Inventory::new(InventoryType::Block, header.hash().clone()));
+        let requested = self.node_state_ref.request_mempools()?;
+        send_log(
+            &self.logger_sender,
+            Log::Info(
+                LogTarget::Sync,
+                format!("Requesting mempool from {requested} peers"),
+            ),
+        );
+        Ok(())
+    }
+
+    /// Comienza la descarga de headers a partir del ultimo header guardado.
+    fn start_headers_download(&mut self) -> Result<(), CustomError> {
+        let last_header = self.node_state_ref.get_last_header_hash()?;
+        let sync_peers = self.request_next_headers(last_header)?;
+        if !sync_peers.is_empty() {
+            send_log(
+                &self.logger_sender,
+                Log::Info(
+                    LogTarget::Sync,
+                    format!(
+                        "Starting headers download with fastest peers: {}",
+                        sync_peers
+                            .iter()
+                            .map(|address| address.ip().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ),
+            );
         }
+        Ok(())
+    }
 
-        drop(node_state);
+    /// Pide el siguiente lote de headers a HEADERS_CROSS_CHECK_PEERS de los peers mas rapidos, rotando entre ellos,
+    /// para comparar sus respuestas. Si no hay peers a los que pedirselo, se le pide a cualquier peer.
+    /// Devuelve los peers a los que se les pidio el lote.
+    fn request_next_headers(
+        &mut self,
+        last_header: Option<Hash32>,
+    ) -> Result<Vec<SocketAddrV6>, CustomError> {
+        self.headers_round += 1;
+        let sync_peers = self.node_state_ref.request_headers_from_sync_peers(
+            last_header,
+            self.sync_peers,
+            self.headers_round,
+            HEADERS_CROSS_CHECK_PEERS,
+        )?;
+        self.pending_headers = match sync_peers.is_empty() {
+            true => {
+                self.peer_action_sender
+                    .send(PeerAction::GetHeaders(last_header))?;
+                None
+            }
+            false => Some(HeadersRound::new(sync_peers.clone())),
+        };
+        Ok(sync_peers)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(count = headers.len()))]
+    fn request_block(&mut self, headers: &[&BlockHeader]) -> Result<(), CustomError> {
+        let spv = self.node_state_ref.is_spv_mode();
+        let inventories = headers
+            .iter()
+            .map(|header| block_inventory(*header.hash(), spv))
+            .collect();
 
         self.peer_action_sender
             .send(PeerAction::GetData(inventories))?;
         Ok(())
     }
 
-    fn handle_block(&mut self, block_hash: Vec<u8>, block: Block) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        if !node_state.is_block_pending(&block_hash)? {
-            drop(node_state);
-            return Ok(());
+    #[instrument(skip_all, fields(transactions = block.transactions.len()))]
+    fn handle_block(
+        &mut self,
+        address: SocketAddrV6,
+        block_hash: Hash32,
+        block: Block,
+    ) -> Result<(), CustomError> {
+        if !self.node_state_ref.is_block_pending(&block_hash)? {
+            return self.handle_peer_misbehaving(address, Misbehavior::UnrequestedBlock);
         }
 
         // check if the node was synced before receiving the block
-        let is_synced = node_state.is_synced();
-
-        node_state.append_block(block_hash, &block)?;
-        drop(node_state);
+        let is_synced = self.node_state_ref.is_synced()?;
 
+        // Las UTXO solo estan completas una vez sincronizado el nodo
         if is_synced {
-            self.broadcast_new_header(block.header)?;
+            if let Err(error) = self.node_state_ref.validate_block(&block) {
+                send_log(
+                    &self.logger_sender,
+                    Log::Message(format!("Rejected block {block_hash}: {error}")),
+                );
+                return self.handle_peer_misbehaving(address, Misbehavior::InvalidBlock);
+            }
+        }
+
+        self.node_state_ref.append_block(block_hash, &block)?;
+        self.node_state_ref
+            .schedule_block_requests(self.sync_peers)?;
+        self.request_mempools_if_synced()?;
+
+        self.publish(PublisherEvent::Block(block_hash, block.serialize()));
+
+        // Un cliente SPV no tiene el bloque completo para entregarlo a quien lo pida
+        if is_synced && !self.node_state_ref.is_spv_mode() {
+            self.broadcast_new_header(Some(address), block.header)?;
         }
         Ok(())
     }
 
+    /// Publica el bloque que agrego el propio nodo y lo anuncia a todos los peers.
+    fn handle_local_block(&mut self, block: Block) -> Result<(), CustomError> {
+        let block_hash = *block.header.hash();
+        self.publish(PublisherEvent::Block(block_hash, block.serialize()));
+        self.broadcast_new_header(None, block.header)
+    }
+
+    /// Agrega la transaccion recibida a las pendientes y la reenvia a los peers si es nueva.
+    /// En modo solo bloques se ignoran las transacciones que envian los peers sin haberlas pedido,
+    /// y siempre se descartan las que no son estandar.
     fn handle_pending_transaction(&mut self, transaction: Transaction) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        if !node_state.is_synced() {
-            drop(node_state);
+        if self.node_state_ref.is_blocks_only() || !self.node_state_ref.is_synced()? {
+            return Ok(());
+        }
+        if let Err(error) = transaction.check_standard() {
+            send_log(
+                &self.logger_sender,
+                Log::Debug(
+                    LogTarget::Wallet,
+                    format!(
+                        "Rejected pending transaction {}: {error}",
+                        transaction.hash()
+                    ),
+                ),
+            );
             return Ok(());
         }
 
-        let is_pending_new = node_state.append_pending_tx(transaction.clone())?;
-        drop(node_state);
+        let is_pending_new = self.node_state_ref.append_pending_tx(transaction.clone())?;
 
         if is_pending_new {
-            self.broadcast(transaction)?;
+            self.publish(PublisherEvent::Transaction(
+                transaction.hash(),
+                transaction.serialize(),
+            ));
+            self.broadcast_transaction(&transaction)?;
         }
         Ok(())
     }
 
-    fn handle_send_headers(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.peer_send_headers(address);
+    /// Reenvia a los peers las transacciones creadas por las wallets que todavia no se confirmaron,
+    /// ya que el envio al crearlas se puede perder si los peers se desconectan o las descartan.
+    fn handle_rebroadcast_transactions(&mut self) -> Result<(), CustomError> {
+        if !self.node_state_ref.is_synced()? {
+            return Ok(());
+        }
+
+        let local_txs = self.node_state_ref.get_local_pending_txs()?;
+        for transaction in &local_txs {
+            self.broadcast_transaction(transaction)?;
+        }
+        if !local_txs.is_empty() {
+            send_log(
+                &self.logger_sender,
+                Log::Info(
+                    LogTarget::Wallet,
+                    format!(
+                        "Rebroadcasted {} unconfirmed wallet transactions",
+                        local_txs.len()
+                    ),
+                ),
+            );
+        }
         Ok(())
     }
 
+    fn handle_send_headers(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        self.node_state_ref.peer_send_headers(address)
+    }
+
     fn handle_get_headers(
         &mut self,
         address: SocketAddrV6,
         getheaders: GetHeaders,
     ) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.peer_requested_headers(address);
-        let headers = node_state.get_headers(getheaders);
+        self.node_state_ref.peer_requested_headers(address)?;
+        let headers = self.node_state_ref.get_headers(getheaders)?;
 
         let message = Headers { headers };
-        send_message(&mut node_state, address, message)
+        send_message(&self.node_state_ref, address, message)
+    }
+
+    /// Responde con un mensaje 'cfilter' por cada bloque del rango pedido.
+    /// Si no se tienen los filtros del rango, o no se anuncia NODE_COMPACT_FILTERS, no se responde,
+    /// para que el peer los pida a otro nodo.
+    fn handle_get_cfilters(
+        &mut self,
+        address: SocketAddrV6,
+        getcfilters: GetCFilters,
+    ) -> Result<(), CustomError> {
+        if getcfilters.filter_type != BASIC_FILTER_TYPE
+            || !self.offers_service(NODE_COMPACT_FILTERS)
+        {
+            return Ok(());
+        }
+        let Some(filters) = self
+            .node_state_ref
+            .get_filters(getcfilters.start_height, &getcfilters.stop_hash)?
+        else {
+            return Ok(());
+        };
+
+        for (block_hash, filter) in filters {
+            let message = CFilter {
+                filter_type: BASIC_FILTER_TYPE,
+                block_hash,
+                filter: filter.data,
+            };
+            send_message(&self.node_state_ref, address, message)?;
+        }
+        Ok(())
+    }
+
+    fn handle_get_cfheaders(
+        &mut self,
+        address: SocketAddrV6,
+        getcfheaders: GetCFHeaders,
+    ) -> Result<(), CustomError> {
+        if getcfheaders.filter_type != BASIC_FILTER_TYPE
+            || !self.offers_service(NODE_COMPACT_FILTERS)
+        {
+            return Ok(());
+        }
+        let Some((previous_filter_header, filter_hashes)) = self
+            .node_state_ref
+            .get_filter_hashes(getcfheaders.start_height, &getcfheaders.stop_hash)?
+        else {
+            return Ok(());
+        };
+
+        let message = CFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: getcfheaders.stop_hash,
+            previous_filter_header,
+            filter_hashes,
+        };
+        send_message(&self.node_state_ref, address, message)
+    }
+
+    fn handle_get_cfcheckpt(
+        &mut self,
+        address: SocketAddrV6,
+        getcfcheckpt: GetCFCheckpt,
+    ) -> Result<(), CustomError> {
+        if getcfcheckpt.filter_type != BASIC_FILTER_TYPE
+            || !self.offers_service(NODE_COMPACT_FILTERS)
+        {
+            return Ok(());
+        }
+        let Some(filter_headers) = self
+            .node_state_ref
+            .get_filter_checkpoints(&getcfcheckpt.stop_hash)?
+        else {
+            return Ok(());
+        };
+
+        let message = CFCheckpt {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: getcfcheckpt.stop_hash,
+            filter_headers,
+        };
+        send_message(&self.node_state_ref, address, message)
+    }
+
+    /// Anuncia al peer las pending txs mediante mensajes inv, de a MAX_INVENTORIES inventarios por mensaje.
+    fn handle_mempool(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let inventories = self.node_state_ref.get_mempool_inventories(address)?;
+        for chunk in inventories.chunks(MAX_INVENTORIES as usize) {
+            send_message(&self.node_state_ref, address, Inv::new(chunk.to_vec()))?;
+        }
+        Ok(())
     }
 
+    /// Responde los bloques y transacciones pedidos, con sus witness solo si se pidieron como
+    /// MSG_WITNESS_BLOCK, MSG_WITNESS_TX o MSG_WTX (BIP144 y BIP339). Lo que no se tiene se informa con notfound.
+    /// Los bloques guardados sin witness no se pueden servir como MSG_WITNESS_BLOCK.
+    /// Solo se sirve lo que permiten los servicios anunciados: los bloques segun NODE_NETWORK y
+    /// NODE_NETWORK_LIMITED, y los witness solo con NODE_WITNESS.
     fn handle_get_data(
         &mut self,
         address: SocketAddrV6,
         getdata: GetData,
     ) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
+        let node_state = &self.node_state_ref;
         for inventory in getdata.get_inventories() {
-            match inventory.inventory_type {
-                InventoryType::Block => {
-                    match node_state.get_block(hash_as_string(inventory.hash.clone())) {
-                        Ok(block) => send_message(&mut node_state, address, block)?,
-                        Err(_) => {
-                            let not_found = NotFound::new(vec![inventory.clone()]);
-                            send_message(&mut node_state, address, not_found)?;
+            let found = match inventory.inventory_type {
+                InventoryType::Block | InventoryType::WitnessBlock
+                    if !self.can_serve_block(inventory)? =>
+                {
+                    false
+                }
+                InventoryType::WitnessTx | InventoryType::Wtx
+                    if !self.offers_service(NODE_WITNESS) =>
+                {
+                    false
+                }
+                InventoryType::Block | InventoryType::WitnessBlock => {
+                    match node_state.get_block(&inventory.hash) {
+                        Ok(block)
+                            if inventory.inventory_type == InventoryType::WitnessBlock
+                                && block.is_stripped() =>
+                        {
+                            false
+                        }
+                        Ok(mut block) => {
+                            if inventory.inventory_type == InventoryType::Block {
+                                block.strip_witness();
+                            }
+                            send_message(node_state, address, block)?;
+                            true
                         }
+                        Err(_) => false,
                     }
                 }
-                InventoryType::Tx => {
-                    match node_state.get_pending_tx(&inventory.hash) {
-                        Some(tx) => send_message(&mut node_state, address, tx)?,
-                        None => {
-                            let not_found = NotFound::new(vec![inventory.clone()]);
-                            send_message(&mut node_state, address, not_found)?;
+                InventoryType::Tx | InventoryType::WitnessTx | InventoryType::Wtx => {
+                    let pending_tx = match inventory.inventory_type {
+                        InventoryType::Wtx => {
+                            node_state.get_pending_tx_by_wtxid(&inventory.hash)?
                         }
+                        _ => node_state.get_pending_tx(&inventory.hash)?,
                     };
+                    match pending_tx {
+                        Some(mut tx) => {
+                            if inventory.inventory_type == InventoryType::Tx {
+                                tx.strip_witness();
+                            }
+                            send_message(node_state, address, tx)?;
+                            true
+                        }
+                        None => false,
+                    }
                 }
-                _ => {
-                    let not_found = NotFound::new(vec![inventory.clone()]);
-                    send_message(&mut node_state, address, not_found)?;
-                }
+                _ => false,
+            };
+            if !found {
+                let not_found = NotFound::new(vec![inventory.clone()]);
+                send_message(node_state, address, not_found)?;
             }
         }
         Ok(())
     }
 
-    fn broadcast(&mut self, message: impl Message) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
+    /// Indica si los servicios anunciados permiten servir el bloque pedido, segun su profundidad en la cadena
+    /// y si se pidio con sus witness.
+    fn can_serve_block(&self, inventory: &Inventory) -> Result<bool, CustomError> {
+        if inventory.inventory_type == InventoryType::WitnessBlock
+            && !self.offers_service(NODE_WITNESS)
+        {
+            return Ok(false);
+        }
+        let local_services = self.node_state_ref.get_local_services();
+        Ok(self
+            .node_state_ref
+            .get_block_depth(&inventory.hash)?
+            .is_some_and(|depth| serves_block_at_depth(local_services, depth)))
+    }
+
+    /// Indica si los servicios que anuncia el nodo incluyen el servicio recibido.
+    fn offers_service(&self, service: u64) -> bool {
+        offers_service(self.node_state_ref.get_local_services(), service)
+    }
+
+    /// Agrega al address manager las direcciones anunciadas por un peer.
+    /// Si el timestamp anunciado es invalido o esta en el futuro, se considera que se vio hace 5 dias, como hace Bitcoin Core.
+    fn handle_new_addresses(&mut self, addresses: Vec<NetAddress>) -> Result<(), CustomError> {
+        let now = get_current_timestamp()?;
+        for net_address in addresses {
+            if net_address.address.port() == 0 || net_address.address.ip().is_unspecified() {
+                continue;
+            }
+            let mut timestamp = net_address.timestamp as u64;
+            if timestamp <= ADDR_MIN_TIMESTAMP || timestamp > now + ADDR_MAX_FUTURE {
+                timestamp = now.saturating_sub(ADDR_PENALTY);
+            }
+            self.node_state_ref.add_address(
+                net_address.address,
+                net_address.services,
+                timestamp,
+            )?;
+        }
+        Ok(())
+    }
 
-        let peers = node_state.get_peers();
+    fn handle_get_addr(&mut self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let addresses = self.node_state_ref.get_addresses_to_share(MAX_ADDR_COUNT)?;
+        if self.node_state_ref.peer_send_addr_v2(address)? {
+            let addresses = addresses.into_iter().map(NetAddressV2::from).collect();
+            return send_message(&self.node_state_ref, address, AddrV2::new(addresses));
+        }
+        send_message(&self.node_state_ref, address, Addr::new(addresses))
+    }
+
+    fn handle_pong(&mut self, address: SocketAddrV6, nonce: u64) -> Result<(), CustomError> {
+        let Some(latency) = self.node_state_ref.peer_pong(address, nonce)? else {
+            return Ok(());
+        };
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Debug,
+                address.into(),
+                format!("Pong received, latency: {} ms", latency),
+            ),
+        );
+        Ok(())
+    }
+
+    /// Envia la transaccion a todos los peers, salvo a los que pidieron no recibir transacciones (fRelay en false).
+    fn broadcast_transaction(&mut self, transaction: &Transaction) -> Result<(), CustomError> {
+        let mut peers = self.node_state_ref.get_peers()?;
         let mut peers_to_remove = vec![];
-        for peer in peers {
-            if message.send(&mut peer.stream).is_err() {
+        for peer in peers.iter_mut().filter(|peer| peer.relay) {
+            if peer.traffic.send(transaction, &mut peer.stream).is_err() {
                 peers_to_remove.push(peer.address);
             }
         }
 
         for address in peers_to_remove {
-            node_state.remove_peer(address);
+            remove_peer(&mut peers, address);
             send_log(
                 &self.logger_sender,
-                Log::Message(format!(
-                    "Error sending message {} to peer {}",
-                    message.get_command(),
-                    address,
-                )),
+                Log::Peer(
+                    LogLevel::Warn,
+                    address.into(),
+                    format!("Error sending message {}", transaction.get_command()),
+                ),
             );
         }
 
         Ok(())
     }
 
-    fn broadcast_new_header(&self, header: BlockHeader) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        let headers_to_send = node_state.get_headers_to_send(header.hash());
+    /// Envia el evento al publisher, si esta habilitado. Si el publisher termino, se registra el error
+    /// sin interrumpir el procesamiento del bloque o la transaccion.
+    fn publish(&self, event: PublisherEvent) {
+        if let Some(publisher_sender) = &self.publisher_sender {
+            if publisher_sender.send(event).is_err() {
+                send_log(
+                    &self.logger_sender,
+                    Log::Message(String::from(
+                        "Cannot publish event, the publisher is closed",
+                    )),
+                );
+            }
+        }
+    }
+
+    /// Anuncia los bloques nuevos a todos los peers salvo al que envio el bloque, si lo envio un peer.
+    /// A los que pidieron sendheaders y ya pidieron headers (por lo que conocen la cadena y pueden conectarlos)
+    /// se les envian los headers, al resto un inv con los hashes de los bloques para que los pidan.
+    fn broadcast_new_header(
+        &self,
+        source: Option<SocketAddrV6>,
+        header: BlockHeader,
+    ) -> Result<(), CustomError> {
+        let headers_to_send = self.node_state_ref.get_headers_to_send(header.hash())?;
         if headers_to_send.is_empty() {
             return Ok(());
         }
+        let mut peers = self.node_state_ref.get_peers()?;
         let mut peers_to_remove = vec![];
-        for peer in node_state.get_peers() {
-            if !peer.requested_headers {
-                continue;
-            }
-            let sent = if peer.send_headers {
+        for peer in peers.iter_mut().filter(|peer| Some(peer.address) != source) {
+            let sent = if peer.send_headers && peer.requested_headers {
                 let headers_msg = Headers {
                     headers: headers_to_send.clone(),
                 };
-                headers_msg.send(&mut peer.stream)
+                peer.send(headers_msg)
             } else {
-                let mut inventories = vec![];
-                for header in &headers_to_send {
-                    inventories.push(Inventory::new(InventoryType::Block, header.hash().clone()));
-                }
-                let inv_msg = Inv::new(inventories);
-                inv_msg.send(&mut peer.stream)
+                let inventories = headers_to_send
+                    .iter()
+                    .map(|header| Inventory::new(InventoryType::Block, *header.hash()))
+                    .collect();
+                peer.send(Inv::new(inventories))
             };
             if sent.is_err() {
                 peers_to_remove.push(peer.address);
@@ -366,22 +1158,76 @@ impl NodeActionLoop {
         }
 
         for address in peers_to_remove {
-            node_state.remove_peer(address);
+            remove_peer(&mut peers, address);
         }
         Ok(())
     }
 }
 
 fn send_message(
-    node_state: &mut std::sync::MutexGuard<'_, NodeState>,
+    node_state: &NodeState,
     address: SocketAddrV6,
     message: impl Message,
 ) -> Result<(), CustomError> {
-    let peer = node_state.get_peer(&address);
+    let mut peers = node_state.get_peers()?;
+    let peer = peers.iter_mut().find(|p| p.address == address);
     if let Some(peer) = peer {
         if peer.send(message).is_err() {
-            node_state.remove_peer(address);
+            remove_peer(&mut peers, address);
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_action_channel_backpressure() {
+        let address = SocketAddrV6::new(std::net::Ipv6Addr::LOCALHOST, 8333, 0, 0);
+        let (sender, receiver) = node_action_channel(2);
+        assert!(!sender.is_full());
+
+        sender.send(NodeAction::GetHeadersError(address)).unwrap();
+        sender.send(NodeAction::GetHeadersError(address)).unwrap();
+        assert!(sender.is_full());
+
+        receiver.recv().unwrap();
+        assert!(!sender.is_full());
+
+        sender.send(NodeAction::GetHeadersError(address)).unwrap();
+        assert!(sender.is_full());
+
+        drop(receiver);
+        assert!(!sender.is_full());
+        assert!(sender.send(NodeAction::Terminate).is_err());
+    }
+
+    #[test]
+    fn node_action_try_send_does_not_block() {
+        let address = SocketAddrV6::new(std::net::Ipv6Addr::LOCALHOST, 8333, 0, 0);
+        let (sender, receiver) = node_action_channel(1);
+
+        sender
+            .try_send(NodeAction::GetHeadersError(address))
+            .unwrap();
+        assert!(matches!(
+            sender.try_send(NodeAction::GetHeadersError(address)),
+            Err(CustomError::NodeBusy)
+        ));
+        assert!(sender.is_full());
+
+        receiver.recv().unwrap();
+        assert!(!sender.is_full());
+        sender
+            .try_send(NodeAction::GetHeadersError(address))
+            .unwrap();
+
+        drop(receiver);
+        assert!(matches!(
+            sender.try_send(NodeAction::Terminate),
+            Err(CustomError::CannotSendMessageToChannel)
+        ));
+    }
+}
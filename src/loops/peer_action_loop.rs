@@ -2,18 +2,24 @@ use std::{
     net::{SocketAddrV6, TcpStream},
     sync::{mpsc, Arc, Mutex},
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
-    message::Message,
+    logger::{send_log, Log, LogLevel},
     messages::{get_data::GetData, transaction::Transaction},
-    peer::request_headers,
-    structs::inventory::Inventory,
+    peer::{request_headers, PeerTraffic},
+    structs::{
+        hash32::Hash32,
+        inventory::{Inventory, InventoryType},
+    },
 };
 
-use super::node_action_loop::NodeAction;
+use super::node_action_loop::{NodeAction, NodeActionSender};
+
+/// Tiempo entre chequeos de la cola de NodeAction mientras los pedidos de getdata estan pausados.
+const BACKPRESSURE_WAIT: Duration = Duration::from_millis(100);
 
 /// PeerAction es una enumeracion de las acciones que puede realizar un peer.
 /// Las acciones son:
@@ -22,7 +28,7 @@ use super::node_action_loop::NodeAction;
 /// - SendTransaction: Envia una transaccion al peer.
 /// - Terminate: Termina la conexion con el peer.
 pub enum PeerAction {
-    GetHeaders(Option<Vec<u8>>),
+    GetHeaders(Option<Hash32>),
     GetData(Vec<Inventory>),
     SendTransaction(Transaction),
     Terminate,
@@ -34,6 +40,7 @@ pub enum PeerAction {
 /// - address: Direccion del peer.
 /// - version: Version del nodo.
 /// - stream: Stream del peer.
+/// - traffic: Bytes recibidos y enviados al peer, compartidos con el Peer.
 /// - logger_sender: Sender para enviar logs al logger.
 /// - peer_action_receiver: Receiver para recibir acciones del peer.
 /// - node_action_sender: Sender para enviar acciones al nodo.
@@ -41,9 +48,10 @@ pub struct PeerActionLoop {
     pub address: SocketAddrV6,
     pub version: i32,
     pub stream: TcpStream,
+    pub traffic: Arc<PeerTraffic>,
     pub logger_sender: mpsc::Sender<Log>,
     pub peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-    pub node_action_sender: mpsc::Sender<NodeAction>,
+    pub node_action_sender: NodeActionSender,
 }
 
 impl PeerActionLoop {
@@ -52,9 +60,10 @@ impl PeerActionLoop {
         address: SocketAddrV6,
         version: i32,
         stream: TcpStream,
+        traffic: Arc<PeerTraffic>,
         logger_sender: mpsc::Sender<Log>,
         peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
     ) -> JoinHandle<Result<(), CustomError>> {
         thread::spawn(move || -> Result<(), CustomError> {
             let mut peer_action_thread = Self {
@@ -62,6 +71,7 @@ impl PeerActionLoop {
                 peer_action_receiver,
                 version,
                 stream,
+                traffic,
                 logger_sender,
                 node_action_sender,
             };
@@ -88,7 +98,11 @@ impl PeerActionLoop {
             if let Err(error) = response {
                 send_log(
                     &self.logger_sender,
-                    Log::Message(format!("Error on PeerActionLoop: {error}")),
+                    Log::Peer(
+                        LogLevel::Warn,
+                        self.address.into(),
+                        format!("Error on PeerActionLoop: {error}"),
+                    ),
                 );
                 self.node_action_sender
                     .send(NodeAction::PeerError(self.address))?;
@@ -99,29 +113,74 @@ impl PeerActionLoop {
     }
 
     fn handle_send_transaction(&mut self, transaction: &Transaction) -> Result<(), CustomError> {
-        transaction.send(&mut self.stream)?;
+        self.traffic.send(transaction, &mut self.stream)?;
         send_log(
             &self.logger_sender,
-            Log::Message("Sending transaction".to_string()),
+            Log::Peer(
+                LogLevel::Debug,
+                self.address.into(),
+                "Sending transaction".to_string(),
+            ),
         );
         Ok(())
     }
     fn handle_getdata(&mut self, inventories: Vec<Inventory>) -> Result<(), CustomError> {
+        self.wait_node_action_queue();
         let inventories_clone = inventories.clone();
-        let request = GetData::new(inventories).send(&mut self.stream);
+        let request = self
+            .traffic
+            .send(&GetData::new(inventories), &mut self.stream);
         if let Err(error) = request {
             self.node_action_sender
                 .send(NodeAction::GetDataError(inventories_clone))?;
             return Err(error);
         };
+
+        let block_hashes: Vec<Hash32> = inventories_clone
+            .iter()
+            .filter(|inventory| {
+                matches!(
+                    inventory.inventory_type,
+                    InventoryType::Block
+                        | InventoryType::WitnessBlock
+                        | InventoryType::FilteredBlock
+                )
+            })
+            .map(|inventory| inventory.hash)
+            .collect();
+        if !block_hashes.is_empty() {
+            self.node_action_sender
+                .send(NodeAction::BlocksRequested(self.address, block_hashes))?;
+        }
         Ok(())
     }
 
-    fn handle_getheaders(&mut self, last_header: Option<Vec<u8>>) -> Result<(), CustomError> {
+    /// Pausa los pedidos de getdata mientras la cola de NodeAction este llena,
+    /// para no pedir mas bloques de los que el nodo llega a procesar.
+    fn wait_node_action_queue(&self) {
+        if !self.node_action_sender.is_full() {
+            return;
+        }
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Debug,
+                self.address.into(),
+                "Node action queue is full, pausing getdata requests".to_string(),
+            ),
+        );
+        while self.node_action_sender.is_full() {
+            thread::sleep(BACKPRESSURE_WAIT);
+        }
+    }
+
+    fn handle_getheaders(&mut self, last_header: Option<Hash32>) -> Result<(), CustomError> {
         request_headers(
+            self.address,
             last_header,
             self.version,
             &mut self.stream,
+            &self.traffic,
             &self.logger_sender,
             &self.node_action_sender,
         )
@@ -1,66 +1,103 @@
 use std::{
     io::Read,
     net::{SocketAddrV6, TcpStream},
-    sync::mpsc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
     thread::{self, JoinHandle},
 };
 
+use tracing::instrument;
+
 use crate::{
+    capture::CaptureReader,
     error::CustomError,
-    logger::{send_log, Log},
-    message::{Message, MessageHeader},
+    logger::{send_log, Log, LogLevel, LogTarget},
+    message::{MessageHeader, MESSAGE_HEADER_SIZE},
     messages::{
+        addr::Addr,
+        addr_v2::AddrV2,
         block::Block,
+        cfcheckpt::GetCFCheckpt,
+        cfheaders::GetCFHeaders,
+        cfilters::GetCFilters,
+        fee_filter::FeeFilter,
+        filter_load::{FilterClear, FilterLoad},
+        get_addr::GetAddr,
         get_data::GetData,
         get_headers::GetHeaders,
         headers::Headers,
         inv::Inv,
+        mempool::Mempool,
+        merkle_block::{FilteredBlock, MerkleBlock},
         ping_pong::{Ping, Pong},
+        reject::Reject,
         send_headers::SendHeaders,
         transaction::Transaction,
     },
-    peer::request_headers,
+    parser::BtcEncode,
+    peer::{Misbehavior, PeerConfig, PeerTraffic, BAN_SCORE},
+    spv::block_inventory,
     structs::{
-        block_header::BlockHeader,
         inventory::{Inventory, InventoryType},
+        net_address::NetAddressV2,
     },
+    utils::{get_current_timestamp, get_current_timestamp_millis},
 };
 
-use super::node_action_loop::NodeAction;
+use super::node_action_loop::{NodeAction, NodeActionSender};
+
+/// Comandos de los mensajes que se consideran utiles, porque aportan datos de la blockchain al nodo.
+const USEFUL_COMMANDS: [&str; 5] = ["headers", "block", "merkleblock", "tx", "inv"];
 
 /// PeerStreamLoop es una estructura que contiene los elementos necesarios para manejar los mensajes recibidos del peer asociado.
 /// Genera el loop de eventos alrededor de los mensajes recibidos por el TcpStream.
 /// Los elementos son:
 /// - address: Direccion del peer.
 /// - stream: Stream del peer.
+/// - config: Configuracion de las conexiones con los peers: red, modo solo bloques o SPV y captura de los mensajes.
+/// - last_useful_message: Timestamp del ultimo mensaje util recibido, compartido con el Peer.
+/// - traffic: Bytes recibidos y enviados al peer, compartidos con el Peer.
 /// - node_action_sender: Sender para enviar acciones al nodo.
-/// - version: Version del nodo.
 /// - logger_sender: Sender para enviar logs al logger.
+/// - filtered_block: Bloque filtrado recibido en un merkleblock, a la espera de sus transacciones.
+/// - captured: Bytes leidos del mensaje actual, None si no se capturan los mensajes recibidos.
 pub struct PeerStreamLoop {
     pub address: SocketAddrV6,
     pub stream: TcpStream,
-    pub node_action_sender: mpsc::Sender<NodeAction>,
-    pub version: i32,
+    pub config: PeerConfig,
+    pub last_useful_message: Arc<AtomicU64>,
+    pub traffic: Arc<PeerTraffic>,
+    pub node_action_sender: NodeActionSender,
     pub logger_sender: mpsc::Sender<Log>,
+    pub filtered_block: Option<FilteredBlock>,
+    pub captured: Option<Vec<u8>>,
 }
 
 impl PeerStreamLoop {
     #[must_use]
     /// Inicializa el loop de eventos en un thread.
     pub fn spawn(
-        version: i32,
         address: SocketAddrV6,
         stream: TcpStream,
+        config: PeerConfig,
+        last_useful_message: Arc<AtomicU64>,
+        traffic: Arc<PeerTraffic>,
         logger_sender: mpsc::Sender<Log>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
     ) -> JoinHandle<Result<(), CustomError>> {
         thread::spawn(move || -> Result<(), CustomError> {
             let mut peer_action_thread = Self {
                 address,
                 stream,
+                captured: config.capture.as_ref().map(|_| vec![]),
+                config,
+                last_useful_message,
+                traffic,
                 node_action_sender,
-                version,
                 logger_sender,
+                filtered_block: None,
             };
             peer_action_thread.event_loop()
         })
@@ -68,110 +105,294 @@ impl PeerStreamLoop {
 
     fn event_loop(&mut self) -> Result<(), CustomError> {
         loop {
-            let response_header = MessageHeader::read(&mut self.stream)?;
-
-            let response = match response_header.command.as_str() {
-                "headers" => self.handle_headers(&response_header),
-                "block" => self.handle_block(&response_header),
-                "ping" => self.handle_ping(&response_header),
-                "inv" => self.handle_inv(&response_header),
-                "tx" => self.handle_tx(&response_header),
-                "notfound" => self.handle_notfound(&response_header),
-                "sendheaders" => self.handle_sendheaders(&response_header),
-                "getheaders" => self.handle_getheaders(&response_header),
-                "getdata" => self.handle_getdata(&response_header),
-                _ => self.ignore_message(&response_header),
+            // Si el peer no envia mensajes antes del read timeout se lo desconecta,
+            // cualquier otro error de lectura indica que se cerro la conexion.
+            let response_header = MessageHeader::read(&mut self.reader());
+            let (command, response) = match response_header {
+                Ok(response_header) => {
+                    let timestamp = get_current_timestamp_millis()? as u64;
+                    let response = self.handle_message(&response_header);
+                    self.record_message(timestamp);
+                    (response_header.command.clone(), response)
+                }
+                Err(CustomError::PeerTimeout) => (String::new(), Err(CustomError::PeerTimeout)),
+                Err(error) => return Err(error),
             };
 
-            if let Err(error) = response {
-                send_log(
-                    &self.logger_sender,
-                    Log::Message(format!("Error on PeerStreamLoop: {error}")),
-                );
+            let Err(error) = response else { continue };
+
+            // Un mensaje invalido suma puntaje de mal comportamiento, el nodo decide si banear al peer.
+            // Si no alcanza para banearlo se mantiene la conexion, ya que el mensaje se leyo completo.
+            if let Some(misbehavior) = Misbehavior::from_error(&error) {
+                self.send_reject(command, misbehavior);
                 self.node_action_sender
-                    .send(NodeAction::PeerError(self.address))?;
-                break;
+                    .send(NodeAction::PeerMisbehaving(self.address, misbehavior))?;
+                if misbehavior.score() < BAN_SCORE {
+                    continue;
+                }
             }
+
+            send_log(
+                &self.logger_sender,
+                Log::Peer(
+                    LogLevel::Warn,
+                    self.address.into(),
+                    format!("Error on PeerStreamLoop: {error}"),
+                ),
+            );
+            self.node_action_sender
+                .send(NodeAction::PeerError(self.address))?;
+            break;
+        }
+        Ok(())
+    }
+
+    /// Devuelve el stream del que se leen los mensajes, que guarda los bytes leidos si se estan capturando.
+    fn reader(&mut self) -> impl Read + '_ {
+        CaptureReader::new(&mut self.stream, self.captured.as_mut())
+    }
+
+    /// Guarda en la captura los bytes del mensaje leido, junto al timestamp en que se recibio y la direccion del peer.
+    /// Si no se puede guardar solo se registra en el log, para no desconectar al peer.
+    fn record_message(&mut self, timestamp: u64) {
+        let (Some(capture), Some(captured)) = (&self.config.capture, self.captured.as_mut()) else {
+            return;
+        };
+        if let Err(error) = capture.record(timestamp, self.address, captured) {
+            send_log(
+                &self.logger_sender,
+                Log::Peer(
+                    LogLevel::Warn,
+                    self.address.into(),
+                    format!("Error recording a message to {}: {error}", capture.path),
+                ),
+            );
+        }
+        captured.clear();
+    }
+
+    /// Le informa al peer con un mensaje 'reject' que se rechazo su mensaje y por que, para que pueda diagnosticarlo.
+    /// Si no se puede enviar no se hace nada, el error de la conexion se detecta al leer el siguiente mensaje.
+    fn send_reject(&mut self, command: String, misbehavior: Misbehavior) {
+        let Some(code) = misbehavior.reject_code() else {
+            return;
+        };
+        let reject = Reject::new(command, code, misbehavior.to_string(), None);
+        let _ = self.traffic.send(&reject, &mut self.stream);
+    }
+
+    /// Lee el payload del mensaje, verificando su checksum, y lo procesa segun su comando.
+    /// Si el payload no corresponde al header, el mensaje esta corrupto y se devuelve error para desconectar al peer.
+    fn handle_message(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
+        // Los payloads de headers y block son los mas grandes, se parsean a medida que se leen del stream
+        match response_header.command.as_str() {
+            "headers" => return self.handle_headers(response_header),
+            "block" => return self.handle_block(response_header),
+            _ => {}
+        }
+
+        let network = self.config.network;
+        let payload = response_header.read_payload(&mut self.reader(), network)?;
+        self.register_received(response_header)?;
+
+        match response_header.command.as_str() {
+            "merkleblock" => self.handle_merkleblock(payload),
+            "ping" => self.handle_ping(payload),
+            "pong" => self.handle_pong(payload),
+            "inv" => self.handle_inv(payload),
+            "tx" => self.handle_tx(payload),
+            "notfound" => self.handle_notfound(payload),
+            "sendheaders" => self.handle_sendheaders(payload),
+            "getheaders" => self.handle_getheaders(payload),
+            "getdata" => self.handle_getdata(payload),
+            "getcfilters" => self.handle_getcfilters(payload),
+            "getcfheaders" => self.handle_getcfheaders(payload),
+            "getcfcheckpt" => self.handle_getcfcheckpt(payload),
+            "addr" => self.handle_addr(payload),
+            "addrv2" => self.handle_addrv2(payload),
+            "getaddr" => self.handle_getaddr(payload),
+            "reject" => self.handle_reject(payload),
+            "mempool" => self.handle_mempool(payload),
+            "feefilter" => self.handle_feefilter(payload),
+            "filterload" => self.handle_filterload(payload),
+            "filterclear" => self.handle_filterclear(payload),
+            _ => self.ignore_message(response_header),
+        }
+    }
+
+    /// Registra el trafico del mensaje recibido y, si es un mensaje util, el momento en que se recibio.
+    fn register_received(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
+        self.traffic
+            .add_received(MESSAGE_HEADER_SIZE + response_header.payload_size as usize);
+        if USEFUL_COMMANDS.contains(&response_header.command.as_str()) {
+            self.last_useful_message
+                .store(get_current_timestamp()?, Ordering::Relaxed);
         }
         Ok(())
     }
 
+    #[instrument(name = "headers_download", skip_all, fields(peer = %self.address))]
     fn handle_headers(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let response = match Headers::read(&mut self.stream, response_header.payload_size) {
-            Ok(response) => response,
+        let network = self.config.network;
+        let response = response_header.read_streamed(&mut self.reader(), network, Headers::decode);
+        let response = match response {
+            Ok(response) => {
+                self.register_received(response_header)?;
+                response
+            }
             Err(error) => {
-                self.node_action_sender.send(NodeAction::GetHeadersError)?;
+                self.node_action_sender
+                    .send(NodeAction::GetHeadersError(self.address))?;
                 return Err(error);
             }
         };
 
-        if response.headers.len() == 2000 {
-            let last_header = response.headers.last().map(BlockHeader::hash).cloned();
-            request_headers(
-                last_header,
-                self.version,
-                &mut self.stream,
-                &self.logger_sender,
-                &self.node_action_sender,
-            )?;
-        }
         self.node_action_sender
-            .send(NodeAction::NewHeaders(response))?;
+            .send(NodeAction::NewHeaders(self.address, response))?;
         Ok(())
     }
 
+    #[instrument(name = "block_download", skip_all, fields(peer = %self.address, size = response_header.payload_size))]
     fn handle_block(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let block = Block::read(&mut self.stream, response_header.payload_size)?;
-        if let Err(error) = block.create_merkle_root() {
-            let inventory = Inventory::new(InventoryType::Block, block.header.hash().clone());
+        let network = self.config.network;
+        let block = response_header.read_streamed(&mut self.reader(), network, Block::decode)?;
+        self.register_received(response_header)?;
+        if let Err(error) = block
+            .create_merkle_root()
+            .and_then(|_| block.check_limits())
+        {
+            let inventory = block_inventory(*block.header.hash(), self.config.spv);
 
             self.node_action_sender
                 .send(NodeAction::GetDataError(vec![inventory]))?;
 
             send_log(
                 &self.logger_sender,
-                Log::Message(format!(
-                    "Error validating the merkle root in the block: {:?}",
-                    block.header.hash()
-                )),
+                Log::Warn(
+                    LogTarget::Sync,
+                    format!(
+                        "Error validating the block {:?}: {error}",
+                        block.header.hash()
+                    ),
+                ),
             );
             return Err(error);
         };
 
-        self.node_action_sender
-            .send(NodeAction::Block((block.header.hash().clone(), block)))?;
+        self.node_action_sender.send(NodeAction::Block(
+            self.address,
+            *block.header.hash(),
+            block,
+        ))?;
 
         Ok(())
     }
 
-    fn handle_ping(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let ping = Ping::read(&mut self.stream, response_header.payload_size)?;
+    /// Verifica el partial merkle tree del merkleblock y espera las transacciones que coincidieron con el filtro,
+    /// que el peer envia a continuacion en mensajes 'tx'. Si no coincidio ninguna el bloque ya esta completo.
+    #[instrument(name = "block_download", skip_all, fields(peer = %self.address, size = payload.len()))]
+    fn handle_merkleblock(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let merkle_block = MerkleBlock::parse(payload)?;
+        let block_hash = *merkle_block.header.hash();
+        let filtered_block =
+            match FilteredBlock::new(merkle_block) {
+                Ok(filtered_block) => filtered_block,
+                Err(error) => {
+                    self.node_action_sender.send(NodeAction::GetDataError(vec![
+                        block_inventory(block_hash, self.config.spv),
+                    ]))?;
+                    send_log(
+                        &self.logger_sender,
+                        Log::Warn(
+                            LogTarget::Sync,
+                            format!("Error validating the merkle block: {:?}", block_hash),
+                        ),
+                    );
+                    return Err(error);
+                }
+            };
+
+        self.filtered_block = Some(filtered_block);
+        self.send_filtered_block_if_complete()
+    }
+
+    /// Envia el bloque filtrado al nodo si ya se recibieron todas sus transacciones.
+    fn send_filtered_block_if_complete(&mut self) -> Result<(), CustomError> {
+        if !self
+            .filtered_block
+            .as_ref()
+            .is_some_and(FilteredBlock::is_complete)
+        {
+            return Ok(());
+        }
+        let Some(filtered_block) = self.filtered_block.take() else {
+            return Ok(());
+        };
+        let block_hash = filtered_block.hash();
+        self.node_action_sender.send(NodeAction::Block(
+            self.address,
+            block_hash,
+            filtered_block.into_block(),
+        ))?;
+        Ok(())
+    }
+
+    fn handle_ping(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let ping = Ping::parse(payload)?;
         let pong = Pong { nonce: ping.nonce };
-        pong.send(&mut self.stream)?;
+        self.traffic.send(&pong, &mut self.stream)?;
         Ok(())
     }
 
-    fn handle_inv(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let inv = Inv::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_pong(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let pong = Pong::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::Pong(self.address, pong.nonce))?;
+        Ok(())
+    }
 
-        for inventory in inv.inventories {
-            if inventory.inventory_type == InventoryType::Tx {
-                let message = GetData::new(vec![inventory]);
-                message.send(&mut self.stream)?;
-            }
+    fn handle_inv(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let inv = Inv::parse(payload)?;
+
+        // Las transacciones anunciadas se piden en un solo getdata, ya que la respuesta a un mempool puede anunciar miles.
+        // Se piden con sus witness, por wtxid si se anunciaron asi (BIP339) o por txid con MSG_WITNESS_TX (BIP144)
+        // En modo solo bloques no se piden, aunque el peer las anuncie a pesar del fRelay en false
+        if self.config.blocks_only {
+            return Ok(());
+        }
+        let inventories: Vec<Inventory> = inv
+            .inventories
+            .into_iter()
+            .filter_map(|inventory| match inventory.inventory_type {
+                InventoryType::Tx => Some(Inventory::new(InventoryType::WitnessTx, inventory.hash)),
+                InventoryType::Wtx => Some(inventory),
+                _ => None,
+            })
+            .collect();
+        if !inventories.is_empty() {
+            self.traffic
+                .send(&GetData::new(inventories), &mut self.stream)?;
         }
         Ok(())
     }
 
-    fn handle_tx(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let tx = Transaction::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_tx(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let tx = Transaction::parse(payload)?;
+        // Las transacciones que coincidieron en un merkleblock llegan como mensajes 'tx', pero pertenecen al bloque
+        let tx = match self.filtered_block.as_mut() {
+            Some(filtered_block) => match filtered_block.add_transaction(tx) {
+                Some(tx) => tx,
+                None => return self.send_filtered_block_if_complete(),
+            },
+            None => tx,
+        };
         self.node_action_sender
             .send(NodeAction::PendingTransaction(tx))?;
         Ok(())
     }
 
-    fn handle_notfound(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let notfound = GetData::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_notfound(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let notfound = GetData::parse(payload)?;
         let inventories = notfound.get_inventories().clone();
         self.node_action_sender
             .send(NodeAction::GetDataError(inventories))?;
@@ -179,40 +400,140 @@ impl PeerStreamLoop {
         Ok(())
     }
 
-    fn handle_sendheaders(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let _ = SendHeaders::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_sendheaders(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let _ = SendHeaders::parse(payload)?;
         self.node_action_sender
             .send(NodeAction::SendHeaders(self.address))?;
         Ok(())
     }
 
-    fn handle_getheaders(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let getheaders = GetHeaders::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_getheaders(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let getheaders = GetHeaders::parse(payload)?;
         self.node_action_sender
             .send(NodeAction::GetHeaders(self.address, getheaders))?;
         Ok(())
     }
 
-    fn handle_getdata(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
-        let getdata = GetData::read(&mut self.stream, response_header.payload_size)?;
+    fn handle_getdata(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let getdata = GetData::parse(payload)?;
         self.node_action_sender
             .send(NodeAction::GetData(self.address, getdata))?;
         Ok(())
     }
 
+    fn handle_getcfilters(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let getcfilters = GetCFilters::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::GetCFilters(self.address, getcfilters))?;
+        Ok(())
+    }
+
+    fn handle_getcfheaders(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let getcfheaders = GetCFHeaders::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::GetCFHeaders(self.address, getcfheaders))?;
+        Ok(())
+    }
+
+    fn handle_getcfcheckpt(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let getcfcheckpt = GetCFCheckpt::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::GetCFCheckpt(self.address, getcfcheckpt))?;
+        Ok(())
+    }
+
+    fn handle_addr(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let addr = Addr::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::NewAddresses(addr.addresses))?;
+        Ok(())
+    }
+
+    /// Solo se envian al nodo las direcciones IPv4 e IPv6, a las que nos podemos conectar directamente.
+    fn handle_addrv2(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let addr = AddrV2::parse(payload)?;
+        let addresses = addr
+            .addresses
+            .iter()
+            .filter_map(NetAddressV2::to_net_address)
+            .collect();
+        self.node_action_sender
+            .send(NodeAction::NewAddresses(addresses))?;
+        Ok(())
+    }
+
+    fn handle_getaddr(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let _ = GetAddr::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::GetAddr(self.address))?;
+        Ok(())
+    }
+
+    /// Registra en el log el motivo por el que el peer rechazo un mensaje nuestro.
+    fn handle_reject(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let reject = Reject::parse(payload)?;
+        let data = match reject.data {
+            Some(hash) => format!(" {hash}"),
+            None => String::new(),
+        };
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Warn,
+                self.address.into(),
+                format!(
+                    "Rejected {}{data}: {} ({})",
+                    reject.message,
+                    reject.code_name(),
+                    reject.reason
+                ),
+            ),
+        );
+        Ok(())
+    }
+
+    fn handle_mempool(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let _ = Mempool::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::Mempool(self.address))?;
+        Ok(())
+    }
+
+    fn handle_feefilter(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let feefilter = FeeFilter::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::FeeFilter(self.address, feefilter.fee_rate))?;
+        Ok(())
+    }
+
+    fn handle_filterload(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let filterload = FilterLoad::parse(payload)?;
+        self.node_action_sender.send(NodeAction::BloomFilter(
+            self.address,
+            Some(filterload.filter),
+        ))?;
+        Ok(())
+    }
+
+    fn handle_filterclear(&mut self, payload: Vec<u8>) -> Result<(), CustomError> {
+        let _ = FilterClear::parse(payload)?;
+        self.node_action_sender
+            .send(NodeAction::BloomFilter(self.address, None))?;
+        Ok(())
+    }
+
     fn ignore_message(&mut self, response_header: &MessageHeader) -> Result<(), CustomError> {
         let cmd = response_header.command.as_str();
-        if cmd != "alert" && cmd != "addr" {
+        if cmd != "alert" {
             send_log(
                 &self.logger_sender,
-                Log::Message(format!(
-                    "Received unknown command: {:?}",
-                    response_header.command
-                )),
+                Log::Peer(
+                    LogLevel::Debug,
+                    self.address.into(),
+                    format!("Received unknown command: {:?}", response_header.command),
+                ),
             );
         }
-        let mut buffer: Vec<u8> = vec![0; response_header.payload_size as usize];
-        self.stream.read_exact(&mut buffer)?;
         Ok(())
     }
 }
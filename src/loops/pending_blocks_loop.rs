@@ -1,65 +1,109 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
     thread,
     time::Duration,
 };
 
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogLevel, LogTarget},
     node_state::NodeState,
-    structs::inventory::{Inventory, InventoryType},
+    peer::Misbehavior,
+    spv::block_inventory,
+    structs::inventory::Inventory,
+    utils::get_socket_address,
 };
 
-use super::peer_action_loop::PeerAction;
+use super::{
+    node_action_loop::{NodeAction, NodeActionSender},
+    peer_action_loop::PeerAction,
+};
 
 /// pending_blocks_loop es una funcion que genera un loop que se encarga de reenviar los bloques que no fueron recibidos por los peers.
+/// Los bloques pendientes se piden a los sync_peers peers mas rapidos, si no hay peers conectados se encolan para cualquier peer.
+/// Si un peer no envia los bloques que se le pidieron, se lo penaliza y se le piden a otro peer.
 /// Los elementos son:
 /// - node_state_ref: Referencia al estado del nodo.
 /// - peer_action_sender: Sender para enviar acciones al los peers.
+/// - node_action_sender: Sender para enviar acciones al nodo.
 /// - logger_sender: Sender para enviar logs al logger.
+/// - sync_peers: Cantidad de peers mas rapidos entre los que se reparten los pedidos de bloques.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
 pub fn pending_blocks_loop(
-    node_state_ref: Arc<Mutex<NodeState>>,
+    node_state_ref: Arc<NodeState>,
     peer_action_sender: mpsc::Sender<PeerAction>,
+    node_action_sender: NodeActionSender,
     logger_sender: mpsc::Sender<Log>,
+    sync_peers: usize,
+    terminate_receiver: mpsc::Receiver<()>,
 ) -> thread::JoinHandle<Result<(), CustomError>> {
     thread::spawn(move || -> Result<(), CustomError> {
         loop {
-            thread::sleep(Duration::from_secs(5));
-            let mut node_state = node_state_ref.lock()?;
-
-            // if node_state.is_blocks_sync() {
-            //     drop(node_state);
+            if terminate_receiver.recv_timeout(Duration::from_secs(5))
+                != Err(RecvTimeoutError::Timeout)
+            {
+                break;
+            }
+            // if node_state_ref.is_blocks_sync() {
             //     continue;
             // }
 
-            let blocks_to_refetch = node_state.get_stale_requests()?;
+            for (address, block_hashes) in node_state_ref.get_stalled_requests()? {
+                send_log(
+                    &logger_sender,
+                    Log::Peer(
+                        LogLevel::Warn,
+                        get_socket_address(address),
+                        format!(
+                            "Stalled sending {} blocks, reassigning them to another peer",
+                            block_hashes.len()
+                        ),
+                    ),
+                );
+                for block_hash in block_hashes {
+                    node_state_ref.append_pending_block(block_hash)?;
+                }
+                node_action_sender.send(NodeAction::PeerMisbehaving(
+                    address,
+                    Misbehavior::StalledBlocks,
+                ))?;
+            }
+
+            // Si hay peers conectados, los bloques pendientes esperan un lugar libre en los peers mas rapidos
+            if node_state_ref
+                .schedule_block_requests(sync_peers)?
+                .is_some()
+            {
+                continue;
+            }
 
+            let blocks_to_refetch = node_state_ref.get_stale_requests()?;
             if !blocks_to_refetch.is_empty() {
                 send_log(
                     &logger_sender,
-                    Log::Message(format!(
-                        "Refetching {} pending blocks...",
-                        blocks_to_refetch.len()
-                    )),
+                    Log::Debug(
+                        LogTarget::Sync,
+                        format!("Refetching {} pending blocks...", blocks_to_refetch.len()),
+                    ),
                 );
 
                 let mut inventories = vec![];
 
                 for block_hash in &blocks_to_refetch {
-                    node_state.append_pending_block(block_hash.clone())?;
-                    inventories.push(Inventory::new(InventoryType::Block, block_hash.clone()));
+                    node_state_ref.append_pending_block(*block_hash)?;
+                    inventories.push(block_inventory(*block_hash, node_state_ref.is_spv_mode()));
                 }
-                drop(node_state);
 
                 let chunks: Vec<&[Inventory]> = inventories.chunks(5).collect();
 
                 for chunk in chunks {
                     peer_action_sender.send(PeerAction::GetData(chunk.to_vec()))?;
                 }
-            } else {
-                drop(node_state);
             }
         }
+        Ok(())
     })
 }
@@ -0,0 +1,50 @@
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log, LogLevel},
+    node_state::NodeState,
+    utils::get_socket_address,
+};
+
+/// Cada cuanto se envia un ping a los peers que respondieron el anterior.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// ping_loop es una funcion que genera un loop que envia pings periodicamente a los peers para medir su latencia.
+/// Los peers que no responden su ping dentro del ping timeout se desconectan, ya que la conexion esta muerta.
+/// Los elementos son:
+/// - node_state_ref: Referencia al estado del nodo.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - ping_timeout: Tiempo maximo que se espera la respuesta de un ping.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
+pub fn ping_loop(
+    node_state_ref: Arc<NodeState>,
+    logger_sender: mpsc::Sender<Log>,
+    ping_timeout: Duration,
+    terminate_receiver: mpsc::Receiver<()>,
+) -> thread::JoinHandle<Result<(), CustomError>> {
+    thread::spawn(move || -> Result<(), CustomError> {
+        while terminate_receiver.recv_timeout(PING_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+            let timed_out = node_state_ref.ping_peers(ping_timeout)?;
+
+            for address in timed_out {
+                send_log(
+                    &logger_sender,
+                    Log::Peer(
+                        LogLevel::Warn,
+                        get_socket_address(address),
+                        String::from("Ping timeout, disconnecting peer"),
+                    ),
+                );
+            }
+        }
+        Ok(())
+    })
+}
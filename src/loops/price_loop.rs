@@ -0,0 +1,59 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    events::GUIEvents,
+    logger::{send_log, Log, LogTarget},
+    price::{PriceCache, PriceSource},
+    utils::get_current_timestamp,
+};
+
+/// Cada cuanto se vuelve a consultar el precio fiat.
+const PRICE_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// price_loop es una funcion que genera un loop que consulta periodicamente el precio de bitcoin en fiat
+/// y lo envia a la interfaz grafica cada vez que cambia, o None cuando el ultimo precio obtenido es demasiado viejo.
+/// Solo se inicia si se habilito FIAT_DISPLAY en la configuracion.
+/// Los elementos son:
+/// - price_cache: Cache de la fuente de precios, que limita la frecuencia de las consultas.
+/// - gui_sender: Sender para enviar eventos a la interfaz grafica.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
+pub fn price_loop<S: PriceSource + 'static>(
+    mut price_cache: PriceCache<S>,
+    gui_sender: mpsc::Sender<GUIEvents>,
+    logger_sender: mpsc::Sender<Log>,
+    terminate_receiver: mpsc::Receiver<()>,
+) -> thread::JoinHandle<Result<(), CustomError>> {
+    thread::spawn(move || -> Result<(), CustomError> {
+        let mut last_price = None;
+        loop {
+            let now = get_current_timestamp()?;
+            if let Err(error) = price_cache.refresh(now) {
+                send_log(
+                    &logger_sender,
+                    Log::Warn(
+                        LogTarget::General,
+                        format!("Cannot update the fiat price: {}", error.description()),
+                    ),
+                );
+            }
+
+            let price = price_cache.get_price(now);
+            if price != last_price {
+                gui_sender.send(GUIEvents::FiatPrice(price.clone()))?;
+                last_price = price;
+            }
+
+            if terminate_receiver.recv_timeout(PRICE_REFRESH_INTERVAL)
+                != Err(RecvTimeoutError::Timeout)
+            {
+                return Ok(());
+            }
+        }
+    })
+}
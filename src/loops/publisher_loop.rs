@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    net::{Ipv6Addr, SocketAddrV6, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    structs::hash32::Hash32,
+};
+
+/// Tiempo maximo que se espera a un suscriptor antes de descartarlo.
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// PublisherEvent es una enumeracion de los eventos que se publican a los suscriptores.
+/// Los eventos son:
+/// - Block: Nuevo bloque agregado a la cadena (hash, bloque serializado).
+/// - Transaction: Nueva transaccion recibida o enviada (hash, transaccion serializada).
+/// - Terminate: Termina el loop.
+pub enum PublisherEvent {
+    Block(Hash32, Vec<u8>),
+    Transaction(Hash32, Vec<u8>),
+    Terminate,
+}
+
+/// PublisherLoop es el loop que publica los eventos de la cadena a los suscriptores conectados, al estilo de ZMQ.
+/// Cada evento genera dos notificaciones, una con el hash (`hashblock`/`hashtx`) y otra con el contenido (`rawblock`/`rawtx`).
+/// Cada notificacion esta compuesta por tres frames: topic, body y numero de secuencia del topic (u32 little endian).
+/// Cada frame se envia precedido por su largo como u32 little endian.
+/// Los elementos son:
+/// - logger_sender: Sender para enviar logs al logger
+/// - publisher_receiver: Receiver para recibir los eventos a publicar
+/// - subscribers: Suscriptores conectados
+/// - sequences: Numero de secuencia de cada topic
+pub struct PublisherLoop {
+    logger_sender: mpsc::Sender<Log>,
+    publisher_receiver: mpsc::Receiver<PublisherEvent>,
+    subscribers: Arc<Mutex<Vec<TcpStream>>>,
+    sequences: HashMap<&'static str, u32>,
+}
+
+impl PublisherLoop {
+    /// Inicializa el loop de publicacion en un thread.
+    /// Crea ademas un thread que acepta a los nuevos suscriptores.
+    /// El puerto se abre antes de crear los threads, por lo que si no se puede escuchar en el se devuelve el error.
+    pub fn spawn(
+        logger_sender: mpsc::Sender<Log>,
+        publisher_receiver: mpsc::Receiver<PublisherEvent>,
+        port: u16,
+    ) -> Result<JoinHandle<Result<(), CustomError>>, CustomError> {
+        let subscribers = Arc::new(Mutex::new(vec![]));
+        listen_subscribers(logger_sender.clone(), subscribers.clone(), port)?;
+
+        Ok(thread::spawn(move || -> Result<(), CustomError> {
+            let mut thread = Self {
+                logger_sender,
+                publisher_receiver,
+                subscribers,
+                sequences: HashMap::new(),
+            };
+            thread.event_loop()
+        }))
+    }
+
+    fn event_loop(&mut self) -> Result<(), CustomError> {
+        while let Ok(event) = self.publisher_receiver.recv() {
+            let response = match event {
+                PublisherEvent::Block(hash, raw) => self
+                    .publish("hashblock", hash.to_rpc_bytes().to_vec())
+                    .and_then(|_| self.publish("rawblock", raw)),
+                PublisherEvent::Transaction(hash, raw) => self
+                    .publish("hashtx", hash.to_rpc_bytes().to_vec())
+                    .and_then(|_| self.publish("rawtx", raw)),
+                PublisherEvent::Terminate => break,
+            };
+
+            if let Err(error) = response {
+                send_log(
+                    &self.logger_sender,
+                    Log::Message(format!("Error on PublisherLoop: {error}")),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self, topic: &'static str, body: Vec<u8>) -> Result<(), CustomError> {
+        let sequence = self.sequences.entry(topic).or_insert(0);
+        let notification = serialize_notification(topic, &body, *sequence);
+        *sequence = sequence.wrapping_add(1);
+
+        let mut subscribers = self.subscribers.lock()?;
+        subscribers.retain_mut(|subscriber| subscriber.write_all(&notification).is_ok());
+        Ok(())
+    }
+}
+
+/// Crea el thread que acepta suscriptores en el puerto recibido.
+fn listen_subscribers(
+    logger_sender: mpsc::Sender<Log>,
+    subscribers: Arc<Mutex<Vec<TcpStream>>>,
+    port: u16,
+) -> Result<(), CustomError> {
+    let address = SocketAddrV6::new(Ipv6Addr::LOCALHOST, port, 0, 0);
+    let listener = TcpListener::bind(address)?;
+    send_log(
+        &logger_sender,
+        Log::Message(format!("Publisher listening on port {}", port)),
+    );
+
+    thread::spawn(move || -> Result<(), CustomError> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    send_log(&logger_sender, Log::Error(error.into()));
+                    continue;
+                }
+            };
+            if let Err(error) = stream.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT)) {
+                send_log(&logger_sender, Log::Error(error.into()));
+                continue;
+            }
+            let peer_address = match stream.peer_addr() {
+                Ok(peer_address) => peer_address,
+                Err(error) => {
+                    send_log(&logger_sender, Log::Error(error.into()));
+                    continue;
+                }
+            };
+            send_log(
+                &logger_sender,
+                Log::Message(format!("New subscriber: {:?}", peer_address)),
+            );
+            subscribers.lock()?.push(stream);
+        }
+        Ok(())
+    });
+    Ok(())
+}
+
+/// Serializa una notificacion con sus tres frames: topic, body y numero de secuencia.
+fn serialize_notification(topic: &str, body: &[u8], sequence: u32) -> Vec<u8> {
+    let mut buffer = vec![];
+    for frame in [topic.as_bytes(), body, &sequence.to_le_bytes()] {
+        buffer.extend((frame.len() as u32).to_le_bytes());
+        buffer.extend(frame);
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publisher_serialize_notification() {
+        let notification = serialize_notification("hashtx", &[1, 2, 3], 7);
+        assert_eq!(
+            notification,
+            vec![
+                6, 0, 0, 0, b'h', b'a', b's', b'h', b't', b'x', 3, 0, 0, 0, 1, 2, 3, 4, 0, 0, 0, 7,
+                0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn publisher_sequences_per_topic() {
+        let (logger_sender, _) = mpsc::channel();
+        let (_, publisher_receiver) = mpsc::channel();
+        let mut publisher = PublisherLoop {
+            logger_sender,
+            publisher_receiver,
+            subscribers: Arc::new(Mutex::new(vec![])),
+            sequences: HashMap::new(),
+        };
+
+        publisher.publish("hashblock", vec![]).unwrap();
+        publisher.publish("hashblock", vec![]).unwrap();
+        publisher.publish("hashtx", vec![]).unwrap();
+
+        assert_eq!(publisher.sequences.get("hashblock"), Some(&2));
+        assert_eq!(publisher.sequences.get("hashtx"), Some(&1));
+    }
+}
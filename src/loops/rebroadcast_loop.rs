@@ -0,0 +1,31 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::error::CustomError;
+
+use super::node_action_loop::{NodeAction, NodeActionSender};
+
+/// Cada cuanto se reenvian a los peers las transacciones locales que todavia no se confirmaron.
+const REBROADCAST_INTERVAL: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// rebroadcast_loop es una funcion que genera un loop que le pide periodicamente al nodo que reenvie
+/// las transacciones creadas por sus wallets que todavia no se confirmaron, hasta que se incluyan en un bloque.
+/// Los elementos son:
+/// - node_action_sender: Sender para enviar acciones al nodo.
+/// - terminate_receiver: Receiver que indica que el loop debe terminar.
+pub fn rebroadcast_loop(
+    node_action_sender: NodeActionSender,
+    terminate_receiver: mpsc::Receiver<()>,
+) -> thread::JoinHandle<Result<(), CustomError>> {
+    thread::spawn(move || -> Result<(), CustomError> {
+        while terminate_receiver.recv_timeout(REBROADCAST_INTERVAL)
+            == Err(RecvTimeoutError::Timeout)
+        {
+            node_action_sender.send(NodeAction::RebroadcastTransactions)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV6, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    capture::{captured_peers, CapturedMessage},
+    error::CustomError,
+    logger::{send_log, Log},
+    message::{Message, MessageHeader},
+    messages::{
+        ping_pong::{Ping, Pong},
+        ver_ack::VerAck,
+        version::Version,
+    },
+    network::Network,
+    parser::BtcEncode,
+    services::{NODE_NETWORK, NODE_WITNESS},
+};
+
+/// Servicios que anuncian los peers reproducidos, para que el nodo les pida headers y bloques.
+const REPLAY_SERVICES: u64 = NODE_NETWORK | NODE_WITNESS;
+
+/// User agent que anuncian los peers reproducidos.
+const REPLAY_USER_AGENT: &str = "/replay:0.1.0/";
+
+/// Stream de un peer reproducido, compartido con el thread que responde sus pings.
+type ReplayStream = Arc<Mutex<TcpStream>>;
+
+/// ReplayLoop reproduce una captura de mensajes recibidos (ver Capture), haciendose pasar por los peers capturados
+/// para reproducir sin conexion el comportamiento del nodo ante esos mensajes.
+/// Escucha en un puerto local por cada peer de la captura y completa el handshake cuando el nodo se conecta.
+/// Una vez conectados todos, les envia los mensajes capturados en el orden en que se recibieron, sin esperar entre ellos.
+/// Los mensajes que envia el nodo se descartan, salvo los pings que se responden para que no desconecte a los peers.
+/// Los elementos son:
+/// - logger_sender: Sender para enviar logs al logger.
+/// - messages: Mensajes de la captura.
+/// - streams: Streams de los peers de la captura conectados al nodo, por su direccion en la captura.
+pub struct ReplayLoop {
+    logger_sender: mpsc::Sender<Log>,
+    messages: Vec<CapturedMessage>,
+    streams: HashMap<SocketAddrV6, ReplayStream>,
+}
+
+impl ReplayLoop {
+    /// Escucha en un puerto local por cada peer de la captura e inicializa el loop en un thread.
+    /// Los mensajes que se intercambian en los handshakes y pings usan el magic number de la red recibida.
+    /// Devuelve las direcciones en las que escucha, a las que debe conectarse el nodo en lugar de a la red.
+    pub fn spawn(
+        logger_sender: mpsc::Sender<Log>,
+        messages: Vec<CapturedMessage>,
+        network: Network,
+    ) -> Result<Vec<SocketAddr>, CustomError> {
+        let mut listeners = vec![];
+        let mut addresses = vec![];
+        for peer in captured_peers(&messages) {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+            addresses.push(listener.local_addr()?);
+            listeners.push((peer, listener));
+        }
+
+        thread::spawn(move || -> Result<(), CustomError> {
+            let streams = accept_peers(&logger_sender, listeners, network)?;
+            let mut thread = Self {
+                logger_sender,
+                messages,
+                streams,
+            };
+            thread.event_loop()
+        });
+        Ok(addresses)
+    }
+
+    fn event_loop(&mut self) -> Result<(), CustomError> {
+        let total_messages = self.messages.len();
+        for message in std::mem::take(&mut self.messages) {
+            let Some(stream) = self.streams.get(&message.address) else {
+                continue;
+            };
+            let mut stream = stream.lock()?;
+            if let Err(error) = stream.write_all(&message.bytes) {
+                send_log(
+                    &self.logger_sender,
+                    Log::Message(format!("Stopped replaying {}: {error}", message.address)),
+                );
+                drop(stream);
+                self.streams.remove(&message.address);
+                continue;
+            }
+            // La captura se corta a mitad de un mensaje cuando fallo su lectura, por lo que se cierra la conexion como con el peer
+            if !message.is_complete() {
+                let _ = stream.shutdown(Shutdown::Both);
+                drop(stream);
+                self.streams.remove(&message.address);
+            }
+        }
+
+        send_log(
+            &self.logger_sender,
+            Log::Message(format!("Replay finished, {total_messages} messages sent")),
+        );
+        Ok(())
+    }
+}
+
+/// Acepta la conexion del nodo en el listener de cada peer de la captura y completa el handshake.
+/// Cada peer se atiende en un thread propio, ya que el nodo puede conectarse a ellos en cualquier orden.
+/// Devuelve los streams de los peers una vez que terminaron todos los handshakes, sin los que fallaron.
+fn accept_peers(
+    logger_sender: &mpsc::Sender<Log>,
+    listeners: Vec<(SocketAddrV6, TcpListener)>,
+    network: Network,
+) -> Result<HashMap<SocketAddrV6, ReplayStream>, CustomError> {
+    let (sender, receiver) = mpsc::channel();
+    let total_peers = listeners.len();
+    for (peer, listener) in listeners {
+        let sender = sender.clone();
+        thread::spawn(move || sender.send((peer, accept_peer(&listener, network))));
+    }
+
+    let mut streams = HashMap::new();
+    for _ in 0..total_peers {
+        let (peer, stream) = receiver.recv()?;
+        match stream {
+            Ok(stream) => {
+                send_log(
+                    logger_sender,
+                    Log::Message(format!("Replaying the messages of {peer}")),
+                );
+                streams.insert(peer, stream);
+            }
+            Err(error) => send_log(
+                logger_sender,
+                Log::Message(format!("Cannot replay the messages of {peer}: {error}")),
+            ),
+        }
+    }
+    Ok(streams)
+}
+
+/// Acepta la conexion del nodo y responde su version con la nuestra y el verack.
+/// Luego inicia el thread que descarta los mensajes del nodo y responde sus pings.
+fn accept_peer(listener: &TcpListener, network: Network) -> Result<ReplayStream, CustomError> {
+    let (mut stream, _) = listener.accept()?;
+    let header = MessageHeader::read(&mut stream)?;
+    let version = Version::parse(header.read_payload(&mut stream, network)?)?;
+    Version::new(
+        version.sender_address,
+        version.receiver_address,
+        version.version,
+        REPLAY_SERVICES,
+        REPLAY_USER_AGENT,
+        true,
+    )
+    .send(&mut stream, network)?;
+    VerAck::new().send(&mut stream, network)?;
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let ping_writer = writer.clone();
+    thread::spawn(move || answer_pings(stream, ping_writer, network));
+    Ok(writer)
+}
+
+/// Lee los mensajes que envia el nodo al peer reproducido hasta que se cierra la conexion, respondiendo solo los pings.
+fn answer_pings(
+    mut stream: TcpStream,
+    writer: ReplayStream,
+    network: Network,
+) -> Result<(), CustomError> {
+    loop {
+        let header = MessageHeader::read(&mut stream)?;
+        let payload = header.read_payload(&mut stream, network)?;
+        if header.command == "ping" {
+            let nonce = Ping::parse(payload)?.nonce;
+            Pong { nonce }.send(&mut *writer.lock()?, network)?;
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv6Addr, SocketAddrV6, TcpListener, TcpStream},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, TryRecvError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    messages::block::Block,
+    node_state::NodeState,
+    parser::BtcEncode,
+    structs::{block_header::BlockHeader, hash32::Hash32},
+    utils::bytes_to_hex,
+};
+
+/// Cantidad maxima de headers que se pueden solicitar en una sola consulta.
+const MAX_REST_HEADERS: usize = 2000;
+/// Tiempo maximo que se espera a que un cliente envie su consulta o lea cada parte de la respuesta.
+const REST_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Tamaño maximo en bytes de la linea de la consulta junto a sus headers.
+const MAX_REST_HEAD_SIZE: u64 = 16 * 1024;
+/// Cantidad maxima de consultas que se atienden a la vez, cada una en su propio thread.
+const MAX_REST_CONNECTIONS: usize = 16;
+
+/// RestFormat representa los formatos de respuesta que soporta la API REST.
+#[derive(Debug, PartialEq)]
+pub enum RestFormat {
+    Json,
+    Hex,
+    Bin,
+}
+
+/// RestRequest representa las consultas que soporta la API REST.
+/// - Block: `/rest/block/<hash>.<formato>`
+/// - BlockByHeight: `/rest/blockheight/<altura>.<formato>`
+/// - Headers: `/rest/headers/<cantidad>/<hash>.<formato>`
+/// - Utxo: `/rest/utxo/<direccion>.json`
+/// - MempoolInfo: `/rest/mempool/info.json`
+#[derive(Debug, PartialEq)]
+pub enum RestRequest {
+    Block(Hash32, RestFormat),
+    BlockByHeight(u32, RestFormat),
+    Headers(usize, Hash32, RestFormat),
+    Utxo(String),
+    MempoolInfo,
+}
+
+/// RestResponse representa la respuesta HTTP que se le envia al cliente.
+struct RestResponse {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl RestResponse {
+    fn new(format: &RestFormat, body: Vec<u8>) -> Self {
+        let (content_type, body) = match format {
+            RestFormat::Json => ("application/json", body),
+            RestFormat::Hex => ("text/plain", bytes_to_hex(&body).into_bytes()),
+            RestFormat::Bin => ("application/octet-stream", body),
+        };
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: status.as_bytes().to_vec(),
+        }
+    }
+
+    fn send(&self, stream: &mut TcpStream) -> Result<(), CustomError> {
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        Ok(())
+    }
+}
+
+/// RestApiLoop es el loop que atiende la API REST de solo lectura del nodo.
+/// Permite consultar bloques, headers y UTXO sin necesidad de interactuar con la GUI.
+/// Los elementos son:
+/// - logger_sender: Sender para enviar logs al logger
+/// - node_state_ref: Referencia al estado del nodo
+/// - port: Puerto donde se escuchan las consultas
+/// - terminate_receiver: Receiver que indica que el loop debe terminar, se verifica con cada conexion entrante
+/// - active_connections: Cantidad de consultas que se estan atendiendo, compartida con sus threads
+pub struct RestApiLoop {
+    logger_sender: mpsc::Sender<Log>,
+    node_state_ref: Arc<NodeState>,
+    port: u16,
+    terminate_receiver: mpsc::Receiver<()>,
+    active_connections: Arc<AtomicUsize>,
+}
+
+impl RestApiLoop {
+    #[must_use]
+    /// Inicializa el loop de la API REST en un thread.
+    /// Devuelve el thread junto al sender para indicarle que termine.
+    pub fn spawn(
+        logger_sender: mpsc::Sender<Log>,
+        node_state_ref: Arc<NodeState>,
+        port: u16,
+    ) -> (JoinHandle<Result<(), CustomError>>, mpsc::Sender<()>) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        let thread = thread::spawn(move || -> Result<(), CustomError> {
+            let mut thread = Self {
+                logger_sender,
+                node_state_ref,
+                port,
+                terminate_receiver,
+                active_connections: Arc::new(AtomicUsize::new(0)),
+            };
+            thread.event_loop()
+        });
+        (thread, terminate_sender)
+    }
+
+    fn event_loop(&mut self) -> Result<(), CustomError> {
+        let address = SocketAddrV6::new(Ipv6Addr::LOCALHOST, self.port, 0, 0);
+        let listener = TcpListener::bind(address)?;
+        send_log(
+            &self.logger_sender,
+            Log::Message(format!("REST API listening on port {}", self.port)),
+        );
+
+        for stream in listener.incoming() {
+            if self.terminate_receiver.try_recv() != Err(TryRecvError::Empty) {
+                break;
+            }
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    send_log(&self.logger_sender, Log::Error(error.into()));
+                    continue;
+                }
+            };
+            // Si ya se atienden MAX_REST_CONNECTIONS consultas se rechaza la conexion, sin iniciar otro thread
+            if self.active_connections.fetch_add(1, Ordering::SeqCst) >= MAX_REST_CONNECTIONS {
+                self.active_connections.fetch_sub(1, Ordering::SeqCst);
+                if stream
+                    .set_write_timeout(Some(REST_CONNECTION_TIMEOUT))
+                    .is_ok()
+                {
+                    RestResponse::error("503 Service Unavailable")
+                        .send(&mut stream)
+                        .ok();
+                }
+                continue;
+            }
+            let logger_sender = self.logger_sender.clone();
+            let node_state_ref = self.node_state_ref.clone();
+            let active_connections = self.active_connections.clone();
+            thread::spawn(move || {
+                if let Err(error) = Self::handle_connection(&node_state_ref, stream) {
+                    send_log(&logger_sender, Log::Error(error));
+                }
+                active_connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Atiende una consulta en su propio thread, para que un cliente lento no demore a los demas.
+    /// Si el cliente no envia la consulta o no lee la respuesta en REST_CONNECTION_TIMEOUT, se cierra la conexion.
+    /// Si la linea de la consulta y sus headers superan MAX_REST_HEAD_SIZE bytes, se rechaza sin leer el resto.
+    fn handle_connection(node_state: &NodeState, mut stream: TcpStream) -> Result<(), CustomError> {
+        stream.set_read_timeout(Some(REST_CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(REST_CONNECTION_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let Some(request_line) = read_request_head(&mut reader)? else {
+            return RestResponse::error("431 Request Header Fields Too Large").send(&mut stream);
+        };
+
+        let response = match parse_request_line(&request_line) {
+            Some(request) => Self::respond(node_state, request)?,
+            None => RestResponse::error("400 Bad Request"),
+        };
+        response.send(&mut stream)
+    }
+
+    fn respond(node_state: &NodeState, request: RestRequest) -> Result<RestResponse, CustomError> {
+        let response = match request {
+            RestRequest::Block(hash, format) => {
+                let header = match node_state.get_headers_from(&hash, 1)? {
+                    Some(headers) if headers[0].1.block_downloaded => headers[0].1.clone(),
+                    _ => return Ok(RestResponse::error("404 Not Found")),
+                };
+                block_response(&node_state.get_block(header.hash())?, format)
+            }
+            RestRequest::BlockByHeight(height, format) => {
+                match node_state.get_block_by_height(height)? {
+                    Some(block) => block_response(&block, format),
+                    None => return Ok(RestResponse::error("404 Not Found")),
+                }
+            }
+            RestRequest::Headers(count, hash, format) => {
+                let headers = match node_state.get_headers_from(&hash, count)? {
+                    Some(headers) => headers,
+                    None => return Ok(RestResponse::error("404 Not Found")),
+                };
+                let body = match format {
+                    RestFormat::Json => {
+                        let headers: Vec<String> = headers
+                            .iter()
+                            .map(|(height, header)| header_as_json(*height, header))
+                            .collect();
+                        format!("[{}]", headers.join(",")).into_bytes()
+                    }
+                    _ => headers
+                        .iter()
+                        .flat_map(|(_, header)| header.serialize())
+                        .collect(),
+                };
+                RestResponse::new(&format, body)
+            }
+            RestRequest::Utxo(address) => {
+                let utxo = match node_state.get_address_utxo(address) {
+                    Ok(utxo) => utxo,
+                    Err(_) => return Ok(RestResponse::error("400 Bad Request")),
+                };
+                let utxo: Vec<String> = utxo
+                    .iter()
+                    .map(|(out_point, value)| {
+                        format!(
+                            "{{\"txid\":\"{}\",\"vout\":{},\"value\":{},\"blockhash\":\"{}\",\"time\":{}}}",
+                            out_point.hash,
+                            out_point.index,
+                            value.tx_out.value,
+                            value.block_hash,
+                            value.block_timestamp
+                        )
+                    })
+                    .collect();
+                RestResponse::new(
+                    &RestFormat::Json,
+                    format!("[{}]", utxo.join(",")).into_bytes(),
+                )
+            }
+            RestRequest::MempoolInfo => {
+                let (size, bytes) = node_state.get_mempool_info()?;
+                RestResponse::new(
+                    &RestFormat::Json,
+                    format!("{{\"size\":{size},\"bytes\":{bytes}}}").into_bytes(),
+                )
+            }
+        };
+
+        Ok(response)
+    }
+}
+
+/// Lee la linea de la consulta y sus headers, que se descartan, hasta la linea vacia que termina la consulta.
+/// Devuelve la linea de la consulta, o None si junto a los headers supera MAX_REST_HEAD_SIZE bytes.
+fn read_request_head(reader: &mut impl BufRead) -> Result<Option<String>, CustomError> {
+    let mut head = reader.take(MAX_REST_HEAD_SIZE);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+
+    let mut line = String::new();
+    while head.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    if head.limit() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(request_line))
+}
+
+/// Devuelve la respuesta con el bloque en el formato recibido.
+fn block_response(block: &Block, format: RestFormat) -> RestResponse {
+    let body = match format {
+        RestFormat::Json => block_as_json(block).into_bytes(),
+        _ => block.serialize(),
+    };
+    RestResponse::new(&format, body)
+}
+
+/// Parsea la primera linea de una consulta HTTP y devuelve la consulta a la API REST.
+/// Devuelve None si la consulta no es valida.
+pub fn parse_request_line(request_line: &str) -> Option<RestRequest> {
+    let mut parts = request_line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let path = parts.next()?.strip_prefix("/rest/")?;
+    let (path, format) = match path.rsplit_once('.')? {
+        (path, "json") => (path, RestFormat::Json),
+        (path, "hex") => (path, RestFormat::Hex),
+        (path, "bin") => (path, RestFormat::Bin),
+        _ => return None,
+    };
+
+    let segments: Vec<&str> = path.split('/').collect();
+    match segments.as_slice() {
+        ["block", hash] => Some(RestRequest::Block(Hash32::from_str(hash).ok()?, format)),
+        ["blockheight", height] => Some(RestRequest::BlockByHeight(height.parse().ok()?, format)),
+        ["headers", count, hash] => {
+            let count = count.parse::<usize>().ok()?;
+            if count == 0 || count > MAX_REST_HEADERS {
+                return None;
+            }
+            Some(RestRequest::Headers(
+                count,
+                Hash32::from_str(hash).ok()?,
+                format,
+            ))
+        }
+        ["utxo", address] if format == RestFormat::Json => {
+            Some(RestRequest::Utxo(address.to_string()))
+        }
+        ["mempool", "info"] if format == RestFormat::Json => Some(RestRequest::MempoolInfo),
+        _ => None,
+    }
+}
+
+fn header_as_json(height: usize, header: &BlockHeader) -> String {
+    format!(
+        "{{\"hash\":\"{}\",\"height\":{},\"version\":{},\"previousblockhash\":\"{}\",\"merkleroot\":\"{}\",\"time\":{},\"bits\":{},\"nonce\":{}}}",
+        header.hash(),
+        height,
+        header.version,
+        header.prev_block_hash,
+        header.merkle_root,
+        header.timestamp,
+        header.bits,
+        header.nonce
+    )
+}
+
+pub fn block_as_json(block: &Block) -> String {
+    let txids: Vec<String> = block
+        .transactions
+        .iter()
+        .map(|tx| format!("\"{}\"", tx.hash()))
+        .collect();
+    format!(
+        "{{\"hash\":\"{}\",\"version\":{},\"previousblockhash\":\"{}\",\"merkleroot\":\"{}\",\"time\":{},\"bits\":{},\"nonce\":{},\"tx\":[{}]}}",
+        block.header.hash(),
+        block.header.version,
+        block.header.prev_block_hash,
+        block.header.merkle_root,
+        block.header.timestamp,
+        block.header.bits,
+        block.header.nonce,
+        txids.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const HASH: &str = "000000000000000ba6f3ca57e4c5f95c6a7c2bb2e3b0c0d2c5e2b3b1a4c8d9e0";
+
+    #[test]
+    fn rest_read_request_heads_up_to_the_maximum_size() {
+        let mut request =
+            Cursor::new("GET /rest/mempool/info.json HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(
+            read_request_head(&mut request).unwrap(),
+            Some(String::from("GET /rest/mempool/info.json HTTP/1.1\r\n"))
+        );
+
+        let mut request = Cursor::new(format!(
+            "GET /rest/{}.json HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_REST_HEAD_SIZE as usize)
+        ));
+        assert_eq!(read_request_head(&mut request).unwrap(), None);
+    }
+
+    #[test]
+    fn rest_parse_block_request() {
+        let request = parse_request_line(&format!("GET /rest/block/{}.json HTTP/1.1\r\n", HASH));
+        let expected_hash = Hash32::from_str(HASH).unwrap();
+        assert_eq!(expected_hash.as_bytes()[31], 0);
+        assert_eq!(expected_hash.as_bytes()[0], 0xe0);
+        assert_eq!(
+            request,
+            Some(RestRequest::Block(expected_hash, RestFormat::Json))
+        );
+        assert_eq!(expected_hash.to_string(), HASH);
+    }
+
+    #[test]
+    fn rest_parse_block_by_height_request() {
+        let request = parse_request_line("GET /rest/blockheight/120.bin HTTP/1.1");
+        assert_eq!(
+            request,
+            Some(RestRequest::BlockByHeight(120, RestFormat::Bin))
+        );
+        assert!(parse_request_line("GET /rest/blockheight/-1.bin HTTP/1.1").is_none());
+    }
+
+    #[test]
+    fn rest_parse_headers_request() {
+        let request = parse_request_line(&format!("GET /rest/headers/5/{}.hex HTTP/1.1", HASH));
+        assert_eq!(
+            request,
+            Some(RestRequest::Headers(
+                5,
+                Hash32::from_str(HASH).unwrap(),
+                RestFormat::Hex
+            ))
+        );
+        assert!(
+            parse_request_line(&format!("GET /rest/headers/0/{}.bin HTTP/1.1", HASH)).is_none()
+        );
+        assert!(
+            parse_request_line(&format!("GET /rest/headers/x/{}.bin HTTP/1.1", HASH)).is_none()
+        );
+    }
+
+    #[test]
+    fn rest_parse_utxo_request() {
+        let request =
+            parse_request_line("GET /rest/utxo/mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm.json HTTP/1.1");
+        assert_eq!(
+            request,
+            Some(RestRequest::Utxo(
+                "mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm".to_string()
+            ))
+        );
+        assert!(parse_request_line(
+            "GET /rest/utxo/mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm.bin HTTP/1.1"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn rest_parse_mempool_info_request() {
+        assert_eq!(
+            parse_request_line("GET /rest/mempool/info.json HTTP/1.1"),
+            Some(RestRequest::MempoolInfo)
+        );
+        assert!(parse_request_line("GET /rest/mempool/info.hex HTTP/1.1").is_none());
+    }
+
+    #[test]
+    fn rest_parse_invalid_requests() {
+        assert!(parse_request_line("POST /rest/block/00.json HTTP/1.1").is_none());
+        assert!(parse_request_line("GET /rest/block/00.json HTTP/1.1").is_none());
+        assert!(parse_request_line(&format!("GET /rest/block/{}.xml HTTP/1.1", HASH)).is_none());
+        assert!(parse_request_line(&format!("GET /rest/tx/{}.json HTTP/1.1", HASH)).is_none());
+        assert!(parse_request_line("").is_none());
+    }
+}
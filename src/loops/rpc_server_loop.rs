@@ -0,0 +1,423 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    iter::Peekable,
+    net::{Ipv6Addr, SocketAddrV6, TcpListener, TcpStream},
+    str::Chars,
+    sync::mpsc::{self, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log},
+    rpc::{RpcCommand, RpcHandler},
+};
+
+/// Tamaño maximo en bytes del cuerpo de una consulta, suficiente para enviar un bloque serializado en hexa.
+const MAX_RPC_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// Tamaño maximo en bytes de la linea de la consulta junto a sus headers.
+const MAX_RPC_HEAD_SIZE: u64 = 16 * 1024;
+/// Tiempo maximo que se espera a que un cliente envie su consulta o lea cada parte de la respuesta.
+const RPC_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Codigo de error JSON-RPC de los comandos que no se pudieron ejecutar.
+const RPC_MISC_ERROR: i32 = -1;
+/// Codigo de error JSON-RPC de las consultas que no se pudieron parsear.
+const RPC_PARSE_ERROR: i32 = -32700;
+
+/// RpcRequest es una consulta JSON-RPC 1.0, como las que envian bitcoin-cli y los mineros:
+/// - method: Nombre del comando.
+/// - params: Parametros del comando, los strings sin comillas y el resto tal como se escribieron.
+/// - id: Identificador de la consulta tal como se escribio, que se devuelve en la respuesta.
+#[derive(Debug, PartialEq)]
+pub struct RpcRequest {
+    pub method: String,
+    pub params: Vec<String>,
+    pub id: String,
+}
+
+/// RpcServerLoop es el loop que atiende las consultas JSON-RPC del nodo sobre HTTP, en localhost.
+/// Ejecuta los comandos de la capa RPC, por ejemplo para que un minero externo pida un template de bloque.
+/// Los elementos son:
+/// - logger_sender: Sender para enviar logs al logger
+/// - rpc_handler: Capa de comandos RPC que ejecuta las consultas
+/// - port: Puerto donde se escuchan las consultas
+/// - terminate_receiver: Receiver que indica que el loop debe terminar, se verifica con cada conexion entrante
+pub struct RpcServerLoop {
+    logger_sender: mpsc::Sender<Log>,
+    rpc_handler: RpcHandler,
+    port: u16,
+    terminate_receiver: mpsc::Receiver<()>,
+}
+
+impl RpcServerLoop {
+    #[must_use]
+    /// Inicializa el loop del servidor JSON-RPC en un thread.
+    /// Devuelve el thread junto al sender para indicarle que termine.
+    pub fn spawn(
+        logger_sender: mpsc::Sender<Log>,
+        rpc_handler: RpcHandler,
+        port: u16,
+    ) -> (JoinHandle<Result<(), CustomError>>, mpsc::Sender<()>) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        let thread = thread::spawn(move || -> Result<(), CustomError> {
+            let mut thread = Self {
+                logger_sender,
+                rpc_handler,
+                port,
+                terminate_receiver,
+            };
+            thread.event_loop()
+        });
+        (thread, terminate_sender)
+    }
+
+    fn event_loop(&mut self) -> Result<(), CustomError> {
+        let address = SocketAddrV6::new(Ipv6Addr::LOCALHOST, self.port, 0, 0);
+        let listener = TcpListener::bind(address)?;
+        send_log(
+            &self.logger_sender,
+            Log::Message(format!("JSON-RPC server listening on port {}", self.port)),
+        );
+
+        for stream in listener.incoming() {
+            if self.terminate_receiver.try_recv() != Err(TryRecvError::Empty) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    send_log(&self.logger_sender, Log::Error(error.into()));
+                    continue;
+                }
+            };
+            let logger_sender = self.logger_sender.clone();
+            let rpc_handler = self.rpc_handler.clone();
+            thread::spawn(move || {
+                if let Err(error) = Self::handle_connection(&rpc_handler, stream) {
+                    send_log(&logger_sender, Log::Error(error));
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Atiende una consulta en su propio thread, para que un cliente lento no demore a los demas.
+    /// Si el cliente no envia la consulta o no lee la respuesta en RPC_CONNECTION_TIMEOUT, se cierra la conexion.
+    /// Si la linea de la consulta y sus headers superan MAX_RPC_HEAD_SIZE bytes, se rechaza sin leer el resto.
+    fn handle_connection(
+        rpc_handler: &RpcHandler,
+        mut stream: TcpStream,
+    ) -> Result<(), CustomError> {
+        stream.set_read_timeout(Some(RPC_CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(RPC_CONNECTION_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let Some((request_line, content_length)) = read_request_head(&mut reader)? else {
+            return send_response(&mut stream, "431 Request Header Fields Too Large", "");
+        };
+
+        if !request_line.starts_with("POST ") {
+            return send_response(&mut stream, "405 Method Not Allowed", "");
+        }
+        if content_length > MAX_RPC_BODY_SIZE {
+            return send_response(&mut stream, "413 Payload Too Large", "");
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+
+        let response = match parse_rpc_request(&String::from_utf8_lossy(&body)) {
+            Some(request) => Self::respond(rpc_handler, request),
+            None => error_response(RPC_PARSE_ERROR, "Parse error", "null"),
+        };
+        send_response(&mut stream, "200 OK", &response)
+    }
+
+    /// Ejecuta la consulta y devuelve la respuesta JSON-RPC, con el resultado o el error del comando.
+    fn respond(rpc_handler: &RpcHandler, request: RpcRequest) -> String {
+        let params: Vec<&str> = request.params.iter().map(String::as_str).collect();
+        let result = RpcCommand::from_method(&request.method, &params).and_then(|command| {
+            // la ayuda es texto plano, por lo que se envia como un string JSON
+            let is_help = matches!(command, RpcCommand::Help);
+            let result = rpc_handler.execute(command)?;
+            Ok(match is_help {
+                true => json_string(&result),
+                false => result,
+            })
+        });
+        match result {
+            Ok(result) => format!(
+                "{{\"result\":{},\"error\":null,\"id\":{}}}",
+                result, request.id
+            ),
+            Err(CustomError::Validation(explanation)) => {
+                error_response(RPC_MISC_ERROR, &explanation, &request.id)
+            }
+            Err(error) => error_response(RPC_MISC_ERROR, &error.to_string(), &request.id),
+        }
+    }
+}
+
+/// Lee la linea de la consulta y sus headers, hasta la linea vacia que los separa del cuerpo.
+/// Devuelve la linea de la consulta y el Content-Length, o None si superan MAX_RPC_HEAD_SIZE bytes.
+fn read_request_head(reader: &mut impl BufRead) -> Result<Option<(String, usize)>, CustomError> {
+    let mut head = reader.take(MAX_RPC_HEAD_SIZE);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut line = String::new();
+    while head.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        line.clear();
+    }
+    if head.limit() == 0 {
+        return Ok(None);
+    }
+    Ok(Some((request_line, content_length)))
+}
+
+fn error_response(code: i32, message: &str, id: &str) -> String {
+    format!(
+        "{{\"result\":null,\"error\":{{\"code\":{},\"message\":{}}},\"id\":{}}}",
+        code,
+        json_string(message),
+        id
+    )
+}
+
+fn send_response(stream: &mut TcpStream, status: &str, body: &str) -> Result<(), CustomError> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    Ok(())
+}
+
+/// Escribe el texto como un string JSON, escapando las comillas, las barras y los caracteres de control.
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Parsea el cuerpo de una consulta JSON-RPC: un objeto con el string method, el array params, que puede faltar,
+/// y el id, que si falta es null. Devuelve None si no es un objeto JSON valido o no tiene method.
+/// Los parametros que son objetos o arrays se ignoran, ya que ningun comando los usa.
+pub fn parse_rpc_request(body: &str) -> Option<RpcRequest> {
+    let mut chars = body.trim().chars().peekable();
+    let mut method = None;
+    let mut params = vec![];
+    let mut id = String::from("null");
+
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        return None;
+    }
+    loop {
+        let key = parse_string(&mut chars)?;
+        expect(&mut chars, ':')?;
+        skip_whitespace(&mut chars);
+        match key.as_str() {
+            "method" => method = Some(parse_string(&mut chars)?),
+            "params" => params = parse_params(&mut chars)?,
+            "id" => id = parse_scalar(&mut chars)?,
+            _ => {
+                parse_value(&mut chars)?;
+            }
+        }
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            ',' => skip_whitespace(&mut chars),
+            '}' => break,
+            _ => return None,
+        }
+    }
+
+    Some(RpcRequest {
+        method: method?,
+        params,
+        id,
+    })
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Option<()> {
+    skip_whitespace(chars);
+    (chars.next()? == expected).then_some(())
+}
+
+/// Parsea un string JSON, devolviendo su contenido sin comillas y con los escapes resueltos.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    expect(chars, '"')?;
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                'u' => {
+                    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Parsea un numero, true, false o null, o un string que se devuelve con sus comillas.
+fn parse_scalar(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.peek() == Some(&'"') {
+        return parse_string(chars).map(|value| json_string(&value));
+    }
+    let mut value = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || "+-.".contains(*c)) {
+        value.push(c);
+    }
+    (!value.is_empty()).then_some(value)
+}
+
+/// Parsea el array de parametros, los strings sin comillas y los escalares tal como se escribieron.
+fn parse_params(chars: &mut Peekable<Chars>) -> Option<Vec<String>> {
+    if chars.peek() != Some(&'[') {
+        parse_value(chars)?;
+        return Some(vec![]);
+    }
+    chars.next();
+    let mut params = vec![];
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Some(params);
+    }
+    loop {
+        skip_whitespace(chars);
+        match chars.peek()? {
+            '"' => params.push(parse_string(chars)?),
+            '[' | '{' => parse_value(chars)?,
+            _ => params.push(parse_scalar(chars)?),
+        }
+        skip_whitespace(chars);
+        match chars.next()? {
+            ',' => continue,
+            ']' => return Some(params),
+            _ => return None,
+        }
+    }
+}
+
+/// Saltea un valor JSON de cualquier tipo.
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<()> {
+    skip_whitespace(chars);
+    match chars.peek()? {
+        '"' => parse_string(chars).map(|_| ()),
+        open @ ('[' | '{') => {
+            let close = if *open == '[' { ']' } else { '}' };
+            chars.next();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&close).is_some() {
+                return Some(());
+            }
+            loop {
+                if close == '}' {
+                    parse_string(chars)?;
+                    expect(chars, ':')?;
+                }
+                parse_value(chars)?;
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    c if c == close => return Some(()),
+                    _ => return None,
+                }
+            }
+        }
+        _ => parse_scalar(chars).map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn parse_json_rpc_requests() {
+        assert_eq!(
+            parse_rpc_request(
+                r#"{"jsonrpc": "1.0", "id": "miner", "method": "getblock", "params": ["00ab", 2, true]}"#
+            ),
+            Some(RpcRequest {
+                method: String::from("getblock"),
+                params: vec![
+                    String::from("00ab"),
+                    String::from("2"),
+                    String::from("true")
+                ],
+                id: String::from("\"miner\""),
+            })
+        );
+        assert_eq!(
+            parse_rpc_request(r#"{"method":"getblocktemplate","params":[{"rules":["segwit"]}]}"#),
+            Some(RpcRequest {
+                method: String::from("getblocktemplate"),
+                params: vec![],
+                id: String::from("null"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_json_rpc_requests() {
+        assert_eq!(parse_rpc_request(""), None);
+        assert_eq!(parse_rpc_request("{}"), None);
+        assert_eq!(parse_rpc_request(r#"{"params":[]}"#), None);
+        assert_eq!(parse_rpc_request(r#"{"method":"getblockcount""#), None);
+        assert_eq!(parse_rpc_request(r#"{"method":1}"#), None);
+    }
+
+    #[test]
+    fn read_request_heads_up_to_the_maximum_size() {
+        let mut request = Cursor::new(
+            "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 42\r\n\r\n{}",
+        );
+        assert_eq!(
+            read_request_head(&mut request).unwrap(),
+            Some((String::from("POST / HTTP/1.1\r\n"), 42))
+        );
+
+        let long_header = format!("X-Padding: {}\r\n", "a".repeat(MAX_RPC_HEAD_SIZE as usize));
+        let mut request = Cursor::new(format!("POST / HTTP/1.1\r\n{long_header}\r\n"));
+        assert_eq!(read_request_head(&mut request).unwrap(), None);
+    }
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(json_string("a \"b\"\\\n"), r#""a \"b\"\\\n""#);
+    }
+}
@@ -1,95 +1,162 @@
 use std::{
-    net::{SocketAddrV6, TcpListener},
-    sync::{mpsc, Arc, Mutex},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc,
+    },
     thread::{self, JoinHandle},
 };
 
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogLevel},
     node_state::NodeState,
     peer::Peer,
+    utils::{get_address_v6, get_socket_address},
 };
 
-use super::{node_action_loop::NodeAction, peer_action_loop::PeerAction};
+/// Funcion que atiende a un peer que se conecto al nodo y realiza el handshake, con los datos del nodo ya capturados.
+pub type PeerAnswerer = Box<dyn Fn(TcpStream) -> Result<Peer, CustomError> + Send>;
 
 /// TcpListenerLoop es el loop de eventos que se encarga de escuchar conexiones entrantes.
 /// Cada vez que se recibe una conexión, inicializa un nuevo Peer y contesta el handshake.
 /// Luego, agrega el nuevo Peer a la lista de peers del nodo
+/// Si ya se alcanzo la cantidad maxima de peers entrantes, se desaloja al peer entrante menos util
+/// para hacerle lugar, o se rechaza la conexion si todos son utiles.
 /// Los elementos son:
 /// - logger_sender: Sender para enviar logs al logger
 /// - node_state_ref: Referencia al estado del nodo
-/// - address: Dirección del nodo
-/// - services: Servicios que ofrece el nodo
-/// - version: Versión del protocolo que maneja el nodo
-/// - peer_action_receiver: Receiver para recibir acciones de los peers
-/// - node_action_sender: Sender para enviar acciones al nodo
+/// - address: Dirección en la que escucha el loop
+/// - answerer: Funcion para atender a un peer que se conecto al nodo
+/// - max_inbound: Cantidad maxima de peers entrantes
+/// - terminate_receiver: Receiver que indica que el loop debe terminar, se verifica con cada conexion entrante
 pub struct TcpListenerLoop {
     logger_sender: mpsc::Sender<Log>,
-    node_state_ref: Arc<Mutex<NodeState>>,
-    address: SocketAddrV6,
-    services: u64,
-    version: i32,
-    peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-    node_action_sender: mpsc::Sender<NodeAction>,
+    node_state_ref: Arc<NodeState>,
+    address: SocketAddr,
+    answerer: PeerAnswerer,
+    max_inbound: usize,
+    terminate_receiver: mpsc::Receiver<()>,
 }
 
 impl TcpListenerLoop {
     #[must_use]
     /// Inicializa el loop de eventos en un thread.
+    /// Devuelve el thread junto al sender para indicarle que termine.
     pub fn spawn(
         logger_sender: mpsc::Sender<Log>,
-        node_state_ref: Arc<Mutex<NodeState>>,
-        address: SocketAddrV6,
-        services: u64,
-        version: i32,
-        peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-        node_action_sender: mpsc::Sender<NodeAction>,
-    ) -> JoinHandle<Result<(), CustomError>> {
-        thread::spawn(move || -> Result<(), CustomError> {
+        node_state_ref: Arc<NodeState>,
+        address: SocketAddr,
+        answerer: PeerAnswerer,
+        max_inbound: usize,
+    ) -> (JoinHandle<Result<(), CustomError>>, mpsc::Sender<()>) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        let thread = thread::spawn(move || -> Result<(), CustomError> {
             let mut thread = Self {
                 logger_sender,
                 node_state_ref,
                 address,
-                services,
-                version,
-                peer_action_receiver,
-                node_action_sender,
+                answerer,
+                max_inbound,
+                terminate_receiver,
             };
             thread.event_loop()
-        })
+        });
+        (thread, terminate_sender)
     }
 
     fn event_loop(&mut self) -> Result<(), CustomError> {
-        let listener = TcpListener::bind(self.address)?;
+        let listener = match TcpListener::bind(self.address) {
+            Ok(listener) => listener,
+            Err(error) => {
+                send_log(
+                    &self.logger_sender,
+                    Log::Message(format!("Cannot listen on {}: {}", self.address, error)),
+                );
+                return Err(error.into());
+            }
+        };
         send_log(
             &self.logger_sender,
-            Log::Message(String::from("Server started...")),
+            Log::Message(format!("Server started on {}...", self.address)),
         );
 
         for stream in listener.incoming() {
+            if self.terminate_receiver.try_recv() != Err(TryRecvError::Empty) {
+                break;
+            }
             let stream = stream?;
             let peer_address = stream.peer_addr()?;
+            if self
+                .node_state_ref
+                .is_banned(get_address_v6(peer_address))?
+            {
+                send_log(
+                    &self.logger_sender,
+                    Log::Peer(
+                        LogLevel::Debug,
+                        peer_address,
+                        String::from("Rejected connection from banned or blacklisted peer"),
+                    ),
+                );
+                continue;
+            }
+            if !self.free_inbound_slot(peer_address)? {
+                send_log(
+                    &self.logger_sender,
+                    Log::Peer(
+                        LogLevel::Info,
+                        peer_address,
+                        String::from("Rejected connection, all inbound slots are in use"),
+                    ),
+                );
+                continue;
+            }
             send_log(
                 &self.logger_sender,
-                Log::Message(format!("New connection: {:?}", peer_address)),
+                Log::Peer(LogLevel::Info, peer_address, String::from("New connection")),
             );
 
-            let new_peer = Peer::answer(
-                stream,
-                self.address,
-                self.services,
-                self.version,
-                self.peer_action_receiver.clone(),
-                self.logger_sender.clone(),
-                self.node_action_sender.clone(),
-            )?;
-
-            let mut node_state = self.node_state_ref.lock()?;
-            node_state.append_peers(vec![new_peer]);
-            drop(node_state);
+            match (self.answerer)(stream) {
+                Ok(new_peer) => self.node_state_ref.append_peers(vec![new_peer])?,
+                Err(error) => send_log(
+                    &self.logger_sender,
+                    Log::Peer(
+                        LogLevel::Warn,
+                        peer_address,
+                        format!("Connection dropped during handshake: {error}"),
+                    ),
+                ),
+            }
         }
 
         Ok(())
     }
+
+    /// Verifica que haya lugar para un nuevo peer entrante, los peers de la whitelist siempre se aceptan.
+    /// Si se alcanzo max_inbound, desaloja al peer entrante menos util.
+    /// Devuelve false si no se pudo liberar un lugar.
+    fn free_inbound_slot(&self, peer_address: SocketAddr) -> Result<bool, CustomError> {
+        if self
+            .node_state_ref
+            .is_whitelisted(get_address_v6(peer_address).ip())?
+        {
+            return Ok(true);
+        }
+        if self.node_state_ref.count_inbound_peers()? < self.max_inbound {
+            return Ok(true);
+        }
+        let Some(evicted) = self.node_state_ref.evict_inbound_peer()? else {
+            return Ok(false);
+        };
+        send_log(
+            &self.logger_sender,
+            Log::Peer(
+                LogLevel::Info,
+                get_socket_address(evicted),
+                String::from("Evicted to free an inbound slot"),
+            ),
+        );
+        Ok(true)
+    }
 }
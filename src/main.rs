@@ -1,30 +1,248 @@
 use bitcoin::{
+    capture::{captured_peers, read_capture, CapturedMessage},
     config::Config,
-    gui::init::GUI,
+    events::GUIEvents,
     logger::{send_log, Log, Logger},
-    loops::node_action_loop::NodeAction,
+    loops::{node_action_loop::NodeAction, replay_loop::ReplayLoop},
     node::Node,
     node_state::NodeState,
-    utils::get_addresses,
+    states::{
+        chain_export::export_chain,
+        core_import::import_core_blocks,
+        utxo_snapshot::{dump_utxo_snapshot, load_utxo_snapshot},
+    },
+    tui::Tui,
+    utils::{get_literal_seed_addresses, get_seed_addresses},
 };
+#[cfg(feature = "gui")]
+use bitcoin::{error::CustomError, gui::init::GUI, loops::node_action_loop::NodeActionSender};
+use clap::Parser;
+#[cfg(feature = "gui")]
 use gtk::glib::{self, Priority};
-use std::{env, path::Path};
+use std::{path::Path, sync::mpsc};
+#[cfg(feature = "gui")]
+use std::{sync::Arc, thread};
 
-const CANT_ARGS: usize = 2;
+/// Bitcoin node with a GTK or terminal wallet.
+/// Every value of the config file can be overridden with the flags below.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Path to the config file
+    config_path: Option<String>,
+    /// Comma separated DNS seeds used to discover peers
+    #[arg(long)]
+    seed: Option<String>,
+    /// Protocol version
+    #[arg(long)]
+    protocol_version: Option<i32>,
+    /// User agent sent to peers, in the /name:version/ format
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// Comma separated services to advertise and serve, e.g. NODE_NETWORK,NODE_WITNESS
+    #[arg(long)]
+    services: Option<String>,
+    /// Port where the node listens for peers
+    #[arg(long)]
+    port: Option<u16>,
+    /// Comma separated addresses to listen on, e.g. 0.0.0.0,[::1]:18444 (default: all interfaces)
+    #[arg(long)]
+    bind: Option<String>,
+    /// Log file
+    #[arg(long)]
+    log: Option<String>,
+    /// Number of peers to connect to
+    #[arg(long)]
+    npeers: Option<u8>,
+    /// Comma separated IPs or subnets that are always allowed, e.g. 10.0.0.0/8,::1
+    #[arg(long)]
+    whitelist: Option<String>,
+    /// Comma separated IPs or subnets that are always refused
+    #[arg(long)]
+    blacklist: Option<String>,
+    /// Maximum number of inbound peers, the least useful one is evicted when full
+    #[arg(long)]
+    max_inbound: Option<usize>,
+    /// Number of fastest peers that headers and blocks are requested from
+    #[arg(long)]
+    sync_peers: Option<usize>,
+    /// Directory where headers, blocks, UTXO and wallets are stored
+    #[arg(long)]
+    store_path: Option<String>,
+    /// Do not listen for incoming peer connections
+    #[arg(long)]
+    client_only: bool,
+    /// Ask peers not to announce transactions, only the node's own ones are relayed
+    #[arg(long)]
+    blocks_only: bool,
+    /// Maximum size of the mempool in megabytes, the lowest fee rate transactions are evicted when full
+    #[arg(long)]
+    max_mempool: Option<usize>,
+    /// Minimum fee rate in satoshis per virtual kilobyte of the transactions accepted and relayed
+    #[arg(long)]
+    min_relay_fee: Option<u64>,
+    /// Hash of a block whose ancestors are assumed valid, skipping the verification of their scripts
+    #[arg(long)]
+    assume_valid: Option<String>,
+    /// Download only headers and the blocks filtered by the wallets (SPV client), implies --client-only
+    #[arg(long)]
+    spv: bool,
+    /// Compress the blocks stored from now on with zstd
+    #[arg(long)]
+    compress_blocks: bool,
+    /// Port of the read-only REST API (0 disables it)
+    #[arg(long)]
+    rest_port: Option<u16>,
+    /// Port of the JSON-RPC server (0 disables it)
+    #[arg(long)]
+    rpc_port: Option<u16>,
+    /// Port of the block and transaction publisher (0 disables it)
+    #[arg(long)]
+    publisher_port: Option<u16>,
+    /// Bitcoin network
+    #[arg(long, value_parser = ["mainnet", "testnet", "regtest"])]
+    network: Option<String>,
+    /// Minimum log level
+    #[arg(long, value_parser = ["debug", "info", "warn", "error"])]
+    log_level: Option<String>,
+    /// Per-module log levels, e.g. sync:warn,peer:debug (modules: general, peer, sync, wallet, gui)
+    #[arg(long)]
+    log_filter: Option<String>,
+    /// Format of the log file and console output
+    #[arg(long, value_parser = ["text", "json"])]
+    log_format: Option<String>,
+    /// Milliseconds to wait when connecting to a peer
+    #[arg(long)]
+    connect_timeout: Option<u64>,
+    /// Milliseconds to wait for each handshake message
+    #[arg(long)]
+    handshake_timeout: Option<u64>,
+    /// Milliseconds without messages before disconnecting a peer
+    #[arg(long)]
+    read_timeout: Option<u64>,
+    /// Milliseconds to wait for a pong before disconnecting a peer
+    #[arg(long)]
+    ping_timeout: Option<u64>,
+    /// SOCKS5 proxy (host:port) for outbound connections, e.g. Tor
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Use different proxy credentials for each peer (Tor stream isolation)
+    #[arg(long)]
+    proxy_isolate: bool,
+    /// Import the blocks of a Bitcoin Core blocks directory (blk*.dat files) into the store and exit
+    #[arg(long, value_name = "DIR")]
+    import_blocks: Option<String>,
+    /// Export the stored headers and blocks to an empty directory, readable by --import-blocks, and exit
+    #[arg(long, value_name = "DIR")]
+    export_blocks: Option<String>,
+    /// Write a snapshot of the stored UTXO set to a file, loadable by --load-utxo, and exit
+    #[arg(long, value_name = "FILE")]
+    dump_utxo: Option<String>,
+    /// Initialize an empty store from a UTXO snapshot, downloading only the blocks after it, and exit
+    #[arg(long, value_name = "FILE")]
+    load_utxo: Option<String>,
+    /// Replay the messages recorded with CAPTURE, connecting only to local peers that send them instead of the network
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+    /// Use the terminal interface instead of the GTK one (always used when built without the gui feature)
+    #[arg(long)]
+    tui: bool,
+}
+
+impl Cli {
+    /// Indica si se usa la interfaz de terminal, que es la unica disponible sin el feature gui.
+    fn use_tui(&self) -> bool {
+        self.tui || cfg!(not(feature = "gui"))
+    }
+
+    /// Devuelve los valores recibidos por linea de comandos con el nombre que tienen en el archivo de configuracion.
+    fn overrides(&self) -> Vec<(&'static str, String)> {
+        let mut overrides = vec![];
+        let mut push = |name: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                overrides.push((name, value));
+            }
+        };
+        push("SEED", self.seed.clone());
+        push(
+            "PROTOCOL_VERSION",
+            self.protocol_version.map(|v| v.to_string()),
+        );
+        push("USER_AGENT", self.user_agent.clone());
+        push("SERVICES", self.services.clone());
+        push("PORT", self.port.map(|v| v.to_string()));
+        push("BIND", self.bind.clone());
+        push("LOG", self.log.clone());
+        push("NPEERS", self.npeers.map(|v| v.to_string()));
+        push("MAX_INBOUND", self.max_inbound.map(|v| v.to_string()));
+        push("SYNC_PEERS", self.sync_peers.map(|v| v.to_string()));
+        push("WHITELIST", self.whitelist.clone());
+        push("BLACKLIST", self.blacklist.clone());
+        push("STORE_PATH", self.store_path.clone());
+        push(
+            "CLIENT_ONLY",
+            self.client_only.then(|| String::from("true")),
+        );
+        push(
+            "BLOCKS_ONLY",
+            self.blocks_only.then(|| String::from("true")),
+        );
+        push("MAX_MEMPOOL", self.max_mempool.map(|v| v.to_string()));
+        push("MIN_RELAY_FEE", self.min_relay_fee.map(|v| v.to_string()));
+        push("ASSUME_VALID", self.assume_valid.clone());
+        push("SPV", self.spv.then(|| String::from("true")));
+        push(
+            "COMPRESS_BLOCKS",
+            self.compress_blocks.then(|| String::from("true")),
+        );
+        push("REST_PORT", self.rest_port.map(|v| v.to_string()));
+        push("RPC_PORT", self.rpc_port.map(|v| v.to_string()));
+        push("PUBLISHER_PORT", self.publisher_port.map(|v| v.to_string()));
+        push("NETWORK", self.network.clone());
+        push("LOG_LEVEL", self.log_level.clone());
+        push("LOG_FILTER", self.log_filter.clone());
+        push("LOG_FORMAT", self.log_format.clone());
+        push(
+            "CONNECT_TIMEOUT",
+            self.connect_timeout.map(|v| v.to_string()),
+        );
+        push(
+            "HANDSHAKE_TIMEOUT",
+            self.handshake_timeout.map(|v| v.to_string()),
+        );
+        push("READ_TIMEOUT", self.read_timeout.map(|v| v.to_string()));
+        push("PING_TIMEOUT", self.ping_timeout.map(|v| v.to_string()));
+        push("PROXY", self.proxy.clone());
+        push(
+            "PROXY_ISOLATE",
+            self.proxy_isolate.then(|| String::from("true")),
+        );
+        overrides
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < CANT_ARGS {
-        println!("ERROR: config file path missing");
-        return;
+    let cli = Cli::parse();
+    if let Some(path) = &cli.config_path {
+        if !Path::new(path).exists() {
+            println!("ERROR: config file not found at {}", path);
+            return;
+        }
     }
-    let path = Path::new(&args[1]);
-    if !path.exists() {
-        println!("ERROR: config file not found at {}", path.display());
-        return;
+
+    let replay = match cli.replay.as_deref().map(read_capture).transpose() {
+        Ok(replay) => replay,
+        Err(error) => {
+            println!("ERROR: cannot read the capture: {error}");
+            return;
+        }
+    };
+    let mut overrides = cli.overrides();
+    if let Some(messages) = &replay {
+        overrides.extend(replay_overrides(messages));
     }
 
-    let config = match Config::from_file(args[1].as_str()) {
+    let config = match Config::from_file_with_overrides(cli.config_path.as_deref(), overrides) {
         Ok(config) => config,
         Err(error) => {
             println!("ERROR: {error}");
@@ -32,9 +250,27 @@ fn main() {
         }
     };
 
-    let (gui_sender, gui_receiver) = glib::MainContext::channel(Priority::default());
+    #[cfg(feature = "flamegraph")]
+    let _flamegraph_guard = init_flamegraph();
+
+    let (gui_sender, events_receiver) = mpsc::channel();
+
+    let ctrlc_sender = gui_sender.clone();
+    if let Err(error) = ctrlc::set_handler(move || {
+        if ctrlc_sender.send(GUIEvents::Terminate).is_err() {
+            println!("Error sending terminate event to gui");
+        }
+    }) {
+        println!("ERROR: cannot set Ctrl-C handler: {error}");
+    }
 
-    let logger = match Logger::new(&config.log_file, gui_sender.clone()) {
+    let logger = match Logger::with_options(
+        &config.log_file,
+        gui_sender.clone(),
+        config.log_filter.clone(),
+        config.log_format,
+        !cli.use_tui(),
+    ) {
         Ok(logger) => logger,
         Err(error) => {
             println!("ERROR: {error}");
@@ -44,10 +280,32 @@ fn main() {
 
     let logger_sender = logger.get_sender();
 
+    if let Some(core_dir) = &cli.import_blocks {
+        import_blocks(&config, core_dir, &logger_sender);
+        stop_logger(logger, &logger_sender);
+        return;
+    }
+    if let Some(export_dir) = &cli.export_blocks {
+        export_blocks(&config, export_dir, &logger_sender);
+        stop_logger(logger, &logger_sender);
+        return;
+    }
+    if let Some(snapshot_path) = &cli.dump_utxo {
+        dump_utxo(&config, snapshot_path, &logger_sender);
+        stop_logger(logger, &logger_sender);
+        return;
+    }
+    if let Some(snapshot_path) = &cli.load_utxo {
+        load_utxo(&config, snapshot_path, &logger_sender);
+        stop_logger(logger, &logger_sender);
+        return;
+    }
+
     let node_state_ref = match NodeState::new(
         logger_sender.clone(),
         gui_sender.clone(),
         &config.store_path,
+        config.network,
     ) {
         Ok(node_state) => node_state,
         Err(error) => {
@@ -66,7 +324,26 @@ fn main() {
 
     let node_action_sender = node.node_action_sender.clone();
 
-    let addresses = get_addresses(config.seed.clone(), config.port);
+    let addresses = match replay {
+        Some(messages) => ReplayLoop::spawn(logger_sender.clone(), messages, config.network)
+            .map(|addresses| addresses.into_iter()),
+        // con un proxy no se consultan las semillas DNS, ya que la consulta saldria por fuera de el
+        None if config.get_proxy().is_some() => {
+            send_log(
+                &logger_sender,
+                Log::Message(String::from(
+                    "Proxy configured, skipping DNS seeds and using the known addresses",
+                )),
+            );
+            Ok(get_literal_seed_addresses(&config.get_seeds(), config.port))
+        }
+        None => get_seed_addresses(
+            &config.get_seeds(),
+            config.network.dns_seeds(),
+            config.port,
+            config.npeers as usize,
+        ),
+    };
     let addresses = match addresses {
         Ok(addresses) => addresses,
         Err(error) => {
@@ -75,19 +352,41 @@ fn main() {
         }
     };
 
-    let node_thread = node.spawn(addresses, gui_sender);
+    let node_thread = match node.spawn(addresses, gui_sender) {
+        Ok(node_thread) => node_thread,
+        Err(error) => {
+            send_log(&logger_sender, Log::Error(error));
+            return;
+        }
+    };
 
-    let gui = GUI::start(
-        gui_receiver,
+    #[cfg(feature = "gui")]
+    let frontend = match cli.use_tui() {
+        true => Tui::start(
+            events_receiver,
+            node_state_ref,
+            logger_sender.clone(),
+            node_action_sender.clone(),
+        ),
+        false => start_gui(
+            events_receiver,
+            node_state_ref,
+            logger_sender.clone(),
+            node_action_sender.clone(),
+        ),
+    };
+    #[cfg(not(feature = "gui"))]
+    let frontend = Tui::start(
+        events_receiver,
         node_state_ref,
         logger_sender.clone(),
         node_action_sender.clone(),
     );
 
-    if let Err(error) = gui {
+    if let Err(error) = frontend {
         send_log(
             &logger_sender,
-            Log::Message(format!("Error starting GUI: {}", error)),
+            Log::Message(format!("Error starting the interface: {}", error)),
         );
     };
 
@@ -100,12 +399,143 @@ fn main() {
         };
     }
 
+    stop_logger(logger, &logger_sender);
+}
+
+/// Devuelve los valores del config para reproducir una captura: el nodo se conecta a un peer por cada peer capturado
+/// y no atiende conexiones entrantes, para recibir solo los mensajes de la captura.
+fn replay_overrides(messages: &[CapturedMessage]) -> Vec<(&'static str, String)> {
+    let npeers = captured_peers(messages).len().min(u8::MAX as usize);
+    vec![
+        ("NPEERS", npeers.to_string()),
+        ("CLIENT_ONLY", String::from("true")),
+    ]
+}
+
+#[cfg(feature = "gui")]
+/// Inicia la interfaz grafica, reenviando los eventos del nodo al main loop de GTK.
+fn start_gui(
+    events_receiver: mpsc::Receiver<GUIEvents>,
+    node_state_ref: Arc<NodeState>,
+    logger_sender: mpsc::Sender<Log>,
+    node_action_sender: NodeActionSender,
+) -> Result<(), CustomError> {
+    let (glib_sender, gui_receiver) = glib::MainContext::channel(Priority::default());
+    thread::spawn(move || {
+        while let Ok(event) = events_receiver.recv() {
+            if glib_sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    GUI::start(
+        gui_receiver,
+        node_state_ref,
+        logger_sender,
+        node_action_sender,
+    )
+}
+
+/// Importa al store los bloques de los archivos de Bitcoin Core del directorio recibido.
+fn import_blocks(config: &Config, core_dir: &str, logger_sender: &mpsc::Sender<Log>) {
+    let result = import_core_blocks(core_dir, &config.store_path, config.network, logger_sender);
+    match result {
+        Ok(summary) => send_log(
+            logger_sender,
+            Log::Message(format!(
+                "Imported {} headers and {} blocks from {core_dir}",
+                summary.headers, summary.blocks
+            )),
+        ),
+        Err(error) => send_log(logger_sender, Log::Error(error)),
+    }
+}
+
+/// Exporta los headers y bloques del store al directorio recibido.
+fn export_blocks(config: &Config, export_dir: &str, logger_sender: &mpsc::Sender<Log>) {
+    let result = export_chain(
+        &config.store_path,
+        export_dir,
+        config.network,
+        logger_sender,
+    );
+    match result {
+        Ok(summary) => send_log(
+            logger_sender,
+            Log::Message(format!(
+                "Exported {} headers and {} blocks to {export_dir}",
+                summary.headers, summary.blocks
+            )),
+        ),
+        Err(error) => send_log(logger_sender, Log::Error(error)),
+    }
+}
+
+/// Guarda en el archivo recibido un snapshot de las UTXO del store.
+fn dump_utxo(config: &Config, snapshot_path: &str, logger_sender: &mpsc::Sender<Log>) {
+    let result = dump_utxo_snapshot(
+        &config.store_path,
+        snapshot_path,
+        config.network,
+        logger_sender,
+    );
+    match result {
+        Ok(base) => send_log(
+            logger_sender,
+            Log::Message(format!(
+                "Dumped the UTXO set at height {} ({}) to {snapshot_path}",
+                base.height, base.block_hash
+            )),
+        ),
+        Err(error) => send_log(logger_sender, Log::Error(error)),
+    }
+}
+
+/// Inicializa el store con el snapshot de UTXO del archivo recibido.
+fn load_utxo(config: &Config, snapshot_path: &str, logger_sender: &mpsc::Sender<Log>) {
+    let result = load_utxo_snapshot(&config.store_path, snapshot_path);
+    match result {
+        Ok(base) => send_log(
+            logger_sender,
+            Log::Message(format!(
+                "Loaded the UTXO set at height {} ({}) from {snapshot_path}",
+                base.height, base.block_hash
+            )),
+        ),
+        Err(error) => send_log(logger_sender, Log::Error(error)),
+    }
+}
+
+/// Termina el logger, esperando a que escriba los logs pendientes.
+fn stop_logger(logger: Logger, logger_sender: &mpsc::Sender<Log>) {
     if logger.tx.send(Log::Terminate).is_ok() {
         if let Err(error) = logger.thread.join() {
             send_log(
-                &logger_sender,
+                logger_sender,
                 Log::Message(format!("Error closing logger thread: {:?}", error)),
             );
         };
     }
 }
+
+/// Archivo donde se guardan los stacks de los spans de tracing, para generar un flamegraph.
+#[cfg(feature = "flamegraph")]
+const FLAMEGRAPH_FILE: &str = "tracing.folded";
+
+#[cfg(feature = "flamegraph")]
+/// Registra un subscriber de tracing que guarda los spans del nodo en FLAMEGRAPH_FILE.
+/// Devuelve el guard que escribe el archivo al ser liberado, al terminar el main.
+fn init_flamegraph() -> Option<tracing_flame::FlushGuard<std::io::BufWriter<std::fs::File>>> {
+    use tracing_subscriber::prelude::*;
+
+    let (flame_layer, guard) = match tracing_flame::FlameLayer::with_file(FLAMEGRAPH_FILE) {
+        Ok(flame) => flame,
+        Err(error) => {
+            println!("ERROR: cannot create {FLAMEGRAPH_FILE}: {error}");
+            return None;
+        }
+    };
+    tracing_subscriber::registry().with(flame_layer).init();
+    Some(guard)
+}
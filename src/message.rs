@@ -1,69 +1,100 @@
 use crate::error::CustomError;
+use crate::network::Network;
+use crate::parser::{BtcEncode, BufferParser};
+use crate::structs::net_address::MAX_ADDR_V2_SIZE;
 use bitcoin_hashes::sha256;
 use bitcoin_hashes::Hash;
+use bitcoin_hashes::HashEngine;
 
+use std::io;
+use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Write;
 use std::net::TcpStream;
 
+/// Tamaño del header de los mensajes.
+pub const MESSAGE_HEADER_SIZE: usize = 24;
+/// Tamaño maximo de payload que se acepta para cualquier mensaje (4MB, igual que un bloque).
+pub const MAX_PAYLOAD_SIZE: u32 = 4_000_000;
+/// Cantidad maxima de hashes en el locator de un getheaders.
+const MAX_LOCATOR_SIZE: u32 = 101;
+/// Cantidad maxima de headers en un mensaje headers.
+const MAX_HEADERS: u32 = 2000;
+/// Cantidad maxima de inventarios en un mensaje inv, getdata o notfound.
+pub const MAX_INVENTORIES: u32 = 50_000;
+/// Cantidad maxima de direcciones en un mensaje addr.
+const MAX_ADDRESSES: u32 = 1000;
+/// Tamaño maximo del user agent de un mensaje version.
+pub const MAX_USER_AGENT_SIZE: u32 = 256;
+
 /// Este trait representa un mensaje del protocolo.
 /// Todos los mensajes deben implementar este trait, por lo que todos deben poder:
-/// - Serializarse.
-/// - Parsearse.
+/// - Serializarse y parsearse, mediante el trait BtcEncode.
 /// - Obtener su comando.
 /// Esto se realiza de forma distinta para cada mensaje, por lo que se implementa de forma individual en cada uno.
 /// A parte de esto, todos los mensajes deben poder enviarse a un stream y leerse de un stream.
 /// Para ello, se implementan los métodos send y read que al ser el procedimiento igual en todos los mensajes, no requieren implementación individual.
-pub trait Message {
-    fn serialize(&self) -> Vec<u8>;
+pub trait Message: BtcEncode {
     fn get_command(&self) -> String;
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError>
-    where
-        Self: Sized;
 
-    /// Envía el mensaje a un stream.
+    /// Envía el mensaje a un stream, con el magic number de la red recibida, y devuelve la cantidad de bytes enviados,
+    /// contando el header.
     /// Devuelve CustomError si:
     /// - No se puede escribir en el stream el header del mensaje.
     /// - No se puede escribir en el stream el payload del mensaje.
     /// - No se puede hacer flush del stream.
-    fn send(&self, stream: &mut TcpStream) -> Result<(), CustomError>
+    fn send(&self, stream: &mut TcpStream, network: Network) -> Result<usize, CustomError>
     where
         Self: Sized,
     {
-        let header = MessageHeader::new(self);
+        let header = MessageHeader::new(self, network);
+        let payload = self.serialize();
 
         stream
             .write(&header.serialize())
             .map_err(|_| CustomError::CannotSendMessageToChannel)?;
 
         stream
-            .write(&self.serialize())
+            .write(&payload)
             .map_err(|_| CustomError::CannotSendMessageToChannel)?;
 
         stream
             .flush()
             .map_err(|_| CustomError::CannotSendMessageToChannel)?;
 
-        Ok(())
+        Ok(MESSAGE_HEADER_SIZE + payload.len())
     }
 
     /// Lee un mensaje de un stream y lo parsea.
     /// Devuelve CustomError si:
+    /// - El tamaño del mensaje supera MAX_PAYLOAD_SIZE.
     /// - No se puede leer del stream
     fn read(stream: &mut TcpStream, message_size: u32) -> Result<Self, CustomError>
     where
         Self: Sized,
     {
+        if message_size > MAX_PAYLOAD_SIZE {
+            return Err(CustomError::MessageTooLarge);
+        }
         let mut payload_buffer = vec![0; message_size as usize];
 
         stream
             .read_exact(&mut payload_buffer)
-            .map_err(|_| CustomError::CannotReadStream)?;
+            .map_err(|error| read_error(error, CustomError::CannotReadStream))?;
 
         Self::parse(payload_buffer)
     }
 }
 
+/// Convierte un error de lectura del stream en CustomError.
+/// Si vencio el read timeout del stream devuelve PeerTimeout, sino el error recibido.
+pub fn read_error(error: std::io::Error, default: CustomError) -> CustomError {
+    match error.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => CustomError::PeerTimeout,
+        _ => default,
+    }
+}
+
 /// Calcula el checksum de un payload.
 /// El checksum es el hash de doble aplicación de sha256.
 /// Devuelve los primeros 4 bytes del hash.
@@ -72,12 +103,39 @@ fn get_checksum(payload: &[u8]) -> [u8; 4] {
     [hash[0], hash[1], hash[2], hash[3]]
 }
 
-/// El magic number es un número que se usa para identificar la red, en nuestro caso, la testnet.
-const MAGIC: u32 = 0x0b110907;
+/// PayloadReader lee el payload de un mensaje de un stream calculando su checksum a medida que se lee,
+/// para verificarlo sin guardar el payload completo.
+/// Los elementos son:
+/// - stream: Stream del que se lee el payload.
+/// - engine: Primer sha256 del checksum, con los bytes leidos hasta el momento.
+/// - read: Cantidad de bytes leidos.
+struct PayloadReader<'a, R: Read> {
+    stream: &'a mut R,
+    engine: sha256::HashEngine,
+    read: usize,
+}
+
+impl<R: Read> Read for PayloadReader<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.stream.read(buffer)?;
+        self.engine.input(&buffer[..read]);
+        self.read += read;
+        Ok(read)
+    }
+}
+
+impl<R: Read> PayloadReader<'_, R> {
+    /// Devuelve el checksum de los bytes leidos, igual que get_checksum.
+    fn checksum(self) -> [u8; 4] {
+        let hash = sha256::Hash::hash(sha256::Hash::from_engine(self.engine).as_byte_array());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+}
+
 #[derive(Debug)]
 /// Representa el header de un mensaje.
 /// El header contiene:
-/// - Un magic number que identifica la red.
+/// - Un magic number que identifica la red a la que se conecta el nodo.
 /// - Un comando que identifica el tipo de mensaje.
 /// - El tamaño del payload.
 /// - El checksum del payload.
@@ -89,14 +147,14 @@ pub struct MessageHeader {
 }
 
 impl MessageHeader {
-    /// Crea un nuevo header a partir de un mensaje.
-    pub fn new(message: &dyn Message) -> Self {
+    /// Crea un nuevo header a partir de un mensaje, con el magic number de la red recibida.
+    pub fn new(message: &dyn Message, network: Network) -> Self {
         let payload = message.serialize();
         let payload_size = payload.len() as u32;
         let checksum = get_checksum(&payload);
 
         MessageHeader {
-            magic: MAGIC,
+            magic: network.magic(),
             command: message.get_command(),
             payload_size,
             checksum,
@@ -125,9 +183,10 @@ impl MessageHeader {
     /// Parsea un header a partir de un buffer de 24 bytes.
     /// Devuelve CustomError si:
     /// - El buffer no tiene 24 bytes.
+    /// - El comando no son caracteres ASCII seguidos unicamente de bytes nulos.
     /// - El comando no se puede parsear a String.
     pub fn parse(buffer: [u8; 24]) -> Result<Self, CustomError> {
-        if buffer.len() != 24 {
+        if buffer.len() != 24 || !is_valid_command(&buffer[4..16]) {
             return Err(CustomError::CannotReadMessageHeader);
         }
         let magic = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
@@ -152,24 +211,147 @@ impl MessageHeader {
     /// Lee un header de un stream y lo parsea.
     /// Devuelve CustomError si:
     /// - No se puede leer del stream.
-    pub fn read(stream: &mut TcpStream) -> Result<Self, CustomError> {
+    /// - Vence el read timeout del stream (PeerTimeout).
+    pub fn read(stream: &mut impl Read) -> Result<Self, CustomError> {
         let mut header_buffer = [0; 24];
 
         stream
             .read_exact(&mut header_buffer)
-            .map_err(|_| CustomError::CannotReadMessageHeader)?;
+            .map_err(|error| read_error(error, CustomError::CannotReadMessageHeader))?;
 
         let header = Self::parse(header_buffer)?;
 
         Ok(header)
     }
+
+    /// Lee del stream el payload declarado por el header y verifica que corresponda al header.
+    /// Devuelve CustomError si:
+    /// - El tamaño declarado supera el maximo para el comando, antes de reservar memoria para el payload.
+    /// - No se puede leer del stream.
+    /// - El magic number no es el de la red recibida.
+    /// - El checksum no coincide con el del payload.
+    pub fn read_payload(
+        &self,
+        stream: &mut impl Read,
+        network: Network,
+    ) -> Result<Vec<u8>, CustomError> {
+        self.verify_payload_size()?;
+        let mut payload = vec![0; self.payload_size as usize];
+
+        stream
+            .read_exact(&mut payload)
+            .map_err(|error| read_error(error, CustomError::CannotReadStream))?;
+
+        self.verify_payload(&payload, network)?;
+        Ok(payload)
+    }
+
+    /// Lee del stream el payload declarado por el header y lo parsea a medida que se lee, sin guardarlo completo
+    /// en memoria, para los payloads grandes como los de block y headers.
+    /// El checksum se calcula mientras se lee, y aunque el parseo falle se lee el resto del payload,
+    /// para que el stream quede al comienzo del siguiente mensaje.
+    /// Devuelve CustomError si:
+    /// - El tamaño declarado supera el maximo para el comando.
+    /// - El magic number no es el de la red recibida.
+    /// - No se puede leer del stream.
+    /// - El checksum no coincide con el del payload, aunque se haya podido parsear.
+    /// - No se puede parsear el payload.
+    pub fn read_streamed<T, R: Read>(
+        &self,
+        stream: &mut R,
+        network: Network,
+        parse: impl FnOnce(&mut BufferParser) -> Result<T, CustomError>,
+    ) -> Result<T, CustomError> {
+        self.verify_payload_size()?;
+        self.verify_magic(network)?;
+        let payload_size = self.payload_size as usize;
+
+        let mut reader = PayloadReader {
+            stream,
+            engine: sha256::Hash::engine(),
+            read: 0,
+        };
+        let parsed = parse(&mut BufferParser::from_reader(&mut reader, payload_size));
+
+        let unread = (payload_size - reader.read) as u64;
+        io::copy(&mut (&mut reader).take(unread), &mut io::sink())
+            .map_err(|error| read_error(error, CustomError::CannotReadStream))?;
+        if reader.read != payload_size {
+            return Err(CustomError::CannotReadStream);
+        }
+        if reader.checksum() != self.checksum {
+            return Err(CustomError::InvalidChecksum);
+        }
+        parsed
+    }
+
+    /// Verifica que el tamaño declarado por el header no supere el maximo permitido para su comando.
+    pub fn verify_payload_size(&self) -> Result<(), CustomError> {
+        if self.payload_size > max_payload_size(&self.command) {
+            return Err(CustomError::MessageTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Verifica que el payload recibido corresponda al header: magic number de la red recibida y checksum.
+    pub fn verify_payload(&self, payload: &[u8], network: Network) -> Result<(), CustomError> {
+        self.verify_magic(network)?;
+        if payload.len() != self.payload_size as usize || get_checksum(payload) != self.checksum {
+            return Err(CustomError::InvalidChecksum);
+        }
+        Ok(())
+    }
+
+    /// Verifica que el magic number del header sea el de la red recibida.
+    fn verify_magic(&self, network: Network) -> Result<(), CustomError> {
+        if self.magic != network.magic() {
+            return Err(CustomError::CannotReadMessageHeader);
+        }
+        Ok(())
+    }
+}
+
+/// Devuelve el tamaño maximo de payload aceptado para un comando.
+/// Los mensajes con listas se limitan a la cantidad maxima de elementos que permite el protocolo,
+/// y los comandos desconocidos al maximo general.
+pub fn max_payload_size(command: &str) -> u32 {
+    match command {
+        "verack" | "sendheaders" | "getaddr" | "sendaddrv2" | "wtxidrelay" | "mempool"
+        | "filterclear" => 0,
+        "ping" | "pong" | "feefilter" => 8,
+        "version" => 80 + 3 + MAX_USER_AGENT_SIZE + 4 + 1,
+        "getheaders" | "getblocks" => 4 + 3 + (MAX_LOCATOR_SIZE + 1) * 32,
+        "headers" => 3 + MAX_HEADERS * 81,
+        "inv" | "getdata" | "notfound" => 3 + MAX_INVENTORIES * 36,
+        "addr" => 3 + MAX_ADDRESSES * 30,
+        "addrv2" => 3 + MAX_ADDRESSES * (4 + 9 + 1 + 3 + MAX_ADDR_V2_SIZE as u32 + 2),
+        _ => MAX_PAYLOAD_SIZE,
+    }
+}
+
+/// Un comando valido esta compuesto por caracteres ASCII imprimibles, completado con bytes nulos hasta los 12 bytes.
+fn is_valid_command(command: &[u8]) -> bool {
+    let length = command
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(command.len());
+    length > 0
+        && command[..length].iter().all(u8::is_ascii_graphic)
+        && command[length..].iter().all(|byte| *byte == 0)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::{Ipv6Addr, SocketAddrV6};
+    use std::{
+        io::Cursor,
+        net::{Ipv6Addr, SocketAddrV6},
+    };
 
-    use crate::messages::version::Version;
+    use crate::{
+        messages::{headers::Headers, ping_pong::Ping, version::Version},
+        network::Network,
+        structs::block_header::BlockHeader,
+    };
 
     use super::*;
 
@@ -184,9 +366,9 @@ mod tests {
     fn test_message_header_length() {
         let sender_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 4321, 0, 0);
         let receiver_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 8080, 0, 0);
-        let version = Version::new(receiver_address, sender_address, 70000, 0x00);
+        let version = Version::new(receiver_address, sender_address, 70000, 0x00, "", true);
 
-        let header = MessageHeader::new(&version).serialize();
+        let header = MessageHeader::new(&version, Network::Testnet).serialize();
         assert_eq!(header.len(), 24);
     }
 
@@ -199,10 +381,107 @@ mod tests {
 
         let header = MessageHeader::parse(header).unwrap();
 
-        assert_eq!(header.magic, MAGIC);
+        assert_eq!(header.magic, Network::Testnet.magic());
         assert_eq!(header.command, "version");
         assert_eq!(header.payload_size, (85 as u32));
         assert_eq!(header.checksum.len(), 4);
         assert_eq!(header.checksum, [75, 114, 249, 186]);
     }
+
+    #[test]
+    fn test_message_header_invalid_command() {
+        let mut header = [0; 24];
+        header[4..8].copy_from_slice(b"ping");
+        assert!(MessageHeader::parse(header).is_ok());
+
+        header[9] = b'x';
+        assert!(MessageHeader::parse(header).is_err());
+
+        let mut header = [0; 24];
+        header[4..8].copy_from_slice(&[b'p', 0xff, b'n', b'g']);
+        assert!(MessageHeader::parse(header).is_err());
+        assert!(MessageHeader::parse([0; 24]).is_err());
+    }
+
+    #[test]
+    fn test_message_header_verify_payload() {
+        let ping = Ping { nonce: 7 };
+        let header = MessageHeader::new(&ping, Network::Testnet);
+        let mut payload = ping.serialize();
+        assert!(header.verify_payload(&payload, Network::Testnet).is_ok());
+        assert!(matches!(
+            header.verify_payload(&payload, Network::Regtest),
+            Err(CustomError::CannotReadMessageHeader)
+        ));
+
+        payload[0] ^= 1;
+        assert!(matches!(
+            header.verify_payload(&payload, Network::Testnet),
+            Err(CustomError::InvalidChecksum)
+        ));
+        assert!(header
+            .verify_payload(&payload[1..], Network::Testnet)
+            .is_err());
+    }
+
+    #[test]
+    fn test_message_header_payload_size_limits() {
+        let ping = Ping { nonce: 7 };
+        let mut header = MessageHeader::new(&ping, Network::Testnet);
+        assert!(header.verify_payload_size().is_ok());
+
+        header.payload_size = 9;
+        assert!(matches!(
+            header.verify_payload_size(),
+            Err(CustomError::MessageTooLarge)
+        ));
+
+        header.command = "headers".to_string();
+        header.payload_size = 3 + 2000 * 81;
+        assert!(header.verify_payload_size().is_ok());
+        header.payload_size += 1;
+        assert!(header.verify_payload_size().is_err());
+
+        header.command = "unknown".to_string();
+        header.payload_size = u32::MAX;
+        assert!(header.verify_payload_size().is_err());
+    }
+
+    #[test]
+    fn test_message_header_read_streamed() {
+        let genesis = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+        let genesis: Vec<u8> = (0..genesis.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&genesis[i..i + 2], 16).unwrap())
+            .collect();
+        let header = BlockHeader::parse(genesis).unwrap();
+        let headers = Headers {
+            headers: vec![header.clone(), header],
+        };
+        let header = MessageHeader::new(&headers, Network::Testnet);
+        let mut payload = headers.serialize();
+        let payload_size = payload.len() as u64;
+        payload.extend([0xAA; 10]);
+
+        let mut stream = Cursor::new(payload.clone());
+        let streamed = header
+            .read_streamed(&mut stream, Network::Testnet, Headers::decode)
+            .unwrap();
+        assert_eq!(streamed.serialize(), headers.serialize());
+        assert_eq!(stream.position(), payload_size);
+
+        payload[100] ^= 1;
+        let mut stream = Cursor::new(payload);
+        assert!(matches!(
+            header.read_streamed(&mut stream, Network::Testnet, Headers::decode),
+            Err(CustomError::InvalidChecksum)
+        ));
+        assert_eq!(stream.position(), payload_size);
+
+        let mut stream = Cursor::new(headers.serialize());
+        assert!(header
+            .read_streamed(&mut stream, Network::Testnet, |parser| parser.extract_u8())
+            .is_ok());
+        assert_eq!(stream.position(), payload_size);
+    }
 }
@@ -0,0 +1,81 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::net_address::NetAddress,
+};
+
+/// Cantidad maxima de direcciones que puede contener un mensaje addr.
+pub const MAX_ADDR_COUNT: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta es la estructura de un mensaje addr, la cual contiene un vector de direcciones de nodos de la red
+pub struct Addr {
+    pub addresses: Vec<NetAddress>,
+}
+
+impl Addr {
+    /// Esta funcion se encarga de crear un nuevo mensaje addr con un vector de direcciones que recibe por parametro
+    pub fn new(addresses: Vec<NetAddress>) -> Self {
+        Self { addresses }
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje addr.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Addr {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.addresses.encode(buffer);
+    }
+
+    /// Parsea un mensaje addr.
+    /// Devuelve CustomError si tiene mas de MAX_ADDR_COUNT direcciones o si sobran o faltan bytes.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let count = parser.extract_varint()? as usize;
+        if count > MAX_ADDR_COUNT || parser.len() != count * 30 {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+
+        let mut addresses = vec![];
+        for _ in 0..count {
+            addresses.push(NetAddress::decode(parser)?);
+        }
+        Ok(Self { addresses })
+    }
+}
+
+/// Implementa el trait Message para el mensaje addr.
+/// Permite obtener el comando
+impl Message for Addr {
+    fn get_command(&self) -> String {
+        String::from("addr")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddrV6;
+
+    use super::*;
+
+    #[test]
+    fn addr_serialize_and_parse() {
+        let address = NetAddress {
+            timestamp: 1681095630,
+            services: 1,
+            address: "[::ffff:10.0.0.1]:18333".parse::<SocketAddrV6>().unwrap(),
+        };
+        let addr = Addr::new(vec![address]);
+        let buffer = addr.serialize();
+        assert_eq!(buffer.len(), 31);
+        assert_eq!(Addr::parse(buffer).unwrap(), addr);
+    }
+
+    #[test]
+    fn addr_invalid_buffer() {
+        let mut buffer = Addr::new(vec![]).serialize();
+        buffer.push(0);
+        assert!(Addr::parse(buffer).is_err());
+        assert!(Addr::parse(vec![0xfd, 0xe9, 0x03]).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    messages::addr::MAX_ADDR_COUNT,
+    parser::{BtcEncode, BufferParser},
+    structs::net_address::NetAddressV2,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta es la estructura de un mensaje addrv2 (BIP155), la cual contiene un vector de direcciones de nodos de la red.
+/// A diferencia del mensaje addr, permite direcciones de otras redes como Tor v3 o I2P.
+pub struct AddrV2 {
+    pub addresses: Vec<NetAddressV2>,
+}
+
+impl AddrV2 {
+    /// Esta funcion se encarga de crear un nuevo mensaje addrv2 con un vector de direcciones que recibe por parametro
+    pub fn new(addresses: Vec<NetAddressV2>) -> Self {
+        Self { addresses }
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje addrv2.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for AddrV2 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.addresses.encode(buffer);
+    }
+
+    /// Parsea un mensaje addrv2.
+    /// Devuelve CustomError si tiene mas de MAX_ADDR_COUNT direcciones, alguna es invalida o sobran bytes.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let count = parser.extract_varint()? as usize;
+        if count > MAX_ADDR_COUNT {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+
+        let mut addresses = vec![];
+        for _ in 0..count {
+            addresses.push(NetAddressV2::decode(parser)?);
+        }
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self { addresses })
+    }
+}
+
+/// Implementa el trait Message para el mensaje addrv2.
+/// Permite obtener el comando
+impl Message for AddrV2 {
+    fn get_command(&self) -> String {
+        String::from("addrv2")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::structs::net_address::NetworkAddress;
+
+    use super::*;
+
+    #[test]
+    fn addr_v2_serialize_and_parse() {
+        let addr = AddrV2::new(vec![
+            NetAddressV2 {
+                timestamp: 1681095630,
+                services: 1,
+                address: NetworkAddress::IPv4(Ipv4Addr::new(10, 0, 0, 1)),
+                port: 18333,
+            },
+            NetAddressV2 {
+                timestamp: 1681095630,
+                services: 1,
+                address: NetworkAddress::TorV3([7; 32]),
+                port: 18333,
+            },
+        ]);
+        let buffer = addr.serialize();
+        assert_eq!(AddrV2::parse(buffer).unwrap(), addr);
+    }
+
+    #[test]
+    fn addr_v2_invalid_buffer() {
+        let mut buffer = AddrV2::new(vec![]).serialize();
+        buffer.push(0);
+        assert!(AddrV2::parse(buffer).is_err());
+        assert!(AddrV2::parse(vec![0xfd, 0xe9, 0x03]).is_err());
+    }
+}
@@ -4,19 +4,24 @@ use std::{
     vec,
 };
 
-use bitcoin_hashes::{sha256, Hash};
-
-use super::transaction::Transaction;
+use super::transaction::{Transaction, WITNESS_SCALE_FACTOR};
 
 use crate::{
     error::CustomError,
     message::Message,
-    parser::{BufferParser, VarIntSerialize},
-    structs::block_header::BlockHeader,
+    parser::{BtcEncode, BufferParser, VarIntSerialize},
+    structs::{block_header::BlockHeader, hash32::Hash32},
     utils::open_new_file,
 };
 
-#[derive(Debug)]
+/// Comienzo del script de la salida de la coinbase con el commitment de los witness del bloque (BIP141).
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+/// Peso maximo de un bloque (BIP141).
+pub const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+/// Costo maximo en sigops de las transacciones de un bloque (BIP141).
+pub const MAX_BLOCK_SIGOPS_COST: usize = 80_000;
+
+#[derive(Debug, Clone)]
 
 /// Esta estructura es la que se encarga de almacenar los bloques, esto lo hace con un BlockHeader y en un vector de 'transactions' por cada uno
 pub struct Block {
@@ -57,8 +62,73 @@ impl Block {
         Ok(())
     }
 
+    /// Descarta los witness de las transacciones del bloque, para enviarlo a quien lo pidio sin ellos (MSG_BLOCK).
+    pub fn strip_witness(&mut self) {
+        for transaction in self.transactions.iter_mut() {
+            transaction.strip_witness();
+        }
+    }
+
+    /// Devuelve la altura del bloque, codificada al comienzo del script de entrada de la coinbase (BIP34).
+    /// Devuelve None si el bloque no tiene coinbase o si su script no comienza con la altura.
+    pub fn coinbase_height(&self) -> Option<u32> {
+        let script_sig = &self.transactions.first()?.inputs.first()?.script_sig;
+        let (&opcode, data) = script_sig.split_first()?;
+        match opcode {
+            // OP_1 a OP_16
+            0x51..=0x60 => Some((opcode - 0x50) as u32),
+            1..=4 => {
+                let height_bytes = data.get(..opcode as usize)?;
+                Some(
+                    height_bytes
+                        .iter()
+                        .rev()
+                        .fold(0, |height, byte| (height << 8) | *byte as u32),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Devuelve el peso del bloque (BIP141), los witness de sus transacciones pesan una cuarta parte que el resto.
+    pub fn weight(&self) -> usize {
+        let header_size = 80 + self.transactions.len().to_varint_bytes().len();
+        let transactions_weight: usize = self.transactions.iter().map(Transaction::weight).sum();
+        header_size * WITNESS_SCALE_FACTOR + transactions_weight
+    }
+
+    /// Verifica los limites del bloque que no dependen de las salidas que gasta: su peso y el costo de sus sigops legacy.
+    /// Las sigops de P2SH y de los witness se cuentan al validar el bloque contra las UTXO.
+    /// Devuelve CustomError::BlockExceedsLimits si se supera alguno de los dos.
+    pub fn check_limits(&self) -> Result<(), CustomError> {
+        let legacy_sigops: usize = self
+            .transactions
+            .iter()
+            .map(Transaction::legacy_sigops)
+            .sum();
+        if self.weight() > MAX_BLOCK_WEIGHT
+            || legacy_sigops * WITNESS_SCALE_FACTOR > MAX_BLOCK_SIGOPS_COST
+        {
+            return Err(CustomError::BlockExceedsLimits);
+        }
+        Ok(())
+    }
+
+    /// Indica si el bloque es segwit pero se guardo sin los witness, por lo que no se puede servir como MSG_WITNESS_BLOCK.
+    /// Un bloque segwit tiene el commitment de los witness en una salida de la coinbase y el witness de la coinbase con su nonce.
+    pub fn is_stripped(&self) -> bool {
+        let Some(coinbase) = self.transactions.first() else {
+            return false;
+        };
+        let has_commitment = coinbase.outputs.iter().any(|output| {
+            output.script_pubkey.len() >= 38
+                && output.script_pubkey.starts_with(&WITNESS_COMMITMENT_HEADER)
+        });
+        has_commitment && !coinbase.has_witness()
+    }
+
     /// Esta funcion se encarga de crear el merkle tree del bloque, recorre las transacciones del bloque y calcula el hash de cada una, luego que el merkle tree es generado a partir de los hashes de las transacciones, se lo devuelve.
-    fn create_merkle_tree(&self) -> Vec<Vec<Vec<u8>>> {
+    fn create_merkle_tree(&self) -> Vec<Vec<Hash32>> {
         let mut hashes = vec![];
         for transaction in &self.transactions {
             hashes.push(transaction.hash());
@@ -75,7 +145,7 @@ impl Block {
         let merkle_tree = self.create_merkle_tree();
 
         let merkle_root = match merkle_tree.last() {
-            Some(root_level) => root_level[0].to_vec(),
+            Some(root_level) => root_level[0],
             None => return Err(CustomError::InvalidMerkleRoot),
         };
 
@@ -87,7 +157,7 @@ impl Block {
 
     /// Esta funcion se encarga de encontrar el indice de una transaccion dado un bloque y el hash de la transaccion
     /// Devuelve CustomError si no puede encontrar la transaccion en el bloque
-    fn find_transaction_index(&self, transaction_hash: &Vec<u8>) -> Result<usize, CustomError> {
+    fn find_transaction_index(&self, transaction_hash: &Hash32) -> Result<usize, CustomError> {
         for i in 0..self.transactions.len() {
             if self.transactions[i].hash() == *transaction_hash {
                 return Ok(i);
@@ -101,8 +171,8 @@ impl Block {
     /// Devuelve CustomError si no puede encontrar la transaccion en el bloque
     pub fn generate_merkle_path(
         &self,
-        transaction_hash: Vec<u8>,
-    ) -> Result<(Vec<u8>, Vec<Vec<u8>>), CustomError> {
+        transaction_hash: Hash32,
+    ) -> Result<(Vec<u8>, Vec<Hash32>), CustomError> {
         let merkle_tree = self.create_merkle_tree();
         let mut hash_index = self.find_transaction_index(&transaction_hash)?;
         let mut mp_flags: Vec<u8> = vec![1];
@@ -115,11 +185,11 @@ impl Block {
             if hash_index % 2 == 0 {
                 mp_flags.insert(0, 1);
                 mp_flags.push(0);
-                mp_hashes.push(level[hash_index + 1].clone());
+                mp_hashes.push(level[hash_index + 1]);
             } else {
                 mp_flags.insert(0, 0);
                 mp_flags.insert(0, 1);
-                mp_hashes.insert(0, level[hash_index - 1].clone());
+                mp_hashes.insert(0, level[hash_index - 1]);
             }
             hash_index /= 2;
         }
@@ -132,67 +202,85 @@ impl Block {
     }
 }
 
+/// Calcula el merkle root de los hashes recibidos, en el orden en que estan en el bloque.
+/// Devuelve None si no recibe ningun hash.
+pub fn merkle_root(hashes: Vec<Hash32>) -> Option<Hash32> {
+    if hashes.is_empty() {
+        return None;
+    }
+    let mut merkle_tree = vec![hashes.clone()];
+    generate_merkle_tree(hashes, &mut merkle_tree);
+    merkle_tree.last()?.first().copied()
+}
+
+/// Devuelve el script de la salida de la coinbase con el commitment de los witness (BIP141) de las transacciones
+/// recibidas, que no incluyen a la coinbase. El wtxid de la coinbase se toma como cero y su witness debe ser
+/// un nonce de 32 bytes en cero, que es el que se usa en el commitment.
+pub fn witness_commitment_script(transactions: &[Transaction]) -> Vec<u8> {
+    let mut wtxids = vec![Hash32::default()];
+    wtxids.extend(transactions.iter().map(Transaction::wtxid));
+    let mut buffer = merkle_root(wtxids).unwrap_or_default().to_vec();
+    buffer.extend([0; 32]);
+
+    let mut script = WITNESS_COMMITMENT_HEADER.to_vec();
+    script.extend(Hash32::sha256d(&buffer).as_bytes());
+    script
+}
+
 /// Esta funcion se encarga de mergear dos hashes, recibe dos hashes y los mergea en un solo hash
-fn merge_hashes(mut left: Vec<u8>, mut right: Vec<u8>) -> Vec<u8> {
-    left.append(&mut right);
-    let hash = sha256::Hash::hash(sha256::Hash::hash(left.as_slice()).as_byte_array())
-        .as_byte_array()
-        .to_vec();
-    hash
+pub fn merge_hashes(left: Hash32, right: Hash32) -> Hash32 {
+    let mut buffer = left.to_vec();
+    buffer.extend(right.as_bytes());
+    Hash32::sha256d(&buffer)
 }
 
-/// Esta funcion se encarga de generar el merkle tree, recibe un vector de hashes y un vector de vectores de vectores de bytes, y va generando el merkle tree recursivamente por niveles
-fn generate_merkle_tree(hashes: Vec<Vec<u8>>, merkle_tree: &mut Vec<Vec<Vec<u8>>>) {
+/// Esta funcion se encarga de generar el merkle tree, recibe un vector de hashes y un vector de niveles de hashes, y va generando el merkle tree recursivamente por niveles
+fn generate_merkle_tree(hashes: Vec<Hash32>, merkle_tree: &mut Vec<Vec<Hash32>>) {
     if hashes.len() == 1 {
         return;
     }
 
     let mut level = vec![];
     for i in (0..hashes.len()).step_by(2) {
-        let current_hash = &hashes[i];
+        let current_hash = hashes[i];
         if i + 1 >= hashes.len() {
-            level.push(merge_hashes(current_hash.clone(), current_hash.clone()));
+            level.push(merge_hashes(current_hash, current_hash));
             break;
         }
-        let next_hash = &hashes[i + 1];
-        level.push(merge_hashes(current_hash.clone(), next_hash.clone()));
+        let next_hash = hashes[i + 1];
+        level.push(merge_hashes(current_hash, next_hash));
     }
 
     merkle_tree.push(level.clone());
     generate_merkle_tree(level, merkle_tree);
 }
 
-/// Implementa el trait Message para bloque
-/// Permite serializar, parsear y obtener el comando
-impl Message for Block {
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.header.serialize());
-        buffer.extend(self.transactions.len().to_varint_bytes());
-        for transaction in &self.transactions {
-            buffer.extend(transaction.serialize());
-        }
-        buffer
+/// Implementa el trait BtcEncode para bloque
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Block {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.header.encode(buffer);
+        self.transactions.encode(buffer);
     }
 
-    fn get_command(&self) -> String {
-        String::from("block")
+    /// Parsea el bloque desde el parser, que puede estar leyendo el payload directamente del stream,
+    /// de forma de no tener que guardar el payload completo antes de parsearlo.
+    /// Los varints del bloque deben estar codificados en su forma mas corta.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        parser.canonical(|parser| {
+            Ok(Self {
+                header: BlockHeader::decode(parser)?,
+                transactions: Vec::decode(parser)?,
+            })
+        })
     }
+}
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, crate::error::CustomError> {
-        let mut parser = BufferParser::new(buffer);
-        let header = BlockHeader::parse(parser.extract_buffer(80)?.to_vec())?;
-        let tx_count = parser.extract_varint()? as usize;
-        let mut transactions = vec![];
-        for _ in 0..tx_count {
-            let transaction = Transaction::parse_from_parser(&mut parser)?;
-            transactions.push(transaction);
-        }
-
-        Ok(Self {
-            header,
-            transactions,
-        })
+/// Implementa el trait Message para bloque
+/// Permite obtener el comando
+impl Message for Block {
+    fn get_command(&self) -> String {
+        String::from("block")
     }
 }
 
@@ -201,21 +289,48 @@ impl Message for Block {
 mod tests {
     use std::io::Read;
 
+    use bitcoin_hashes::{sha256, Hash};
+
     use crate::utils::open_new_file;
 
     use super::*;
 
     #[test]
     fn merge_hashes_test() {
-        let left_hash: Vec<u8> = vec![1, 2, 3];
-        let right_hash: Vec<u8> = vec![4, 5, 6];
+        let left_hash = Hash32::new([1; 32]);
+        let right_hash = Hash32::new([2; 32]);
         let result = merge_hashes(left_hash, right_hash);
-        let hash = sha256::Hash::hash(
-            sha256::Hash::hash(vec![1, 2, 3, 4, 5, 6].as_slice()).as_byte_array(),
-        )
-        .as_byte_array()
-        .to_vec();
-        assert_eq!(result, hash);
+        let mut buffer = vec![1; 32];
+        buffer.extend([2; 32]);
+        let hash = sha256::Hash::hash(sha256::Hash::hash(buffer.as_slice()).as_byte_array())
+            .to_byte_array();
+        assert_eq!(result, Hash32::new(hash));
+    }
+
+    #[test]
+    fn coinbase_height_is_parsed() {
+        let mut file = open_new_file("tests/blocks/test_block.bin".to_string(), true).unwrap();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+        let block = Block::parse(buffer).unwrap();
+        assert_eq!(block.coinbase_height(), Some(2437483));
+    }
+
+    #[test]
+    fn block_weight_and_limits() {
+        let mut file = open_new_file("tests/blocks/test_block.bin".to_string(), true).unwrap();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+        let mut block = Block::parse(buffer).unwrap();
+        block.strip_witness();
+        assert_eq!(
+            block.weight(),
+            block.serialize().len() * WITNESS_SCALE_FACTOR
+        );
+        assert!(block.check_limits().is_ok());
+
+        block.transactions[0].outputs[0].script_pubkey = vec![0xac; MAX_BLOCK_SIGOPS_COST];
+        assert!(block.check_limits().is_err());
     }
 
     #[test]
@@ -236,6 +351,24 @@ mod tests {
                 && merkle_tree[4].len() == 2
                 && merkle_tree[5].len() == 1
         );
+        assert_eq!(
+            merkle_root(merkle_tree[0].clone()),
+            Some(block.header.merkle_root)
+        );
+        assert_eq!(merkle_root(vec![]), None);
+    }
+
+    #[test]
+    fn witness_commitment_of_transactions() {
+        let mut file = open_new_file("tests/blocks/test_block.bin".to_string(), true).unwrap();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+        let block = Block::parse(buffer).unwrap();
+
+        let script = witness_commitment_script(&block.transactions[1..]);
+        assert_eq!(script.len(), 38);
+        assert!(script.starts_with(&WITNESS_COMMITMENT_HEADER));
+        assert_ne!(script, witness_commitment_script(&block.transactions[2..]));
     }
 
     #[test]
@@ -247,18 +380,16 @@ mod tests {
 
         let merkle_tree = block.create_merkle_tree();
         let transactions_hashes = merkle_tree.get(0).unwrap();
-        let (flags, hashes) = block
-            .generate_merkle_path(transactions_hashes[6].clone())
-            .unwrap();
+        let (flags, hashes) = block.generate_merkle_path(transactions_hashes[6]).unwrap();
 
         assert_eq!(flags, vec![1, 1, 1, 0, 1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
         assert_eq!(hashes.len(), 6);
 
-        let merging = merge_hashes(hashes[2].clone(), hashes[3].clone());
-        let merging = merge_hashes(hashes[1].clone(), merging);
-        let merging = merge_hashes(hashes[0].clone(), merging);
-        let merging = merge_hashes(merging, hashes[4].clone());
-        let merging = merge_hashes(merging, hashes[5].clone());
+        let merging = merge_hashes(hashes[2], hashes[3]);
+        let merging = merge_hashes(hashes[1], merging);
+        let merging = merge_hashes(hashes[0], merging);
+        let merging = merge_hashes(merging, hashes[4]);
+        let merging = merge_hashes(merging, hashes[5]);
 
         assert_eq!(hashes.len(), 6);
         assert_eq!(merging, block.header.merkle_root);
@@ -277,4 +408,22 @@ mod tests {
         let block = Block::new(block_header, vec![]);
         assert_eq!(block.get_command(), "block");
     }
+
+    #[test]
+    fn stripped_segwit_block() {
+        let mut file = open_new_file("tests/blocks/test_block.bin".to_string(), true).unwrap();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+        let mut block = Block::parse(buffer).unwrap();
+        assert!(block.is_stripped());
+
+        block.transactions[0].inputs[0].witness = vec![vec![0; 32]];
+        assert!(!block.is_stripped());
+        let mut block = Block::parse(block.serialize()).unwrap();
+        assert!(!block.is_stripped());
+        assert!(block.create_merkle_root().is_ok());
+
+        block.strip_witness();
+        assert!(block.is_stripped());
+    }
 }
@@ -0,0 +1,112 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::hash32::Hash32,
+};
+
+/// Cada cuantos bloques se incluye un checkpoint en el mensaje 'cfcheckpt' (BIP157).
+pub const CFCHECKPT_INTERVAL: usize = 1000;
+
+/// Esta estructura representa al mensaje 'getcfcheckpt' de Bitcoin (BIP157), con el que un cliente liviano
+/// pide los headers de los filtros cada CFCHECKPT_INTERVAL bloques hasta el bloque stop_hash.
+/// - filter_type: Tipo de filtro pedido.
+/// - stop_hash: Hash del ultimo bloque.
+pub struct GetCFCheckpt {
+    pub filter_type: u8,
+    pub stop_hash: Hash32,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'getcfcheckpt'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for GetCFCheckpt {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.stop_hash.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let stop_hash = parser.extract_hash()?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self {
+            filter_type,
+            stop_hash,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'getcfcheckpt'
+/// Permite obtener el comando
+impl Message for GetCFCheckpt {
+    fn get_command(&self) -> String {
+        String::from("getcfcheckpt")
+    }
+}
+
+/// Esta estructura representa al mensaje 'cfcheckpt' de Bitcoin (BIP157), la respuesta a 'getcfcheckpt'.
+/// - filter_type: Tipo de los filtros.
+/// - stop_hash: Hash del ultimo bloque.
+/// - filter_headers: Headers de los filtros de los bloques en las alturas multiplo de CFCHECKPT_INTERVAL.
+pub struct CFCheckpt {
+    pub filter_type: u8,
+    pub stop_hash: Hash32,
+    pub filter_headers: Vec<Hash32>,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'cfcheckpt'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for CFCheckpt {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.stop_hash.encode(buffer);
+        self.filter_headers.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let stop_hash = parser.extract_hash()?;
+        let filter_headers = Vec::decode(parser)?;
+        Ok(Self {
+            filter_type,
+            stop_hash,
+            filter_headers,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'cfcheckpt'
+/// Permite obtener el comando
+impl Message for CFCheckpt {
+    fn get_command(&self) -> String {
+        String::from("cfcheckpt")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_cfcheckpt() {
+        let message = CFCheckpt {
+            filter_type: 0,
+            stop_hash: Hash32::new([1; 32]),
+            filter_headers: vec![Hash32::new([2; 32])],
+        };
+        let parsed = CFCheckpt::parse(message.serialize()).unwrap();
+        assert_eq!(parsed.stop_hash, Hash32::new([1; 32]));
+        assert_eq!(parsed.filter_headers, vec![Hash32::new([2; 32])]);
+
+        let request = GetCFCheckpt {
+            filter_type: 0,
+            stop_hash: Hash32::new([1; 32]),
+        };
+        assert_eq!(
+            GetCFCheckpt::parse(request.serialize()).unwrap().stop_hash,
+            Hash32::new([1; 32])
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::hash32::Hash32,
+};
+
+/// Cantidad maxima de headers de filtros que se pueden pedir en un mensaje 'getcfheaders' (BIP157).
+pub const MAX_GETCFHEADERS_SIZE: usize = 2000;
+
+/// Esta estructura representa al mensaje 'getcfheaders' de Bitcoin (BIP157), con el que un cliente liviano
+/// pide los hashes de los filtros de los bloques desde start_height hasta el bloque stop_hash (incluido).
+/// - filter_type: Tipo de filtro pedido.
+/// - start_height: Altura del primer bloque.
+/// - stop_hash: Hash del ultimo bloque.
+pub struct GetCFHeaders {
+    pub filter_type: u8,
+    pub start_height: u32,
+    pub stop_hash: Hash32,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'getcfheaders'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for GetCFHeaders {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.start_height.encode(buffer);
+        self.stop_hash.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let start_height = parser.extract_u32()?;
+        let stop_hash = parser.extract_hash()?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self {
+            filter_type,
+            start_height,
+            stop_hash,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'getcfheaders'
+/// Permite obtener el comando
+impl Message for GetCFHeaders {
+    fn get_command(&self) -> String {
+        String::from("getcfheaders")
+    }
+}
+
+/// Esta estructura representa al mensaje 'cfheaders' de Bitcoin (BIP157), la respuesta a 'getcfheaders'.
+/// Con el header del filtro anterior y los hashes de los filtros el cliente puede calcular los headers de cada filtro.
+/// - filter_type: Tipo de los filtros.
+/// - stop_hash: Hash del ultimo bloque.
+/// - previous_filter_header: Header del filtro del bloque anterior al primero.
+/// - filter_hashes: Hashes de los filtros de cada bloque.
+pub struct CFHeaders {
+    pub filter_type: u8,
+    pub stop_hash: Hash32,
+    pub previous_filter_header: Hash32,
+    pub filter_hashes: Vec<Hash32>,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'cfheaders'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for CFHeaders {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.stop_hash.encode(buffer);
+        self.previous_filter_header.encode(buffer);
+        self.filter_hashes.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let stop_hash = parser.extract_hash()?;
+        let previous_filter_header = parser.extract_hash()?;
+        let count = parser.extract_varint()? as usize;
+        if count > MAX_GETCFHEADERS_SIZE {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        let mut filter_hashes = vec![];
+        for _ in 0..count {
+            filter_hashes.push(parser.extract_hash()?);
+        }
+        Ok(Self {
+            filter_type,
+            stop_hash,
+            previous_filter_header,
+            filter_hashes,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'cfheaders'
+/// Permite obtener el comando
+impl Message for CFHeaders {
+    fn get_command(&self) -> String {
+        String::from("cfheaders")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_cfheaders() {
+        let message = CFHeaders {
+            filter_type: 0,
+            stop_hash: Hash32::new([1; 32]),
+            previous_filter_header: Hash32::new([2; 32]),
+            filter_hashes: vec![Hash32::new([3; 32]), Hash32::new([4; 32])],
+        };
+        let serialized = message.serialize();
+        assert_eq!(serialized.len(), 1 + 32 + 32 + 1 + 64);
+
+        let parsed = CFHeaders::parse(serialized).unwrap();
+        assert_eq!(parsed.previous_filter_header, Hash32::new([2; 32]));
+        assert_eq!(parsed.filter_hashes, message.filter_hashes);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::hash32::Hash32,
+};
+
+/// Cantidad maxima de filtros que se pueden pedir en un mensaje 'getcfilters' (BIP157).
+pub const MAX_GETCFILTERS_SIZE: usize = 1000;
+
+/// Esta estructura representa al mensaje 'getcfilters' de Bitcoin (BIP157), con el que un cliente liviano
+/// pide los filtros de los bloques desde start_height hasta el bloque stop_hash (incluido).
+/// - filter_type: Tipo de filtro pedido.
+/// - start_height: Altura del primer bloque.
+/// - stop_hash: Hash del ultimo bloque.
+pub struct GetCFilters {
+    pub filter_type: u8,
+    pub start_height: u32,
+    pub stop_hash: Hash32,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'getcfilters'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for GetCFilters {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.start_height.encode(buffer);
+        self.stop_hash.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let start_height = parser.extract_u32()?;
+        let stop_hash = parser.extract_hash()?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self {
+            filter_type,
+            start_height,
+            stop_hash,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'getcfilters'
+/// Permite obtener el comando
+impl Message for GetCFilters {
+    fn get_command(&self) -> String {
+        String::from("getcfilters")
+    }
+}
+
+/// Esta estructura representa al mensaje 'cfilter' de Bitcoin (BIP157), la respuesta a 'getcfilters' con el filtro de un bloque.
+/// - filter_type: Tipo del filtro.
+/// - block_hash: Hash del bloque.
+/// - filter: Filtro serializado.
+pub struct CFilter {
+    pub filter_type: u8,
+    pub block_hash: Hash32,
+    pub filter: Vec<u8>,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'cfilter'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for CFilter {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.filter_type);
+        self.block_hash.encode(buffer);
+        self.filter.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter_type = parser.extract_u8()?;
+        let block_hash = parser.extract_hash()?;
+        let filter = Vec::decode(parser)?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self {
+            filter_type,
+            block_hash,
+            filter,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'cfilter'
+/// Permite obtener el comando
+impl Message for CFilter {
+    fn get_command(&self) -> String {
+        String::from("cfilter")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_getcfilters() {
+        let message = GetCFilters {
+            filter_type: 0,
+            start_height: 100,
+            stop_hash: Hash32::new([1; 32]),
+        };
+        let serialized = message.serialize();
+        assert_eq!(serialized.len(), 37);
+
+        let parsed = GetCFilters::parse(serialized).unwrap();
+        assert_eq!(parsed.start_height, 100);
+        assert_eq!(parsed.stop_hash, Hash32::new([1; 32]));
+    }
+
+    #[test]
+    fn serialize_and_parse_cfilter() {
+        let message = CFilter {
+            filter_type: 0,
+            block_hash: Hash32::new([2; 32]),
+            filter: vec![1, 0x9d, 0xfc, 0xa8],
+        };
+        let parsed = CFilter::parse(message.serialize()).unwrap();
+        assert_eq!(parsed.block_hash, Hash32::new([2; 32]));
+        assert_eq!(parsed.filter, vec![1, 0x9d, 0xfc, 0xa8]);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta estructura representa al mensaje 'feefilter' de Bitcoin (BIP133), con el que un peer indica
+/// que no le anuncien transacciones con un fee menor al indicado.
+/// - fee_rate: Fee minimo en satoshis por kilobyte.
+pub struct FeeFilter {
+    pub fee_rate: u64,
+}
+
+impl FeeFilter {
+    /// Crea un mensaje 'feefilter' con el fee minimo recibido, en satoshis por kilobyte.
+    pub fn new(fee_rate: u64) -> Self {
+        Self { fee_rate }
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'feefilter'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for FeeFilter {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.fee_rate.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let fee_rate = parser.extract_u64()?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self { fee_rate })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'feefilter'
+/// Permite obtener el comando
+impl Message for FeeFilter {
+    fn get_command(&self) -> String {
+        String::from("feefilter")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_fee_filter() {
+        let fee_filter = FeeFilter::new(1000);
+        assert_eq!(fee_filter.serialize(), vec![0xe8, 0x03, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            FeeFilter::parse(fee_filter.serialize()).unwrap(),
+            fee_filter
+        );
+        assert!(FeeFilter::parse(vec![0xe8, 0x03]).is_err());
+    }
+}
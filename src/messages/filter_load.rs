@@ -0,0 +1,100 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::bloom_filter::BloomFilter,
+};
+
+/// El peer agrega al filtro los outpoints de todas las salidas que coincidan, para detectar cuando se gastan.
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+
+#[derive(Debug, Clone)]
+/// Esta estructura representa al mensaje 'filterload' de Bitcoin (BIP37), con el que se le indica a un peer
+/// que solo envie las transacciones que coincidan con el bloom filter, en los inv y en los merkleblock.
+/// - filter: Bloom filter con los elementos de las transacciones buscadas.
+/// - flags: Indica como debe actualizar el peer el filtro al encontrar coincidencias.
+pub struct FilterLoad {
+    pub filter: BloomFilter,
+    pub flags: u8,
+}
+
+impl FilterLoad {
+    /// Crea un mensaje 'filterload' con el filtro recibido, que se actualiza con los outpoints de las coincidencias.
+    pub fn new(filter: BloomFilter) -> Self {
+        Self {
+            filter,
+            flags: BLOOM_UPDATE_ALL,
+        }
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'filterload'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for FilterLoad {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.filter.encode(buffer);
+        buffer.push(self.flags);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let filter = BloomFilter::decode(parser)?;
+        let flags = parser.extract_u8()?;
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self { filter, flags })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'filterload'
+/// Permite obtener el comando
+impl Message for FilterLoad {
+    fn get_command(&self) -> String {
+        String::from("filterload")
+    }
+}
+
+#[derive(Debug)]
+/// Esta estructura representa al mensaje 'filterclear' de Bitcoin (BIP37), un mensaje vacio con el que
+/// un peer descarta el filtro cargado y vuelve a recibir todas las transacciones.
+pub struct FilterClear {}
+
+/// Implementa el trait BtcEncode para el mensaje 'filterclear'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for FilterClear {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Self {})
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'filterclear'
+/// Permite obtener el comando
+impl Message for FilterClear {
+    fn get_command(&self) -> String {
+        String::from("filterclear")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_filter_load() {
+        let mut filter = BloomFilter::new(3, 0.01, 0);
+        filter.insert(&[1, 2, 3]);
+        let filter_load = FilterLoad::new(filter.clone());
+
+        let serialized = filter_load.serialize();
+        assert_eq!(serialized.last(), Some(&BLOOM_UPDATE_ALL));
+
+        let parsed = FilterLoad::parse(serialized).unwrap();
+        assert_eq!(parsed.filter, filter);
+        assert_eq!(parsed.flags, BLOOM_UPDATE_ALL);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
+
+#[derive(Debug)]
+/// GetAddr es un mensaje vacio que se envia para pedirle a un peer direcciones de otros nodos de la red.
+/// El peer responde con un mensaje addr.
+pub struct GetAddr {}
+
+impl GetAddr {
+    /// Crea un nuevo mensaje de solicitud de direcciones.
+    pub fn new() -> Self {
+        GetAddr {}
+    }
+}
+
+impl Default for GetAddr {
+    fn default() -> Self {
+        GetAddr::new()
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje de solicitud de direcciones.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for GetAddr {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(GetAddr {})
+    }
+}
+
+/// Implementa el trait Message para el mensaje de solicitud de direcciones.
+/// Permite obtener el comando
+impl Message for GetAddr {
+    fn get_command(&self) -> String {
+        String::from("getaddr")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_get_addr() {
+        let get_addr = GetAddr::new();
+        assert_eq!(get_addr.get_command(), String::from("getaddr"));
+        assert!(GetAddr::parse(get_addr.serialize()).is_ok());
+        assert!(GetAddr::parse(vec![0x00]).is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{message::Message, structs::inventory::Inventory};
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::inventory::Inventory,
+};
 
 use super::inv::Inv;
 
@@ -20,44 +25,53 @@ impl GetData {
     }
 }
 
-/// Implementa el trait Message para el mensaje 'getdata'
-/// Permite serializar, parsear y obtener el comando
-impl Message for GetData {
-    fn serialize(&self) -> Vec<u8> {
-        self.inv.serialize()
-    }
-
-    fn get_command(&self) -> String {
-        String::from("getdata")
+/// Implementa el trait BtcEncode para el mensaje 'getdata'
+/// Permite serializarlo y parsearlo, igual que un mensaje 'inv'
+impl BtcEncode for GetData {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.inv.encode(buffer);
     }
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, crate::error::CustomError> {
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         Ok(Self {
-            inv: Inv::parse(buffer)?,
+            inv: Inv::decode(parser)?,
         })
     }
 }
 
+/// Implementa el trait Message para el mensaje 'getdata'
+/// Permite obtener el comando
+impl Message for GetData {
+    fn get_command(&self) -> String {
+        String::from("getdata")
+    }
+}
+
 #[cfg(test)]
 
 mod tests {
 
-    use crate::structs::inventory::{Inventory, InventoryType};
+    use crate::structs::{
+        hash32::Hash32,
+        inventory::{Inventory, InventoryType},
+    };
 
     use super::*;
 
     #[test]
     fn serialize_get_data() {
+        let mut hash = [0; 32];
+        hash[30] = 0xab;
+        hash[31] = 0xcd;
         let inv = Inv::new(vec![Inventory::new(
             InventoryType::Block,
-            vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xab, 0xcd],
+            Hash32::new(hash),
         )]);
         let get_data = GetData { inv };
         let serialized_get_data = get_data.serialize();
-        assert_eq!(
-            serialized_get_data,
-            vec![1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xab, 0xcd,]
-        );
+        let mut expected = vec![1, 2, 0, 0, 0];
+        expected.extend(hash);
+        assert_eq!(serialized_get_data, expected);
     }
 
     #[test]
@@ -72,10 +86,10 @@ mod tests {
         assert_eq!(inventories[0].inventory_type, InventoryType::Block);
         assert_eq!(
             inventories[0].hash,
-            vec![
+            Hash32::new([
                 0, 0, 0, 0, 0, 0, 0, 0, 0xab, 0xcd, 4, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0xef,
                 0xaa, 3, 12, 7, 0, 7, 8,
-            ]
+            ])
         );
     }
 
@@ -1,7 +1,8 @@
 use crate::{
     error::CustomError,
     message::Message,
-    parser::{BufferParser, VarIntSerialize},
+    parser::{BtcEncode, BufferParser},
+    structs::hash32::Hash32,
 };
 
 #[derive(PartialEq, Debug)]
@@ -9,13 +10,13 @@ use crate::{
 /// Esta estructura representa el mensaje 'getheaders' de Bitcoin, el cual se utiliza para pedirle a un nodo que nos envie los headers de los bloques que tiene a partir de un bloque especifico.
 pub struct GetHeaders {
     pub version: i32,
-    pub block_locator_hashes: Vec<Vec<u8>>,
-    pub hash_stop: Vec<u8>,
+    pub block_locator_hashes: Vec<Hash32>,
+    pub hash_stop: Hash32,
 }
 
 impl GetHeaders {
     /// Esta funcion se encarga de crear un nuevo mensaje 'getheaders' con la version, el block locator hashes y el hash stop que se reciben por parametro
-    pub fn new(version: i32, block_locator_hashes: Vec<Vec<u8>>, hash_stop: Vec<u8>) -> Self {
+    pub fn new(version: i32, block_locator_hashes: Vec<Hash32>, hash_stop: Hash32) -> Self {
         GetHeaders {
             version,
             block_locator_hashes,
@@ -24,43 +25,24 @@ impl GetHeaders {
     }
 }
 
-/// Implementa el trait Message para el mensaje 'getheaders'.
-/// Permite serializar, parsear y obtener el comando
-impl Message for GetHeaders {
-    fn get_command(&self) -> String {
-        String::from("getheaders")
-    }
-
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&self.version.to_le_bytes());
-        buffer.extend(self.block_locator_hashes.len().to_varint_bytes());
-        for hash in &self.block_locator_hashes {
-            buffer.extend(hash);
-        }
-        buffer.extend(&self.hash_stop);
-        buffer
+/// Implementa el trait BtcEncode para el mensaje 'getheaders'.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for GetHeaders {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.version.encode(buffer);
+        self.block_locator_hashes.encode(buffer);
+        self.hash_stop.encode(buffer);
     }
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let mut parser = BufferParser::new(buffer);
-
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         if parser.len() < 37 {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
         let version = parser.extract_i32()?;
-        let hash_count = parser.extract_varint()?;
+        let block_locator_hashes = Vec::decode(parser)?;
+        let hash_stop = parser.extract_hash()?;
 
-        let mut block_locator_hashes: Vec<Vec<u8>> = vec![];
-
-        while parser.len() > 32 {
-            let hash = parser.extract_buffer(32)?.to_vec();
-            block_locator_hashes.push(hash);
-        }
-
-        let hash_stop = parser.extract_buffer(32)?.to_vec();
-
-        if !parser.is_empty() || block_locator_hashes.len() != hash_count as usize {
+        if !parser.is_empty() {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
 
@@ -72,6 +54,14 @@ impl Message for GetHeaders {
     }
 }
 
+/// Implementa el trait Message para el mensaje 'getheaders'.
+/// Permite obtener el comando
+impl Message for GetHeaders {
+    fn get_command(&self) -> String {
+        String::from("getheaders")
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -81,9 +71,8 @@ mod tests {
 
     #[test]
     fn get_headers_serialize() {
-        let mut empty_stop_hash: Vec<u8> = vec![];
-        empty_stop_hash.resize(32, 0);
-        let get_headers = GetHeaders::new(70015, [GENESIS.to_vec()].to_vec(), empty_stop_hash);
+        let empty_stop_hash = Hash32::default();
+        let get_headers = GetHeaders::new(70015, [Hash32::new(GENESIS)].to_vec(), empty_stop_hash);
         let serialized_getheaders = get_headers.serialize();
         let parsed_getheaders = GetHeaders::parse(serialized_getheaders).unwrap();
         assert_eq!(get_headers, parsed_getheaders);
@@ -103,12 +92,12 @@ mod tests {
         assert_eq!(parsed_getheaders.block_locator_hashes.len(), 1);
         assert_eq!(
             parsed_getheaders.block_locator_hashes[0],
-            [
+            Hash32::new([
                 155, 77, 153, 101, 77, 212, 76, 182, 137, 41, 103, 109, 128, 43, 106, 32, 200, 118,
                 162, 103, 247, 127, 103, 118, 167, 48, 41, 155, 158, 132, 88, 193
-            ]
+            ])
         );
-        assert_eq!(parsed_getheaders.hash_stop, vec![0; 32]);
+        assert_eq!(parsed_getheaders.hash_stop, Hash32::default());
     }
 
     #[test]
@@ -134,7 +123,7 @@ mod tests {
         let parsed_getheaders = GetHeaders::parse(serialized_getheaders).unwrap();
         assert_eq!(parsed_getheaders.version, 70015);
         assert_eq!(parsed_getheaders.block_locator_hashes.len(), 0);
-        assert_eq!(parsed_getheaders.hash_stop, vec![0; 32]);
+        assert_eq!(parsed_getheaders.hash_stop, Hash32::default());
     }
 
     #[test]
@@ -156,7 +145,7 @@ mod tests {
 
     #[test]
     fn get_command_getheaders() {
-        let getheaders = GetHeaders::new(70012, vec![], vec![]);
+        let getheaders = GetHeaders::new(70012, vec![], Hash32::default());
         assert_eq!(getheaders.get_command(), "getheaders");
     }
 }
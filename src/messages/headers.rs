@@ -1,7 +1,7 @@
 use crate::{
     error::CustomError,
     message::Message,
-    parser::{BufferParser, VarIntSerialize},
+    parser::{BtcEncode, BufferParser, VarIntSerialize},
     structs::block_header::BlockHeader,
 };
 
@@ -25,38 +25,30 @@ impl Default for Headers {
     }
 }
 
-/// Implementa el trair Message para Headers
-/// Permite serializar, parsear y obtener el comando
-impl Message for Headers {
-    fn get_command(&self) -> String {
-        String::from("headers")
-    }
-
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
+/// Implementa el trait BtcEncode para Headers
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Headers {
+    fn encode(&self, buffer: &mut Vec<u8>) {
         buffer.extend(self.headers.len().to_varint_bytes());
         for header in &self.headers {
-            buffer.extend(&header.serialize());
-            buffer.extend(0_u8.to_le_bytes());
+            header.encode(buffer);
+            buffer.push(0);
         }
-        buffer
     }
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let mut parser = BufferParser::new(buffer);
-
+    /// Parsea los headers desde el parser, que puede estar leyendo el payload directamente del stream.
+    /// Cada header ocupa 81 bytes (80 del header y 1 de la cantidad de transacciones, siempre 0),
+    /// y no deben quedar bytes sin leer al terminar.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         let header_count = parser.extract_varint()?;
-        if parser.len() % 81 != 0 {
-            return Err(CustomError::SerializedBufferIsInvalid);
-        }
 
         let mut headers = vec![];
-        while parser.len() >= 81 {
-            headers.push(BlockHeader::parse(parser.extract_buffer(80)?.to_vec())?);
+        for _ in 0..header_count {
+            headers.push(BlockHeader::decode(parser)?);
             parser.extract_buffer(1)?;
         }
 
-        if header_count != headers.len() as u64 {
+        if !parser.is_empty() {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
 
@@ -64,6 +56,14 @@ impl Message for Headers {
     }
 }
 
+/// Implementa el trait Message para Headers
+/// Permite obtener el comando
+impl Message for Headers {
+    fn get_command(&self) -> String {
+        String::from("headers")
+    }
+}
+
 #[cfg(test)]
 
 mod tests {
@@ -1,5 +1,7 @@
 use crate::{
-    error::CustomError, message::Message, parser::BufferParser, parser::VarIntSerialize,
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
     structs::inventory::Inventory,
 };
 
@@ -16,44 +18,35 @@ impl Inv {
     }
 }
 
-/// Implementa el trait Message para el mensaje inv.
-/// Permite serializar, parsear y obtener el comando
-impl Message for Inv {
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.inventories.len().to_varint_bytes());
-        for inventory in &self.inventories {
-            buffer.extend(inventory.serialize());
-        }
-        buffer
-    }
-
-    fn get_command(&self) -> String {
-        String::from("inv")
+/// Implementa el trait BtcEncode para el mensaje inv.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Inv {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.inventories.encode(buffer);
     }
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, crate::error::CustomError> {
-        let mut parser = BufferParser::new(buffer);
-
-        let count = parser.extract_varint()? as usize;
-
-        if parser.len() % 36 != 0 {
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let inventories = Vec::decode(parser)?;
+        if !parser.is_empty() {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
-
-        let mut inventories = vec![];
-        for _i in 0..count {
-            inventories.push(Inventory::parse(parser.extract_buffer(36)?.to_vec())?);
-        }
         Ok(Self { inventories })
     }
 }
 
+/// Implementa el trait Message para el mensaje inv.
+/// Permite obtener el comando
+impl Message for Inv {
+    fn get_command(&self) -> String {
+        String::from("inv")
+    }
+}
+
 #[cfg(test)]
 
 mod tests {
 
-    use crate::structs::inventory::InventoryType;
+    use crate::structs::{hash32::Hash32, inventory::InventoryType};
 
     use super::*;
 
@@ -61,11 +54,10 @@ mod tests {
     fn inv_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::Block,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let inv = Inv::new(vec![inventory]);
         let buffer = inv.serialize();
@@ -77,15 +69,17 @@ mod tests {
     fn inv_invalid_buffer() {
         let inventory = Inventory {
             inventory_type: InventoryType::Block,
-            hash: [
+            hash: Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
-                124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220, 100, 200, 129,
-                233, 45, 56, 82, 56, 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 46,
-            ]
-            .to_vec(),
+                124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
+            ]),
         };
         let inv = Inv::new(vec![inventory]);
-        let buffer = inv.serialize();
+        let mut buffer = inv.serialize();
+        buffer.extend([
+            100, 200, 129, 233, 45, 56, 82, 56, 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110,
+            112, 46,
+        ]);
         let parsed_inv = Inv::parse(buffer);
         assert!(parsed_inv.is_err());
     }
@@ -0,0 +1,57 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
+
+#[derive(Debug)]
+/// Mempool es un mensaje vacio con el que se le pide a un peer que anuncie, mediante mensajes inv,
+/// las transacciones pendientes que tiene en su mempool.
+pub struct Mempool {}
+
+impl Mempool {
+    /// Crea un nuevo mensaje mempool.
+    pub fn new() -> Self {
+        Mempool {}
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new()
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje mempool.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Mempool {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(Mempool {})
+    }
+}
+
+/// Implementa el trait Message para el mensaje mempool.
+/// Permite obtener el comando
+impl Message for Mempool {
+    fn get_command(&self) -> String {
+        String::from("mempool")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_mempool() {
+        let mempool = Mempool::new();
+        assert_eq!(mempool.serialize(), Vec::<u8>::new());
+        assert!(Mempool::parse(mempool.serialize()).is_ok());
+        assert!(Mempool::parse(vec![0x00]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::{block_header::BlockHeader, hash32::Hash32},
+};
+
+use super::{
+    block::{merge_hashes, Block},
+    transaction::Transaction,
+};
+
+/// Esta estructura representa al mensaje 'merkleblock' de Bitcoin (BIP37), que envia un peer cuando se le pide un bloque filtrado.
+/// Contiene el header del bloque y un partial merkle tree con el que se prueba que las transacciones que coinciden con el filtro
+/// pertenecen al bloque, sin tener que descargar el resto. Luego del merkleblock el peer envia esas transacciones en mensajes 'tx'.
+/// - header: Header del bloque.
+/// - total_transactions: Cantidad de transacciones del bloque completo.
+/// - hashes: Hashes del partial merkle tree, en el orden en que se recorre el arbol (primero en profundidad).
+/// - flags: Bits que indican, para cada nodo recorrido, si es ancestro de una transaccion que coincide.
+pub struct MerkleBlock {
+    pub header: BlockHeader,
+    pub total_transactions: u32,
+    pub hashes: Vec<Hash32>,
+    pub flags: Vec<u8>,
+}
+
+impl MerkleBlock {
+    /// Reconstruye el merkle root a partir del partial merkle tree y lo compara con el del header.
+    /// Devuelve los hashes de las transacciones que coinciden con el filtro, en el orden del bloque.
+    /// Devuelve CustomError si el arbol esta mal formado o si el merkle root no coincide.
+    pub fn extract_matches(&self) -> Result<Vec<Hash32>, CustomError> {
+        if self.total_transactions == 0
+            || self.hashes.len() > self.total_transactions as usize
+            || self.flags.len() * 8 < self.hashes.len()
+        {
+            return Err(CustomError::InvalidMerkleRoot);
+        }
+
+        let mut height = 0;
+        while self.tree_width(height) > 1 {
+            height += 1;
+        }
+
+        let mut traversal = Traversal::default();
+        let merkle_root = self.traverse(height, 0, &mut traversal)?;
+
+        if traversal.hashes_used != self.hashes.len()
+            || traversal.bits_used.div_ceil(8) != self.flags.len()
+            || merkle_root != self.header.merkle_root
+        {
+            return Err(CustomError::InvalidMerkleRoot);
+        }
+        Ok(traversal.matches)
+    }
+
+    /// Cantidad de nodos del arbol en la altura recibida, siendo 0 la altura de las transacciones.
+    fn tree_width(&self, height: u32) -> u32 {
+        (self.total_transactions + (1 << height) - 1) >> height
+    }
+
+    fn traverse(
+        &self,
+        height: u32,
+        position: u32,
+        traversal: &mut Traversal,
+    ) -> Result<Hash32, CustomError> {
+        let Some(flags_byte) = self.flags.get(traversal.bits_used / 8) else {
+            return Err(CustomError::InvalidMerkleRoot);
+        };
+        let parent_of_match = flags_byte & (1 << (traversal.bits_used % 8)) != 0;
+        traversal.bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let Some(hash) = self.hashes.get(traversal.hashes_used) else {
+                return Err(CustomError::InvalidMerkleRoot);
+            };
+            traversal.hashes_used += 1;
+            if height == 0 && parent_of_match {
+                traversal.matches.push(*hash);
+            }
+            return Ok(*hash);
+        }
+
+        let left = self.traverse(height - 1, position * 2, traversal)?;
+        if position * 2 + 1 >= self.tree_width(height - 1) {
+            return Ok(merge_hashes(left, left));
+        }
+        let right = self.traverse(height - 1, position * 2 + 1, traversal)?;
+        // Dos ramas iguales permitirian duplicar transacciones sin cambiar el merkle root (CVE-2012-2459)
+        if right == left {
+            return Err(CustomError::InvalidMerkleRoot);
+        }
+        Ok(merge_hashes(left, right))
+    }
+}
+
+#[derive(Default)]
+/// Estado del recorrido del partial merkle tree.
+struct Traversal {
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<Hash32>,
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'merkleblock'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for MerkleBlock {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.header.encode(buffer);
+        self.total_transactions.encode(buffer);
+        self.hashes.encode(buffer);
+        self.flags.encode(buffer);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let header = BlockHeader::decode(parser)?;
+        let total_transactions = parser.extract_u32()?;
+        let hash_count = parser.extract_varint()? as usize;
+        if hash_count > total_transactions as usize {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        let mut hashes = vec![];
+        for _ in 0..hash_count {
+            hashes.push(parser.extract_hash()?);
+        }
+        let flags = Vec::decode(parser)?;
+
+        Ok(Self {
+            header,
+            total_transactions,
+            hashes,
+            flags,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'merkleblock'
+/// Permite obtener el comando
+impl Message for MerkleBlock {
+    fn get_command(&self) -> String {
+        String::from("merkleblock")
+    }
+}
+
+/// FilteredBlock es un bloque que se arma con las transacciones que envia un peer luego de un merkleblock.
+/// Solo contiene las transacciones que coincidieron con el filtro, por lo que no se puede validar su merkle root
+/// con create_merkle_root; la inclusion de las transacciones se verifica con el partial merkle tree.
+/// - header: Header del bloque.
+/// - matches: Hashes de las transacciones que coincidieron, en el orden del bloque.
+/// - transactions: Transacciones recibidas hasta el momento.
+pub struct FilteredBlock {
+    header: BlockHeader,
+    matches: Vec<Hash32>,
+    transactions: Vec<Transaction>,
+}
+
+impl FilteredBlock {
+    /// Verifica el merkleblock y crea el bloque filtrado, a la espera de las transacciones que coincidieron.
+    pub fn new(merkle_block: MerkleBlock) -> Result<Self, CustomError> {
+        let matches = merkle_block.extract_matches()?;
+        Ok(Self {
+            header: merkle_block.header,
+            matches,
+            transactions: vec![],
+        })
+    }
+
+    /// Devuelve el hash del bloque.
+    pub fn hash(&self) -> Hash32 {
+        *self.header.hash()
+    }
+
+    /// Agrega la transaccion si es una de las que coincidieron y todavia no se recibio.
+    /// Devuelve la transaccion si no pertenece al bloque.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Option<Transaction> {
+        let hash = transaction.hash();
+        if !self.matches.contains(&hash) || self.transactions.iter().any(|tx| tx.hash() == hash) {
+            return Some(transaction);
+        }
+        self.transactions.push(transaction);
+        None
+    }
+
+    /// Indica si ya se recibieron todas las transacciones que coincidieron.
+    pub fn is_complete(&self) -> bool {
+        self.transactions.len() == self.matches.len()
+    }
+
+    /// Devuelve el bloque con las transacciones recibidas, en el orden del bloque completo.
+    pub fn into_block(mut self) -> Block {
+        self.transactions.sort_by_key(|transaction| {
+            let hash = transaction.hash();
+            self.matches.iter().position(|matched| *matched == hash)
+        });
+        Block::new(self.header, self.transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::utils::open_new_file;
+
+    use super::*;
+
+    fn test_block() -> Block {
+        let mut file = open_new_file("tests/blocks/test_block.bin".to_string(), true).unwrap();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+        Block::parse(buffer).unwrap()
+    }
+
+    /// Arma el merkleblock de una transaccion del bloque a partir de su merkle path, empaquetando los flags en bits.
+    fn merkle_block_for(block: &Block, index: usize) -> MerkleBlock {
+        let (path_flags, hashes) = block
+            .generate_merkle_path(block.transactions[index].hash())
+            .unwrap();
+        let mut flags = vec![0; path_flags.len() / 8];
+        for (bit, flag) in path_flags.iter().enumerate() {
+            flags[bit / 8] |= flag << (bit % 8);
+        }
+        MerkleBlock {
+            header: block.header.clone(),
+            total_transactions: block.transactions.len() as u32,
+            hashes,
+            flags,
+        }
+    }
+
+    #[test]
+    fn merkle_block_extracts_matches() {
+        let block = test_block();
+        let merkle_block = merkle_block_for(&block, 6);
+        assert_eq!(
+            merkle_block.extract_matches().unwrap(),
+            vec![block.transactions[6].hash()]
+        );
+
+        let parsed = MerkleBlock::parse(merkle_block.serialize()).unwrap();
+        assert_eq!(parsed.hashes, merkle_block.hashes);
+        assert_eq!(parsed.flags, merkle_block.flags);
+    }
+
+    #[test]
+    fn merkle_block_with_invalid_hash_fails() {
+        let block = test_block();
+        let mut merkle_block = merkle_block_for(&block, 6);
+        merkle_block.hashes[0] = Hash32::default();
+        assert!(merkle_block.extract_matches().is_err());
+
+        let mut merkle_block = merkle_block_for(&block, 6);
+        merkle_block.hashes.pop();
+        assert!(merkle_block.extract_matches().is_err());
+    }
+
+    #[test]
+    fn filtered_block_waits_for_matched_transactions() {
+        let block = test_block();
+        let mut filtered_block = FilteredBlock::new(merkle_block_for(&block, 6)).unwrap();
+        assert!(!filtered_block.is_complete());
+
+        assert!(filtered_block
+            .add_transaction(block.transactions[5].clone())
+            .is_some());
+        assert!(filtered_block
+            .add_transaction(block.transactions[6].clone())
+            .is_none());
+        assert!(filtered_block.is_complete());
+
+        let filtered = filtered_block.into_block();
+        assert_eq!(filtered.header.hash(), block.header.hash());
+        assert_eq!(filtered.transactions.len(), 1);
+    }
+}
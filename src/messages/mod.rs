@@ -1,11 +1,24 @@
+pub mod addr;
+pub mod addr_v2;
 pub mod block;
+pub mod cfcheckpt;
+pub mod cfheaders;
+pub mod cfilters;
+pub mod fee_filter;
+pub mod filter_load;
+pub mod get_addr;
 pub mod get_data;
 pub mod get_headers;
 pub mod headers;
 pub mod inv;
+pub mod mempool;
+pub mod merkle_block;
 pub mod not_found;
 pub mod ping_pong;
+pub mod reject;
+pub mod send_addr_v2;
 pub mod send_headers;
 pub mod transaction;
 pub mod ver_ack;
 pub mod version;
+pub mod wtxid_relay;
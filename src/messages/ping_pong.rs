@@ -1,4 +1,8 @@
-use crate::{error::CustomError, message::Message, parser::BufferParser};
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
 
 const NONCE_BYTES: usize = 8;
 
@@ -7,53 +11,57 @@ pub struct Ping {
     pub nonce: u64,
 }
 
-/// Implementa el trait Message para el mensaje ping.
-/// Permite serializar, parsear y obtener el comando
-impl Message for Ping {
-    fn get_command(&self) -> String {
-        String::from("ping")
-    }
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&self.nonce.to_le_bytes());
-        buffer
+/// Implementa el trait BtcEncode para el mensaje ping.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Ping {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.nonce.encode(buffer);
     }
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        if buffer.len() != NONCE_BYTES {
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if parser.len() != NONCE_BYTES {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
-        let mut parser = BufferParser::new(buffer);
         let nonce = parser.extract_u64()?;
         Ok(Ping { nonce })
     }
 }
 
+/// Implementa el trait Message para el mensaje ping.
+/// Permite obtener el comando
+impl Message for Ping {
+    fn get_command(&self) -> String {
+        String::from("ping")
+    }
+}
+
 /// Esta es la estructura de un mensaje pong, la cual contiene un nonce que debe ser igual al nonce del mensaje ping
 pub struct Pong {
     pub nonce: u64,
 }
 
-/// Implementa el trait Message para el mensaje pong.
-/// Permite serializar, parsear y obtener el comando
-impl Message for Pong {
-    fn get_command(&self) -> String {
-        String::from("pong")
-    }
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&self.nonce.to_le_bytes());
-        buffer
+/// Implementa el trait BtcEncode para el mensaje pong.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Pong {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.nonce.encode(buffer);
     }
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        if buffer.len() != NONCE_BYTES {
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if parser.len() != NONCE_BYTES {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
-        let mut parser = BufferParser::new(buffer);
         let nonce = parser.extract_u64()?;
         Ok(Pong { nonce })
     }
 }
 
+/// Implementa el trait Message para el mensaje pong.
+/// Permite obtener el comando
+impl Message for Pong {
+    fn get_command(&self) -> String {
+        String::from("pong")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
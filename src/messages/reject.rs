@@ -0,0 +1,129 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+    structs::hash32::Hash32,
+};
+
+/// Codigos con los que un nodo indica por que rechazo un mensaje (BIP61).
+pub const REJECT_MALFORMED: u8 = 0x01;
+pub const REJECT_INVALID: u8 = 0x10;
+pub const REJECT_OBSOLETE: u8 = 0x11;
+pub const REJECT_DUPLICATE: u8 = 0x12;
+pub const REJECT_NONSTANDARD: u8 = 0x40;
+pub const REJECT_DUST: u8 = 0x41;
+pub const REJECT_INSUFFICIENTFEE: u8 = 0x42;
+pub const REJECT_CHECKPOINT: u8 = 0x43;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta estructura representa al mensaje 'reject' de Bitcoin (BIP61), con el que un nodo indica que rechazo un mensaje.
+/// Los nodos actuales ya no lo envian, pero los mas viejos lo usan para explicar por que rechazaron una transaccion o un bloque.
+/// - message: Comando del mensaje rechazado.
+/// - code: Codigo del motivo del rechazo.
+/// - reason: Descripcion del motivo del rechazo.
+/// - data: Hash de la transaccion o del bloque rechazado, si el mensaje era 'tx' o 'block'.
+pub struct Reject {
+    pub message: String,
+    pub code: u8,
+    pub reason: String,
+    pub data: Option<Hash32>,
+}
+
+impl Reject {
+    /// Crea un mensaje 'reject' para el mensaje con el comando recibido.
+    pub fn new(message: String, code: u8, reason: String, data: Option<Hash32>) -> Self {
+        Self {
+            message,
+            code,
+            reason,
+            data,
+        }
+    }
+
+    /// Devuelve el nombre del codigo de rechazo, como lo muestra Bitcoin Core.
+    pub fn code_name(&self) -> &'static str {
+        match self.code {
+            REJECT_MALFORMED => "malformed",
+            REJECT_INVALID => "invalid",
+            REJECT_OBSOLETE => "obsolete",
+            REJECT_DUPLICATE => "duplicate",
+            REJECT_NONSTANDARD => "nonstandard",
+            REJECT_DUST => "dust",
+            REJECT_INSUFFICIENTFEE => "insufficientfee",
+            REJECT_CHECKPOINT => "checkpoint",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje 'reject'
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Reject {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.message.encode(buffer);
+        buffer.push(self.code);
+        self.reason.encode(buffer);
+        if let Some(data) = &self.data {
+            data.encode(buffer);
+        }
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let message = String::decode(parser)?;
+        let code = parser.extract_u8()?;
+        let reason = String::decode(parser)?;
+        let data = match parser.is_empty() {
+            true => None,
+            false => Some(parser.extract_hash()?),
+        };
+        Ok(Self {
+            message,
+            code,
+            reason,
+            data,
+        })
+    }
+}
+
+/// Implementa el trait Message para el mensaje 'reject'
+/// Permite obtener el comando
+impl Message for Reject {
+    fn get_command(&self) -> String {
+        String::from("reject")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse_reject() {
+        let reject = Reject::new(
+            String::from("tx"),
+            REJECT_INSUFFICIENTFEE,
+            String::from("min relay fee not met"),
+            Some(Hash32::new([1; 32])),
+        );
+        let serialized = reject.serialize();
+        assert_eq!(serialized[..4], [2, b't', b'x', REJECT_INSUFFICIENTFEE]);
+        assert_eq!(serialized.len(), 1 + 2 + 1 + 1 + 21 + 32);
+
+        let parsed = Reject::parse(serialized).unwrap();
+        assert_eq!(parsed, reject);
+        assert_eq!(parsed.code_name(), "insufficientfee");
+    }
+
+    #[test]
+    fn parse_reject_without_data() {
+        let reject = Reject::new(
+            String::from("version"),
+            REJECT_OBSOLETE,
+            String::from("Version must be 70001 or greater"),
+            None,
+        );
+        let parsed = Reject::parse(reject.serialize()).unwrap();
+        assert_eq!(parsed.data, None);
+        assert_eq!(parsed.code_name(), "obsolete");
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
+
+#[derive(Debug)]
+/// SendAddrV2 es un mensaje vacio que se envia entre los mensajes version y verack (BIP155).
+/// Indica al peer que preferimos recibir las direcciones en mensajes addrv2 en lugar de addr.
+pub struct SendAddrV2 {}
+
+impl SendAddrV2 {
+    /// Crea un nuevo mensaje de preferencia de addrv2.
+    pub fn new() -> Self {
+        SendAddrV2 {}
+    }
+}
+
+impl Default for SendAddrV2 {
+    fn default() -> Self {
+        SendAddrV2::new()
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje de preferencia de addrv2.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for SendAddrV2 {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(SendAddrV2 {})
+    }
+}
+
+/// Implementa el trait Message para el mensaje de preferencia de addrv2.
+/// Permite obtener el comando
+impl Message for SendAddrV2 {
+    fn get_command(&self) -> String {
+        String::from("sendaddrv2")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_send_addr_v2() {
+        let send_addr_v2 = SendAddrV2::new();
+        let serialize_send_addr_v2 = send_addr_v2.serialize();
+        assert_eq!(serialize_send_addr_v2, vec![]);
+    }
+
+    #[test]
+    fn parse_send_addr_v2() {
+        let send_addr_v2 = SendAddrV2::new();
+        let serialize_send_addr_v2 = send_addr_v2.serialize();
+        let parsed_send_addr_v2 = SendAddrV2::parse(serialize_send_addr_v2);
+        assert!(parsed_send_addr_v2.is_ok());
+    }
+
+    #[test]
+    fn parse_invalid_send_addr_v2() {
+        let buffer_too_long = vec![0x00];
+        let parsed_send_addr_v2 = SendAddrV2::parse(buffer_too_long);
+        assert!(parsed_send_addr_v2.is_err());
+    }
+
+    #[test]
+    fn get_command_send_addr_v2() {
+        let send_addr_v2 = SendAddrV2::new();
+        assert_eq!(send_addr_v2.get_command(), String::from("sendaddrv2"));
+    }
+}
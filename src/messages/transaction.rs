@@ -1,21 +1,63 @@
 use std::collections::HashMap;
 
-use bitcoin_hashes::{sha256, sha256d, Hash};
+use bitcoin_hashes::{sha256d, Hash};
 use secp256k1::Secp256k1;
 
 use crate::{
     error::CustomError,
     message::Message,
-    parser::{BufferParser, VarIntSerialize},
+    parser::{BtcEncode, BufferParser, VarIntSerialize},
     states::utxo_state::UTXO,
     structs::{
-        movement::Movement, outpoint::OutPoint, tx_input::TransactionInput,
+        hash32::Hash32,
+        movement::Movement,
+        outpoint::OutPoint,
+        script::{
+            count_legacy_sigops, count_p2sh_sigops, count_witness_sigops, executed_scripts,
+            is_push_only, unconditional_lock_checks, ScriptType, OP_CHECKLOCKTIMEVERIFY,
+        },
+        tx_input::TransactionInput,
         tx_output::TransactionOutput,
     },
     wallet::{get_script_pubkey, Wallet},
 };
 
 const SIGHASH_ALL: u32 = 1;
+/// Marcador y flag que siguen a la version en las transacciones serializadas con witness (BIP144).
+const SEGWIT_MARKER: u8 = 0x00;
+const SEGWIT_FLAG: u8 = 0x01;
+
+/// Peso de los datos que no son witness respecto de los witness (BIP141).
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+/// Limites de las transacciones estandar, las unicas que se aceptan y retransmiten desde los peers.
+const MAX_STANDARD_VERSION: u32 = 2;
+const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+const MAX_STANDARD_SCRIPT_SIG_SIZE: usize = 1650;
+const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// Los lock time menores a este valor son alturas de bloque, y los mayores timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+/// Sequence de las entradas que no usan el lock time de la transaccion.
+const SEQUENCE_FINAL: u32 = 0xffffffff;
+/// Sequence de las entradas de las transacciones creadas con lock time: al no ser final hace que se aplique,
+/// sin señalizar que la transaccion se puede reemplazar (BIP125) ni activar los lock time relativos (BIP68).
+const SEQUENCE_ENABLE_LOCKTIME: u32 = SEQUENCE_FINAL - 1;
+/// Bits del sequence de una entrada para los lock time relativos (BIP68): el que los desactiva, el que indica
+/// que se miden en tiempo (en unidades de 512 segundos) en lugar de bloques, y la mascara del valor.
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
+#[derive(Debug, Clone, Copy)]
+/// Altura y median time past (BIP113) de un bloque, con los que se evaluan los lock time.
+/// - height: Altura del bloque.
+/// - median_time_past: Mediana de los timestamps de los 11 bloques anteriores al bloque.
+pub struct LockPoint {
+    pub height: u32,
+    pub median_time_past: u32,
+}
 
 #[derive(Debug, Clone)]
 
@@ -25,6 +67,8 @@ const SIGHASH_ALL: u32 = 1;
 /// - inputs: Vector de TransactionInputs de la transacción.
 /// - outputs: Vector de TransactionOutputs de la transacción.
 /// - lock_time: Tiempo de bloqueo de la transacción.
+///
+/// Los witness de las transacciones segwit se guardan en cada TransactionInput.
 pub struct Transaction {
     pub version: u32,
     pub inputs: Vec<TransactionInput>,
@@ -33,34 +77,261 @@ pub struct Transaction {
 }
 
 impl Transaction {
-    /// Esta funcion se encarga de hashear una transacción.
-    pub fn hash(&self) -> Vec<u8> {
-        sha256::Hash::hash(sha256::Hash::hash(self.serialize().as_slice()).as_byte_array())
-            .as_byte_array()
-            .to_vec()
+    /// Esta funcion se encarga de hashear una transacción, sin sus witness (txid).
+    pub fn hash(&self) -> Hash32 {
+        Hash32::sha256d(&self.serialize_without_witness())
     }
 
-    /// Esta funcion se encarga de parsear una transacción a partir de un parser.
-    pub fn parse_from_parser(parser: &mut BufferParser) -> Result<Self, CustomError> {
-        let version = parser.extract_u32()?;
-        let tx_in_count = parser.extract_varint()? as usize;
-        let mut inputs = vec![];
-        for _ in 0..tx_in_count {
-            inputs.push(TransactionInput::parse(parser)?);
+    /// Devuelve el hash de la transacción incluyendo sus witness (wtxid, BIP141).
+    /// Si la transacción no tiene witness es igual al txid.
+    pub fn wtxid(&self) -> Hash32 {
+        Hash32::sha256d(&self.serialize())
+    }
+
+    /// Indica si alguno de los inputs de la transacción tiene witness.
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Descarta los witness de la transacción, para enviarla a quien la pidio sin ellos (MSG_TX).
+    pub fn strip_witness(&mut self) {
+        for input in self.inputs.iter_mut() {
+            input.witness.clear();
         }
-        let tx_out_count = parser.extract_varint()? as usize;
-        let mut outputs = vec![];
-        for _ in 0..tx_out_count {
-            outputs.push(TransactionOutput::parse(parser)?);
+    }
+
+    /// Serializa la transacción sin sus witness, como la conocen los nodos anteriores a segwit.
+    pub fn serialize_without_witness(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![];
+        self.encode_without_witness(&mut buffer);
+        buffer
+    }
+
+    fn encode_without_witness(&self, buffer: &mut Vec<u8>) {
+        self.version.encode(buffer);
+        self.inputs.encode(buffer);
+        self.outputs.encode(buffer);
+        self.lock_time.encode(buffer);
+    }
+
+    /// Parsea los campos de la transacción, tanto en la serializacion sin witness como en la de BIP144.
+    fn decode_fields(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let version = parser.extract_u32()?;
+        let segwit = match parser.peek(2) {
+            Some([SEGWIT_MARKER, flag]) if *flag != 0 => {
+                if *flag != SEGWIT_FLAG {
+                    return Err(CustomError::SerializedBufferIsInvalid);
+                }
+                parser.extract_buffer(2)?;
+                true
+            }
+            _ => false,
+        };
+        let mut inputs: Vec<TransactionInput> = Vec::decode(parser)?;
+        let outputs = Vec::decode(parser)?;
+        if segwit {
+            for input in inputs.iter_mut() {
+                input.witness = Vec::decode(parser)?;
+            }
         }
 
         let lock_time = parser.extract_u32()?;
-        Ok(Self {
+        let transaction = Self {
             version,
             inputs,
             outputs,
             lock_time,
-        })
+        };
+        if segwit && !transaction.has_witness() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(transaction)
+    }
+
+    /// Devuelve el peso de la transacción (BIP141), los witness pesan una cuarta parte que el resto de la transacción.
+    pub fn weight(&self) -> usize {
+        self.serialize_without_witness().len() * (WITNESS_SCALE_FACTOR - 1) + self.serialize().len()
+    }
+
+    /// Devuelve el tamaño virtual de la transacción (BIP141): su peso dividido 4, redondeado hacia arriba.
+    /// Es el tamaño con el que se calculan los fees.
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    /// Cuenta las sigops de los script sig y script pubkey de la transacción, sin las de P2SH ni las de los witness.
+    pub fn legacy_sigops(&self) -> usize {
+        let inputs_sigops: usize = self
+            .inputs
+            .iter()
+            .map(|input| count_legacy_sigops(&input.script_sig))
+            .sum();
+        let outputs_sigops: usize = self
+            .outputs
+            .iter()
+            .map(|output| count_legacy_sigops(&output.script_pubkey))
+            .sum();
+        inputs_sigops + outputs_sigops
+    }
+
+    /// Devuelve el costo en sigops de la transacción (BIP141): las sigops de sus scripts y de los redeem scripts P2SH
+    /// pesan WITNESS_SCALE_FACTOR, y las de los witness una.
+    /// Recibe las salidas que gasta cada entrada, en el mismo orden; las de las entradas cuya salida es desconocida
+    /// solo cuentan sus sigops legacy.
+    pub fn sigop_cost(&self, spent_outputs: &[Option<&TransactionOutput>]) -> usize {
+        let mut cost = self.legacy_sigops() * WITNESS_SCALE_FACTOR;
+        for (input, spent_output) in self.inputs.iter().zip(spent_outputs) {
+            let Some(spent_output) = spent_output else {
+                continue;
+            };
+            let script_pubkey = &spent_output.script_pubkey;
+            cost += count_p2sh_sigops(script_pubkey, &input.script_sig) * WITNESS_SCALE_FACTOR;
+            cost += count_witness_sigops(script_pubkey, &input.script_sig, &input.witness);
+        }
+        cost
+    }
+
+    /// Verifica que la transacción se pueda incluir en el bloque recibido: que su lock time absoluto ya haya pasado,
+    /// comparando los timestamps con el median time past (BIP113), que se cumplan los lock time relativos
+    /// de sus entradas (BIP68), y los OP_CHECKLOCKTIMEVERIFY (BIP65) y OP_CHECKSEQUENCEVERIFY (BIP112) de los
+    /// scripts que ejecuta fuera de condicionales, salvo que verify_scripts sea false.
+    /// Recibe, para cada entrada, la salida que gasta y el bloque que la incluyo; las entradas desconocidas no se verifican.
+    /// Devuelve CustomError::NonFinalTransaction si no cumple alguna de las condiciones.
+    pub fn check_locks(
+        &self,
+        block: LockPoint,
+        spent_outputs: &[Option<(&TransactionOutput, LockPoint)>],
+        verify_scripts: bool,
+    ) -> Result<(), CustomError> {
+        if !self.is_final(block) {
+            return Err(CustomError::NonFinalTransaction);
+        }
+        for (input, spent_output) in self.inputs.iter().zip(spent_outputs) {
+            let Some((spent_output, coin)) = spent_output else {
+                continue;
+            };
+            if !self.sequence_lock_satisfied(input.sequence, *coin, block) {
+                return Err(CustomError::NonFinalTransaction);
+            }
+            if !verify_scripts {
+                continue;
+            }
+            for script in executed_scripts(
+                &spent_output.script_pubkey,
+                &input.script_sig,
+                &input.witness,
+            ) {
+                for (opcode, number) in unconditional_lock_checks(script) {
+                    let satisfied = match opcode {
+                        OP_CHECKLOCKTIMEVERIFY => self.lock_time_satisfies(number, input.sequence),
+                        _ => self.sequence_satisfies(number, input.sequence),
+                    };
+                    if !satisfied {
+                        return Err(CustomError::NonFinalTransaction);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Indica si el lock time absoluto de la transacción ya paso en el bloque recibido.
+    /// Si todas sus entradas tienen sequence final el lock time no se aplica.
+    fn is_final(&self, block: LockPoint) -> bool {
+        if self.lock_time == 0
+            || self
+                .inputs
+                .iter()
+                .all(|input| input.sequence == SEQUENCE_FINAL)
+        {
+            return true;
+        }
+        match self.lock_time < LOCKTIME_THRESHOLD {
+            true => self.lock_time < block.height,
+            false => self.lock_time < block.median_time_past,
+        }
+    }
+
+    /// Indica si se cumple el lock time relativo de una entrada (BIP68), que gasta una salida del bloque coin.
+    /// Solo se aplica en las transacciones de version 2 o mayor.
+    fn sequence_lock_satisfied(&self, sequence: u32, coin: LockPoint, block: LockPoint) -> bool {
+        if self.version < 2 || sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return true;
+        }
+        let value = sequence & SEQUENCE_LOCKTIME_MASK;
+        match sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            true => {
+                coin.median_time_past as u64 + ((value as u64) << SEQUENCE_LOCKTIME_GRANULARITY)
+                    <= block.median_time_past as u64
+            }
+            false => coin.height as u64 + value as u64 <= block.height as u64,
+        }
+    }
+
+    /// Indica si el lock time de la transacción satisface un OP_CHECKLOCKTIMEVERIFY con el numero recibido (BIP65).
+    fn lock_time_satisfies(&self, number: Option<i64>, sequence: u32) -> bool {
+        let Some(lock_time) = number.and_then(|number| u32::try_from(number).ok()) else {
+            return false;
+        };
+        (lock_time < LOCKTIME_THRESHOLD) == (self.lock_time < LOCKTIME_THRESHOLD)
+            && lock_time <= self.lock_time
+            && sequence != SEQUENCE_FINAL
+    }
+
+    /// Indica si el sequence de la entrada satisface un OP_CHECKSEQUENCEVERIFY con el numero recibido (BIP112).
+    fn sequence_satisfies(&self, number: Option<i64>, sequence: u32) -> bool {
+        let Some(required) = number.and_then(|number| u32::try_from(number).ok()) else {
+            return false;
+        };
+        if required & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return true;
+        }
+        if self.version < 2 || sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+        (required & SEQUENCE_LOCKTIME_TYPE_FLAG) == (sequence & SEQUENCE_LOCKTIME_TYPE_FLAG)
+            && required & SEQUENCE_LOCKTIME_MASK <= sequence & SEQUENCE_LOCKTIME_MASK
+    }
+
+    /// Verifica que la transacción sea estandar, para no guardar ni retransmitir transacciones que los demas nodos descartan:
+    /// version conocida, peso acotado, script sig que solo agregan datos, salidas de tipos conocidos que no son dust,
+    /// a lo sumo una salida OP_RETURN pequeña y una cantidad acotada de sigops.
+    /// Devuelve CustomError::NonStandardTransaction si no cumple alguna de las condiciones.
+    pub fn check_standard(&self) -> Result<(), CustomError> {
+        if self.version == 0 || self.version > MAX_STANDARD_VERSION {
+            return Err(CustomError::NonStandardTransaction);
+        }
+        if self.weight() > MAX_STANDARD_TX_WEIGHT {
+            return Err(CustomError::NonStandardTransaction);
+        }
+        for input in &self.inputs {
+            if input.script_sig.len() > MAX_STANDARD_SCRIPT_SIG_SIZE
+                || !is_push_only(&input.script_sig)
+            {
+                return Err(CustomError::NonStandardTransaction);
+            }
+        }
+        let mut null_data_outputs = 0;
+        for output in &self.outputs {
+            match ScriptType::classify(&output.script_pubkey) {
+                ScriptType::NonStandard => return Err(CustomError::NonStandardTransaction),
+                ScriptType::NullData => {
+                    null_data_outputs += 1;
+                    if output.script_pubkey.len() > MAX_OP_RETURN_RELAY {
+                        return Err(CustomError::NonStandardTransaction);
+                    }
+                }
+                _ if output.is_dust() => return Err(CustomError::NonStandardTransaction),
+                _ => (),
+            }
+        }
+        if null_data_outputs > 1 {
+            return Err(CustomError::NonStandardTransaction);
+        }
+        if self.legacy_sigops() * WITNESS_SCALE_FACTOR > MAX_STANDARD_TX_SIGOPS_COST {
+            return Err(CustomError::NonStandardTransaction);
+        }
+        Ok(())
     }
 
     /// Esta funcion se encarga de obtener un movement de una transacción.(ver structs/movement.rs)
@@ -89,6 +360,7 @@ impl Transaction {
                 tx_hash: self.hash(),
                 value,
                 block_hash: None,
+                conflicted: false,
             }))
         } else {
             Ok(None)
@@ -97,6 +369,8 @@ impl Transaction {
 
     /// Esta funcion se encarga de crear una transacción.
     /// Recibe por parametro la wallet de la cual se quiere enviar la transacción, un vector de OutPoint que contiene los outpoints de las transacciones que se quieren gastar, y un HashMap que contiene los public key hash de las wallets a las cuales se quiere enviar dinero y la cantidad de dinero que se quiere enviar a cada una.
+    /// Opcionalmente recibe un lock time, una altura de bloque o un timestamp segun LOCKTIME_THRESHOLD, antes del cual la transaccion no se puede minar.
+    /// Para que se aplique, las entradas se crean con SEQUENCE_ENABLE_LOCKTIME en lugar de SEQUENCE_FINAL.
     /// Crea la transacción y la manda a firmar con la wallet de la cual se quiere enviar la transacción.
     /// Finalmente devuelve la transacción firmada.
     /// Devuelve CustomError si:
@@ -106,18 +380,24 @@ impl Transaction {
         sender_wallet: &Wallet,
         inputs_outpoints: Vec<OutPoint>,
         outputs: HashMap<String, u64>,
+        lock_time: Option<u32>,
     ) -> Result<Self, CustomError> {
         let mut transaction = Transaction {
             version: 1,
             inputs: vec![],
             outputs: vec![],
-            lock_time: 0,
+            lock_time: lock_time.unwrap_or(0),
+        };
+        let sequence = match lock_time {
+            Some(_) => SEQUENCE_ENABLE_LOCKTIME,
+            None => SEQUENCE_FINAL,
         };
         for outpoint in inputs_outpoints {
             let input = TransactionInput {
                 previous_output: outpoint,
                 script_sig: vec![],
-                sequence: 0xffffffff,
+                sequence,
+                witness: vec![],
             };
             transaction.inputs.push(input);
         }
@@ -148,7 +428,7 @@ impl Transaction {
 
         for i in 0..self.inputs.len() {
             self.inputs[i].script_sig = script_pubkey.clone();
-            let serialized_unsigned_tx = self.serialize();
+            let serialized_unsigned_tx = self.serialize_without_witness();
             let script_sig = sign(serialized_unsigned_tx, &privkey_hash)?;
             script_sigs.push(script_sig);
             self.inputs[i].script_sig = vec![];
@@ -162,31 +442,38 @@ impl Transaction {
     }
 }
 
-/// Implementa el trait Message para la estructura Transaction.
-/// Permite serializar, parsear y obtener el comando
-impl Message for Transaction {
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.version.to_le_bytes());
-        buffer.extend(self.inputs.len().to_varint_bytes());
-        for input in &self.inputs {
-            buffer.extend(input.serialize());
+/// Implementa el trait BtcEncode para la estructura Transaction.
+/// Permite serializarla y parsearla
+/// Las transacciones con witness se serializan con el formato de BIP144, el resto sin marcador.
+impl BtcEncode for Transaction {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        if !self.has_witness() {
+            return self.encode_without_witness(buffer);
         }
-        buffer.extend(self.outputs.len().to_varint_bytes());
-        for output in &self.outputs {
-            buffer.extend(output.serialize());
+        self.version.encode(buffer);
+        buffer.extend([SEGWIT_MARKER, SEGWIT_FLAG]);
+        self.inputs.encode(buffer);
+        self.outputs.encode(buffer);
+        for input in &self.inputs {
+            input.witness.encode(buffer);
         }
-        buffer.extend(self.lock_time.to_le_bytes());
-        buffer
+        self.lock_time.encode(buffer);
     }
 
-    fn get_command(&self) -> String {
-        String::from("tx")
+    /// Esta funcion se encarga de parsear una transacción a partir de un parser.
+    /// Acepta tanto la serializacion sin witness como la de BIP144, con el marcador y los witness de cada input.
+    /// Devuelve CustomError si el flag de segwit es desconocido, si la transacción tiene el marcador pero ningun witness
+    /// o si alguno de sus varints no esta codificado en su forma mas corta.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        parser.canonical(Self::decode_fields)
     }
+}
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, crate::error::CustomError> {
-        let mut parser = BufferParser::new(buffer);
-        Transaction::parse_from_parser(&mut parser)
+/// Implementa el trait Message para la estructura Transaction.
+/// Permite obtener el comando
+impl Message for Transaction {
+    fn get_command(&self) -> String {
+        String::from("tx")
     }
 }
 
@@ -219,6 +506,8 @@ fn sign(mut buffer: Vec<u8>, privkey: &[u8]) -> Result<Vec<u8>, CustomError> {
 
 #[cfg(test)]
 mod tests {
+    use bitcoin_hashes::hex::FromHex;
+
     use super::*;
 
     #[test]
@@ -245,17 +534,17 @@ mod tests {
             0x88, 0xAC, 0x00, 0x00, 0x00, 0x00,
         ];
         let mut parser = BufferParser::new(buffer.clone());
-        let tx = Transaction::parse_from_parser(&mut parser).unwrap();
+        let tx = Transaction::decode(&mut parser).unwrap();
         assert_eq!(tx.version, 1);
         assert_eq!(tx.inputs.len(), 1);
         let outpoint = tx.inputs.get(0).unwrap().previous_output.clone();
         assert_eq!(
             outpoint.hash,
-            vec![
+            Hash32::new([
                 0x6D, 0xBD, 0xDB, 0x08, 0x5B, 0x1D, 0x8A, 0xF7, 0x51, 0x84, 0xF0, 0xBC, 0x01, 0xFA,
                 0xD5, 0x8D, 0x12, 0x66, 0xE9, 0xB6, 0x3B, 0x50, 0x88, 0x19, 0x90, 0xE4, 0xB4, 0x0D,
                 0x6A, 0xEE, 0x36, 0x29,
-            ]
+            ])
         );
         let script_sig = tx.inputs.get(0).unwrap().script_sig.clone();
         assert_eq!(script_sig.len(), 139);
@@ -307,7 +596,200 @@ mod tests {
             0x88, 0xAC, 0x00, 0x00, 0x00, 0x00,
         ];
         let mut parser = BufferParser::new(buffer);
-        let mut tx = Transaction::parse_from_parser(&mut parser).unwrap();
+        let mut tx = Transaction::decode(&mut parser).unwrap();
         assert!(tx.get_script_sigs(&wallet).is_ok());
     }
+
+    #[test]
+    fn segwit_tx_parse_and_serialize() {
+        // Transaccion P2WPKH firmada del ejemplo de BIP143
+        let buffer = Vec::from_hex("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000").unwrap();
+        let tx = Transaction::parse(buffer.clone()).unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.inputs[1].witness.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.lock_time, 17);
+        assert_eq!(tx.serialize(), buffer);
+
+        let stripped = Transaction::parse(tx.serialize_without_witness()).unwrap();
+        assert!(!stripped.has_witness());
+        assert_eq!(stripped.hash(), tx.hash());
+        assert_eq!(stripped.wtxid(), tx.hash());
+        assert_ne!(tx.wtxid(), tx.hash());
+        assert_eq!(tx.vsize(), 261);
+        assert_eq!(stripped.vsize(), 233);
+    }
+
+    #[test]
+    fn standard_transactions() {
+        // Transaccion P2WPKH firmada del ejemplo de BIP143
+        let buffer = Vec::from_hex("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000").unwrap();
+        let tx = Transaction::parse(buffer).unwrap();
+        assert!(tx.check_standard().is_ok());
+        assert_eq!(tx.legacy_sigops(), 2);
+
+        let mut dust = tx.clone();
+        dust.outputs[0].value = 545;
+        assert!(dust.check_standard().is_err());
+
+        let mut unknown_script = tx.clone();
+        unknown_script.outputs[0].script_pubkey = vec![0x51];
+        assert!(unknown_script.check_standard().is_err());
+
+        let mut not_push_only = tx.clone();
+        not_push_only.inputs[0].script_sig.push(0xac);
+        assert!(not_push_only.check_standard().is_err());
+
+        let mut null_data = tx.clone();
+        null_data.outputs.push(TransactionOutput {
+            value: 0,
+            script_pubkey: vec![0x6a, 3, 1, 2, 3],
+        });
+        assert!(null_data.check_standard().is_ok());
+        null_data.outputs.push(null_data.outputs[2].clone());
+        assert!(null_data.check_standard().is_err());
+
+        let mut unknown_version = tx;
+        unknown_version.version = 3;
+        assert!(unknown_version.check_standard().is_err());
+    }
+
+    #[test]
+    fn lock_times_and_sequence_locks() {
+        let lock_point = |height, median_time_past| LockPoint {
+            height,
+            median_time_past,
+        };
+        let output = TransactionOutput {
+            value: 1000,
+            script_pubkey: vec![0x51],
+        };
+        let mut tx = Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: Hash32::default(),
+                    index: 0,
+                },
+                script_sig: vec![],
+                sequence: 0xfffffffe,
+                witness: vec![],
+            }],
+            outputs: vec![],
+            lock_time: 100,
+        };
+        let coin = Some((&output, lock_point(50, 0)));
+
+        // Lock time absoluto por altura y por median time past
+        assert!(tx.check_locks(lock_point(100, 0), &[coin], true).is_err());
+        assert!(tx.check_locks(lock_point(101, 0), &[coin], true).is_ok());
+        tx.lock_time = 600_000_000;
+        assert!(tx
+            .check_locks(lock_point(101, 600_000_000), &[coin], true)
+            .is_err());
+        assert!(tx
+            .check_locks(lock_point(101, 600_000_001), &[coin], true)
+            .is_ok());
+        tx.inputs[0].sequence = SEQUENCE_FINAL;
+        assert!(tx.check_locks(lock_point(101, 0), &[coin], true).is_ok());
+
+        // Lock time relativo de 10 bloques desde la salida gastada
+        tx.lock_time = 0;
+        tx.inputs[0].sequence = 10;
+        assert!(tx.check_locks(lock_point(59, 0), &[coin], true).is_err());
+        assert!(tx.check_locks(lock_point(60, 0), &[coin], true).is_ok());
+        tx.version = 1;
+        assert!(tx.check_locks(lock_point(59, 0), &[coin], true).is_ok());
+
+        // OP_CHECKLOCKTIMEVERIFY con lock time 100 en el script gastado
+        let cltv_output = TransactionOutput {
+            value: 1000,
+            script_pubkey: vec![1, 100, OP_CHECKLOCKTIMEVERIFY, 0x75, 0x51],
+        };
+        let cltv_coin = Some((&cltv_output, lock_point(50, 0)));
+        tx.lock_time = 99;
+        assert!(tx
+            .check_locks(lock_point(200, 0), &[cltv_coin], true)
+            .is_err());
+        tx.lock_time = 100;
+        assert!(tx
+            .check_locks(lock_point(200, 0), &[cltv_coin], true)
+            .is_ok());
+        tx.inputs[0].sequence = SEQUENCE_FINAL;
+        assert!(tx
+            .check_locks(lock_point(200, 0), &[cltv_coin], true)
+            .is_err());
+        assert!(tx
+            .check_locks(lock_point(200, 0), &[cltv_coin], false)
+            .is_ok());
+    }
+
+    #[test]
+    fn created_transactions_enforce_lock_time() {
+        let wallet = Wallet::new(
+            String::from("test"),
+            String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"),
+            String::from("cNpwEsaVLhju18SJowLtdCNaJtvMvqL4jtFLm2FXw7vZjg4sRWvH"),
+            &UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap(),
+        )
+        .unwrap();
+        let out_point = OutPoint {
+            hash: Hash32::default(),
+            index: 0,
+        };
+        let outputs = HashMap::from([(wallet.pubkey.clone(), 1000)]);
+        let lock_point = |height| LockPoint {
+            height,
+            median_time_past: 0,
+        };
+
+        let tx =
+            Transaction::create(&wallet, vec![out_point.clone()], outputs.clone(), None).unwrap();
+        assert_eq!(tx.lock_time, 0);
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_FINAL);
+
+        let tx = Transaction::create(&wallet, vec![out_point], outputs, Some(100)).unwrap();
+        assert_eq!(tx.lock_time, 100);
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_ENABLE_LOCKTIME);
+        assert!(tx.check_locks(lock_point(100), &[], false).is_err());
+        assert!(tx.check_locks(lock_point(101), &[], false).is_ok());
+    }
+
+    #[test]
+    fn segwit_marker_without_witness_is_invalid() {
+        let mut buffer = vec![1, 0, 0, 0, SEGWIT_MARKER, SEGWIT_FLAG, 1];
+        buffer.extend([0; 36]);
+        buffer.extend([0, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0]);
+        assert!(Transaction::parse(buffer).is_err());
+
+        let empty = Transaction {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: 0,
+        };
+        assert!(Transaction::parse(empty.serialize()).is_ok());
+    }
+
+    #[test]
+    fn non_canonical_varint_is_invalid() {
+        // una transaccion con un input y un output, con la cantidad de outputs codificada en 3 bytes
+        let mut buffer = vec![1, 0, 0, 0, 1];
+        buffer.extend([0; 36]);
+        buffer.extend([0, 0xff, 0xff, 0xff, 0xff]);
+        let mut non_canonical = buffer.clone();
+        buffer.push(1);
+        non_canonical.extend([0xfd, 1, 0]);
+        for tx in [&mut buffer, &mut non_canonical] {
+            tx.extend([0; 8]);
+            tx.extend([0, 0, 0, 0, 0]);
+        }
+
+        assert!(Transaction::parse(buffer).is_ok());
+        assert!(matches!(
+            Transaction::parse(non_canonical),
+            Err(CustomError::NonCanonicalVarint)
+        ));
+    }
 }
@@ -1,4 +1,8 @@
-use crate::{error::CustomError, message::Message};
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
 
 #[derive(Debug)]
 /// VerAck es un mensaje vacio que se envia tras intercambiar los mensajes de version.
@@ -18,25 +22,27 @@ impl Default for VerAck {
     }
 }
 
-/// Implementa el trait Message para el mensaje de verificación de conexión.
-/// Permite serializar, parsear y obtener el comando
-impl Message for VerAck {
-    fn get_command(&self) -> String {
-        String::from("verack")
-    }
-
-    fn serialize(&self) -> Vec<u8> {
-        vec![]
-    }
+/// Implementa el trait BtcEncode para el mensaje de verificación de conexión.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for VerAck {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, crate::error::CustomError> {
-        if !buffer.is_empty() {
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
         Ok(VerAck {})
     }
 }
 
+/// Implementa el trait Message para el mensaje de verificación de conexión.
+/// Permite obtener el comando
+impl Message for VerAck {
+    fn get_command(&self) -> String {
+        String::from("verack")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,7 +2,8 @@ use std::net::SocketAddrV6;
 
 use crate::error::CustomError;
 use crate::message::Message;
-use crate::parser::BufferParser;
+use crate::parser::{BtcEncode, BufferParser};
+use crate::utils::get_random_nonce;
 
 #[derive(PartialEq, Debug)]
 /// Crea una estructura para el mensaje de versión con los campos necesarios de acuerdo con el protocolo de Bitcoin.
@@ -15,9 +16,10 @@ use crate::parser::BufferParser;
 /// - sender_services: que indica los servicios que ofrece el nodo que envía el mensaje.
 /// - sender_address: que indica la socket v6 del nodo que envía el mensaje.
 /// - nonce: que indica un número aleatorio que se utiliza para detectar conexiones a sí mismo.
-/// - user_agent: que indica el software que utiliza el nodo que envía el mensaje, puede ser vacío.
-/// - user_agent_length: que indica la longitud del campo user_agent. Si es 0, el campo user_agent no se incluye.
+/// - user_agent: que indica el software que utiliza el nodo que envía el mensaje con el formato /nombre:version/ (BIP14), puede ser vacío.
 /// - start_height: que indica el tamaño de la blockchain del nodo que envía el mensaje.
+/// - relay: que indica si el nodo que envía el mensaje quiere que le anuncien transacciones (fRelay, BIP37).
+///   Si el mensaje no lo incluye se considera true.
 pub struct Version {
     pub version: i32,
     pub services: u64,
@@ -28,20 +30,21 @@ pub struct Version {
     pub sender_address: SocketAddrV6,
     pub nonce: u64,
     pub user_agent: String,
-    pub user_agent_length: u8,
     pub start_height: i32,
+    pub relay: bool,
 }
 
 impl Version {
     /// Crea un nuevo mensaje de versión a partir de un nodo transmisor y una dirección IPv6 del nodo que recibe el mensaje.
-    /// El campo user_agent se inicializa con un string vacío y el campo user_agent_length con 0.
-    /// El campo nonce se inicializa con 0.
+    /// El campo nonce se inicializa con un numero aleatorio, para poder detectar conexiones a sí mismo.
     /// El campo start_height se inicializa con 0.
     pub fn new(
         receiver_address: SocketAddrV6,
         sender_address: SocketAddrV6,
         version: i32,
         services: u64,
+        user_agent: &str,
+        relay: bool,
     ) -> Self {
         Version {
             version,
@@ -51,51 +54,34 @@ impl Version {
             receiver_address,
             sender_services: services,
             sender_address,
-            nonce: 0x00,
-            user_agent: String::from(""),
-            user_agent_length: 0x00,
+            nonce: get_random_nonce(),
+            user_agent: String::from(user_agent),
             start_height: 0x00,
+            relay,
         }
     }
 }
 
-/// Implementa el trait Message para el mensaje de versión.
-/// Permite serializar, parsear y obtener el comando
-impl Message for Version {
-    fn get_command(&self) -> String {
-        String::from("version")
-    }
-
-    fn serialize(&self) -> Vec<u8> {
-        let mut buffer = vec![];
-        buffer.extend_from_slice(&self.version.to_le_bytes());
-        buffer.extend_from_slice(&self.services.to_le_bytes());
-        buffer.extend_from_slice(&self.timestamp.to_le_bytes());
-        buffer.extend_from_slice(&self.receiver_services.to_le_bytes());
-        let ipv6_buffer = self.receiver_address.ip().octets();
-        for byte in ipv6_buffer {
-            buffer.extend_from_slice(&[byte]);
-        }
-        buffer.extend_from_slice(&self.receiver_address.port().to_be_bytes());
-        buffer.extend_from_slice(&self.sender_services.to_le_bytes());
-        let ipv6_buffer = self.sender_address.ip().octets();
-        for byte in ipv6_buffer {
-            buffer.extend_from_slice(&[byte]);
-        }
-        buffer.extend_from_slice(&self.sender_address.port().to_be_bytes());
-        buffer.extend_from_slice(&self.nonce.to_le_bytes());
-        buffer.extend_from_slice(&self.user_agent_length.to_le_bytes());
-        buffer.extend_from_slice(self.user_agent.as_bytes());
-        buffer.extend_from_slice(&self.start_height.to_le_bytes());
-
-        buffer
+/// Implementa el trait BtcEncode para el mensaje de versión.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for Version {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.version.encode(buffer);
+        self.services.encode(buffer);
+        self.timestamp.encode(buffer);
+        self.receiver_services.encode(buffer);
+        buffer.extend(self.receiver_address.ip().octets());
+        buffer.extend(self.receiver_address.port().to_be_bytes());
+        self.sender_services.encode(buffer);
+        buffer.extend(self.sender_address.ip().octets());
+        buffer.extend(self.sender_address.port().to_be_bytes());
+        self.nonce.encode(buffer);
+        self.user_agent.encode(buffer);
+        self.start_height.encode(buffer);
+        buffer.push(self.relay as u8);
     }
 
-    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError>
-    where
-        Self: Sized,
-    {
-        let mut parser = BufferParser::new(buffer);
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         if parser.len() < 85 {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
@@ -108,9 +94,16 @@ impl Message for Version {
         let sender_services = parser.extract_u64()?;
         let sender_address = parser.extract_address()?;
         let nonce = parser.extract_u64()?;
-        let user_agent_length = parser.extract_u8()?;
-        let user_agent = parser.extract_string(user_agent_length as usize)?;
+        let user_agent_length = parser.extract_varint()? as usize;
+        // El user agent lo elige el peer, no se rechaza el handshake si no es UTF-8 valido
+        let user_agent =
+            String::from_utf8_lossy(parser.extract_buffer(user_agent_length)?).into_owned();
         let start_height = parser.extract_i32()?;
+        // Los nodos anteriores a BIP37 no envian el campo relay
+        let relay = match parser.is_empty() {
+            true => true,
+            false => parser.extract_u8()? != 0,
+        };
 
         Ok(Version {
             version,
@@ -122,12 +115,20 @@ impl Message for Version {
             sender_address,
             nonce,
             user_agent,
-            user_agent_length,
             start_height,
+            relay,
         })
     }
 }
 
+/// Implementa el trait Message para el mensaje de versión.
+/// Permite obtener el comando
+impl Message for Version {
+    fn get_command(&self) -> String {
+        String::from("version")
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -139,10 +140,31 @@ mod tests {
     fn create_version_message() -> Result<(), CustomError> {
         let sender_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 4321, 0, 0);
         let receiver_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 1234, 0, 0);
-        let version: Version = Version::new(receiver_address, sender_address, 7000, 0x00);
+        let version: Version = Version::new(
+            receiver_address,
+            sender_address,
+            7000,
+            0x00,
+            "/bitcoin-node:0.1.0/",
+            false,
+        );
         let buffer = version.serialize();
         let parsed_version = Version::parse(buffer)?;
         assert_eq!(version, parsed_version);
+        assert_eq!(parsed_version.user_agent, "/bitcoin-node:0.1.0/");
+        assert!(!parsed_version.relay);
+        Ok(())
+    }
+
+    #[test]
+    fn version_without_relay_flag_relays() -> Result<(), CustomError> {
+        let address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 1234, 0, 0);
+        let version = Version::new(address, address, 7000, 0x00, "", false);
+        let mut buffer = version.serialize();
+        buffer.pop();
+
+        let parsed_version = Version::parse(buffer)?;
+        assert!(parsed_version.relay);
         Ok(())
     }
 
@@ -156,4 +178,12 @@ mod tests {
         let parsed_version = Version::parse(buffer_too_short);
         assert_eq!(parsed_version.is_err(), true);
     }
+
+    #[test]
+    fn version_messages_have_different_nonces() {
+        let address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 1234, 0, 0);
+        let first = Version::new(address, address, 7000, 0x00, "", true);
+        let second = Version::new(address, address, 7000, 0x00, "", true);
+        assert_ne!(first.nonce, second.nonce);
+    }
 }
@@ -0,0 +1,77 @@
+use crate::{
+    error::CustomError,
+    message::Message,
+    parser::{BtcEncode, BufferParser},
+};
+
+#[derive(Debug)]
+/// WtxidRelay es un mensaje vacio que se envia entre los mensajes version y verack (BIP339).
+/// Indica al peer que preferimos anunciar y pedir las transacciones por su wtxid en lugar de su txid.
+pub struct WtxidRelay {}
+
+impl WtxidRelay {
+    /// Crea un nuevo mensaje de preferencia de wtxid.
+    pub fn new() -> Self {
+        WtxidRelay {}
+    }
+}
+
+impl Default for WtxidRelay {
+    fn default() -> Self {
+        WtxidRelay::new()
+    }
+}
+
+/// Implementa el trait BtcEncode para el mensaje de preferencia de wtxid.
+/// Permite serializarlo y parsearlo
+impl BtcEncode for WtxidRelay {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(WtxidRelay {})
+    }
+}
+
+/// Implementa el trait Message para el mensaje de preferencia de wtxid.
+/// Permite obtener el comando
+impl Message for WtxidRelay {
+    fn get_command(&self) -> String {
+        String::from("wtxidrelay")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_wtxid_relay() {
+        let wtxid_relay = WtxidRelay::new();
+        let serialize_wtxid_relay = wtxid_relay.serialize();
+        assert_eq!(serialize_wtxid_relay, vec![]);
+    }
+
+    #[test]
+    fn parse_wtxid_relay() {
+        let wtxid_relay = WtxidRelay::new();
+        let serialize_wtxid_relay = wtxid_relay.serialize();
+        let parsed_wtxid_relay = WtxidRelay::parse(serialize_wtxid_relay);
+        assert!(parsed_wtxid_relay.is_ok());
+    }
+
+    #[test]
+    fn parse_invalid_wtxid_relay() {
+        let buffer_too_long = vec![0x00];
+        let parsed_wtxid_relay = WtxidRelay::parse(buffer_too_long);
+        assert!(parsed_wtxid_relay.is_err());
+    }
+
+    #[test]
+    fn get_command_wtxid_relay() {
+        let wtxid_relay = WtxidRelay::new();
+        assert_eq!(wtxid_relay.get_command(), String::from("wtxidrelay"));
+    }
+}
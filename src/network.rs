@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use crate::{error::CustomError, peer::GENESIS, structs::hash32::Hash32};
+
+/// MAINNET_GENESIS es el hash del bloque genesis de la mainnet.
+const MAINNET_GENESIS: [u8; 32] = [
+    111, 226, 140, 10, 182, 241, 179, 114, 193, 166, 162, 70, 174, 99, 247, 79, 147, 30, 131, 101,
+    225, 90, 8, 156, 104, 214, 25, 0, 0, 0, 0, 0,
+];
+
+/// REGTEST_GENESIS es el hash del bloque genesis de la regtest.
+const REGTEST_GENESIS: [u8; 32] = [
+    6, 34, 110, 70, 17, 26, 11, 89, 202, 175, 18, 96, 67, 235, 91, 191, 40, 195, 79, 58, 94, 51,
+    42, 31, 199, 178, 183, 60, 241, 136, 145, 15,
+];
+
+/// Semillas DNS de la mainnet.
+const MAINNET_DNS_SEEDS: [&str; 6] = [
+    "seed.bitcoin.sipa.be",
+    "dnsseed.bluematt.me",
+    "seed.bitcoin.jonasschnelli.ch",
+    "seed.btc.petertodd.net",
+    "seed.bitcoin.sprovoost.nl",
+    "dnsseed.emzy.de",
+];
+
+/// Semillas DNS de la testnet.
+const TESTNET_DNS_SEEDS: [&str; 4] = [
+    "testnet-seed.bitcoin.jonasschnelli.ch",
+    "seed.tbtc.petertodd.net",
+    "seed.testnet.bitcoin.sprovoost.nl",
+    "testnet-seed.bluematt.me",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Network representa las redes de Bitcoin a las que se puede conectar el nodo.
+/// Cada red tiene su propio magic number y bloque genesis.
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    /// Devuelve el magic number que identifica a los mensajes de la red.
+    pub fn magic(&self) -> u32 {
+        match self {
+            Network::Mainnet => 0xf9beb4d9,
+            Network::Testnet => 0x0b110907,
+            Network::Regtest => 0xfabfb5da,
+        }
+    }
+
+    /// Devuelve las semillas DNS conocidas de la red, que se usan si las configuradas no alcanzan.
+    pub fn dns_seeds(&self) -> &'static [&'static str] {
+        match self {
+            Network::Mainnet => &MAINNET_DNS_SEEDS,
+            Network::Testnet => &TESTNET_DNS_SEEDS,
+            Network::Regtest => &[],
+        }
+    }
+
+    /// Devuelve el hash del bloque genesis de la red.
+    pub fn genesis(&self) -> Hash32 {
+        let genesis = match self {
+            Network::Mainnet => MAINNET_GENESIS,
+            Network::Testnet => GENESIS,
+            Network::Regtest => REGTEST_GENESIS,
+        };
+        Hash32::new(genesis)
+    }
+
+    /// Devuelve el byte de version de las direcciones P2PKH de la red.
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest => 0x6f,
+        }
+    }
+
+    /// Devuelve el byte de version de las direcciones P2SH de la red.
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Regtest => 0xc4,
+        }
+    }
+
+    /// Devuelve cada cuantos bloques se reduce a la mitad el subsidio de la coinbase.
+    pub fn subsidy_halving_interval(&self) -> u32 {
+        match self {
+            Network::Mainnet | Network::Testnet => 210_000,
+            Network::Regtest => 150,
+        }
+    }
+
+    /// Devuelve los bits de la dificultad minima de la red, el target mas alto que puede tener un bloque.
+    pub fn pow_limit_bits(&self) -> u32 {
+        match self {
+            Network::Mainnet | Network::Testnet => 0x1d00ffff,
+            Network::Regtest => 0x207fffff,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = CustomError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(CustomError::ConfigErrorReadingValue),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_from_str() {
+        assert_eq!(Network::from_str("mainnet").unwrap(), Network::Mainnet);
+        assert_eq!(Network::from_str("testnet").unwrap(), Network::Testnet);
+        assert_eq!(Network::from_str("regtest").unwrap(), Network::Regtest);
+        assert!(matches!(
+            Network::from_str("signet"),
+            Err(CustomError::ConfigErrorReadingValue)
+        ));
+    }
+
+    #[test]
+    fn network_testnet_magic_and_genesis() {
+        assert_eq!(Network::Testnet.magic().to_be_bytes(), [11, 17, 9, 7]);
+        assert_eq!(Network::Testnet.genesis(), Hash32::new(GENESIS));
+        assert_eq!(Network::Mainnet.genesis().as_bytes()[31], 0);
+    }
+
+    #[test]
+    fn network_address_prefixes() {
+        assert_eq!(Network::Mainnet.p2pkh_prefix(), 0x00);
+        assert_eq!(Network::Mainnet.p2sh_prefix(), 0x05);
+        assert_eq!(Network::Testnet.p2pkh_prefix(), 0x6f);
+        assert_eq!(Network::Regtest.p2sh_prefix(), 0xc4);
+    }
+}
@@ -1,25 +1,40 @@
 use std::{
-    net::{Ipv6Addr, SocketAddr, SocketAddrV6},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream},
     sync::{mpsc, Arc, Mutex},
     thread::{self, JoinHandle},
     vec::IntoIter,
 };
 
-use gtk::glib;
+use tracing::instrument;
 
 use crate::{
     config::Config,
     error::CustomError,
-    gui::init::GUIEvents,
-    logger::{send_log, Log, Logger},
+    events::GUIEvents,
+    logger::{send_log, Log, LogTarget, Logger},
     loops::{
-        node_action_loop::{NodeAction, NodeActionLoop},
+        connection_loop::{connect_to_peers, ConnectionLoop, PeerConnector},
+        flush_loop::flush_loop,
+        node_action_loop::{
+            node_action_channel, NodeActionLoop, NodeActionReceiver, NodeActionSender,
+            NODE_ACTION_QUEUE_SIZE,
+        },
         peer_action_loop::PeerAction,
         pending_blocks_loop::pending_blocks_loop,
-        tcp_listener_loop::TcpListenerLoop,
+        ping_loop::ping_loop,
+        price_loop::price_loop,
+        publisher_loop::{PublisherEvent, PublisherLoop},
+        rebroadcast_loop::rebroadcast_loop,
+        rest_api_loop::RestApiLoop,
+        rpc_server_loop::RpcServerLoop,
+        tcp_listener_loop::{PeerAnswerer, TcpListenerLoop},
     },
     node_state::NodeState,
-    peer::{request_headers, Peer},
+    peer::{Peer, PeerConfig},
+    price::{HttpPriceSource, PriceCache},
+    rpc::RpcHandler,
+    spv::{wallets_filter, NODE_BLOOM},
+    utils::{get_address_v6, get_current_timestamp},
 };
 
 /// Node es la estructura que representa nuestro nodo.
@@ -28,54 +43,129 @@ use crate::{
 /// - services: Servicios que ofrece el nodo.
 /// - version: Version del nodo.
 /// - client_only: Indica si el nodo es completo o solo cliente.
+/// - peer_config: Configuracion de las conexiones con los peers: red, timeouts, user agent, modo solo bloques o SPV, proxy y captura.
 /// - logger_sender: Sender para enviar logs al logger.
 /// - peer_action_sender: Sender para enviar acciones al los peers.
 /// - peer_action_receiver: Receiver para recibir acciones del peer.
 /// - node_action_sender: Sender para enviar acciones al nodo.
 /// - node_action_receiver: Receiver para recibir acciones del nodo.
-/// - tcp_listener_thread: Thread del loop para atender conexiones entrantes a este nodo.
+/// - terminate_senders: Senders para indicarle a los loops auxiliares que deben terminar.
+/// - pending_blocks_thread: Thread del loop que vuelve a pedir los bloques pendientes.
+/// - ping_thread: Thread del loop que envia pings a los peers y desconecta a los que no responden.
+/// - rebroadcast_thread: Thread del loop que reenvia las transacciones locales que no se confirmaron.
+/// - flush_thread: Thread del loop que guarda periodicamente en disco el estado que cambio.
+/// - bind_addresses: Direcciones en las que el nodo escucha conexiones entrantes.
+/// - tcp_listener_threads: Threads de los loops que atienden las conexiones entrantes, uno por cada direccion.
+/// - connection_thread: Thread del loop que mantiene la cantidad de peers salientes.
+/// - rest_port: Puerto de la API REST, si es 0 no se inicia.
+/// - rest_api_thread: Thread del loop que atiende la API REST.
+/// - rpc_port: Puerto del servidor JSON-RPC, si es 0 no se inicia.
+/// - rpc_server_thread: Thread del loop que atiende las consultas JSON-RPC.
+/// - publisher_port: Puerto del publisher de eventos, si es 0 no se inicia.
+/// - publisher_sender: Sender para publicar eventos, se le entrega al NodeActionLoop.
+/// - publisher_thread: Thread del loop que publica los eventos.
+/// - price_cache: Cache de la fuente de precios fiat, None si no se habilito FIAT_DISPLAY.
+/// - price_thread: Thread del loop que consulta el precio fiat.
 /// - node_state_ref: Referencia al estado del nodo.
 /// - npeers: Cantidad de peers.
+/// - sync_peers: Cantidad de peers mas rapidos entre los que se reparten los pedidos de headers y bloques.
+/// - max_inbound: Cantidad maxima de peers entrantes.
 pub struct Node {
     pub address: SocketAddrV6,
     pub services: u64,
     pub version: i32,
     client_only: bool,
+    peer_config: PeerConfig,
     logger_sender: mpsc::Sender<Log>,
     peer_action_sender: mpsc::Sender<PeerAction>,
     peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-    pub node_action_sender: mpsc::Sender<NodeAction>,
-    node_action_receiver: Option<mpsc::Receiver<NodeAction>>,
-    tcp_listener_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
-    node_state_ref: Arc<Mutex<NodeState>>,
+    pub node_action_sender: NodeActionSender,
+    node_action_receiver: Option<NodeActionReceiver>,
+    terminate_senders: Vec<mpsc::Sender<()>>,
+    pending_blocks_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    ping_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    rebroadcast_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    flush_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    bind_addresses: Vec<SocketAddr>,
+    tcp_listener_threads: Vec<(SocketAddr, thread::JoinHandle<Result<(), CustomError>>)>,
+    connection_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    rest_port: u16,
+    rest_api_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    rpc_port: u16,
+    rpc_server_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    publisher_port: u16,
+    publisher_sender: Option<mpsc::Sender<PublisherEvent>>,
+    publisher_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    price_cache: Option<PriceCache<HttpPriceSource>>,
+    price_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+    node_state_ref: Arc<NodeState>,
     npeers: u8,
+    sync_peers: usize,
+    max_inbound: usize,
 }
 
 impl Node {
     /// Inicializa el nodo.
     /// Crea los channels necesarios para la comunicacion con los peers y el logger.
+    /// El NodeState se tiene que haber restaurado con la red de la configuracion; si es otra devuelve CustomError::ConfigInvalid.
+    /// Guarda la configuracion de las conexiones con peers (timeouts, user agent, proxy, modo solo bloques o SPV y captura)
+    /// y establece en el NodeState las listas de direcciones permitidas y rechazadas, si funciona como cliente SPV
+    /// o en modo solo bloques y los servicios que anuncia.
     pub fn new(
         config: &Config,
         logger: &Logger,
-        node_state_ref: Arc<Mutex<NodeState>>,
+        node_state_ref: Arc<NodeState>,
     ) -> Result<Self, CustomError> {
         let logger_sender = logger.get_sender();
         let (peer_action_sender, receiver) = mpsc::channel();
         let peer_action_receiver = Arc::new(Mutex::new(receiver));
-        let (node_action_sender, node_action_receiver) = mpsc::channel();
+        let (node_action_sender, node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
+        if node_state_ref.get_network() != config.network {
+            return Err(CustomError::ConfigInvalid);
+        }
+        let peer_config = config.get_peer_config()?;
+        node_state_ref.set_access_list(config.get_access_list())?;
+        node_state_ref.set_spv_mode(config.spv)?;
+        node_state_ref.set_blocks_only(config.blocks_only)?;
+        node_state_ref.set_local_services(peer_config.services)?;
+        node_state_ref.set_max_mempool_size(config.max_mempool)?;
+        node_state_ref.set_min_relay_fee(config.min_relay_fee)?;
+        node_state_ref.set_assume_valid(config.assume_valid)?;
+        node_state_ref.set_compress_blocks(config.compress_blocks)?;
+        node_state_ref.set_notify_commands(config.get_notify_commands())?;
 
         let node = Self {
             address: SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), config.port, 0, 0),
-            services: 0x00,
+            services: peer_config.services,
             version: config.protocol_version,
-            client_only: config.client_only,
+            client_only: config.client_only || config.spv,
+            peer_config,
             logger_sender,
             peer_action_sender,
             peer_action_receiver,
             node_action_sender,
             node_action_receiver: Some(node_action_receiver),
-            tcp_listener_thread: None,
+            terminate_senders: vec![],
+            pending_blocks_thread: None,
+            ping_thread: None,
+            rebroadcast_thread: None,
+            flush_thread: None,
+            bind_addresses: config.get_bind_addresses(),
+            tcp_listener_threads: vec![],
+            connection_thread: None,
+            rest_port: config.rest_port,
+            rest_api_thread: None,
+            rpc_port: config.rpc_port,
+            rpc_server_thread: None,
+            publisher_port: config.publisher_port,
+            publisher_sender: None,
+            publisher_thread: None,
+            price_cache: config.get_price_cache(),
+            price_thread: None,
             npeers: config.npeers,
+            sync_peers: config.sync_peers,
+            max_inbound: config.max_inbound,
             node_state_ref,
         };
 
@@ -83,136 +173,375 @@ impl Node {
     }
 
     /// Inicializa el nodo en un thread.
+    /// Comienza el thread de publisher_loop, devolviendo el error si no puede escuchar en su puerto.
     /// Comienza el thread de pending_blocks_loop.
+    /// Comienza el thread de ping_loop.
+    /// Comienza el thread de rebroadcast_loop.
+    /// Comienza el thread de flush_loop.
     /// Comienza el thread de tcp_listener_loop.
+    /// Comienza el thread de rest_api_loop.
+    /// Comienza el thread de rpc_server_loop.
+    /// Comienza el thread de price_loop.
+    /// Se conecta con los peers y comienza el thread de connection_loop.
     /// Comienza la descarga de headers.
     /// Comienza el thread de node_action_loop.
+    /// Cuando el node_action_loop recibe NodeAction::Terminate, termina ordenadamente el resto de los threads y guarda el estado en disco.
     pub fn spawn(
         mut self,
         addresses: IntoIter<SocketAddr>,
-        gui_sender: glib::Sender<GUIEvents>,
-    ) -> JoinHandle<Result<(), CustomError>> {
+        gui_sender: mpsc::Sender<GUIEvents>,
+    ) -> Result<JoinHandle<Result<(), CustomError>>, CustomError> {
+        self.initialize_publisher_loop()?;
         self.initialize_pending_blocks_loop();
+        self.initialize_ping_loop();
+        self.initialize_rebroadcast_loop();
+        self.initialize_flush_loop();
         self.initialize_tcp_listener_loop();
+        self.initialize_rest_api_loop();
+        self.initialize_rpc_server_loop();
+        self.initialize_price_loop(gui_sender.clone());
 
-        thread::spawn(move || -> Result<(), CustomError> {
+        Ok(thread::spawn(move || -> Result<(), CustomError> {
             if let Err(error) = self.connect(addresses, self.npeers) {
                 send_log(&self.logger_sender, Log::Error(error));
             }
-            if let Err(error) = self.initialize_ibd() {
-                send_log(&self.logger_sender, Log::Error(error));
-            }
+            self.initialize_connection_loop();
             if let Err(error) = self.initialize_event_loop(gui_sender) {
                 send_log(&self.logger_sender, Log::Error(error));
             }
-            Ok(())
-        })
+            self.shutdown()
+        }))
     }
 
+    #[instrument(skip_all)]
     fn connect(
         &mut self,
         addresses: IntoIter<SocketAddr>,
-        mut number_of_peers: u8,
+        number_of_peers: u8,
     ) -> Result<(), CustomError> {
+        // Las direcciones de la semilla se suman a las conocidas de ejecuciones anteriores
+        let timestamp = get_current_timestamp()?;
+        for address in addresses {
+            self.node_state_ref
+                .add_address(get_address_v6(address), 0, timestamp)?;
+        }
+
+        // Primero se intenta con los peers ancla de la ejecucion anterior
+        let mut candidates = self.node_state_ref.take_anchors()?;
+        let total_anchors = candidates.len();
+        for address in self.node_state_ref.get_address_candidates()? {
+            if !candidates.contains(&address) {
+                candidates.push(address);
+            }
+        }
+
         send_log(
             &self.logger_sender,
-            Log::Message(format!(
-                "Handshaking with {} nodes ({} available)",
-                number_of_peers,
-                addresses.len()
-            )),
+            Log::Info(
+                LogTarget::Peer,
+                format!(
+                    "Handshaking with {} nodes ({} available, {} anchors)",
+                    number_of_peers,
+                    candidates.len(),
+                    total_anchors
+                ),
+            ),
         );
 
-        let mut peers = vec![];
-
-        for address in addresses {
-            if number_of_peers == 0 {
-                break;
-            }
+        let max_attempts = candidates.len();
+        let peers = connect_to_peers(
+            &self.node_state_ref,
+            &self.logger_sender,
+            &self.peer_connector(),
+            candidates,
+            number_of_peers as usize,
+            max_attempts,
+        )?;
+        self.node_state_ref.append_peers(peers)
+    }
 
-            match Peer::call(
+    /// Devuelve la funcion para llamar a un peer con los datos de este nodo.
+    /// Como cliente SPV solo se mantienen los peers que aceptan bloom filters, y se les carga el filtro de las wallets.
+    fn peer_connector(&self) -> PeerConnector {
+        let sender_address = self.address;
+        let version = self.version;
+        let peer_config = self.peer_config.clone();
+        let peer_action_receiver = self.peer_action_receiver.clone();
+        let logger_sender = self.logger_sender.clone();
+        let node_action_sender = self.node_action_sender.clone();
+        let node_state_ref = self.node_state_ref.clone();
+        Box::new(move |address| {
+            let filter_load = match peer_config.spv {
+                true => Some(wallets_filter(&node_state_ref.get_wallets()?)?),
+                false => None,
+            };
+            let mut peer = Peer::call(
                 address,
-                self.address,
-                self.services,
-                self.version,
-                self.peer_action_receiver.clone(),
-                self.logger_sender.clone(),
-                self.node_action_sender.clone(),
-            ) {
-                Ok(peer) => {
-                    peers.push(peer);
-                    number_of_peers -= 1;
-                }
-                Err(error) => {
-                    send_log(
-                        &self.logger_sender,
-                        Log::Message(format!("Error connecting to peer: {:?}", error)),
-                    );
-                }
+                sender_address,
+                version,
+                &peer_config,
+                peer_action_receiver.clone(),
+                logger_sender.clone(),
+                node_action_sender.clone(),
+            )?;
+            let Some(filter_load) = filter_load else {
+                return Ok(peer);
             };
-        }
+            if peer.services & NODE_BLOOM == 0 {
+                peer.disconnect();
+                return Err(CustomError::PeerWithoutBloomFilters);
+            }
+            if let Err(error) = peer.send(filter_load) {
+                peer.disconnect();
+                return Err(error);
+            }
+            Ok(peer)
+        })
+    }
 
-        let mut node_state = self.node_state_ref.lock()?;
-        node_state.append_peers(peers);
-        Ok(())
+    /// Devuelve la funcion para atender a un peer que se conecto a este nodo.
+    fn peer_answerer(&self) -> PeerAnswerer {
+        let sender_address = self.address;
+        let version = self.version;
+        let peer_config = self.peer_config.clone();
+        let peer_action_receiver = self.peer_action_receiver.clone();
+        let logger_sender = self.logger_sender.clone();
+        let node_action_sender = self.node_action_sender.clone();
+        Box::new(move |stream| {
+            Peer::answer(
+                stream,
+                sender_address,
+                version,
+                &peer_config,
+                peer_action_receiver.clone(),
+                logger_sender.clone(),
+                node_action_sender.clone(),
+            )
+        })
+    }
+
+    fn initialize_connection_loop(&mut self) {
+        let (thread, terminate_sender) = ConnectionLoop::spawn(
+            self.logger_sender.clone(),
+            self.node_state_ref.clone(),
+            self.peer_connector(),
+            self.npeers,
+        );
+        self.connection_thread = Some(thread);
+        self.terminate_senders.push(terminate_sender);
     }
 
-    fn initialize_pending_blocks_loop(&self) {
-        pending_blocks_loop(
+    fn initialize_pending_blocks_loop(&mut self) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        self.terminate_senders.push(terminate_sender);
+        self.pending_blocks_thread = Some(pending_blocks_loop(
             self.node_state_ref.clone(),
             self.peer_action_sender.clone(),
+            self.node_action_sender.clone(),
             self.logger_sender.clone(),
-        );
+            self.sync_peers,
+            terminate_receiver,
+        ));
+    }
+
+    fn initialize_ping_loop(&mut self) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        self.terminate_senders.push(terminate_sender);
+        self.ping_thread = Some(ping_loop(
+            self.node_state_ref.clone(),
+            self.logger_sender.clone(),
+            self.peer_config.timeouts.ping,
+            terminate_receiver,
+        ));
+    }
+
+    fn initialize_rebroadcast_loop(&mut self) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        self.terminate_senders.push(terminate_sender);
+        self.rebroadcast_thread = Some(rebroadcast_loop(
+            self.node_action_sender.clone(),
+            terminate_receiver,
+        ));
+    }
+
+    fn initialize_flush_loop(&mut self) {
+        let (terminate_sender, terminate_receiver) = mpsc::channel();
+        self.terminate_senders.push(terminate_sender);
+        self.flush_thread = Some(flush_loop(
+            self.node_state_ref.clone(),
+            self.logger_sender.clone(),
+            terminate_receiver,
+        ));
     }
 
     fn initialize_tcp_listener_loop(&mut self) {
-        if !self.client_only {
-            self.tcp_listener_thread = Some(TcpListenerLoop::spawn(
+        if self.client_only {
+            return;
+        }
+        for address in self.bind_addresses.clone() {
+            let (thread, terminate_sender) = TcpListenerLoop::spawn(
                 self.logger_sender.clone(),
                 self.node_state_ref.clone(),
-                self.address,
-                self.services,
-                self.version,
-                self.peer_action_receiver.clone(),
-                self.node_action_sender.clone(),
+                address,
+                self.peer_answerer(),
+                self.max_inbound,
+            );
+            self.tcp_listener_threads.push((address, thread));
+            self.terminate_senders.push(terminate_sender);
+        }
+    }
+
+    fn initialize_rest_api_loop(&mut self) {
+        if self.rest_port != 0 {
+            let (thread, terminate_sender) = RestApiLoop::spawn(
+                self.logger_sender.clone(),
+                self.node_state_ref.clone(),
+                self.rest_port,
+            );
+            self.rest_api_thread = Some(thread);
+            self.terminate_senders.push(terminate_sender);
+        }
+    }
+
+    fn initialize_rpc_server_loop(&mut self) {
+        if self.rpc_port != 0 {
+            let rpc_handler = RpcHandler {
+                node_state_ref: self.node_state_ref.clone(),
+                node_action_sender: self.node_action_sender.clone(),
+            };
+            let (thread, terminate_sender) =
+                RpcServerLoop::spawn(self.logger_sender.clone(), rpc_handler, self.rpc_port);
+            self.rpc_server_thread = Some(thread);
+            self.terminate_senders.push(terminate_sender);
+        }
+    }
+
+    fn initialize_publisher_loop(&mut self) -> Result<(), CustomError> {
+        if self.publisher_port != 0 {
+            let (publisher_sender, publisher_receiver) = mpsc::channel();
+            self.publisher_thread = Some(PublisherLoop::spawn(
+                self.logger_sender.clone(),
+                publisher_receiver,
+                self.publisher_port,
+            )?);
+            self.publisher_sender = Some(publisher_sender);
+        }
+        Ok(())
+    }
+
+    fn initialize_price_loop(&mut self, gui_sender: mpsc::Sender<GUIEvents>) {
+        if let Some(price_cache) = self.price_cache.take() {
+            let (terminate_sender, terminate_receiver) = mpsc::channel();
+            self.terminate_senders.push(terminate_sender);
+            self.price_thread = Some(price_loop(
+                price_cache,
+                gui_sender,
+                self.logger_sender.clone(),
+                terminate_receiver,
             ));
         }
     }
 
-    fn initialize_ibd(&self) -> Result<(), CustomError> {
-        let mut node_state = self.node_state_ref.lock()?;
-        let last_header = node_state.get_last_header_hash();
-        let fastest_peer = node_state.get_fastest_peer();
+    /// Termina ordenadamente los threads del nodo y guarda su estado en disco.
+    /// - Indica a los loops auxiliares que terminen y los espera.
+    /// - Guarda como anclas a los peers salientes de larga duracion.
+    /// - Envia PeerAction::Terminate a cada peer, cierra su conexion y espera a sus dos threads.
+    /// - Espera al publisher, que termina al cerrarse el NodeActionLoop.
+    /// - Guarda en disco los headers, las UTXO y las wallets.
+    fn shutdown(&mut self) -> Result<(), CustomError> {
+        send_log(
+            &self.logger_sender,
+            Log::Message(String::from("Shutting down node...")),
+        );
 
-        if let Some(fastest_peer) = fastest_peer {
-            request_headers(
-                last_header,
-                self.version,
-                &mut fastest_peer.stream,
-                &self.logger_sender,
-                &self.node_action_sender,
-            )?;
+        for terminate_sender in self.terminate_senders.drain(..) {
+            terminate_sender.send(()).ok();
+        }
+        for (address, _) in &self.tcp_listener_threads {
+            wake_listener(*address);
+        }
+        if self.rest_api_thread.is_some() {
+            wake_listener(SocketAddr::from((Ipv6Addr::LOCALHOST, self.rest_port)));
+        }
+        if self.rpc_server_thread.is_some() {
+            wake_listener(SocketAddr::from((Ipv6Addr::LOCALHOST, self.rpc_port)));
+        }
+        join_thread(
+            &self.logger_sender,
+            "pending_blocks_loop",
+            self.pending_blocks_thread.take(),
+        );
+        join_thread(&self.logger_sender, "ping_loop", self.ping_thread.take());
+        join_thread(
+            &self.logger_sender,
+            "rebroadcast_loop",
+            self.rebroadcast_thread.take(),
+        );
+        join_thread(&self.logger_sender, "flush_loop", self.flush_thread.take());
+        join_thread(&self.logger_sender, "price_loop", self.price_thread.take());
+        for (_, thread) in self.tcp_listener_threads.drain(..) {
+            join_thread(&self.logger_sender, "tcp_listener_loop", Some(thread));
+        }
+        join_thread(
+            &self.logger_sender,
+            "connection_loop",
+            self.connection_thread.take(),
+        );
+        join_thread(
+            &self.logger_sender,
+            "rest_api_loop",
+            self.rest_api_thread.take(),
+        );
+        join_thread(
+            &self.logger_sender,
+            "rpc_server_loop",
+            self.rpc_server_thread.take(),
+        );
 
-            send_log(
+        if let Err(error) = self.node_state_ref.save_anchors() {
+            send_log(&self.logger_sender, Log::Error(error));
+        }
+
+        // Se reemplaza el sender para que, al terminar de enviar los Terminate, no quede ningun sender vivo
+        // y tambien terminen los threads de peers que ya habian sido eliminados del nodo.
+        let peer_action_sender = std::mem::replace(&mut self.peer_action_sender, mpsc::channel().0);
+        let peers = self.node_state_ref.take_peers()?;
+        for _ in peers.iter() {
+            peer_action_sender.send(PeerAction::Terminate)?;
+        }
+        drop(peer_action_sender);
+        for mut peer in peers {
+            peer.disconnect();
+            join_thread(
                 &self.logger_sender,
-                Log::Message(format!(
-                    "Starting headers download with fastest peer: {}",
-                    fastest_peer.address.ip()
-                )),
+                "peer_action_loop",
+                peer.peer_action_thread.take(),
+            );
+            join_thread(
+                &self.logger_sender,
+                "peer_stream_loop",
+                peer.peer_stream_thread.take(),
             );
-
-            return Ok(());
         }
 
-        drop(node_state);
-        self.peer_action_sender
-            .send(PeerAction::GetHeaders(last_header))?;
+        self.publisher_sender.take();
+        join_thread(
+            &self.logger_sender,
+            "publisher_loop",
+            self.publisher_thread.take(),
+        );
+
+        self.node_state_ref.flush()?;
+        send_log(
+            &self.logger_sender,
+            Log::Message(String::from("Node state saved, shutdown completed")),
+        );
         Ok(())
     }
 
     fn initialize_event_loop(
         &mut self,
-        gui_sender: glib::Sender<GUIEvents>,
+        gui_sender: mpsc::Sender<GUIEvents>,
     ) -> Result<(), CustomError> {
         if let Some(receiver) = self.node_action_receiver.take() {
             NodeActionLoop::start(
@@ -221,6 +550,8 @@ impl Node {
                 self.peer_action_sender.clone(),
                 self.logger_sender.clone(),
                 self.node_state_ref.clone(),
+                self.publisher_sender.take(),
+                self.sync_peers,
             );
             return Ok(());
         }
@@ -228,22 +559,35 @@ impl Node {
     }
 }
 
-impl Drop for Node {
-    /// Espera a que los threads de PeerActions terminen.
-    /// Estos junto al NodeActionLoop son los únicos threads que modifican el estado del nodo, por lo que hace falta esperarlos.
-    fn drop(&mut self) {
-        if let Ok(mut node_state) = self.node_state_ref.lock() {
-            let peers = node_state.get_peers();
-            for _ in peers.iter() {
-                self.peer_action_sender.send(PeerAction::Terminate).unwrap();
-            }
-            for peer in peers {
-                if let Some(thread) = peer.peer_action_thread.take() {
-                    if let Err(error) = thread.join() {
-                        println!("Error joining thread: {:?}", error);
-                    }
-                }
-            }
-        }
+/// Espera a que termine un thread del nodo, registrando si termino con error.
+fn join_thread(
+    logger_sender: &mpsc::Sender<Log>,
+    name: &str,
+    thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
+) {
+    let Some(thread) = thread else {
+        return;
+    };
+    match thread.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(error)) => send_log(
+            logger_sender,
+            Log::Message(format!("Thread {} finished with error: {}", name, error)),
+        ),
+        Err(error) => send_log(
+            logger_sender,
+            Log::Message(format!("Error joining thread {}: {:?}", name, error)),
+        ),
     }
 }
+
+/// Despierta a un loop bloqueado esperando conexiones entrantes, para que verifique si debe terminar.
+/// Si el loop escucha en todas las interfaces, se conecta a la direccion local de la misma familia.
+fn wake_listener(address: SocketAddr) {
+    let ip = match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    TcpStream::connect(SocketAddr::new(ip, address.port())).ok();
+}
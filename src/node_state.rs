@@ -1,201 +1,931 @@
 use std::{
     collections::HashMap,
     fs,
-    net::SocketAddrV6,
+    net::{Ipv6Addr, SocketAddrV6},
     path::Path,
-    sync::{mpsc, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, RwLock,
+    },
+    time::Duration,
 };
 
-use gtk::glib::Sender;
-
 use crate::{
+    access_list::AccessList,
     error::CustomError,
-    gui::init::GUIEvents,
-    logger::{send_log, Log},
-    messages::{block::Block, get_headers::GetHeaders, headers::Headers, transaction::Transaction},
-    peer::Peer,
+    events::GUIEvents,
+    logger::{send_log, Log, LogTarget},
+    messages::{
+        block::{Block, MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT},
+        cfcheckpt::CFCHECKPT_INTERVAL,
+        cfheaders::MAX_GETCFHEADERS_SIZE,
+        cfilters::MAX_GETCFILTERS_SIZE,
+        get_data::GetData,
+        get_headers::GetHeaders,
+        headers::Headers,
+        mempool::Mempool,
+        transaction::{LockPoint, Transaction},
+    },
+    network::Network,
+    notify::NotifyCommands,
+    peer::{Misbehavior, Peer},
+    spv::{block_inventory, wallets_filter, NODE_BLOOM},
     states::{
+        address_book_state::AddressBookState,
+        addresses_state::AddressesState,
+        anchors_state::{AnchorsState, ANCHOR_MIN_UPTIME},
+        bans_state::{BanEntry, BansState},
+        block_store::BlockStore,
         blocks_state::BlocksState,
         headers_state::HeadersState,
-        pending_blocks_state::PendingBlocks,
+        pending_blocks_state::{PendingBlocks, MAX_BLOCKS_IN_FLIGHT_PER_PEER},
         pending_txs_state::PendingTxs,
-        utxo_state::{UTXOValue, UTXO},
+        utxo_snapshot::SnapshotBase,
+        utxo_state::{block_subsidy, UTXOValue, UTXO},
+        wallets_backup::WalletsBackup,
         wallets_state::WalletsState,
     },
-    structs::{block_header::BlockHeader, movement::Movement, outpoint::OutPoint},
-    wallet::Wallet,
+    structs::{
+        acceleration_preview::AccelerationPreview,
+        block_filter::BlockFilter,
+        block_header::BlockHeader,
+        block_template::{
+            solve_header, BlockTemplate, BLOCK_TEMPLATE_VERSION, COINBASE_RESERVED_SIGOPS_COST,
+            COINBASE_RESERVED_WEIGHT,
+        },
+        bloom_filter::BloomFilter,
+        consolidation_preview::ConsolidationPreview,
+        hash32::Hash32,
+        history_query::{HistoryPage, HistoryQuery},
+        inventory::{Inventory, InventoryType},
+        movement::Movement,
+        net_address::NetAddress,
+        node_status::NodeStatus,
+        outpoint::OutPoint,
+        peer_info::PeerInfo,
+        sync_progress::{estimate_network_height, SyncProgress, SyncProgressTracker},
+        transaction_details::TransactionDetails,
+        transfer_summary::TransferSummary,
+        tx_output::TransactionOutput,
+    },
+    utils::{dir_size, get_current_timestamp, get_current_timestamp_millis},
+    wallet::{get_pubkey_hash, merge_recipients, Wallet},
 };
 
+/// Segundos durante los que un peer entrante que envio un mensaje util no puede ser desalojado.
+/// Los peers recien conectados tambien quedan protegidos durante este tiempo.
+const EVICTION_PROTECTION_TIME: u64 = 2 * 60;
+
 /// NodeState es una estructura que contiene el estado del nodo.
+/// Cada componente tiene su propio lock, de forma que la GUI, el procesamiento de bloques y el manejo de peers
+/// no se bloqueen entre si. Para evitar deadlocks, cuando se toman varios locks a la vez se respeta el orden
+/// headers, blocks, pending_blocks, utxo, wallets, pending_txs, peers, bans, addresses, anchors, address_book, sync_progress, notify_commands.
 /// Los elementos son (para mas informacion de cada una de estas estructuras ver su documentacion en la carpeta states):
 /// - logger_sender: Sender para enviar logs al logger.
 /// - gui_sender: Sender para enviar eventos a la interfaz grafica.
+/// - store_path: Directorio donde se guardan los archivos del nodo.
+/// - network: Red a la que se conecta el nodo.
+/// - local_services: Servicios que anuncia el nodo, determinan que pedidos de los peers se atienden.
+/// - spv: Indica si el nodo funciona como cliente SPV, sin descargar los bloques completos.
+/// - blocks_only: Indica si el nodo funciona en modo solo bloques, sin pedir ni retransmitir transacciones de los peers.
+/// - access_list: Lista de redes cuyos peers no se banean ni se desalojan.
 /// - headers: HeadersState.
 /// - peers: Vector de peers conectados al nodo.
 /// - wallets: WalletsState.
 /// - blocks: BlocksState.
+/// - pending_blocks_ref: Referencia a los PendingBlocks, compartida con BlocksState.
 /// - utxo: UTXO.
 /// - pending_txs: PendingTxs.
+/// - bans: BansState.
+/// - addresses: AddressesState, direcciones de peers conocidas.
+/// - anchors: AnchorsState, peers salientes a los que se intenta reconectar primero al iniciar.
+/// - address_book: AddressBookState, etiquetas de las direcciones a las que se envia y de los movimientos de las wallets.
+/// - sync_progress: SyncProgressTracker, mide la velocidad de descarga para reportar el progreso de la sincronizacion.
+/// - notify_commands: NotifyCommands, comandos externos que se ejecutan con cada bloque nuevo y transaccion de las wallets.
 pub struct NodeState {
     logger_sender: mpsc::Sender<Log>,
-    gui_sender: Sender<GUIEvents>,
-    headers: HeadersState,
-    peers: Vec<Peer>,
-    wallets: WalletsState,
-    blocks: BlocksState,
-    utxo: UTXO,
-    pending_txs: PendingTxs,
+    gui_sender: mpsc::Sender<GUIEvents>,
+    store_path: String,
+    network: Network,
+    local_services: AtomicU64,
+    spv: AtomicBool,
+    blocks_only: AtomicBool,
+    access_list: RwLock<AccessList>,
+    headers: RwLock<HeadersState>,
+    peers: Mutex<Vec<Peer>>,
+    wallets: RwLock<WalletsState>,
+    blocks: Mutex<BlocksState>,
+    pending_blocks_ref: Arc<Mutex<PendingBlocks>>,
+    utxo: RwLock<UTXO>,
+    pending_txs: Mutex<PendingTxs>,
+    bans: Mutex<BansState>,
+    addresses: Mutex<AddressesState>,
+    anchors: Mutex<AnchorsState>,
+    address_book: Mutex<AddressBookState>,
+    sync_progress: Mutex<SyncProgressTracker>,
+    notify_commands: RwLock<NotifyCommands>,
 }
 
 impl NodeState {
     /// Inicializa el estado del nodo. Inicializa todas sus estructuras indicando donde se encuentra el archivo donde se guardan.
     pub fn new(
         logger_sender: mpsc::Sender<Log>,
-        gui_sender: Sender<GUIEvents>,
+        gui_sender: mpsc::Sender<GUIEvents>,
         store_path: &String,
-    ) -> Result<Arc<Mutex<Self>>, CustomError> {
+        network: Network,
+    ) -> Result<Arc<Self>, CustomError> {
         send_log(
             &logger_sender,
             Log::Message(String::from("Initializing node state...")),
         );
         create_store_dir(store_path)?;
 
-        let headers =
-            HeadersState::new(format!("{}/headers.bin", store_path), logger_sender.clone())?;
-        let pending_blocks_ref = PendingBlocks::new(store_path, headers.get_all());
+        let mut headers = HeadersState::new(
+            format!("{}/headers.bin", store_path),
+            logger_sender.clone(),
+            network,
+        )?;
+        let snapshot_base = SnapshotBase::load(store_path)?;
+        if let Some(base) = snapshot_base {
+            send_log(
+                &logger_sender,
+                Log::Info(
+                    LogTarget::Sync,
+                    format!(
+                        "Starting from the UTXO snapshot at height {} ({})",
+                        base.height, base.block_hash
+                    ),
+                ),
+            );
+        }
+        headers.set_snapshot_base(snapshot_base);
+        let mut block_store = BlockStore::open(format!("{}/blocks", store_path), network)?;
+        let migrated = block_store.migrate_legacy_blocks()?;
+        if migrated > 0 {
+            send_log(
+                &logger_sender,
+                Log::Info(
+                    LogTarget::Sync,
+                    format!("Migrated {migrated} blocks to the block files"),
+                ),
+            );
+        }
+        // solo se leen del archivo los headers desde el inicio del IBD, con la altura del snapshot relativa a ellos
+        let first_block_index = headers.first_block_index();
+        let pending_blocks_ref = PendingBlocks::new(
+            &block_store,
+            &headers.iter_from(first_block_index).collect(),
+            snapshot_base.map_or(0, |base| base.height.saturating_sub(first_block_index)),
+        );
 
-        let node_state_ref = Arc::new(Mutex::new(Self {
+        let node_state_ref = Arc::new(Self {
             logger_sender: logger_sender.clone(),
             gui_sender,
-            headers,
-            peers: vec![],
-            wallets: WalletsState::new(format!("{}/wallets.bin", store_path))?,
-            blocks: BlocksState::new(store_path.clone(), logger_sender, pending_blocks_ref),
-            utxo: UTXO::new(store_path.clone(), "/utxo.bin".to_string())?,
-            pending_txs: PendingTxs::new(),
-        }));
+            store_path: store_path.clone(),
+            network,
+            local_services: AtomicU64::new(0),
+            spv: AtomicBool::new(false),
+            blocks_only: AtomicBool::new(false),
+            access_list: RwLock::new(AccessList::default()),
+            headers: RwLock::new(headers),
+            peers: Mutex::new(vec![]),
+            wallets: RwLock::new(WalletsState::new(
+                format!("{}/wallets.bin", store_path),
+                logger_sender.clone(),
+            )?),
+            blocks: Mutex::new(BlocksState::new(
+                store_path.clone(),
+                block_store,
+                logger_sender.clone(),
+                pending_blocks_ref.clone(),
+            )),
+            pending_blocks_ref,
+            utxo: RwLock::new(UTXO::new(store_path.clone(), "/utxo.bin".to_string())?),
+            pending_txs: Mutex::new(PendingTxs::from_file(format!(
+                "{}/mempool.bin",
+                store_path
+            ))?),
+            bans: Mutex::new(BansState::new(
+                format!("{}/bans.bin", store_path),
+                get_current_timestamp()?,
+            )?),
+            addresses: Mutex::new(AddressesState::new(format!("{}/peers.bin", store_path))?),
+            anchors: Mutex::new(AnchorsState::new(format!("{}/anchors.bin", store_path))?),
+            address_book: Mutex::new(AddressBookState::new(
+                format!("{}/address_book.bin", store_path),
+                logger_sender,
+            )?),
+            sync_progress: Mutex::new(SyncProgressTracker::default()),
+            notify_commands: RwLock::new(NotifyCommands::default()),
+        });
 
         Ok(node_state_ref)
     }
 
+    /// Verifica que ninguna transaccion del bloque gaste mas de lo que recibe, que la coinbase no supere
+    /// el subsidio mas los fees, que no se supere el costo maximo en sigops y que se cumplan los lock time
+    /// de sus transacciones, usando las UTXO y los headers actuales.
+    /// Devuelve CustomError si el bloque no cumple alguna de las condiciones.
+    pub fn validate_block(&self, block: &Block) -> Result<(), CustomError> {
+        let headers = self.headers.read()?;
+        self.utxo.read()?.validate_block(block, &headers)
+    }
+
     /// Agrega un bloque nuevo, lo guarda en su archivo y actualiza los pending_blocks, wallets, pending_txs y utxo.
     /// Tambien verifica si ahora el nodo esta actualizado con la red
-    pub fn append_block(&mut self, block_hash: Vec<u8>, block: &Block) -> Result<(), CustomError> {
+    pub fn append_block(&self, block_hash: Hash32, block: &Block) -> Result<(), CustomError> {
+        let total_headers = self.headers.read()?.total_headers_to_download();
         self.blocks
-            .append_block(&block_hash, block, self.headers.total_headers_to_download())?;
-        self.headers.set_downloaded(&block_hash);
+            .lock()?
+            .append_block(&block_hash, block, total_headers)?;
+        self.headers.write()?.set_downloaded(&block_hash);
 
         self.verify_sync()?;
+        self.report_sync_progress()?;
 
         self.update_wallets(block)?;
         self.update_pending_tx(block)?;
 
-        if self.is_synced() {
-            self.utxo.update_from_block(block, true)?;
+        // al igual que blocknotify de Bitcoin Core, no se notifican los bloques de la descarga inicial
+        if self.is_synced()? {
+            self.notify_commands
+                .read()?
+                .notify_block(&self.logger_sender, &block_hash);
+        }
+
+        if self.is_synced()? {
+            let spent_scripts = self.utxo.write()?.update_from_block(block, true)?;
+            self.blocks.lock()?.append_filter(block, &spent_scripts)?;
         }
 
         Ok(())
     }
 
     /// Obtiene un bloque a partir de su hash
-    pub fn get_block(&self, block_string_hash: String) -> Result<Block, CustomError> {
-        self.blocks.get_block(block_string_hash)
+    pub fn get_block(&self, block_hash: &Hash32) -> Result<Block, CustomError> {
+        self.blocks.lock()?.get_block(block_hash)
+    }
+
+    /// Indica si el bloque con el hash recibido esta descargado.
+    pub fn has_block(&self, block_hash: &Hash32) -> Result<bool, CustomError> {
+        Ok(self.blocks.lock()?.has_block(block_hash))
+    }
+
+    /// Devuelve la cantidad de bloques que hay por encima del bloque con el hash recibido, o None si no se conoce.
+    pub fn get_block_depth(&self, block_hash: &Hash32) -> Result<Option<usize>, CustomError> {
+        Ok(self.headers.read()?.get_depth(block_hash))
+    }
+
+    /// Devuelve los detalles de la transaccion con el hash recibido, buscandola en el bloque recibido,
+    /// o entre las pending txs si no tiene bloque. Devuelve None si no se encuentra la transaccion.
+    /// Las salidas que gastan sus entradas se buscan en el UTXO, en las pending txs y, como no hay un indice de transacciones,
+    /// en los bloques de las transacciones del historial de la wallet activa.
+    pub fn get_transaction_details(
+        &self,
+        tx_hash: &Hash32,
+        block_hash: Option<Hash32>,
+    ) -> Result<Option<TransactionDetails>, CustomError> {
+        let (transaction, confirmations) = match block_hash {
+            Some(block_hash) => {
+                let block = self.get_block(&block_hash)?;
+                let Some(transaction) = block
+                    .transactions
+                    .into_iter()
+                    .find(|tx| tx.hash() == *tx_hash)
+                else {
+                    return Ok(None);
+                };
+                let depth = self.get_block_depth(&block_hash)?;
+                (transaction, depth.map_or(0, |depth| depth + 1))
+            }
+            None => {
+                let Some(transaction) = self.get_pending_tx(tx_hash)? else {
+                    return Ok(None);
+                };
+                (transaction, 0)
+            }
+        };
+
+        let utxo = self.utxo.read()?;
+        let mut spent_outputs: Vec<Option<TransactionOutput>> = self
+            .pending_txs
+            .lock()?
+            .get_spent_outputs(&transaction, &utxo)
+            .into_iter()
+            .map(|spent_output| spent_output.map(|(output, _)| output))
+            .collect();
+        drop(utxo);
+
+        let history = match self.get_active_wallet()? {
+            Some(wallet) => wallet.get_history(),
+            None => vec![],
+        };
+        for (input, spent_output) in transaction.inputs.iter().zip(spent_outputs.iter_mut()) {
+            if spent_output.is_some() {
+                continue;
+            }
+            let previous_output = &input.previous_output;
+            let Some(previous_block_hash) = history
+                .iter()
+                .find(|movement| movement.tx_hash == previous_output.hash)
+                .and_then(|movement| movement.block_hash)
+            else {
+                continue;
+            };
+            let Ok(block) = self.get_block(&previous_block_hash) else {
+                continue;
+            };
+            *spent_output = block
+                .transactions
+                .into_iter()
+                .find(|tx| tx.hash() == previous_output.hash)
+                .and_then(|tx| tx.outputs.into_iter().nth(previous_output.index as usize));
+        }
+
+        Ok(Some(TransactionDetails {
+            transaction,
+            block_hash,
+            confirmations,
+            spent_outputs,
+        }))
+    }
+
+    /// Devuelve los filtros de los bloques desde start_height hasta stop_hash, para responder un 'getcfilters'.
+    /// Devuelve None si el rango es invalido o si falta el filtro de alguno de los bloques.
+    pub fn get_filters(
+        &self,
+        start_height: u32,
+        stop_hash: &Hash32,
+    ) -> Result<Option<Vec<(Hash32, BlockFilter)>>, CustomError> {
+        let headers = self.headers.read()?;
+        let Some(block_hashes) =
+            headers.get_hashes_range(start_height as usize, stop_hash, MAX_GETCFILTERS_SIZE)
+        else {
+            return Ok(None);
+        };
+
+        let blocks = self.blocks.lock()?;
+        let mut filters = vec![];
+        for block_hash in block_hashes {
+            let Some((filter, _)) = blocks.get_filter(&block_hash)? else {
+                return Ok(None);
+            };
+            filters.push((block_hash, filter));
+        }
+        Ok(Some(filters))
+    }
+
+    /// Devuelve el header del filtro anterior a start_height y los hashes de los filtros de los bloques
+    /// hasta stop_hash, para responder un 'getcfheaders'.
+    /// Devuelve None si el rango es invalido o si falta el filtro de alguno de los bloques.
+    pub fn get_filter_hashes(
+        &self,
+        start_height: u32,
+        stop_hash: &Hash32,
+    ) -> Result<Option<(Hash32, Vec<Hash32>)>, CustomError> {
+        let headers = self.headers.read()?;
+        let Some(block_hashes) =
+            headers.get_hashes_range(start_height as usize, stop_hash, MAX_GETCFHEADERS_SIZE)
+        else {
+            return Ok(None);
+        };
+
+        let blocks = self.blocks.lock()?;
+        let mut previous_header = None;
+        let mut filter_hashes = vec![];
+        for block_hash in block_hashes {
+            let Some((filter, filter_previous_header)) = blocks.get_filter(&block_hash)? else {
+                return Ok(None);
+            };
+            previous_header.get_or_insert(filter_previous_header);
+            filter_hashes.push(filter.hash());
+        }
+        Ok(previous_header.map(|previous_header| (previous_header, filter_hashes)))
+    }
+
+    /// Devuelve los headers de los filtros cada CFCHECKPT_INTERVAL bloques hasta stop_hash, para responder un 'getcfcheckpt'.
+    /// Devuelve None si no se encuentra stop_hash o si falta el filtro de alguno de los checkpoints.
+    pub fn get_filter_checkpoints(
+        &self,
+        stop_hash: &Hash32,
+    ) -> Result<Option<Vec<Hash32>>, CustomError> {
+        let headers = self.headers.read()?;
+        let Some(block_hashes) = headers.get_hashes_range(1, stop_hash, usize::MAX) else {
+            return Ok(None);
+        };
+
+        let blocks = self.blocks.lock()?;
+        let mut filter_headers = vec![];
+        for block_hash in block_hashes
+            .iter()
+            .skip(CFCHECKPT_INTERVAL - 1)
+            .step_by(CFCHECKPT_INTERVAL)
+        {
+            let Some((filter, previous_header)) = blocks.get_filter(block_hash)? else {
+                return Ok(None);
+            };
+            filter_headers.push(filter.header(&previous_header));
+        }
+        Ok(Some(filter_headers))
+    }
+
+    /// Guarda en disco los headers, las UTXO y las wallets que cambiaron desde la ultima vez que se guardaron.
+    /// Los headers se guardan primero, para que el ultimo bloque de las UTXO guardadas siempre este entre los headers guardados.
+    pub fn flush_state(&self) -> Result<(), CustomError> {
+        self.headers.write()?.flush()?;
+        self.utxo.write()?.flush()?;
+        self.wallets.write()?.flush()
+    }
+
+    /// Guarda en disco los headers, las UTXO, las wallets, las transacciones pendientes y las direcciones de peers conocidas del nodo.
+    pub fn flush(&self) -> Result<(), CustomError> {
+        self.flush_state()?;
+        self.pending_txs.lock()?.flush()?;
+        self.addresses.lock()?.flush()
     }
 
     /********************     PEERS     ********************/
 
-    /// Devuelve referencia a los peers del nodo
-    pub fn get_peers(&mut self) -> &mut Vec<Peer> {
-        &mut self.peers
+    /// Bloquea y devuelve los peers del nodo
+    pub fn get_peers(&self) -> Result<MutexGuard<'_, Vec<Peer>>, CustomError> {
+        Ok(self.peers.lock()?)
     }
 
-    /// Devuelve referencia a un peer en particular
-    pub fn get_peer(&mut self, address: &SocketAddrV6) -> Option<&mut Peer> {
-        self.peers.iter_mut().find(|p| &p.address == address)
+    /// Devuelve los datos de los peers conectados, para listarlos sin mantener bloqueados a los peers.
+    pub fn get_peers_info(&self) -> Result<Vec<PeerInfo>, CustomError> {
+        Ok(self.peers.lock()?.iter().map(PeerInfo::from).collect())
     }
 
     /// Agrega varios peers nuevos al nodo
-    pub fn append_peers(&mut self, peers: Vec<Peer>) {
-        self.peers.extend(peers);
+    pub fn append_peers(&self, peers: Vec<Peer>) -> Result<(), CustomError> {
+        self.peers.lock()?.extend(peers);
+        Ok(())
+    }
+
+    /// Devuelve la cantidad de peers conectados por iniciativa del nodo (salientes).
+    pub fn count_outbound_peers(&self) -> Result<usize, CustomError> {
+        Ok(self
+            .peers
+            .lock()?
+            .iter()
+            .filter(|peer| !peer.inbound)
+            .count())
+    }
+
+    /// Devuelve la cantidad de peers que se conectaron al nodo (entrantes).
+    pub fn count_inbound_peers(&self) -> Result<usize, CustomError> {
+        Ok(self
+            .peers
+            .lock()?
+            .iter()
+            .filter(|peer| peer.inbound)
+            .count())
+    }
+
+    /// Desconecta al peer entrante menos util para liberar un lugar para una nueva conexion entrante.
+    /// Solo se consideran los peers fuera de la whitelist que no enviaron mensajes utiles en los ultimos EVICTION_PROTECTION_TIME segundos,
+    /// y entre ellos se elige el de mayor latencia y, a igual latencia, el que hace mas tiempo no envia mensajes utiles.
+    /// Devuelve la direccion del peer desalojado, o None si todos los peers entrantes son utiles.
+    pub fn evict_inbound_peer(&self) -> Result<Option<SocketAddrV6>, CustomError> {
+        let protected_since = get_current_timestamp()?.saturating_sub(EVICTION_PROTECTION_TIME);
+        let access_list = self.access_list.read()?;
+        let mut peers = self.peers.lock()?;
+        let evicted = peers
+            .iter()
+            .filter(|peer| peer.inbound && peer.get_last_useful_message() < protected_since)
+            .filter(|peer| !access_list.is_whitelisted(peer.address.ip()))
+            .max_by_key(|peer| (peer.latency, u64::MAX - peer.get_last_useful_message()))
+            .map(|peer| peer.address);
+
+        if let Some(address) = evicted {
+            remove_peer(&mut peers, address);
+        }
+        Ok(evicted)
+    }
+
+    /// Envia un ping a cada peer que no tenga uno pendiente.
+    /// Desconecta a los peers que no respondieron su ping dentro de timeout y devuelve sus direcciones.
+    pub fn ping_peers(&self, timeout: Duration) -> Result<Vec<SocketAddrV6>, CustomError> {
+        let now = get_current_timestamp_millis()?;
+        let mut peers = self.peers.lock()?;
+        let timed_out: Vec<SocketAddrV6> = peers
+            .iter()
+            .filter(|peer| peer.ping_timed_out(now, timeout))
+            .map(|peer| peer.address)
+            .collect();
+        for address in &timed_out {
+            remove_peer(&mut peers, *address);
+        }
+
+        for peer in peers.iter_mut().filter(|peer| peer.pending_ping.is_none()) {
+            // Si no se puede enviar el ping, el peer_stream_thread detecta la conexion cerrada
+            peer.ping().ok();
+        }
+        Ok(timed_out)
+    }
+
+    /// Registra el pong recibido de un peer.
+    /// Devuelve la latencia medida, o None si el pong no corresponde a un ping pendiente.
+    pub fn peer_pong(&self, address: SocketAddrV6, nonce: u64) -> Result<Option<i64>, CustomError> {
+        let mut peers = self.peers.lock()?;
+        let Some(peer) = peers.iter_mut().find(|p| p.address == address) else { return Ok(None) };
+        peer.pong(nonce)
+    }
+
+    /// Pide su mempool a los peers que aceptan bloom filters, ya que el resto desconecta a quien lo envia.
+    /// Devuelve la cantidad de peers a los que se les pidio.
+    pub fn request_mempools(&self) -> Result<usize, CustomError> {
+        let mut peers = self.peers.lock()?;
+        let mut requested = 0;
+        for peer in peers
+            .iter_mut()
+            .filter(|peer| peer.services & NODE_BLOOM != 0)
+        {
+            // Si no se puede enviar, el peer_stream_thread detecta la conexion cerrada
+            if peer.send(Mempool::new()).is_ok() {
+                requested += 1;
+            }
+        }
+        Ok(requested)
+    }
+
+    /// Pide los headers siguientes a last_header a count de los sync_peers peers mas rapidos, rotando entre ellos segun round.
+    /// Devuelve las direcciones de los peers a los que se les pidieron, vacia si no hay peers a los que pedirselos.
+    pub fn request_headers_from_sync_peers(
+        &self,
+        last_header: Option<Hash32>,
+        sync_peers: usize,
+        round: usize,
+        count: usize,
+    ) -> Result<Vec<SocketAddrV6>, CustomError> {
+        let mut peers = self.peers.lock()?;
+        let ranked = rank_sync_peers(&peers, sync_peers);
+        let mut requested = vec![];
+        for i in 0..count.min(ranked.len()) {
+            let address = ranked[(round + i) % ranked.len()];
+            let Some(peer) = peers.iter_mut().find(|p| p.address == address) else {
+                continue;
+            };
+            if peer.request_headers(last_header).is_ok() {
+                requested.push(address);
+            }
+        }
+        Ok(requested)
+    }
+
+    /// Pide los bloques pendientes que todavia no fueron pedidos a los sync_peers peers mas rapidos,
+    /// sin superar MAX_BLOCKS_IN_FLIGHT_PER_PEER bloques sin recibir por peer.
+    /// Devuelve la cantidad de bloques pedidos, o None si no hay peers conectados.
+    pub fn schedule_block_requests(&self, sync_peers: usize) -> Result<Option<usize>, CustomError> {
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
+        let mut peers = self.peers.lock()?;
+        let ranked = rank_sync_peers(&peers, sync_peers);
+        if ranked.is_empty() {
+            return Ok(None);
+        }
+
+        let mut requested = 0;
+        for address in ranked {
+            let free_slots = MAX_BLOCKS_IN_FLIGHT_PER_PEER
+                .saturating_sub(pending_blocks.count_in_flight(address));
+            let block_hashes = pending_blocks.get_unrequested(free_slots);
+            if block_hashes.is_empty() {
+                continue;
+            }
+            let Some(peer) = peers.iter_mut().find(|p| p.address == address) else {
+                continue;
+            };
+            let inventories = block_hashes
+                .iter()
+                .map(|block_hash| block_inventory(*block_hash, self.is_spv_mode()))
+                .collect();
+            if peer.send(GetData::new(inventories)).is_err() {
+                continue;
+            }
+            for block_hash in block_hashes {
+                pending_blocks.request_block(block_hash, address)?;
+                requested += 1;
+            }
+        }
+        Ok(Some(requested))
+    }
+
+    /// Indica si el nodo ya esta conectado con la direccion recibida.
+    pub fn is_connected(&self, address: SocketAddrV6) -> Result<bool, CustomError> {
+        Ok(self
+            .peers
+            .lock()?
+            .iter()
+            .any(|peer| peer.address == address))
     }
 
     /// Elimina del nodo a un peer en particular
-    pub fn remove_peer(&mut self, address: SocketAddrV6) {
-        let index = self.peers.iter().position(|p| p.address == address);
+    pub fn remove_peer(&self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let mut peers = self.peers.lock()?;
+        remove_peer(&mut peers, address);
+        Ok(())
+    }
+
+    /// Suma el puntaje de un mal comportamiento a un peer.
+    /// Devuelve el puntaje acumulado por el peer, o None si el peer ya no esta conectado.
+    pub fn peer_misbehaving(
+        &self,
+        address: SocketAddrV6,
+        misbehavior: Misbehavior,
+    ) -> Result<Option<u32>, CustomError> {
+        let mut peers = self.peers.lock()?;
+        let Some(peer) = peers.iter_mut().find(|p| p.address == address) else { return Ok(None) };
+        Ok(Some(peer.misbehave(misbehavior)))
+    }
+
+    /// Banea una direccion IP durante duration segundos y desconecta a los peers conectados desde ella.
+    pub fn set_ban(
+        &self,
+        address: Ipv6Addr,
+        reason: String,
+        duration: u64,
+    ) -> Result<(), CustomError> {
+        let banned_until = get_current_timestamp()? + duration;
+        let mut peers = self.peers.lock()?;
+        for peer in peers.iter().filter(|peer| *peer.address.ip() == address) {
+            peer.disconnect();
+        }
+        peers.retain(|peer| *peer.address.ip() != address);
+        self.bans.lock()?.ban(address, reason, banned_until)
+    }
+
+    /// Quita el baneo de una direccion IP, devuelve true si estaba baneada.
+    pub fn unban(&self, address: Ipv6Addr) -> Result<bool, CustomError> {
+        self.bans.lock()?.unban(address)
+    }
+
+    /// Quita todos los baneos.
+    pub fn clear_banned(&self) -> Result<(), CustomError> {
+        self.bans.lock()?.clear()
+    }
+
+    /// Devuelve los baneos vigentes.
+    pub fn list_banned(&self) -> Result<Vec<BanEntry>, CustomError> {
+        Ok(self.bans.lock()?.get_all(get_current_timestamp()?))
+    }
 
-        if let Some(index) = index {
-            self.peers.remove(index);
+    /// Indica si la direccion IP de un peer esta baneada o en la blacklist.
+    /// Las direcciones de la whitelist nunca se consideran baneadas.
+    pub fn is_banned(&self, address: SocketAddrV6) -> Result<bool, CustomError> {
+        let access_list = self.access_list.read()?;
+        if access_list.is_blacklisted(address.ip()) {
+            return Ok(true);
         }
+        if access_list.is_whitelisted(address.ip()) {
+            return Ok(false);
+        }
+        drop(access_list);
+        Ok(self
+            .bans
+            .lock()?
+            .is_banned(address.ip(), get_current_timestamp()?))
+    }
+
+    /// Registra en el address manager la direccion de un peer aprendida de la red.
+    pub fn add_address(
+        &self,
+        address: SocketAddrV6,
+        services: u64,
+        timestamp: u64,
+    ) -> Result<(), CustomError> {
+        self.addresses.lock()?.add(address, services, timestamp);
+        Ok(())
+    }
+
+    /// Registra en el address manager una conexion exitosa con un peer.
+    pub fn address_connected(&self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let timestamp = get_current_timestamp()?;
+        self.addresses.lock()?.mark_good(address, timestamp);
+        Ok(())
+    }
+
+    /// Registra en el address manager un intento fallido de conexion con un peer.
+    pub fn address_failed(&self, address: SocketAddrV6) -> Result<(), CustomError> {
+        self.addresses.lock()?.mark_attempt(address);
+        Ok(())
+    }
+
+    /// Devuelve hasta count direcciones conocidas para anunciar a otros peers.
+    pub fn get_addresses_to_share(&self, count: usize) -> Result<Vec<NetAddress>, CustomError> {
+        let sample = self.addresses.lock()?.get_sample(count);
+        Ok(sample
+            .into_iter()
+            .map(|known_address| NetAddress {
+                timestamp: known_address.last_seen as u32,
+                services: known_address.services,
+                address: known_address.address,
+            })
+            .collect())
+    }
+
+    /// Devuelve las direcciones conocidas en el orden en que conviene intentar conectarse.
+    pub fn get_address_candidates(&self) -> Result<Vec<SocketAddrV6>, CustomError> {
+        Ok(self.addresses.lock()?.get_candidates())
+    }
+
+    /// Devuelve los peers ancla guardados en la ejecucion anterior, solo la primera vez que se llama.
+    pub fn take_anchors(&self) -> Result<Vec<SocketAddrV6>, CustomError> {
+        Ok(self.anchors.lock()?.take())
+    }
+
+    /// Guarda como peers ancla a los peers salientes conectados hace al menos ANCHOR_MIN_UPTIME segundos,
+    /// priorizando a los conectados hace mas tiempo.
+    pub fn save_anchors(&self) -> Result<(), CustomError> {
+        let connected_before = get_current_timestamp()?.saturating_sub(ANCHOR_MIN_UPTIME);
+        let peers = self.peers.lock()?;
+        let mut anchors: Vec<&Peer> = peers
+            .iter()
+            .filter(|peer| !peer.inbound && peer.connected_at <= connected_before)
+            .collect();
+        anchors.sort_by_key(|peer| peer.connected_at);
+        let anchors: Vec<SocketAddrV6> = anchors.iter().map(|peer| peer.address).collect();
+        drop(peers);
+
+        self.anchors.lock()?.save(&anchors)
+    }
+
+    /// Quita todos los peers del nodo y los devuelve
+    pub fn take_peers(&self) -> Result<Vec<Peer>, CustomError> {
+        Ok(std::mem::take(&mut *self.peers.lock()?))
     }
 
     /// Registra que un peer solicito el envio directo de headers
-    pub fn peer_send_headers(&mut self, address: SocketAddrV6) {
-        let peer = self.peers.iter_mut().find(|p| p.address == address);
-        if let Some(peer) = peer {
+    pub fn peer_send_headers(&self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let mut peers = self.peers.lock()?;
+        if let Some(peer) = peers.iter_mut().find(|p| p.address == address) {
             peer.send_headers = true;
         }
+        Ok(())
     }
 
-    /// Registra que un peer solicito headers
-    pub fn peer_requested_headers(&mut self, address: SocketAddrV6) {
-        let peer = self.peers.iter_mut().find(|p| p.address == address);
-        if let Some(peer) = peer {
-            peer.requested_headers = true;
+    /// Indica si un peer prefiere recibir direcciones en mensajes addrv2.
+    pub fn peer_send_addr_v2(&self, address: SocketAddrV6) -> Result<bool, CustomError> {
+        let peers = self.peers.lock()?;
+        Ok(peers
+            .iter()
+            .any(|peer| peer.address == address && peer.send_addr_v2))
+    }
+
+    /// Registra el fee minimo de las transacciones que un peer quiere que le anuncien.
+    pub fn peer_fee_filter(&self, address: SocketAddrV6, fee_rate: u64) -> Result<(), CustomError> {
+        let mut peers = self.peers.lock()?;
+        if let Some(peer) = peers.iter_mut().find(|p| p.address == address) {
+            peer.fee_filter = fee_rate;
         }
+        Ok(())
     }
 
-    /// Obtiene el peer con el que haya realizado el handshake mas rapido
-    pub fn get_fastest_peer(&mut self) -> Option<&mut Peer> {
-        self.peers
-            .iter_mut()
-            .min_by(|a, b| a.benchmark.cmp(&b.benchmark))
+    /// Registra el bloom filter cargado por un peer, o lo descarta si es None.
+    /// Al cargar o descartar un filtro el peer pasa a recibir transacciones aunque haya enviado fRelay en false (BIP37).
+    pub fn peer_bloom_filter(
+        &self,
+        address: SocketAddrV6,
+        bloom_filter: Option<BloomFilter>,
+    ) -> Result<(), CustomError> {
+        let mut peers = self.peers.lock()?;
+        if let Some(peer) = peers.iter_mut().find(|p| p.address == address) {
+            peer.relay = true;
+            peer.bloom_filter = bloom_filter;
+        }
+        Ok(())
+    }
+
+    /// Registra que un peer solicito headers
+    pub fn peer_requested_headers(&self, address: SocketAddrV6) -> Result<(), CustomError> {
+        let mut peers = self.peers.lock()?;
+        if let Some(peer) = peers.iter_mut().find(|p| p.address == address) {
+            peer.requested_headers = true;
+        }
+        Ok(())
     }
 
     /********************     HEADERS     ********************/
 
     /// devuelve el hash del ultimo header guardado
-    pub fn get_last_header_hash(&self) -> Option<Vec<u8>> {
-        self.headers.get_last_header_hash()
+    pub fn get_last_header_hash(&self) -> Result<Option<Hash32>, CustomError> {
+        Ok(self.headers.read()?.get_last_header_hash())
     }
 
     /// agrega un header nuevo en HeadersState
-    pub fn append_headers(&mut self, headers: &Headers) -> Result<(), CustomError> {
+    /// Devuelve los headers que no se conocian, que son los unicos que se agregan.
+    pub fn append_headers(&self, headers: &Headers) -> Result<Headers, CustomError> {
+        let is_synced = self.is_synced()?;
         let mut new_headers = vec![];
 
         for header in headers.headers.iter() {
             let mut header = header.clone();
-            if !self.is_synced() {
+            if !is_synced {
                 header.broadcasted = true;
             }
             new_headers.push(header);
         }
 
-        self.headers.append_headers(new_headers)?;
+        let mut headers_state = self.headers.write()?;
+        let appended = headers_state.append_headers(new_headers)?;
+        if !headers_state.snapshot_base_matches() {
+            self.discard_snapshot(&mut headers_state)?;
+        }
+        drop(headers_state);
         self.gui_sender.send(GUIEvents::NewHeaders)?;
+        self.report_sync_progress()?;
 
+        Ok(Headers { headers: appended })
+    }
+
+    /// Descarta el snapshot de UTXO desde el que se inicio el nodo, porque su bloque no esta en la cadena de los headers.
+    /// Se borran el snapshot y las UTXO generadas a partir de el, y se vuelven a encolar los bloques posteriores
+    /// a START_DATE_IBD que no estan guardados, para generar las UTXO desde ellos como si no se hubiera usado el snapshot.
+    fn discard_snapshot(&self, headers: &mut HeadersState) -> Result<(), CustomError> {
+        let Some(base) = headers.get_snapshot_base() else {
+            return Ok(());
+        };
+        SnapshotBase::discard(&self.store_path)?;
+        headers.set_snapshot_base(None);
+
+        let starting_index = headers.first_block_index() + 1;
+        let blocks = self.blocks.lock()?;
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
+        for header in headers.iter_from(starting_index) {
+            if !blocks.has_block(header.hash()) && !pending_blocks.is_block_pending(header.hash()) {
+                pending_blocks.append_block(*header.hash())?;
+            }
+        }
+        send_log(
+            &self.logger_sender,
+            Log::Warn(
+                LogTarget::Sync,
+                format!(
+                    "The block {} of the UTXO snapshot is not in the chain at height {}, discarding the snapshot and downloading all the blocks",
+                    base.block_hash, base.height
+                ),
+            ),
+        );
         Ok(())
     }
 
+    /// Indica si el bloque no hace falta descargarlo porque esta antes del snapshot de UTXO desde el que se inicio el nodo.
+    pub fn is_before_snapshot(&self, block_hash: &Hash32) -> Result<bool, CustomError> {
+        Ok(self.headers.read()?.is_before_snapshot(block_hash))
+    }
+
     /// Devuelve los ultimos count headers del HeaderState
-    pub fn get_last_headers(&self, count: usize) -> Vec<(usize, BlockHeader)> {
-        self.headers.get_last_headers(count)
+    pub fn get_last_headers(&self, count: usize) -> Result<Vec<(usize, BlockHeader)>, CustomError> {
+        Ok(self.headers.read()?.get_last_headers(count))
+    }
+
+    /// Devuelve el header del bloque a la altura recibida, None si no existe.
+    pub fn get_header_by_height(&self, height: usize) -> Result<Option<BlockHeader>, CustomError> {
+        Ok(self.headers.read()?.get_header_by_height(height))
+    }
+
+    /// Devuelve el hash del bloque a la altura recibida, None si no existe.
+    pub fn get_block_hash_by_height(&self, height: usize) -> Result<Option<Hash32>, CustomError> {
+        Ok(self.headers.read()?.get_hash_by_height(height))
+    }
+
+    /// Devuelve el bloque a la altura recibida, None si no existe o si todavia no se descargo.
+    pub fn get_block_by_height(&self, height: u32) -> Result<Option<Block>, CustomError> {
+        let header = match self.get_header_by_height(height as usize)? {
+            Some(header) if header.block_downloaded => header,
+            _ => return Ok(None),
+        };
+        self.get_block(header.hash()).map(Some)
+    }
+
+    /// Devuelve hasta count headers a partir del header con el hash recibido junto a su height
+    pub fn get_headers_from(
+        &self,
+        block_hash: &Hash32,
+        count: usize,
+    ) -> Result<Option<Vec<(usize, BlockHeader)>>, CustomError> {
+        Ok(self.headers.read()?.get_headers_from(block_hash, count))
     }
 
     /// Devuelve los headers que se le solicitan mediante el mensaje GetHeaders del protocolo btc
-    pub fn get_headers(&self, get_headers: GetHeaders) -> Vec<BlockHeader> {
-        self.headers.get_headers(get_headers)
+    pub fn get_headers(&self, get_headers: GetHeaders) -> Result<Vec<BlockHeader>, CustomError> {
+        Ok(self.headers.read()?.get_headers(get_headers))
     }
 
     /// Devuelve los headers listos para enviar a medida que se descargan sus bloques, siguiendo el orden de la blockchain.
-    pub fn get_headers_to_send(&mut self, block_hash: &Vec<u8>) -> Vec<BlockHeader> {
-        self.headers.get_headers_to_send(block_hash)
+    pub fn get_headers_to_send(
+        &self,
+        block_hash: &Hash32,
+    ) -> Result<Vec<BlockHeader>, CustomError> {
+        Ok(self.headers.write()?.get_headers_to_send(block_hash))
     }
 
     /********************     SYNC     ********************/
 
     /// Devuelve true si el nodo esta sincronizado con la red
-    pub fn is_synced(&self) -> bool {
-        self.headers.is_synced() && self.blocks.is_synced() && self.utxo.is_synced()
+    pub fn is_synced(&self) -> Result<bool, CustomError> {
+        Ok(self.headers.read()?.is_synced()
+            && self.blocks.lock()?.is_synced()
+            && self.utxo.read()?.is_synced())
     }
 
     /// Verifica si el nodo esta sincronizado con la red
@@ -204,17 +934,27 @@ impl NodeState {
     /// Si los headers estan sincronizados, verifica si los bloques estan sincronizados
     /// Si los bloques estan sincronizados, genera el UTXO
     ///
-    pub fn verify_sync(&mut self) -> Result<(), CustomError> {
-        if self.headers.is_synced() {
-            self.blocks.verify_sync()?;
+    pub fn verify_sync(&self) -> Result<(), CustomError> {
+        if self.headers.read()?.is_synced() {
+            self.blocks.lock()?.verify_sync()?;
         }
 
-        if self.blocks.is_synced() && !self.utxo.is_synced() {
-            self.utxo
-                .generate(self.headers.get_all(), &mut self.logger_sender)?;
+        // sin headers no hay bloques desde los que generar las UTXO, como en una regtest nueva
+        if self.blocks.lock()?.is_synced()
+            && !self.utxo.read()?.is_synced()
+            && !self.headers.read()?.is_empty()
+        {
+            let headers = self.headers.read()?;
+            let blocks = self.blocks.lock()?;
+            self.utxo.write()?.generate(
+                &headers.iter_from(headers.first_block_index()).collect(),
+                &blocks,
+                &mut self.logger_sender.clone(),
+            )?;
         }
 
-        if self.is_synced() {
+        if self.is_synced()? {
+            self.restore_mempool()?;
             self.gui_sender
                 .send(GUIEvents::NodeStateReady)
                 .map_err(|_| CustomError::CannotInitGUI)?;
@@ -223,78 +963,510 @@ impl NodeState {
         Ok(())
     }
 
+    /// Envia a la interfaz grafica el progreso de la sincronizacion mientras el nodo no esta sincronizado,
+    /// como mucho una vez cada pocos milisegundos.
+    pub fn report_sync_progress(&self) -> Result<(), CustomError> {
+        if self.is_synced()? {
+            return Ok(());
+        }
+
+        let progress = self.get_sync_progress()?;
+        let Some(progress) = self
+            .sync_progress
+            .lock()?
+            .report(get_current_timestamp_millis()?, progress)
+        else {
+            return Ok(());
+        };
+        self.gui_sender.send(GUIEvents::SyncProgress(progress))?;
+        Ok(())
+    }
+
+    /// Devuelve el progreso de la sincronizacion, sin velocidad de descarga ni ETA.
+    /// La altura de la red es la mayor informada por los peers al conectarse, o si no hay peers se estima
+    /// a partir del timestamp del ultimo header.
+    fn get_sync_progress(&self) -> Result<SyncProgress, CustomError> {
+        let headers = self.headers.read()?;
+        let header_height = headers.len();
+        let headers_synced = headers.is_synced();
+        let blocks_total = headers.total_headers_to_download();
+        let last_timestamp = header_height
+            .checked_sub(1)
+            .and_then(|index| headers.get(index))
+            .map(|header| header.timestamp);
+        drop(headers);
+
+        let blocks_pending = self.pending_blocks_ref.lock()?.len();
+        let peers_height = self
+            .peers
+            .lock()?
+            .iter()
+            .map(|peer| peer.start_height.max(0) as usize)
+            .max();
+        let network_height = match (peers_height, last_timestamp) {
+            (Some(peers_height), _) => peers_height,
+            (None, Some(timestamp)) => {
+                estimate_network_height(header_height, timestamp, get_current_timestamp()?)
+            }
+            (None, None) => header_height,
+        };
+
+        Ok(SyncProgress::new(
+            header_height,
+            network_height,
+            headers_synced,
+            blocks_total.saturating_sub(blocks_pending),
+            blocks_total,
+        ))
+    }
+
+    /// Devuelve un resumen del estado del nodo: el ultimo bloque procesado, la altura de los headers,
+    /// el progreso de la sincronizacion, la cantidad de UTXO, de pending txs y de peers, y el espacio que ocupa en disco.
+    pub fn get_status(&self) -> Result<NodeStatus, CustomError> {
+        let sync_percentage = match self.is_synced()? {
+            true => 100.0,
+            false => self.get_sync_progress()?.fraction() * 100.0,
+        };
+        let (tip_height, utxo_size) = {
+            let utxo = self.utxo.read()?;
+            (utxo.get_tip_height(), utxo.tx_set.len())
+        };
+        let headers = self.headers.read()?;
+        let tip_hash = headers
+            .get_hash_by_height(tip_height as usize)
+            .unwrap_or_else(|| self.network.genesis());
+        let header_height = headers.len();
+        drop(headers);
+
+        Ok(NodeStatus {
+            tip_hash,
+            tip_height,
+            header_height,
+            sync_percentage,
+            utxo_size,
+            mempool_size: self.pending_txs.lock()?.len(),
+            peers: self.peers.lock()?.len(),
+            disk_usage: dir_size(&self.store_path)?,
+        })
+    }
+
     /********************     WALLETS     ********************/
 
-    /// Devuelve todas las wallets del nodo
-    pub fn get_wallets(&self) -> &Vec<Wallet> {
-        self.wallets.get_all()
+    /// Devuelve una copia de todas las wallets del nodo
+    pub fn get_wallets(&self) -> Result<Vec<Wallet>, CustomError> {
+        Ok(self.wallets.read()?.get_all().clone())
     }
 
     /// Agrega una wallet nueva a WalletState
     pub fn append_wallet(
-        &mut self,
+        &self,
         name: String,
         public_key: String,
         private_key: String,
     ) -> Result<(), CustomError> {
-        let new_wallet = Wallet::new(name, public_key, private_key, &self.utxo)?;
-        self.wallets.append(new_wallet)
+        let new_wallet = Wallet::new(name, public_key, private_key, &*self.utxo.read()?)?;
+        let mut wallets = self.wallets.write()?;
+        wallets.append(new_wallet)?;
+
+        // Como cliente SPV los peers solo envian lo que coincide con el filtro, que debe incluir a la nueva wallet
+        if self.is_spv_mode() {
+            let filter_load = wallets_filter(wallets.get_all())?;
+            for peer in self.peers.lock()?.iter_mut() {
+                peer.send(filter_load.clone()).ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Cambia el nombre de la wallet con la public key recibida.
+    pub fn rename_wallet(&self, public_key: &str, name: &str) -> Result<(), CustomError> {
+        self.wallets.write()?.rename(public_key, name)
     }
 
-    /// Devuelve la wallet activa de WalletState
-    pub fn get_active_wallet(&self) -> Option<&Wallet> {
-        self.wallets.get_active()
+    /// Mueve la wallet con la public key recibida a la posicion indicada de la lista de wallets.
+    pub fn move_wallet(&self, public_key: &str, index: usize) -> Result<(), CustomError> {
+        self.wallets.write()?.move_to(public_key, index)
+    }
+
+    /// Quita la wallet con la public key recibida, con sus claves y su historial.
+    /// Si se recibe un path y una passphrase, antes de quitarla guarda sus claves, su historial y las etiquetas
+    /// de sus movimientos en un backup cifrado, que se puede restaurar como el de todas las wallets.
+    /// El backup se escribe sin tener tomadas las wallets, ya que derivar su clave demora, y se quita despues.
+    /// Si era la wallet activa no queda ninguna activa. Devuelve la wallet quitada.
+    pub fn remove_wallet(
+        &self,
+        public_key: &str,
+        key_backup: Option<(&str, &str)>,
+    ) -> Result<Wallet, CustomError> {
+        let wallet = self
+            .wallets
+            .read()?
+            .get_all()
+            .iter()
+            .find(|wallet| wallet.pubkey == public_key)
+            .cloned()
+            .ok_or(CustomError::WalletNotFound)?;
+        if let Some((path, passphrase)) = key_backup {
+            let movement_labels = self
+                .address_book
+                .lock()?
+                .get_movement_labels()
+                .into_iter()
+                .filter(|(tx_hash, _)| {
+                    wallet
+                        .history
+                        .iter()
+                        .any(|movement| movement.tx_hash == *tx_hash)
+                })
+                .collect();
+            WalletsBackup {
+                wallets: vec![wallet],
+                contacts: vec![],
+                movement_labels,
+            }
+            .export(path, passphrase)?;
+        }
+
+        let mut wallets = self.wallets.write()?;
+        let was_active = wallets
+            .get_active()
+            .is_some_and(|active| active.pubkey == public_key);
+        let removed = wallets.remove(public_key)?;
+        if self.is_spv_mode() {
+            let filter_load = wallets_filter(wallets.get_all())?;
+            for peer in self.peers.lock()?.iter_mut() {
+                peer.send(filter_load.clone()).ok();
+            }
+        }
+        drop(wallets);
+
+        if was_active {
+            self.gui_sender.send(GUIEvents::WalletChanged)?;
+        }
+        Ok(removed)
+    }
+
+    /// Guarda en el path recibido un backup de todas las wallets (claves, nombres e historiales) y de la libreta de direcciones,
+    /// cifrado con la passphrase. Devuelve la cantidad de wallets guardadas.
+    pub fn export_wallets_backup(
+        &self,
+        path: &str,
+        passphrase: &str,
+    ) -> Result<usize, CustomError> {
+        let wallets = self.wallets.read()?.get_all().clone();
+        let address_book = self.address_book.lock()?;
+        let backup = WalletsBackup {
+            wallets,
+            contacts: address_book.get_contacts(),
+            movement_labels: address_book.get_movement_labels(),
+        };
+        drop(address_book);
+
+        backup.export(path, passphrase)?;
+        Ok(backup.wallets.len())
+    }
+
+    /// Restaura el backup cifrado guardado en el path recibido, agregando las wallets que no estaban y los movimientos
+    /// y etiquetas que les faltaban a las existentes. Las wallets nuevas se completan con sus UTXO, como al agregarlas.
+    /// Devuelve la cantidad de wallets agregadas.
+    pub fn restore_wallets_backup(
+        &self,
+        path: &str,
+        passphrase: &str,
+    ) -> Result<usize, CustomError> {
+        let backup = WalletsBackup::import(path, passphrase)?;
+        let utxo = self.utxo.read()?;
+        let mut restored = vec![];
+        for wallet in backup.wallets {
+            let mut restored_wallet =
+                Wallet::new(wallet.name, wallet.pubkey, wallet.privkey, &utxo)?;
+            let utxo_history = std::mem::replace(&mut restored_wallet.history, wallet.history);
+            restored_wallet.merge_history(utxo_history);
+            restored.push(restored_wallet);
+        }
+        let mut wallets = self.wallets.write()?;
+        let added = wallets.merge(restored)?;
+        if added > 0 && self.is_spv_mode() {
+            let filter_load = wallets_filter(wallets.get_all())?;
+            for peer in self.peers.lock()?.iter_mut() {
+                peer.send(filter_load.clone()).ok();
+            }
+        }
+        let has_active = wallets.has_active();
+        drop(wallets);
+        drop(utxo);
+
+        self.address_book
+            .lock()?
+            .merge(backup.contacts, backup.movement_labels)?;
+        self.gui_sender.send(GUIEvents::AddressBookChanged)?;
+        if has_active {
+            self.gui_sender.send(GUIEvents::WalletsUpdated)?;
+        }
+        Ok(added)
+    }
+
+    /// Devuelve una copia de la wallet activa de WalletState
+    pub fn get_active_wallet(&self) -> Result<Option<Wallet>, CustomError> {
+        Ok(self.wallets.read()?.get_active().cloned())
     }
 
     /// Cambia la wallet activa de WalletState
-    pub fn change_wallet(&mut self, public_key: String) -> Result<(), CustomError> {
-        self.wallets.set_active(&public_key)?;
+    pub fn change_wallet(&self, public_key: String) -> Result<(), CustomError> {
+        self.wallets.write()?.set_active(&public_key)?;
         self.gui_sender.send(GUIEvents::WalletChanged)?;
         Ok(())
     }
 
     /// Actualiza las wallets de WalletState
-    pub fn update_wallets(&mut self, block: &Block) -> Result<(), CustomError> {
-        let wallets_updated = self.wallets.update(block, &self.utxo)?;
-        if wallets_updated {
+    pub fn update_wallets(&self, block: &Block) -> Result<(), CustomError> {
+        let utxo = self.utxo.read()?;
+        let mut wallets = self.wallets.write()?;
+        let wallet_txs = wallets.update(block, &utxo)?;
+        let has_active = wallets.has_active();
+        drop(wallets);
+        drop(utxo);
+
+        if !wallet_txs.is_empty() && has_active {
             self.gui_sender
                 .send(GUIEvents::WalletsUpdated)
                 .map_err(|_| CustomError::CannotInitGUI)?;
         }
+        let notify_commands = self.notify_commands.read()?;
+        for tx_hash in &wallet_txs {
+            notify_commands.notify_wallet_tx(&self.logger_sender, tx_hash);
+        }
         Ok(())
     }
 
+    /********************     ADDRESS BOOK     ********************/
+
+    /// Devuelve las etiquetas y direcciones de la libreta de direcciones, ordenadas por etiqueta.
+    pub fn get_contacts(&self) -> Result<Vec<(String, String)>, CustomError> {
+        Ok(self.address_book.lock()?.get_contacts())
+    }
+
+    /// Agrega una direccion a la libreta de direcciones con su etiqueta, o le cambia la etiqueta si ya estaba.
+    pub fn set_contact(&self, address: String, label: String) -> Result<(), CustomError> {
+        self.address_book.lock()?.set_contact(address, label)?;
+        self.gui_sender.send(GUIEvents::AddressBookChanged)?;
+        Ok(())
+    }
+
+    /// Quita una direccion de la libreta de direcciones. Devuelve false si no estaba.
+    pub fn remove_contact(&self, address: &str) -> Result<bool, CustomError> {
+        let removed = self.address_book.lock()?.remove_contact(address)?;
+        if removed {
+            self.gui_sender.send(GUIEvents::AddressBookChanged)?;
+        }
+        Ok(removed)
+    }
+
+    /// Reemplaza los destinatarios ingresados con la etiqueta de una direccion de la libreta por su direccion.
+    pub fn resolve_recipients(
+        &self,
+        recipients: Vec<(String, u64)>,
+    ) -> Result<Vec<(String, u64)>, CustomError> {
+        let address_book = self.address_book.lock()?;
+        Ok(recipients
+            .into_iter()
+            .map(
+                |(recipient, value)| match address_book.resolve(&recipient) {
+                    Some(address) => (address, value),
+                    None => (recipient, value),
+                },
+            )
+            .collect())
+    }
+
+    /// Establece la etiqueta del movimiento de la transaccion recibida, o la quita si la etiqueta esta vacia.
+    pub fn set_movement_label(&self, tx_hash: Hash32, label: String) -> Result<(), CustomError> {
+        self.address_book
+            .lock()?
+            .set_movement_label(tx_hash, label)?;
+        self.gui_sender.send(GUIEvents::AddressBookChanged)?;
+        Ok(())
+    }
+
+    /// Devuelve la etiqueta del movimiento de la transaccion recibida, si tiene.
+    pub fn get_movement_label(&self, tx_hash: &Hash32) -> Result<Option<String>, CustomError> {
+        Ok(self.address_book.lock()?.get_movement_label(tx_hash))
+    }
+
+    /// Etiqueta el movimiento de una transaccion enviada por la wallet activa con las etiquetas de sus destinatarios
+    /// que estan en la libreta de direcciones, salvo que el usuario ya le haya puesto una etiqueta.
+    pub fn label_sent_transaction(&self, transaction: &Transaction) -> Result<(), CustomError> {
+        let mut address_book = self.address_book.lock()?;
+        let tx_hash = transaction.hash();
+        if address_book.get_movement_label(&tx_hash).is_some() {
+            return Ok(());
+        }
+        let mut labels: Vec<String> = transaction
+            .outputs
+            .iter()
+            .filter_map(|output| address_book.get_contact_label(&output.address(self.network)?))
+            .collect();
+        labels.dedup();
+        if labels.is_empty() {
+            return Ok(());
+        }
+        address_book.set_movement_label(tx_hash, labels.join(", "))
+    }
+
     /********************     UTXO     ********************/
 
     /// Devuelve el balance de la wallet activa
     pub fn get_active_wallet_balance(&self) -> Result<u64, CustomError> {
-        let Some(active_wallet) = self.wallets.get_active() else { return Err(CustomError::WalletNotFound) };
-        self.utxo.wallet_balance(active_wallet)
+        let utxo = self.utxo.read()?;
+        let wallets = self.wallets.read()?;
+        let Some(active_wallet) = wallets.get_active() else { return Err(CustomError::WalletNotFound) };
+        utxo.wallet_balance(active_wallet)
     }
 
     /// Devuelve el UTXO de la wallet activa
     pub fn get_active_wallet_utxo(&self) -> Result<Vec<(OutPoint, UTXOValue)>, CustomError> {
-        let Some(active_wallet) = self.wallets.get_active() else { return Err(CustomError::WalletNotFound) };
-        self.utxo.generate_wallet_utxo(active_wallet)
+        let utxo = self.utxo.read()?;
+        let wallets = self.wallets.read()?;
+        let Some(active_wallet) = wallets.get_active() else { return Err(CustomError::WalletNotFound) };
+        utxo.generate_wallet_utxo(active_wallet)
+    }
+
+    /// Devuelve el UTXO de una direccion
+    pub fn get_address_utxo(
+        &self,
+        address: String,
+    ) -> Result<Vec<(OutPoint, UTXOValue)>, CustomError> {
+        self.utxo
+            .read()?
+            .generate_pubkey_hash_utxo(&get_pubkey_hash(address)?)
     }
 
     /********************     PENDING TXs     ********************/
 
-    /// Actualiza las pending txs de PendingTxs
-    pub fn update_pending_tx(&mut self, block: &Block) -> Result<(), CustomError> {
-        self.pending_txs.update_pending_tx(block)
+    /// Actualiza las pending txs de PendingTxs con las transacciones del bloque.
+    /// Las pending txs de las wallets que entraron en conflicto con el bloque se marcan como conflicted en su historial.
+    /// Se debe llamar antes de actualizar el UTXO con el bloque, para poder calcular sus movimientos.
+    pub fn update_pending_tx(&self, block: &Block) -> Result<(), CustomError> {
+        let conflicted = self.pending_txs.lock()?.update_pending_tx(block)?;
+        if conflicted.is_empty() {
+            return Ok(());
+        }
+
+        let utxo = self.utxo.read()?;
+        let wallets_updated = self.wallets.write()?.mark_conflicted(&conflicted, &utxo)?;
+        drop(utxo);
+
+        send_log(
+            &self.logger_sender,
+            Log::Info(
+                LogTarget::Wallet,
+                format!(
+                    "Dropped {} pending transactions that conflict with block {}",
+                    conflicted.len(),
+                    block.header.hash()
+                ),
+            ),
+        );
+        if wallets_updated {
+            self.gui_sender
+                .send(GUIEvents::WalletsUpdated)
+                .map_err(|_| CustomError::CannotInitGUI)?;
+        }
+        Ok(())
+    }
+
+    /// Devuelve la pagina del historial de la wallet activa que pide la consulta, incluyendo sus pending txs como los movimientos mas recientes.
+    pub fn query_active_wallet_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<HistoryPage, CustomError> {
+        let headers = self.headers.read()?;
+        let utxo = self.utxo.read()?;
+        let wallets = self.wallets.read()?;
+        let Some(active_wallet) = wallets.get_active() else {
+            return Err(CustomError::WalletNotFound);
+        };
+        let pending = self.pending_txs.lock()?.from_wallet(active_wallet, &utxo)?;
+
+        wallets.query_active_history(query, pending, |block_hash| {
+            headers.get_block_timestamp(block_hash)
+        })
     }
 
     /// Devuelve las pending txs de la wallet activa
     pub fn get_active_wallet_pending_txs(&self) -> Result<Vec<Movement>, CustomError> {
-        let Some(active_wallet) = self.wallets.get_active() else { return Err(CustomError::WalletNotFound) };
+        let utxo = self.utxo.read()?;
+        let wallets = self.wallets.read()?;
+        let Some(active_wallet) = wallets.get_active() else { return Err(CustomError::WalletNotFound) };
 
-        self.pending_txs.from_wallet(active_wallet, &self.utxo)
+        self.pending_txs.lock()?.from_wallet(active_wallet, &utxo)
     }
 
-    /// Agrega una pending tx nueva a PendingTxs
-    pub fn append_pending_tx(&mut self, transaction: Transaction) -> Result<bool, CustomError> {
-        let updated = self.pending_txs.append_pending_tx(transaction);
+    /// Agrega una pending tx nueva a PendingTxs, calculando su fee con el UTXO.
+    /// Si gasta las mismas salidas que otras pending txs, PendingTxs decide con las reglas de RBF cual se queda.
+    /// Si en el conflicto participa alguna de las wallets se avisa a la interfaz con PendingTxConflict.
+    /// Las transacciones cuyos lock time no se cumplen en el siguiente bloque se rechazan, ya que no se pueden minar todavia.
+    /// Devuelve false si ya se tenia, si se rechazo como doble gasto o si se descarto por superar el tamaño maximo de la mempool.
+    pub fn append_pending_tx(&self, transaction: Transaction) -> Result<bool, CustomError> {
+        let headers = self.headers.read()?;
+        let utxo = self.utxo.read()?;
+        let wallets = self.wallets.read()?;
+        let mut pending_txs = self.pending_txs.lock()?;
+        let tx_hash = transaction.hash();
+
+        let next_block = headers.get_next_lock_point();
+        let spent_outputs = pending_txs.get_spent_outputs(&transaction, &utxo);
+        let spent_coins: Vec<Option<(&TransactionOutput, LockPoint)>> = spent_outputs
+            .iter()
+            .map(|spent| {
+                spent.as_ref().map(|(output, height)| {
+                    let coin = height.map_or(next_block, |height| headers.get_lock_point(height));
+                    (output, coin)
+                })
+            })
+            .collect();
+        if let Err(error) = transaction.check_locks(next_block, &spent_coins, true) {
+            send_log(
+                &self.logger_sender,
+                Log::Debug(
+                    LogTarget::Wallet,
+                    format!("Rejected pending transaction {tx_hash}: {error}"),
+                ),
+            );
+            return Ok(false);
+        }
+        drop(headers);
+
+        let conflicts: Vec<Transaction> = pending_txs
+            .get_conflicts(&transaction)
+            .iter()
+            .filter_map(|conflict| pending_txs.get_pending_tx(conflict))
+            .collect();
+        let notify_commands = self.notify_commands.read()?.clone();
+        let wallet_tx = match !conflicts.is_empty() || notify_commands.wallet_notify.is_some() {
+            true => wallets.involves(&transaction, &utxo)?,
+            false => false,
+        };
+        let mut wallet_conflict = false;
+        if !conflicts.is_empty() {
+            wallet_conflict = wallet_tx;
+            for conflict in &conflicts {
+                wallet_conflict |= wallets.involves(conflict, &utxo)?;
+            }
+        }
+        let updated = pending_txs.append_pending_tx(transaction, &utxo);
+        drop(pending_txs);
+        drop(wallets);
+        drop(utxo);
+
+        if !conflicts.is_empty() {
+            self.log_pending_tx_conflict(tx_hash, &conflicts, updated, wallet_conflict);
+            if wallet_conflict {
+                self.gui_sender
+                    .send(GUIEvents::PendingTxConflict)
+                    .map_err(|_| CustomError::CannotInitGUI)?;
+            }
+        }
 
         if updated {
             self.gui_sender
@@ -302,23 +1474,350 @@ impl NodeState {
                 .map_err(|_| CustomError::CannotInitGUI)?;
             send_log(
                 &self.logger_sender,
-                Log::Message("New pending transaction received".to_string()),
+                Log::Info(
+                    LogTarget::Wallet,
+                    "New pending transaction received".to_string(),
+                ),
             );
+            if wallet_tx {
+                notify_commands.notify_wallet_tx(&self.logger_sender, &tx_hash);
+            }
         }
 
         Ok(updated)
     }
 
+    /// Registra el conflicto entre una pending tx nueva y las que gastaban sus mismas salidas.
+    /// Si participa alguna de las wallets se registra como advertencia, ya que cambia su balance pendiente.
+    fn log_pending_tx_conflict(
+        &self,
+        tx_hash: Hash32,
+        conflicts: &[Transaction],
+        replaced: bool,
+        wallet_conflict: bool,
+    ) {
+        let conflict_hashes = conflicts
+            .iter()
+            .map(|conflict| conflict.hash().to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let message = match replaced {
+            true => format!("Pending transaction {tx_hash} replaced {conflict_hashes}"),
+            false => format!(
+                "Rejected pending transaction {tx_hash}, it double spends {conflict_hashes}"
+            ),
+        };
+        let log = match wallet_conflict {
+            true => Log::Warn(LogTarget::Wallet, message),
+            false => Log::Debug(LogTarget::Wallet, message),
+        };
+        send_log(&self.logger_sender, log);
+    }
+
+    /// Agrega a PendingTxs una transaccion creada por alguna de las wallets, marcandola como local
+    /// para que se reenvie periodicamente a los peers hasta que se confirme.
+    pub fn append_local_tx(&self, transaction: Transaction) -> Result<bool, CustomError> {
+        let tx_hash = transaction.hash();
+        let updated = self.append_pending_tx(transaction)?;
+        self.pending_txs.lock()?.mark_local(&tx_hash);
+        Ok(updated)
+    }
+
+    /// Devuelve las pending txs locales que todavia no se confirmaron, los padres antes que los hijos.
+    pub fn get_local_pending_txs(&self) -> Result<Vec<Transaction>, CustomError> {
+        Ok(self.pending_txs.lock()?.get_local_txs())
+    }
+
+    /// Establece el tamaño maximo de la mempool en megabytes, descartando las transacciones de menor fee rate si se supera.
+    pub fn set_max_mempool_size(&self, megabytes: usize) -> Result<(), CustomError> {
+        self.pending_txs.lock()?.set_max_size(megabytes * 1_000_000);
+        Ok(())
+    }
+
+    /// Establece el fee rate minimo en satoshis por kilobyte virtual de las pending txs que se aceptan y retransmiten.
+    pub fn set_min_relay_fee(&self, min_relay_fee: u64) -> Result<(), CustomError> {
+        self.pending_txs.lock()?.set_min_relay_fee(min_relay_fee);
+        Ok(())
+    }
+
+    /// Establece el bloque cuyos ancestros se asumen validos, sin verificar sus scripts.
+    pub fn set_assume_valid(&self, assume_valid: Option<Hash32>) -> Result<(), CustomError> {
+        self.headers.write()?.set_assume_valid(assume_valid);
+        Ok(())
+    }
+
+    /// Establece si los bloques que se guardan se comprimen.
+    pub fn set_compress_blocks(&self, compress: bool) -> Result<(), CustomError> {
+        self.blocks.lock()?.set_compression(compress);
+        Ok(())
+    }
+
+    /// Establece los comandos externos que se ejecutan con cada bloque nuevo y transaccion de las wallets.
+    pub fn set_notify_commands(&self, notify_commands: NotifyCommands) -> Result<(), CustomError> {
+        *self.notify_commands.write()? = notify_commands;
+        Ok(())
+    }
+
+    /// Establece si el nodo funciona como cliente SPV, en cuyo caso no se guardan los filtros de los bloques.
+    pub fn set_spv_mode(&self, spv: bool) -> Result<(), CustomError> {
+        self.blocks.lock()?.set_spv_mode(spv);
+        self.spv.store(spv, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Indica si el nodo funciona como cliente SPV.
+    pub fn is_spv_mode(&self) -> bool {
+        self.spv.load(Ordering::Relaxed)
+    }
+
+    /// Establece si el nodo funciona en modo solo bloques.
+    pub fn set_blocks_only(&self, blocks_only: bool) -> Result<(), CustomError> {
+        self.blocks_only.store(blocks_only, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Indica si el nodo funciona en modo solo bloques.
+    pub fn is_blocks_only(&self) -> bool {
+        self.blocks_only.load(Ordering::Relaxed)
+    }
+
+    /// Establece los servicios que anuncia el nodo.
+    pub fn set_local_services(&self, services: u64) -> Result<(), CustomError> {
+        self.local_services.store(services, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Devuelve los servicios que anuncia el nodo.
+    pub fn get_local_services(&self) -> u64 {
+        self.local_services.load(Ordering::Relaxed)
+    }
+
+    /// Establece la lista de redes cuyos peers no se banean ni se desalojan.
+    pub fn set_access_list(&self, access_list: AccessList) -> Result<(), CustomError> {
+        *self.access_list.write()? = access_list;
+        Ok(())
+    }
+
+    /// Indica si la direccion recibida pertenece a una red de la lista de acceso.
+    pub fn is_whitelisted(&self, address: &Ipv6Addr) -> Result<bool, CustomError> {
+        Ok(self.access_list.read()?.is_whitelisted(address))
+    }
+
+    /// Devuelve la red a la que se conecta el nodo.
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    /// Arma un template del bloque siguiente al ultimo header para que lo mine un minero externo, con las pending txs
+    /// seleccionadas por fee rate que entran en el peso y el costo en sigops maximos, reservando lugar para la coinbase.
+    /// Devuelve CustomError::Validation si el nodo funciona como cliente SPV, ya que no tiene las UTXO con las que
+    /// se validan las transacciones, o si no esta sincronizado, salvo en la regtest donde un nodo solo nunca lo esta.
+    pub fn get_block_template(&self) -> Result<BlockTemplate, CustomError> {
+        self.sync_regtest_chain()?;
+        if self.is_spv_mode() || (self.network != Network::Regtest && !self.is_synced()?) {
+            return Err(CustomError::Validation(String::from(
+                "The node is not synced, cannot create a block template",
+            )));
+        }
+        let headers = self.headers.read()?;
+        let utxo = self.utxo.read()?;
+        let pending_txs = self.pending_txs.lock()?;
+
+        let previous_block_hash = headers
+            .get_last_header_hash()
+            .unwrap_or(self.network.genesis());
+        let next_block = headers.get_next_lock_point();
+        let min_time = next_block.median_time_past + 1;
+        let cur_time = (get_current_timestamp()? as u32).max(min_time);
+        let transactions = pending_txs.select_for_block(
+            &utxo,
+            MAX_BLOCK_WEIGHT - COINBASE_RESERVED_WEIGHT,
+            MAX_BLOCK_SIGOPS_COST - COINBASE_RESERVED_SIGOPS_COST,
+        );
+        let fees: u64 = transactions.iter().map(|template_tx| template_tx.fee).sum();
+
+        Ok(BlockTemplate {
+            version: BLOCK_TEMPLATE_VERSION,
+            previous_block_hash,
+            height: next_block.height,
+            bits: headers.get_next_bits(cur_time),
+            cur_time,
+            min_time,
+            transactions,
+            coinbase_value: block_subsidy(next_block.height, self.network) + fees,
+        })
+    }
+
+    /// Mina en la regtest el bloque siguiente a partir de su template, con la coinbase pagando al script recibido,
+    /// y lo agrega a la cadena como si lo hubiera enviado un peer. Devuelve el bloque para anunciarlo a los peers.
+    /// Devuelve CustomError::Validation si la red no es la regtest, la unica con una dificultad que se puede minar con la CPU.
+    pub fn generate_block(&self, script_pubkey: Vec<u8>) -> Result<Block, CustomError> {
+        if self.network != Network::Regtest {
+            return Err(CustomError::Validation(String::from(
+                "Blocks can only be generated on regtest",
+            )));
+        }
+        let template = self.get_block_template()?;
+        let mut block = template.block(template.coinbase(script_pubkey));
+        if !solve_header(&mut block.header) {
+            return Err(CustomError::Validation(String::from(
+                "Cannot find a nonce for the block",
+            )));
+        }
+        self.submit_block(&block)?;
+        Ok(block)
+    }
+
+    /// Agrega a la cadena un bloque minado localmente, recibido con submitblock o generado en la regtest.
+    /// Verifica que extienda al ultimo header con la dificultad y el timestamp esperados, su merkle root, sus limites,
+    /// la altura de su coinbase y, si el nodo esta sincronizado, sus transacciones contra las UTXO.
+    /// Devuelve CustomError::Validation con el motivo del rechazo, con los nombres que usa Bitcoin Core.
+    pub fn submit_block(&self, block: &Block) -> Result<(), CustomError> {
+        if self.is_spv_mode() {
+            return Err(rejected("An SPV client cannot accept blocks"));
+        }
+        self.sync_regtest_chain()?;
+        let block_hash = *block.header.hash();
+        let headers = self.headers.read()?;
+        if headers.contains(&block_hash) {
+            return Err(rejected("duplicate"));
+        }
+        let tip = headers
+            .get_last_header_hash()
+            .unwrap_or(self.network.genesis());
+        if block.header.prev_block_hash != tip {
+            let reason = match headers.contains(&block.header.prev_block_hash) {
+                true => "inconclusive",
+                false => "prev-blk-not-found",
+            };
+            return Err(rejected(reason));
+        }
+        let next_block = headers.get_next_lock_point();
+        if block.header.bits != headers.get_next_bits(block.header.timestamp) {
+            return Err(rejected("bad-diffbits"));
+        }
+        if block.header.timestamp <= next_block.median_time_past {
+            return Err(rejected("time-too-old"));
+        }
+        drop(headers);
+
+        if block.create_merkle_root().is_err() {
+            return Err(rejected("bad-txnmrklroot"));
+        }
+        if block.check_limits().is_err() {
+            return Err(rejected("bad-blk-weight"));
+        }
+        if block.coinbase_height() != Some(next_block.height) {
+            return Err(rejected("bad-cb-height"));
+        }
+        // Las UTXO solo estan completas una vez sincronizado el nodo, o en una regtest que todavia no tiene bloques
+        if self.is_synced()? || self.network == Network::Regtest {
+            match self.validate_block(block) {
+                Err(CustomError::Validation(reason)) => return Err(rejected(&reason)),
+                Err(error) => return Err(rejected(error.description())),
+                Ok(()) => {}
+            }
+        }
+
+        self.append_headers(&Headers {
+            headers: vec![block.header.clone()],
+        })?;
+        self.append_block(block_hash, block)
+    }
+
+    /// En la regtest el nodo mina su propia cadena sin peers de los que sincronizarse, por lo que al minar se considera
+    /// sincronizado con los headers guardados, generando las UTXO a partir de sus bloques si todavia no se generaron.
+    fn sync_regtest_chain(&self) -> Result<(), CustomError> {
+        if self.network != Network::Regtest || self.is_synced()? {
+            return Ok(());
+        }
+        self.headers.write()?.verify_headers_sync(0)?;
+        self.verify_sync()
+    }
+
+    /// Devuelve la cantidad de pending txs y la suma de sus tamaños virtuales.
+    pub fn get_mempool_info(&self) -> Result<(usize, usize), CustomError> {
+        let pending_txs = self.pending_txs.lock()?;
+        Ok((pending_txs.len(), pending_txs.size()))
+    }
+
+    /// Devuelve las pending txs con un fee rate de al menos min_fee_rate satoshis por kilobyte virtual,
+    /// de mayor a menor fee rate junto a su fee rate.
+    pub fn get_pending_txs_by_fee_rate(
+        &self,
+        min_fee_rate: u64,
+    ) -> Result<Vec<(Transaction, u64)>, CustomError> {
+        Ok(self
+            .pending_txs
+            .lock()?
+            .get_by_fee_rate(min_fee_rate)
+            .into_iter()
+            .map(|(tx, fee_rate)| (tx.clone(), fee_rate))
+            .collect())
+    }
+
+    /// Agrega a PendingTxs las transacciones guardadas al cerrar el nodo que siguen siendo validas con el UTXO actual.
+    /// Solo tiene efecto la primera vez, las siguientes no quedan transacciones guardadas por validar.
+    fn restore_mempool(&self) -> Result<(), CustomError> {
+        let utxo = self.utxo.read()?;
+        let restored = self.pending_txs.lock()?.restore_saved(&utxo);
+        drop(utxo);
+
+        if restored > 0 {
+            self.gui_sender
+                .send(GUIEvents::NewPendingTx)
+                .map_err(|_| CustomError::CannotInitGUI)?;
+            send_log(
+                &self.logger_sender,
+                Log::Info(
+                    LogTarget::Wallet,
+                    format!("Restored {restored} pending transactions from the mempool file"),
+                ),
+            );
+        }
+        Ok(())
+    }
+
     /// Devuelve una pending tx de PendingTxs en base a su hash
-    pub fn get_pending_tx(&self, tx_hash: &Vec<u8>) -> Option<Transaction> {
-        self.pending_txs.get_pending_tx(tx_hash)
+    pub fn get_pending_tx(&self, tx_hash: &Hash32) -> Result<Option<Transaction>, CustomError> {
+        Ok(self.pending_txs.lock()?.get_pending_tx(tx_hash))
+    }
+
+    /// Devuelve una pending tx de PendingTxs en base a su wtxid
+    pub fn get_pending_tx_by_wtxid(
+        &self,
+        wtxid: &Hash32,
+    ) -> Result<Option<Transaction>, CustomError> {
+        Ok(self.pending_txs.lock()?.get_pending_tx_by_wtxid(wtxid))
+    }
+
+    /// Devuelve los inventarios de las pending txs que se le anuncian a un peer que pidio la mempool,
+    /// respetando su feefilter y su bloom filter. Si se negocio wtxidrelay se anuncian por wtxid.
+    pub fn get_mempool_inventories(
+        &self,
+        address: SocketAddrV6,
+    ) -> Result<Vec<Inventory>, CustomError> {
+        let pending_txs = self.pending_txs.lock()?;
+        let peers = self.peers.lock()?;
+        let Some(peer) = peers.iter().find(|p| p.address == address) else {
+            return Ok(vec![]);
+        };
+
+        Ok(pending_txs
+            .get_mempool(peer.fee_filter, peer.bloom_filter.as_ref())
+            .into_iter()
+            .map(|tx| match peer.wtxid_relay {
+                true => Inventory::new(InventoryType::Wtx, tx.wtxid()),
+                false => Inventory::new(InventoryType::Tx, tx.hash()),
+            })
+            .collect())
     }
 
     /********************     PENDING BLOCKS     ********************/
 
     /// Agrega un pending block nuevo a PendingBlocks
-    pub fn append_pending_block(&mut self, header_hash: Vec<u8>) -> Result<(), CustomError> {
-        let mut pending_blocks = self.blocks.pending_blocks_ref.lock()?;
+    pub fn append_pending_block(&self, header_hash: Hash32) -> Result<(), CustomError> {
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
         pending_blocks.append_block(header_hash)?;
         drop(pending_blocks);
 
@@ -326,20 +1825,39 @@ impl NodeState {
     }
 
     // /// Devuelve los pending blocks de PendingBlocks
-    pub fn get_stale_requests(&self) -> Result<Vec<Vec<u8>>, CustomError> {
-        let mut pending_blocks = self.blocks.pending_blocks_ref.lock()?;
+    pub fn get_stale_requests(&self) -> Result<Vec<Hash32>, CustomError> {
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
         pending_blocks.get_stale_requests()
     }
 
+    /// Registra los bloques pendientes que se le pidieron a un peer, para detectar si deja de enviarlos.
+    pub fn request_pending_blocks(
+        &self,
+        address: SocketAddrV6,
+        block_hashes: Vec<Hash32>,
+    ) -> Result<(), CustomError> {
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
+        for block_hash in block_hashes {
+            pending_blocks.request_block(block_hash, address)?;
+        }
+        Ok(())
+    }
+
+    /// Devuelve, agrupados por peer, los bloques pedidos que el peer no envio a tiempo.
+    pub fn get_stalled_requests(&self) -> Result<HashMap<SocketAddrV6, Vec<Hash32>>, CustomError> {
+        let mut pending_blocks = self.pending_blocks_ref.lock()?;
+        pending_blocks.get_stalled_requests()
+    }
+
     // /// Devuelve true si el bloque esta en PendingBlocks
-    pub fn is_block_pending(&self, block_hash: &Vec<u8>) -> Result<bool, CustomError> {
-        let pending_blocks = self.blocks.pending_blocks_ref.lock()?;
+    pub fn is_block_pending(&self, block_hash: &Hash32) -> Result<bool, CustomError> {
+        let pending_blocks = self.pending_blocks_ref.lock()?;
         Ok(pending_blocks.is_block_pending(block_hash))
     }
 
     // /// Devuelve true si PendingBlocks esta vacio
     pub fn is_pending_blocks_empty(&self) -> Result<bool, CustomError> {
-        let pending_blocks = self.blocks.pending_blocks_ref.lock()?;
+        let pending_blocks = self.pending_blocks_ref.lock()?;
         Ok(pending_blocks.is_empty())
     }
 
@@ -349,26 +1867,292 @@ impl NodeState {
     /// con los outputs y el fee recibidos por parametro
     /// Devuelve la transaccion creada
     /// Si no hay una wallet activa, devuelve un error
+    /// Si algun output es invalido o dust, devuelve un error
     /// Si no hay suficientes fondos, devuelve un error
+    /// Si el fee no alcanza el fee rate minimo de retransmision, devuelve un error
+    /// Si tiene lock time y todavia no se alcanzo en el siguiente bloque, devuelve un error
     pub fn make_transaction(
-        &mut self,
-        mut outputs: HashMap<String, u64>,
+        &self,
+        outputs: HashMap<String, u64>,
         fee: u64,
+        lock_time: Option<u32>,
     ) -> Result<Transaction, CustomError> {
-        let Some(active_wallet) = self.get_active_wallet() else { return Err(CustomError::WalletNotFound) };
+        Ok(self.build_transaction(outputs, fee, lock_time)?.0)
+    }
 
+    /// Arma la transaccion como make_transaction, sin enviarla, y devuelve su resumen para que el usuario la confirme.
+    pub fn summarize_transaction(
+        &self,
+        outputs: HashMap<String, u64>,
+        fee: u64,
+        lock_time: Option<u32>,
+    ) -> Result<TransferSummary, CustomError> {
+        Ok(self.build_transaction(outputs, fee, lock_time)?.1)
+    }
+
+    /// Arma y firma la transaccion de make_transaction junto a su resumen.
+    /// Si el cambio es dust no se le agrega un output, ya que no se retransmitiria, y se suma al fee.
+    /// El fee rate se calcula con el tamaño de la transaccion completa, que crece con cada output.
+    /// Las transacciones con un lock time que no se cumple en el siguiente bloque se rechazan,
+    /// ya que ni el nodo ni los peers las aceptan en la mempool hasta que se puedan minar.
+    fn build_transaction(
+        &self,
+        outputs: HashMap<String, u64>,
+        fee: u64,
+        lock_time: Option<u32>,
+    ) -> Result<(Transaction, TransferSummary), CustomError> {
+        let Some(active_wallet) = self.get_active_wallet()? else { return Err(CustomError::WalletNotFound) };
+
+        let recipients: Vec<(String, u64)> = outputs.into_iter().collect();
+        let mut outputs = merge_recipients(&recipients)?;
         let total_value = self.calculate_total_value(fee, &outputs)?;
         let mut active_wallet_utxo = self.get_active_wallet_utxo()?;
 
         active_wallet_utxo.sort_by(|a, b| b.1.tx_out.value.cmp(&a.1.tx_out.value));
         let (inputs, total_input_value) = calculate_inputs(&active_wallet_utxo, total_value);
 
-        let change = total_input_value - total_value;
+        let mut recipients: Vec<(String, u64)> = outputs.clone().into_iter().collect();
+        recipients.sort();
+        let change = TransactionOutput {
+            value: total_input_value - total_value,
+            script_pubkey: active_wallet.get_script_pubkey()?,
+        };
+        let change = match change.is_dust() {
+            true => 0,
+            false => change.value,
+        };
         if change > 0 {
-            outputs.insert(active_wallet.pubkey.clone(), change);
+            *outputs.entry(active_wallet.pubkey.clone()).or_insert(0) += change;
+        }
+        let fee = total_input_value - total_value + fee - change;
+
+        let transaction = Transaction::create(&active_wallet, inputs, outputs, lock_time)?;
+        let next_block = self.headers.read()?.get_next_lock_point();
+        if transaction.check_locks(next_block, &[], false).is_err() {
+            return Err(CustomError::Validation(format!(
+                "The lock time has not been reached yet, the next block is {}",
+                next_block.height
+            )));
+        }
+        let min_relay_fee = self.pending_txs.lock()?.get_min_relay_fee();
+        if fee * 1000 / (transaction.vsize() as u64) < min_relay_fee {
+            return Err(CustomError::Validation(format!(
+                "The fee must be at least {} satoshis, the minimum relay fee is {} satoshis per virtual kilobyte",
+                (min_relay_fee * transaction.vsize() as u64).div_ceil(1000),
+                min_relay_fee
+            )));
+        }
+        let summary = TransferSummary {
+            recipients,
+            change,
+            fee,
+            vsize: transaction.vsize(),
+            lock_time,
+        };
+        Ok((transaction, summary))
+    }
+
+    /// Arma la transaccion que junta en una sola salida de la wallet activa sus UTXO de hasta max_value satoshis
+    /// (todos si es None), pagando fee_rate satoshis por byte virtual, y devuelve su resumen sin enviarla.
+    /// Conviene hacerlo cuando los fees estan bajos, ya que cada UTXO agrega un input a las transacciones que lo gastan.
+    pub fn preview_consolidation(
+        &self,
+        max_value: Option<u64>,
+        fee_rate: u64,
+    ) -> Result<ConsolidationPreview, CustomError> {
+        Ok(self.build_consolidation(max_value, fee_rate)?.1)
+    }
+
+    /// Arma y firma la transaccion de preview_consolidation.
+    /// Si hay menos de dos UTXO para juntar, si el fee no alcanza el fee rate minimo de retransmision
+    /// o si la salida que queda es dust, devuelve un error.
+    pub fn make_consolidation(
+        &self,
+        max_value: Option<u64>,
+        fee_rate: u64,
+    ) -> Result<Transaction, CustomError> {
+        Ok(self.build_consolidation(max_value, fee_rate)?.0)
+    }
+
+    /// Arma y firma la transaccion de make_consolidation junto a su resumen.
+    /// Se saltean los UTXO que ya gasta una pending tx, ya que la consolidacion seria un doble gasto.
+    /// Como el tamaño depende de las firmas, la transaccion primero se arma sin fee para medirla.
+    fn build_consolidation(
+        &self,
+        max_value: Option<u64>,
+        fee_rate: u64,
+    ) -> Result<(Transaction, ConsolidationPreview), CustomError> {
+        let Some(active_wallet) = self.get_active_wallet()? else { return Err(CustomError::WalletNotFound) };
+        let active_wallet_utxo = self.get_active_wallet_utxo()?;
+        let pending_txs = self.pending_txs.lock()?;
+
+        let mut inputs: Vec<(OutPoint, u64)> = active_wallet_utxo
+            .into_iter()
+            .filter(|(out_point, utxo_value)| {
+                max_value.is_none_or(|max_value| utxo_value.tx_out.value <= max_value)
+                    && !pending_txs.is_spent(out_point)
+            })
+            .map(|(out_point, utxo_value)| (out_point, utxo_value.tx_out.value))
+            .collect();
+        if inputs.len() < 2 {
+            return Err(CustomError::Validation(String::from(
+                "There must be at least 2 unspent outputs to consolidate",
+            )));
+        }
+        inputs.sort_by_key(|(_, value)| *value);
+        let out_points: Vec<OutPoint> = inputs
+            .iter()
+            .map(|(out_point, _)| out_point.clone())
+            .collect();
+        let input_value: u64 = inputs.iter().map(|(_, value)| value).sum();
+
+        let unfunded = Transaction::create(
+            &active_wallet,
+            out_points.clone(),
+            HashMap::from([(active_wallet.pubkey.clone(), input_value)]),
+            None,
+        )?;
+        let fee = fee_rate * unfunded.vsize() as u64;
+        let min_relay_fee = pending_txs.get_min_relay_fee();
+        if fee * 1000 / (unfunded.vsize() as u64) < min_relay_fee {
+            return Err(CustomError::Validation(format!(
+                "The fee rate must be at least {} satoshis per virtual byte",
+                min_relay_fee.div_ceil(1000)
+            )));
+        }
+        let output = TransactionOutput {
+            value: input_value.saturating_sub(fee),
+            script_pubkey: active_wallet.get_script_pubkey()?,
+        };
+        if output.is_dust() {
+            return Err(CustomError::Validation(String::from(
+                "The unspent outputs to consolidate do not cover the fee",
+            )));
+        }
+
+        let transaction = Transaction::create(
+            &active_wallet,
+            out_points,
+            HashMap::from([(active_wallet.pubkey.clone(), output.value)]),
+            None,
+        )?;
+        let preview = ConsolidationPreview {
+            inputs,
+            address: active_wallet.pubkey,
+            value: output.value,
+            fee,
+            vsize: transaction.vsize(),
+        };
+        Ok((transaction, preview))
+    }
+
+    /// Arma una transaccion hija que gasta las salidas de la wallet activa de la pending tx recibida, pagando un fee
+    /// que lleve a las dos transacciones juntas a fee_rate satoshis por byte virtual (child pays for parent),
+    /// y devuelve su resumen sin enviarla. Los mineros eligen las transacciones por el fee rate de sus ancestros,
+    /// por lo que la hija hace que la pending tx se confirme antes.
+    pub fn preview_acceleration(
+        &self,
+        tx_hash: Hash32,
+        fee_rate: u64,
+    ) -> Result<AccelerationPreview, CustomError> {
+        Ok(self.build_acceleration(tx_hash, fee_rate)?.1)
+    }
+
+    /// Arma y firma la transaccion de preview_acceleration.
+    /// Si la transaccion no esta pendiente, si no tiene salidas sin gastar de la wallet activa, si ya paga fee_rate,
+    /// si fee_rate no alcanza el fee rate minimo de retransmision o si la salida que queda es dust, devuelve un error.
+    pub fn make_acceleration(
+        &self,
+        tx_hash: Hash32,
+        fee_rate: u64,
+    ) -> Result<Transaction, CustomError> {
+        Ok(self.build_acceleration(tx_hash, fee_rate)?.0)
+    }
+
+    /// Arma y firma la transaccion de make_acceleration junto a su resumen.
+    /// Como el tamaño depende de las firmas, la transaccion primero se arma sin fee para medirla.
+    fn build_acceleration(
+        &self,
+        tx_hash: Hash32,
+        fee_rate: u64,
+    ) -> Result<(Transaction, AccelerationPreview), CustomError> {
+        let Some(active_wallet) = self.get_active_wallet()? else { return Err(CustomError::WalletNotFound) };
+        let pubkey_hash = get_pubkey_hash(active_wallet.pubkey.clone())?;
+        let pending_txs = self.pending_txs.lock()?;
+        let Some(parent) = pending_txs.get_pending_tx(&tx_hash) else {
+            return Err(CustomError::Validation(format!(
+                "Transaction {tx_hash} is not pending"
+            )));
+        };
+
+        let mut out_points = vec![];
+        let mut input_value = 0;
+        for (index, output) in parent.outputs.iter().enumerate() {
+            let out_point = OutPoint {
+                hash: tx_hash,
+                index: index as u32,
+            };
+            if output.is_sent_to_key(&pubkey_hash)? && !pending_txs.is_spent(&out_point) {
+                out_points.push(out_point);
+                input_value += output.value;
+            }
+        }
+        if out_points.is_empty() {
+            return Err(CustomError::Validation(String::from(
+                "The transaction has no unspent outputs of the active wallet",
+            )));
+        }
+        let min_relay_fee = pending_txs.get_min_relay_fee();
+        if fee_rate * 1000 < min_relay_fee {
+            return Err(CustomError::Validation(format!(
+                "The fee rate must be at least {} satoshis per virtual byte",
+                min_relay_fee.div_ceil(1000)
+            )));
         }
 
-        Transaction::create(active_wallet, inputs, outputs)
+        let parent_fee = pending_txs.get_fee(&tx_hash);
+        let parent_vsize = parent.vsize();
+        let unfunded = Transaction::create(
+            &active_wallet,
+            out_points.clone(),
+            HashMap::from([(active_wallet.pubkey.clone(), input_value)]),
+            None,
+        )?;
+        let package_fee = fee_rate * (parent_vsize + unfunded.vsize()) as u64;
+        let fee = match package_fee.checked_sub(parent_fee.unwrap_or(0)) {
+            Some(fee) if fee > 0 => fee,
+            _ => {
+                return Err(CustomError::Validation(format!(
+                    "The transaction already pays at least {fee_rate} satoshis per virtual byte"
+                )))
+            }
+        };
+        let output = TransactionOutput {
+            value: input_value.saturating_sub(fee),
+            script_pubkey: active_wallet.get_script_pubkey()?,
+        };
+        if output.is_dust() {
+            return Err(CustomError::Validation(String::from(
+                "The outputs of the transaction do not cover the fee",
+            )));
+        }
+
+        let transaction = Transaction::create(
+            &active_wallet,
+            out_points,
+            HashMap::from([(active_wallet.pubkey.clone(), output.value)]),
+            None,
+        )?;
+        let preview = AccelerationPreview {
+            parent_hash: tx_hash,
+            parent_fee,
+            parent_vsize,
+            address: active_wallet.pubkey,
+            value: output.value,
+            fee,
+            vsize: transaction.vsize(),
+        };
+        Ok((transaction, preview))
     }
 
     fn calculate_total_value(
@@ -388,6 +2172,32 @@ impl NodeState {
     }
 }
 
+/// Error con el motivo por el que se rechaza un bloque enviado con submitblock.
+fn rejected(reason: &str) -> CustomError {
+    CustomError::Validation(String::from(reason))
+}
+
+/// Elimina de los peers recibidos a un peer en particular y cierra su conexion
+pub fn remove_peer(peers: &mut Vec<Peer>, address: SocketAddrV6) {
+    let index = peers.iter().position(|p| p.address == address);
+
+    if let Some(index) = index {
+        peers.remove(index).disconnect();
+    }
+}
+
+/// Ordena a los peers por su puntaje de mal comportamiento (ej: bloques trabados) y luego por su latencia,
+/// que se actualiza con cada pong, y devuelve las direcciones de los primeros sync_peers.
+fn rank_sync_peers(peers: &[Peer], sync_peers: usize) -> Vec<SocketAddrV6> {
+    let mut ranked: Vec<&Peer> = peers.iter().collect();
+    ranked.sort_by_key(|peer| (peer.misbehavior, peer.latency));
+    ranked
+        .into_iter()
+        .take(sync_peers)
+        .map(|peer| peer.address)
+        .collect()
+}
+
 fn calculate_inputs(
     active_wallet_utxo: &[(OutPoint, UTXOValue)],
     total_value: u64,
@@ -413,5 +2223,9 @@ fn create_store_dir(path: &String) -> Result<(), CustomError> {
     if !blocks_path.exists() {
         fs::create_dir(blocks_path)?;
     }
+    let filters_path = path.join("filters");
+    if !filters_path.exists() {
+        fs::create_dir(filters_path)?;
+    }
     Ok(())
 }
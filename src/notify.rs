@@ -0,0 +1,72 @@
+use std::{process::Command, sync::mpsc, thread};
+
+use crate::{
+    logger::{send_log, Log, LogTarget},
+    structs::hash32::Hash32,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// NotifyCommands son los comandos externos que se ejecutan ante eventos del nodo,
+/// al estilo de blocknotify y walletnotify de Bitcoin Core.
+/// En cada comando se reemplaza %s por el hash del bloque o de la transaccion, y se ejecuta con `sh -c`
+/// en un thread propio, de forma que un comando lento no demore al nodo.
+/// Los elementos son:
+/// - block_notify: Comando que se ejecuta por cada bloque nuevo una vez sincronizado el nodo.
+/// - wallet_notify: Comando que se ejecuta por cada transaccion que mueve fondos de alguna de las wallets,
+///   al recibirla como pendiente y al confirmarse en un bloque.
+pub struct NotifyCommands {
+    pub block_notify: Option<String>,
+    pub wallet_notify: Option<String>,
+}
+
+impl NotifyCommands {
+    /// Ejecuta el comando de block_notify con el hash del bloque, si esta configurado.
+    pub fn notify_block(&self, logger_sender: &mpsc::Sender<Log>, block_hash: &Hash32) {
+        if let Some(command) = &self.block_notify {
+            run_command(logger_sender.clone(), command_for(command, block_hash));
+        }
+    }
+
+    /// Ejecuta el comando de wallet_notify con el hash de la transaccion, si esta configurado.
+    pub fn notify_wallet_tx(&self, logger_sender: &mpsc::Sender<Log>, tx_hash: &Hash32) {
+        if let Some(command) = &self.wallet_notify {
+            run_command(logger_sender.clone(), command_for(command, tx_hash));
+        }
+    }
+}
+
+/// Reemplaza %s en el comando por el hash, en hexadecimal como se muestra al usuario.
+fn command_for(command: &str, hash: &Hash32) -> String {
+    command.replace("%s", &hash.to_string())
+}
+
+/// Ejecuta el comando en un thread, registrando como advertencia si no se pudo ejecutar o termino con error.
+fn run_command(logger_sender: mpsc::Sender<Log>, command: String) {
+    thread::spawn(move || {
+        let message = match Command::new("sh").arg("-c").arg(&command).status() {
+            Ok(status) if status.success() => return,
+            Ok(status) => format!("Notify command `{command}` exited with {status}"),
+            Err(error) => format!("Cannot run notify command `{command}`: {error}"),
+        };
+        send_log(&logger_sender, Log::Warn(LogTarget::General, message));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn replaces_hash_in_command() {
+        let hash =
+            Hash32::from_str("000000000000000ba6f3ca57e4c5f95c6a7c2bb2e3b0c0d2c5e2b3b1a4c8d9e0")
+                .unwrap();
+        assert_eq!(
+            command_for("echo %s >> blocks.txt", &hash),
+            format!("echo {hash} >> blocks.txt")
+        );
+        assert_eq!(command_for("true", &hash), "true");
+    }
+}
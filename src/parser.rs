@@ -1,35 +1,112 @@
-use std::net::{Ipv6Addr, SocketAddrV6};
+use std::{
+    io::Read,
+    net::{Ipv6Addr, SocketAddrV6},
+};
 
-use crate::error::CustomError;
+use crate::{error::CustomError, message::read_error, structs::hash32::Hash32};
+
+/// Cantidad de bytes que se leen de una vez del reader de un BufferParser, salvo que se necesiten mas.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
 
 /// BufferParser es una estructura que contiene los elementos necesarios para parsear un buffer.
+/// El buffer puede estar completo desde el inicio, o leerse de un reader a medida que se parsea
+/// (por ejemplo el payload de un bloque desde el TcpStream), descartando lo que ya se extrajo.
 /// Los elementos son:
 /// - buffer: Buffer a parsear.
 /// - pos: Posicion actual del buffer.
-pub struct BufferParser {
+/// - reader: Reader del que se leen los bytes que todavia no estan en el buffer, si los hay.
+/// - unread: Cantidad de bytes que quedan por leer del reader.
+/// - canonical_varints: Si es true, los varints que no estan codificados en su forma mas corta son invalidos.
+pub struct BufferParser<'a> {
     buffer: Vec<u8>,
     pos: usize,
+    reader: Option<&'a mut dyn Read>,
+    unread: usize,
+    canonical_varints: bool,
 }
 
-impl BufferParser {
+impl<'a> BufferParser<'a> {
     #[must_use]
     /// Inicializa el parser de un buffer en la posicion 0.
     pub fn new(buffer: Vec<u8>) -> Self {
-        Self { buffer, pos: 0 }
+        Self {
+            buffer,
+            pos: 0,
+            reader: None,
+            unread: 0,
+            canonical_varints: false,
+        }
     }
 
-    /// Devuelve el largo restante del buffer.
+    #[must_use]
+    /// Inicializa el parser de los siguientes size bytes del reader, que se leen a medida que se extraen.
+    /// La memoria usada no depende de size sino del tamaño de lo que se extrae de una vez.
+    pub fn from_reader(reader: &'a mut dyn Read, size: usize) -> Self {
+        Self {
+            buffer: vec![],
+            pos: 0,
+            reader: Some(reader),
+            unread: size,
+            canonical_varints: false,
+        }
+    }
+
+    /// Ejecuta parse exigiendo que los varints esten codificados en su forma mas corta, como lo exige el consenso
+    /// para bloques y transacciones. Al terminar vuelve al modo anterior, por lo que se puede anidar.
+    pub fn canonical<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, CustomError>,
+    ) -> Result<T, CustomError> {
+        let previous = self.canonical_varints;
+        self.canonical_varints = true;
+        let result = parse(self);
+        self.canonical_varints = previous;
+        result
+    }
+
+    /// Devuelve el largo restante del buffer, contando lo que falta leer del reader.
     pub fn len(&mut self) -> usize {
-        self.buffer.len() - self.pos
+        self.buffer.len() - self.pos + self.unread
     }
 
     /// Devuelve true si fue recorrido completamente
     pub fn is_empty(&mut self) -> bool {
-        self.buffer.len() - self.pos == 0
+        self.len() == 0
+    }
+
+    /// Devuelve los siguientes size bytes del buffer sin avanzar la posicion, o None si no quedan suficientes.
+    pub fn peek(&mut self, size: usize) -> Option<&[u8]> {
+        self.fill(size).ok()?;
+        self.buffer.get(self.pos..(self.pos + size))
+    }
+
+    /// Lee del reader lo necesario para que el buffer tenga size bytes desde la posicion actual, o todo lo que
+    /// queda si no alcanza. Antes de leer se descarta lo ya extraido, por lo que el buffer solo guarda lo pendiente.
+    /// Devuelve CustomError si no se puede leer del reader, PeerTimeout si vencio su read timeout.
+    fn fill(&mut self, size: usize) -> Result<(), CustomError> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(());
+        };
+        let missing = (self.pos + size).saturating_sub(self.buffer.len());
+        if missing == 0 || self.unread == 0 {
+            return Ok(());
+        }
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+
+        let read_size = missing.max(READ_CHUNK_SIZE).min(self.unread);
+        let start = self.buffer.len();
+        self.buffer.resize(start + read_size, 0);
+        reader
+            .read_exact(&mut self.buffer[start..])
+            .map_err(|error| read_error(error, CustomError::CannotReadStream))?;
+        self.unread -= read_size;
+        Ok(())
     }
 
     /// Extrae un buffer de tamaño size del buffer.
     pub fn extract_buffer(&mut self, size: usize) -> Result<&[u8], CustomError> {
+        self.fill(size)?;
         let buffer = match self.buffer.get(self.pos..(self.pos + size)) {
             Some(buffer) => Ok(buffer),
             None => return Err(CustomError::SerializedBufferIsInvalid),
@@ -119,6 +196,7 @@ impl BufferParser {
     }
 
     /// extrae un varint del buffer
+    /// Dentro de canonical devuelve CustomError::NonCanonicalVarint si el valor entraba en una codificacion mas corta.
     pub fn extract_varint(&mut self) -> Result<u64, CustomError> {
         let first_byte = self.extract_u8()?;
         let slice = match first_byte {
@@ -150,9 +228,23 @@ impl BufferParser {
             }
             _ => u64::from_le_bytes([first_byte, 0, 0, 0, 0, 0, 0, 0]),
         };
+        let minimum = match first_byte {
+            0xFF_u8 => 0x1_0000_0000,
+            0xFE_u8 => 0x1_0000,
+            0xFD_u8 => 0xFD,
+            _ => 0,
+        };
+        if self.canonical_varints && slice < minimum {
+            return Err(CustomError::NonCanonicalVarint);
+        }
         Ok(slice)
     }
 
+    /// extrae un hash de 32 bytes del buffer
+    pub fn extract_hash(&mut self) -> Result<Hash32, CustomError> {
+        Hash32::from_slice(self.extract_buffer(32)?)
+    }
+
     /// extrae una direccion del buffer
     pub fn extract_address(&mut self) -> Result<SocketAddrV6, CustomError> {
         let ipv6 = Ipv6Addr::new(
@@ -204,6 +296,115 @@ impl VarIntSerialize for usize {
     }
 }
 
+/// BtcEncode es el trait comun de los tipos que se serializan con el formato del protocolo de Bitcoin:
+/// mensajes, transacciones, headers, inventarios, direcciones, etc.
+/// Cada tipo implementa encode y decode, y a partir de ellos se obtienen serialize y parse.
+/// Se implementa para los enteros (little endian), Hash32, String (varint con el largo y los bytes en UTF-8),
+/// Vec<u8> (varint con el largo y los bytes) y Vec<T> de cualquier tipo que lo implemente (varint con la cantidad
+/// y cada elemento), por lo que los tipos compuestos se pueden serializar campo por campo.
+pub trait BtcEncode {
+    /// Agrega el tipo serializado al final del buffer.
+    fn encode(&self, buffer: &mut Vec<u8>);
+
+    /// Parsea el tipo desde la posicion actual del parser, que puede estar leyendo directamente de un stream.
+    /// Devuelve CustomError si faltan bytes o si los datos no son validos para el tipo.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError>
+    where
+        Self: Sized;
+
+    /// Serializa el tipo en un vector de bytes.
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        self.encode(&mut buffer);
+        buffer
+    }
+
+    /// Parsea el tipo desde el comienzo del buffer.
+    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError>
+    where
+        Self: Sized,
+    {
+        Self::decode(&mut BufferParser::new(buffer))
+    }
+}
+
+macro_rules! impl_btc_encode_for_int {
+    ($($int:ty => $extract:ident),*) => {
+        $(impl BtcEncode for $int {
+            fn encode(&self, buffer: &mut Vec<u8>) {
+                buffer.extend(self.to_le_bytes());
+            }
+
+            fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+                parser.$extract()
+            }
+        })*
+    };
+}
+
+impl_btc_encode_for_int!(
+    u16 => extract_u16,
+    u32 => extract_u32,
+    u64 => extract_u64,
+    i32 => extract_i32,
+    i64 => extract_i64
+);
+
+impl BtcEncode for Hash32 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.as_bytes());
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        parser.extract_hash()
+    }
+}
+
+impl BtcEncode for String {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.len().to_varint_bytes());
+        buffer.extend(self.as_bytes());
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let size = parser.extract_varint()? as usize;
+        parser.extract_string(size)
+    }
+}
+
+/// Los bytes se extraen de una vez en lugar de uno por uno, ya que son los scripts, witness y filtros.
+impl BtcEncode for Vec<u8> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.len().to_varint_bytes());
+        buffer.extend(self);
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let size = parser.extract_varint()? as usize;
+        Ok(parser.extract_buffer(size)?.to_vec())
+    }
+}
+
+/// No se reserva memoria a partir de la cantidad recibida, ya que la envia el peer:
+/// si es falsa el parseo falla al quedarse sin bytes.
+impl<T: BtcEncode> BtcEncode for Vec<T> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(self.len().to_varint_bytes());
+        for item in self {
+            item.encode(buffer);
+        }
+    }
+
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let count = parser.extract_varint()?;
+        let mut items = vec![];
+        for _ in 0..count {
+            items.push(T::decode(parser)?);
+        }
+        Ok(items)
+    }
+}
+
 #[cfg(test)]
 
 mod tests {
@@ -302,6 +503,36 @@ mod tests {
         assert_eq!(buffer.extract_varint().unwrap(), 0x00000000010203);
     }
 
+    #[test]
+    fn extract_canonical_varint() {
+        let non_canonical = [
+            vec![0xFD, 0xFC, 0x00],
+            vec![0xFE, 0xFF, 0xFF, 0x00, 0x00],
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for buffer in non_canonical {
+            let mut parser = BufferParser::new(buffer.clone());
+            assert!(parser.extract_varint().is_ok());
+            let mut parser = BufferParser::new(buffer);
+            assert!(matches!(
+                parser.canonical(|parser| parser.extract_varint()),
+                Err(CustomError::NonCanonicalVarint)
+            ));
+        }
+
+        let mut parser = BufferParser::new(vec![0xFD, 0xFD, 0x00, 0xFC, 0x01]);
+        assert_eq!(
+            parser.canonical(|parser| parser.extract_varint()).unwrap(),
+            0xFD
+        );
+        assert_eq!(
+            parser.canonical(|parser| parser.extract_varint()).unwrap(),
+            0xFC
+        );
+        // fuera de canonical vuelve a aceptar cualquier codificacion
+        assert_eq!(parser.extract_varint().unwrap(), 0x01);
+    }
+
     #[test]
     fn extract_address() {
         let mut buffer = BufferParser::new(vec![
@@ -319,10 +550,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_from_reader() {
+        let bytes: Vec<u8> = (0..READ_CHUNK_SIZE + 100).map(|i| i as u8).collect();
+        let mut reader = bytes.as_slice();
+        let mut parser = BufferParser::from_reader(&mut reader, bytes.len() - 4);
+
+        assert_eq!(parser.len(), bytes.len() - 4);
+        assert_eq!(parser.peek(2), Some(&bytes[..2]));
+        assert_eq!(parser.extract_u8().unwrap(), 0);
+        assert_eq!(
+            parser.extract_buffer(READ_CHUNK_SIZE).unwrap(),
+            &bytes[1..READ_CHUNK_SIZE + 1]
+        );
+        assert_eq!(parser.len(), 95);
+        assert!(parser.extract_buffer(96).is_err());
+        // solo se leen del reader los bytes del parser
+        assert_eq!(reader.len(), 4);
+    }
+
     #[test]
     fn serialize_varint() {
         let number: usize = 0x03;
         let bytes = number.to_varint_bytes();
         assert_eq!(bytes, vec![0x03]);
     }
+
+    #[test]
+    fn btc_encode_vectors_and_strings() {
+        let hashes = vec![Hash32::new([1; 32]), Hash32::new([2; 32])];
+        let buffer = hashes.serialize();
+        assert_eq!(buffer.len(), 1 + 64);
+        assert_eq!(buffer[0], 2);
+        assert_eq!(Vec::<Hash32>::parse(buffer).unwrap(), hashes);
+
+        let witness = vec![vec![0xAB; 300], vec![]];
+        let buffer = witness.serialize();
+        assert_eq!(buffer[..4], [2, 0xFD, 0x2C, 0x01]);
+        assert_eq!(Vec::<Vec<u8>>::parse(buffer).unwrap(), witness);
+
+        let string = String::from("/Satoshi:25.0.0/");
+        assert_eq!(String::parse(string.serialize()).unwrap(), string);
+        assert!(String::parse(vec![2, 0xFF, 0xFE]).is_err());
+
+        let mut parser = BufferParser::new(vec![3, 1, 0, 0, 0, 2, 0, 0, 0]);
+        assert!(Vec::<u32>::decode(&mut parser).is_err());
+    }
 }
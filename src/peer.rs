@@ -1,24 +1,47 @@
 use std::{
-    net::{SocketAddr, SocketAddrV6, TcpStream},
-    sync::{mpsc, Arc, Mutex},
+    collections::VecDeque,
+    fmt,
+    net::{Shutdown, SocketAddr, SocketAddrV6, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
+    time::Duration,
 };
 
 use chrono::Local;
+use tracing::instrument;
 
 use crate::{
+    capture::Capture,
     error::CustomError,
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogLevel, LogTarget},
     loops::{
-        node_action_loop::NodeAction,
+        node_action_loop::{NodeAction, NodeActionSender},
         peer_action_loop::{PeerAction, PeerActionLoop},
         peer_stream_loop::PeerStreamLoop,
     },
-    message::{Message, MessageHeader},
+    message::{Message, MessageHeader, MESSAGE_HEADER_SIZE},
     messages::{
-        get_headers::GetHeaders, send_headers::SendHeaders, ver_ack::VerAck, version::Version,
+        get_addr::GetAddr,
+        get_headers::GetHeaders,
+        ping_pong::Ping,
+        reject::{REJECT_INVALID, REJECT_MALFORMED},
+        send_addr_v2::SendAddrV2,
+        send_headers::SendHeaders,
+        ver_ack::VerAck,
+        version::Version,
+        wtxid_relay::WtxidRelay,
+    },
+    network::Network,
+    parser::BtcEncode,
+    proxy::Proxy,
+    structs::{bloom_filter::BloomFilter, hash32::Hash32},
+    utils::{
+        get_address_v6, get_current_timestamp, get_current_timestamp_millis, get_random_nonce,
+        open_stream,
     },
-    utils::{get_address_v6, open_stream},
 };
 
 /// GENESIS es el hash del bloque genesis de la blockchain de Bitcoin.
@@ -27,6 +50,226 @@ pub const GENESIS: [u8; 32] = [
     132, 233, 14, 173, 1, 234, 51, 9, 0, 0, 0, 0,
 ];
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Timeouts utilizados en las conexiones con peers, para no bloquearse con un peer que no responde.
+/// - connect: tiempo maximo para establecer la conexion TCP.
+/// - handshake: tiempo maximo de espera de cada mensaje durante el handshake.
+/// - read: tiempo maximo sin recibir mensajes del peer luego del handshake.
+/// - ping: tiempo maximo de espera del pong de cada ping enviado al peer.
+pub struct PeerTimeouts {
+    pub connect: Duration,
+    pub handshake: Duration,
+    pub read: Duration,
+    pub ping: Duration,
+}
+
+impl Default for PeerTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_millis(500),
+            handshake: Duration::from_secs(5),
+            read: Duration::from_secs(600),
+            ping: Duration::from_secs(120),
+        }
+    }
+}
+
+/// User agent por defecto, con el nombre y la version del nodo en el formato de BIP14.
+pub fn default_user_agent() -> String {
+    format!("/bitcoin-node:{}/", env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// PeerConfig contiene la configuracion del nodo que se usa en las conexiones con todos los peers.
+/// Los elementos son:
+/// - network: Red a la que se conecta el nodo, determina el magic number de los mensajes y el bloque genesis.
+/// - services: Servicios que anuncia el nodo en el mensaje version.
+/// - timeouts: Timeouts de las conexiones con los peers.
+/// - user_agent: User agent con el que el nodo se presenta en el mensaje version.
+/// - blocks_only: Indica si el nodo funciona en modo solo bloques, en cuyo caso les pide a los peers que no le anuncien
+///   transacciones (fRelay en false) e ignora las que le anuncian. Las transacciones propias se siguen enviando.
+/// - spv: Indica si el nodo funciona como cliente SPV, en cuyo caso pide los bloques filtrados por las wallets.
+/// - proxy: Proxy SOCKS5 por el que se abren las conexiones salientes, si se configuro alguno.
+/// - capture: Captura en la que se guardan los mensajes recibidos de los peers, si se configuro alguna.
+pub struct PeerConfig {
+    pub network: Network,
+    pub services: u64,
+    pub timeouts: PeerTimeouts,
+    pub user_agent: String,
+    pub blocks_only: bool,
+    pub spv: bool,
+    pub proxy: Option<Proxy>,
+    pub capture: Option<Capture>,
+}
+
+impl Default for PeerConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::Testnet,
+            services: 0,
+            timeouts: PeerTimeouts::default(),
+            user_agent: default_user_agent(),
+            blocks_only: false,
+            spv: false,
+            proxy: None,
+            capture: None,
+        }
+    }
+}
+
+/// Version del protocolo a partir de la cual se envia sendaddrv2 (BIP155).
+const ADDR_V2_VERSION: i32 = 70016;
+/// Version del protocolo a partir de la cual se negocia wtxidrelay (BIP339).
+const WTXID_RELAY_VERSION: i32 = 70016;
+
+/// Cantidad maxima de mensajes que se aceptan durante el handshake antes del verack.
+const MAX_HANDSHAKE_MESSAGES: usize = 10;
+
+/// Puntaje de mal comportamiento a partir del cual se desconecta y banea a un peer.
+pub const BAN_SCORE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Comportamientos no esperados de un peer, cada uno suma un puntaje distinto a su puntaje de mal comportamiento.
+/// - InvalidHeaders: headers que no cumplen con la proof of work.
+/// - DivergentHeaders: headers que divergen de la cadena con mas trabajo que envian los demas peers, lejos de su punta.
+/// - InvalidBlock: bloque cuyo merkle root no coincide con el del header, o que gasta mas de lo que recibe.
+/// - InvalidChecksum: payload que no coincide con el checksum del header.
+/// - OversizedMessage: payload mas grande que el maximo permitido para su comando.
+/// - MalformedMessage: payload que no se puede parsear.
+/// - UnrequestedBlock: bloque que no fue solicitado.
+/// - StalledBlocks: bloques solicitados que no envio a tiempo.
+pub enum Misbehavior {
+    InvalidHeaders,
+    DivergentHeaders,
+    InvalidBlock,
+    InvalidChecksum,
+    OversizedMessage,
+    MalformedMessage,
+    UnrequestedBlock,
+    StalledBlocks,
+}
+
+impl Misbehavior {
+    /// Devuelve el mal comportamiento que corresponde al error con el que fallo el procesamiento de un mensaje, si lo hay.
+    pub fn from_error(error: &CustomError) -> Option<Self> {
+        match error {
+            CustomError::HeaderInvalidPoW => Some(Self::InvalidHeaders),
+            CustomError::InvalidMerkleRoot
+            | CustomError::InvalidBalance
+            | CustomError::BlockExceedsLimits => Some(Self::InvalidBlock),
+            CustomError::InvalidChecksum => Some(Self::InvalidChecksum),
+            CustomError::MessageTooLarge => Some(Self::OversizedMessage),
+            CustomError::SerializedBufferIsInvalid | CustomError::NonCanonicalVarint => {
+                Some(Self::MalformedMessage)
+            }
+            _ => None,
+        }
+    }
+
+    /// Codigo del mensaje 'reject' con el que se le informa al peer por que se rechazo su mensaje (BIP61).
+    /// Devuelve None si el mal comportamiento no se debe a un mensaje invalido.
+    pub fn reject_code(&self) -> Option<u8> {
+        match self {
+            Self::InvalidHeaders | Self::InvalidBlock => Some(REJECT_INVALID),
+            Self::InvalidChecksum | Self::OversizedMessage | Self::MalformedMessage => {
+                Some(REJECT_MALFORMED)
+            }
+            Self::DivergentHeaders | Self::UnrequestedBlock | Self::StalledBlocks => None,
+        }
+    }
+
+    /// Puntaje que suma el mal comportamiento, al llegar a BAN_SCORE se banea al peer.
+    pub fn score(&self) -> u32 {
+        match self {
+            Self::InvalidHeaders | Self::InvalidBlock | Self::OversizedMessage => BAN_SCORE,
+            Self::InvalidChecksum => 50,
+            Self::DivergentHeaders | Self::MalformedMessage => 20,
+            Self::UnrequestedBlock | Self::StalledBlocks => 10,
+        }
+    }
+}
+
+impl fmt::Display for Misbehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::InvalidHeaders => "invalid proof of work in headers",
+            Self::DivergentHeaders => "headers diverging from the most-work chain",
+            Self::InvalidBlock => "invalid merkle root in block",
+            Self::InvalidChecksum => "invalid message checksum",
+            Self::OversizedMessage => "oversized message",
+            Self::MalformedMessage => "malformed message",
+            Self::UnrequestedBlock => "unrequested block",
+            Self::StalledBlocks => "stalled block download",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// Cantidad de nonces de mensajes version enviados que se recuerdan para detectar conexiones a sí mismo.
+const MAX_SENT_NONCES: usize = 100;
+
+/// Nonces de los ultimos mensajes version enviados por el nodo.
+/// Si un peer nos envia un version con alguno de estos nonces, la conexion es con nosotros mismos.
+static SENT_NONCES: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Ping enviado a un peer que todavia no fue respondido.
+/// - nonce: Nonce del ping, el pong debe tener el mismo.
+/// - sent_at: Timestamp en milisegundos en el que se envio.
+pub struct PendingPing {
+    pub nonce: u64,
+    pub sent_at: u128,
+}
+
+#[derive(Debug)]
+/// Bytes recibidos y enviados en la conexion con un peer, contando los headers de los mensajes.
+/// Se comparte entre el Peer y sus threads, que lo actualizan al leer y escribir en el stream.
+/// Los mensajes se envian con el magic number de la red de la conexion.
+pub struct PeerTraffic {
+    network: Network,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl PeerTraffic {
+    /// Inicializa el trafico de una conexion con un peer de la red recibida.
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+        }
+    }
+
+    /// Devuelve la red de la conexion.
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    /// Suma los bytes de un mensaje leido del stream.
+    pub fn add_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Envia un mensaje por el stream y suma sus bytes a los enviados.
+    pub fn send(&self, message: &impl Message, stream: &mut TcpStream) -> Result<(), CustomError> {
+        let bytes = message.send(stream, self.network)?;
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Devuelve los bytes recibidos del peer.
+    pub fn get_bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Devuelve los bytes enviados al peer.
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+}
+
 /// Peer es una representacion de los Peers a los que nos conectamos, contiene los elementos necesarios para manejar la conexion con el peer.
 /// Cada peer tiene dos threads asociados:
 /// - peer_action_thread: Thread que escucha las acciones a realizar por el peer.
@@ -34,68 +277,123 @@ pub const GENESIS: [u8; 32] = [
 ///
 /// Los elementos son:
 /// - address: Direccion del peer.
+/// - network: Red de la conexion, determina el magic number de los mensajes.
 /// - services: Servicios del peer.
 /// - version: Version del peer.
+/// - user_agent: User agent del peer, indica el software que utiliza.
+/// - start_height: Altura de la cadena del peer al conectarse, informada en su version.
 /// - send_headers: Booleano que indica si el peer soporta el envio de headers.
 /// - requested_headers: Booleano que indica si el peer ya nos solicito headers.
+/// - send_addr_v2: Booleano que indica si el peer prefiere recibir direcciones en mensajes addrv2 (BIP155).
+/// - relay: Booleano que indica si el peer quiere que le enviemos transacciones (fRelay del version, BIP37).
+/// - wtxid_relay: Booleano que indica si se negocio con el peer anunciar y pedir las transacciones por wtxid (BIP339).
+/// - inbound: Booleano que indica si la conexion la inicio el peer (entrante) o el nodo (saliente).
+/// - fee_filter: Fee minimo en satoshis por kilobyte de las transacciones que el peer quiere que le anuncien (BIP133).
+/// - bloom_filter: Bloom filter cargado por el peer con 'filterload', solo se le anuncian las transacciones que coinciden (BIP37).
 /// - stream: Stream del peer.
-/// - benchmark: Velocidad de handshake con el peer, utilizado para elegir el mejor peer.
+/// - latency: Latencia en milisegundos con el peer, medida en el handshake y actualizada con cada pong. Se usa para elegir el mejor peer.
+/// - pending_ping: Ping enviado al peer que todavia no fue respondido.
+/// - misbehavior: Puntaje de mal comportamiento acumulado por el peer, al llegar a BAN_SCORE se lo banea.
+/// - connected_at: Timestamp en segundos en el que se establecio la conexion.
+/// - last_useful_message: Timestamp en segundos del ultimo mensaje util (headers, bloques, transacciones o inventarios)
+///   recibido del peer, lo actualiza el peer_stream_thread. Se usa para elegir que peer entrante desalojar.
+/// - traffic: Bytes recibidos y enviados al peer, compartidos con sus threads.
 /// - peer_action_thread: Thread que escucha las acciones a realizar por el peer.
 /// - peer_stream_thread: Thread que escucha el stream del peer.
 ///
 pub struct Peer {
     pub address: SocketAddrV6,
+    pub network: Network,
     pub services: u64,
     pub version: i32,
+    pub user_agent: String,
+    pub start_height: i32,
     pub send_headers: bool,
     pub requested_headers: bool,
+    pub send_addr_v2: bool,
+    pub relay: bool,
+    pub wtxid_relay: bool,
+    pub inbound: bool,
+    pub fee_filter: u64,
+    pub bloom_filter: Option<BloomFilter>,
     pub stream: TcpStream,
-    pub benchmark: i64,
+    pub latency: i64,
+    pub pending_ping: Option<PendingPing>,
+    pub misbehavior: u32,
+    pub connected_at: u64,
+    pub last_useful_message: Arc<AtomicU64>,
+    pub traffic: Arc<PeerTraffic>,
     pub peer_action_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
     pub peer_stream_thread: Option<thread::JoinHandle<Result<(), CustomError>>>,
 }
 
 impl Peer {
     /// Llama a un peer de la red de Bitcoin y crea un Peer.
+    /// La conexion se abre con los timeouts y el proxy de la configuracion recibida.
     /// Realiza el handshake con el peer y crea los threads asociados.
     pub fn call(
         address: SocketAddr,
         sender_address: SocketAddrV6,
-        services: u64,
         version: i32,
+        config: &PeerConfig,
         peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
         logger_sender: mpsc::Sender<Log>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
     ) -> Result<Self, CustomError> {
-        let stream = open_stream(address)?;
+        let stream = open_stream(address, config.timeouts.connect, config.proxy.as_ref())?;
 
         let mut peer = Self {
             address: get_address_v6(address),
+            network: config.network,
             peer_action_thread: None,
             peer_stream_thread: None,
-            services,
+            services: config.services,
             version,
+            user_agent: String::new(),
+            start_height: 0,
             stream,
-            benchmark: 99999,
+            latency: 99999,
+            pending_ping: None,
+            misbehavior: 0,
+            connected_at: get_current_timestamp()?,
+            last_useful_message: Arc::new(AtomicU64::new(get_current_timestamp()?)),
+            traffic: Arc::new(PeerTraffic::new(config.network)),
             send_headers: false,
             requested_headers: false,
+            send_addr_v2: false,
+            relay: true,
+            wtxid_relay: false,
+            inbound: false,
+            fee_filter: 0,
+            bloom_filter: None,
         };
 
         let timestamp_before_handshake = Local::now().timestamp_millis();
-        peer.call_handshake(sender_address)?;
+        peer.stream
+            .set_read_timeout(Some(config.timeouts.handshake))?;
+        peer.call_handshake(sender_address, config)?;
+        peer.stream.set_read_timeout(Some(config.timeouts.read))?;
         let timestamp_after_handshake = Local::now().timestamp_millis();
-        peer.benchmark = timestamp_after_handshake - timestamp_before_handshake;
+        peer.latency = timestamp_after_handshake - timestamp_before_handshake;
 
         send_log(
             &logger_sender,
-            Log::Message(format!(
-                "Successful handshake with {} in {}ms",
-                peer.address.ip(),
-                peer.benchmark
-            )),
+            Log::Peer(
+                LogLevel::Info,
+                peer.address.into(),
+                format!(
+                    "Successful handshake in {}ms with {}",
+                    peer.latency, peer.user_agent
+                ),
+            ),
         );
 
-        peer.spawn_threads(peer_action_receiver, node_action_sender, logger_sender)?;
+        peer.spawn_threads(
+            config,
+            peer_action_receiver,
+            node_action_sender,
+            logger_sender,
+        )?;
         Ok(peer)
     }
 
@@ -104,88 +402,206 @@ impl Peer {
     pub fn answer(
         stream: TcpStream,
         sender_address: SocketAddrV6,
-        services: u64,
         version: i32,
+        config: &PeerConfig,
         peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
         logger_sender: mpsc::Sender<Log>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
     ) -> Result<Self, CustomError> {
         let mut peer = Self {
             address: get_address_v6(stream.peer_addr()?),
+            network: config.network,
             peer_action_thread: None,
             peer_stream_thread: None,
-            services,
+            services: config.services,
             version,
+            user_agent: String::new(),
+            start_height: 0,
             stream,
-            benchmark: 99999,
+            latency: 99999,
+            pending_ping: None,
+            misbehavior: 0,
+            connected_at: get_current_timestamp()?,
+            last_useful_message: Arc::new(AtomicU64::new(get_current_timestamp()?)),
+            traffic: Arc::new(PeerTraffic::new(config.network)),
             send_headers: false,
             requested_headers: false,
+            send_addr_v2: false,
+            relay: true,
+            wtxid_relay: false,
+            inbound: true,
+            fee_filter: 0,
+            bloom_filter: None,
         };
 
         let timestamp_before_handshake = Local::now().timestamp_millis();
-        peer.answer_handshake(sender_address)?;
+        peer.stream
+            .set_read_timeout(Some(config.timeouts.handshake))?;
+        peer.answer_handshake(sender_address, config)?;
+        peer.stream.set_read_timeout(Some(config.timeouts.read))?;
         let timestamp_after_handshake = Local::now().timestamp_millis();
-        peer.benchmark = timestamp_after_handshake - timestamp_before_handshake;
+        peer.latency = timestamp_after_handshake - timestamp_before_handshake;
 
         send_log(
             &logger_sender,
-            Log::Message(format!(
-                "Successful handshake with {} in {}ms",
-                peer.address.ip(),
-                peer.benchmark
-            )),
+            Log::Peer(
+                LogLevel::Info,
+                peer.address.into(),
+                format!(
+                    "Successful handshake in {}ms with {}",
+                    peer.latency, peer.user_agent
+                ),
+            ),
         );
 
-        peer.spawn_threads(peer_action_receiver, node_action_sender, logger_sender)?;
+        peer.spawn_threads(
+            config,
+            peer_action_receiver,
+            node_action_sender,
+            logger_sender,
+        )?;
         Ok(peer)
     }
 
     /// Realiza el handshake de Node con el Peer, cuando el Node es el que llama al Peer.
-    fn call_handshake(&mut self, sender_address: SocketAddrV6) -> Result<(), CustomError> {
-        Version::new(self.address, sender_address, self.version, self.services)
-            .send(&mut self.stream)?;
+    /// Se presenta con el user agent de la configuracion, y en modo solo bloques le pide que no anuncie transacciones.
+    #[instrument(name = "handshake", skip_all, fields(peer = %self.address))]
+    fn call_handshake(
+        &mut self,
+        sender_address: SocketAddrV6,
+        config: &PeerConfig,
+    ) -> Result<(), CustomError> {
+        let version = Version::new(
+            self.address,
+            sender_address,
+            self.version,
+            self.services,
+            &config.user_agent,
+            !config.blocks_only,
+        );
+        remember_sent_nonce(version.nonce)?;
+        self.send(version)?;
 
-        let response_header = MessageHeader::read(&mut self.stream)?;
-        let version_response = Version::read(&mut self.stream, response_header.payload_size)
-            .map_err(|_| CustomError::CannotHandshakeNode)?;
+        let (_, payload) = self.read_message()?;
+        let version_response =
+            Version::parse(payload).map_err(|_| CustomError::CannotHandshakeNode)?;
+        if is_sent_nonce(version_response.nonce)? {
+            return Err(CustomError::SelfConnection);
+        }
         self.version = version_response.version;
         self.services = version_response.services;
+        self.user_agent = version_response.user_agent;
+        self.start_height = version_response.start_height;
+        self.relay = version_response.relay;
 
-        let response_header = MessageHeader::read(&mut self.stream)?;
-        VerAck::read(&mut self.stream, response_header.payload_size)
-            .map_err(|_| CustomError::CannotHandshakeNode)?;
+        self.send_wtxid_relay_preference()?;
+        self.send_addr_v2_preference()?;
+        self.read_verack()?;
 
-        VerAck::new().send(&mut self.stream)?;
-        SendHeaders::new().send(&mut self.stream)?;
+        self.send(VerAck::new())?;
+        self.send(SendHeaders::new())?;
+        self.send(GetAddr::new())?;
 
         Ok(())
     }
 
     /// Realiza el handshake de Node con el Peer, cuando el Peer es el que llama al Node.
-    fn answer_handshake(&mut self, sender_address: SocketAddrV6) -> Result<(), CustomError> {
-        let response_header = MessageHeader::read(&mut self.stream)?;
-        let version_response = Version::read(&mut self.stream, response_header.payload_size)
-            .map_err(|_| CustomError::CannotHandshakeNode)?;
+    /// Se presenta con el user agent de la configuracion, y en modo solo bloques le pide que no anuncie transacciones.
+    #[instrument(name = "handshake", skip_all, fields(peer = %self.address))]
+    fn answer_handshake(
+        &mut self,
+        sender_address: SocketAddrV6,
+        config: &PeerConfig,
+    ) -> Result<(), CustomError> {
+        let (_, payload) = self.read_message()?;
+        let version_response =
+            Version::parse(payload).map_err(|_| CustomError::CannotHandshakeNode)?;
+        if is_sent_nonce(version_response.nonce)? {
+            return Err(CustomError::SelfConnection);
+        }
 
-        Version::new(self.address, sender_address, self.version, self.services)
-            .send(&mut self.stream)?;
+        let version = Version::new(
+            self.address,
+            sender_address,
+            self.version,
+            self.services,
+            &config.user_agent,
+            !config.blocks_only,
+        );
+        remember_sent_nonce(version.nonce)?;
+        self.send(version)?;
         self.version = version_response.version;
         self.services = version_response.services;
+        self.user_agent = version_response.user_agent;
+        self.start_height = version_response.start_height;
+        self.relay = version_response.relay;
+
+        self.send_wtxid_relay_preference()?;
+        self.send_addr_v2_preference()?;
+        self.send(VerAck::new())?;
 
-        VerAck::new().send(&mut self.stream)?;
+        self.read_verack()?;
+        self.send(SendHeaders::new())?;
 
-        let response_header = MessageHeader::read(&mut self.stream)?;
-        VerAck::read(&mut self.stream, response_header.payload_size)
-            .map_err(|_| CustomError::CannotHandshakeNode)?;
-        SendHeaders::new().send(&mut self.stream)?;
+        Ok(())
+    }
+
+    /// Envia wtxidrelay (BIP339) si la version del peer lo soporta, debe hacerse antes de nuestro verack.
+    fn send_wtxid_relay_preference(&mut self) -> Result<(), CustomError> {
+        if self.version >= WTXID_RELAY_VERSION {
+            self.send(WtxidRelay::new())?;
+        }
+        Ok(())
+    }
 
+    /// Envia sendaddrv2 (BIP155) si la version del peer lo soporta, debe hacerse antes de nuestro verack.
+    fn send_addr_v2_preference(&mut self) -> Result<(), CustomError> {
+        if self.version >= ADDR_V2_VERSION {
+            self.send(SendAddrV2::new())?;
+        }
         Ok(())
     }
 
+    /// Lee los mensajes del peer hasta recibir su verack.
+    /// Registra si el peer envio sendaddrv2 o wtxidrelay e ignora el resto de los mensajes que pueden llegar antes del verack.
+    /// El relay por wtxid solo queda negociado si tambien se lo enviamos al peer, es decir si su version lo soporta.
+    /// Devuelve CustomError si el peer envia demasiados mensajes sin enviar el verack.
+    fn read_verack(&mut self) -> Result<(), CustomError> {
+        for _ in 0..MAX_HANDSHAKE_MESSAGES {
+            let (response_header, payload) = self.read_message()?;
+            match response_header.command.as_str() {
+                "verack" => {
+                    VerAck::parse(payload).map_err(|_| CustomError::CannotHandshakeNode)?;
+                    return Ok(());
+                }
+                "sendaddrv2" => {
+                    SendAddrV2::parse(payload)?;
+                    self.send_addr_v2 = true;
+                }
+                "wtxidrelay" => {
+                    WtxidRelay::parse(payload)?;
+                    self.wtxid_relay = self.version >= WTXID_RELAY_VERSION;
+                }
+                _ => (),
+            }
+        }
+        Err(CustomError::CannotHandshakeNode)
+    }
+
+    /// Lee un mensaje del stream durante el handshake y suma sus bytes a los recibidos.
+    fn read_message(&mut self) -> Result<(MessageHeader, Vec<u8>), CustomError> {
+        let header = MessageHeader::read(&mut self.stream)?;
+        let payload = header.read_payload(&mut self.stream, self.network)?;
+        self.traffic
+            .add_received(MESSAGE_HEADER_SIZE + payload.len());
+        Ok((header, payload))
+    }
+
     fn spawn_threads(
         &mut self,
+        config: &PeerConfig,
         peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
-        node_action_sender: mpsc::Sender<NodeAction>,
+        node_action_sender: NodeActionSender,
         logger_sender: mpsc::Sender<Log>,
     ) -> Result<(), CustomError> {
         //thread que escucha al nodo
@@ -193,6 +609,7 @@ impl Peer {
             self.address,
             self.version,
             self.stream.try_clone()?,
+            self.traffic.clone(),
             logger_sender.clone(),
             peer_action_receiver,
             node_action_sender.clone(),
@@ -200,9 +617,11 @@ impl Peer {
 
         //Thread que escucha el stream
         self.peer_stream_thread = Some(PeerStreamLoop::spawn(
-            self.version,
             self.address,
             self.stream.try_clone()?,
+            config.clone(),
+            self.last_useful_message.clone(),
+            self.traffic.clone(),
             logger_sender,
             node_action_sender,
         ));
@@ -211,30 +630,249 @@ impl Peer {
 
     /// Envia un mensaje al peer.
     pub fn send(&mut self, message: impl Message) -> Result<(), CustomError> {
-        message.send(&mut self.stream)
+        self.traffic.send(&message, &mut self.stream)
+    }
+
+    /// Solicita al peer los headers siguientes a last_header, o al bloque genesis si no hay headers.
+    pub fn request_headers(&mut self, last_header: Option<Hash32>) -> Result<(), CustomError> {
+        let block_header_hashes = vec![last_header.unwrap_or_else(|| self.network.genesis())];
+        self.send(GetHeaders::new(
+            self.version,
+            block_header_hashes,
+            Hash32::default(),
+        ))
+    }
+
+    /// Suma el puntaje de un mal comportamiento al peer y devuelve el puntaje acumulado.
+    /// Si alcanza BAN_SCORE el peer debe ser baneado.
+    pub fn misbehave(&mut self, misbehavior: Misbehavior) -> u32 {
+        self.misbehavior += misbehavior.score();
+        self.misbehavior
+    }
+
+    /// Devuelve el timestamp en segundos del ultimo mensaje util recibido del peer.
+    pub fn get_last_useful_message(&self) -> u64 {
+        self.last_useful_message.load(Ordering::Relaxed)
+    }
+
+    /// Envia un ping al peer y lo registra como pendiente hasta recibir su pong.
+    pub fn ping(&mut self) -> Result<(), CustomError> {
+        let ping = Ping {
+            nonce: get_random_nonce(),
+        };
+        self.pending_ping = Some(PendingPing {
+            nonce: ping.nonce,
+            sent_at: get_current_timestamp_millis()?,
+        });
+        self.send(ping)
+    }
+
+    /// Registra el pong recibido del peer, actualizando su latencia si corresponde al ping pendiente.
+    /// Devuelve la latencia medida, o None si el nonce no corresponde a ningun ping pendiente.
+    pub fn pong(&mut self, nonce: u64) -> Result<Option<i64>, CustomError> {
+        let Some(pending_ping) = self.pending_ping.filter(|ping| ping.nonce == nonce) else {
+            return Ok(None);
+        };
+        self.pending_ping = None;
+        self.latency = (get_current_timestamp_millis()? - pending_ping.sent_at) as i64;
+        Ok(Some(self.latency))
+    }
+
+    /// Indica si el peer no respondio el ping pendiente dentro del timeout.
+    pub fn ping_timed_out(&self, now: u128, timeout: Duration) -> bool {
+        self.pending_ping
+            .is_some_and(|ping| now.saturating_sub(ping.sent_at) > timeout.as_millis())
+    }
+
+    /// Cierra la conexion con el peer, lo que termina su peer_stream_thread.
+    pub fn disconnect(&self) {
+        self.stream.shutdown(Shutdown::Both).ok();
     }
 }
 
-/// Se encarga de solicitar a un peer los headers siguientes a su ultimo header.
+/// Se encarga de solicitar a un peer los headers siguientes a su ultimo header, o al genesis de la red si no hay headers.
 pub fn request_headers(
-    last_header: Option<Vec<u8>>,
+    address: SocketAddrV6,
+    last_header: Option<Hash32>,
     version: i32,
     stream: &mut TcpStream,
+    traffic: &PeerTraffic,
     logger_sender: &mpsc::Sender<Log>,
-    node_action_sender: &mpsc::Sender<NodeAction>,
+    node_action_sender: &NodeActionSender,
 ) -> Result<(), CustomError> {
     let block_header_hashes = match last_header {
         Some(header) => [header].to_vec(),
-        None => [GENESIS.to_vec()].to_vec(),
+        None => [traffic.get_network().genesis()].to_vec(),
     };
 
-    let request = GetHeaders::new(version, block_header_hashes, vec![0; 32]).send(stream);
+    let request = traffic.send(
+        &GetHeaders::new(version, block_header_hashes, Hash32::default()),
+        stream,
+    );
     if request.is_err() {
         send_log(
             logger_sender,
-            Log::Message("Error requesting headers".to_string()),
+            Log::Warn(LogTarget::Sync, "Error requesting headers".to_string()),
         );
-        node_action_sender.send(NodeAction::GetHeadersError)?;
+        node_action_sender.send(NodeAction::GetHeadersError(address))?;
     }
     Ok(())
 }
+
+/// Guarda el nonce de un mensaje version enviado, descartando el mas viejo si se supera MAX_SENT_NONCES.
+fn remember_sent_nonce(nonce: u64) -> Result<(), CustomError> {
+    let mut sent_nonces = SENT_NONCES.lock()?;
+    if sent_nonces.len() >= MAX_SENT_NONCES {
+        sent_nonces.pop_front();
+    }
+    sent_nonces.push_back(nonce);
+    Ok(())
+}
+
+/// Indica si el nonce de un mensaje version recibido corresponde a uno enviado por el nodo.
+fn is_sent_nonce(nonce: u64) -> Result<bool, CustomError> {
+    Ok(SENT_NONCES.lock()?.contains(&nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::TcpListener};
+
+    use crate::node_state::NodeState;
+
+    use super::*;
+
+    /// Crea un Peer conectado por un socket local, sin handshake ni threads, y devuelve el otro extremo del socket.
+    /// Cada peer escucha en un puerto distinto, por lo que tienen direcciones distintas.
+    fn loopback_peer() -> Result<(Peer, TcpStream), CustomError> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = TcpStream::connect(listener.local_addr()?)?;
+        let (remote, _) = listener.accept()?;
+
+        let peer = Peer {
+            address: get_address_v6(stream.peer_addr()?),
+            network: Network::Testnet,
+            services: 0,
+            version: 70015,
+            user_agent: String::new(),
+            start_height: 0,
+            send_headers: false,
+            requested_headers: false,
+            send_addr_v2: false,
+            relay: true,
+            wtxid_relay: false,
+            inbound: false,
+            fee_filter: 0,
+            bloom_filter: None,
+            stream,
+            latency: 99999,
+            pending_ping: None,
+            misbehavior: 0,
+            connected_at: get_current_timestamp()?,
+            last_useful_message: Arc::new(AtomicU64::new(get_current_timestamp()?)),
+            traffic: Arc::new(PeerTraffic::new(Network::Testnet)),
+            peer_action_thread: None,
+            peer_stream_thread: None,
+        };
+        Ok((peer, remote))
+    }
+
+    #[test]
+    fn pong_must_match_the_pending_ping_nonce() -> Result<(), CustomError> {
+        let (mut peer, mut remote) = loopback_peer()?;
+        peer.ping()?;
+
+        let header = MessageHeader::read(&mut remote)?;
+        let payload = header.read_payload(&mut remote, Network::Testnet)?;
+        assert_eq!(header.command, "ping");
+        let nonce = Ping::parse(payload)?.nonce;
+        assert_eq!(peer.pending_ping.map(|ping| ping.nonce), Some(nonce));
+
+        // un pong con otro nonce no responde al ping pendiente
+        assert_eq!(peer.pong(nonce.wrapping_add(1))?, None);
+        assert!(peer.pending_ping.is_some());
+
+        assert!(peer.pong(nonce)?.is_some());
+        assert!(peer.pending_ping.is_none());
+        assert_eq!(peer.pong(nonce)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn ping_timeout_drops_unresponsive_peers() -> Result<(), CustomError> {
+        let timeout = Duration::from_secs(120);
+        let (mut unresponsive, _unresponsive_remote) = loopback_peer()?;
+        let (responsive, _responsive_remote) = loopback_peer()?;
+        let unresponsive_address = unresponsive.address;
+        let responsive_address = responsive.address;
+
+        unresponsive.ping()?;
+        let sent_at = unresponsive.pending_ping.map_or(0, |ping| ping.sent_at);
+        assert!(!unresponsive.ping_timed_out(sent_at + timeout.as_millis(), timeout));
+        assert!(unresponsive.ping_timed_out(sent_at + timeout.as_millis() + 1, timeout));
+        assert!(!responsive.ping_timed_out(sent_at + timeout.as_millis() + 1, timeout));
+        // el ping se envio hace mas que el timeout
+        unresponsive.pending_ping = Some(PendingPing {
+            nonce: 1,
+            sent_at: sent_at - timeout.as_millis() - 1,
+        });
+
+        let store_path = String::from("tests/store_ping_timeout");
+        let (logger_sender, _logger_receiver) = mpsc::channel();
+        let (gui_sender, _gui_receiver) = mpsc::channel();
+        let node_state = NodeState::new(logger_sender, gui_sender, &store_path, Network::Testnet)?;
+        node_state.append_peers(vec![unresponsive, responsive])?;
+
+        let timed_out = node_state.ping_peers(timeout)?;
+        let peers = node_state.get_peers()?;
+        let remaining: Vec<SocketAddrV6> = peers.iter().map(|peer| peer.address).collect();
+        // al peer que responde se le envia un nuevo ping
+        let pinged = peers.iter().all(|peer| peer.pending_ping.is_some());
+        drop(peers);
+        fs::remove_dir_all(store_path)?;
+
+        assert_eq!(timed_out, vec![unresponsive_address]);
+        assert_eq!(remaining, vec![responsive_address]);
+        assert!(pinged);
+        Ok(())
+    }
+
+    #[test]
+    fn misbehavior_from_error() {
+        assert_eq!(
+            Misbehavior::from_error(&CustomError::HeaderInvalidPoW),
+            Some(Misbehavior::InvalidHeaders)
+        );
+        assert_eq!(
+            Misbehavior::from_error(&CustomError::SerializedBufferIsInvalid),
+            Some(Misbehavior::MalformedMessage)
+        );
+        assert_eq!(Misbehavior::from_error(&CustomError::PeerTimeout), None);
+    }
+
+    #[test]
+    fn misbehavior_scores() {
+        assert_eq!(Misbehavior::InvalidHeaders.score(), BAN_SCORE);
+        assert_eq!(Misbehavior::OversizedMessage.score(), BAN_SCORE);
+        assert!(Misbehavior::MalformedMessage.score() * 4 < BAN_SCORE);
+        assert!(Misbehavior::MalformedMessage.score() * 5 >= BAN_SCORE);
+    }
+
+    #[test]
+    fn traffic_counts_sent_and_received_bytes() -> Result<(), CustomError> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let mut stream = TcpStream::connect(listener.local_addr()?)?;
+        let (mut remote, _) = listener.accept()?;
+
+        let traffic = PeerTraffic::new(Network::Testnet);
+        traffic.send(&VerAck::new(), &mut stream)?;
+        traffic.send(&SendHeaders::new(), &mut stream)?;
+        assert_eq!(traffic.get_bytes_sent(), 2 * MESSAGE_HEADER_SIZE as u64);
+
+        let header = MessageHeader::read(&mut remote)?;
+        let payload = header.read_payload(&mut remote, Network::Testnet)?;
+        traffic.add_received(MESSAGE_HEADER_SIZE + payload.len());
+        assert_eq!(traffic.get_bytes_received(), MESSAGE_HEADER_SIZE as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{error::CustomError, proxy::Proxy};
+
+/// Tiempo minimo en segundos entre dos consultas a la fuente de precios, aunque la anterior haya fallado.
+const MIN_FETCH_INTERVAL: u64 = 60;
+/// Antigüedad maxima en segundos de un precio para seguir mostrandolo cuando no se puede actualizar.
+const MAX_PRICE_AGE: u64 = 60 * 60;
+/// Timeout de la conexion con la fuente de precios y de la lectura de su respuesta.
+const PRICE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Tamaño maximo en bytes de la respuesta de la fuente de precios.
+const MAX_RESPONSE_SIZE: u64 = 64 * 1024;
+/// Campos en los que se busca el precio en una respuesta JSON, ademas del codigo de la moneda.
+const PRICE_FIELDS: [&str; 4] = ["amount", "price", "rate", "last"];
+
+#[derive(Debug, Clone, PartialEq)]
+/// Precio de un bitcoin en una moneda fiat:
+/// - currency: Codigo de la moneda (ej: USD).
+/// - price: Precio de un bitcoin en la moneda.
+pub struct FiatPrice {
+    pub currency: String,
+    pub price: f64,
+}
+
+impl FiatPrice {
+    /// Formatea el valor aproximado en la moneda de un monto en satoshis, con 2 decimales.
+    pub fn format(&self, value: i64) -> String {
+        let fiat_value = value as f64 / 100_000_000.0 * self.price;
+        format!("≈ {:.2} {}", fiat_value, self.currency)
+    }
+}
+
+/// Fuente del precio de bitcoin en una moneda fiat.
+/// Se implementa para cada proveedor, de forma que se pueda reemplazar sin cambiar como se muestra el precio.
+pub trait PriceSource: Send {
+    /// Consulta el precio de un bitcoin en la moneda recibida.
+    fn fetch_price(&self, currency: &str) -> Result<f64, CustomError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Fuente de precios que consulta una URL http://host[:port]/path, en la que {currency} se reemplaza por el codigo de la moneda.
+/// La respuesta puede ser el precio como texto o un JSON con el precio en el campo de la moneda o en uno de PRICE_FIELDS.
+/// Si tiene un proxy la consulta sale por el, resolviendo el host en el proxy.
+/// No se envia user agent ni ningun otro dato del nodo.
+pub struct HttpPriceSource {
+    host: String,
+    port: u16,
+    path: String,
+    proxy: Option<Proxy>,
+}
+
+impl HttpPriceSource {
+    /// Crea la fuente de precios a partir de la URL.
+    /// Devuelve CustomError::ConfigErrorReadingValue si la URL no es http o no tiene host.
+    pub fn from_url(url: &str) -> Result<Self, CustomError> {
+        let url = url
+            .strip_prefix("http://")
+            .ok_or(CustomError::ConfigErrorReadingValue)?;
+        let (authority, path) = match url.find('/') {
+            Some(index) => url.split_at(index),
+            None => (url, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| CustomError::ConfigErrorReadingValue)?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(CustomError::ConfigErrorReadingValue);
+        }
+
+        Ok(Self {
+            host: String::from(host),
+            port,
+            path: String::from(path),
+            proxy: None,
+        })
+    }
+
+    /// Devuelve la fuente de precios consultandola a traves del proxy recibido, si lo hay.
+    pub fn with_proxy(mut self, proxy: Option<Proxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    fn connect(&self) -> Result<TcpStream, CustomError> {
+        if let Some(proxy) = &self.proxy {
+            return proxy.connect_domain(&self.host, self.port, PRICE_TIMEOUT);
+        }
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|_| CustomError::CannotFetchPrice)?
+            .next()
+            .ok_or(CustomError::CannotFetchPrice)?;
+        TcpStream::connect_timeout(&address, PRICE_TIMEOUT)
+            .map_err(|_| CustomError::CannotFetchPrice)
+    }
+}
+
+impl PriceSource for HttpPriceSource {
+    fn fetch_price(&self, currency: &str) -> Result<f64, CustomError> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(PRICE_TIMEOUT))?;
+        stream.set_write_timeout(Some(PRICE_TIMEOUT))?;
+
+        let path = self.path.replace("{currency}", currency);
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+            path, self.host
+        );
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = vec![];
+        stream.take(MAX_RESPONSE_SIZE).read_to_end(&mut response)?;
+        let response = String::from_utf8_lossy(&response);
+        parse_price(parse_http_response(&response)?, currency)
+    }
+}
+
+/// Devuelve el cuerpo de una respuesta HTTP.
+/// Devuelve CustomError::CannotFetchPrice si la respuesta no es 200 OK.
+fn parse_http_response(response: &str) -> Result<&str, CustomError> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or(CustomError::CannotFetchPrice)?;
+    let status = head.split_whitespace().nth(1);
+    if !head.starts_with("HTTP/") || status != Some("200") {
+        return Err(CustomError::CannotFetchPrice);
+    }
+    Ok(body)
+}
+
+/// Parsea el precio del cuerpo de la respuesta, que puede ser solo el precio
+/// o un JSON con el precio en el campo de la moneda (ej: {"bitcoin":{"usd":30000}}) o en uno de PRICE_FIELDS.
+/// Devuelve CustomError::CannotFetchPrice si no encuentra un precio positivo.
+fn parse_price(body: &str, currency: &str) -> Result<f64, CustomError> {
+    let price = match body.trim().parse::<f64>() {
+        Ok(price) => Some(price),
+        Err(_) => {
+            let body = body.to_lowercase();
+            let currency = currency.to_lowercase();
+            let price = [currency.as_str()]
+                .into_iter()
+                .chain(PRICE_FIELDS)
+                .find_map(|field| json_number_field(&body, field));
+            price
+        }
+    };
+    match price {
+        Some(price) if price.is_finite() && price > 0.0 => Ok(price),
+        _ => Err(CustomError::CannotFetchPrice),
+    }
+}
+
+/// Busca en un JSON el primer campo con el nombre recibido cuyo valor sea un numero, o un string con un numero.
+fn json_number_field(json: &str, field: &str) -> Option<f64> {
+    let key = format!("\"{field}\"");
+    json.match_indices(&key).find_map(|(index, _)| {
+        let value = json[index + key.len()..].trim_start().strip_prefix(':')?;
+        let value = value.trim_start();
+        let value = value.strip_prefix('"').unwrap_or(value);
+        let end = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    })
+}
+
+/// PriceCache guarda el ultimo precio obtenido de una fuente de precios, limitando la frecuencia de las consultas.
+/// Los elementos son:
+/// - source: Fuente de precios.
+/// - currency: Codigo de la moneda en la que se consulta el precio.
+/// - price: Ultimo precio obtenido y el timestamp en segundos en que se obtuvo.
+/// - last_fetch: Timestamp en segundos de la ultima consulta, haya sido exitosa o no.
+pub struct PriceCache<S: PriceSource> {
+    source: S,
+    currency: String,
+    price: Option<(f64, u64)>,
+    last_fetch: Option<u64>,
+}
+
+impl<S: PriceSource> PriceCache<S> {
+    /// Crea la cache, sin ningun precio todavia.
+    pub fn new(source: S, currency: &str) -> Self {
+        Self {
+            source,
+            currency: currency.to_uppercase(),
+            price: None,
+            last_fetch: None,
+        }
+    }
+
+    /// Consulta el precio a la fuente, salvo que no hayan pasado MIN_FETCH_INTERVAL segundos desde la ultima consulta.
+    /// Si la consulta falla se conserva el precio anterior y se devuelve el error.
+    pub fn refresh(&mut self, now: u64) -> Result<(), CustomError> {
+        if let Some(last_fetch) = self.last_fetch {
+            if now.saturating_sub(last_fetch) < MIN_FETCH_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_fetch = Some(now);
+        let price = self.source.fetch_price(&self.currency)?;
+        self.price = Some((price, now));
+        Ok(())
+    }
+
+    /// Devuelve el ultimo precio obtenido, o None si no hay ninguno de los ultimos MAX_PRICE_AGE segundos.
+    pub fn get_price(&self, now: u64) -> Option<FiatPrice> {
+        let (price, fetched_at) = self.price?;
+        if now.saturating_sub(fetched_at) > MAX_PRICE_AGE {
+            return None;
+        }
+        Some(FiatPrice {
+            currency: self.currency.clone(),
+            price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// Fuente de precios de prueba que devuelve los precios recibidos en orden y cuenta las consultas.
+    struct FakePriceSource {
+        prices: RefCell<Vec<Result<f64, CustomError>>>,
+        fetches: Cell<usize>,
+    }
+
+    impl PriceSource for FakePriceSource {
+        fn fetch_price(&self, _currency: &str) -> Result<f64, CustomError> {
+            self.fetches.set(self.fetches.get() + 1);
+            self.prices.borrow_mut().remove(0)
+        }
+    }
+
+    fn fake_cache(prices: Vec<Result<f64, CustomError>>) -> PriceCache<FakePriceSource> {
+        let source = FakePriceSource {
+            prices: RefCell::new(prices),
+            fetches: Cell::new(0),
+        };
+        PriceCache::new(source, "usd")
+    }
+
+    #[test]
+    fn price_is_parsed_from_plain_text_and_json() -> Result<(), CustomError> {
+        assert_eq!(parse_price("30000.5\n", "USD")?, 30000.5);
+        assert_eq!(
+            parse_price(r#"{"bitcoin":{"usd":30123.45}}"#, "USD")?,
+            30123.45
+        );
+        assert_eq!(
+            parse_price(
+                r#"{"data":{"amount":"29000.10","base":"BTC","currency":"EUR"}}"#,
+                "EUR"
+            )?,
+            29000.1
+        );
+        assert!(parse_price(r#"{"error":"unknown"}"#, "USD").is_err());
+        assert!(parse_price("-5", "USD").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn url_is_parsed() -> Result<(), CustomError> {
+        let source = HttpPriceSource::from_url("http://localhost:8080/price/{currency}")?;
+        assert_eq!(source.host, "localhost");
+        assert_eq!(source.port, 8080);
+        assert_eq!(source.path, "/price/{currency}");
+
+        let source = HttpPriceSource::from_url("http://prices.example")?;
+        assert_eq!(source.port, 80);
+        assert_eq!(source.path, "/");
+
+        assert!(HttpPriceSource::from_url("https://prices.example/").is_err());
+        assert!(HttpPriceSource::from_url("http://:80/").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cache_limits_fetches_and_keeps_last_price() -> Result<(), CustomError> {
+        let mut cache = fake_cache(vec![Ok(30_000.0), Err(CustomError::CannotFetchPrice)]);
+
+        cache.refresh(1_000)?;
+        cache.refresh(1_000 + MIN_FETCH_INTERVAL - 1)?;
+        assert_eq!(cache.source.fetches.get(), 1);
+        assert_eq!(
+            cache.get_price(1_000),
+            Some(FiatPrice {
+                currency: String::from("USD"),
+                price: 30_000.0
+            })
+        );
+
+        assert!(cache.refresh(1_000 + MIN_FETCH_INTERVAL).is_err());
+        assert_eq!(cache.source.fetches.get(), 2);
+        assert!(cache.get_price(1_000 + MIN_FETCH_INTERVAL).is_some());
+        assert!(cache.get_price(1_000 + MAX_PRICE_AGE + 1).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn fiat_value_is_formatted() {
+        let price = FiatPrice {
+            currency: String::from("USD"),
+            price: 30_000.0,
+        };
+        assert_eq!(price.format(150_000), "≈ 45.00 USD");
+        assert_eq!(price.format(-100_000_000), "≈ -30000.00 USD");
+    }
+
+    #[test]
+    fn http_source_fetches_the_price() -> Result<(), CustomError> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"usd\":31000}",
+                )
+                .unwrap();
+            String::from_utf8_lossy(&request[..read]).to_string()
+        });
+
+        let source = HttpPriceSource::from_url(&format!("http://127.0.0.1:{port}/p/{{currency}}"))?;
+        assert_eq!(source.fetch_price("USD")?, 31_000.0);
+
+        let request = handle.join().unwrap();
+        assert!(request.starts_with("GET /p/USD HTTP/1.0\r\n"));
+        assert!(!request.to_lowercase().contains("user-agent"));
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{error::CustomError, utils::get_random_nonce};
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+const CONNECT: u8 = 0x01;
+const IPV4: u8 = 0x01;
+const DOMAIN: u8 = 0x03;
+const IPV6: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Proxy SOCKS5 (por ejemplo Tor) por el que se abren las conexiones con los peers.
+/// - address: direccion del proxy, con el formato host:port.
+/// - isolate: si es true se usan credenciales aleatorias en cada conexion, lo que hace que Tor
+///   use un circuito distinto para cada peer (stream isolation).
+pub struct Proxy {
+    pub address: String,
+    pub isolate: bool,
+}
+
+impl Proxy {
+    /// Abre una conexion con la direccion recibida a traves del proxy.
+    /// Devuelve CustomError si no se puede conectar con el proxy o si el proxy rechaza la conexion.
+    pub fn connect(&self, target: SocketAddr, timeout: Duration) -> Result<TcpStream, CustomError> {
+        self.open(&socket_address(target), timeout)
+    }
+
+    /// Abre una conexion con el host y puerto recibidos a traves del proxy.
+    /// El nombre del host lo resuelve el proxy, de forma que la consulta DNS tampoco sale por fuera de el.
+    /// Devuelve CustomError si el host es demasiado largo, si no se puede conectar con el proxy o si el proxy rechaza la conexion.
+    pub fn connect_domain(
+        &self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<TcpStream, CustomError> {
+        self.open(&domain_address(host, port)?, timeout)
+    }
+
+    /// Abre una conexion con el proxy y le pide conectarse con la direccion recibida, ya codificada como en el pedido SOCKS5.
+    fn open(&self, target: &[u8], timeout: Duration) -> Result<TcpStream, CustomError> {
+        let proxy_address = self
+            .address
+            .to_socket_addrs()
+            .map_err(|_| CustomError::CannotConnectThroughProxy)?
+            .next()
+            .ok_or(CustomError::CannotConnectThroughProxy)?;
+        let mut stream = TcpStream::connect_timeout(&proxy_address, timeout)
+            .map_err(|_| CustomError::CannotConnectThroughProxy)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let credentials = self.isolate.then(|| {
+            let nonce = get_random_nonce().to_string();
+            (nonce.clone(), nonce)
+        });
+        socks5_handshake(&mut stream, target, credentials)
+            .map_err(|_| CustomError::CannotConnectThroughProxy)?;
+
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)?;
+        Ok(stream)
+    }
+}
+
+/// Codifica una direccion IP y su puerto como en el pedido SOCKS5.
+fn socket_address(target: SocketAddr) -> Vec<u8> {
+    let mut address = vec![];
+    match target {
+        SocketAddr::V4(target) => {
+            address.push(IPV4);
+            address.extend(target.ip().octets());
+        }
+        SocketAddr::V6(target) => {
+            address.push(IPV6);
+            address.extend(target.ip().octets());
+        }
+    }
+    address.extend(target.port().to_be_bytes());
+    address
+}
+
+/// Codifica un nombre de host y su puerto como en el pedido SOCKS5, para que lo resuelva el proxy.
+/// Devuelve CustomError si el host esta vacio o tiene mas de 255 bytes.
+fn domain_address(host: &str, port: u16) -> Result<Vec<u8>, CustomError> {
+    if host.is_empty() || host.len() > u8::MAX as usize {
+        return Err(CustomError::CannotConnectThroughProxy);
+    }
+    let mut address = vec![DOMAIN, host.len() as u8];
+    address.extend(host.as_bytes());
+    address.extend(port.to_be_bytes());
+    Ok(address)
+}
+
+/// Realiza el handshake SOCKS5 (RFC 1928) sobre el stream abierto con el proxy y solicita la conexion con target,
+/// ya codificada como tipo de direccion, direccion y puerto.
+/// Si se reciben credenciales se autentica con usuario y contraseña (RFC 1929).
+fn socks5_handshake(
+    stream: &mut TcpStream,
+    target: &[u8],
+    credentials: Option<(String, String)>,
+) -> Result<(), CustomError> {
+    let method = match credentials {
+        Some(_) => USERNAME_PASSWORD,
+        None => NO_AUTHENTICATION,
+    };
+    stream.write_all(&[SOCKS_VERSION, 1, method])?;
+
+    let mut response = [0; 2];
+    stream.read_exact(&mut response)?;
+    if response != [SOCKS_VERSION, method] {
+        return Err(CustomError::CannotConnectThroughProxy);
+    }
+
+    if let Some((username, password)) = credentials {
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend(username.as_bytes());
+        request.push(password.len() as u8);
+        request.extend(password.as_bytes());
+        stream.write_all(&request)?;
+
+        stream.read_exact(&mut response)?;
+        if response[1] != 0x00 {
+            return Err(CustomError::CannotConnectThroughProxy);
+        }
+    }
+
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0x00];
+    request.extend(target);
+    stream.write_all(&request)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != SOCKS_VERSION || reply[1] != 0x00 {
+        return Err(CustomError::CannotConnectThroughProxy);
+    }
+    let bound_address_len = match reply[3] {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        _ => return Err(CustomError::CannotConnectThroughProxy),
+    };
+    let mut bound_address = vec![0; bound_address_len + 2];
+    stream.read_exact(&mut bound_address)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    /// Proxy SOCKS5 de prueba que acepta una conexion, verifica el pedido y responde el mensaje recibido.
+    fn spawn_fake_proxy(expected_method: u8) -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [SOCKS_VERSION, 1, expected_method]);
+            stream.write_all(&[SOCKS_VERSION, expected_method]).unwrap();
+
+            if expected_method == USERNAME_PASSWORD {
+                let mut header = [0; 2];
+                stream.read_exact(&mut header).unwrap();
+                let mut username = vec![0; header[1] as usize + 1];
+                stream.read_exact(&mut username).unwrap();
+                let mut password = vec![0; username[header[1] as usize] as usize];
+                stream.read_exact(&mut password).unwrap();
+                stream.write_all(&[0x01, 0x00]).unwrap();
+            }
+
+            let mut request = [0; 10];
+            stream.read_exact(&mut request).unwrap();
+            stream
+                .write_all(&[SOCKS_VERSION, 0x00, 0x00, IPV4, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+
+            let mut message = [0; 4];
+            stream.read_exact(&mut message).unwrap();
+            stream.write_all(&message).unwrap();
+            request.to_vec()
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn proxy_connects_through_socks5() {
+        for (isolate, method) in [(false, NO_AUTHENTICATION), (true, USERNAME_PASSWORD)] {
+            let (address, handle) = spawn_fake_proxy(method);
+            let proxy = Proxy {
+                address: address.to_string(),
+                isolate,
+            };
+            let target = SocketAddr::from(([10, 0, 0, 1], 18333));
+
+            let mut stream = proxy.connect(target, Duration::from_secs(5)).unwrap();
+            stream.write_all(b"ping").unwrap();
+            let mut echo = [0; 4];
+            stream.read_exact(&mut echo).unwrap();
+            assert_eq!(&echo, b"ping");
+
+            let request = handle.join().unwrap();
+            assert_eq!(
+                request,
+                vec![SOCKS_VERSION, CONNECT, 0x00, IPV4, 10, 0, 0, 1, 0x47, 0x9d]
+            );
+        }
+    }
+
+    #[test]
+    fn domain_targets_are_resolved_by_the_proxy() {
+        assert_eq!(
+            domain_address("example.com", 80).unwrap(),
+            [&[DOMAIN, 11][..], b"example.com", &[0, 80]].concat()
+        );
+        assert!(domain_address("", 80).is_err());
+        assert!(domain_address(&"a".repeat(256), 80).is_err());
+    }
+
+    #[test]
+    fn proxy_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let proxy = Proxy {
+            address: address.to_string(),
+            isolate: false,
+        };
+        let target = SocketAddr::from(([10, 0, 0, 1], 18333));
+        assert!(matches!(
+            proxy.connect(target, Duration::from_millis(500)),
+            Err(CustomError::CannotConnectThroughProxy)
+        ));
+    }
+}
@@ -0,0 +1,324 @@
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    error::CustomError,
+    loops::{
+        node_action_loop::{NodeAction, NodeActionSender},
+        rest_api_loop::block_as_json,
+    },
+    messages::{block::Block, transaction::Transaction},
+    node_state::NodeState,
+    parser::BtcEncode,
+    structs::{hash32::Hash32, peer_info::PeerInfo},
+    utils::hex_to_bytes,
+    wallet::get_script_pubkey,
+};
+
+/// Comandos disponibles junto a sus parametros, como se muestran en la ayuda.
+const RPC_HELP: [&str; 13] = [
+    "generate <nblocks>",
+    "generatetoaddress <nblocks> <address>",
+    "getbestblockhash",
+    "getblock <hash>",
+    "getblockchaininfo",
+    "getblockcount",
+    "getblockhash <height>",
+    "getblocktemplate",
+    "getmempoolinfo",
+    "getpeerinfo",
+    "help",
+    "sendrawtransaction <hex>",
+    "submitblock <hex>",
+];
+
+#[derive(Debug)]
+/// RpcCommand representa los comandos estilo RPC de Bitcoin Core que acepta el nodo.
+/// Es la capa de comandos comun a la consola de la interfaz grafica y a un servidor JSON-RPC.
+/// Los comandos son:
+/// - Help: Lista los comandos disponibles.
+/// - Generate: Mina en la regtest la cantidad de bloques indicada, pagandole a la wallet activa.
+/// - GenerateToAddress: Mina en la regtest la cantidad de bloques indicada, pagandole al script pubkey de la direccion.
+/// - GetBestBlockHash: Hash del ultimo header de la cadena.
+/// - GetBlockCount: Altura del ultimo header de la cadena.
+/// - GetBlockHash: Hash del bloque a la altura recibida.
+/// - GetBlock: Bloque descargado con el hash recibido.
+/// - GetBlockchainInfo: Resumen del estado del nodo (ver NodeStatus).
+/// - GetBlockTemplate: Template del bloque siguiente para que lo mine un minero externo (BIP22).
+/// - GetPeerInfo: Datos de los peers conectados.
+/// - GetMempoolInfo: Cantidad de pending txs y la suma de sus tamaños virtuales.
+/// - SendRawTransaction: Agrega una transaccion serializada a la mempool y la envia a los peers.
+/// - SubmitBlock: Agrega a la cadena un bloque serializado minado por un minero externo y lo anuncia a los peers.
+pub enum RpcCommand {
+    Help,
+    Generate(u32),
+    GenerateToAddress(u32, Vec<u8>),
+    GetBestBlockHash,
+    GetBlockCount,
+    GetBlockHash(usize),
+    GetBlock(Hash32),
+    GetBlockchainInfo,
+    GetBlockTemplate,
+    GetPeerInfo,
+    GetMempoolInfo,
+    SendRawTransaction(Transaction),
+    SubmitBlock(Block),
+}
+
+impl RpcCommand {
+    /// Genera el comando a partir del nombre del metodo y sus parametros, como llegan en una consulta JSON-RPC.
+    /// Devuelve CustomError::Validation si el metodo no existe o los parametros no son validos.
+    pub fn from_method(method: &str, params: &[&str]) -> Result<Self, CustomError> {
+        let command = match (method, params) {
+            ("help", []) => Self::Help,
+            ("generate", [count]) => Self::Generate(parse_block_count(count)?),
+            ("generatetoaddress", [count, address]) => Self::GenerateToAddress(
+                parse_block_count(count)?,
+                get_script_pubkey(address.to_string())
+                    .map_err(|_| CustomError::Validation(format!("Invalid address: {address}")))?,
+            ),
+            ("getbestblockhash", []) => Self::GetBestBlockHash,
+            ("getblockcount", []) => Self::GetBlockCount,
+            ("getblockhash", [height]) => {
+                Self::GetBlockHash(height.parse().map_err(|_| {
+                    CustomError::Validation(format!("Invalid block height: {height}"))
+                })?)
+            }
+            ("getblock", [hash]) => Self::GetBlock(
+                Hash32::from_str(hash)
+                    .map_err(|_| CustomError::Validation(format!("Invalid block hash: {hash}")))?,
+            ),
+            ("getblockchaininfo", []) => Self::GetBlockchainInfo,
+            ("getblocktemplate", []) => Self::GetBlockTemplate,
+            ("getpeerinfo", []) => Self::GetPeerInfo,
+            ("getmempoolinfo", []) => Self::GetMempoolInfo,
+            ("sendrawtransaction", [hex]) => {
+                let transaction = hex_to_bytes(hex)
+                    .and_then(Transaction::parse)
+                    .map_err(|_| {
+                        CustomError::Validation(String::from("Invalid transaction hex"))
+                    })?;
+                Self::SendRawTransaction(transaction)
+            }
+            ("submitblock", [hex]) => {
+                let block = hex_to_bytes(hex).and_then(Block::parse).map_err(|error| {
+                    CustomError::Validation(format!("Block decode failed: {error}"))
+                })?;
+                Self::SubmitBlock(block)
+            }
+            _ => {
+                return Err(CustomError::Validation(
+                    match RPC_HELP
+                        .iter()
+                        .find(|usage| usage.split(' ').next() == Some(method))
+                    {
+                        Some(usage) => format!("Invalid parameters, usage: {usage}"),
+                        None => format!("Unknown command: {method}, use help to list the commands"),
+                    },
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+
+impl FromStr for RpcCommand {
+    type Err = CustomError;
+
+    /// Parsea una linea de la consola: el nombre del comando seguido de sus parametros separados por espacios.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let method = words
+            .next()
+            .ok_or_else(|| CustomError::Validation(String::from("Empty command")))?;
+        let params: Vec<&str> = words.collect();
+        Self::from_method(method, &params)
+    }
+}
+
+#[derive(Clone)]
+/// RpcHandler ejecuta los RpcCommand sobre el estado del nodo y devuelve su resultado en JSON.
+/// Los elementos son:
+/// - node_state_ref: Referencia al estado del nodo.
+/// - node_action_sender: Sender para enviar acciones al nodo, por ejemplo las transacciones a retransmitir.
+pub struct RpcHandler {
+    pub node_state_ref: Arc<NodeState>,
+    pub node_action_sender: NodeActionSender,
+}
+
+impl RpcHandler {
+    /// Parsea una linea de la consola y ejecuta el comando.
+    pub fn execute_line(&self, line: &str) -> Result<String, CustomError> {
+        self.execute(line.parse()?)
+    }
+
+    /// Ejecuta el comando y devuelve su resultado en JSON.
+    /// Devuelve CustomError::Validation si el comando no se puede realizar, por ejemplo si el bloque no se descargo.
+    pub fn execute(&self, command: RpcCommand) -> Result<String, CustomError> {
+        let node_state = &self.node_state_ref;
+
+        let result = match command {
+            RpcCommand::Help => RPC_HELP.join("\n"),
+            RpcCommand::Generate(count) => {
+                let wallet = node_state.get_active_wallet()?.ok_or_else(|| {
+                    CustomError::Validation(String::from("There is no active wallet to pay to"))
+                })?;
+                self.generate_blocks(count, wallet.get_script_pubkey()?)?
+            }
+            RpcCommand::GenerateToAddress(count, script_pubkey) => {
+                self.generate_blocks(count, script_pubkey)?
+            }
+            RpcCommand::GetBestBlockHash => match node_state.get_last_header_hash()? {
+                Some(hash) => format!("\"{hash}\""),
+                None => String::from("null"),
+            },
+            RpcCommand::GetBlockCount => match node_state.get_last_headers(1)?.first() {
+                Some((height, _)) => height.to_string(),
+                None => String::from("0"),
+            },
+            RpcCommand::GetBlockHash(height) => {
+                match node_state.get_block_hash_by_height(height)? {
+                    Some(hash) => format!("\"{hash}\""),
+                    None => {
+                        return Err(CustomError::Validation(String::from(
+                            "Block height out of range",
+                        )))
+                    }
+                }
+            }
+            RpcCommand::GetBlock(hash) => {
+                match node_state.get_headers_from(&hash, 1)? {
+                    Some(headers) if headers[0].1.block_downloaded => {}
+                    _ => return Err(CustomError::Validation(String::from("Block not found"))),
+                }
+                block_as_json(&node_state.get_block(&hash)?)
+            }
+            RpcCommand::GetBlockchainInfo => node_state.get_status()?.to_json(),
+            RpcCommand::GetBlockTemplate => node_state.get_block_template()?.to_json(),
+            RpcCommand::GetPeerInfo => {
+                let peers: Vec<String> = node_state
+                    .get_peers_info()?
+                    .iter()
+                    .map(peer_info_as_json)
+                    .collect();
+                format!("[{}]", peers.join(","))
+            }
+            RpcCommand::GetMempoolInfo => {
+                let (size, bytes) = node_state.get_mempool_info()?;
+                format!("{{\"size\":{size},\"bytes\":{bytes}}}")
+            }
+            RpcCommand::SendRawTransaction(transaction) => {
+                if transaction.check_standard().is_err() {
+                    return Err(CustomError::Validation(String::from(
+                        "Transaction is not standard",
+                    )));
+                }
+                let tx_hash = transaction.hash();
+                self.node_action_sender
+                    .try_send(NodeAction::SendRawTransaction(transaction))?;
+                format!("\"{tx_hash}\"")
+            }
+            // al igual que Bitcoin Core, se devuelve null si se acepta el bloque y si no el motivo del rechazo
+            RpcCommand::SubmitBlock(block) => match node_state.submit_block(&block) {
+                Ok(()) => {
+                    self.node_action_sender
+                        .try_send(NodeAction::LocalBlock(block))?;
+                    String::from("null")
+                }
+                Err(CustomError::Validation(reason)) => format!("\"{reason}\""),
+                Err(error) => return Err(error),
+            },
+        };
+
+        Ok(result)
+    }
+
+    /// Mina los bloques de a uno, con la coinbase pagandole al script recibido, y los anuncia a los peers.
+    /// Devuelve los hashes de los bloques minados en JSON.
+    fn generate_blocks(&self, count: u32, script_pubkey: Vec<u8>) -> Result<String, CustomError> {
+        let mut block_hashes = vec![];
+        for _ in 0..count {
+            let block = self.node_state_ref.generate_block(script_pubkey.clone())?;
+            block_hashes.push(format!("\"{}\"", block.header.hash()));
+            self.node_action_sender
+                .try_send(NodeAction::LocalBlock(block))?;
+        }
+        Ok(format!("[{}]", block_hashes.join(",")))
+    }
+}
+
+/// Devuelve el mensaje de error que se muestra al usuario como resultado de un comando,
+/// con la explicacion en el caso de CustomError::Validation.
+pub fn error_message(error: &CustomError) -> String {
+    match error {
+        CustomError::Validation(explanation) => format!("Error: {explanation}"),
+        error => error.to_string(),
+    }
+}
+
+/// Parsea la cantidad de bloques a minar de los comandos generate.
+fn parse_block_count(count: &str) -> Result<u32, CustomError> {
+    count
+        .parse()
+        .map_err(|_| CustomError::Validation(format!("Invalid number of blocks: {count}")))
+}
+
+fn peer_info_as_json(peer: &PeerInfo) -> String {
+    format!(
+        "{{\"addr\":\"{}\",\"subver\":\"{}\",\"version\":{},\"pingtime\":{},\"bytesrecv\":{},\"bytessent\":{},\"inbound\":{},\"banscore\":{},\"conntime\":{}}}",
+        peer.address,
+        peer.user_agent.replace('\\', "\\\\").replace('"', "\\\""),
+        peer.version,
+        peer.latency,
+        peer.bytes_received,
+        peer.bytes_sent,
+        peer.inbound,
+        peer.misbehavior,
+        peer.connected_at
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "000000000000000ba6f3ca57e4c5f95c6a7c2bb2e3b0c0d2c5e2b3b1a4c8d9e0";
+
+    #[test]
+    fn parse_console_commands() {
+        assert!(matches!("help".parse(), Ok(RpcCommand::Help)));
+        assert!(matches!(
+            "  getpeerinfo ".parse(),
+            Ok(RpcCommand::GetPeerInfo)
+        ));
+        assert!(matches!(
+            format!("getblock {HASH}").parse(),
+            Ok(RpcCommand::GetBlock(hash)) if hash == Hash32::from_str(HASH).unwrap()
+        ));
+        assert!(matches!(
+            "getblockchaininfo".parse(),
+            Ok(RpcCommand::GetBlockchainInfo)
+        ));
+        assert!(matches!(
+            "getblockhash 101".parse(),
+            Ok(RpcCommand::GetBlockHash(101))
+        ));
+        assert!(matches!(
+            "generatetoaddress 101 mscatccDgq7azndWHFTzvEuZuywCsUvTRu".parse(),
+            Ok(RpcCommand::GenerateToAddress(101, script_pubkey)) if script_pubkey.len() == 25
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_console_commands() {
+        assert!("".parse::<RpcCommand>().is_err());
+        assert!("getblock".parse::<RpcCommand>().is_err());
+        assert!("getblock 00".parse::<RpcCommand>().is_err());
+        assert!("getpeerinfo 1".parse::<RpcCommand>().is_err());
+        assert!("getblockhash -1".parse::<RpcCommand>().is_err());
+        assert!("stop".parse::<RpcCommand>().is_err());
+        assert!("generate -1".parse::<RpcCommand>().is_err());
+        assert!("sendrawtransaction 0".parse::<RpcCommand>().is_err());
+        assert!("sendrawtransaction zz".parse::<RpcCommand>().is_err());
+        assert!("submitblock 0000".parse::<RpcCommand>().is_err());
+    }
+}
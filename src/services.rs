@@ -0,0 +1,77 @@
+use crate::error::CustomError;
+
+/// Bit de servicios que indica que el nodo sirve todos los bloques de la cadena.
+pub const NODE_NETWORK: u64 = 1;
+/// Bit de servicios que indica que el nodo sirve transacciones y bloques con sus witness (BIP144).
+pub const NODE_WITNESS: u64 = 1 << 3;
+/// Bit de servicios que indica que el nodo sirve filtros de bloques (BIP157).
+pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
+/// Bit de servicios que indica que el nodo sirve solo los ultimos NODE_NETWORK_LIMITED_BLOCKS bloques (BIP159).
+pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
+
+/// Cantidad de bloques desde el ultimo que sirve un nodo con NODE_NETWORK_LIMITED.
+pub const NODE_NETWORK_LIMITED_BLOCKS: usize = 288;
+
+/// Servicios que anuncia por defecto un nodo completo.
+/// No se anuncia NODE_NETWORK ya que solo se descargan los bloques posteriores a START_DATE_IBD.
+pub const DEFAULT_SERVICES: u64 = NODE_NETWORK_LIMITED | NODE_WITNESS | NODE_COMPACT_FILTERS;
+
+/// Nombres de los servicios configurables, como se escriben en la clave SERVICES del archivo de configuracion.
+const SERVICE_NAMES: [(&str, u64); 4] = [
+    ("NODE_NETWORK", NODE_NETWORK),
+    ("NODE_WITNESS", NODE_WITNESS),
+    ("NODE_COMPACT_FILTERS", NODE_COMPACT_FILTERS),
+    ("NODE_NETWORK_LIMITED", NODE_NETWORK_LIMITED),
+];
+
+/// Indica si los servicios que anuncia el nodo incluyen el servicio recibido, en cuyo caso debe atender los pedidos correspondientes.
+pub fn offers_service(services: u64, service: u64) -> bool {
+    services & service != 0
+}
+
+/// Indica si un nodo con los servicios recibidos sirve un bloque que esta depth bloques por debajo del ultimo (0 es el ultimo).
+/// Con NODE_NETWORK se sirven todos, con NODE_NETWORK_LIMITED solo los ultimos NODE_NETWORK_LIMITED_BLOCKS.
+pub fn serves_block_at_depth(services: u64, depth: usize) -> bool {
+    offers_service(services, NODE_NETWORK)
+        || (offers_service(services, NODE_NETWORK_LIMITED) && depth < NODE_NETWORK_LIMITED_BLOCKS)
+}
+
+/// Parsea una lista de servicios separados por coma (ej: NODE_NETWORK,NODE_WITNESS) y devuelve sus bits.
+/// Una lista vacia indica que no se ofrece ningun servicio.
+/// Devuelve CustomError si alguno de los nombres no es un servicio configurable.
+pub fn parse_services(value: &str) -> Result<u64, CustomError> {
+    let mut services = 0;
+    for name in value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let Some((_, service)) = SERVICE_NAMES
+            .iter()
+            .find(|(service_name, _)| *service_name == name)
+        else {
+            return Err(CustomError::ConfigErrorReadingValue);
+        };
+        services |= service;
+    }
+    Ok(services)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_list_of_services() {
+        assert_eq!(
+            parse_services("NODE_NETWORK, NODE_WITNESS").unwrap(),
+            NODE_NETWORK | NODE_WITNESS
+        );
+        assert_eq!(
+            parse_services("NODE_NETWORK_LIMITED,NODE_COMPACT_FILTERS").unwrap(),
+            NODE_NETWORK_LIMITED | NODE_COMPACT_FILTERS
+        );
+        assert_eq!(parse_services("").unwrap(), 0);
+        assert!(parse_services("NODE_BLOOM").is_err());
+    }
+}
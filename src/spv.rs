@@ -0,0 +1,39 @@
+use crate::{
+    error::CustomError,
+    messages::filter_load::FilterLoad,
+    structs::{
+        bloom_filter::BloomFilter,
+        hash32::Hash32,
+        inventory::{Inventory, InventoryType},
+    },
+    utils::get_random_nonce,
+    wallet::Wallet,
+};
+
+/// Bit de servicios que indica que el peer acepta bloom filters (BIP111).
+pub const NODE_BLOOM: u64 = 1 << 2;
+
+/// Tasa de falsos positivos del filtro de las wallets.
+const FILTER_FP_RATE: f64 = 0.0001;
+
+/// Devuelve el inventario con el que se pide un bloque, filtrado si el nodo es un cliente SPV (spv en true),
+/// que solo descarga headers y los merkleblocks de las transacciones de sus wallets.
+/// Los nodos completos lo piden con los witness, para poder servirlo a otros nodos segwit.
+pub fn block_inventory(block_hash: Hash32, spv: bool) -> Inventory {
+    match spv {
+        true => Inventory::new(InventoryType::FilteredBlock, block_hash),
+        false => Inventory::new(InventoryType::WitnessBlock, block_hash),
+    }
+}
+
+/// Arma el mensaje 'filterload' con el que se le pide a los peers solo las transacciones de las wallets.
+/// El filtro contiene el hash y la public key de cada wallet, para encontrar tanto las salidas que reciben
+/// como las entradas que las gastan.
+pub fn wallets_filter(wallets: &[Wallet]) -> Result<FilterLoad, CustomError> {
+    let mut filter = BloomFilter::new(wallets.len() * 2, FILTER_FP_RATE, get_random_nonce() as u32);
+    for wallet in wallets {
+        filter.insert(&wallet.get_pubkey_hash()?);
+        filter.insert(&wallet.get_public_key()?);
+    }
+    Ok(FilterLoad::new(filter))
+}
@@ -0,0 +1,296 @@
+use std::{collections::HashMap, fs, io::Read, sync::mpsc::Sender};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log, LogTarget},
+    parser::BufferParser,
+    structs::hash32::Hash32,
+    utils::{open_new_file, write_file_atomically},
+    wallet::get_script_pubkey,
+};
+
+use super::store_file::{seal_store_file, StoreFile, StoreFileKind};
+
+/// Tamaño maximo en bytes de una etiqueta, ya que su largo se guarda en un byte.
+pub const MAX_LABEL_SIZE: usize = 255;
+
+/// AddressBookState es la libreta de direcciones de las wallets: etiquetas para las direcciones a las que se envia
+/// y para los movimientos del historial, que se muestran en lugar de las direcciones y los hashes.
+/// Se guarda completa con cada cambio, ya que solo cambia cuando el usuario edita una etiqueta o envia una transaccion.
+/// Los elementos son:
+/// - contacts: Etiqueta de cada direccion, por direccion. Las etiquetas no se repiten.
+/// - movement_labels: Etiqueta de cada movimiento, por el hash de su transaccion.
+/// - path: Path del archivo donde se guarda la libreta de direcciones.
+pub struct AddressBookState {
+    contacts: HashMap<String, String>,
+    movement_labels: HashMap<Hash32, String>,
+    path: String,
+}
+
+impl AddressBookState {
+    /// Inicializa la libreta de direcciones.
+    /// Si el archivo donde se guarda no existe, se crea.
+    /// Si el archivo esta corrupto se conserva una copia con la extension .corrupt y se inicia una libreta vacia.
+    pub fn new(path: String, logger_sender: Sender<Log>) -> Result<Self, CustomError> {
+        let mut address_book = Self {
+            contacts: HashMap::new(),
+            movement_labels: HashMap::new(),
+            path,
+        };
+        if address_book.restore().is_err() {
+            let backup_path = format!("{}.corrupt", address_book.path);
+            fs::copy(&address_book.path, &backup_path)?;
+            send_log(
+                &logger_sender,
+                Log::Warn(
+                    LogTarget::Wallet,
+                    format!("Address book file is corrupt, saved a copy to {backup_path}"),
+                ),
+            );
+            address_book.contacts.clear();
+            address_book.movement_labels.clear();
+            address_book.save()?;
+        }
+        Ok(address_book)
+    }
+
+    fn restore(&mut self) -> Result<(), CustomError> {
+        let mut file = open_new_file(self.path.clone(), false)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        let saved = StoreFile::parse(StoreFileKind::AddressBook, buffer)?;
+        if !saved.intact {
+            return Err(CustomError::StoreFileCorrupt);
+        }
+        let mut parser = BufferParser::new(saved.contents);
+        if parser.is_empty() {
+            return Ok(());
+        }
+
+        for _ in 0..parser.extract_u32()? {
+            let address = extract_label(&mut parser)?;
+            self.contacts.insert(address, extract_label(&mut parser)?);
+        }
+        for _ in 0..parser.extract_u32()? {
+            let tx_hash = Hash32::from_slice(parser.extract_buffer(32)?)?;
+            self.movement_labels
+                .insert(tx_hash, extract_label(&mut parser)?);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), CustomError> {
+        let mut buffer = vec![];
+        buffer.extend((self.contacts.len() as u32).to_le_bytes());
+        for (address, label) in &self.contacts {
+            append_label(&mut buffer, address);
+            append_label(&mut buffer, label);
+        }
+        buffer.extend((self.movement_labels.len() as u32).to_le_bytes());
+        for (tx_hash, label) in &self.movement_labels {
+            buffer.extend(tx_hash.as_bytes());
+            append_label(&mut buffer, label);
+        }
+
+        write_file_atomically(
+            &self.path,
+            &seal_store_file(StoreFileKind::AddressBook, &buffer),
+        )
+    }
+
+    /// Devuelve las etiquetas y direcciones de la libreta, ordenadas por etiqueta.
+    pub fn get_contacts(&self) -> Vec<(String, String)> {
+        let mut contacts: Vec<(String, String)> = self
+            .contacts
+            .iter()
+            .map(|(address, label)| (label.clone(), address.clone()))
+            .collect();
+        contacts.sort();
+        contacts
+    }
+
+    /// Agrega una direccion a la libreta con su etiqueta, o le cambia la etiqueta si ya estaba.
+    /// Devuelve CustomError::Validation si la direccion es invalida, si la etiqueta esta vacia o es demasiado larga,
+    /// o si la etiqueta ya es de otra direccion.
+    pub fn set_contact(&mut self, address: String, label: String) -> Result<(), CustomError> {
+        let label = validate_label(label)?;
+        if get_script_pubkey(address.clone()).is_err() {
+            return Err(CustomError::Validation(format!(
+                "Invalid address: {address}"
+            )));
+        }
+        if self.resolve(&label).is_some_and(|other| other != address) {
+            return Err(CustomError::Validation(format!(
+                "The label {label} is already used by another address"
+            )));
+        }
+        self.contacts.insert(address, label);
+        self.save()
+    }
+
+    /// Quita una direccion de la libreta. Devuelve false si no estaba.
+    pub fn remove_contact(&mut self, address: &str) -> Result<bool, CustomError> {
+        if self.contacts.remove(address).is_none() {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Devuelve la etiqueta de una direccion, si esta en la libreta.
+    pub fn get_contact_label(&self, address: &str) -> Option<String> {
+        self.contacts.get(address).cloned()
+    }
+
+    /// Devuelve la direccion con la etiqueta recibida, si hay alguna.
+    pub fn resolve(&self, label: &str) -> Option<String> {
+        self.contacts
+            .iter()
+            .find(|(_, contact_label)| contact_label.as_str() == label)
+            .map(|(address, _)| address.clone())
+    }
+
+    /// Establece la etiqueta del movimiento de la transaccion recibida, o la quita si la etiqueta esta vacia.
+    /// Devuelve CustomError::Validation si la etiqueta es demasiado larga.
+    pub fn set_movement_label(
+        &mut self,
+        tx_hash: Hash32,
+        label: String,
+    ) -> Result<(), CustomError> {
+        match label.trim().is_empty() {
+            true => self.movement_labels.remove(&tx_hash),
+            false => self.movement_labels.insert(tx_hash, validate_label(label)?),
+        };
+        self.save()
+    }
+
+    /// Devuelve la etiqueta del movimiento de la transaccion recibida, si tiene.
+    pub fn get_movement_label(&self, tx_hash: &Hash32) -> Option<String> {
+        self.movement_labels.get(tx_hash).cloned()
+    }
+
+    /// Devuelve las etiquetas de todos los movimientos, con el hash de su transaccion.
+    pub fn get_movement_labels(&self) -> Vec<(Hash32, String)> {
+        self.movement_labels
+            .iter()
+            .map(|(tx_hash, label)| (*tx_hash, label.clone()))
+            .collect()
+    }
+
+    /// Agrega los contactos y las etiquetas de movimientos recibidos, por ejemplo de un backup, sin pisar los existentes:
+    /// se ignoran las direcciones y los movimientos que ya tienen etiqueta, las etiquetas que ya son de otra direccion
+    /// y las direcciones o etiquetas invalidas. Devuelve la cantidad de contactos agregados.
+    pub fn merge(
+        &mut self,
+        contacts: Vec<(String, String)>,
+        movement_labels: Vec<(Hash32, String)>,
+    ) -> Result<usize, CustomError> {
+        let mut added = 0;
+        for (label, address) in contacts {
+            let Ok(label) = validate_label(label) else {
+                continue;
+            };
+            if self.contacts.contains_key(&address)
+                || self.resolve(&label).is_some()
+                || get_script_pubkey(address.clone()).is_err()
+            {
+                continue;
+            }
+            self.contacts.insert(address, label);
+            added += 1;
+        }
+        for (tx_hash, label) in movement_labels {
+            if let Ok(label) = validate_label(label) {
+                self.movement_labels.entry(tx_hash).or_insert(label);
+            }
+        }
+        self.save()?;
+        Ok(added)
+    }
+}
+
+/// Devuelve la etiqueta sin los espacios de los extremos.
+/// Devuelve CustomError::Validation si queda vacia o si supera MAX_LABEL_SIZE bytes.
+fn validate_label(label: String) -> Result<String, CustomError> {
+    let label = label.trim();
+    if label.is_empty() || label.len() > MAX_LABEL_SIZE {
+        return Err(CustomError::Validation(format!(
+            "Labels must have between 1 and {MAX_LABEL_SIZE} bytes"
+        )));
+    }
+    Ok(label.to_string())
+}
+
+/// Serializa una etiqueta precedida por su largo en un byte.
+pub fn append_label(buffer: &mut Vec<u8>, label: &str) {
+    buffer.push(label.len() as u8);
+    buffer.extend(label.as_bytes());
+}
+
+/// Deserializa una etiqueta serializada con append_label.
+pub fn extract_label(parser: &mut BufferParser) -> Result<String, CustomError> {
+    let length = parser.extract_u8()? as usize;
+    parser.extract_string(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_file, sync::mpsc};
+
+    use super::*;
+
+    const ADDRESS: &str = "mscatccDgq7azndWHFTzvEuZuywCsUvTRu";
+    const OTHER_ADDRESS: &str = "mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm";
+
+    #[test]
+    fn address_book_is_restored() -> Result<(), CustomError> {
+        let path = String::from("tests/test_address_book_state.bin");
+        let (logger_sender, _logger_receiver) = mpsc::channel();
+        let tx_hash = Hash32::from_slice(&[7; 32])?;
+
+        let mut address_book = AddressBookState::new(path.clone(), logger_sender.clone())?;
+        address_book.set_contact(ADDRESS.to_string(), String::from(" Exchange "))?;
+        address_book.set_contact(OTHER_ADDRESS.to_string(), String::from("Alice"))?;
+        address_book.set_movement_label(tx_hash, String::from("Exchange withdrawal"))?;
+
+        let restored = AddressBookState::new(path.clone(), logger_sender)?;
+        remove_file(path)?;
+
+        assert_eq!(
+            restored.get_contacts(),
+            vec![
+                (String::from("Alice"), OTHER_ADDRESS.to_string()),
+                (String::from("Exchange"), ADDRESS.to_string())
+            ]
+        );
+        assert_eq!(restored.resolve("Exchange"), Some(ADDRESS.to_string()));
+        assert_eq!(
+            restored.get_movement_label(&tx_hash),
+            Some(String::from("Exchange withdrawal"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn address_book_rejects_invalid_contacts() -> Result<(), CustomError> {
+        let path = String::from("tests/test_address_book_invalid.bin");
+        let (logger_sender, _logger_receiver) = mpsc::channel();
+
+        let mut address_book = AddressBookState::new(path.clone(), logger_sender)?;
+        address_book.set_contact(ADDRESS.to_string(), String::from("Exchange"))?;
+        let duplicated_label =
+            address_book.set_contact(OTHER_ADDRESS.to_string(), String::from("Exchange"));
+        let empty_label = address_book.set_contact(OTHER_ADDRESS.to_string(), String::from(" "));
+        let invalid_address = address_book.set_contact(String::from("0"), String::from("Bob"));
+        address_book.set_contact(ADDRESS.to_string(), String::from("Savings"))?;
+        let removed = address_book.remove_contact(ADDRESS)?;
+        remove_file(path)?;
+
+        assert!(duplicated_label.is_err());
+        assert!(empty_label.is_err());
+        assert!(invalid_address.is_err());
+        assert!(removed);
+        assert!(address_book.get_contacts().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::SocketAddrV6,
+};
+
+use crate::{error::CustomError, parser::BufferParser, utils::open_new_file};
+
+/// Cantidad maxima de direcciones nuevas (nunca conectadas) que se guardan.
+const MAX_NEW_ADDRESSES: usize = 2000;
+/// Intentos fallidos a partir de los cuales una direccion no se comparte con otros peers.
+const MAX_SHARED_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+/// KnownAddress representa una direccion de un peer conocida por el nodo.
+/// Los elementos son:
+/// - address: Direccion del peer.
+/// - services: Servicios que anuncio el peer.
+/// - last_seen: Timestamp en segundos de la ultima vez que se supo de la direccion.
+/// - last_success: Timestamp en segundos de la ultima conexion exitosa, 0 si nunca se conecto.
+/// - attempts: Intentos fallidos de conexion desde la ultima conexion exitosa.
+/// - tried: Indica si la direccion esta en el bucket tried (alguna vez se conecto) o en el new.
+pub struct KnownAddress {
+    pub address: SocketAddrV6,
+    pub services: u64,
+    pub last_seen: u64,
+    pub last_success: u64,
+    pub attempts: u32,
+    pub tried: bool,
+}
+
+impl KnownAddress {
+    /// Serializa la direccion conocida, la direccion se serializa igual que en los mensajes del protocolo.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = self.address.ip().octets().to_vec();
+        buffer.extend(self.address.port().to_be_bytes());
+        buffer.extend(self.services.to_le_bytes());
+        buffer.extend(self.last_seen.to_le_bytes());
+        buffer.extend(self.last_success.to_le_bytes());
+        buffer.extend(self.attempts.to_le_bytes());
+        buffer.push(self.tried as u8);
+        buffer
+    }
+
+    /// Deserializa una direccion conocida.
+    pub fn parse(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        Ok(Self {
+            address: parser.extract_address()?,
+            services: parser.extract_u64()?,
+            last_seen: parser.extract_u64()?,
+            last_success: parser.extract_u64()?,
+            attempts: parser.extract_u32()?,
+            tried: parser.extract_u8()? != 0,
+        })
+    }
+}
+
+/// AddressesState es el address manager del nodo, registra las direcciones de peers conocidas para poder
+/// conectarse a ellas al reiniciar el nodo sin depender solo de la semilla DNS.
+/// Las direcciones se dividen en dos buckets:
+/// - new: direcciones aprendidas a las que nunca nos conectamos.
+/// - tried: direcciones a las que alguna vez nos conectamos con exito.
+///
+/// Los elementos son:
+/// - addresses: Direcciones conocidas.
+/// - path: Path del archivo donde se guardan las direcciones.
+pub struct AddressesState {
+    addresses: HashMap<SocketAddrV6, KnownAddress>,
+    path: String,
+}
+
+impl AddressesState {
+    /// Inicializa el address manager.
+    /// Si el archivo donde se guardan las direcciones no existe, se crea.
+    /// Si el archivo existe, se restauran las direcciones.
+    pub fn new(path: String) -> Result<Self, CustomError> {
+        let mut addresses = Self {
+            addresses: HashMap::new(),
+            path,
+        };
+        addresses.restore()?;
+        Ok(addresses)
+    }
+
+    fn restore(&mut self) -> Result<(), CustomError> {
+        let mut file = open_new_file(self.path.clone(), false)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        let mut parser = BufferParser::new(buffer);
+
+        while !parser.is_empty() {
+            let known_address = KnownAddress::parse(&mut parser)?;
+            self.addresses.insert(known_address.address, known_address);
+        }
+        Ok(())
+    }
+
+    /// Guarda las direcciones conocidas y fuerza su escritura en disco.
+    pub fn flush(&self) -> Result<(), CustomError> {
+        let mut buffer = vec![];
+        for known_address in self.addresses.values() {
+            buffer.append(&mut known_address.serialize());
+        }
+
+        let mut file = open_new_file(self.path.clone(), false)?;
+        file.set_len(0)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Registra una direccion aprendida en el bucket new, o actualiza cuando se supo de ella si ya era conocida.
+    /// Si el bucket new esta lleno, se descarta la direccion nueva que hace mas tiempo no se ve.
+    pub fn add(&mut self, address: SocketAddrV6, services: u64, timestamp: u64) {
+        if let Some(known_address) = self.addresses.get_mut(&address) {
+            known_address.last_seen = known_address.last_seen.max(timestamp);
+            known_address.services |= services;
+            return;
+        }
+
+        if self.new_addresses().count() >= MAX_NEW_ADDRESSES {
+            let oldest = self
+                .new_addresses()
+                .min_by_key(|known_address| known_address.last_seen)
+                .map(|known_address| known_address.address);
+            if let Some(oldest) = oldest {
+                self.addresses.remove(&oldest);
+            }
+        }
+
+        self.addresses.insert(
+            address,
+            KnownAddress {
+                address,
+                services,
+                last_seen: timestamp,
+                last_success: 0,
+                attempts: 0,
+                tried: false,
+            },
+        );
+    }
+
+    /// Registra una conexion exitosa con la direccion, moviendola al bucket tried.
+    pub fn mark_good(&mut self, address: SocketAddrV6, timestamp: u64) {
+        self.add(address, 0, timestamp);
+        if let Some(known_address) = self.addresses.get_mut(&address) {
+            known_address.last_success = timestamp;
+            known_address.attempts = 0;
+            known_address.tried = true;
+        }
+    }
+
+    /// Registra un intento fallido de conexion con la direccion.
+    pub fn mark_attempt(&mut self, address: SocketAddrV6) {
+        if let Some(known_address) = self.addresses.get_mut(&address) {
+            known_address.attempts += 1;
+        }
+    }
+
+    /// Devuelve las direcciones conocidas en el orden en que conviene intentar conectarse:
+    /// primero las del bucket tried y luego las del new, cada una ordenada por menos intentos fallidos y mas recientes.
+    pub fn get_candidates(&self) -> Vec<SocketAddrV6> {
+        let mut candidates: Vec<&KnownAddress> = self.addresses.values().collect();
+        candidates.sort_by_key(|known_address| {
+            (
+                !known_address.tried,
+                known_address.attempts,
+                u64::MAX - known_address.last_success.max(known_address.last_seen),
+            )
+        });
+        candidates
+            .iter()
+            .map(|known_address| known_address.address)
+            .collect()
+    }
+
+    /// Devuelve hasta count direcciones para compartir con otros peers, las vistas mas recientemente.
+    /// Se descartan las direcciones con varios intentos fallidos de conexion desde la ultima exitosa.
+    pub fn get_sample(&self, count: usize) -> Vec<KnownAddress> {
+        let mut sample: Vec<&KnownAddress> = self
+            .addresses
+            .values()
+            .filter(|known_address| known_address.attempts < MAX_SHARED_ATTEMPTS)
+            .collect();
+        sample.sort_by_key(|known_address| u64::MAX - known_address.last_seen);
+        sample.into_iter().take(count).cloned().collect()
+    }
+
+    /// Devuelve la direccion conocida, si existe.
+    pub fn get(&self, address: &SocketAddrV6) -> Option<&KnownAddress> {
+        self.addresses.get(address)
+    }
+
+    fn new_addresses(&self) -> impl Iterator<Item = &KnownAddress> {
+        self.addresses
+            .values()
+            .filter(|known_address| !known_address.tried)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use super::*;
+
+    #[test]
+    fn addresses_state_buckets_and_persistence() -> Result<(), CustomError> {
+        let path = String::from("tests/test_addresses_state.bin");
+        let first: SocketAddrV6 = "[::ffff:10.0.0.1]:18333".parse().unwrap();
+        let second: SocketAddrV6 = "[::ffff:10.0.0.2]:18333".parse().unwrap();
+        let third: SocketAddrV6 = "[::ffff:10.0.0.3]:18333".parse().unwrap();
+
+        let mut addresses = AddressesState::new(path.clone())?;
+        addresses.add(first, 1, 100);
+        addresses.add(second, 1, 200);
+        addresses.add(third, 1, 300);
+        addresses.mark_good(first, 400);
+        addresses.mark_attempt(third);
+        assert_eq!(addresses.get_candidates(), vec![first, second, third]);
+        addresses.flush()?;
+
+        let restored = AddressesState::new(path.clone())?;
+        assert_eq!(restored.get_candidates(), vec![first, second, third]);
+        assert_eq!(restored.get(&first), addresses.get(&first));
+        assert!(restored.get(&first).is_some_and(|known| known.tried));
+
+        addresses.mark_attempt(third);
+        addresses.mark_attempt(third);
+        let sample = addresses.get_sample(5);
+        assert_eq!(sample.len(), 2);
+        assert_eq!(sample[0].address, first);
+        assert_eq!(addresses.get_sample(1).len(), 1);
+
+        remove_file(path)?;
+        Ok(())
+    }
+}
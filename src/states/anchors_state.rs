@@ -0,0 +1,99 @@
+use std::{
+    io::{Read, Write},
+    net::SocketAddrV6,
+};
+
+use crate::{error::CustomError, parser::BufferParser, utils::open_new_file};
+
+/// Cantidad maxima de peers ancla que se guardan al cerrar el nodo.
+pub const MAX_ANCHORS: usize = 2;
+/// Segundos que tiene que haber estado conectado un peer saliente para ser guardado como ancla.
+pub const ANCHOR_MIN_UPTIME: u64 = 10 * 60;
+
+/// AnchorsState contiene los peers ancla: peers salientes con los que el nodo estuvo conectado mucho tiempo
+/// antes de cerrarse, a los que se intenta reconectar primero al iniciar.
+/// Reconectarse a peers conocidos dificulta que un atacante rodee al nodo al reiniciar (eclipse attack).
+/// Al iniciar se leen y se vacia el archivo, para no volver a usar las mismas anclas si el nodo se cierra inesperadamente.
+/// Los elementos son:
+/// - anchors: Direcciones de los peers ancla leidas al iniciar, se entregan una unica vez.
+/// - path: Path del archivo donde se guardan los peers ancla.
+pub struct AnchorsState {
+    anchors: Vec<SocketAddrV6>,
+    path: String,
+}
+
+impl AnchorsState {
+    /// Inicializa los peers ancla.
+    /// Si el archivo donde se guardan no existe, se crea.
+    /// Si el archivo existe, se restauran los peers ancla y se vacia el archivo.
+    pub fn new(path: String) -> Result<Self, CustomError> {
+        let mut anchors = Self {
+            anchors: vec![],
+            path,
+        };
+        anchors.restore()?;
+        Ok(anchors)
+    }
+
+    fn restore(&mut self) -> Result<(), CustomError> {
+        let mut file = open_new_file(self.path.clone(), false)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        file.set_len(0)?;
+        let mut parser = BufferParser::new(buffer);
+
+        while !parser.is_empty() {
+            self.anchors.push(parser.extract_address()?);
+        }
+        Ok(())
+    }
+
+    /// Devuelve los peers ancla leidos al iniciar, las siguientes llamadas devuelven un vector vacio.
+    pub fn take(&mut self) -> Vec<SocketAddrV6> {
+        std::mem::take(&mut self.anchors)
+    }
+
+    /// Guarda las direcciones de los peers ancla, hasta MAX_ANCHORS.
+    pub fn save(&self, anchors: &[SocketAddrV6]) -> Result<(), CustomError> {
+        let mut buffer = vec![];
+        for address in anchors.iter().take(MAX_ANCHORS) {
+            buffer.extend(address.ip().octets());
+            buffer.extend(address.port().to_be_bytes());
+        }
+
+        let mut file = open_new_file(self.path.clone(), false)?;
+        file.set_len(0)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use super::*;
+
+    #[test]
+    fn anchors_state_are_read_once() -> Result<(), CustomError> {
+        let path = String::from("tests/test_anchors_state.bin");
+        let first: SocketAddrV6 = "[::ffff:10.0.0.1]:18333".parse().unwrap();
+        let second: SocketAddrV6 = "[2001:db8::1]:18333".parse().unwrap();
+        let third: SocketAddrV6 = "[::ffff:10.0.0.3]:18333".parse().unwrap();
+
+        let mut anchors = AnchorsState::new(path.clone())?;
+        assert!(anchors.take().is_empty());
+        anchors.save(&[first, second, third])?;
+
+        let mut restored = AnchorsState::new(path.clone())?;
+        assert_eq!(restored.take(), vec![first, second]);
+        assert!(restored.take().is_empty());
+
+        let mut restored = AnchorsState::new(path.clone())?;
+        assert!(restored.take().is_empty());
+
+        remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::{Read, Write},
+    net::Ipv6Addr,
+};
+
+use crate::{error::CustomError, parser::BufferParser, utils::open_new_file};
+
+/// Tiempo por defecto que dura un baneo, en segundos (24 horas).
+pub const DEFAULT_BAN_TIME: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+/// BanEntry representa una direccion IP baneada.
+/// Los elementos son:
+/// - address: Direccion IP baneada, las direcciones IPv4 se guardan mapeadas a IPv6.
+/// - reason: Motivo del baneo.
+/// - banned_until: Timestamp en segundos hasta el que dura el baneo.
+pub struct BanEntry {
+    pub address: Ipv6Addr,
+    pub reason: String,
+    pub banned_until: u64,
+}
+
+impl BanEntry {
+    /// Serializa el baneo: direccion (16 bytes), largo del motivo (1 byte), motivo y vencimiento (8 bytes).
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut reason = self.reason.as_bytes().to_vec();
+        reason.truncate(u8::MAX as usize);
+
+        let mut buffer = self.address.octets().to_vec();
+        buffer.push(reason.len() as u8);
+        buffer.extend(reason);
+        buffer.extend(self.banned_until.to_le_bytes());
+        buffer
+    }
+
+    /// Deserializa un baneo.
+    pub fn parse(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(parser.extract_buffer(16)?);
+        let reason_len = parser.extract_u8()? as usize;
+        let reason = parser.extract_string(reason_len)?;
+        let banned_until = parser.extract_u64()?;
+
+        Ok(Self {
+            address: Ipv6Addr::from(octets),
+            reason,
+            banned_until,
+        })
+    }
+}
+
+/// BansState es una estructura que contiene las direcciones IP baneadas, con su motivo y vencimiento.
+/// Se guardan en disco para mantener los baneos entre ejecuciones del nodo.
+/// Los elementos son:
+/// - bans: Vector de baneos.
+/// - path: Path del archivo donde se guardan los baneos.
+pub struct BansState {
+    bans: Vec<BanEntry>,
+    path: String,
+}
+
+impl BansState {
+    /// Inicializa los baneos del nodo.
+    /// Si el archivo donde se guardan los baneos no existe, se crea.
+    /// Si el archivo existe, se restauran los baneos que todavia no vencieron.
+    pub fn new(path: String, now: u64) -> Result<Self, CustomError> {
+        let mut bans = Self { bans: vec![], path };
+        bans.restore(now)?;
+        Ok(bans)
+    }
+
+    fn restore(&mut self, now: u64) -> Result<(), CustomError> {
+        let mut file = open_new_file(self.path.clone(), false)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        let mut parser = BufferParser::new(buffer);
+
+        let mut bans = vec![];
+        while !parser.is_empty() {
+            let ban = BanEntry::parse(&mut parser)?;
+            if ban.banned_until > now {
+                bans.push(ban);
+            }
+        }
+
+        self.bans = bans;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), CustomError> {
+        let mut buffer = vec![];
+        for ban in &self.bans {
+            buffer.append(&mut ban.serialize());
+        }
+
+        let mut file = open_new_file(self.path.clone(), false)?;
+        file.set_len(0)?;
+        file.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Banea una direccion IP hasta el timestamp banned_until, reemplazando el baneo anterior si lo habia.
+    pub fn ban(
+        &mut self,
+        address: Ipv6Addr,
+        reason: String,
+        banned_until: u64,
+    ) -> Result<(), CustomError> {
+        self.bans.retain(|ban| ban.address != address);
+        self.bans.push(BanEntry {
+            address,
+            reason,
+            banned_until,
+        });
+        self.save()
+    }
+
+    /// Quita el baneo de una direccion IP.
+    /// Devuelve true si la direccion estaba baneada.
+    pub fn unban(&mut self, address: Ipv6Addr) -> Result<bool, CustomError> {
+        let total_bans = self.bans.len();
+        self.bans.retain(|ban| ban.address != address);
+        if self.bans.len() == total_bans {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Quita todos los baneos.
+    pub fn clear(&mut self) -> Result<(), CustomError> {
+        self.bans.clear();
+        self.save()
+    }
+
+    /// Indica si una direccion IP esta baneada en el timestamp now.
+    pub fn is_banned(&self, address: &Ipv6Addr, now: u64) -> bool {
+        self.bans
+            .iter()
+            .any(|ban| ban.address == *address && ban.banned_until > now)
+    }
+
+    /// Devuelve los baneos que no vencieron en el timestamp now.
+    pub fn get_all(&self, now: u64) -> Vec<BanEntry> {
+        self.bans
+            .iter()
+            .filter(|ban| ban.banned_until > now)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use super::*;
+
+    #[test]
+    fn bans_state_persists_and_expires() -> Result<(), CustomError> {
+        let path = String::from("tests/test_bans_state.bin");
+        let first: Ipv6Addr = "::ffff:10.0.0.1".parse().unwrap();
+        let second: Ipv6Addr = "::ffff:10.0.0.2".parse().unwrap();
+
+        let mut bans = BansState::new(path.clone(), 1000)?;
+        bans.ban(first, String::from("invalid headers"), 2000)?;
+        bans.ban(second, String::from("manual"), 1500)?;
+        assert!(bans.is_banned(&first, 1000));
+        assert!(!bans.is_banned(&second, 1500));
+
+        let mut restored = BansState::new(path.clone(), 1600)?;
+        assert_eq!(restored.get_all(1600).len(), 1);
+        assert_eq!(restored.get_all(1600)[0].reason, "invalid headers");
+        assert!(restored.unban(first)?);
+        assert!(!restored.unban(first)?);
+
+        let restored = BansState::new(path.clone(), 1000)?;
+        assert!(restored.get_all(1000).is_empty());
+
+        remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    messages::{block::Block, transaction::WITNESS_SCALE_FACTOR},
+    structs::hash32::Hash32,
+};
+
+/// Tamaño maximo por defecto de los bloques en cache, en bytes.
+pub const DEFAULT_BLOCK_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Bloque guardado en la cache.
+/// - block: Bloque parseado.
+/// - size: Tamaño virtual del bloque, con el que se cuenta el tamaño de la cache.
+/// - last_access: Numero de acceso en el que se uso el bloque por ultima vez.
+struct CachedBlock {
+    block: Block,
+    size: usize,
+    last_access: u64,
+}
+
+/// BlockCache es una cache LRU de los bloques leidos recientemente, para no leerlos y parsearlos de disco en cada pedido.
+/// Cuando el tamaño de los bloques supera el maximo se descartan los usados hace mas tiempo.
+/// Los elementos son:
+/// - blocks: Bloques en cache, indexados por su hash.
+/// - accesses: Hash de cada bloque indexado por su ultimo acceso, ordenados del mas viejo al mas reciente.
+/// - next_access: Numero del proximo acceso.
+/// - size: Suma de los tamaños de los bloques en cache.
+/// - max_size: Tamaño maximo de la cache.
+pub struct BlockCache {
+    blocks: HashMap<Hash32, CachedBlock>,
+    accesses: BTreeMap<u64, Hash32>,
+    next_access: u64,
+    size: usize,
+    max_size: usize,
+}
+
+impl BlockCache {
+    /// Crea una cache vacia con el tamaño maximo recibido, 0 la deshabilita.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            blocks: HashMap::new(),
+            accesses: BTreeMap::new(),
+            next_access: 0,
+            size: 0,
+            max_size,
+        }
+    }
+
+    /// Devuelve una copia del bloque con el hash recibido si esta en cache, y lo marca como el usado mas recientemente.
+    pub fn get(&mut self, block_hash: &Hash32) -> Option<Block> {
+        let access = self.next_access;
+        let cached = self.blocks.get_mut(block_hash)?;
+        self.accesses.remove(&cached.last_access);
+        self.accesses.insert(access, *block_hash);
+        cached.last_access = access;
+        self.next_access += 1;
+        Some(cached.block.clone())
+    }
+
+    /// Agrega el bloque a la cache, descartando los usados hace mas tiempo hasta que entre.
+    /// Los bloques mas grandes que la cache no se agregan.
+    pub fn insert(&mut self, block: Block) {
+        let size = block.weight().div_ceil(WITNESS_SCALE_FACTOR);
+        let block_hash = *block.header.hash();
+        if size > self.max_size || self.blocks.contains_key(&block_hash) {
+            return;
+        }
+        while self.size + size > self.max_size {
+            self.evict_oldest();
+        }
+
+        let access = self.next_access;
+        self.next_access += 1;
+        self.accesses.insert(access, block_hash);
+        self.blocks.insert(
+            block_hash,
+            CachedBlock {
+                block,
+                size,
+                last_access: access,
+            },
+        );
+        self.size += size;
+    }
+
+    /// Descarta el bloque usado hace mas tiempo.
+    fn evict_oldest(&mut self) {
+        let Some((_, block_hash)) = self.accesses.pop_first() else {
+            return;
+        };
+        if let Some(cached) = self.blocks.remove(&block_hash) {
+            self.size -= cached.size;
+        }
+    }
+
+    /// Devuelve la cantidad de bloques en cache.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Indica si la cache esta vacia.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Indica si el bloque con el hash recibido esta en cache.
+    pub fn contains(&self, block_hash: &Hash32) -> bool {
+        self.blocks.contains_key(block_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use crate::parser::BtcEncode;
+
+    use super::*;
+
+    fn test_block(nonce: u32) -> Block {
+        let mut buffer = vec![];
+        File::open("tests/blocks/test_block.bin")
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+        let mut block = Block::parse(buffer).unwrap();
+        block.header.nonce = nonce;
+        block.header.hash = Hash32::new([nonce as u8; 32]);
+        block
+    }
+
+    #[test]
+    fn evicts_least_recently_used_blocks() {
+        let block_size = test_block(0).weight().div_ceil(WITNESS_SCALE_FACTOR);
+        let mut cache = BlockCache::new(block_size * 2);
+        let first = test_block(1);
+        let second = test_block(2);
+        let third = test_block(3);
+        let (first_hash, second_hash, third_hash) = (
+            *first.header.hash(),
+            *second.header.hash(),
+            *third.header.hash(),
+        );
+
+        cache.insert(first);
+        cache.insert(second);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&first_hash).is_some());
+
+        // el segundo es el usado hace mas tiempo
+        cache.insert(third);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&first_hash));
+        assert!(!cache.contains(&second_hash));
+        assert!(cache.contains(&third_hash));
+        assert!(cache.get(&second_hash).is_none());
+
+        let mut disabled = BlockCache::new(0);
+        disabled.insert(test_block(4));
+        assert!(disabled.is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::CustomError, messages::block::Block, network::Network, parser::BtcEncode,
+    structs::hash32::Hash32, utils::open_new_file,
+};
+
+/// Tamaño maximo de cada archivo de bloques, al superarlo se empieza uno nuevo, como hace Bitcoin Core.
+pub const MAX_BLOCK_FILE_SIZE: u64 = 128 * 1024 * 1024;
+/// Tamaño del encabezado con el que se escribe cada bloque: magic number de la red y largo del bloque.
+const RECORD_HEADER_SIZE: u64 = 8;
+/// Tamaño del header serializado al comienzo de cada bloque, a partir del cual se calcula su hash.
+const BLOCK_HEADER_SIZE: usize = 80;
+/// Bit del largo de un bloque que indica que esta comprimido. Los bloques nunca llegan a ese largo,
+/// y los archivos sin bloques comprimidos siguen siendo compatibles con los de Bitcoin Core.
+const COMPRESSED_FLAG: u32 = 1 << 31;
+/// Nivel de compresion de zstd con el que se comprimen los bloques.
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Posicion de un bloque dentro de los archivos de bloques.
+/// - file: Numero del archivo blkNNNNN.dat que contiene al bloque.
+/// - offset: Posicion del bloque serializado dentro del archivo, despues de su encabezado.
+/// - length: Largo del bloque serializado, comprimido si compressed es true.
+/// - compressed: Indica si el bloque esta comprimido.
+pub struct BlockPosition {
+    pub file: u32,
+    pub offset: u64,
+    pub length: u32,
+    pub compressed: bool,
+}
+
+/// BlockStore guarda los bloques en archivos blkNNNNN.dat de solo agregado, como Bitcoin Core, en lugar de un archivo por bloque.
+/// Cada bloque se escribe precedido por el magic number de la red y su largo, y se ubica con un indice en memoria
+/// que se reconstruye recorriendo los archivos al abrirlos.
+/// Si la compresion esta habilitada, los bloques nuevos se escriben con su header sin comprimir, para poder indexarlos,
+/// seguido del resto del bloque comprimido con zstd, y se marcan con COMPRESSED_FLAG en el largo. Se pueden
+/// mezclar bloques comprimidos y sin comprimir, por lo que la compresion se puede cambiar sin migrar los archivos.
+/// Los elementos son:
+/// - dir: Directorio donde se encuentran los archivos de bloques.
+/// - network: Red de los bloques, cuyo magic number precede a cada bloque.
+/// - index: Posicion de cada bloque, indexada por su hash.
+/// - last_file: Numero del archivo al que se agregan los bloques nuevos.
+/// - last_file_size: Tamaño actual del archivo al que se agregan los bloques nuevos.
+/// - compress: Indica si los bloques nuevos se comprimen.
+pub struct BlockStore {
+    dir: String,
+    network: Network,
+    index: HashMap<Hash32, BlockPosition>,
+    last_file: u32,
+    last_file_size: u64,
+    compress: bool,
+}
+
+impl BlockStore {
+    /// Crea un almacenamiento vacio de bloques de la red recibida en el directorio recibido,
+    /// sin leer los archivos que ya existan en el.
+    pub fn new(dir: String, network: Network) -> Self {
+        Self {
+            dir,
+            network,
+            index: HashMap::new(),
+            last_file: 0,
+            last_file_size: 0,
+            compress: false,
+        }
+    }
+
+    /// Abre el almacenamiento del directorio recibido, creandolo si no existe, e indexa los bloques de sus archivos.
+    /// Un bloque incompleto al final del ultimo archivo (ej: por un cierre inesperado) se descarta.
+    /// Devuelve CustomError::StoreFileIncompatible si un archivo tiene bloques de una red distinta a la recibida,
+    /// y CustomError::StoreFileCorrupt si un archivo que no es el ultimo termina con un bloque incompleto.
+    pub fn open(dir: String, network: Network) -> Result<Self, CustomError> {
+        fs::create_dir_all(&dir)?;
+        let mut block_store = Self::new(dir, network);
+        let mut files = 0;
+        while Path::new(&block_store.file_path(files)).exists() {
+            files += 1;
+        }
+        for file in 0..files {
+            block_store.index_file(file, file + 1 == files)?;
+        }
+        Ok(block_store)
+    }
+
+    /// Establece si los bloques que se agreguen se comprimen. Los bloques ya guardados no se modifican.
+    pub fn set_compression(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    fn file_path(&self, file: u32) -> String {
+        format!("{}/blk{:05}.dat", self.dir, file)
+    }
+
+    /// Agrega al indice los bloques del archivo. Si es el ultimo archivo, lo trunca despues del ultimo bloque completo,
+    /// ya que solo en el que se estaba escribiendo puede quedar un bloque a medio escribir.
+    fn index_file(&mut self, file_number: u32, is_last: bool) -> Result<(), CustomError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(self.file_path(file_number))?;
+        let size = file.metadata()?.len();
+
+        let mut offset = 0;
+        let mut record_header = [0; RECORD_HEADER_SIZE as usize];
+        let mut block_header = [0; BLOCK_HEADER_SIZE];
+        while offset + RECORD_HEADER_SIZE <= size {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut record_header)?;
+            let magic = u32::from_be_bytes([
+                record_header[0],
+                record_header[1],
+                record_header[2],
+                record_header[3],
+            ]);
+            let length = u32::from_le_bytes([
+                record_header[4],
+                record_header[5],
+                record_header[6],
+                record_header[7],
+            ]);
+            let compressed = length & COMPRESSED_FLAG != 0;
+            let length = length & !COMPRESSED_FLAG;
+            let start = offset + RECORD_HEADER_SIZE;
+            if magic != self.network.magic() {
+                return Err(CustomError::StoreFileIncompatible);
+            }
+            if (length as usize) < BLOCK_HEADER_SIZE || start + length as u64 > size {
+                break;
+            }
+
+            file.read_exact(&mut block_header)?;
+            self.index.insert(
+                Hash32::sha256d(&block_header),
+                BlockPosition {
+                    file: file_number,
+                    offset: start,
+                    length,
+                    compressed,
+                },
+            );
+            offset = start + length as u64;
+        }
+
+        if offset < size {
+            if !is_last {
+                return Err(CustomError::StoreFileCorrupt);
+            }
+            file.set_len(offset)?;
+        }
+        self.last_file = file_number;
+        self.last_file_size = offset;
+        Ok(())
+    }
+
+    /// Mueve a los archivos de bloques los bloques guardados con el formato anterior, un archivo <hash>.bin por bloque,
+    /// y borra los archivos viejos. Los que no se pueden parsear se borran sin migrarlos.
+    /// Devuelve la cantidad de bloques migrados.
+    pub fn migrate_legacy_blocks(&mut self) -> Result<usize, CustomError> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_legacy_block_file(path))
+            .collect();
+        paths.sort();
+
+        let mut migrated = 0;
+        for path in paths {
+            let mut buffer = vec![];
+            File::open(&path)?.read_to_end(&mut buffer)?;
+            if let Ok(block) = Block::parse(buffer) {
+                self.append(&block)?;
+                migrated += 1;
+            }
+            fs::remove_file(path)?;
+        }
+        Ok(migrated)
+    }
+
+    /// Agrega el bloque al final del ultimo archivo, o de uno nuevo si superaria MAX_BLOCK_FILE_SIZE.
+    /// Si la compresion esta habilitada, el bloque se guarda comprimido.
+    /// Si el bloque ya estaba guardado no hace nada.
+    pub fn append(&mut self, block: &Block) -> Result<(), CustomError> {
+        let block_hash = *block.header.hash();
+        if self.contains(&block_hash) {
+            return Ok(());
+        }
+
+        let data = match self.compress {
+            true => compress_block(&block.serialize())?,
+            false => block.serialize(),
+        };
+        let record_size = RECORD_HEADER_SIZE + data.len() as u64;
+        if self.last_file_size > 0 && self.last_file_size + record_size > MAX_BLOCK_FILE_SIZE {
+            self.last_file += 1;
+            self.last_file_size = 0;
+        }
+
+        let mut buffer = self.network.magic().to_be_bytes().to_vec();
+        let length = match self.compress {
+            true => data.len() as u32 | COMPRESSED_FLAG,
+            false => data.len() as u32,
+        };
+        buffer.extend(length.to_le_bytes());
+        buffer.extend(&data);
+        open_new_file(self.file_path(self.last_file), true)?.write_all(&buffer)?;
+
+        self.index.insert(
+            block_hash,
+            BlockPosition {
+                file: self.last_file,
+                offset: self.last_file_size + RECORD_HEADER_SIZE,
+                length: data.len() as u32,
+                compressed: self.compress,
+            },
+        );
+        self.last_file_size += record_size;
+        Ok(())
+    }
+
+    /// Lee el bloque con el hash recibido de su archivo, descomprimiendolo si esta comprimido.
+    /// Devuelve CustomError::BlockNotFound si el bloque no esta guardado.
+    pub fn get(&self, block_hash: &Hash32) -> Result<Block, CustomError> {
+        let Some(position) = self.index.get(block_hash) else {
+            return Err(CustomError::BlockNotFound);
+        };
+        let mut file = File::open(self.file_path(position.file))?;
+        file.seek(SeekFrom::Start(position.offset))?;
+        let mut buffer = vec![0; position.length as usize];
+        file.read_exact(&mut buffer)?;
+        if position.compressed {
+            buffer = decompress_block(&buffer)?;
+        }
+        Block::parse(buffer)
+    }
+
+    /// Devuelve la posicion del bloque con el hash recibido, si esta guardado.
+    pub fn get_position(&self, block_hash: &Hash32) -> Option<BlockPosition> {
+        self.index.get(block_hash).copied()
+    }
+
+    /// Indica si el bloque con el hash recibido esta guardado.
+    pub fn contains(&self, block_hash: &Hash32) -> bool {
+        self.index.contains_key(block_hash)
+    }
+
+    /// Devuelve la cantidad de bloques guardados.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Indica si no hay bloques guardados.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+/// Comprime el bloque serializado, dejando el header sin comprimir para poder calcular su hash al indexarlo.
+fn compress_block(data: &[u8]) -> Result<Vec<u8>, CustomError> {
+    let Some((header, rest)) = data.split_at_checked(BLOCK_HEADER_SIZE) else {
+        return Err(CustomError::SerializedBufferIsInvalid);
+    };
+    let mut buffer = header.to_vec();
+    buffer.extend(zstd::encode_all(rest, COMPRESSION_LEVEL)?);
+    Ok(buffer)
+}
+
+/// Descomprime un bloque guardado con compress_block.
+fn decompress_block(data: &[u8]) -> Result<Vec<u8>, CustomError> {
+    let Some((header, rest)) = data.split_at_checked(BLOCK_HEADER_SIZE) else {
+        return Err(CustomError::SerializedBufferIsInvalid);
+    };
+    let mut buffer = header.to_vec();
+    buffer.extend(zstd::decode_all(rest)?);
+    Ok(buffer)
+}
+
+/// Indica si el path es el de un bloque guardado con el formato anterior: el hash en hexa seguido de .bin.
+fn is_legacy_block_file(path: &Path) -> bool {
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    path.extension().is_some_and(|extension| extension == "bin")
+        && stem.len() == 64
+        && stem.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::utils::bytes_to_hex;
+
+    use super::*;
+
+    fn test_block() -> Block {
+        let mut buffer = vec![];
+        File::open("tests/blocks/test_block.bin")
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+        Block::parse(buffer).unwrap()
+    }
+
+    #[test]
+    fn append_and_reopen_block_store() {
+        let dir = "tests/block_store_append".to_string();
+        fs::remove_dir_all(&dir).ok();
+        let block = test_block();
+        let block_hash = *block.header.hash();
+
+        let mut block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        block_store.append(&block).unwrap();
+        block_store.append(&block).unwrap();
+        assert_eq!(block_store.len(), 1);
+        let position = block_store.get_position(&block_hash).unwrap();
+        assert_eq!(position.offset, RECORD_HEADER_SIZE);
+        assert_eq!(
+            block_store.get(&block_hash).unwrap().serialize(),
+            block.serialize()
+        );
+
+        // un bloque a medio escribir al final del archivo se descarta al reabrirlo
+        let mut file = open_new_file(format!("{dir}/blk00000.dat"), true).unwrap();
+        file.write_all(&Network::Testnet.magic().to_be_bytes())
+            .unwrap();
+        file.write_all(&1000_u32.to_le_bytes()).unwrap();
+        file.write_all(&[0; 100]).unwrap();
+
+        let block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        assert_eq!(block_store.get_position(&block_hash), Some(position));
+        assert!(matches!(
+            block_store.get(&Hash32::default()),
+            Err(CustomError::BlockNotFound)
+        ));
+        assert_eq!(
+            fs::metadata(format!("{dir}/blk00000.dat")).unwrap().len(),
+            RECORD_HEADER_SIZE + position.length as u64
+        );
+
+        // en un archivo que no es el ultimo, un bloque incompleto no se trunca
+        fs::copy(format!("{dir}/blk00000.dat"), format!("{dir}/blk00001.dat")).unwrap();
+        let mut file = open_new_file(format!("{dir}/blk00000.dat"), true).unwrap();
+        file.write_all(&Network::Testnet.magic().to_be_bytes())
+            .unwrap();
+        assert!(matches!(
+            BlockStore::open(dir.clone(), Network::Testnet),
+            Err(CustomError::StoreFileCorrupt)
+        ));
+        assert_eq!(
+            fs::metadata(format!("{dir}/blk00000.dat")).unwrap().len(),
+            2 * RECORD_HEADER_SIZE - 4 + position.length as u64
+        );
+
+        // los bloques de otra red no se descartan
+        file.set_len(RECORD_HEADER_SIZE + position.length as u64)
+            .unwrap();
+        let mut file = open_new_file(format!("{dir}/blk00001.dat"), true).unwrap();
+        file.write_all(&[0xff; RECORD_HEADER_SIZE as usize])
+            .unwrap();
+        assert!(matches!(
+            BlockStore::open(dir.clone(), Network::Testnet),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn append_compressed_blocks() {
+        let dir = "tests/block_store_compressed".to_string();
+        fs::remove_dir_all(&dir).ok();
+        let block = test_block();
+        let block_hash = *block.header.hash();
+
+        let mut block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        block_store.set_compression(true);
+        block_store.append(&block).unwrap();
+        let position = block_store.get_position(&block_hash).unwrap();
+        assert!(position.compressed);
+        assert!((position.length as usize) < block.serialize().len());
+
+        // al reabrirlo el bloque se indexa por su header sin comprimir
+        let block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        assert_eq!(block_store.get_position(&block_hash), Some(position));
+        assert_eq!(
+            block_store.get(&block_hash).unwrap().serialize(),
+            block.serialize()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_blocks_from_legacy_layout() {
+        let dir = "tests/block_store_migration".to_string();
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        let block = test_block();
+        // los bloques se guardaban con el hash en hexa en mayusculas y en el orden de la red
+        let legacy_name = bytes_to_hex(block.header.hash().as_bytes()).to_uppercase();
+        let legacy_path = format!("{dir}/{legacy_name}.bin");
+        block.save(legacy_path.clone()).unwrap();
+        block.save(format!("{dir}/other.bin")).unwrap();
+
+        let mut block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        assert_eq!(block_store.migrate_legacy_blocks().unwrap(), 1);
+        assert!(!Path::new(&legacy_path).exists());
+        assert!(Path::new(&format!("{dir}/other.bin")).exists());
+        assert!(block_store.contains(block.header.hash()));
+
+        let block_store = BlockStore::open(dir.clone(), Network::Testnet).unwrap();
+        assert!(block_store.contains(block.header.hash()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,16 +1,25 @@
 use std::{
-    fs::read_dir,
+    io::{Read, Write},
+    path::Path,
     sync::{mpsc::Sender, Arc, Mutex},
 };
 
+use tracing::instrument;
+
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
+    logger::{send_log, Log, LogTarget},
     messages::block::Block,
-    utils::get_current_timestamp_millis,
+    parser::BufferParser,
+    structs::{block_filter::BlockFilter, hash32::Hash32},
+    utils::{get_current_timestamp_millis, open_new_file},
 };
 
-use super::pending_blocks_state::PendingBlocks;
+use super::{
+    block_cache::{BlockCache, DEFAULT_BLOCK_CACHE_SIZE},
+    block_store::BlockStore,
+    pending_blocks_state::PendingBlocks,
+};
 
 /// BlocksIBDStats es una estructura que contiene los elementos necesarios para manejar las
 /// estadisticas de la descarga masiva de bloques.
@@ -32,22 +41,29 @@ struct BlocksIBDStats {
 /// BlocksState es una estructura que contiene los elementos necesarios para manejar los bloques.
 /// Los elementos son:
 /// - ibd_stats: Option<BLocksIBDStats> solamente se inicializa cuando corresponde.
-/// - store_path: Path de la carpeta donde se crea el directorio donde se encuentran los bloques.
+/// - store_path: Path de la carpeta donde se crea el directorio donde se encuentran los filtros de los bloques.
+/// - block_store: Archivos donde se guardan los bloques.
+/// - block_cache: Cache de los bloques leidos recientemente de block_store.
 /// - logger_sender: Sender para enviar logs al logger.
 /// - pending_blocks_ref: Referencia a los bloques pendientes.
 /// - sync: Booleano que indica si el nodo esta sincronizado.
+/// - spv: Booleano que indica si el nodo funciona como cliente SPV, en cuyo caso no construye los filtros de los bloques.
 pub struct BlocksState {
     ibd_stats: Option<BlocksIBDStats>,
     store_path: String,
+    block_store: BlockStore,
+    block_cache: Mutex<BlockCache>,
     logger_sender: Sender<Log>,
     pub pending_blocks_ref: Arc<Mutex<PendingBlocks>>,
     sync: bool,
+    spv: bool,
 }
 
 impl BlocksState {
     /// Inicializa el estado de los bloques.
     pub fn new(
         store_path: String,
+        block_store: BlockStore,
         logger_sender: Sender<Log>,
         pending_blocks_ref: Arc<Mutex<PendingBlocks>>,
     ) -> Self {
@@ -55,29 +71,38 @@ impl BlocksState {
             ibd_stats: None,
             pending_blocks_ref,
             store_path,
+            block_store,
+            block_cache: Mutex::new(BlockCache::new(DEFAULT_BLOCK_CACHE_SIZE)),
             logger_sender,
             sync: false,
+            spv: false,
         }
     }
 
+    /// Establece si los bloques que se guardan se comprimen con zstd.
+    pub fn set_compression(&mut self, compress: bool) {
+        self.block_store.set_compression(compress);
+    }
+
+    /// Establece si el nodo funciona como cliente SPV.
+    pub fn set_spv_mode(&mut self, spv: bool) {
+        self.spv = spv;
+    }
+
     /// Se encarga de guardar en disco el bloque y eliminarlo de los bloques pendientes.
     /// Si la cantidad de bloques a descargar es mayor al 2% de los headers posteriores al START_DATE_IBD
     /// comienza los stats de la descarga.
+    #[instrument(level = "debug", skip_all)]
     pub fn append_block(
         &mut self,
-        block_hash: &Vec<u8>,
+        block_hash: &Hash32,
         block: &Block,
         total_blocks: usize,
     ) -> Result<(), CustomError> {
-        let path = format!(
-            "{}/blocks/{}.bin",
-            self.store_path,
-            block.header.hash_as_string()
-        );
-        block.save(path)?;
+        self.block_store.append(block)?;
 
         if self.ibd_stats.is_none() {
-            let blocks_downloaded = read_dir(format!("{}/blocks", self.store_path))?.count();
+            let blocks_downloaded = self.block_store.len();
             let percentage = (blocks_downloaded * 100) / total_blocks;
 
             if percentage < 98_usize {
@@ -103,7 +128,7 @@ impl BlocksState {
         if self.is_synced() || self.ibd_stats.is_none() {
             send_log(
                 &self.logger_sender,
-                Log::Message(String::from("New block received")),
+                Log::Info(LogTarget::Sync, String::from("New block received")),
             );
         } else {
             self.print_stats(total_blocks)?;
@@ -120,15 +145,19 @@ impl BlocksState {
             let percentage = (ibd_stats.blocks_downloaded * 100) / total_blocks;
             if percentage > ibd_stats.checkpoint_percentage {
                 let now = get_current_timestamp_millis()?;
-                let checkpoint_time = now - ibd_stats.checkpoint_timestamp;
+                // con un peer local los bloques pueden llegar en el mismo milisegundo
+                let checkpoint_time = (now - ibd_stats.checkpoint_timestamp).max(1);
                 let blocks_per_second = ibd_stats.checkpoint_downloads * 1000 / checkpoint_time;
 
                 send_log(
                     &self.logger_sender,
-                    Log::Message(format!(
-                        "Blocks sync {}% at {} blocks/s... total {}",
-                        percentage, blocks_per_second, ibd_stats.blocks_downloaded
-                    )),
+                    Log::Info(
+                        LogTarget::Sync,
+                        format!(
+                            "Blocks sync {}% at {} blocks/s... total {}",
+                            percentage, blocks_per_second, ibd_stats.blocks_downloaded
+                        ),
+                    ),
                 );
 
                 ibd_stats.checkpoint_percentage = percentage;
@@ -154,16 +183,75 @@ impl BlocksState {
             pending_blocks.drain();
             send_log(
                 &self.logger_sender,
-                Log::Message("blocks sync completed".to_string()),
+                Log::Info(LogTarget::Sync, "blocks sync completed".to_string()),
             );
         }
         Ok(())
     }
 
     /// Devuelve el bloque correspondiente al hash pasado por parametro.
-    pub fn get_block(&self, block_string_hash: String) -> Result<Block, CustomError> {
-        let path = format!("{}/blocks/{}.bin", self.store_path, block_string_hash);
-        Block::restore(path)
+    /// Los bloques leidos de disco se guardan en la cache, de donde se devuelven mientras no se descarten.
+    pub fn get_block(&self, block_hash: &Hash32) -> Result<Block, CustomError> {
+        if let Some(block) = self.block_cache.lock()?.get(block_hash) {
+            return Ok(block);
+        }
+        let block = self.block_store.get(block_hash)?;
+        self.block_cache.lock()?.insert(block.clone());
+        Ok(block)
+    }
+
+    /// Indica si el bloque correspondiente al hash pasado por parametro esta guardado.
+    pub fn has_block(&self, block_hash: &Hash32) -> bool {
+        self.block_store.contains(block_hash)
+    }
+
+    /// Construye y guarda el filtro basico (BIP158) del bloque, a partir de sus salidas y de los scripts de las salidas que gasta.
+    /// Junto al filtro se guarda el header del filtro del bloque anterior, o ceros si no se tiene su filtro.
+    /// Si el filtro ya existe no se vuelve a construir, y como cliente SPV no se construyen porque los bloques estan filtrados.
+    pub fn append_filter(
+        &self,
+        block: &Block,
+        spent_scripts: &[Vec<u8>],
+    ) -> Result<(), CustomError> {
+        let path = self.filter_path(block.header.hash());
+        if self.spv || Path::new(&path).exists() {
+            return Ok(());
+        }
+
+        let previous_header = match self.get_filter(&block.header.prev_block_hash)? {
+            Some((filter, previous_header)) => filter.header(&previous_header),
+            None => Hash32::default(),
+        };
+        let filter = BlockFilter::from_block(block, spent_scripts);
+
+        let mut buffer = previous_header.to_vec();
+        buffer.extend(&filter.data);
+        open_new_file(path, false)?.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Devuelve el filtro basico del bloque junto al header del filtro del bloque anterior.
+    /// Devuelve None si no se construyo el filtro del bloque.
+    pub fn get_filter(
+        &self,
+        block_hash: &Hash32,
+    ) -> Result<Option<(BlockFilter, Hash32)>, CustomError> {
+        let path = self.filter_path(block_hash);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        let mut buffer = vec![];
+        open_new_file(path, false)?.read_to_end(&mut buffer)?;
+        let mut parser = BufferParser::new(buffer);
+        let previous_header = parser.extract_hash()?;
+        let remaining = parser.len();
+        let data = parser.extract_buffer(remaining)?.to_vec();
+        Ok(Some((BlockFilter { data }, previous_header)))
+    }
+
+    fn filter_path(&self, block_hash: &Hash32) -> String {
+        format!("{}/filters/{}.bin", self.store_path, block_hash)
     }
 
     /// Retorna el estado de sincronizacion de los bloques.
@@ -177,44 +265,82 @@ mod tests {
 
     use std::{fs, path::Path, sync::mpsc};
 
+    use crate::{network::Network, parser::BtcEncode};
+
     use super::*;
 
+    /// Crea el estado de los bloques de un store de prueba, vacio.
+    fn test_blocks_state(store_path: &str) -> BlocksState {
+        fs::remove_dir_all(store_path).ok();
+        let (logger_sender, _) = mpsc::channel();
+        let block_store =
+            BlockStore::open(format!("{store_path}/blocks"), Network::Testnet).unwrap();
+        let pending_blocks_ref = PendingBlocks::new(&block_store, &vec![], 0);
+        BlocksState::new(
+            store_path.to_string(),
+            block_store,
+            logger_sender,
+            pending_blocks_ref,
+        )
+    }
+
     #[test]
     fn blocks_state_append() {
-        let store_path = "tests".to_string();
-        let (logger_sender, _) = mpsc::channel();
-        let pending_blocks_ref = PendingBlocks::new(&store_path, &vec![]);
-        let mut blocks_state =
-            BlocksState::new(store_path.clone(), logger_sender, pending_blocks_ref);
+        let store_path = "tests/blocks_state_append";
+        let mut blocks_state = test_blocks_state(store_path);
 
         let mut pending = blocks_state.pending_blocks_ref.lock().unwrap();
-        pending.append_block(vec![1, 2, 3]).unwrap();
+        pending.append_block(Hash32::new([1; 32])).unwrap();
         drop(pending);
 
-        let mut block = blocks_state.get_block("test_block".to_string()).unwrap();
-        block.header.hash = vec![1, 2, 3];
+        let mut block = Block::restore("tests/blocks/test_block.bin".to_string()).unwrap();
+        block.header.hash = Hash32::new([1; 32]);
 
         blocks_state
-            .append_block(&vec![1, 2, 3], &block, 1)
+            .append_block(&Hash32::new([1; 32]), &block, 1)
             .unwrap();
 
         let pending = blocks_state.pending_blocks_ref.lock().unwrap();
         assert_eq!(pending.is_empty(), true);
 
-        assert!(Path::new(&format!("{}/blocks/010203.bin", store_path)).exists());
-        fs::remove_file(format!("{}/blocks/010203.bin", store_path)).unwrap();
+        assert!(blocks_state.has_block(&Hash32::new([1; 32])));
+        let stored = blocks_state.get_block(&Hash32::new([1; 32])).unwrap();
+        assert_eq!(stored.serialize(), block.serialize());
+        assert!(Path::new(&format!("{store_path}/blocks/blk00000.dat")).exists());
+        fs::remove_dir_all(store_path).unwrap();
+    }
+
+    #[test]
+    fn blocks_state_append_filter() {
+        let store_path = "tests/blocks_state_filter";
+        let blocks_state = test_blocks_state(store_path);
+        fs::create_dir_all(format!("{store_path}/filters")).unwrap();
+
+        let block = Block::restore("tests/blocks/test_block.bin".to_string()).unwrap();
+        let spent_scripts = vec![vec![0x76, 0xa9, 0x14]];
+        blocks_state.append_filter(&block, &spent_scripts).unwrap();
+
+        let (filter, previous_header) = blocks_state
+            .get_filter(block.header.hash())
+            .unwrap()
+            .unwrap();
+        assert_eq!(previous_header, Hash32::default());
+        let output_script = &block.transactions[0].outputs[0].script_pubkey;
+        assert!(filter.contains(block.header.hash(), output_script).unwrap());
+        assert!(filter
+            .contains(block.header.hash(), &spent_scripts[0])
+            .unwrap());
+
+        fs::remove_dir_all(store_path).unwrap();
     }
 
     #[test]
     fn blocks_state_verify_sync() {
-        let store_path = "tests".to_string();
-        let (logger_sender, _) = mpsc::channel();
-        let pending_blocks_ref = PendingBlocks::new(&store_path, &vec![]);
-        let mut blocks_state =
-            BlocksState::new(store_path.clone(), logger_sender, pending_blocks_ref);
+        let store_path = "tests/blocks_state_sync";
+        let mut blocks_state = test_blocks_state(store_path);
 
         let mut pending = blocks_state.pending_blocks_ref.lock().unwrap();
-        pending.append_block(vec![1, 2, 3]).unwrap();
+        pending.append_block(Hash32::new([1; 32])).unwrap();
         drop(pending);
 
         assert_eq!(blocks_state.is_synced(), false);
@@ -222,7 +348,7 @@ mod tests {
         assert_eq!(blocks_state.is_synced(), false);
 
         let mut pending = blocks_state.pending_blocks_ref.lock().unwrap();
-        pending.remove_block(&vec![1, 2, 3]).unwrap();
+        pending.remove_block(&Hash32::new([1; 32])).unwrap();
         drop(pending);
 
         assert_eq!(blocks_state.is_synced(), false);
@@ -230,11 +356,12 @@ mod tests {
         assert_eq!(blocks_state.is_synced(), true);
 
         let mut pending = blocks_state.pending_blocks_ref.lock().unwrap();
-        pending.append_block(vec![1, 2, 3]).unwrap();
+        pending.append_block(Hash32::new([1; 32])).unwrap();
         drop(pending);
 
         assert_eq!(blocks_state.is_synced(), true);
         blocks_state.verify_sync().unwrap();
         assert_eq!(blocks_state.is_synced(), true);
+        fs::remove_dir_all(store_path).unwrap();
     }
 }
@@ -0,0 +1,146 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    sync::mpsc::Sender,
+};
+
+use crate::{error::CustomError, logger::Log, network::Network, parser::BtcEncode};
+
+use super::{
+    block_store::{BlockStore, MAX_BLOCK_FILE_SIZE},
+    core_import::{core_file_path, HEADERS_FILE, RECORD_HEADER_SIZE},
+    headers_state::HeadersState,
+};
+
+#[derive(Debug, Default, PartialEq)]
+/// Resultado de una exportacion de la cadena.
+/// - headers: Cantidad de headers exportados.
+/// - blocks: Cantidad de bloques exportados.
+pub struct ExportSummary {
+    pub headers: usize,
+    pub blocks: usize,
+}
+
+/// Exporta la cadena guardada en el store al directorio recibido, que debe estar vacio o no existir,
+/// para que otro nodo la importe sin conexion con --import-blocks. Se escriben:
+/// - headers.dat: Todos los headers serializados (80 bytes cada uno), en orden desde el bloque 1.
+///   Permite conectar los bloques exportados con el genesis, ya que el nodo solo guarda los bloques posteriores a START_DATE_IBD.
+/// - blkNNNNN.dat: Los bloques guardados en el orden de la cadena, con el formato de Bitcoin Core (magic number
+///   de la red, largo y bloque), empezando un archivo nuevo cada MAX_BLOCK_FILE_SIZE.
+pub fn export_chain(
+    store_path: &str,
+    export_dir: &str,
+    network: Network,
+    logger_sender: &Sender<Log>,
+) -> Result<ExportSummary, CustomError> {
+    fs::create_dir_all(export_dir)?;
+    if fs::read_dir(export_dir)?.next().is_some() {
+        return Err(CustomError::Validation(format!(
+            "Export directory {export_dir} is not empty"
+        )));
+    }
+
+    let headers = HeadersState::new(
+        format!("{store_path}/headers.bin"),
+        logger_sender.clone(),
+        network,
+    )?;
+    let block_store = BlockStore::open(format!("{store_path}/blocks"), network)?;
+    let mut summary = ExportSummary::default();
+
+    let mut headers_file = BufWriter::new(File::create(format!("{export_dir}/{HEADERS_FILE}"))?);
+    for header in headers.iter_from(0) {
+        headers_file.write_all(&header.serialize())?;
+        summary.headers += 1;
+    }
+    headers_file.flush()?;
+
+    let mut file_number = 0;
+    let mut file_size = 0;
+    let mut blocks_file: Option<BufWriter<File>> = None;
+    for header in headers.iter_from(0) {
+        if !block_store.contains(header.hash()) {
+            continue;
+        }
+        let data = block_store.get(header.hash())?.serialize();
+        let record_size = (RECORD_HEADER_SIZE + data.len()) as u64;
+        if file_size > 0 && file_size + record_size > MAX_BLOCK_FILE_SIZE {
+            if let Some(mut file) = blocks_file.take() {
+                file.flush()?;
+            }
+            file_number += 1;
+            file_size = 0;
+        }
+        let file = match &mut blocks_file {
+            Some(file) => file,
+            None => blocks_file.insert(BufWriter::new(File::create(core_file_path(
+                export_dir,
+                file_number,
+            ))?)),
+        };
+        file.write_all(&network.magic().to_be_bytes())?;
+        file.write_all(&(data.len() as u32).to_le_bytes())?;
+        file.write_all(&data)?;
+        file_size += record_size;
+        summary.blocks += 1;
+    }
+    if let Some(mut file) = blocks_file {
+        file.flush()?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::states::core_import::import_core_blocks;
+
+    use super::*;
+
+    #[test]
+    fn export_and_import_chain() {
+        let store_path = "tests/chain_export_store";
+        let export_dir = "tests/chain_export_files";
+        let import_path = "tests/chain_export_import";
+        for dir in [store_path, export_dir, import_path] {
+            fs::remove_dir_all(dir).ok();
+        }
+        fs::create_dir_all(store_path).unwrap();
+        fs::copy(
+            "tests/test_headers.bin",
+            format!("{store_path}/headers.bin"),
+        )
+        .unwrap();
+        let logger_sender = mpsc::channel().0;
+
+        let summary =
+            export_chain(store_path, export_dir, Network::Testnet, &logger_sender).unwrap();
+        assert_eq!(
+            summary,
+            ExportSummary {
+                headers: 2,
+                blocks: 0
+            }
+        );
+        assert_eq!(
+            fs::metadata(format!("{export_dir}/{HEADERS_FILE}"))
+                .unwrap()
+                .len(),
+            160
+        );
+        assert!(export_chain(store_path, export_dir, Network::Testnet, &logger_sender).is_err());
+
+        let summary =
+            import_core_blocks(export_dir, import_path, Network::Testnet, &logger_sender).unwrap();
+        assert_eq!(summary.headers, 2);
+        assert_eq!(
+            fs::read(format!("{import_path}/headers.bin")).unwrap(),
+            fs::read("tests/test_headers.bin").unwrap()
+        );
+
+        for dir in [store_path, export_dir, import_path] {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fs, path::Path, sync::mpsc::Sender};
+
+use crate::{
+    error::CustomError,
+    logger::{send_log, Log, LogTarget},
+    messages::block::Block,
+    network::Network,
+    parser::BtcEncode,
+    structs::{block_header::BlockHeader, hash32::Hash32},
+};
+
+use super::{block_store::BlockStore, headers_state::HeadersState};
+
+/// Tamaño del encabezado de cada bloque en los archivos de Bitcoin Core: magic number de la red y largo del bloque.
+pub const RECORD_HEADER_SIZE: usize = 8;
+/// Tamaño del header serializado al comienzo de cada bloque.
+const BLOCK_HEADER_SIZE: usize = 80;
+/// Cantidad de headers que se agregan juntos, como en un mensaje headers.
+const HEADERS_BATCH_SIZE: usize = 2000;
+/// Archivo con los headers serializados que acompaña a los bloques exportados por export_chain.
+pub const HEADERS_FILE: &str = "headers.dat";
+
+#[derive(Debug, Clone)]
+/// Bloque encontrado en los archivos de Bitcoin Core.
+/// - header: Header del bloque, con su proof of work ya verificada.
+/// - file: Numero del archivo blkNNNNN.dat que contiene al bloque.
+/// - offset: Posicion del bloque serializado dentro del archivo.
+/// - length: Largo del bloque serializado.
+struct CoreBlock {
+    header: BlockHeader,
+    file: u32,
+    offset: usize,
+    length: usize,
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// Resultado de una importacion de bloques.
+/// - headers: Cantidad de headers agregados.
+/// - blocks: Cantidad de bloques guardados.
+pub struct ImportSummary {
+    pub headers: usize,
+    pub blocks: usize,
+}
+
+/// Importa los bloques de los archivos blk*.dat del directorio de bloques de Bitcoin Core (ej: ~/.bitcoin/testnet3/blocks).
+/// Como Bitcoin Core guarda los bloques en el orden en que los recibe, primero se indexan todos y luego se recorre
+/// la cadena con mas trabajo que continua al ultimo header guardado.
+/// Se agregan sus headers, y se guardan los bloques posteriores a START_DATE_IBD que el nodo descargaria de la red,
+/// verificando su merkle root y sus limites. La importacion se detiene en el primer bloque invalido.
+/// Si el directorio fue generado por export_chain, antes se agregan los headers de su archivo HEADERS_FILE.
+/// Solo se importan los bloques de la red recibida, que debe ser la del store.
+pub fn import_core_blocks(
+    core_dir: &str,
+    store_path: &str,
+    network: Network,
+    logger_sender: &Sender<Log>,
+) -> Result<ImportSummary, CustomError> {
+    let xor_key = read_xor_key(core_dir)?;
+    let mut index = HashMap::new();
+    let mut file = 0;
+    while Path::new(&core_file_path(core_dir, file)).exists() {
+        let buffer = read_core_file(core_dir, file, &xor_key)?;
+        let found = scan_block_file(&buffer, file, network, &mut index);
+        send_log(
+            logger_sender,
+            Log::Info(
+                LogTarget::Sync,
+                format!("Found {found} blocks in blk{file:05}.dat"),
+            ),
+        );
+        file += 1;
+    }
+
+    fs::create_dir_all(store_path)?;
+    let mut headers = HeadersState::new(
+        format!("{store_path}/headers.bin"),
+        logger_sender.clone(),
+        network,
+    )?;
+    let mut block_store = BlockStore::open(format!("{store_path}/blocks"), network)?;
+    let mut summary = ImportSummary {
+        headers: import_headers_file(core_dir, &mut headers)?,
+        blocks: 0,
+    };
+
+    let start = headers.get_last_header_hash().unwrap_or(network.genesis());
+    let chain = best_chain(start, &index);
+    for batch in chain.chunks(HEADERS_BATCH_SIZE) {
+        let batch = batch
+            .iter()
+            .filter_map(|hash| index.get(hash))
+            .map(|core_block| core_block.header.clone())
+            .collect();
+        summary.headers += headers.append_headers(batch)?.len();
+    }
+    headers.flush()?;
+
+    let mut loaded_file: Option<(u32, Vec<u8>)> = None;
+    for header in headers.iter_from(headers.first_block_index()) {
+        let block_hash = header.hash();
+        let Some(core_block) = index.get(block_hash) else {
+            continue;
+        };
+        if block_store.contains(block_hash) {
+            continue;
+        }
+
+        if loaded_file
+            .as_ref()
+            .is_none_or(|(file, _)| *file != core_block.file)
+        {
+            loaded_file = Some((
+                core_block.file,
+                read_core_file(core_dir, core_block.file, &xor_key)?,
+            ));
+        }
+        let Some((_, buffer)) = &loaded_file else {
+            continue;
+        };
+        let data = buffer[core_block.offset..core_block.offset + core_block.length].to_vec();
+        let block = match Block::parse(data).and_then(|block| {
+            block.create_merkle_root()?;
+            block.check_limits()?;
+            Ok(block)
+        }) {
+            Ok(block) => block,
+            Err(error) => {
+                send_log(
+                    logger_sender,
+                    Log::Warn(
+                        LogTarget::Sync,
+                        format!("Stopping the import at invalid block {block_hash}: {error}"),
+                    ),
+                );
+                break;
+            }
+        };
+        block_store.append(&block)?;
+        summary.blocks += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Devuelve el path del archivo de bloques con el numero recibido, con el nombre que usa Bitcoin Core.
+pub fn core_file_path(core_dir: &str, file: u32) -> String {
+    format!("{core_dir}/blk{file:05}.dat")
+}
+
+/// Agrega los headers del archivo HEADERS_FILE del directorio, si existe, y devuelve cuantos no se conocian.
+/// Devuelve CustomError si algun header no tiene una proof of work valida o no continua la cadena guardada.
+fn import_headers_file(core_dir: &str, headers: &mut HeadersState) -> Result<usize, CustomError> {
+    let path = format!("{core_dir}/{HEADERS_FILE}");
+    if !Path::new(&path).exists() {
+        return Ok(0);
+    }
+    let new_headers = fs::read(path)?
+        .chunks_exact(BLOCK_HEADER_SIZE)
+        .map(|buffer| BlockHeader::parse(buffer.to_vec()))
+        .collect::<Result<Vec<BlockHeader>, CustomError>>()?;
+
+    let mut appended = 0;
+    for batch in new_headers.chunks(HEADERS_BATCH_SIZE) {
+        appended += headers.append_headers(batch.to_vec())?.len();
+    }
+    Ok(appended)
+}
+
+/// Lee la clave con la que Bitcoin Core ofusca sus archivos de bloques (xor.dat, desde la version 28).
+/// Si no existe los archivos no estan ofuscados.
+fn read_xor_key(core_dir: &str) -> Result<Vec<u8>, CustomError> {
+    let path = format!("{core_dir}/xor.dat");
+    if !Path::new(&path).exists() {
+        return Ok(vec![]);
+    }
+    Ok(fs::read(path)?)
+}
+
+/// Lee un archivo de bloques de Bitcoin Core y le quita la ofuscacion: cada byte se combina con XOR
+/// con el byte de la clave que corresponde a su posicion en el archivo.
+fn read_core_file(core_dir: &str, file: u32, xor_key: &[u8]) -> Result<Vec<u8>, CustomError> {
+    let mut buffer = fs::read(core_file_path(core_dir, file))?;
+    if xor_key.iter().any(|byte| *byte != 0) {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte ^= xor_key[i % xor_key.len()];
+        }
+    }
+    Ok(buffer)
+}
+
+/// Agrega al indice los bloques del archivo, cuyos headers tienen una proof of work valida, y devuelve cuantos encontro.
+/// Bitcoin Core reserva espacio al final de los archivos completandolo con ceros, por lo que se deja de leer
+/// al encontrar un magic number que no es el de la red recibida o un bloque incompleto.
+fn scan_block_file(
+    buffer: &[u8],
+    file: u32,
+    network: Network,
+    index: &mut HashMap<Hash32, CoreBlock>,
+) -> usize {
+    let magic = network.magic().to_be_bytes();
+    let mut found = 0;
+    let mut offset = 0;
+    while offset + RECORD_HEADER_SIZE <= buffer.len() && buffer[offset..offset + 4] == magic {
+        let mut length = [0; 4];
+        length.copy_from_slice(&buffer[offset + 4..offset + RECORD_HEADER_SIZE]);
+        let length = u32::from_le_bytes(length) as usize;
+        let start = offset + RECORD_HEADER_SIZE;
+        if length < BLOCK_HEADER_SIZE || start + length > buffer.len() {
+            break;
+        }
+
+        if let Ok(header) = BlockHeader::parse(buffer[start..start + BLOCK_HEADER_SIZE].to_vec()) {
+            index.insert(
+                *header.hash(),
+                CoreBlock {
+                    header,
+                    file,
+                    offset: start,
+                    length,
+                },
+            );
+            found += 1;
+        }
+        offset = start + length;
+    }
+    found
+}
+
+/// Devuelve los hashes, en orden, de la cadena de bloques indexados con mas trabajo acumulado que continua al bloque start.
+/// Los bloques que no se conectan con start (ej: anteriores a el o de otra rama) se ignoran.
+fn best_chain(start: Hash32, index: &HashMap<Hash32, CoreBlock>) -> Vec<Hash32> {
+    // Trabajo acumulado desde start de cada bloque, None si no se conecta con start
+    let mut chain_work: HashMap<Hash32, Option<u128>> = HashMap::new();
+    for hash in index.keys() {
+        let mut path = vec![];
+        let mut current = *hash;
+        let mut work = loop {
+            if current == start {
+                break Some(0);
+            }
+            if let Some(work) = chain_work.get(&current) {
+                break *work;
+            }
+            let Some(core_block) = index.get(&current) else {
+                break None;
+            };
+            path.push(current);
+            current = core_block.header.prev_block_hash;
+        };
+        for hash in path.into_iter().rev() {
+            work = work.map(|work| work + index[&hash].header.work());
+            chain_work.insert(hash, work);
+        }
+    }
+
+    let Some(mut tip) = chain_work
+        .iter()
+        .filter_map(|(hash, work)| work.map(|work| (work, *hash)))
+        .max()
+        .map(|(_, hash)| hash)
+    else {
+        return vec![];
+    };
+
+    let mut chain = vec![];
+    while tip != start {
+        chain.push(tip);
+        tip = index[&tip].header.prev_block_hash;
+    }
+    chain.reverse();
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn test_block() -> Block {
+        let mut buffer = vec![];
+        fs::File::open("tests/blocks/test_block.bin")
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+        Block::parse(buffer).unwrap()
+    }
+
+    fn core_block(hash: u8, prev_hash: u8, bits: u32) -> (Hash32, CoreBlock) {
+        let header = BlockHeader {
+            version: 1,
+            prev_block_hash: Hash32::new([prev_hash; 32]),
+            merkle_root: Hash32::default(),
+            timestamp: 0,
+            bits,
+            nonce: 0,
+            hash: Hash32::new([hash; 32]),
+            block_downloaded: false,
+            broadcasted: false,
+        };
+        let core_block = CoreBlock {
+            header,
+            file: 0,
+            offset: 0,
+            length: 0,
+        };
+        (Hash32::new([hash; 32]), core_block)
+    }
+
+    #[test]
+    fn scan_obfuscated_core_block_file() {
+        let dir = "tests/core_import_scan";
+        fs::remove_dir_all(dir).ok();
+        fs::create_dir_all(dir).unwrap();
+        let block = test_block();
+        let data = block.serialize();
+
+        let mut buffer = vec![];
+        for _ in 0..2 {
+            buffer.extend(Network::Testnet.magic().to_be_bytes());
+            buffer.extend((data.len() as u32).to_le_bytes());
+            buffer.extend(&data);
+        }
+        // espacio reservado por Bitcoin Core al final del archivo
+        buffer.extend([0; 100]);
+        let xor_key = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let obfuscated: Vec<u8> = buffer
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ xor_key[i % xor_key.len()])
+            .collect();
+        fs::write(format!("{dir}/xor.dat"), &xor_key).unwrap();
+        fs::write(core_file_path(dir, 0), obfuscated).unwrap();
+
+        let xor_key = read_xor_key(dir).unwrap();
+        let buffer = read_core_file(dir, 0, &xor_key).unwrap();
+        let mut index = HashMap::new();
+        assert_eq!(scan_block_file(&buffer, 0, Network::Testnet, &mut index), 2);
+        assert_eq!(index.len(), 1);
+        let core_block = &index[block.header.hash()];
+        assert_eq!(
+            buffer[core_block.offset..core_block.offset + core_block.length],
+            data
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn best_chain_follows_the_most_work() {
+        let start = Hash32::new([1; 32]);
+        let index: HashMap<Hash32, CoreBlock> = [
+            core_block(2, 1, 0x1d00ffff),
+            // rama mas larga pero con menos trabajo
+            core_block(3, 2, 0x1d00ffff),
+            core_block(4, 3, 0x1d00ffff),
+            // rama mas corta pero con mas trabajo
+            core_block(5, 2, 0x1c00ffff),
+            // bloques que no se conectan con start
+            core_block(6, 7, 0x1b00ffff),
+            core_block(8, 9, 0x1b00ffff),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            best_chain(start, &index),
+            vec![Hash32::new([2; 32]), Hash32::new([5; 32])]
+        );
+        assert!(best_chain(Hash32::new([10; 32]), &index).is_empty());
+    }
+}
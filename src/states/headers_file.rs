@@ -0,0 +1,141 @@
+use std::{collections::HashMap, fs::File, sync::OnceLock};
+
+use memmap2::{Mmap, MmapOptions};
+
+use crate::{
+    error::CustomError,
+    structs::{block_header::BlockHeader, hash32::Hash32},
+};
+
+/// Tamaño de cada header en el archivo donde se guardan los headers: el header serializado seguido de su hash.
+pub const HEADER_BACKUP_SIZE: usize = 112;
+/// Posicion del timestamp dentro de cada header guardado.
+const TIMESTAMP_OFFSET: usize = 68;
+/// Posicion del hash dentro de cada header guardado.
+const HASH_OFFSET: usize = 80;
+
+/// HeadersFile es una vista de solo lectura de los headers guardados en el archivo de headers, mapeado en memoria.
+/// Los headers se leen del archivo a medida que se piden, por lo que al iniciar no hace falta cargarlos todos.
+/// Los elementos son:
+/// - map: Archivo mapeado en memoria, None si no tiene headers.
+/// - records_start: Posicion del primer header en el archivo, despues del encabezado.
+/// - len: Cantidad de headers mapeados.
+/// - index: Posicion de cada header mapeado indexada por su hash, que se construye la primera vez que se busca un header.
+pub struct HeadersFile {
+    map: Option<Mmap>,
+    records_start: usize,
+    len: usize,
+    index: OnceLock<HashMap<Hash32, usize>>,
+}
+
+impl HeadersFile {
+    /// Crea una vista sin headers.
+    pub fn empty() -> Self {
+        Self {
+            map: None,
+            records_start: 0,
+            len: 0,
+            index: OnceLock::new(),
+        }
+    }
+
+    /// Mapea en memoria los primeros len headers guardados en el archivo a partir de records_start.
+    /// Los headers mapeados ya tienen que estar verificados, ya que al leerlos no se vuelve a comparar su hash.
+    pub fn map(file: &File, records_start: usize, len: usize) -> Result<Self, CustomError> {
+        if len == 0 {
+            return Ok(Self::empty());
+        }
+        // El archivo es de solo agregado: los headers mapeados no se modifican ni se truncan mientras el nodo los usa.
+        let map = unsafe {
+            MmapOptions::new()
+                .len(records_start + len * HEADER_BACKUP_SIZE)
+                .map(file)?
+        };
+        Ok(Self {
+            map: Some(map),
+            records_start,
+            len,
+            index: OnceLock::new(),
+        })
+    }
+
+    /// Devuelve la cantidad de headers mapeados.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Indica si no hay headers mapeados.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn record(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.len {
+            return None;
+        }
+        let start = self.records_start + index * HEADER_BACKUP_SIZE;
+        self.map
+            .as_ref()
+            .map(|map| &map[start..start + HEADER_BACKUP_SIZE])
+    }
+
+    /// Devuelve el header en la posicion recibida, leyendolo del archivo.
+    pub fn get(&self, index: usize) -> Option<BlockHeader> {
+        let record = self.record(index)?;
+        BlockHeader::parse_backup_record(record.to_vec()).ok()
+    }
+
+    /// Devuelve el hash del header en la posicion recibida, sin parsear el resto del header.
+    pub fn hash(&self, index: usize) -> Option<Hash32> {
+        let record = self.record(index)?;
+        Hash32::from_slice(&record[HASH_OFFSET..]).ok()
+    }
+
+    /// Devuelve el timestamp del header en la posicion recibida, sin parsear el resto del header.
+    pub fn timestamp(&self, index: usize) -> Option<u32> {
+        let record = self.record(index)?;
+        let bytes = record[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 4]
+            .try_into()
+            .ok()?;
+        Some(u32::from_le_bytes(bytes))
+    }
+
+    /// Devuelve la posicion del header con el hash recibido, None si no esta entre los mapeados.
+    /// La primera busqueda recorre el archivo para construir el indice.
+    pub fn position(&self, block_hash: &Hash32) -> Option<usize> {
+        self.index
+            .get_or_init(|| {
+                (0..self.len)
+                    .filter_map(|index| self.hash(index).map(|hash| (hash, index)))
+                    .collect()
+            })
+            .get(block_hash)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::states::store_file::STORE_HEADER_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn read_mapped_headers() {
+        let file = File::open("tests/test_headers.bin").unwrap();
+        let headers = HeadersFile::map(&file, STORE_HEADER_SIZE, 2).unwrap();
+        assert_eq!(headers.len(), 2);
+
+        let second = headers.get(1).unwrap();
+        assert_eq!(headers.hash(1), Some(second.hash));
+        assert_eq!(headers.timestamp(1), Some(second.timestamp));
+        assert_eq!(second.prev_block_hash, headers.hash(0).unwrap());
+        assert_eq!(headers.position(&second.hash), Some(1));
+        assert_eq!(headers.position(&Hash32::default()), None);
+        assert!(headers.get(2).is_none());
+
+        assert!(HeadersFile::map(&file, STORE_HEADER_SIZE, 0)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,22 +1,47 @@
 use std::{
-    io::{Read, Write},
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
     sync::mpsc::Sender,
 };
 
+use tracing::instrument;
+
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
-    messages::get_headers::GetHeaders,
+    logger::{send_log, Log, LogTarget},
+    messages::{get_headers::GetHeaders, transaction::LockPoint},
+    network::Network,
     parser::BufferParser,
-    peer::GENESIS,
-    structs::block_header::BlockHeader,
+    structs::{block_header::BlockHeader, hash32::Hash32},
     utils::{
-        calculate_index_from_timestamp, get_current_timestamp, get_current_timestamp_millis,
-        open_new_file,
+        get_current_timestamp, get_current_timestamp_millis, open_new_file, write_file_atomically,
     },
 };
 
-use super::utxo_state::START_DATE_IBD;
+use super::{
+    headers_file::{HeadersFile, HEADER_BACKUP_SIZE},
+    store_file::{read_store_file_header, store_file_header, StoreFileKind, STORE_HEADER_SIZE},
+    utxo_snapshot::SnapshotBase,
+    utxo_state::START_DATE_IBD,
+};
+
+/// Cantidad de headers al final del archivo que se verifican al iniciar, ya que los anteriores se escribieron
+/// antes del ultimo flush. Si el primero de ellos es invalido se verifican mas hacia atras.
+const VERIFIED_TAIL_SIZE: usize = 2000;
+
+/// Cantidad maxima de headers de un mensaje headers, recibir menos indica que se llego al ultimo header del peer.
+const MAX_HEADERS: usize = 2000;
+
+/// Cantidad de bloques anteriores con cuyos timestamps se calcula el median time past (BIP113).
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Cada cuantos bloques se ajusta la dificultad.
+const DIFFICULTY_ADJUSTMENT_INTERVAL: usize = 2016;
+/// Tiempo en segundos que deberia tardar en minarse un periodo de DIFFICULTY_ADJUSTMENT_INTERVAL bloques.
+const TARGET_TIMESPAN: u64 = 14 * 24 * 60 * 60;
+/// Tiempo en segundos que deberia tardar en minarse un bloque.
+const TARGET_SPACING: u32 = 10 * 60;
 
 /// HeaderIBDStats es una estructura que contiene los elementos necesarios para manejar las
 /// estadisticas de la descarga masiva de headers.
@@ -33,155 +58,513 @@ struct HeaderIBDStats {
 }
 
 /// HeadersState es una estructura que contiene los elementos necesarios para manejar los headers.
+/// Los headers guardados al iniciar se leen del archivo mapeado en memoria a medida que se piden, y solo los
+/// agregados durante la ejecucion se mantienen en memoria. La posicion de cada header es la de toda la cadena:
+/// primero los del archivo y despues los agregados.
 /// Los elementos son:
-/// - headers: Headers del nodo.
+/// - file: Headers guardados en el archivo al iniciar, con su bloque descargado y ya enviados.
+/// - headers: Headers agregados despues de los del archivo.
+/// - index: Posicion de cada header de headers, indexada por su hash. La de los headers del archivo la indexa el archivo.
 /// - logger_sender: Sender para enviar logs al logger.
 /// - path: Path del archivo donde se guardan los headers.
+/// - network: Red de los headers, determina el bloque genesis y las reglas de dificultad.
 /// - ibd_stats: Option<HeaderIBDStats> solamente se inicializa cuando corresponde.
 /// - sync: Indica si los headers del nodo estan sincronizados con la red.
+/// - assume_valid: Hash del bloque configurado cuyos ancestros se asumen validos (ASSUME_VALID).
+/// - snapshot_base: Bloque del snapshot de UTXO desde el que se inicio el nodo, si se inicio desde uno.
+/// - unsaved: Cantidad de headers al final de headers que todavia no se escribieron en el archivo.
 pub struct HeadersState {
+    file: HeadersFile,
     headers: Vec<BlockHeader>,
+    index: HashMap<Hash32, usize>,
     logger_sender: Sender<Log>,
     path: String,
+    network: Network,
     ibd_stats: Option<HeaderIBDStats>,
     sync: bool,
+    assume_valid: Option<Hash32>,
+    snapshot_base: Option<SnapshotBase>,
+    unsaved: usize,
 }
 
 impl HeadersState {
     /// Inicializa los headers del nodo.
     /// Si el archivo donde se guardan los headers no existe, se crea.
     /// Si el archivo existe, se restauran los headers.
-    pub fn new(path: String, logger_sender: Sender<Log>) -> Result<Self, CustomError> {
+    pub fn new(
+        path: String,
+        logger_sender: Sender<Log>,
+        network: Network,
+    ) -> Result<Self, CustomError> {
         let mut headers = Self {
+            file: HeadersFile::empty(),
             headers: Vec::new(),
+            index: HashMap::new(),
             logger_sender: logger_sender.clone(),
             path,
+            network,
             ibd_stats: None,
             sync: false,
+            assume_valid: None,
+            snapshot_base: None,
+            unsaved: 0,
         };
 
         headers.restore()?;
 
         send_log(
             &logger_sender,
-            Log::Message(format!("Total headers restored: {}", headers.len())),
+            Log::Info(
+                LogTarget::Sync,
+                format!("Total headers restored: {}", headers.len()),
+            ),
         );
         Ok(headers)
     }
 
+    /// Restaura los headers guardados en el archivo, que comienza con el encabezado de los archivos del store.
+    /// Los headers no se cargan en memoria: el archivo se mapea y cada header se lee cuando se pide.
+    /// Como el archivo es de solo agregado no lleva un checksum del contenido: cada header se verifica con su hash guardado.
+    /// Al iniciar solo se verifican los ultimos VERIFIED_TAIL_SIZE headers, que son los que pueden haber quedado
+    /// a medio escribir por un cierre inesperado. Si alguno es invalido se trunca el archivo desde ese header en adelante,
+    /// y los headers descartados se vuelven a descargar.
+    /// Un archivo de una version anterior del formato se verifica completo, se migra y se reescribe con el formato actual.
+    /// Devuelve CustomError::StoreFileIncompatible si el archivo es de otro tipo o de una version mas nueva.
     fn restore(&mut self) -> Result<(), CustomError> {
-        let mut buffer = vec![];
         let mut file = open_new_file(self.path.clone(), true)?;
-        file.read_to_end(&mut buffer)?;
+        let file_len = file.metadata()?.len() as usize;
+        let mut buffer = vec![0; STORE_HEADER_SIZE.min(file_len)];
+        file.read_exact(&mut buffer)?;
+        let version =
+            read_store_file_header(StoreFileKind::Headers, &mut BufferParser::new(buffer))?;
+
+        let valid_count = if version < StoreFileKind::Headers.version() {
+            let valid_count = self.migrate(version, &mut file, file_len)?;
+            file = open_new_file(self.path.clone(), true)?;
+            valid_count
+        } else {
+            let records_len = file_len - STORE_HEADER_SIZE;
+            let valid_count = verified_records_count(&mut file, records_len / HEADER_BACKUP_SIZE)?;
+            if valid_count * HEADER_BACKUP_SIZE < records_len {
+                self.warn_corrupt_headers(valid_count);
+                file.set_len((STORE_HEADER_SIZE + valid_count * HEADER_BACKUP_SIZE) as u64)?;
+            }
+            valid_count
+        };
 
+        self.file = HeadersFile::map(&file, STORE_HEADER_SIZE, valid_count)?;
+        Ok(())
+    }
+
+    /// Verifica todos los headers de un archivo de una version anterior del formato, y lo reescribe con el formato actual
+    /// descartando los headers desde el primero invalido.
+    /// Devuelve la cantidad de headers validos.
+    fn migrate(
+        &self,
+        version: u32,
+        file: &mut File,
+        file_len: usize,
+    ) -> Result<usize, CustomError> {
+        let mut buffer = vec![];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut buffer)?;
         let mut parser = BufferParser::new(buffer);
-        if parser.len() % 112 != 0 {
-            return Err(CustomError::SerializedBufferIsInvalid);
+        read_store_file_header(StoreFileKind::Headers, &mut parser)?;
+        let records_len = parser.len();
+        let records = StoreFileKind::Headers
+            .migrate(version, parser.extract_buffer(records_len)?.to_vec())?;
+
+        let valid_count = valid_records_count(&records);
+        if valid_count * HEADER_BACKUP_SIZE < records.len() {
+            self.warn_corrupt_headers(valid_count);
         }
 
-        while !parser.is_empty() {
-            let header = BlockHeader::parse_from_backup(parser.extract_buffer(112)?.to_vec())?;
-            self.headers.push(header);
+        let mut buffer = store_file_header(StoreFileKind::Headers);
+        buffer.extend(&records[..valid_count * HEADER_BACKUP_SIZE]);
+        write_file_atomically(&self.path, &buffer)?;
+        if file_len > 0 {
+            send_log(
+                &self.logger_sender,
+                Log::Info(
+                    LogTarget::Sync,
+                    format!(
+                        "Headers file migrated from format version {version} to {}",
+                        StoreFileKind::Headers.version()
+                    ),
+                ),
+            );
         }
+        Ok(valid_count)
+    }
 
-        Ok(())
+    fn warn_corrupt_headers(&self, valid_count: usize) {
+        send_log(
+            &self.logger_sender,
+            Log::Warn(
+                LogTarget::Sync,
+                format!("Headers file is corrupt after {valid_count} headers, discarding the rest"),
+            ),
+        );
     }
 
-    fn save(&self, headers: &Vec<BlockHeader>) -> Result<(), CustomError> {
-        let mut file = open_new_file(self.path.clone(), true)?;
+    /// Agrega al archivo los headers que todavia no se guardaron y fuerza su escritura en disco.
+    /// Si no hay headers sin guardar no hace nada.
+    pub fn flush(&mut self) -> Result<(), CustomError> {
+        if self.unsaved == 0 {
+            return Ok(());
+        }
         let mut buffer = vec![];
-        for header in headers {
-            let header_buffer: Vec<u8> = header.serialize_for_backup();
-            buffer.extend(header_buffer);
+        for header in &self.headers[self.headers.len() - self.unsaved..] {
+            buffer.extend(header.serialize_for_backup());
         }
 
+        let mut file = open_new_file(self.path.clone(), true)?;
         file.write_all(buffer.as_slice())?;
+        file.sync_all()?;
+        self.unsaved = 0;
         Ok(())
     }
 
-    fn len(&self) -> usize {
-        self.headers.len()
+    /// Devuelve la cantidad de headers del nodo.
+    pub fn len(&self) -> usize {
+        self.file.len() + self.headers.len()
+    }
+
+    /// Indica si el nodo no tiene headers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Devuelve la red de los headers.
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    /// Devuelve el header en la posicion recibida, None si no existe.
+    pub fn get(&self, index: usize) -> Option<BlockHeader> {
+        match index.checked_sub(self.file.len()) {
+            Some(appended_index) => self.headers.get(appended_index).cloned(),
+            None => self.file.get(index),
+        }
+    }
+
+    fn get_hash(&self, index: usize) -> Option<Hash32> {
+        match index.checked_sub(self.file.len()) {
+            Some(appended_index) => self.headers.get(appended_index).map(|header| header.hash),
+            None => self.file.hash(index),
+        }
+    }
+
+    fn get_timestamp(&self, index: usize) -> Option<u32> {
+        match index.checked_sub(self.file.len()) {
+            Some(appended_index) => self
+                .headers
+                .get(appended_index)
+                .map(|header| header.timestamp),
+            None => self.file.timestamp(index),
+        }
+    }
+
+    fn position(&self, block_hash: &Hash32) -> Option<usize> {
+        self.index
+            .get(block_hash)
+            .copied()
+            .or_else(|| self.file.position(block_hash))
+    }
+
+    /// Devuelve la altura del bloque con el hash recibido, 0 para el genesis, o None si no se encuentra.
+    pub fn get_height(&self, block_hash: &Hash32) -> Option<usize> {
+        if *block_hash == self.network.genesis() {
+            return Some(0);
+        }
+        self.position(block_hash).map(|index| index + 1)
+    }
+
+    /// Devuelve los headers desde la posicion recibida hasta el ultimo.
+    pub fn iter_from(&self, start: usize) -> impl Iterator<Item = BlockHeader> + '_ {
+        (start..self.len()).filter_map(|index| self.get(index))
+    }
+
+    /// Devuelve la posicion del ultimo header con timestamp anterior a START_DATE_IBD, desde el que se descargan los bloques.
+    /// Si no hay ninguno devuelve 0.
+    pub fn first_block_index(&self) -> usize {
+        (0..self.len())
+            .rev()
+            .find(|index| {
+                self.get_timestamp(*index)
+                    .is_some_and(|timestamp| timestamp <= START_DATE_IBD)
+            })
+            .unwrap_or(0)
     }
 
     /// Devuelve la cantidad de headers posteriores a la fecha de inicio del IBD.
     pub fn total_headers_to_download(&self) -> usize {
-        self.len() - calculate_index_from_timestamp(&self.headers, START_DATE_IBD)
+        self.len() - self.first_block_index()
     }
 
-    /// Devuelve todos los headers del nodo.
-    pub fn get_all(&self) -> &Vec<BlockHeader> {
-        &self.headers
+    /// Indica si el header con el hash recibido ya esta almacenado.
+    pub fn contains(&self, block_hash: &Hash32) -> bool {
+        self.position(block_hash).is_some()
     }
 
-    /// Devuelve la posicion de un header en el vector de headers del nodo dado el hash del mismo.
+    /// Devuelve la posicion de un header en la cadena de headers del nodo dado el hash del mismo.
     /// Si no se encuentra el header, devuelve 0.
-    pub fn get_header_index(&self, block_hash: &Vec<u8>) -> usize {
-        let position_from_end = self
-            .headers
-            .iter()
-            .rev()
-            .position(|header| header.hash() == block_hash);
+    pub fn get_header_index(&self, block_hash: &Hash32) -> usize {
+        self.position(block_hash).unwrap_or(0)
+    }
+
+    /// Devuelve el timestamp del header con el hash recibido, o None si no se encuentra.
+    pub fn get_block_timestamp(&self, block_hash: &Hash32) -> Option<u32> {
+        self.position(block_hash)
+            .and_then(|index| self.get_timestamp(index))
+    }
+
+    /// Devuelve la cantidad de bloques que hay por encima del bloque con el hash recibido, 0 si es el ultimo.
+    /// Si no se encuentra el header, devuelve None.
+    pub fn get_depth(&self, block_hash: &Hash32) -> Option<usize> {
+        self.position(block_hash)
+            .map(|index| self.len() - index - 1)
+    }
+
+    /// Devuelve hasta count headers a partir del header con el hash recibido (incluido) junto a su height.
+    /// Si no se encuentra el header, devuelve None.
+    pub fn get_headers_from(
+        &self,
+        block_hash: &Hash32,
+        count: usize,
+    ) -> Option<Vec<(usize, BlockHeader)>> {
+        let start = self.position(block_hash)?;
+
+        Some(
+            self.iter_from(start)
+                .take(count)
+                .enumerate()
+                .map(|(offset, header)| (start + offset + 1, header))
+                .collect(),
+        )
+    }
+
+    /// Devuelve los hashes de los bloques desde la altura start_height hasta el bloque stop_hash (incluido).
+    /// Devuelve None si no se encuentra stop_hash, si esta antes de start_height o si son mas de max bloques.
+    pub fn get_hashes_range(
+        &self,
+        start_height: usize,
+        stop_hash: &Hash32,
+        max: usize,
+    ) -> Option<Vec<Hash32>> {
+        let stop_index = self.position(stop_hash)?;
+        let start_index = start_height.checked_sub(1)?;
+        if start_index > stop_index || stop_index - start_index >= max {
+            return None;
+        }
+
+        (start_index..=stop_index)
+            .map(|index| self.get_hash(index))
+            .collect()
+    }
+
+    /// Establece el bloque cuyos ancestros se asumen validos, None para verificar todos los bloques.
+    pub fn set_assume_valid(&mut self, assume_valid: Option<Hash32>) {
+        self.assume_valid = assume_valid;
+    }
+
+    /// Indica si el bloque es el bloque configurado en ASSUME_VALID o uno de sus ancestros, en cuyo caso
+    /// no se verifican sus scripts, como hace Bitcoin Core.
+    /// Si el bloque configurado todavia no esta entre los headers, no se asume valido ningun bloque.
+    pub fn is_assumed_valid(&self, block_hash: &Hash32) -> bool {
+        let Some(assume_valid) = self.assume_valid else {
+            return false;
+        };
+        let Some(assume_valid_depth) = self.get_depth(&assume_valid) else {
+            return false;
+        };
+        self.get_depth(block_hash)
+            .is_some_and(|depth| depth >= assume_valid_depth)
+    }
+
+    /// Establece el bloque del snapshot de UTXO desde el que se inicio el nodo, None si se descargan todos los bloques.
+    pub fn set_snapshot_base(&mut self, snapshot_base: Option<SnapshotBase>) {
+        self.snapshot_base = snapshot_base;
+    }
+
+    /// Devuelve el bloque del snapshot de UTXO desde el que se inicio el nodo, si se inicio desde uno.
+    pub fn get_snapshot_base(&self) -> Option<SnapshotBase> {
+        self.snapshot_base
+    }
+
+    /// Indica si los headers son compatibles con el snapshot de UTXO: si el header a la altura del snapshot es su bloque.
+    /// Mientras los headers no lleguen a esa altura no se puede verificar, y se consideran compatibles.
+    pub fn snapshot_base_matches(&self) -> bool {
+        let Some(base) = self.snapshot_base else {
+            return true;
+        };
+        match base
+            .height
+            .checked_sub(1)
+            .and_then(|index| self.get_hash(index))
+        {
+            Some(block_hash) => block_hash == base.block_hash,
+            None => true,
+        }
+    }
+
+    /// Indica si el bloque esta a la altura del snapshot de UTXO o antes, en cuyo caso no hace falta descargarlo
+    /// porque sus salidas ya estan en el snapshot.
+    pub fn is_before_snapshot(&self, block_hash: &Hash32) -> bool {
+        let Some(base) = self.snapshot_base else {
+            return false;
+        };
+        self.position(block_hash)
+            .is_some_and(|index| index < base.height)
+    }
+
+    /// Devuelve la altura y el median time past (BIP113) del bloque a la altura recibida, que puede ser el siguiente al ultimo.
+    /// El median time past es la mediana de los timestamps de los MEDIAN_TIME_SPAN bloques anteriores.
+    pub fn get_lock_point(&self, height: u32) -> LockPoint {
+        let end = (height as usize).saturating_sub(1).min(self.len());
+        let start = end.saturating_sub(MEDIAN_TIME_SPAN);
+        let mut timestamps: Vec<u32> = (start..end)
+            .filter_map(|index| self.get_timestamp(index))
+            .collect();
+        timestamps.sort_unstable();
+        LockPoint {
+            height,
+            median_time_past: timestamps.get(timestamps.len() / 2).copied().unwrap_or(0),
+        }
+    }
+
+    /// Devuelve la altura y el median time past del bloque siguiente al ultimo header, en el que se incluirian
+    /// las transacciones pendientes.
+    pub fn get_next_lock_point(&self) -> LockPoint {
+        self.get_lock_point(self.len() as u32 + 1)
+    }
+
+    /// Devuelve los bits de dificultad que debe tener el bloque siguiente al ultimo header, si tiene el timestamp recibido.
+    /// La dificultad se ajusta cada DIFFICULTY_ADJUSTMENT_INTERVAL bloques segun cuanto tardo en minarse el periodo anterior,
+    /// y en la regtest no se ajusta. En la testnet un bloque puede tener la dificultad minima si su timestamp supera
+    /// en el doble de TARGET_SPACING al del anterior, y si no, la del ultimo bloque que no la tuvo por esa regla.
+    pub fn get_next_bits(&self, timestamp: u32) -> u32 {
+        let network = self.network;
+        let pow_limit = network.pow_limit_bits();
+        let Some(last) = self.len().checked_sub(1).and_then(|index| self.get(index)) else {
+            return pow_limit;
+        };
+        // el header en la posicion index es el del bloque a la altura index + 1, y el siguiente esta a la altura len + 1
+        let next_height = self.len() + 1;
+        if network == Network::Regtest {
+            return last.bits;
+        }
 
-        if let Some(position_from_end) = position_from_end {
-            return self.headers.len() - position_from_end - 1;
+        if !next_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+            if network != Network::Testnet {
+                return last.bits;
+            }
+            if timestamp > last.timestamp + 2 * TARGET_SPACING {
+                return pow_limit;
+            }
+            let mut index = self.len() - 1;
+            while index > 0 && !(index + 1).is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+                match self.get(index) {
+                    Some(header) if header.bits == pow_limit => index -= 1,
+                    _ => break,
+                }
+            }
+            return self.get(index).map_or(last.bits, |header| header.bits);
         }
 
-        0
+        // el primer bloque del periodo esta a la altura next_height - DIFFICULTY_ADJUSTMENT_INTERVAL
+        let Some(first) = (next_height - DIFFICULTY_ADJUSTMENT_INTERVAL)
+            .checked_sub(1)
+            .and_then(|index| self.get(index))
+        else {
+            return last.bits;
+        };
+        let timespan = last.timestamp.saturating_sub(first.timestamp) as u64;
+        retarget_bits(last.bits, timespan, pow_limit)
     }
 
     /// Devuelve el hash del ultimo header del nodo.
-    pub fn get_last_header_hash(&self) -> Option<Vec<u8>> {
-        self.headers.last().map(|header| header.hash().clone())
+    pub fn get_last_header_hash(&self) -> Option<Hash32> {
+        self.len()
+            .checked_sub(1)
+            .and_then(|index| self.get_hash(index))
     }
 
     /// Devuelve los ultimos count headers del nodo junto a su height.
     pub fn get_last_headers(&self, count: usize) -> Vec<(usize, BlockHeader)> {
-        let mut last_headers = vec![];
-
-        self.headers
-            .iter()
-            .enumerate()
+        (0..self.len())
             .rev()
             .take(count)
-            .for_each(|(index, header)| last_headers.push((index + 1, header.clone())));
+            .filter_map(|index| self.get(index).map(|header| (index + 1, header)))
+            .collect()
+    }
 
-        last_headers
+    /// Devuelve el header del bloque a la altura recibida, None si no existe.
+    /// La posicion de cada header es su altura menos uno, ya que el genesis (altura 0) no se almacena.
+    pub fn get_header_by_height(&self, height: usize) -> Option<BlockHeader> {
+        self.get(height.checked_sub(1)?)
     }
 
-    /// Agrega los headers al nodo y los almacena.
-    /// Tambien verifica si con los nuevos queda sincronizado con la red
-    pub fn append_headers(&mut self, mut headers: Vec<BlockHeader>) -> Result<(), CustomError> {
-        if let Some(first_header) = headers.first() {
-            let last_header = self.headers.last();
-            let last_header_hash = last_header
-                .map(|header| header.hash().clone())
-                .unwrap_or(GENESIS.to_vec());
+    /// Devuelve el hash del bloque a la altura recibida, el del genesis para la altura 0, o None si no existe.
+    pub fn get_hash_by_height(&self, height: usize) -> Option<Hash32> {
+        match height.checked_sub(1) {
+            Some(index) => self.get_hash(index),
+            None => Some(self.network.genesis()),
+        }
+    }
 
-            if last_header_hash != first_header.prev_block_hash {
-                return Err(CustomError::BlockChainBroken);
+    /// Agrega los headers al nodo, descartando en silencio los que ya estaban almacenados.
+    /// Los headers agregados se escriben en el archivo en el proximo flush.
+    /// Devuelve los headers agregados.
+    /// Devuelve CustomError::UnknownParentHeader si el padre de algun header es desconocido, y
+    /// CustomError::BlockChainBroken si el padre es conocido pero no es el ultimo header (ej: una bifurcacion).
+    /// Tambien verifica si con los nuevos queda sincronizado con la red
+    #[instrument(name = "header_batch", skip_all, fields(count = headers.len()))]
+    pub fn append_headers(
+        &mut self,
+        headers: Vec<BlockHeader>,
+    ) -> Result<Vec<BlockHeader>, CustomError> {
+        let batch_count = headers.len();
+        let headers: Vec<BlockHeader> = headers
+            .into_iter()
+            .filter(|header| !self.contains(header.hash()))
+            .collect();
+
+        let mut previous_hash = self
+            .get_last_header_hash()
+            .unwrap_or(self.network.genesis());
+        for header in &headers {
+            if header.prev_block_hash != previous_hash {
+                return match self.contains(&header.prev_block_hash)
+                    || header.prev_block_hash == self.network.genesis()
+                {
+                    true => Err(CustomError::BlockChainBroken),
+                    false => Err(CustomError::UnknownParentHeader),
+                };
             }
+            previous_hash = *header.hash();
+        }
 
+        if let Some(first_header) = headers.first() {
             let percentage = self.calculate_percentage_downloaded(first_header.timestamp)?;
             if self.ibd_stats.is_none() && percentage < 95_u64 {
                 self.start_stats_printing()?;
             }
         }
 
-        self.save(&headers)?;
-        let headers_count = headers.len();
-        self.headers.append(&mut headers);
+        for header in &headers {
+            self.index.insert(*header.hash(), self.len());
+            self.headers.push(header.clone());
+        }
+        self.unsaved += headers.len();
 
-        self.print_status(headers_count)?;
-        self.verify_headers_sync(headers_count)?;
-        Ok(())
+        self.print_status(headers.len())?;
+        self.verify_headers_sync(batch_count)?;
+        Ok(headers)
     }
 
     fn calculate_percentage_downloaded(&self, received_timestamp: u32) -> Result<u64, CustomError> {
-        let first_timestamp = self
-            .headers
-            .first()
-            .map(|header| header.timestamp)
-            .unwrap_or(received_timestamp) as u64;
+        let first_timestamp = self.get_timestamp(0).unwrap_or(received_timestamp) as u64;
 
         let now = get_current_timestamp()?;
 
@@ -206,11 +589,10 @@ impl HeadersState {
         if self.is_synced() || self.ibd_stats.is_none() {
             send_log(
                 &self.logger_sender,
-                Log::Message(format!(
-                    "New headers: {}, total {}",
-                    headers_count,
-                    self.headers.len()
-                )),
+                Log::Info(
+                    LogTarget::Sync,
+                    format!("New headers: {}, total {}", headers_count, self.len()),
+                ),
             );
         } else {
             self.print_stats(headers_count)?;
@@ -220,8 +602,13 @@ impl HeadersState {
     }
 
     fn print_stats(&mut self, headers_count: usize) -> Result<(), CustomError> {
-        let last_timestamp = self.headers.last().map(|h| h.timestamp).unwrap_or(0);
+        let last_timestamp = self
+            .len()
+            .checked_sub(1)
+            .and_then(|index| self.get_timestamp(index))
+            .unwrap_or(0);
         let percentage = self.calculate_percentage_downloaded(last_timestamp)?;
+        let total = self.len();
 
         if let Some(ibd_stats) = &mut self.ibd_stats {
             ibd_stats.checkpoint_downloads += headers_count as u128;
@@ -237,12 +624,13 @@ impl HeadersState {
                 };
                 send_log(
                     &self.logger_sender,
-                    Log::Message(format!(
-                        "Headers sync {}% at {} headers/s... total {}",
-                        percentage,
-                        headers_per_second,
-                        self.headers.len(),
-                    )),
+                    Log::Info(
+                        LogTarget::Sync,
+                        format!(
+                            "Headers sync {}% at {} headers/s... total {}",
+                            percentage, headers_per_second, total,
+                        ),
+                    ),
                 );
 
                 ibd_stats.checkpoint_downloads = 0;
@@ -255,12 +643,12 @@ impl HeadersState {
     }
 
     /// Registra que un header tiene su bloque descargado.
-    pub fn set_downloaded(&mut self, block_hash: &Vec<u8>) {
+    /// Los headers del archivo ya tienen su bloque descargado.
+    pub fn set_downloaded(&mut self, block_hash: &Hash32) {
         let downloaded_block = self
-            .headers
-            .iter_mut()
-            .rev()
-            .find(|header| header.hash() == block_hash);
+            .index
+            .get(block_hash)
+            .and_then(|index| self.headers.get_mut(index - self.file.len()));
 
         if let Some(header) = downloaded_block {
             header.block_downloaded = true;
@@ -270,18 +658,25 @@ impl HeadersState {
     /// Devuelve un vector de headers que se deben enviar a un nodo a partir de un header hash.
     /// Los headers se envian unicamente si tienen al bloque anterior a ellos enviado y su bloque descargado.
     /// Todos los headers obtenidos se marcan como enviados.
-    pub fn get_headers_to_send(&mut self, block_hash: &Vec<u8>) -> Vec<BlockHeader> {
+    pub fn get_headers_to_send(&mut self, block_hash: &Hash32) -> Vec<BlockHeader> {
         let downloaded_block_index = self.get_header_index(block_hash);
 
         let mut next_to_send = downloaded_block_index == 0;
         if !next_to_send {
-            let downloaded_block_prev = self.headers[downloaded_block_index - 1].clone();
-            next_to_send = downloaded_block_prev.broadcasted
+            next_to_send = match (downloaded_block_index - 1).checked_sub(self.file.len()) {
+                Some(appended_index) => self.headers[appended_index].broadcasted,
+                None => true,
+            };
         };
 
         let mut headers_to_send = vec![];
         if next_to_send {
-            for header in self.headers.iter_mut().skip(downloaded_block_index) {
+            // los headers del archivo tienen su bloque descargado y ya estan marcados como enviados
+            headers_to_send.extend(
+                (downloaded_block_index..self.file.len()).filter_map(|index| self.file.get(index)),
+            );
+            let appended_start = downloaded_block_index.saturating_sub(self.file.len());
+            for header in self.headers.iter_mut().skip(appended_start) {
                 if header.block_downloaded {
                     headers_to_send.push(header.clone());
                     header.broadcasted = true;
@@ -298,11 +693,11 @@ impl HeadersState {
             return Ok(());
         }
 
-        self.sync = new_headers_count < 2000;
+        self.sync = new_headers_count < MAX_HEADERS;
         if self.sync {
             send_log(
                 &self.logger_sender,
-                Log::Message("headers sync completed".to_string()),
+                Log::Info(LogTarget::Sync, "headers sync completed".to_string()),
             );
         }
         Ok(())
@@ -313,59 +708,100 @@ impl HeadersState {
         self.sync
     }
 
-    /// Ante un mensaje get headers, devuelve los headers esperados de acuerdo al protocolo btc.
+    /// Ante un mensaje get headers, devuelve los headers esperados de acuerdo al protocolo btc: los siguientes al primer
+    /// hash del block locator que esta en la cadena, hasta MAX_HEADERS o hasta hash_stop (incluido).
+    /// Los hashes del locator van del mas nuevo al mas viejo, y cada uno se busca en el indice por hash, sin recorrer los headers.
+    /// Si no se conoce ninguno se devuelven desde el primero despues del genesis.
     pub fn get_headers(&self, get_headers: GetHeaders) -> Vec<BlockHeader> {
-        let peer_last_header = get_headers
+        let start = get_headers
             .block_locator_hashes
-            .last()
-            .unwrap_or(&GENESIS.to_vec())
-            .clone();
-        if let Some(last_header) = self.headers.last() {
-            if peer_last_header == *last_header.hash() {
-                return vec![];
+            .iter()
+            .find_map(|hash| self.get_height(hash))
+            .unwrap_or(0);
+
+        let mut headers = vec![];
+        for header in self.iter_from(start).take(MAX_HEADERS) {
+            let is_stop = *header.hash() == get_headers.hash_stop;
+            headers.push(header);
+            if is_stop {
+                break;
             }
         }
+        headers
+    }
+}
 
-        if peer_last_header == GENESIS.to_vec() {
-            return self.first_headers(get_headers.hash_stop);
+/// Verifica los ultimos headers del archivo, desde los ultimos VERIFIED_TAIL_SIZE y extendiendo la verificacion hacia
+/// atras mientras el primero verificado sea invalido, y devuelve la cantidad de headers validos desde el comienzo.
+fn verified_records_count(file: &mut File, records_count: usize) -> Result<usize, CustomError> {
+    let mut verified_size = VERIFIED_TAIL_SIZE;
+    loop {
+        let start = records_count.saturating_sub(verified_size);
+        file.seek(SeekFrom::Start(
+            (STORE_HEADER_SIZE + start * HEADER_BACKUP_SIZE) as u64,
+        ))?;
+        let mut records = vec![];
+        file.read_to_end(&mut records)?;
+
+        let valid_count = valid_records_count(&records);
+        if valid_count > 0 || start == 0 {
+            return Ok(start + valid_count);
         }
-
-        self.get_requested_headers(peer_last_header, get_headers.hash_stop)
+        verified_size *= 2;
     }
+}
 
-    fn get_requested_headers(
-        &self,
-        peer_last_header: Vec<u8>,
-        hash_stop: Vec<u8>,
-    ) -> Vec<BlockHeader> {
-        let mut headers = vec![];
-        let mut found = false;
-        for header in &self.headers {
-            if header.prev_block_hash == peer_last_header {
-                found = true;
-            }
-            if found {
-                headers.push(header.clone());
-            }
-            if headers.len() == 2000 || *header.hash() == hash_stop {
-                break;
-            }
+/// Devuelve la cantidad de headers validos al comienzo de los registros: cada uno tiene que coincidir con su hash guardado
+/// y continuar la cadena del anterior.
+fn valid_records_count(records: &[u8]) -> usize {
+    let mut previous_hash = None;
+    let mut count = 0;
+    for record in records.chunks_exact(HEADER_BACKUP_SIZE) {
+        let Ok(header) = BlockHeader::parse_from_backup(record.to_vec()) else {
+            break;
+        };
+        if previous_hash.is_some_and(|previous_hash| previous_hash != header.prev_block_hash) {
+            break;
         }
+        previous_hash = Some(header.hash);
+        count += 1;
+    }
+    count
+}
 
-        if !found {
-            return self.first_headers(hash_stop);
-        }
-        headers
+/// Ajusta la dificultad de los bits recibidos segun cuanto tardo en minarse el ultimo periodo, como lo hace Bitcoin Core:
+/// el nuevo target es target * timespan / TARGET_TIMESPAN, con el timespan limitado a un cuarto y al cuadruple de
+/// TARGET_TIMESPAN, y sin superar el target de pow_limit.
+fn retarget_bits(bits: u32, timespan: u64, pow_limit: u32) -> u32 {
+    let timespan = timespan.clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4) as u128;
+    let exponent = (bits >> 24) as i32;
+    let mantissa = (bits & 0x007f_ffff) as u128;
+    // se agregan bytes de precision a la mantisa para que la division no pierda los digitos que se conservan en los bits
+    let extra_bytes = (exponent - 3).clamp(0, 8);
+    let target = (mantissa << (8 * extra_bytes)) * timespan / TARGET_TIMESPAN as u128;
+    let new_bits = compact_bits(target, exponent - 3 - extra_bytes);
+
+    let as_key = |bits: u32| (bits >> 24, bits & 0x00ff_ffff);
+    match as_key(new_bits) > as_key(pow_limit) {
+        true => pow_limit,
+        false => new_bits,
     }
+}
 
-    fn first_headers(&self, hash_stop: Vec<u8>) -> Vec<BlockHeader> {
-        self.headers
-            .iter()
-            .take(2000)
-            .take_while(|block| block.hash != hash_stop)
-            .cloned()
-            .collect()
+/// Codifica en bits compactos el target value * 256^shift_bytes: la cantidad de bytes del target seguida de sus
+/// 3 bytes mas significativos, sin usar el bit mas alto que en los bits indica el signo.
+fn compact_bits(value: u128, shift_bytes: i32) -> u32 {
+    let value_bytes = (128 - value.leading_zeros() as i32 + 7) / 8;
+    let mut size = value_bytes + shift_bytes;
+    let mut mantissa = match value_bytes >= 3 {
+        true => (value >> (8 * (value_bytes - 3))) as u32,
+        false => (value << (8 * (3 - value_bytes))) as u32,
+    };
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
     }
+    ((size as u32) << 24) | mantissa
 }
 
 #[cfg(test)]
@@ -376,16 +812,59 @@ mod tests {
         sync::mpsc,
     };
 
-    use crate::messages::headers::Headers;
+    use crate::{
+        messages::headers::Headers,
+        peer::GENESIS,
+        states::store_file::{seal_store_file, STORE_HEADER_SIZE},
+    };
 
     use super::*;
 
+    #[test]
+    fn difficulty_is_retargeted() {
+        // ajuste de la mainnet a la altura 32256, el periodo tardo menos de dos semanas
+        assert_eq!(
+            retarget_bits(0x1d00ffff, 1262152739 - 1261130161, 0x1d00ffff),
+            0x1d00d86a
+        );
+        // el target no puede superar al de la dificultad minima
+        assert_eq!(
+            retarget_bits(0x1d00ffff, TARGET_TIMESPAN * 2, 0x1d00ffff),
+            0x1d00ffff
+        );
+        // el timespan se limita a un cuarto del esperado
+        assert_eq!(retarget_bits(0x1c0ffff0, 1, 0x1d00ffff), 0x1c03fffc);
+    }
+
+    /// Crea headers con los del archivo de prueba agregados en memoria en lugar de leidos del archivo, para poder
+    /// modificar si tienen su bloque descargado y si se enviaron.
+    fn appended_test_headers(path: &str) -> HeadersState {
+        let (logger_sender, _) = mpsc::channel();
+        let restored = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender.clone(),
+            Network::Testnet,
+        )
+        .unwrap();
+        let mut headers =
+            HeadersState::new(path.to_string(), logger_sender, Network::Testnet).unwrap();
+        headers
+            .append_headers(restored.iter_from(0).collect())
+            .unwrap();
+        remove_file(path).unwrap();
+        headers
+    }
+
     #[test]
     fn headers_creation_empty() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/non_existing_headers.bin".to_string(), logger_sender).unwrap();
-        assert_eq!(headers.headers.len(), 0);
+        let headers = HeadersState::new(
+            "tests/non_existing_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        assert_eq!(headers.len(), 0);
 
         remove_file("tests/non_existing_headers.bin").unwrap();
     }
@@ -393,8 +872,12 @@ mod tests {
     #[test]
     fn headers_to_download() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
         let headers_to_download = headers.total_headers_to_download();
         // 2 headers in the file, only one after START_DATE_IBD
         assert_eq!(headers_to_download, 1);
@@ -403,25 +886,127 @@ mod tests {
     #[test]
     fn headers_get_header_index() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let first_hash = headers.headers[0].hash.clone();
-        let second_hash = headers.headers[1].hash.clone();
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
 
         assert_eq!(headers.get_header_index(&first_hash), 0);
         assert_eq!(headers.get_header_index(&second_hash), 1);
-        assert_eq!(headers.get_header_index(&vec![0; 32]), 0);
+        assert_eq!(headers.get_header_index(&Hash32::default()), 0);
+    }
+
+    #[test]
+    fn headers_snapshot_base() {
+        let (logger_sender, _) = mpsc::channel();
+        let mut headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+        assert!(headers.snapshot_base_matches());
+        assert!(!headers.is_before_snapshot(&first_hash));
+
+        headers.set_snapshot_base(Some(SnapshotBase {
+            height: 1,
+            block_hash: first_hash,
+        }));
+        assert!(headers.snapshot_base_matches());
+        assert!(headers.is_before_snapshot(&first_hash));
+        assert!(!headers.is_before_snapshot(&second_hash));
+
+        headers.set_snapshot_base(Some(SnapshotBase {
+            height: 2,
+            block_hash: first_hash,
+        }));
+        assert!(!headers.snapshot_base_matches());
+
+        // los headers todavia no llegan a la altura del snapshot
+        headers.set_snapshot_base(Some(SnapshotBase {
+            height: 3,
+            block_hash: Hash32::default(),
+        }));
+        assert!(headers.snapshot_base_matches());
+    }
+
+    #[test]
+    fn headers_get_lock_point() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let first_timestamp = headers.get(0).unwrap().timestamp;
+        let second_timestamp = headers.get(1).unwrap().timestamp;
+
+        assert_eq!(headers.get_lock_point(1).median_time_past, 0);
+        assert_eq!(headers.get_lock_point(2).median_time_past, first_timestamp);
+        let next = headers.get_next_lock_point();
+        assert_eq!(next.height, 3);
+        assert_eq!(next.median_time_past, first_timestamp.max(second_timestamp));
+    }
+
+    #[test]
+    fn headers_assume_valid() {
+        let (logger_sender, _) = mpsc::channel();
+        let mut headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+        assert!(!headers.is_assumed_valid(&first_hash));
+
+        headers.set_assume_valid(Some(first_hash));
+        assert!(headers.is_assumed_valid(&first_hash));
+        assert!(!headers.is_assumed_valid(&second_hash));
+
+        headers.set_assume_valid(Some(Hash32::default()));
+        assert!(!headers.is_assumed_valid(&first_hash));
+    }
+
+    #[test]
+    fn headers_get_depth() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+
+        assert_eq!(headers.get_depth(&first_hash), Some(1));
+        assert_eq!(headers.get_depth(&second_hash), Some(0));
+        assert_eq!(headers.get_depth(&Hash32::default()), None);
     }
 
     #[test]
     fn headers_get_last_headers() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let first_hash = headers.headers[0].hash.clone();
-        let second_hash = headers.headers[1].hash.clone();
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
 
         let last_header_zero = headers.get_last_headers(0);
         let last_headers_one = headers.get_last_headers(1);
@@ -447,32 +1032,114 @@ mod tests {
     }
 
     #[test]
-    fn headers_set_downloaded() {
+    fn headers_get_by_height() {
         let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let restored = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let appended = appended_test_headers("tests/test_headers_by_height.bin");
+
+        for headers in [restored, appended] {
+            let first_hash = headers.get(0).unwrap().hash;
+            let second_hash = headers.get(1).unwrap().hash;
+
+            assert!(headers.get_header_by_height(0).is_none());
+            assert_eq!(headers.get_header_by_height(1).unwrap().hash, first_hash);
+            assert_eq!(headers.get_header_by_height(2).unwrap().hash, second_hash);
+            assert!(headers.get_header_by_height(3).is_none());
+
+            assert_eq!(
+                headers.get_hash_by_height(0),
+                Some(Network::Testnet.genesis())
+            );
+            assert_eq!(headers.get_hash_by_height(2), Some(second_hash));
+            assert_eq!(headers.get_hash_by_height(3), None);
+        }
+    }
+
+    #[test]
+    fn headers_get_headers_from() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+
+        let from_first = headers.get_headers_from(&first_hash, 5).unwrap();
+        assert_eq!(from_first.len(), 2);
+        assert_eq!(from_first[0].0, 1);
+        assert_eq!(from_first[1].1.hash, second_hash);
+
+        let from_second = headers.get_headers_from(&second_hash, 1).unwrap();
+        assert_eq!(from_second.len(), 1);
+        assert_eq!(from_second[0].0, 2);
+
+        assert!(headers.get_headers_from(&Hash32::default(), 1).is_none());
+    }
+
+    #[test]
+    fn headers_get_hashes_range() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+
+        let range = headers.get_hashes_range(1, &second_hash, 10).unwrap();
+        assert_eq!(range, vec![first_hash, second_hash]);
+        assert_eq!(
+            headers.get_hashes_range(2, &second_hash, 1).unwrap(),
+            vec![second_hash]
+        );
+
+        assert!(headers.get_hashes_range(1, &second_hash, 1).is_none());
+        assert!(headers.get_hashes_range(2, &first_hash, 10).is_none());
+        assert!(headers.get_hashes_range(0, &first_hash, 10).is_none());
+        assert!(headers
+            .get_hashes_range(1, &Hash32::default(), 10)
+            .is_none());
+    }
+
+    #[test]
+    fn headers_set_downloaded() {
+        let mut headers = appended_test_headers("tests/test_headers_set_downloaded.bin");
 
         headers.headers[1].block_downloaded = false;
 
         assert_eq!(headers.headers[1].block_downloaded, false);
-        headers.set_downloaded(&headers.headers[1].hash.clone());
+        let hash = headers.headers[1].hash;
+        headers.set_downloaded(&hash);
         assert_eq!(headers.headers[1].block_downloaded, true);
     }
 
     #[test]
     fn headers_get_headers_to_send_with_only_one_downloaded() {
-        let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = appended_test_headers(
+            "tests/test_headers_get_headers_to_send_with_only_one_downloaded.bin",
+        );
 
-        let first_hash = headers.headers[0].hash.clone();
+        let first_hash = headers.headers[0].hash;
         headers.headers[0].block_downloaded = false;
         headers.headers[1].block_downloaded = false;
 
         headers.headers[0].broadcasted = false;
         headers.headers[1].broadcasted = false;
 
-        headers.set_downloaded(&headers.headers[0].hash.clone());
+        let hash = headers.headers[0].hash;
+        headers.set_downloaded(&hash);
 
         let headers_to_send = headers.get_headers_to_send(&first_hash);
         assert_eq!(headers_to_send.len(), 1);
@@ -481,18 +1148,19 @@ mod tests {
 
     #[test]
     fn headers_get_headers_to_send_with_first_prev_broadcasted() {
-        let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = appended_test_headers(
+            "tests/test_headers_get_headers_to_send_with_first_prev_broadcasted.bin",
+        );
 
-        let second_hash = headers.headers[1].hash.clone();
+        let second_hash = headers.headers[1].hash;
         headers.headers[0].block_downloaded = true;
         headers.headers[1].block_downloaded = false;
 
         headers.headers[0].broadcasted = true;
         headers.headers[1].broadcasted = false;
 
-        headers.set_downloaded(&headers.headers[1].hash.clone());
+        let hash = headers.headers[1].hash;
+        headers.set_downloaded(&hash);
 
         let headers_to_send = headers.get_headers_to_send(&second_hash);
         assert_eq!(headers_to_send.len(), 1);
@@ -501,19 +1169,20 @@ mod tests {
 
     #[test]
     fn headers_get_headers_to_send_with_second_prev_downloaded() {
-        let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = appended_test_headers(
+            "tests/test_headers_get_headers_to_send_with_second_prev_downloaded.bin",
+        );
 
-        let first_hash = headers.headers[0].hash.clone();
-        let second_hash = headers.headers[1].hash.clone();
+        let first_hash = headers.headers[0].hash;
+        let second_hash = headers.headers[1].hash;
         headers.headers[0].block_downloaded = false;
         headers.headers[1].block_downloaded = true;
 
         headers.headers[0].broadcasted = false;
         headers.headers[1].broadcasted = false;
 
-        headers.set_downloaded(&headers.headers[0].hash.clone());
+        let hash = headers.headers[0].hash;
+        headers.set_downloaded(&hash);
         let headers_to_send = headers.get_headers_to_send(&first_hash);
 
         assert_eq!(headers_to_send.len(), 2);
@@ -523,18 +1192,19 @@ mod tests {
 
     #[test]
     fn headers_get_headers_to_send_without_prev_broadcasted() {
-        let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = appended_test_headers(
+            "tests/test_headers_get_headers_to_send_without_prev_broadcasted.bin",
+        );
 
-        let second_hash = headers.headers[1].hash.clone();
+        let second_hash = headers.headers[1].hash;
         headers.headers[0].block_downloaded = false;
         headers.headers[1].block_downloaded = false;
 
         headers.headers[0].broadcasted = false;
         headers.headers[1].broadcasted = false;
 
-        headers.set_downloaded(&headers.headers[1].hash.clone());
+        let hash = headers.headers[1].hash;
+        headers.set_downloaded(&hash);
         let headers_to_send = headers.get_headers_to_send(&second_hash);
 
         assert_eq!(headers_to_send.len(), 0);
@@ -546,42 +1216,127 @@ mod tests {
         let headers = HeadersState::new(
             "tests/test_headers.bin".to_string(),
             Sender::clone(&mut logger_sender),
+            Network::Testnet,
         )
         .unwrap();
-        assert_eq!(headers.headers.len(), 2);
+        assert_eq!(headers.len(), 2);
     }
 
     #[test]
     fn headers_creation_with_restore_error() {
-        let (mut logger_sender, _) = mpsc::channel();
-        let headers = HeadersState::new(
-            "tests/test_headers_error.bin".to_string(),
-            Sender::clone(&mut logger_sender),
+        let (logger_sender, _) = mpsc::channel();
+        let path = "tests/test_headers_error_restore.bin";
+
+        // un archivo con headers invalidos se descarta por completo
+        fs::copy("tests/test_headers_error.bin", path).unwrap();
+        let headers =
+            HeadersState::new(path.to_string(), logger_sender.clone(), Network::Testnet).unwrap();
+        assert_eq!(headers.len(), 0);
+        assert_eq!(fs::metadata(path).unwrap().len(), STORE_HEADER_SIZE as u64);
+
+        // un header a medio escribir al final se descarta y se trunca el archivo
+        let mut buffer = fs::read("tests/test_headers.bin").unwrap();
+        buffer.extend([0; 50]);
+        fs::write(path, buffer).unwrap();
+        let headers =
+            HeadersState::new(path.to_string(), logger_sender.clone(), Network::Testnet).unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            fs::metadata(path).unwrap().len(),
+            (STORE_HEADER_SIZE + 2 * HEADER_BACKUP_SIZE) as u64
         );
-        assert_eq!(headers.is_err(), true);
+
+        // un header con un byte cambiado no coincide con su hash guardado
+        let mut buffer = fs::read("tests/test_headers.bin").unwrap();
+        buffer[STORE_HEADER_SIZE + HEADER_BACKUP_SIZE + 40] ^= 0xff;
+        fs::write(path, buffer).unwrap();
+        let headers =
+            HeadersState::new(path.to_string(), logger_sender.clone(), Network::Testnet).unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(
+            fs::metadata(path).unwrap().len(),
+            (STORE_HEADER_SIZE + HEADER_BACKUP_SIZE) as u64
+        );
+
+        remove_file(path).unwrap();
     }
 
     #[test]
-    fn headers_get_all() {
+    fn headers_creation_with_legacy_and_incompatible_files() {
         let (logger_sender, _) = mpsc::channel();
+        let path = "tests/test_headers_legacy.bin";
+
+        // un archivo sin encabezado se reescribe con el formato actual
+        let buffer = fs::read("tests/test_headers.bin").unwrap();
+        fs::write(path, &buffer[STORE_HEADER_SIZE..]).unwrap();
         let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+            HeadersState::new(path.to_string(), logger_sender.clone(), Network::Testnet).unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(fs::read(path).unwrap(), buffer);
+
+        fs::write(path, seal_store_file(StoreFileKind::Wallets, &[])).unwrap();
+        assert!(matches!(
+            HeadersState::new(path.to_string(), logger_sender, Network::Testnet),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn headers_iter_from() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        assert_eq!(headers.get_all().len(), 2);
+        assert_eq!(headers.iter_from(0).count(), 2);
+        let from_second: Vec<BlockHeader> = headers.iter_from(1).collect();
+        assert_eq!(from_second.len(), 1);
+        assert_eq!(Some(from_second[0].hash), headers.get_last_header_hash());
+        assert_eq!(headers.iter_from(3).count(), 0);
+    }
+
+    #[test]
+    fn headers_restored_are_read_from_the_file() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+
+        // los headers del archivo no se cargan en memoria
+        assert_eq!(headers.file.len(), 2);
+        assert!(headers.headers.is_empty());
+        assert_eq!(
+            headers.get(1).unwrap().prev_block_hash,
+            headers.get(0).unwrap().hash
+        );
+        assert!(headers.get(2).is_none());
+        assert_eq!(headers.first_block_index(), 1);
     }
 
     #[test]
     fn headers_get_last_header_hash() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
         assert_eq!(
             headers.get_last_header_hash().unwrap(),
-            vec![
+            Hash32::new([
                 32, 120, 42, 0, 82, 85, 182, 87, 105, 110, 160, 87, 213, 185, 143, 52, 222, 252,
                 247, 81, 150, 246, 79, 110, 234, 200, 2, 108, 0, 0, 0, 0
-            ]
+            ])
         );
     }
 
@@ -589,27 +1344,43 @@ mod tests {
     fn headers_append_headers() {
         let (logger_sender, _) = mpsc::channel();
         fs::copy("tests/test_headers.bin", "tests/test_headers_append.bin").unwrap();
-        let mut headers =
-            HeadersState::new("tests/test_headers_append.bin".to_string(), logger_sender).unwrap();
+        let mut headers = HeadersState::new(
+            "tests/test_headers_append.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
         let mut new_headers = Headers::new();
         new_headers.headers.push(BlockHeader {
-            prev_block_hash: vec![
+            prev_block_hash: Hash32::new([
                 32, 120, 42, 0, 82, 85, 182, 87, 105, 110, 160, 87, 213, 185, 143, 52, 222, 252,
                 247, 81, 150, 246, 79, 110, 234, 200, 2, 108, 0, 0, 0, 0,
-            ],
-            merkle_root: vec![],
+            ]),
+            merkle_root: Hash32::default(),
             version: 0,
             timestamp: 1677449562,
             bits: 0,
             nonce: 0,
-            hash: vec![],
+            hash: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         });
 
-        headers.append_headers(new_headers.headers).unwrap();
-        assert_eq!(headers.headers.len(), 3);
+        let appended = headers.append_headers(new_headers.headers.clone()).unwrap();
+        assert_eq!(appended.len(), 1);
+        assert_eq!(headers.len(), 3);
+        assert!(headers.contains(&Hash32::default()));
+
+        let appended = headers.append_headers(new_headers.headers).unwrap();
+        assert!(appended.is_empty());
+        assert_eq!(headers.len(), 3);
+
+        // los headers nuevos se escriben en el archivo al hacer flush
+        let file_len = || fs::metadata("tests/test_headers_append.bin").unwrap().len() as usize;
+        assert_eq!(file_len(), STORE_HEADER_SIZE + 2 * HEADER_BACKUP_SIZE);
+        headers.flush().unwrap();
+        assert_eq!(file_len(), STORE_HEADER_SIZE + 3 * HEADER_BACKUP_SIZE);
 
         remove_file("tests/test_headers_append.bin").unwrap();
     }
@@ -618,34 +1389,127 @@ mod tests {
     fn headers_append_headers_blockchain_broken() {
         let (logger_sender, _) = mpsc::channel();
         fs::copy("tests/test_headers.bin", "tests/test_headers_append2.bin").unwrap();
-        let mut headers =
-            HeadersState::new("tests/test_headers_append2.bin".to_string(), logger_sender).unwrap();
+        let mut headers = HeadersState::new(
+            "tests/test_headers_append2.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
         let mut new_headers = Headers::new();
         new_headers.headers.push(BlockHeader {
-            prev_block_hash: vec![1, 2, 3],
-            merkle_root: vec![],
+            prev_block_hash: Hash32::new([1; 32]),
+            merkle_root: Hash32::default(),
             version: 0,
             timestamp: 1677449562,
             bits: 0,
             nonce: 0,
-            hash: vec![],
+            hash: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         });
 
-        assert_eq!(headers.headers.len(), 2);
-        assert!(headers.append_headers(new_headers.headers).is_err());
-        assert_eq!(headers.headers.len(), 2);
+        assert_eq!(headers.len(), 2);
+        assert!(matches!(
+            headers.append_headers(new_headers.headers.clone()),
+            Err(CustomError::UnknownParentHeader)
+        ));
+        assert_eq!(headers.len(), 2);
+
+        new_headers.headers[0].prev_block_hash = headers.get(0).unwrap().hash;
+        assert!(matches!(
+            headers.append_headers(new_headers.headers),
+            Err(CustomError::BlockChainBroken)
+        ));
+        assert_eq!(headers.len(), 2);
 
         remove_file("tests/test_headers_append2.bin").unwrap();
     }
 
+    /// Crea una cadena de headers de testnet sobre el genesis, sin proof of work y con hashes fijos.
+    fn chained_headers(length: u8) -> Vec<BlockHeader> {
+        let mut prev_block_hash = Network::Testnet.genesis();
+        (1..=length)
+            .map(|height| {
+                let header = BlockHeader {
+                    version: 1,
+                    prev_block_hash,
+                    merkle_root: Hash32::default(),
+                    timestamp: 1_600_000_000 + height as u32 * 600,
+                    bits: Network::Testnet.pow_limit_bits(),
+                    nonce: 0,
+                    hash: Hash32::new([height; 32]),
+                    broadcasted: false,
+                    block_downloaded: false,
+                };
+                prev_block_hash = header.hash;
+                header
+            })
+            .collect()
+    }
+
     #[test]
-    fn headers_verify_headers_sync() {
+    fn headers_append_valid_extension() {
+        let (logger_sender, _) = mpsc::channel();
+        let path = "tests/test_headers_append_valid.bin";
+        let mut headers =
+            HeadersState::new(path.to_string(), logger_sender, Network::Testnet).unwrap();
+        let chain = chained_headers(8);
+
+        assert_eq!(
+            headers.append_headers(chain[..5].to_vec()).unwrap().len(),
+            5
+        );
+        // los headers ya almacenados se descartan y el resto extiende al ultimo
+        let appended = headers.append_headers(chain[3..].to_vec()).unwrap();
+        remove_file(path).ok();
+
+        assert_eq!(appended.len(), 3);
+        assert_eq!(appended[0].hash, chain[5].hash);
+        assert_eq!(headers.len(), 8);
+        assert_eq!(headers.get_last_header_hash(), Some(chain[7].hash));
+    }
+
+    #[test]
+    fn headers_append_batch_broken_in_the_middle() {
         let (logger_sender, _) = mpsc::channel();
+        let path = "tests/test_headers_append_broken_batch.bin";
         let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+            HeadersState::new(path.to_string(), logger_sender, Network::Testnet).unwrap();
+        let chain = chained_headers(10);
+        headers.append_headers(chain[..5].to_vec()).unwrap();
+
+        // falta un header en el medio del lote
+        let mut gap = chain[5..].to_vec();
+        gap.remove(2);
+        let unknown_parent = headers.append_headers(gap);
+
+        // un header del medio del lote se bifurca de uno ya almacenado
+        let mut fork = chain[5..].to_vec();
+        fork[2].prev_block_hash = chain[3].hash;
+        let broken = headers.append_headers(fork);
+        remove_file(path).ok();
+
+        assert!(matches!(
+            unknown_parent,
+            Err(CustomError::UnknownParentHeader)
+        ));
+        assert!(matches!(broken, Err(CustomError::BlockChainBroken)));
+        // no se agrega ninguno de los headers de los lotes, ni los anteriores al corte
+        assert_eq!(headers.len(), 5);
+        assert_eq!(headers.get_last_header_hash(), Some(chain[4].hash));
+        assert!(!headers.contains(&chain[5].hash));
+    }
+
+    #[test]
+    fn headers_verify_headers_sync() {
+        let (logger_sender, _) = mpsc::channel();
+        let mut headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
         assert_eq!(headers.is_synced(), false);
 
         headers.verify_headers_sync(2000).unwrap();
@@ -661,26 +1525,34 @@ mod tests {
     #[test]
     fn headers_get_headers_from_genesis() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let getheaders = GetHeaders::new(1, vec![], vec![0; 32]);
+        let getheaders = GetHeaders::new(1, vec![], Hash32::default());
         assert_eq!(headers.get_headers(getheaders).len(), 2);
 
-        let getheaders = GetHeaders::new(1, vec![GENESIS.to_vec()], vec![0; 32]);
+        let getheaders = GetHeaders::new(1, vec![Hash32::new(GENESIS)], Hash32::default());
         assert_eq!(headers.get_headers(getheaders).len(), 2);
     }
 
     #[test]
     fn headers_get_headers_from_last() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
         let getheaders = GetHeaders::new(
             1,
             vec![headers.get_last_header_hash().unwrap()],
-            vec![0; 32],
+            Hash32::default(),
         );
         assert_eq!(headers.get_headers(getheaders).len(), 0);
     }
@@ -688,28 +1560,32 @@ mod tests {
     #[test]
     fn headers_get_headers_from_first() {
         let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let second_hash = headers.headers[1].hash.clone();
+        let second_hash = headers.get(1).unwrap().hash;
 
         let new_header = BlockHeader {
-            prev_block_hash: vec![
+            prev_block_hash: Hash32::new([
                 32, 120, 42, 0, 82, 85, 182, 87, 105, 110, 160, 87, 213, 185, 143, 52, 222, 252,
                 247, 81, 150, 246, 79, 110, 234, 200, 2, 108, 0, 0, 0, 0,
-            ],
-            merkle_root: vec![],
+            ]),
+            merkle_root: Hash32::default(),
             version: 0,
             timestamp: 1677449562,
             bits: 0,
             nonce: 0,
-            hash: vec![1, 2, 3],
+            hash: Hash32::new([1; 32]),
             block_downloaded: true,
             broadcasted: true,
         };
         headers.headers.push(new_header.clone());
 
-        let getheaders = GetHeaders::new(1, vec![headers.headers[0].hash.clone()], vec![0; 32]);
+        let getheaders = GetHeaders::new(1, vec![headers.get(0).unwrap().hash], Hash32::default());
         let getheaders = headers.get_headers(getheaders);
         assert_eq!(getheaders.len(), 2);
         assert_eq!(getheaders[0].hash, second_hash);
@@ -719,47 +1595,74 @@ mod tests {
     #[test]
     fn headers_get_headers_with_hash_stop() {
         let (logger_sender, _) = mpsc::channel();
-        let mut headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let mut headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let second_hash = headers.headers[1].hash.clone();
+        let second_hash = headers.get(1).unwrap().hash;
 
         let new_header = BlockHeader {
-            prev_block_hash: vec![
+            prev_block_hash: Hash32::new([
                 32, 120, 42, 0, 82, 85, 182, 87, 105, 110, 160, 87, 213, 185, 143, 52, 222, 252,
                 247, 81, 150, 246, 79, 110, 234, 200, 2, 108, 0, 0, 0, 0,
-            ],
-            merkle_root: vec![],
+            ]),
+            merkle_root: Hash32::default(),
             version: 0,
             timestamp: 1677449562,
             bits: 0,
             nonce: 0,
-            hash: vec![1, 2, 3],
+            hash: Hash32::new([1; 32]),
             block_downloaded: true,
             broadcasted: true,
         };
         headers.headers.push(new_header.clone());
 
-        let getheaders = GetHeaders::new(
-            1,
-            vec![headers.headers[0].hash.clone()],
-            second_hash.clone(),
-        );
+        let getheaders = GetHeaders::new(1, vec![headers.get(0).unwrap().hash], second_hash);
         let getheaders = headers.get_headers(getheaders);
         assert_eq!(getheaders.len(), 1);
         assert_eq!(getheaders[0].hash, second_hash);
     }
 
+    #[test]
+    fn headers_get_headers_from_first_known_locator_hash() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
+        assert_eq!(headers.get_height(&Network::Testnet.genesis()), Some(0));
+        assert_eq!(headers.get_height(&second_hash), Some(2));
+        assert_eq!(headers.get_height(&Hash32::new([1; 32])), None);
+
+        // los locators van del hash mas nuevo al mas viejo y terminan en el genesis
+        let locator = vec![Hash32::new([1; 32]), first_hash, Network::Testnet.genesis()];
+        let getheaders = headers.get_headers(GetHeaders::new(1, locator, Hash32::default()));
+        assert_eq!(getheaders.len(), 1);
+        assert_eq!(getheaders[0].hash, second_hash);
+    }
+
     #[test]
     fn headers_get_headers_with_wrong_block_locator_hashes() {
         let (logger_sender, _) = mpsc::channel();
-        let headers =
-            HeadersState::new("tests/test_headers.bin".to_string(), logger_sender).unwrap();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
 
-        let first_hash = headers.headers[0].hash.clone();
-        let second_hash = headers.headers[1].hash.clone();
+        let first_hash = headers.get(0).unwrap().hash;
+        let second_hash = headers.get(1).unwrap().hash;
 
-        let getheaders = GetHeaders::new(1, vec![vec![1, 2, 3]], vec![0; 32]);
+        let getheaders = GetHeaders::new(1, vec![Hash32::new([1; 32])], Hash32::default());
         let getheaders = headers.get_headers(getheaders);
         assert_eq!(getheaders.len(), 2);
         assert_eq!(getheaders[0].hash, first_hash);
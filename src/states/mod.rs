@@ -1,6 +1,19 @@
+pub mod address_book_state;
+pub mod addresses_state;
+pub mod anchors_state;
+pub mod bans_state;
+pub mod block_cache;
+pub mod block_store;
 pub mod blocks_state;
+pub mod chain_export;
+pub mod core_import;
+pub mod headers_file;
 pub mod headers_state;
 pub mod pending_blocks_state;
 pub mod pending_txs_state;
+pub mod store_file;
+pub mod utxo_set;
+pub mod utxo_snapshot;
 pub mod utxo_state;
+pub mod wallets_backup;
 pub mod wallets_state;
@@ -1,76 +1,126 @@
 use std::{
     collections::HashMap,
-    path::Path,
+    net::SocketAddrV6,
     sync::{Arc, Mutex},
 };
 
 use crate::{
     error::CustomError,
-    structs::block_header::BlockHeader,
+    structs::{block_header::BlockHeader, hash32::Hash32},
     utils::{calculate_index_from_timestamp, get_current_timestamp},
 };
 
-use super::utxo_state::START_DATE_IBD;
+use super::{block_store::BlockStore, utxo_state::START_DATE_IBD};
+
+/// Cantidad maxima de bloques pedidos a un mismo peer que todavia no fueron recibidos.
+pub const MAX_BLOCKS_IN_FLIGHT_PER_PEER: usize = 16;
 
 /// PendingBlocks es una estructura para manejar los bloques solicitados pendientes de recibir.
 /// Los elementos son:
 /// - blocks: HashMap que contiene los bloques pendientes de recibir y un timestamp del momento en el que se generó.
+/// - in_flight: HashMap con el peer al que se le pidio cada bloque, una vez que el pedido fue enviado.
 /// - stale_time: Tiempo en segundos que debe pasar para que una peticion de bloque sea considerada como vencida.
+/// - stall_time: Tiempo en segundos que debe pasar para que un peer que no envio un bloque pedido sea considerado trabado.
 pub struct PendingBlocks {
-    blocks: HashMap<Vec<u8>, u64>,
+    blocks: HashMap<Hash32, u64>,
+    in_flight: HashMap<Hash32, SocketAddrV6>,
     stale_time: u64,
+    stall_time: u64,
 }
 
 impl PendingBlocks {
     #[must_use]
-    /// Inicializa la estructura.
-    pub fn new(store_path: &String, headers: &Vec<BlockHeader>) -> Arc<Mutex<Self>> {
+    /// Inicializa la estructura con los bloques posteriores a START_DATE_IBD que todavia no estan guardados.
+    /// Si el nodo se inicio desde un snapshot de UTXO, solo se incluyen los bloques posteriores a la altura recibida.
+    pub fn new(
+        block_store: &BlockStore,
+        headers: &Vec<BlockHeader>,
+        snapshot_height: usize,
+    ) -> Arc<Mutex<Self>> {
         let mut blocks = HashMap::new();
-        let starting_index = calculate_index_from_timestamp(headers, START_DATE_IBD) + 1;
+        let starting_index =
+            (calculate_index_from_timestamp(headers, START_DATE_IBD) + 1).max(snapshot_height);
 
         for header in headers.iter().skip(starting_index) {
-            let path = format!("{}/blocks/{}.bin", store_path, header.hash_as_string());
-
-            if !Path::new(&path).exists() {
-                blocks.insert(header.hash().clone(), 0_u64);
+            if !block_store.contains(header.hash()) {
+                blocks.insert(*header.hash(), 0_u64);
             }
         }
 
         Arc::new(Mutex::new(Self {
             blocks,
+            in_flight: HashMap::new(),
             stale_time: 5,
+            stall_time: 30,
         }))
     }
 
     /// Agrega un bloque a la lista de bloques pendientes de recibir con el timestamp actual.
-    pub fn append_block(&mut self, block_hash: Vec<u8>) -> Result<(), CustomError> {
+    pub fn append_block(&mut self, block_hash: Hash32) -> Result<(), CustomError> {
         let current_time = get_current_timestamp()?;
         self.blocks.insert(block_hash, current_time);
         Ok(())
     }
 
+    /// Registra que el pedido de un bloque pendiente fue enviado a un peer, reiniciando su timestamp.
+    /// Si el bloque ya fue recibido no hace nada.
+    pub fn request_block(
+        &mut self,
+        block_hash: Hash32,
+        address: SocketAddrV6,
+    ) -> Result<(), CustomError> {
+        let Some(timestamp) = self.blocks.get_mut(&block_hash) else {
+            return Ok(());
+        };
+        *timestamp = get_current_timestamp()?;
+        self.in_flight.insert(block_hash, address);
+        Ok(())
+    }
+
     /// Elimina un bloque de la lista de bloques pendientes de recibir.
-    pub fn remove_block(&mut self, block_hash: &Vec<u8>) -> Result<(), CustomError> {
+    pub fn remove_block(&mut self, block_hash: &Hash32) -> Result<(), CustomError> {
         self.blocks.remove(block_hash);
+        self.in_flight.remove(block_hash);
         Ok(())
     }
 
     /// Elimina todos los bloques de la lista de bloques pendientes de recibir.
     pub fn drain(&mut self) {
         self.blocks.drain();
+        self.in_flight.drain();
     }
 
     pub fn len(&self) -> usize {
         self.blocks.len()
     }
 
-    /// Devuelve los bloques pendientes de recibir que ya vencieron.
-    pub fn get_stale_requests(&mut self) -> Result<Vec<Vec<u8>>, CustomError> {
+    /// Devuelve hasta max bloques pendientes que todavia no fueron pedidos a ningun peer.
+    pub fn get_unrequested(&self, max: usize) -> Vec<Hash32> {
+        self.blocks
+            .keys()
+            .filter(|block_hash| !self.in_flight.contains_key(block_hash))
+            .take(max)
+            .copied()
+            .collect()
+    }
+
+    /// Devuelve la cantidad de bloques pedidos a un peer que todavia no fueron recibidos.
+    pub fn count_in_flight(&self, address: SocketAddrV6) -> usize {
+        self.in_flight
+            .values()
+            .filter(|requested_from| **requested_from == address)
+            .count()
+    }
+
+    /// Devuelve los bloques pendientes de recibir que ya vencieron y no fueron pedidos a ningun peer.
+    pub fn get_stale_requests(&mut self) -> Result<Vec<Hash32>, CustomError> {
         let mut to_remove = Vec::new();
 
         for (block_hash, timestamp) in &self.blocks {
-            if *timestamp + self.stale_time < get_current_timestamp()? {
-                to_remove.push(block_hash.clone());
+            if !self.in_flight.contains_key(block_hash)
+                && *timestamp + self.stale_time < get_current_timestamp()?
+            {
+                to_remove.push(*block_hash);
             }
         }
 
@@ -81,8 +131,32 @@ impl PendingBlocks {
         Ok(to_remove)
     }
 
+    /// Devuelve, agrupados por peer, los bloques pedidos que el peer no envio dentro de stall_time.
+    /// Los bloques se eliminan de la lista para que se vuelvan a pedir a otro peer.
+    pub fn get_stalled_requests(
+        &mut self,
+    ) -> Result<HashMap<SocketAddrV6, Vec<Hash32>>, CustomError> {
+        let mut stalled: HashMap<SocketAddrV6, Vec<Hash32>> = HashMap::new();
+
+        for (block_hash, address) in &self.in_flight {
+            let Some(timestamp) = self.blocks.get(block_hash) else {
+                continue;
+            };
+            if *timestamp + self.stall_time < get_current_timestamp()? {
+                stalled.entry(*address).or_default().push(*block_hash);
+            }
+        }
+
+        for block_hash in stalled.values().flatten() {
+            self.blocks.remove(block_hash);
+            self.in_flight.remove(block_hash);
+        }
+
+        Ok(stalled)
+    }
+
     /// Devuelve true si el bloque esta en la lista de bloques pendientes de recibir.
-    pub fn is_block_pending(&self, block_hash: &Vec<u8>) -> bool {
+    pub fn is_block_pending(&self, block_hash: &Hash32) -> bool {
         self.blocks.contains_key(block_hash)
     }
 
@@ -97,11 +171,17 @@ mod tests {
 
     use std::{thread, time::Duration};
 
+    use crate::network::Network;
+
     use super::*;
 
     #[test]
     fn pending_blocks_creation() {
-        let pending_blocks = PendingBlocks::new(&"".to_string(), &vec![]);
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
         let pending_blocks = pending_blocks.lock().unwrap();
 
         assert_eq!(pending_blocks.is_empty(), true);
@@ -109,11 +189,15 @@ mod tests {
 
     #[test]
     fn append_block() {
-        let pending_blocks = PendingBlocks::new(&"".to_string(), &vec![]);
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
         let mut pending_blocks = pending_blocks.lock().unwrap();
 
-        let block_hash = vec![1, 2, 3, 4, 5];
-        pending_blocks.append_block(block_hash.clone()).unwrap();
+        let block_hash = Hash32::new([1; 32]);
+        pending_blocks.append_block(block_hash).unwrap();
 
         assert_eq!(pending_blocks.is_block_pending(&block_hash), true);
         assert_eq!(pending_blocks.is_empty(), false);
@@ -121,11 +205,15 @@ mod tests {
 
     #[test]
     fn remove_block() {
-        let pending_blocks = PendingBlocks::new(&"".to_string(), &vec![]);
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
         let mut pending_blocks = pending_blocks.lock().unwrap();
 
-        let block_hash = vec![1, 2, 3, 4, 5];
-        pending_blocks.append_block(block_hash.clone()).unwrap();
+        let block_hash = Hash32::new([1; 32]);
+        pending_blocks.append_block(block_hash).unwrap();
         assert_eq!(pending_blocks.is_empty(), false);
         pending_blocks.remove_block(&block_hash).unwrap();
 
@@ -135,13 +223,17 @@ mod tests {
 
     #[test]
     fn drain() {
-        let pending_blocks = PendingBlocks::new(&"".to_string(), &vec![]);
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
         let mut pending_blocks = pending_blocks.lock().unwrap();
 
-        let block_hash = vec![1, 2, 3, 4, 5];
-        let block_hash2 = vec![6, 7, 8, 9, 10];
-        pending_blocks.append_block(block_hash.clone()).unwrap();
-        pending_blocks.append_block(block_hash2.clone()).unwrap();
+        let block_hash = Hash32::new([1; 32]);
+        let block_hash2 = Hash32::new([6; 32]);
+        pending_blocks.append_block(block_hash).unwrap();
+        pending_blocks.append_block(block_hash2).unwrap();
 
         assert_eq!(pending_blocks.is_empty(), false);
         pending_blocks.drain();
@@ -150,12 +242,16 @@ mod tests {
 
     #[test]
     fn get_stale_requests() {
-        let pending_blocks = PendingBlocks::new(&"".to_string(), &vec![]);
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
         let mut pending_blocks = pending_blocks.lock().unwrap();
 
-        let block_hash = vec![1, 2, 3, 4, 5];
+        let block_hash = Hash32::new([1; 32]);
 
-        pending_blocks.append_block(block_hash.clone()).unwrap();
+        pending_blocks.append_block(block_hash).unwrap();
 
         let stale_requests = pending_blocks.get_stale_requests().unwrap();
         assert_eq!(stale_requests.len(), 0);
@@ -167,16 +263,54 @@ mod tests {
         assert_eq!(stale_requests[0], block_hash);
     }
 
+    #[test]
+    fn get_stalled_requests() {
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &vec![],
+            0,
+        );
+        let mut pending_blocks = pending_blocks.lock().unwrap();
+
+        let block_hash = Hash32::new([1; 32]);
+        let other_block_hash = Hash32::new([2; 32]);
+        let address: SocketAddrV6 = "[::ffff:10.0.0.1]:18333".parse().unwrap();
+
+        pending_blocks.append_block(block_hash).unwrap();
+        pending_blocks.request_block(block_hash, address).unwrap();
+        pending_blocks
+            .request_block(other_block_hash, address)
+            .unwrap();
+        assert!(!pending_blocks.is_block_pending(&other_block_hash));
+
+        pending_blocks.stale_time = 0;
+        thread::sleep(Duration::from_secs(1));
+        assert_eq!(pending_blocks.get_stale_requests().unwrap().len(), 0);
+        assert_eq!(pending_blocks.get_stalled_requests().unwrap().len(), 0);
+
+        assert_eq!(pending_blocks.count_in_flight(address), 1);
+        assert!(pending_blocks.get_unrequested(1).is_empty());
+
+        pending_blocks.stall_time = 0;
+        let stalled = pending_blocks.get_stalled_requests().unwrap();
+        assert_eq!(stalled.get(&address), Some(&vec![block_hash]));
+        assert!(!pending_blocks.is_block_pending(&block_hash));
+        assert_eq!(pending_blocks.count_in_flight(address), 0);
+
+        pending_blocks.append_block(block_hash).unwrap();
+        assert_eq!(pending_blocks.get_unrequested(1), vec![block_hash]);
+    }
+
     #[test]
     fn start_with_lost_blocks() {
         let old_header = BlockHeader {
             bits: 0,
             nonce: 0,
-            prev_block_hash: vec![],
+            prev_block_hash: Hash32::default(),
             timestamp: START_DATE_IBD - 1,
             version: 0,
-            hash: vec![1, 2, 3],
-            merkle_root: vec![],
+            hash: Hash32::new([1; 32]),
+            merkle_root: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         };
@@ -184,24 +318,36 @@ mod tests {
         let lost_header = BlockHeader {
             bits: 0,
             nonce: 0,
-            prev_block_hash: vec![],
+            prev_block_hash: Hash32::default(),
             timestamp: START_DATE_IBD + 1,
             version: 0,
-            hash: vec![
+            hash: Hash32::new([
                 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7,
                 8, 9, 1, 2,
-            ],
-            merkle_root: vec![],
+            ]),
+            merkle_root: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         };
 
-        let pending_blocks =
-            PendingBlocks::new(&"".to_string(), &vec![old_header, lost_header.clone()]);
+        let headers = vec![old_header, lost_header.clone()];
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &headers,
+            0,
+        );
 
         let pending_blocks = pending_blocks.lock().unwrap();
         assert_eq!(pending_blocks.is_empty(), false);
         assert_eq!(pending_blocks.len(), 1);
         assert_eq!(pending_blocks.is_block_pending(&lost_header.hash), true);
+
+        // los bloques hasta la altura del snapshot de UTXO no se descargan
+        let pending_blocks = PendingBlocks::new(
+            &BlockStore::new(String::new(), Network::Testnet),
+            &headers,
+            2,
+        );
+        assert!(pending_blocks.lock().unwrap().is_empty());
     }
 }
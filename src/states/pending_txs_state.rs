@@ -1,22 +1,81 @@
 use std::{
-    collections::{hash_map, HashMap},
-    vec,
+    collections::{BTreeSet, HashMap, HashSet},
+    io::{Read, Write},
 };
 
 use crate::{
     error::CustomError,
     messages::{block::Block, transaction::Transaction},
-    structs::movement::Movement,
+    parser::{BtcEncode, BufferParser},
+    structs::{
+        block_template::TemplateTransaction, bloom_filter::BloomFilter, hash32::Hash32,
+        movement::Movement, outpoint::OutPoint, tx_output::TransactionOutput,
+    },
+    utils::open_new_file,
     wallet::Wallet,
 };
 
-use super::utxo_state::UTXO;
+use super::utxo_state::{sum_money, UTXO};
 
-/// PendingTxs es una estructura que contiene los elementos necesarios para manejar las transacciones pendientes.
+/// Tamaño maximo por defecto de la mempool en megabytes, el mismo que usa Bitcoin Core.
+pub const DEFAULT_MAX_MEMPOOL_SIZE: usize = 300;
+/// Fee rate minimo por defecto en satoshis por kilobyte virtual para aceptar y retransmitir una transaccion.
+pub const DEFAULT_MIN_RELAY_FEE: u64 = 1000;
+
+/// Sequence maximo de una entrada para que su transaccion señalice que se puede reemplazar (BIP125).
+const MAX_BIP125_RBF_SEQUENCE: u32 = 0xfffffffd;
+/// Fee rate en satoshis por kilobyte virtual que debe pagar un reemplazo por sobre el fee de las transacciones que descarta.
+const INCREMENTAL_RELAY_FEE: u64 = 1000;
+/// Cantidad maxima de transacciones que puede descartar un reemplazo, contando los descendientes.
+const MAX_REPLACEMENT_EVICTIONS: usize = 100;
+
+/// MempoolEntry es una transaccion pendiente junto a los datos que la mempool calcula al agregarla.
+/// Los elementos son:
+/// - transaction: Transaccion pendiente.
+/// - vsize: Tamaño virtual de la transaccion, con el que se calcula su fee rate y el tamaño de la mempool.
+/// - fee: Fee de la transaccion en satoshis, None si no se conoce alguna de las salidas que gasta.
+/// - parents: Hashes de las transacciones pendientes de las que gasta alguna salida.
+/// - children: Hashes de las transacciones pendientes que gastan alguna de sus salidas.
+/// - local: Indica si la transaccion la creo alguna de las wallets del nodo, en cuyo caso se reenvia hasta que se confirme.
+struct MempoolEntry {
+    transaction: Transaction,
+    vsize: usize,
+    fee: Option<u64>,
+    parents: HashSet<Hash32>,
+    children: HashSet<Hash32>,
+    local: bool,
+}
+
+impl MempoolEntry {
+    /// Devuelve el fee rate de la transaccion en satoshis por kilobyte virtual, None si no se conoce su fee.
+    fn fee_rate(&self) -> Option<u64> {
+        Some(self.fee? * 1000 / self.vsize as u64)
+    }
+}
+
+/// PendingTxs es la mempool del nodo, contiene las transacciones pendientes indexadas por su fee rate.
+/// Cuando su tamaño supera el maximo se descartan las de menor fee rate junto a sus descendientes,
+/// ya que sin ellas no se pueden incluir en un bloque.
 /// Los elementos son:
-/// - tx_set: HashMap que contiene los hashes de las transacciones pendientes con su Transaction.
+/// - tx_set: HashMap que contiene los hashes de las transacciones pendientes con su MempoolEntry.
+/// - wtxids: HashMap que contiene el wtxid de cada transaccion pendiente con su hash, para los peers que las piden por wtxid (BIP339).
+/// - spent_outpoints: HashMap con las salidas que gastan las transacciones pendientes y el hash de la que las gasta.
+/// - by_fee_rate: Transacciones pendientes ordenadas por fee rate, las de fee desconocido con fee rate 0.
+/// - total_vsize: Suma de los tamaños virtuales de las transacciones pendientes.
+/// - max_vsize: Tamaño virtual maximo de la mempool.
+/// - min_relay_fee: Fee rate minimo en satoshis por kilobyte virtual de las transacciones que se aceptan.
+/// - path: Path del archivo donde se guardan las transacciones pendientes al cerrar el nodo, None si no se guardan.
+/// - saved: Transacciones leidas del archivo al iniciar, que todavia no se validaron contra el UTXO, e indicador de si son locales.
 pub struct PendingTxs {
-    tx_set: HashMap<Vec<u8>, Transaction>,
+    tx_set: HashMap<Hash32, MempoolEntry>,
+    wtxids: HashMap<Hash32, Hash32>,
+    spent_outpoints: HashMap<OutPoint, Hash32>,
+    by_fee_rate: BTreeSet<(u64, Hash32)>,
+    total_vsize: usize,
+    max_vsize: usize,
+    min_relay_fee: u64,
+    path: Option<String>,
+    saved: Vec<(Transaction, bool)>,
 }
 
 impl Default for PendingTxs {
@@ -26,33 +85,250 @@ impl Default for PendingTxs {
 }
 
 impl PendingTxs {
-    /// Inicializa la estructura.
+    /// Inicializa la estructura, con el tamaño maximo por defecto y sin guardarla en disco.
     pub fn new() -> Self {
         PendingTxs {
             tx_set: HashMap::new(),
+            wtxids: HashMap::new(),
+            spent_outpoints: HashMap::new(),
+            by_fee_rate: BTreeSet::new(),
+            total_vsize: 0,
+            max_vsize: DEFAULT_MAX_MEMPOOL_SIZE * 1_000_000,
+            min_relay_fee: DEFAULT_MIN_RELAY_FEE,
+            path: None,
+            saved: vec![],
+        }
+    }
+
+    /// Inicializa la estructura guardandola en el archivo recibido.
+    /// Si el archivo no existe, se crea.
+    /// Si el archivo existe, se leen las transacciones guardadas, cada una precedida por un byte que indica si es local.
+    /// Se agregan recien con restore_saved ya que hasta tener el UTXO sincronizado no se puede saber si siguen siendo validas.
+    pub fn from_file(path: String) -> Result<Self, CustomError> {
+        let mut file = open_new_file(path.clone(), false)?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)?;
+        let mut parser = BufferParser::new(buffer);
+
+        let mut pending_txs = Self::new();
+        while !parser.is_empty() {
+            let local = parser.extract_u8()? == 1;
+            let transaction = Transaction::decode(&mut parser)?;
+            pending_txs.saved.push((transaction, local));
+        }
+        pending_txs.path = Some(path);
+        Ok(pending_txs)
+    }
+
+    /// Agrega las transacciones leidas del archivo que siguen siendo validas: las que gastan salidas que estan
+    /// en el UTXO o en otras transacciones pendientes y que ninguna otra transaccion pendiente gasta.
+    /// Las que se incluyeron en un bloque mientras el nodo estaba cerrado gastan salidas que ya no estan en el UTXO.
+    /// Devuelve la cantidad de transacciones agregadas.
+    pub fn restore_saved(&mut self, utxo: &UTXO) -> usize {
+        let mut restored = 0;
+        for (transaction, local) in std::mem::take(&mut self.saved) {
+            let tx_hash = transaction.hash();
+            let valid = transaction.inputs.iter().all(|input| {
+                let previous_output = &input.previous_output;
+                !self.spent_outpoints.contains_key(previous_output)
+                    && (utxo.tx_set.contains_key(previous_output)
+                        || self.tx_set.get(&previous_output.hash).is_some_and(|entry| {
+                            (previous_output.index as usize) < entry.transaction.outputs.len()
+                        }))
+            });
+            if valid && self.append_pending_tx(transaction, utxo) {
+                if local {
+                    self.mark_local(&tx_hash);
+                }
+                restored += 1;
+            }
+        }
+        restored
+    }
+
+    /// Guarda las transacciones pendientes en el archivo y fuerza su escritura en disco, junto a las leidas
+    /// al iniciar que todavia no se validaron. Se guardan los padres antes que los hijos, para poder validarlas en orden.
+    pub fn flush(&self) -> Result<(), CustomError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut entries: Vec<(usize, &MempoolEntry)> = self
+            .tx_set
+            .iter()
+            .map(|(tx_hash, entry)| (self.get_ancestors(tx_hash).len(), entry))
+            .collect();
+        entries.sort_by_key(|(ancestors, _)| *ancestors);
+
+        let mut buffer = vec![];
+        for (transaction, local) in self.saved.iter().map(|(tx, local)| (tx, *local)).chain(
+            entries
+                .into_iter()
+                .map(|(_, entry)| (&entry.transaction, entry.local)),
+        ) {
+            buffer.push(local as u8);
+            buffer.extend(transaction.serialize());
         }
+
+        let mut file = open_new_file(path.clone(), false)?;
+        file.set_len(0)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Establece el tamaño virtual maximo de la mempool en bytes, descartando transacciones si se supera.
+    pub fn set_max_size(&mut self, max_vsize: usize) {
+        self.max_vsize = max_vsize;
+        self.trim_to_size();
+    }
+
+    /// Establece el fee rate minimo en satoshis por kilobyte virtual de las transacciones que se aceptan.
+    pub fn set_min_relay_fee(&mut self, min_relay_fee: u64) {
+        self.min_relay_fee = min_relay_fee;
+    }
+
+    /// Devuelve el fee rate minimo en satoshis por kilobyte virtual de las transacciones que se aceptan.
+    pub fn get_min_relay_fee(&self) -> u64 {
+        self.min_relay_fee
     }
 
     /// Agrega una transaccion a la lista de transacciones pendientes, devuelve true si es una transaccion que no teniamos.
-    pub fn append_pending_tx(&mut self, transaction: Transaction) -> bool {
+    /// Su fee se calcula con los valores de las salidas que gasta, buscandolas en el UTXO o en las otras transacciones pendientes.
+    /// Si gasta mas de lo que reciben sus entradas, o su fee rate no alcanza el fee rate minimo, se rechaza. Si no se conoce su fee se acepta, y se descarta
+    /// al llegar sus padres si no lo alcanza.
+    /// Si gasta salidas que ya gastan otras transacciones pendientes, solo se agrega si las puede reemplazar (BIP125),
+    /// descartandolas junto a sus descendientes. Si no, se rechaza como doble gasto y devuelve false.
+    /// Si con ella se supera el tamaño maximo se descartan las de menor fee rate, y si se descarta la misma transaccion devuelve false.
+    pub fn append_pending_tx(&mut self, transaction: Transaction, utxo: &UTXO) -> bool {
         let tx_hash = transaction.hash();
+        if self.tx_set.contains_key(&tx_hash) {
+            return false;
+        }
 
-        if let hash_map::Entry::Vacant(e) = self.tx_set.entry(tx_hash) {
-            e.insert(transaction);
-            return true;
+        let Ok(fee) = self.calculate_fee(&transaction, utxo) else {
+            return false;
+        };
+        let vsize = transaction.vsize();
+        if fee.is_some_and(|fee| fee * 1000 / (vsize as u64) < self.min_relay_fee) {
+            return false;
+        }
+
+        let conflicts = self.get_conflicts(&transaction);
+        if !conflicts.is_empty() {
+            let Some(evicted) = self.replacement_evictions(&transaction, &conflicts, utxo) else {
+                return false;
+            };
+            for hash in evicted {
+                self.remove_entry(&hash);
+            }
+        }
+
+        let parents = transaction
+            .inputs
+            .iter()
+            .map(|input| input.previous_output.hash)
+            .filter(|parent| self.tx_set.contains_key(parent))
+            .collect::<HashSet<Hash32>>();
+        let children = (0..transaction.outputs.len())
+            .filter_map(|index| {
+                self.spent_outpoints.get(&OutPoint {
+                    hash: tx_hash,
+                    index: index as u32,
+                })
+            })
+            .copied()
+            .collect::<HashSet<Hash32>>();
+
+        for parent in &parents {
+            if let Some(parent_entry) = self.tx_set.get_mut(parent) {
+                parent_entry.children.insert(tx_hash);
+            }
+        }
+        for input in &transaction.inputs {
+            self.spent_outpoints
+                .insert(input.previous_output.clone(), tx_hash);
+        }
+        self.wtxids.insert(transaction.wtxid(), tx_hash);
+
+        let entry = MempoolEntry {
+            fee,
+            vsize,
+            transaction,
+            parents,
+            children: children.clone(),
+            local: false,
+        };
+        self.total_vsize += entry.vsize;
+        self.by_fee_rate
+            .insert((entry.fee_rate().unwrap_or(0), tx_hash));
+        self.tx_set.insert(tx_hash, entry);
+
+        // Los hijos que llegaron antes que esta transaccion ahora pueden calcular su fee
+        for child in children {
+            self.link_parent(child, tx_hash, utxo);
+        }
+
+        self.trim_to_size();
+        self.tx_set.contains_key(&tx_hash)
+    }
+
+    /// Marca una transaccion pendiente como local, creada por alguna de las wallets del nodo.
+    pub fn mark_local(&mut self, tx_hash: &Hash32) {
+        if let Some(entry) = self.tx_set.get_mut(tx_hash) {
+            entry.local = true;
         }
-        false
+    }
+
+    /// Devuelve las transacciones pendientes locales, que se reenvian a los peers hasta que se confirmen.
+    /// Se devuelven los padres antes que los hijos, para que los peers puedan validarlas en orden.
+    pub fn get_local_txs(&self) -> Vec<Transaction> {
+        let mut local_txs: Vec<(usize, &Transaction)> = self
+            .tx_set
+            .iter()
+            .filter(|(_, entry)| entry.local)
+            .map(|(tx_hash, entry)| (self.get_ancestors(tx_hash).len(), &entry.transaction))
+            .collect();
+        local_txs.sort_by_key(|(ancestors, _)| *ancestors);
+        local_txs.into_iter().map(|(_, tx)| tx.clone()).collect()
+    }
+
+    /// Devuelve los hashes de las transacciones pendientes que gastan alguna de las salidas que gasta la transaccion.
+    pub fn get_conflicts(&self, transaction: &Transaction) -> HashSet<Hash32> {
+        let tx_hash = transaction.hash();
+        transaction
+            .inputs
+            .iter()
+            .filter_map(|input| self.spent_outpoints.get(&input.previous_output))
+            .filter(|conflict| **conflict != tx_hash)
+            .copied()
+            .collect()
+    }
+
+    /// Devuelve true si alguna transaccion pendiente gasta la salida.
+    pub fn is_spent(&self, out_point: &OutPoint) -> bool {
+        self.spent_outpoints.contains_key(out_point)
     }
 
     /// Actualiza la lista de transacciones pendientes, eliminando las transacciones que esten en el bloque.
-    pub fn update_pending_tx(&mut self, block: &Block) -> Result<(), CustomError> {
+    /// Tambien elimina las que entran en conflicto con el bloque, que gastan salidas que gasta alguna de sus transacciones,
+    /// junto a sus descendientes, ya que nunca se van a poder incluir en un bloque.
+    /// Devuelve las transacciones eliminadas por conflicto.
+    pub fn update_pending_tx(&mut self, block: &Block) -> Result<Vec<Transaction>, CustomError> {
         for tx in &block.transactions {
-            if self.tx_set.contains_key(&tx.hash()) {
-                self.tx_set.remove(&tx.hash());
-            }
+            self.remove_entry(&tx.hash());
         }
 
-        Ok(())
+        let mut conflicted = HashSet::new();
+        for tx in &block.transactions {
+            for conflict in self.get_conflicts(tx) {
+                conflicted.extend(self.get_descendants(&conflict));
+                conflicted.insert(conflict);
+            }
+        }
+        Ok(conflicted
+            .iter()
+            .filter_map(|tx_hash| self.remove_entry(tx_hash))
+            .collect())
     }
 
     /// Devuelve las transacciones pendientes que pertenecen a la wallet.
@@ -60,29 +336,493 @@ impl PendingTxs {
         let pubkey_hash = wallet.get_pubkey_hash()?;
         let mut pending_movements = vec![];
 
-        for tx in self.tx_set.values() {
-            if let Some(mov) = tx.get_movement(&pubkey_hash, utxo)? {
+        for entry in self.tx_set.values() {
+            if let Some(mov) = entry.transaction.get_movement(&pubkey_hash, utxo)? {
                 pending_movements.push(mov);
             }
         }
         Ok(pending_movements)
     }
 
-    pub fn get_pending_tx(&self, tx_hash: &Vec<u8>) -> Option<Transaction> {
-        self.tx_set.get(tx_hash).cloned()
+    pub fn get_pending_tx(&self, tx_hash: &Hash32) -> Option<Transaction> {
+        self.tx_set
+            .get(tx_hash)
+            .map(|entry| entry.transaction.clone())
+    }
+
+    /// Devuelve una transaccion pendiente a partir de su wtxid.
+    pub fn get_pending_tx_by_wtxid(&self, wtxid: &Hash32) -> Option<Transaction> {
+        let tx_hash = self.wtxids.get(wtxid)?;
+        self.get_pending_tx(tx_hash)
+    }
+
+    /// Devuelve la cantidad de transacciones pendientes.
+    pub fn len(&self) -> usize {
+        self.tx_set.len()
+    }
+
+    /// Indica si no hay transacciones pendientes.
+    pub fn is_empty(&self) -> bool {
+        self.tx_set.is_empty()
+    }
+
+    /// Devuelve la suma de los tamaños virtuales de las transacciones pendientes.
+    pub fn size(&self) -> usize {
+        self.total_vsize
+    }
+
+    /// Devuelve el fee en satoshis de una transaccion pendiente.
+    /// Devuelve None si no esta en la mempool o si no se conoce su fee.
+    pub fn get_fee(&self, tx_hash: &Hash32) -> Option<u64> {
+        self.tx_set.get(tx_hash)?.fee
+    }
+
+    /// Devuelve el fee rate de una transaccion pendiente en satoshis por kilobyte virtual.
+    /// Devuelve None si no esta en la mempool o si no se conoce su fee.
+    pub fn get_fee_rate(&self, tx_hash: &Hash32) -> Option<u64> {
+        self.tx_set.get(tx_hash)?.fee_rate()
+    }
+
+    /// Devuelve las transacciones pendientes con un fee rate de al menos min_fee_rate satoshis por kilobyte virtual,
+    /// de mayor a menor fee rate junto a su fee rate. Las de fee desconocido se consideran con fee rate 0.
+    pub fn get_by_fee_rate(&self, min_fee_rate: u64) -> Vec<(&Transaction, u64)> {
+        self.by_fee_rate
+            .iter()
+            .rev()
+            .take_while(|(fee_rate, _)| *fee_rate >= min_fee_rate)
+            .filter_map(|(fee_rate, tx_hash)| {
+                let entry = self.tx_set.get(tx_hash)?;
+                Some((&entry.transaction, *fee_rate))
+            })
+            .collect()
+    }
+
+    /// Devuelve los hashes de todas las transacciones pendientes de las que depende la transaccion, sin incluirla.
+    pub fn get_ancestors(&self, tx_hash: &Hash32) -> HashSet<Hash32> {
+        self.collect_related(tx_hash, |entry| &entry.parents)
+    }
+
+    /// Devuelve los hashes de todas las transacciones pendientes que dependen de la transaccion, sin incluirla.
+    pub fn get_descendants(&self, tx_hash: &Hash32) -> HashSet<Hash32> {
+        self.collect_related(tx_hash, |entry| &entry.children)
+    }
+
+    /// Devuelve el fee rate del paquete formado por la transaccion y sus ancestros, que es el que obtiene
+    /// un minero al incluirla en un bloque. Devuelve None si no se conoce el fee de alguna de ellas.
+    pub fn get_ancestor_fee_rate(&self, tx_hash: &Hash32) -> Option<u64> {
+        let mut fee = 0;
+        let mut vsize = 0;
+        for hash in self.get_ancestors(tx_hash).iter().chain([tx_hash]) {
+            let entry = self.tx_set.get(hash)?;
+            fee += entry.fee?;
+            vsize += entry.vsize;
+        }
+        Some(fee * 1000 / vsize as u64)
+    }
+
+    /// Selecciona las transacciones pendientes a incluir en un bloque nuevo como lo hace un minero: agrega primero el paquete
+    /// de mayor fee rate, formado por una transaccion y sus ancestros todavia no seleccionados, mientras el peso
+    /// y el costo en sigops del bloque no superen los maximos recibidos. Los paquetes que no entran se saltean.
+    /// No se incluyen las transacciones cuyo fee, o el de alguno de sus ancestros, es desconocido.
+    /// Devuelve las transacciones seleccionadas, los padres antes que los hijos.
+    pub fn select_for_block(
+        &self,
+        utxo: &UTXO,
+        max_weight: usize,
+        max_sigops_cost: usize,
+    ) -> Vec<TemplateTransaction> {
+        let mut selected: Vec<TemplateTransaction> = vec![];
+        let mut included: HashSet<Hash32> = HashSet::new();
+        let mut skipped: HashSet<Hash32> = HashSet::new();
+        let mut weight = 0;
+        let mut sigops_cost = 0;
+
+        loop {
+            let best = self
+                .tx_set
+                .keys()
+                .filter(|tx_hash| !included.contains(*tx_hash) && !skipped.contains(*tx_hash))
+                .filter_map(|tx_hash| {
+                    let (fee_rate, package) = self.package_for_block(tx_hash, &included)?;
+                    Some((fee_rate, *tx_hash, package))
+                })
+                .max_by_key(|(fee_rate, tx_hash, _)| (*fee_rate, *tx_hash));
+            let Some((_, tx_hash, package)) = best else {
+                break;
+            };
+
+            let package_txs: Vec<TemplateTransaction> = package
+                .iter()
+                .filter_map(|hash| self.template_transaction(hash, utxo))
+                .collect();
+            let package_weight: usize = package_txs
+                .iter()
+                .map(|template_tx| template_tx.transaction.weight())
+                .sum();
+            let package_sigops_cost: usize = package_txs
+                .iter()
+                .map(|template_tx| template_tx.sigops_cost)
+                .sum();
+            if package_txs.len() != package.len()
+                || weight + package_weight > max_weight
+                || sigops_cost + package_sigops_cost > max_sigops_cost
+            {
+                skipped.insert(tx_hash);
+                continue;
+            }
+
+            weight += package_weight;
+            sigops_cost += package_sigops_cost;
+            included.extend(package);
+            selected.extend(package_txs);
+        }
+        selected
+    }
+
+    /// Devuelve el fee rate del paquete formado por la transaccion y sus ancestros que no estan en included,
+    /// junto a sus hashes ordenados de forma que los padres queden antes que los hijos.
+    /// Devuelve None si no se conoce el fee de alguna de ellas.
+    fn package_for_block(
+        &self,
+        tx_hash: &Hash32,
+        included: &HashSet<Hash32>,
+    ) -> Option<(u64, Vec<Hash32>)> {
+        let mut package: Vec<Hash32> = self
+            .get_ancestors(tx_hash)
+            .into_iter()
+            .filter(|hash| !included.contains(hash))
+            .chain([*tx_hash])
+            .collect();
+        let mut fee = 0;
+        let mut vsize = 0;
+        for hash in &package {
+            let entry = self.tx_set.get(hash)?;
+            fee += entry.fee?;
+            vsize += entry.vsize;
+        }
+        // un padre siempre tiene menos ancestros que sus hijos
+        package.sort_by_key(|hash| self.get_ancestors(hash).len());
+        Some((fee * 1000 / vsize as u64, package))
+    }
+
+    /// Devuelve la transaccion pendiente con su fee y su costo en sigops, calculado con las salidas que gasta.
+    fn template_transaction(&self, tx_hash: &Hash32, utxo: &UTXO) -> Option<TemplateTransaction> {
+        let entry = self.tx_set.get(tx_hash)?;
+        let spent = self.get_spent_outputs(&entry.transaction, utxo);
+        let spent_outputs: Vec<Option<&TransactionOutput>> = spent
+            .iter()
+            .map(|spent| spent.as_ref().map(|(output, _)| output))
+            .collect();
+        Some(TemplateTransaction {
+            transaction: entry.transaction.clone(),
+            fee: entry.fee?,
+            sigops_cost: entry.transaction.sigop_cost(&spent_outputs),
+        })
+    }
+
+    /// Devuelve las transacciones pendientes que se le pueden anunciar a un peer en respuesta a 'mempool':
+    /// las que pagan al menos fee_filter satoshis por kilobyte y coinciden con su bloom filter, si cargo uno.
+    /// Si no se conoce alguna de las salidas que gasta la transaccion no se puede calcular su fee, y se anuncia igual.
+    pub fn get_mempool(
+        &self,
+        fee_filter: u64,
+        bloom_filter: Option<&BloomFilter>,
+    ) -> Vec<&Transaction> {
+        self.tx_set
+            .values()
+            .filter(|entry| {
+                entry
+                    .fee_rate()
+                    .is_none_or(|fee_rate| fee_rate >= fee_filter)
+            })
+            .map(|entry| &entry.transaction)
+            .filter(|tx| bloom_filter.is_none_or(|filter| filter.matches_transaction(tx)))
+            .collect()
+    }
+
+    /// Calcula el fee de la transaccion en satoshis, buscando los valores de sus entradas
+    /// en el UTXO o en las otras transacciones pendientes. Devuelve None si alguna entrada es desconocida.
+    /// Devuelve CustomError::InvalidBalance si la transaccion gasta mas de lo que reciben sus entradas.
+    /// Devuelve las salidas que gastan las entradas de la transaccion, buscandolas en el UTXO o en las transacciones pendientes,
+    /// junto a la altura del bloque que las incluyo, None si la salida es de una transaccion pendiente.
+    pub fn get_spent_outputs(
+        &self,
+        transaction: &Transaction,
+        utxo: &UTXO,
+    ) -> Vec<Option<(TransactionOutput, Option<u32>)>> {
+        transaction
+            .inputs
+            .iter()
+            .map(|input| {
+                let previous_output = &input.previous_output;
+                match utxo.tx_set.get(previous_output) {
+                    Some(utxo_value) => Some((utxo_value.tx_out, Some(utxo_value.height))),
+                    None => self
+                        .tx_set
+                        .get(&previous_output.hash)?
+                        .transaction
+                        .outputs
+                        .get(previous_output.index as usize)
+                        .map(|output| (output.clone(), None)),
+                }
+            })
+            .collect()
+    }
+
+    fn calculate_fee(
+        &self,
+        transaction: &Transaction,
+        utxo: &UTXO,
+    ) -> Result<Option<u64>, CustomError> {
+        let outputs_value = sum_money(transaction.outputs.iter().map(|output| output.value))?;
+        let Some(spent_values) = self
+            .get_spent_outputs(transaction, utxo)
+            .into_iter()
+            .map(|spent_output| spent_output.map(|(output, _)| output.value))
+            .collect::<Option<Vec<u64>>>()
+        else {
+            return Ok(None);
+        };
+        let inputs_value = sum_money(spent_values)?;
+        match inputs_value.checked_sub(outputs_value) {
+            Some(fee) => Ok(Some(fee)),
+            None => Err(CustomError::InvalidBalance),
+        }
+    }
+
+    /// Decide si la transaccion puede reemplazar a las transacciones pendientes con las que entra en conflicto,
+    /// siguiendo las reglas de BIP125:
+    /// - Todas las reemplazadas, o alguno de sus ancestros, deben señalizar que se pueden reemplazar.
+    /// - Solo puede gastar salidas de transacciones pendientes que ya gastaban las reemplazadas.
+    /// - Su fee debe cubrir el de todas las transacciones descartadas, mas INCREMENTAL_RELAY_FEE por su tamaño.
+    /// - Su fee rate debe superar al de cada una de las reemplazadas.
+    /// - No puede descartar mas de MAX_REPLACEMENT_EVICTIONS transacciones.
+    ///
+    /// Devuelve los hashes de las transacciones a descartar, las reemplazadas y sus descendientes, o None si se rechaza.
+    /// Tambien se rechaza si no se conoce el fee de alguna de ellas, ya que no se pueden comparar.
+    fn replacement_evictions(
+        &self,
+        transaction: &Transaction,
+        conflicts: &HashSet<Hash32>,
+        utxo: &UTXO,
+    ) -> Option<HashSet<Hash32>> {
+        let signals_rbf = |hash: &Hash32| {
+            self.tx_set.get(hash).is_some_and(|entry| {
+                entry
+                    .transaction
+                    .inputs
+                    .iter()
+                    .any(|input| input.sequence <= MAX_BIP125_RBF_SEQUENCE)
+            })
+        };
+        let replaceable = conflicts.iter().all(|conflict| {
+            signals_rbf(conflict) || self.get_ancestors(conflict).iter().any(signals_rbf)
+        });
+        if !replaceable {
+            return None;
+        }
+
+        let mut evicted = conflicts.clone();
+        for conflict in conflicts {
+            evicted.extend(self.get_descendants(conflict));
+        }
+        if evicted.len() > MAX_REPLACEMENT_EVICTIONS {
+            return None;
+        }
+
+        for input in &transaction.inputs {
+            let parent = &input.previous_output.hash;
+            if !self.tx_set.contains_key(parent) {
+                continue;
+            }
+            let spent_by_conflict = conflicts.iter().any(|conflict| {
+                self.tx_set
+                    .get(conflict)
+                    .is_some_and(|entry| entry.parents.contains(parent))
+            });
+            if evicted.contains(parent) || !spent_by_conflict {
+                return None;
+            }
+        }
+
+        let fee = self.calculate_fee(transaction, utxo).ok()??;
+        let vsize = transaction.vsize() as u64;
+        let mut evicted_fee = 0;
+        for hash in &evicted {
+            evicted_fee += self.tx_set.get(hash)?.fee?;
+        }
+        if fee < evicted_fee + INCREMENTAL_RELAY_FEE * vsize / 1000 {
+            return None;
+        }
+        let fee_rate = fee * 1000 / vsize;
+        for conflict in conflicts {
+            if fee_rate <= self.tx_set.get(conflict)?.fee_rate()? {
+                return None;
+            }
+        }
+        Some(evicted)
+    }
+
+    /// Registra que la transaccion child gasta una salida de parent, que llego despues que ella.
+    /// Si no se conocia el fee de child se vuelve a calcular, y si gasta mas de lo que recibe
+    /// o no alcanza el fee rate minimo se descarta junto a sus descendientes.
+    fn link_parent(&mut self, child: Hash32, parent: Hash32, utxo: &UTXO) {
+        let Some(entry) = self.tx_set.get_mut(&child) else {
+            return;
+        };
+        entry.parents.insert(parent);
+        if entry.fee.is_some() {
+            return;
+        }
+        let (fee, overspends) = match self.calculate_fee(&self.tx_set[&child].transaction, utxo) {
+            Ok(fee) => (fee, false),
+            Err(_) => (None, true),
+        };
+        let Some(entry) = self.tx_set.get_mut(&child) else {
+            return;
+        };
+        self.by_fee_rate.remove(&(0, child));
+        entry.fee = fee;
+        let fee_rate = entry.fee_rate();
+        self.by_fee_rate.insert((fee_rate.unwrap_or(0), child));
+
+        if overspends || fee_rate.is_some_and(|fee_rate| fee_rate < self.min_relay_fee) {
+            for descendant in self.get_descendants(&child) {
+                self.remove_entry(&descendant);
+            }
+            self.remove_entry(&child);
+        }
+    }
+
+    /// Recorre las transacciones relacionadas a la transaccion siguiendo la relacion recibida (padres o hijos).
+    fn collect_related(
+        &self,
+        tx_hash: &Hash32,
+        related: impl Fn(&MempoolEntry) -> &HashSet<Hash32>,
+    ) -> HashSet<Hash32> {
+        let mut collected = HashSet::new();
+        let mut to_visit = vec![*tx_hash];
+        while let Some(hash) = to_visit.pop() {
+            let Some(entry) = self.tx_set.get(&hash) else {
+                continue;
+            };
+            for related_hash in related(entry) {
+                if collected.insert(*related_hash) {
+                    to_visit.push(*related_hash);
+                }
+            }
+        }
+        collected
+    }
+
+    /// Descarta las transacciones de menor fee rate, junto a sus descendientes, hasta no superar el tamaño maximo.
+    fn trim_to_size(&mut self) {
+        while self.total_vsize > self.max_vsize {
+            let Some((_, tx_hash)) = self.by_fee_rate.first().copied() else {
+                return;
+            };
+            for descendant in self.get_descendants(&tx_hash) {
+                self.remove_entry(&descendant);
+            }
+            self.remove_entry(&tx_hash);
+        }
+    }
+
+    /// Elimina una transaccion de la mempool y de todos sus indices, sus hijos dejan de tenerla como padre.
+    /// Devuelve la transaccion eliminada, None si no estaba en la mempool.
+    fn remove_entry(&mut self, tx_hash: &Hash32) -> Option<Transaction> {
+        let entry = self.tx_set.remove(tx_hash)?;
+        self.wtxids.remove(&entry.transaction.wtxid());
+        self.by_fee_rate
+            .remove(&(entry.fee_rate().unwrap_or(0), *tx_hash));
+        self.total_vsize -= entry.vsize;
+        for input in &entry.transaction.inputs {
+            if self.spent_outpoints.get(&input.previous_output) == Some(tx_hash) {
+                self.spent_outpoints.remove(&input.previous_output);
+            }
+        }
+        for parent in &entry.parents {
+            if let Some(parent_entry) = self.tx_set.get_mut(parent) {
+                parent_entry.children.remove(tx_hash);
+            }
+        }
+        for child in &entry.children {
+            if let Some(child_entry) = self.tx_set.get_mut(child) {
+                child_entry.parents.remove(tx_hash);
+            }
+        }
+        Some(entry.transaction)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::sync::mpsc;
+
     use crate::{
-        states::wallets_state::WalletsState,
-        structs::{block_header::BlockHeader, tx_output::TransactionOutput},
+        states::{utxo_state::UTXOValue, wallets_state::WalletsState},
+        structs::{
+            block_header::BlockHeader, tx_input::TransactionInput, tx_output::TransactionOutput,
+        },
     };
 
     use super::*;
 
+    /// Crea una transaccion que gasta la salida recibida y tiene una unica salida con el valor recibido.
+    fn spend(previous_output: OutPoint, value: u64) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        }
+    }
+
+    /// Crea un UTXO con una salida de 100000 satoshis en el indice 0 de cada hash recibido.
+    fn utxo_with_outputs(hashes: &[Hash32]) -> UTXO {
+        let mut utxo = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
+        for hash in hashes {
+            utxo.tx_set.insert(
+                OutPoint {
+                    hash: *hash,
+                    index: 0,
+                },
+                UTXOValue {
+                    tx_out: TransactionOutput {
+                        value: 100_000,
+                        script_pubkey: vec![0x51],
+                    },
+                    block_hash: Hash32::default(),
+                    block_timestamp: 0,
+                    height: 0,
+                    is_coinbase: false,
+                },
+            );
+        }
+        utxo
+    }
+
+    fn outpoint(hash: Hash32) -> OutPoint {
+        OutPoint { hash, index: 0 }
+    }
+
+    /// Marca las entradas de la transaccion para señalizar que se puede reemplazar (BIP125).
+    fn signal_rbf(mut transaction: Transaction) -> Transaction {
+        for input in &mut transaction.inputs {
+            input.sequence = MAX_BIP125_RBF_SEQUENCE;
+        }
+        transaction
+    }
+
     #[test]
     fn pendings_txs_creation() {
         let pending_txs = PendingTxs::new();
@@ -93,7 +833,10 @@ mod tests {
 
     #[test]
     fn append_pending_tx() {
+        let utxo = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
         let mut pending_txs = PendingTxs::new();
+        // La transaccion sin entradas ni salidas no paga fee
+        pending_txs.set_min_relay_fee(0);
         let tx = Transaction {
             version: 1,
             inputs: vec![],
@@ -101,14 +844,17 @@ mod tests {
             lock_time: 0,
         };
         let tx_hash = tx.hash();
-        pending_txs.append_pending_tx(tx);
+        pending_txs.append_pending_tx(tx, &utxo);
         assert_eq!(pending_txs.tx_set.len(), 1);
         assert_eq!(pending_txs.tx_set.contains_key(&tx_hash), true);
     }
 
     #[test]
     fn append_existing_pending_tx() {
+        let utxo = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
         let mut pending_txs = PendingTxs::new();
+        // La transaccion sin entradas ni salidas no paga fee
+        pending_txs.set_min_relay_fee(0);
         let tx = Transaction {
             version: 1,
             inputs: vec![],
@@ -117,9 +863,9 @@ mod tests {
         };
         let tx_hash = tx.hash();
 
-        let updated = pending_txs.append_pending_tx(tx.clone());
+        let updated = pending_txs.append_pending_tx(tx.clone(), &utxo);
         assert_eq!(updated, true);
-        let updated = pending_txs.append_pending_tx(tx);
+        let updated = pending_txs.append_pending_tx(tx, &utxo);
         assert_eq!(updated, false);
 
         assert_eq!(pending_txs.tx_set.len(), 1);
@@ -128,7 +874,10 @@ mod tests {
 
     #[test]
     fn update_pendings() {
+        let utxo = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
         let mut pending_txs = PendingTxs::new();
+        // La transaccion sin entradas ni salidas no paga fee
+        pending_txs.set_min_relay_fee(0);
         let tx = Transaction {
             version: 1,
             inputs: vec![],
@@ -139,12 +888,12 @@ mod tests {
         let block = Block {
             header: BlockHeader {
                 version: 536887296,
-                prev_block_hash: vec![],
-                merkle_root: vec![],
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
                 timestamp: 1686626483,
                 bits: 421617023,
                 nonce: 3878826733,
-                hash: vec![],
+                hash: Hash32::default(),
                 block_downloaded: true,
                 broadcasted: true,
             },
@@ -152,7 +901,7 @@ mod tests {
             transactions: vec![tx.clone()],
         };
 
-        let updated = pending_txs.append_pending_tx(tx);
+        let updated = pending_txs.append_pending_tx(tx, &utxo);
         assert_eq!(updated, true);
         assert_eq!(pending_txs.tx_set.len(), 1);
 
@@ -160,17 +909,56 @@ mod tests {
         assert_eq!(pending_txs.tx_set.len(), 0);
     }
 
+    #[test]
+    fn block_double_spend_drops_conflicts_and_descendants() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let conflicted = spend(outpoint(Hash32::new([1; 32])), 99_000);
+        let child = spend(outpoint(conflicted.hash()), 98_000);
+        let unrelated = spend(outpoint(Hash32::new([2; 32])), 99_000);
+        pending_txs.append_pending_tx(conflicted.clone(), &utxo);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+        pending_txs.append_pending_tx(unrelated.clone(), &utxo);
+
+        let mined = spend(outpoint(Hash32::new([1; 32])), 90_000);
+        let block = Block {
+            header: BlockHeader {
+                version: 536887296,
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
+                timestamp: 1686626483,
+                bits: 421617023,
+                nonce: 3878826733,
+                hash: Hash32::default(),
+                block_downloaded: true,
+                broadcasted: true,
+            },
+            transactions: vec![mined],
+        };
+        let dropped: HashSet<Hash32> = pending_txs
+            .update_pending_tx(&block)
+            .unwrap()
+            .iter()
+            .map(|tx| tx.hash())
+            .collect();
+        assert_eq!(dropped, HashSet::from([conflicted.hash(), child.hash()]));
+        assert_eq!(pending_txs.len(), 1);
+        assert!(pending_txs.get_pending_tx(&unrelated.hash()).is_some());
+    }
+
     #[test]
     fn pendings_from_wallet() {
-        let mut wallets = WalletsState::new("tests/test_wallets.bin".to_string()).unwrap();
+        let mut wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         wallets
             .set_active("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm")
             .unwrap();
 
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
         let mut pending_txs = PendingTxs::new();
         let tx = Transaction {
             version: 1,
-            inputs: vec![],
+            inputs: spend(outpoint(Hash32::new([1; 32])), 0).inputs,
             outputs: vec![TransactionOutput {
                 value: 100,
                 script_pubkey: vec![
@@ -181,15 +969,275 @@ mod tests {
             lock_time: 0,
         };
 
-        pending_txs.append_pending_tx(tx);
+        pending_txs.append_pending_tx(tx, &utxo);
 
         let pendings_from_wallet = pending_txs
-            .from_wallet(
-                &wallets.get_active().unwrap(),
-                &UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap(),
-            )
+            .from_wallet(wallets.get_active().unwrap(), &utxo)
             .unwrap();
         assert_eq!(pendings_from_wallet.len(), 1);
         assert_eq!(pendings_from_wallet[0].value, 100);
     }
+
+    #[test]
+    fn mempool_respects_fee_filter_and_bloom_filter() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let parent = spend(outpoint(Hash32::new([1; 32])), 80_000);
+        let child = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: parent.hash(),
+                    index: 0,
+                },
+                script_sig: vec![],
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 70_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let child_fee_rate = 10_000 * 1000 / child.vsize() as u64;
+        pending_txs.append_pending_tx(parent.clone(), &utxo);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+
+        assert_eq!(pending_txs.get_mempool(0, None).len(), 2);
+        let mempool = pending_txs.get_mempool(child_fee_rate + 1, None);
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool[0].hash(), parent.hash());
+
+        let mut filter = BloomFilter::new(1, 0.0001, 0);
+        filter.insert(child.hash().as_bytes());
+        let mempool = pending_txs.get_mempool(child_fee_rate, Some(&filter));
+        assert_eq!(mempool.len(), 1);
+        assert_eq!(mempool[0].hash(), child.hash());
+    }
+
+    #[test]
+    fn mempool_tracks_ancestors_and_descendants() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let grandparent = spend(outpoint(Hash32::new([1; 32])), 90_000);
+        let parent = spend(outpoint(grandparent.hash()), 89_000);
+        let child = spend(outpoint(parent.hash()), 50_000);
+        assert!(pending_txs.append_pending_tx(grandparent.clone(), &utxo));
+        assert!(pending_txs.append_pending_tx(parent.clone(), &utxo));
+        assert!(pending_txs.append_pending_tx(child.clone(), &utxo));
+
+        assert_eq!(
+            pending_txs.get_ancestors(&child.hash()),
+            HashSet::from([grandparent.hash(), parent.hash()])
+        );
+        assert_eq!(
+            pending_txs.get_descendants(&grandparent.hash()),
+            HashSet::from([parent.hash(), child.hash()])
+        );
+        assert_eq!(
+            pending_txs.get_fee_rate(&parent.hash()),
+            Some(1000 * 1000 / parent.vsize() as u64)
+        );
+        let package_vsize = grandparent.vsize() + parent.vsize() + child.vsize();
+        assert_eq!(
+            pending_txs.get_ancestor_fee_rate(&child.hash()),
+            Some(50_000 * 1000 / package_vsize as u64)
+        );
+
+        let by_fee_rate: Vec<Hash32> = pending_txs
+            .get_by_fee_rate(0)
+            .into_iter()
+            .map(|(tx, _)| tx.hash())
+            .collect();
+        assert_eq!(
+            by_fee_rate,
+            vec![child.hash(), grandparent.hash(), parent.hash()]
+        );
+        assert_eq!(pending_txs.get_by_fee_rate(200_000).len(), 1);
+        assert_eq!(pending_txs.size(), package_vsize);
+    }
+
+    #[test]
+    fn select_transactions_for_block_by_package_fee_rate() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let parent = spend(outpoint(Hash32::new([1; 32])), 99_000);
+        let child = spend(outpoint(parent.hash()), 50_000);
+        let other = spend(outpoint(Hash32::new([2; 32])), 90_000);
+        pending_txs.append_pending_tx(parent.clone(), &utxo);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+        pending_txs.append_pending_tx(other.clone(), &utxo);
+
+        // el paquete del hijo paga mas que other, por lo que el padre entra antes aunque pague menos
+        let selected: Vec<(Hash32, u64)> = pending_txs
+            .select_for_block(&utxo, usize::MAX, usize::MAX)
+            .into_iter()
+            .map(|template_tx| (template_tx.transaction.hash(), template_tx.fee))
+            .collect();
+        assert_eq!(
+            selected,
+            vec![
+                (parent.hash(), 1_000),
+                (child.hash(), 49_000),
+                (other.hash(), 10_000)
+            ]
+        );
+
+        let selected = pending_txs.select_for_block(&utxo, other.weight(), usize::MAX);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].transaction.hash(), other.hash());
+    }
+
+    #[test]
+    fn mempool_calculates_fee_when_parent_arrives_later() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let parent = spend(outpoint(Hash32::new([1; 32])), 90_000);
+        let child = spend(outpoint(parent.hash()), 80_000);
+
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+        assert_eq!(pending_txs.get_fee_rate(&child.hash()), None);
+
+        pending_txs.append_pending_tx(parent.clone(), &utxo);
+        assert_eq!(
+            pending_txs.get_fee_rate(&child.hash()),
+            Some(10_000 * 1000 / child.vsize() as u64)
+        );
+        assert_eq!(
+            pending_txs.get_ancestors(&child.hash()),
+            HashSet::from([parent.hash()])
+        );
+    }
+
+    #[test]
+    fn mempool_evicts_lowest_fee_rate_with_descendants() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let parent = spend(outpoint(Hash32::new([1; 32])), 99_000);
+        let child = spend(outpoint(parent.hash()), 50_000);
+        let other = spend(outpoint(Hash32::new([2; 32])), 95_000);
+        pending_txs.append_pending_tx(parent.clone(), &utxo);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+        pending_txs.append_pending_tx(other.clone(), &utxo);
+
+        pending_txs.set_max_size(pending_txs.size() - 1);
+        assert_eq!(pending_txs.len(), 1);
+        assert!(pending_txs.get_pending_tx(&other.hash()).is_some());
+        assert_eq!(pending_txs.size(), other.vsize());
+
+        pending_txs.set_max_size(other.vsize());
+        let cheaper = spend(outpoint(Hash32::new([1; 32])), 99_500);
+        assert!(!pending_txs.append_pending_tx(cheaper, &utxo));
+        assert_eq!(pending_txs.len(), 1);
+    }
+
+    #[test]
+    fn transactions_below_min_relay_fee_are_rejected() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        pending_txs.set_min_relay_fee(5000);
+        let cheap = spend(outpoint(Hash32::new([1; 32])), 99_900);
+        assert!(!pending_txs.append_pending_tx(cheap, &utxo));
+        assert!(pending_txs.is_empty());
+
+        // El fee del hijo recien se conoce al llegar el padre, y ahi se descarta
+        let parent = spend(outpoint(Hash32::new([2; 32])), 90_000);
+        let child = spend(outpoint(parent.hash()), 89_990);
+        assert!(pending_txs.append_pending_tx(child.clone(), &utxo));
+        assert!(pending_txs.append_pending_tx(parent.clone(), &utxo));
+        assert!(pending_txs.get_pending_tx(&child.hash()).is_none());
+        assert_eq!(pending_txs.len(), 1);
+    }
+
+    #[test]
+    fn transactions_spending_more_than_their_inputs_are_rejected() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let overspending = spend(outpoint(Hash32::new([1; 32])), 100_001);
+        assert!(!pending_txs.append_pending_tx(overspending, &utxo));
+
+        let parent = spend(outpoint(Hash32::new([2; 32])), 90_000);
+        let child = spend(outpoint(parent.hash()), 95_000);
+        assert!(pending_txs.append_pending_tx(child.clone(), &utxo));
+        assert!(pending_txs.append_pending_tx(parent, &utxo));
+        assert!(pending_txs.get_pending_tx(&child.hash()).is_none());
+        assert_eq!(pending_txs.len(), 1);
+    }
+
+    #[test]
+    fn replacement_evicts_conflicts_and_descendants() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let original = signal_rbf(spend(outpoint(Hash32::new([1; 32])), 99_000));
+        let child = spend(outpoint(original.hash()), 98_000);
+        pending_txs.append_pending_tx(original.clone(), &utxo);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+
+        let replacement = spend(outpoint(Hash32::new([1; 32])), 90_000);
+        assert_eq!(
+            pending_txs.get_conflicts(&replacement),
+            HashSet::from([original.hash()])
+        );
+        assert!(pending_txs.append_pending_tx(replacement.clone(), &utxo));
+        assert_eq!(pending_txs.len(), 1);
+        assert!(pending_txs.get_pending_tx(&original.hash()).is_none());
+        assert!(pending_txs.get_pending_tx(&child.hash()).is_none());
+        assert!(pending_txs
+            .get_conflicts(&original)
+            .contains(&replacement.hash()));
+        assert_eq!(pending_txs.size(), replacement.vsize());
+    }
+
+    #[test]
+    fn replacement_is_rejected_without_signal_or_enough_fee() {
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::new();
+        let final_tx = spend(outpoint(Hash32::new([1; 32])), 99_000);
+        pending_txs.append_pending_tx(final_tx.clone(), &utxo);
+        let double_spend = spend(outpoint(Hash32::new([1; 32])), 50_000);
+        assert!(!pending_txs.append_pending_tx(double_spend.clone(), &utxo));
+        assert!(pending_txs.get_pending_tx(&final_tx.hash()).is_some());
+        assert!(pending_txs.get_pending_tx(&double_spend.hash()).is_none());
+
+        let original = signal_rbf(spend(outpoint(Hash32::new([2; 32])), 99_000));
+        pending_txs.append_pending_tx(original.clone(), &utxo);
+        let same_fee = spend(outpoint(Hash32::new([2; 32])), 98_999);
+        assert!(!pending_txs.append_pending_tx(same_fee, &utxo));
+        assert!(pending_txs.get_pending_tx(&original.hash()).is_some());
+        assert_eq!(pending_txs.len(), 2);
+    }
+
+    #[test]
+    fn mempool_is_saved_and_restored() -> Result<(), CustomError> {
+        let path = String::from("tests/test_mempool.bin");
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32]), Hash32::new([2; 32])]);
+        let mut pending_txs = PendingTxs::from_file(path.clone())?;
+        let parent = spend(outpoint(Hash32::new([1; 32])), 90_000);
+        let child = spend(outpoint(parent.hash()), 80_000);
+        let mined = spend(outpoint(Hash32::new([2; 32])), 90_000);
+        pending_txs.append_pending_tx(child.clone(), &utxo);
+        pending_txs.append_pending_tx(parent.clone(), &utxo);
+        pending_txs.append_pending_tx(mined.clone(), &utxo);
+        pending_txs.mark_local(&parent.hash());
+        pending_txs.flush()?;
+
+        let mut restored = PendingTxs::from_file(path.clone())?;
+        assert!(restored.is_empty());
+
+        // La salida que gastaba mined ya no esta en el UTXO, se incluyo en un bloque
+        let utxo = utxo_with_outputs(&[Hash32::new([1; 32])]);
+        assert_eq!(restored.restore_saved(&utxo), 2);
+        assert!(restored.get_pending_tx(&mined.hash()).is_none());
+        let local_txs = restored.get_local_txs();
+        assert_eq!(local_txs.len(), 1);
+        assert_eq!(local_txs[0].hash(), parent.hash());
+        assert_eq!(
+            restored.get_fee_rate(&child.hash()),
+            Some(10_000 * 1000 / child.vsize() as u64)
+        );
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
 }
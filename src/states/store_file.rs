@@ -0,0 +1,261 @@
+use crate::{error::CustomError, parser::BufferParser, structs::hash32::Hash32};
+
+/// Magic bytes con los que comienzan los archivos que guarda el nodo.
+const STORE_MAGIC: [u8; 4] = *b"BNST";
+/// Version de los archivos guardados antes de que tuvieran encabezado, que se leen sin verificar.
+pub const LEGACY_VERSION: u32 = 0;
+/// Tamaño del encabezado de los archivos: magic, tipo de archivo y version del formato.
+pub const STORE_HEADER_SIZE: usize = 12;
+/// Tamaño del checksum que sigue al encabezado en los archivos que se reescriben completos.
+const CHECKSUM_SIZE: usize = 4;
+
+/// Migracion del contenido de un archivo, sin el encabezado, de una version de su formato a la siguiente.
+pub type Migration = fn(Vec<u8>) -> Result<Vec<u8>, CustomError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Tipos de archivo que guarda el nodo, cada uno con su propio formato y version.
+pub enum StoreFileKind {
+    Headers,
+    Utxo,
+    Wallets,
+    UtxoSnapshot,
+    SnapshotBase,
+    AddressBook,
+    WalletsBackup,
+}
+
+impl StoreFileKind {
+    /// Identificador del tipo de archivo, que se escribe despues del magic.
+    fn tag(&self) -> [u8; 4] {
+        match self {
+            Self::Headers => *b"HDRS",
+            Self::Utxo => *b"UTXO",
+            Self::Wallets => *b"WLTS",
+            Self::UtxoSnapshot => *b"SNAP",
+            Self::SnapshotBase => *b"BASE",
+            Self::AddressBook => *b"ADDR",
+            Self::WalletsBackup => *b"WBAK",
+        }
+    }
+
+    /// Migraciones del formato de los archivos de este tipo, la de la posicion i lleva el contenido de la version i a la i + 1.
+    /// Para cambiar la serializacion de un archivo se agrega al final la migracion desde la version anterior,
+    /// y los archivos guardados se convierten al abrirlos en lugar de tener que volver a descargarlos.
+    fn migrations(&self) -> &'static [Migration] {
+        match self {
+            Self::Headers => &[unchanged_contents],
+            Self::Utxo => &[unchanged_contents],
+            Self::Wallets => &[unchanged_contents],
+            Self::UtxoSnapshot => &[unchanged_contents],
+            Self::SnapshotBase => &[unchanged_contents],
+            Self::AddressBook => &[unchanged_contents],
+            Self::WalletsBackup => &[unchanged_contents],
+        }
+    }
+
+    /// Version del formato con el que se escriben los archivos de este tipo.
+    pub fn version(&self) -> u32 {
+        self.migrations().len() as u32
+    }
+
+    /// Convierte el contenido de un archivo de este tipo de la version recibida a la actual.
+    /// Devuelve CustomError::StoreFileCorrupt si alguna de las migraciones no puede convertir el contenido.
+    pub fn migrate(&self, version: u32, contents: Vec<u8>) -> Result<Vec<u8>, CustomError> {
+        apply_migrations(self.migrations(), version, contents)
+    }
+}
+
+/// Aplica al contenido las migraciones desde la version recibida en adelante.
+fn apply_migrations(
+    migrations: &[Migration],
+    version: u32,
+    mut contents: Vec<u8>,
+) -> Result<Vec<u8>, CustomError> {
+    for migration in migrations.iter().skip(version as usize) {
+        contents = migration(contents).map_err(|_| CustomError::StoreFileCorrupt)?;
+    }
+    Ok(contents)
+}
+
+/// Migracion de los archivos sin encabezado a la version 1, que solo agrego el encabezado sin cambiar el contenido.
+fn unchanged_contents(contents: Vec<u8>) -> Result<Vec<u8>, CustomError> {
+    Ok(contents)
+}
+
+/// Serializa el encabezado de un archivo del tipo recibido, con la version actual de su formato.
+pub fn store_file_header(kind: StoreFileKind) -> Vec<u8> {
+    let mut buffer = STORE_MAGIC.to_vec();
+    buffer.extend(kind.tag());
+    buffer.extend(kind.version().to_le_bytes());
+    buffer
+}
+
+/// Lee el encabezado al comienzo del archivo y devuelve la version de su formato.
+/// Si el archivo no comienza con el magic es del formato anterior, sin encabezado: devuelve LEGACY_VERSION sin consumir nada.
+/// Devuelve CustomError::StoreFileIncompatible si el archivo es de otro tipo o de una version mas nueva que la soportada.
+pub fn read_store_file_header(
+    kind: StoreFileKind,
+    parser: &mut BufferParser,
+) -> Result<u32, CustomError> {
+    if parser.peek(STORE_MAGIC.len()) != Some(STORE_MAGIC.as_slice()) {
+        return Ok(LEGACY_VERSION);
+    }
+    parser.extract_buffer(STORE_MAGIC.len())?;
+    if parser
+        .extract_buffer(4)
+        .map_err(|_| CustomError::StoreFileCorrupt)?
+        != kind.tag()
+    {
+        return Err(CustomError::StoreFileIncompatible);
+    }
+    let version = parser
+        .extract_u32()
+        .map_err(|_| CustomError::StoreFileCorrupt)?;
+    if version > kind.version() {
+        return Err(CustomError::StoreFileIncompatible);
+    }
+    Ok(version)
+}
+
+/// Serializa un archivo que se reescribe completo: el encabezado, el checksum del contenido y el contenido.
+pub fn seal_store_file(kind: StoreFileKind, contents: &[u8]) -> Vec<u8> {
+    let mut buffer = store_file_header(kind);
+    buffer.extend(checksum(contents));
+    buffer.extend(contents);
+    buffer
+}
+
+/// Calcula el checksum del contenido, los primeros bytes de su doble sha256 como en los mensajes de la red.
+fn checksum(contents: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let hash = Hash32::sha256d(contents);
+    let mut checksum = [0; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&hash.as_bytes()[..CHECKSUM_SIZE]);
+    checksum
+}
+
+#[derive(Debug)]
+/// StoreFile es el contenido leido de un archivo que se reescribe completo (UTXO, wallets).
+/// - version: Version del formato con el que estaba guardado el archivo, LEGACY_VERSION si no tenia encabezado.
+/// - contents: Contenido del archivo, sin el encabezado ni el checksum, ya convertido a la version actual del formato.
+/// - intact: Indica si el checksum coincide con el contenido. Los archivos sin encabezado no se pueden verificar.
+pub struct StoreFile {
+    pub version: u32,
+    pub contents: Vec<u8>,
+    pub intact: bool,
+}
+
+impl StoreFile {
+    /// Lee un archivo escrito con seal_store_file, o uno del formato anterior sin encabezado,
+    /// y convierte su contenido a la version actual del formato.
+    /// Devuelve CustomError::StoreFileIncompatible si es de otro tipo o de una version mas nueva,
+    /// y CustomError::StoreFileCorrupt si el encabezado esta incompleto o no se puede migrar el contenido.
+    pub fn parse(kind: StoreFileKind, buffer: Vec<u8>) -> Result<Self, CustomError> {
+        let mut parser = BufferParser::new(buffer);
+        let version = read_store_file_header(kind, &mut parser)?;
+        if version == LEGACY_VERSION {
+            let length = parser.len();
+            return Ok(Self {
+                version,
+                contents: kind.migrate(version, parser.extract_buffer(length)?.to_vec())?,
+                intact: true,
+            });
+        }
+
+        let expected_checksum = parser
+            .extract_buffer(CHECKSUM_SIZE)
+            .map_err(|_| CustomError::StoreFileCorrupt)?
+            .to_vec();
+        let length = parser.len();
+        let contents = parser.extract_buffer(length)?.to_vec();
+        let intact = checksum(&contents).as_slice() == expected_checksum;
+        Ok(Self {
+            version,
+            contents: kind.migrate(version, contents)?,
+            intact,
+        })
+    }
+
+    /// Indica si el archivo estaba guardado con una version anterior del formato, y hay que reescribirlo.
+    pub fn is_outdated(&self, kind: StoreFileKind) -> bool {
+        self.version < kind.version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_parse_store_file() {
+        let buffer = seal_store_file(StoreFileKind::Wallets, &[1, 2, 3]);
+        assert_eq!(buffer.len(), STORE_HEADER_SIZE + CHECKSUM_SIZE + 3);
+
+        let file = StoreFile::parse(StoreFileKind::Wallets, buffer.clone()).unwrap();
+        assert_eq!(file.version, StoreFileKind::Wallets.version());
+        assert_eq!(file.contents, vec![1, 2, 3]);
+        assert!(file.intact);
+
+        let mut corrupt = buffer.clone();
+        corrupt[STORE_HEADER_SIZE + CHECKSUM_SIZE] = 9;
+        let file = StoreFile::parse(StoreFileKind::Wallets, corrupt).unwrap();
+        assert!(!file.intact);
+
+        let file = StoreFile::parse(StoreFileKind::Wallets, vec![1, 2, 3]).unwrap();
+        assert_eq!(file.version, LEGACY_VERSION);
+        assert_eq!(file.contents, vec![1, 2, 3]);
+        assert!(file.intact);
+    }
+
+    #[test]
+    fn incompatible_store_files() {
+        let buffer = seal_store_file(StoreFileKind::Utxo, &[1, 2, 3]);
+        assert!(matches!(
+            StoreFile::parse(StoreFileKind::Wallets, buffer),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        let mut buffer = seal_store_file(StoreFileKind::Utxo, &[1, 2, 3]);
+        buffer[8..STORE_HEADER_SIZE]
+            .copy_from_slice(&(StoreFileKind::Utxo.version() + 1).to_le_bytes());
+        assert!(matches!(
+            StoreFile::parse(StoreFileKind::Utxo, buffer),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        assert!(matches!(
+            StoreFile::parse(StoreFileKind::Utxo, STORE_MAGIC.to_vec()),
+            Err(CustomError::StoreFileCorrupt)
+        ));
+    }
+
+    #[test]
+    fn migrations_are_applied_from_the_saved_version() {
+        fn duplicate(contents: Vec<u8>) -> Result<Vec<u8>, CustomError> {
+            Ok([contents.clone(), contents].concat())
+        }
+        fn drop_first(contents: Vec<u8>) -> Result<Vec<u8>, CustomError> {
+            match contents.split_first() {
+                Some((_, rest)) => Ok(rest.to_vec()),
+                None => Err(CustomError::SerializedBufferIsInvalid),
+            }
+        }
+        let migrations: [Migration; 3] = [unchanged_contents, duplicate, drop_first];
+
+        assert_eq!(
+            apply_migrations(&migrations, 0, vec![1, 2]).unwrap(),
+            vec![2, 1, 2]
+        );
+        assert_eq!(
+            apply_migrations(&migrations, 2, vec![1, 2]).unwrap(),
+            vec![2]
+        );
+        assert_eq!(
+            apply_migrations(&migrations, 3, vec![1, 2]).unwrap(),
+            vec![1, 2]
+        );
+        assert!(matches!(
+            apply_migrations(&migrations, 1, vec![]),
+            Err(CustomError::StoreFileCorrupt)
+        ));
+    }
+}
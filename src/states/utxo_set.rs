@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::structs::{
+    hash32::Hash32,
+    outpoint::OutPoint,
+    script::{ScriptType, OP_0, OP_1, OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160},
+    tx_output::TransactionOutput,
+};
+
+use super::utxo_state::UTXOValue;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Script pubkey guardado de forma compacta: de los tipos estandar solo se guarda el hash o la clave que contienen,
+/// y el resto de los scripts se guardan completos en un buffer del tamaño justo.
+enum CompactScript {
+    P2PKH([u8; 20]),
+    P2SH([u8; 20]),
+    P2WPKH([u8; 20]),
+    P2WSH([u8; 32]),
+    P2TR([u8; 32]),
+    Other(Box<[u8]>),
+}
+
+impl CompactScript {
+    /// Comprime el script recibido segun su tipo.
+    fn compress(script: &[u8]) -> Self {
+        let compressed = match ScriptType::classify(script) {
+            ScriptType::P2PKH => script[3..23].try_into().ok().map(Self::P2PKH),
+            ScriptType::P2SH => script[2..22].try_into().ok().map(Self::P2SH),
+            ScriptType::P2WPKH => script[2..22].try_into().ok().map(Self::P2WPKH),
+            ScriptType::P2WSH => script[2..34].try_into().ok().map(Self::P2WSH),
+            ScriptType::P2TR => script[2..34].try_into().ok().map(Self::P2TR),
+            _ => None,
+        };
+        compressed.unwrap_or_else(|| Self::Other(script.into()))
+    }
+
+    /// Reconstruye el script completo.
+    fn expand(&self) -> Vec<u8> {
+        match self {
+            Self::P2PKH(hash) => [
+                [OP_DUP, OP_HASH160, 20].as_slice(),
+                hash,
+                &[OP_EQUALVERIFY, OP_CHECKSIG],
+            ]
+            .concat(),
+            Self::P2SH(hash) => [[OP_HASH160, 20].as_slice(), hash, &[OP_EQUAL]].concat(),
+            Self::P2WPKH(hash) => [[OP_0, 20].as_slice(), hash].concat(),
+            Self::P2WSH(hash) => [[OP_0, 32].as_slice(), hash].concat(),
+            Self::P2TR(key) => [[OP_1, 32].as_slice(), key].concat(),
+            Self::Other(script) => script.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Salida no gastada guardada de forma compacta.
+/// - value: Valor de la salida.
+/// - script: Script pubkey comprimido.
+/// - block: Posicion en la tabla de bloques del hash del bloque donde se encuentra la salida.
+/// - block_timestamp: Timestamp del bloque donde se encuentra la salida.
+/// - height: Altura del bloque donde se encuentra la salida.
+/// - is_coinbase: Indica si la salida es de la coinbase del bloque.
+struct CompactUtxo {
+    value: u64,
+    script: CompactScript,
+    block: u32,
+    block_timestamp: u32,
+    height: u32,
+    is_coinbase: bool,
+}
+
+#[derive(Debug, Default)]
+/// UtxoSet es el conjunto de las UTXO indexadas por su OutPoint, guardadas de forma compacta para que entren
+/// en memoria aun con muchas salidas: los scripts estandar se guardan como su hash, y el hash del bloque,
+/// que comparten todas las salidas de un mismo bloque, se guarda una sola vez en una tabla de bloques.
+/// Los valores se reconstruyen como UTXOValue al leerlos.
+/// Los elementos son:
+/// - utxos: Salidas no gastadas indexadas por su OutPoint.
+/// - blocks: Hashes de los bloques de las salidas.
+/// - block_ids: Posicion de cada bloque en blocks, indexada por su hash.
+pub struct UtxoSet {
+    utxos: HashMap<OutPoint, CompactUtxo>,
+    blocks: Vec<Hash32>,
+    block_ids: HashMap<Hash32, u32>,
+}
+
+impl UtxoSet {
+    /// Crea un conjunto de UTXO vacio.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Devuelve la cantidad de UTXO.
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    /// Indica si no hay UTXO.
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    /// Indica si la salida del OutPoint recibido no esta gastada.
+    pub fn contains_key(&self, out_point: &OutPoint) -> bool {
+        self.utxos.contains_key(out_point)
+    }
+
+    /// Devuelve la UTXO del OutPoint recibido, None si no esta en el conjunto.
+    pub fn get(&self, out_point: &OutPoint) -> Option<UTXOValue> {
+        self.utxos.get(out_point).map(|utxo| self.expand(utxo))
+    }
+
+    /// Agrega la UTXO, reemplazando la que tuviera el mismo OutPoint.
+    pub fn insert(&mut self, out_point: OutPoint, value: UTXOValue) {
+        let block = match self.block_ids.get(&value.block_hash) {
+            Some(block) => *block,
+            None => {
+                let block = self.blocks.len() as u32;
+                self.blocks.push(value.block_hash);
+                self.block_ids.insert(value.block_hash, block);
+                block
+            }
+        };
+        let utxo = CompactUtxo {
+            value: value.tx_out.value,
+            script: CompactScript::compress(&value.tx_out.script_pubkey),
+            block,
+            block_timestamp: value.block_timestamp,
+            height: value.height,
+            is_coinbase: value.is_coinbase,
+        };
+        self.utxos.insert(out_point, utxo);
+    }
+
+    /// Quita la UTXO del OutPoint recibido y la devuelve, None si no estaba en el conjunto.
+    pub fn remove(&mut self, out_point: &OutPoint) -> Option<UTXOValue> {
+        let utxo = self.utxos.remove(out_point)?;
+        Some(self.expand(&utxo))
+    }
+
+    /// Devuelve todas las UTXO con su OutPoint, en un orden arbitrario.
+    pub fn iter(&self) -> impl Iterator<Item = (&OutPoint, UTXOValue)> + '_ {
+        self.utxos
+            .iter()
+            .map(|(out_point, utxo)| (out_point, self.expand(utxo)))
+    }
+
+    /// Devuelve la mayor altura de los bloques de las UTXO, 0 si no hay ninguna.
+    pub fn max_height(&self) -> u32 {
+        self.utxos
+            .values()
+            .map(|utxo| utxo.height)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn expand(&self, utxo: &CompactUtxo) -> UTXOValue {
+        UTXOValue {
+            tx_out: TransactionOutput {
+                value: utxo.value,
+                script_pubkey: utxo.script.expand(),
+            },
+            block_hash: self.blocks[utxo.block as usize],
+            block_timestamp: utxo.block_timestamp,
+            height: utxo.height,
+            is_coinbase: utxo.is_coinbase,
+        }
+    }
+}
+
+impl PartialEq for UtxoSet {
+    /// Dos conjuntos son iguales si tienen las mismas UTXO, sin importar el orden de sus tablas de bloques.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(out_point, value)| other.get(out_point) == Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::*;
+
+    #[test]
+    fn compress_and_expand_scripts() {
+        let mut p2pkh = vec![OP_DUP, OP_HASH160, 20];
+        p2pkh.extend([1; 20]);
+        p2pkh.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+        let mut p2sh = vec![OP_HASH160, 20];
+        p2sh.extend([2; 20]);
+        p2sh.push(OP_EQUAL);
+        let mut p2wpkh = vec![OP_0, 20];
+        p2wpkh.extend([3; 20]);
+        let mut p2wsh = vec![OP_0, 32];
+        p2wsh.extend([4; 32]);
+        let mut p2tr = vec![OP_1, 32];
+        p2tr.extend([5; 32]);
+        let other = vec![OP_DUP, 1, 2, 3];
+
+        for script in [p2pkh, p2sh, p2wpkh, p2wsh, p2tr] {
+            let compressed = CompactScript::compress(&script);
+            assert!(!matches!(compressed, CompactScript::Other(_)));
+            assert_eq!(compressed.expand(), script);
+        }
+        assert_eq!(
+            CompactScript::compress(&other),
+            CompactScript::Other(other.clone().into())
+        );
+        assert_eq!(CompactScript::compress(&other).expand(), other);
+    }
+
+    #[test]
+    fn insert_get_and_remove_utxos() {
+        let mut utxo_set = UtxoSet::new();
+        let out_point = |index| OutPoint {
+            hash: Hash32::new([index as u8; 32]),
+            index,
+        };
+        let value = |script_pubkey, block| UTXOValue {
+            tx_out: TransactionOutput {
+                value: 1000,
+                script_pubkey,
+            },
+            block_hash: Hash32::new([block; 32]),
+            block_timestamp: block as u32,
+            height: block as u32,
+            is_coinbase: block == 1,
+        };
+        let mut p2wpkh = vec![OP_0, 20];
+        p2wpkh.extend([3; 20]);
+
+        utxo_set.insert(out_point(0), value(p2wpkh.clone(), 1));
+        utxo_set.insert(out_point(1), value(vec![1, 2, 3], 1));
+        utxo_set.insert(out_point(2), value(p2wpkh.clone(), 2));
+        assert_eq!(utxo_set.len(), 3);
+        assert_eq!(utxo_set.blocks.len(), 2);
+        assert_eq!(utxo_set.max_height(), 2);
+
+        assert_eq!(utxo_set.get(&out_point(0)), Some(value(p2wpkh.clone(), 1)));
+        assert_eq!(utxo_set.get(&out_point(1)), Some(value(vec![1, 2, 3], 1)));
+        assert_eq!(utxo_set.remove(&out_point(2)), Some(value(p2wpkh, 2)));
+        assert!(!utxo_set.contains_key(&out_point(2)));
+        assert_eq!(utxo_set.remove(&out_point(2)), None);
+        assert_eq!(utxo_set.iter().count(), 2);
+
+        // los scripts estandar no usan memoria fuera de la salida
+        assert!(size_of::<CompactUtxo>() <= 64);
+    }
+}
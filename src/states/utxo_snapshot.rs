@@ -0,0 +1,211 @@
+use std::{fs, path::Path, sync::mpsc::Sender};
+
+use crate::{
+    error::CustomError, logger::Log, network::Network, parser::BufferParser,
+    structs::hash32::Hash32, utils::write_file_atomically,
+};
+
+use super::{
+    headers_state::HeadersState,
+    store_file::{seal_store_file, StoreFile, StoreFileKind},
+    utxo_state::UTXO,
+};
+
+/// Archivo del store donde se guarda el bloque del snapshot desde el que se inicio el nodo.
+const SNAPSHOT_BASE_FILE: &str = "snapshot.bin";
+/// Archivo del store donde se guardan las UTXO.
+const UTXO_FILE: &str = "utxo.bin";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// SnapshotBase es el bloque en el que se tomo el snapshot de UTXO desde el que se inicio el nodo.
+/// Los bloques hasta el no se descargan, ya que sus salidas estan en el snapshot, y cuando llegan los headers
+/// hasta su altura se verifica que el header a esa altura sea el del snapshot.
+/// - height: Altura del bloque.
+/// - block_hash: Hash del bloque.
+pub struct SnapshotBase {
+    pub height: usize,
+    pub block_hash: Hash32,
+}
+
+impl SnapshotBase {
+    /// Lee el bloque del snapshot guardado en el store, None si el nodo no se inicio desde un snapshot.
+    pub fn load(store_path: &str) -> Result<Option<Self>, CustomError> {
+        let path = format!("{store_path}/{SNAPSHOT_BASE_FILE}");
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        let file = StoreFile::parse(StoreFileKind::SnapshotBase, fs::read(path)?)?;
+        if !file.intact {
+            return Err(CustomError::StoreFileCorrupt);
+        }
+        let mut parser = BufferParser::new(file.contents);
+        Ok(Some(Self {
+            height: parser.extract_u32()? as usize,
+            block_hash: parser.extract_hash()?,
+        }))
+    }
+
+    fn save(&self, store_path: &str) -> Result<(), CustomError> {
+        let mut contents = (self.height as u32).to_le_bytes().to_vec();
+        contents.extend(self.block_hash.as_bytes());
+        write_file_atomically(
+            &format!("{store_path}/{SNAPSHOT_BASE_FILE}"),
+            &seal_store_file(StoreFileKind::SnapshotBase, &contents),
+        )
+    }
+
+    /// Descarta el snapshot desde el que se inicio el nodo, junto a las UTXO generadas a partir de el.
+    pub fn discard(store_path: &str) -> Result<(), CustomError> {
+        for file in [SNAPSHOT_BASE_FILE, UTXO_FILE] {
+            let path = format!("{store_path}/{file}");
+            if Path::new(&path).exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Guarda en el archivo recibido un snapshot de las UTXO del store, tomado en el ultimo bloque con el que se guardaron.
+/// El snapshot contiene la altura de ese bloque seguida del contenido del archivo de UTXO, su hash y las salidas.
+/// Devuelve CustomError si el store no tiene UTXO generadas, o si su bloque no esta entre los headers de la red recibida.
+pub fn dump_utxo_snapshot(
+    store_path: &str,
+    snapshot_path: &str,
+    network: Network,
+    logger_sender: &Sender<Log>,
+) -> Result<SnapshotBase, CustomError> {
+    let utxo_file = StoreFile::parse(
+        StoreFileKind::Utxo,
+        fs::read(format!("{store_path}/{UTXO_FILE}"))?,
+    )?;
+    if !utxo_file.intact {
+        return Err(CustomError::StoreFileCorrupt);
+    }
+    let (block_hash, _) = UTXO::parse(utxo_file.contents.clone())?;
+
+    let headers = HeadersState::new(
+        format!("{store_path}/headers.bin"),
+        logger_sender.clone(),
+        network,
+    )?;
+    let Some(depth) = headers.get_depth(&block_hash) else {
+        return Err(CustomError::Validation(format!(
+            "Block {block_hash} of the UTXO set is not among the headers"
+        )));
+    };
+    let base = SnapshotBase {
+        height: headers.len() - depth,
+        block_hash,
+    };
+
+    let mut contents = (base.height as u32).to_le_bytes().to_vec();
+    contents.extend(utxo_file.contents);
+    write_file_atomically(
+        snapshot_path,
+        &seal_store_file(StoreFileKind::UtxoSnapshot, &contents),
+    )?;
+    Ok(base)
+}
+
+/// Inicializa el store con el snapshot de UTXO del archivo recibido, para que el nodo solo descargue los bloques posteriores.
+/// Devuelve CustomError si el snapshot esta corrupto o si el store ya tiene UTXO generadas.
+pub fn load_utxo_snapshot(
+    store_path: &str,
+    snapshot_path: &str,
+) -> Result<SnapshotBase, CustomError> {
+    let utxo_path = format!("{store_path}/{UTXO_FILE}");
+    if fs::metadata(&utxo_path).is_ok_and(|metadata| metadata.len() > 0) {
+        return Err(CustomError::Validation(format!(
+            "The store at {store_path} already has a UTXO set"
+        )));
+    }
+
+    let snapshot = StoreFile::parse(StoreFileKind::UtxoSnapshot, fs::read(snapshot_path)?)?;
+    if !snapshot.intact {
+        return Err(CustomError::StoreFileCorrupt);
+    }
+    let mut parser = BufferParser::new(snapshot.contents);
+    let height = parser.extract_u32()? as usize;
+    let length = parser.len();
+    let utxo_contents = parser.extract_buffer(length)?.to_vec();
+    let (block_hash, _) = UTXO::parse(utxo_contents.clone())?;
+
+    fs::create_dir_all(store_path)?;
+    write_file_atomically(
+        &utxo_path,
+        &seal_store_file(StoreFileKind::Utxo, &utxo_contents),
+    )?;
+    let base = SnapshotBase { height, block_hash };
+    base.save(store_path)?;
+    Ok(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn dump_and_load_utxo_snapshot() {
+        let store_path = "tests/utxo_snapshot_store";
+        let new_store_path = "tests/utxo_snapshot_new_store";
+        let snapshot_path = "tests/utxo_snapshot.bin";
+        fs::remove_dir_all(store_path).ok();
+        fs::remove_dir_all(new_store_path).ok();
+        fs::create_dir_all(store_path).unwrap();
+        fs::copy(
+            "tests/test_headers.bin",
+            format!("{store_path}/headers.bin"),
+        )
+        .unwrap();
+        let logger_sender = mpsc::channel().0;
+
+        // UTXO vacias guardadas en el segundo header
+        let headers = HeadersState::new(
+            format!("{store_path}/headers.bin"),
+            logger_sender.clone(),
+            Network::Testnet,
+        )
+        .unwrap();
+        let block_hash = headers.get(1).unwrap().hash;
+        let mut contents = block_hash.to_vec();
+        contents.extend(0_u64.to_le_bytes());
+        fs::write(
+            format!("{store_path}/{UTXO_FILE}"),
+            seal_store_file(StoreFileKind::Utxo, &contents),
+        )
+        .unwrap();
+
+        let base = dump_utxo_snapshot(store_path, snapshot_path, Network::Testnet, &logger_sender)
+            .unwrap();
+        assert_eq!(
+            base,
+            SnapshotBase {
+                height: 2,
+                block_hash
+            }
+        );
+
+        assert_eq!(SnapshotBase::load(new_store_path).unwrap(), None);
+        assert_eq!(
+            load_utxo_snapshot(new_store_path, snapshot_path).unwrap(),
+            base
+        );
+        assert_eq!(SnapshotBase::load(new_store_path).unwrap(), Some(base));
+        assert_eq!(
+            fs::read(format!("{new_store_path}/{UTXO_FILE}")).unwrap(),
+            fs::read(format!("{store_path}/{UTXO_FILE}")).unwrap()
+        );
+        assert!(load_utxo_snapshot(new_store_path, snapshot_path).is_err());
+
+        SnapshotBase::discard(new_store_path).unwrap();
+        assert_eq!(SnapshotBase::load(new_store_path).unwrap(), None);
+        assert!(!Path::new(&format!("{new_store_path}/{UTXO_FILE}")).exists());
+
+        fs::remove_dir_all(store_path).unwrap();
+        fs::remove_dir_all(new_store_path).unwrap();
+        fs::remove_file(snapshot_path).unwrap();
+    }
+}
@@ -1,24 +1,77 @@
 use crate::{
     error::CustomError,
-    logger::{send_log, Log},
-    messages::block::Block,
-    parser::BufferParser,
+    logger::{send_log, Log, LogTarget},
+    messages::{
+        block::{Block, MAX_BLOCK_SIGOPS_COST},
+        transaction::{LockPoint, Transaction},
+    },
+    network::Network,
+    parser::{BtcEncode, BufferParser},
     structs::tx_output::TransactionOutput,
-    structs::{block_header::BlockHeader, outpoint::OutPoint},
-    utils::{calculate_index_from_timestamp, open_new_file},
+    structs::{block_header::BlockHeader, hash32::Hash32, outpoint::OutPoint},
+    utils::{calculate_index_from_timestamp, open_new_file, write_file_atomically},
     wallet::Wallet,
 };
-use std::{
-    collections::HashMap,
-    fs::remove_file,
-    io::{Read, Write},
-    path::Path,
-    process::exit,
-    sync::mpsc::Sender,
-    vec,
+use std::{collections::HashMap, io::Read, process::exit, sync::mpsc::Sender, vec};
+
+use tracing::instrument;
+
+use super::{
+    blocks_state::BlocksState,
+    headers_state::HeadersState,
+    store_file::{seal_store_file, StoreFile, StoreFileKind},
+    utxo_set::UtxoSet,
 };
 
 pub const START_DATE_IBD: u32 = 1681095630;
+/// Cantidad de bloques que deben pasar para poder gastar las salidas de una coinbase.
+pub const COINBASE_MATURITY: u32 = 100;
+/// Subsidio en satoshis de la coinbase de los primeros bloques.
+const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+/// Cantidad maxima de satoshis que pueden existir: ningun monto ni suma de montos puede superarla.
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+/// Suma los montos en satoshis recibidos, que pueden venir de un peer.
+/// Devuelve CustomError::InvalidBalance si alguno de ellos o el total supera MAX_MONEY, por lo que la suma nunca desborda.
+pub fn sum_money(values: impl IntoIterator<Item = u64>) -> Result<u64, CustomError> {
+    values.into_iter().try_fold(0_u64, |total, value| {
+        total
+            .checked_add(value)
+            .filter(|total| value <= MAX_MONEY && *total <= MAX_MONEY)
+            .ok_or(CustomError::InvalidBalance)
+    })
+}
+
+/// Calcula el fee de una transaccion a partir de las salidas que gastan sus entradas.
+/// Devuelve None si alguna salida es desconocida, o CustomError::InvalidBalance si gasta mas de lo que recibe
+/// o si algun monto supera MAX_MONEY.
+fn transaction_fee(
+    tx: &Transaction,
+    spent_outputs: &[Option<&TransactionOutput>],
+) -> Result<Option<u64>, CustomError> {
+    let outputs_value = sum_money(tx.outputs.iter().map(|output| output.value))?;
+    let Some(spent_values) = spent_outputs
+        .iter()
+        .map(|spent_output| spent_output.map(|output| output.value))
+        .collect::<Option<Vec<u64>>>()
+    else {
+        return Ok(None);
+    };
+    let inputs_value = sum_money(spent_values)?;
+    match inputs_value.checked_sub(outputs_value) {
+        Some(fee) => Ok(Some(fee)),
+        None => Err(CustomError::InvalidBalance),
+    }
+}
+
+/// Devuelve el subsidio en satoshis que puede cobrar la coinbase del bloque a la altura recibida,
+/// segun el intervalo de reduccion a la mitad de la red recibida.
+pub fn block_subsidy(height: u32, network: Network) -> u64 {
+    match height / network.subsidy_halving_interval() {
+        halvings @ 0..=63 => INITIAL_SUBSIDY >> halvings,
+        _ => 0,
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 /// UTXOValue es una estructura que contiene los valores que necesitamos guardar de las UTXO.
@@ -26,26 +79,43 @@ pub const START_DATE_IBD: u32 = 1681095630;
 /// - tx_out: TransactionOutput.
 /// - block_hash: Hash del bloque donde se encuentra el UTXO.
 /// - block_timestamp: Timestamp del bloque donde se encuentra el UTXO.
+/// - height: Altura del bloque donde se encuentra el UTXO.
+/// - is_coinbase: Indica si el UTXO es una salida de la coinbase del bloque.
 pub struct UTXOValue {
     pub tx_out: TransactionOutput,
-    pub block_hash: Vec<u8>,
+    pub block_hash: Hash32,
     pub block_timestamp: u32,
+    pub height: u32,
+    pub is_coinbase: bool,
+}
+
+impl UTXOValue {
+    /// Indica si el UTXO se puede gastar en el bloque siguiente a tip_height.
+    /// Las salidas de una coinbase recien se pueden gastar COINBASE_MATURITY bloques despues.
+    pub fn is_mature(&self, tip_height: u32) -> bool {
+        !self.is_coinbase || tip_height + 1 >= self.height + COINBASE_MATURITY
+    }
 }
 
 #[derive(PartialEq)]
 /// UTXO es una estructura que contiene los elementos necesarios para manejar las UTXO.
 /// Los elementos son:
-/// - tx_set: HashMap que contiene las UTXO con su OutPoint y UTXOValue.
+/// - tx_set: UtxoSet que contiene las UTXO con su OutPoint y UTXOValue, guardadas de forma compacta.
+/// - tip_height: Altura del ultimo bloque procesado.
 /// - sync: Indica si las UTXO estan sincronizadas con la red.
 /// - store_path: Path de la carpeta store.
 /// - path: Path del archivo donde se guardan las UTXO.
+/// - dirty: Indica si las UTXO cambiaron desde la ultima vez que se guardaron.
 /// El UTXO tiene un sistema de guardado tipo checkpoint
 /// donde cada vez que se actualiza genera un archivo donde lista los utxo del momento y el timestamp del ultimo bloque procesado.
 pub struct UTXO {
-    pub tx_set: HashMap<OutPoint, UTXOValue>,
+    pub tx_set: UtxoSet,
+    last_block_hash: Option<Hash32>,
+    tip_height: u32,
     sync: bool,
     store_path: String,
     path: String,
+    dirty: bool,
 }
 
 impl UTXO {
@@ -53,40 +123,142 @@ impl UTXO {
     /// El utxo comienza desincronizado y vacio.
     pub fn new(store_path: String, path: String) -> Result<Self, CustomError> {
         Ok(Self {
-            tx_set: HashMap::new(),
+            tx_set: UtxoSet::new(),
+            last_block_hash: None,
+            tip_height: 0,
             sync: false,
             store_path,
             path,
+            dirty: false,
         })
     }
 
-    /// Devuelve el balance de una wallet.
+    /// Devuelve el balance de una wallet, sin las salidas de coinbase que todavia no maduraron.
     pub fn wallet_balance(&self, wallet: &Wallet) -> Result<u64, CustomError> {
         let mut balance = 0;
         let pubkey_hash = wallet.get_pubkey_hash()?;
-        for value in self.tx_set.values() {
-            if value.tx_out.is_sent_to_key(&pubkey_hash)? {
+        for (_, value) in self.tx_set.iter() {
+            if value.is_mature(self.tip_height) && value.tx_out.is_sent_to_key(&pubkey_hash)? {
                 balance += value.tx_out.value;
             }
         }
         Ok(balance)
     }
 
-    /// Devuelve las UTXO de una wallet.
+    /// Devuelve las UTXO de una wallet que se pueden gastar, sin las salidas de coinbase que todavia no maduraron.
     pub fn generate_wallet_utxo(
         &self,
         wallet: &Wallet,
     ) -> Result<Vec<(OutPoint, UTXOValue)>, CustomError> {
-        let pubkey_hash = wallet.get_pubkey_hash()?;
+        let mut wallet_utxo = self.generate_pubkey_hash_utxo(&wallet.get_pubkey_hash()?)?;
+        wallet_utxo.retain(|(_, value)| value.is_mature(self.tip_height));
+        Ok(wallet_utxo)
+    }
 
-        let mut active_wallet_utxo = vec![];
-        for (out_point, value) in &self.tx_set {
-            if value.tx_out.is_sent_to_key(&pubkey_hash)? {
-                active_wallet_utxo.push((out_point.clone(), value.clone()));
+    /// Verifica que ninguna transaccion del bloque gaste mas de lo que reciben sus entradas,
+    /// que la coinbase no cobre mas que el subsidio del bloque mas los fees de sus transacciones,
+    /// que el costo en sigops de sus transacciones, incluyendo las de P2SH y las de los witness, no supere el maximo,
+    /// y que se cumplan los lock time absolutos y relativos de sus transacciones a la altura del bloque.
+    /// Las entradas se buscan en las UTXO y en las salidas de las transacciones anteriores del mismo bloque.
+    /// Como las UTXO solo contienen las salidas posteriores a START_DATE_IBD, las transacciones con entradas
+    /// desconocidas no se pueden verificar, y en ese caso tampoco se verifica la coinbase.
+    /// Los lock time solo se verifican si la coinbase indica la altura del bloque (BIP34).
+    /// Devuelve CustomError si el bloque no cumple alguna de las reglas.
+    pub fn validate_block(&self, block: &Block, headers: &HeadersState) -> Result<(), CustomError> {
+        let height = block.coinbase_height();
+        let lock_point = height.map(|height| headers.get_lock_point(height));
+        let verify_scripts = !headers.is_assumed_valid(block.header.hash());
+        let mut block_outputs: HashMap<OutPoint, (&TransactionOutput, u32)> = HashMap::new();
+        let mut fees = Some(0);
+        let mut sigops_cost = 0;
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            let spent: Vec<Option<(TransactionOutput, u32)>> = match tx_index {
+                0 => vec![],
+                _ => tx
+                    .inputs
+                    .iter()
+                    .map(|tx_in| self.spent_output(&tx_in.previous_output, &block_outputs))
+                    .collect(),
+            };
+            let spent_outputs: Vec<Option<&TransactionOutput>> = spent
+                .iter()
+                .map(|spent| spent.as_ref().map(|(output, _)| output))
+                .collect();
+            if tx_index > 0 {
+                let fee = transaction_fee(tx, &spent_outputs)?;
+                fees = fees
+                    .zip(fee)
+                    .map(|(fees, fee)| sum_money([fees, fee]))
+                    .transpose()?;
+            }
+            sigops_cost += tx.sigop_cost(&spent_outputs);
+            if sigops_cost > MAX_BLOCK_SIGOPS_COST {
+                return Err(CustomError::BlockExceedsLimits);
+            }
+            if let Some(lock_point) = lock_point {
+                let spent_coins: Vec<Option<(&TransactionOutput, LockPoint)>> = spent
+                    .iter()
+                    .map(|spent| {
+                        spent.as_ref().map(|(output, coin_height)| {
+                            (output, headers.get_lock_point(*coin_height))
+                        })
+                    })
+                    .collect();
+                tx.check_locks(lock_point, &spent_coins, verify_scripts)?;
             }
+            for (index, tx_out) in tx.outputs.iter().enumerate() {
+                let out_point = OutPoint {
+                    hash: tx.hash(),
+                    index: index as u32,
+                };
+                block_outputs.insert(out_point, (tx_out, height.unwrap_or_default()));
+            }
+        }
+
+        let (Some(coinbase), Some(fees), Some(height)) = (block.transactions.first(), fees, height)
+        else {
+            return Ok(());
+        };
+        let coinbase_value = sum_money(coinbase.outputs.iter().map(|output| output.value))?;
+        if coinbase_value > block_subsidy(height, headers.get_network()) + fees {
+            return Err(CustomError::InvalidBalance);
+        }
+        Ok(())
+    }
+
+    /// Busca la salida que gasta una entrada de un bloque en las UTXO y en las salidas recibidas del mismo bloque,
+    /// junto a la altura del bloque que la incluyo.
+    fn spent_output(
+        &self,
+        out_point: &OutPoint,
+        block_outputs: &HashMap<OutPoint, (&TransactionOutput, u32)>,
+    ) -> Option<(TransactionOutput, u32)> {
+        match self.tx_set.get(out_point) {
+            Some(utxo_value) => Some((utxo_value.tx_out, utxo_value.height)),
+            None => block_outputs
+                .get(out_point)
+                .map(|(output, height)| ((*output).clone(), *height)),
         }
+    }
 
-        Ok(active_wallet_utxo)
+    /// Devuelve la altura del ultimo bloque procesado.
+    pub fn get_tip_height(&self) -> u32 {
+        self.tip_height
+    }
+
+    /// Devuelve las UTXO enviadas al hash de una public key.
+    pub fn generate_pubkey_hash_utxo(
+        &self,
+        pubkey_hash: &Vec<u8>,
+    ) -> Result<Vec<(OutPoint, UTXOValue)>, CustomError> {
+        let mut pubkey_hash_utxo = vec![];
+        for (out_point, value) in self.tx_set.iter() {
+            if value.tx_out.is_sent_to_key(pubkey_hash)? {
+                pubkey_hash_utxo.push((out_point.clone(), value));
+            }
+        }
+
+        Ok(pubkey_hash_utxo)
     }
 
     /// Devuelve si el utxo esta sincronizado.
@@ -98,28 +270,34 @@ impl UTXO {
     /// Si el archivo donde se guardan las UTXO no existe, se crea.
     /// Si el archivo existe, se restauran las UTXO hasta ese punto y se recorren unicamente
     /// los bloques posteriores al bloque del hash guardado en el archivo.
+    /// Al recorrer cada bloque se construye su filtro, ya que se conocen los scripts de las salidas que gasta.
+    #[instrument(name = "utxo_generation", skip_all, fields(headers = headers.len()))]
     pub fn generate(
         &mut self,
         headers: &Vec<BlockHeader>,
+        blocks: &BlocksState,
         logger_sender: &mut Sender<Log>,
     ) -> Result<(), CustomError> {
-        let last_block_hash = self.restore_utxo()?.unwrap_or_else(|| {
+        let last_block_hash = self.restore_utxo(logger_sender)?.unwrap_or_else(|| {
             let first_block_index = calculate_index_from_timestamp(headers, START_DATE_IBD);
-            headers[first_block_index].hash().clone()
+            *headers[first_block_index].hash()
         });
 
-        let new_last_block_hash = self.update(headers, last_block_hash, logger_sender)?;
+        let new_last_block_hash = self.update(headers, last_block_hash, blocks, logger_sender)?;
 
         self.sync = true;
         self.save(new_last_block_hash)?;
 
         send_log(
             logger_sender,
-            Log::Message("Utxo generation is (100%) completed...".to_string()),
+            Log::Info(
+                LogTarget::Sync,
+                "Utxo generation is (100%) completed...".to_string(),
+            ),
         );
         send_log(
             logger_sender,
-            Log::Message("Utxo generation is finished".to_string()),
+            Log::Info(LogTarget::Sync, "Utxo generation is finished".to_string()),
         );
         Ok(())
     }
@@ -127,18 +305,40 @@ impl UTXO {
     /// Restaura las UTXO a partir del archivo donde se guardan.
     /// Obtiene los datos del UTXO.
     /// Obtiene el hash del ultimo bloque procesado y lo retorna
-    fn restore_utxo(&mut self) -> Result<Option<Vec<u8>>, CustomError> {
+    /// Si el archivo esta corrupto se descarta, y las UTXO se vuelven a generar desde los bloques.
+    /// Un archivo de una version anterior del formato se migra, y se reescribe con el formato actual al guardar las UTXO.
+    /// Devuelve CustomError::StoreFileIncompatible si el archivo es de otro tipo o de una version mas nueva.
+    fn restore_utxo(&mut self, logger_sender: &Sender<Log>) -> Result<Option<Hash32>, CustomError> {
         let path = format!("{}/{}", self.store_path, self.path);
         let mut file = open_new_file(path, false)?;
 
         let mut saved_utxo_buffer = vec![];
         file.read_to_end(&mut saved_utxo_buffer)?;
 
-        let (last_block_hash, tx_set) = match Self::parse(saved_utxo_buffer) {
-            Ok((last_block_hash, tx_set)) => (Some(last_block_hash), tx_set),
-            Err(_) => (None, HashMap::new()),
+        let is_empty = saved_utxo_buffer.is_empty();
+        let parsed = match StoreFile::parse(StoreFileKind::Utxo, saved_utxo_buffer) {
+            Ok(saved_utxo) if saved_utxo.intact => Self::parse(saved_utxo.contents).ok(),
+            Ok(_) | Err(CustomError::StoreFileCorrupt) => None,
+            Err(error) => return Err(error),
+        };
+        let (last_block_hash, tx_set) = match parsed {
+            Some((last_block_hash, tx_set)) => (Some(last_block_hash), tx_set),
+            None => {
+                if !is_empty {
+                    send_log(
+                        logger_sender,
+                        Log::Warn(
+                            LogTarget::Sync,
+                            "UTXO file is corrupt, generating it again from the blocks".to_string(),
+                        ),
+                    );
+                }
+                (None, UtxoSet::new())
+            }
         };
 
+        // La coinbase del ultimo bloque no se puede gastar todavia, por lo que siempre esta en el UTXO
+        self.tip_height = tx_set.max_height();
         self.tx_set = tx_set;
         Ok(last_block_hash)
     }
@@ -149,9 +349,10 @@ impl UTXO {
     fn update(
         &mut self,
         headers: &Vec<BlockHeader>,
-        last_block_hash: Vec<u8>,
+        last_block_hash: Hash32,
+        blocks: &BlocksState,
         logger_sender: &mut Sender<Log>,
-    ) -> Result<Vec<u8>, CustomError> {
+    ) -> Result<Hash32, CustomError> {
         let mut last_block_hash = last_block_hash;
 
         let block_position = headers
@@ -166,13 +367,22 @@ impl UTXO {
 
         send_log(
             logger_sender,
-            Log::Message(format!(
-                "Utxo generation is starting ({} new blocks)",
-                headers.len() - starting_index
-            )),
+            Log::Info(
+                LogTarget::Sync,
+                format!(
+                    "Utxo generation is starting ({} new blocks)",
+                    headers.len() - starting_index
+                ),
+            ),
         );
 
-        self.update_from_headers(headers, starting_index, logger_sender, &mut last_block_hash)?;
+        self.update_from_headers(
+            headers,
+            starting_index,
+            blocks,
+            logger_sender,
+            &mut last_block_hash,
+        )?;
         Ok(last_block_hash)
     }
 
@@ -182,8 +392,9 @@ impl UTXO {
         &mut self,
         headers: &Vec<BlockHeader>,
         starting_index: usize,
+        blocks: &BlocksState,
         logger_sender: &mut Sender<Log>,
-        last_block_hash: &mut Vec<u8>,
+        last_block_hash: &mut Hash32,
     ) -> Result<(), CustomError> {
         let mut i = 0;
         let mut percentage = 0;
@@ -193,60 +404,67 @@ impl UTXO {
                     percentage += 10;
                     send_log(
                         logger_sender,
-                        Log::Message(format!("Utxo generation is ({percentage}%) completed...")),
+                        Log::Info(
+                            LogTarget::Sync,
+                            format!("Utxo generation is ({percentage}%) completed..."),
+                        ),
                     );
                     i = 0;
                 }
-                let path = format!("{}/blocks/{}.bin", self.store_path, header.hash_as_string());
-                let block = match Block::restore(path) {
+                let block = match blocks.get_block(header.hash()) {
                     Ok(block) => block,
                     Err(_) => {
                         send_log(
                         logger_sender,
-                        Log::Message(String::from(
+                        Log::Warn(LogTarget::Sync, String::from(
                             "Error generating UTXO (block file broken), please restart the app.",
                         )),
                     );
                         exit(0);
                     }
                 };
-                self.update_from_block(&block, false)?;
+                let spent_scripts = self.update_from_block(&block, false)?;
+                blocks.append_filter(&block, &spent_scripts)?;
                 drop(block);
-                *last_block_hash = header.hash().clone();
+                *last_block_hash = *header.hash();
                 i += 1;
             },
         )
     }
 
     /// Serializa las utxo y el hash del ultimo bloque procesado.
-    fn serialize(&mut self, block_hash: Vec<u8>) -> Vec<u8> {
+    fn serialize(&mut self, block_hash: Hash32) -> Vec<u8> {
         let mut buffer = vec![];
-        buffer.extend(block_hash);
+        buffer.extend(block_hash.as_bytes());
         buffer.extend((self.tx_set.len() as u64).to_le_bytes());
 
-        for (out_point, value) in &self.tx_set {
+        for (out_point, value) in self.tx_set.iter() {
             buffer.extend(out_point.serialize());
             buffer.extend(value.tx_out.serialize());
-            buffer.extend(value.block_hash.clone());
+            buffer.extend(value.block_hash.as_bytes());
             buffer.extend(value.block_timestamp.to_le_bytes());
+            buffer.extend(value.height.to_le_bytes());
+            buffer.push(value.is_coinbase as u8);
         }
         buffer
     }
 
-    pub fn parse(buffer: Vec<u8>) -> Result<(Vec<u8>, HashMap<OutPoint, UTXOValue>), CustomError> {
+    pub fn parse(buffer: Vec<u8>) -> Result<(Hash32, UtxoSet), CustomError> {
         let mut parser = BufferParser::new(buffer);
 
-        let last_block_hash = parser.extract_buffer(32)?.to_vec();
+        let last_block_hash = parser.extract_hash()?;
         let tx_set_len = parser.extract_u64()? as usize;
-        let mut tx_set: HashMap<OutPoint, UTXOValue> = HashMap::new();
+        let mut tx_set = UtxoSet::new();
 
         for _i in 0..tx_set_len {
-            let out_point = OutPoint::parse(parser.extract_buffer(36)?.to_vec())?;
+            let out_point = OutPoint::decode(&mut parser)?;
 
             let value = UTXOValue {
-                tx_out: TransactionOutput::parse(&mut parser)?,
-                block_hash: parser.extract_buffer(32)?.to_vec(),
+                tx_out: TransactionOutput::decode(&mut parser)?,
+                block_hash: parser.extract_hash()?,
                 block_timestamp: parser.extract_u32()?,
+                height: parser.extract_u32()?,
+                is_coinbase: parser.extract_u8()? == 1,
             };
             tx_set.insert(out_point, value);
         }
@@ -255,62 +473,117 @@ impl UTXO {
     }
 
     /// Actualiza las UTXO a partir de un bloque, eliminando los outputs gastados y agregando los nuevos outputs.
-    /// Si save es true, guarda el UTXO actualizado en disco.
-    pub fn update_from_block(&mut self, block: &Block, save: bool) -> Result<(), CustomError> {
-        for tx in &block.transactions {
+    /// Si save es true, las UTXO actualizadas se guardan en disco en el proximo flush.
+    /// Devuelve los scripts de los outputs gastados que se encontraban en las UTXO.
+    #[instrument(level = "debug", skip_all, fields(transactions = block.transactions.len()))]
+    pub fn update_from_block(
+        &mut self,
+        block: &Block,
+        save: bool,
+    ) -> Result<Vec<Vec<u8>>, CustomError> {
+        let mut spent_scripts = vec![];
+        let height = block.coinbase_height().unwrap_or(self.tip_height + 1);
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
             for tx_in in &tx.inputs {
-                self.tx_set.remove(&tx_in.previous_output);
+                if let Some(spent) = self.tx_set.remove(&tx_in.previous_output) {
+                    spent_scripts.push(spent.tx_out.script_pubkey);
+                }
             }
             for (index, tx_out) in tx.outputs.iter().enumerate() {
                 let out_point = OutPoint {
-                    hash: tx.hash().clone(),
+                    hash: tx.hash(),
                     index: index as u32,
                 };
                 let value = UTXOValue {
                     tx_out: tx_out.clone(),
-                    block_hash: block.header.hash().clone(),
+                    block_hash: *block.header.hash(),
                     block_timestamp: block.header.timestamp,
+                    height,
+                    is_coinbase: tx_index == 0,
                 };
                 self.tx_set.insert(out_point.clone(), value);
             }
         }
+        self.tip_height = height;
 
         if save {
-            self.save(block.header.hash().clone())?;
+            self.last_block_hash = Some(*block.header.hash());
+            self.dirty = true;
         }
 
-        Ok(())
+        Ok(spent_scripts)
     }
 
-    fn save(&mut self, block_hash: Vec<u8>) -> Result<(), CustomError> {
-        let buffer = self.serialize(block_hash);
+    fn save(&mut self, block_hash: Hash32) -> Result<(), CustomError> {
+        let buffer = seal_store_file(StoreFileKind::Utxo, &self.serialize(block_hash));
 
         let path = format!("{}/{}", self.store_path, self.path);
-        if Path::new(&path).exists() {
-            remove_file(path.clone())?;
-        }
-        let mut file = open_new_file(path, false)?;
-
-        file.write_all(&buffer)?;
+        write_file_atomically(&path, &buffer)?;
+        self.last_block_hash = Some(block_hash);
+        self.dirty = false;
         Ok(())
     }
+
+    /// Guarda las UTXO junto al ultimo bloque procesado, lo que fuerza su escritura en disco.
+    /// Si todavia no se generaron las UTXO o no cambiaron desde que se guardaron no hace nada.
+    pub fn flush(&mut self) -> Result<(), CustomError> {
+        let Some(last_block_hash) = self.last_block_hash else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        self.save(last_block_hash)
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use std::fs;
+    use std::{fs, path::Path, sync::mpsc};
 
     use chrono::Local;
-    use gtk::glib::{self, Priority};
 
     use crate::{
-        logger::Logger, messages::transaction::Transaction, structs::tx_input::TransactionInput,
+        logger::Logger,
+        messages::transaction::Transaction,
+        states::{block_store::BlockStore, pending_blocks_state::PendingBlocks},
+        structs::tx_input::TransactionInput,
         wallet::get_script_pubkey,
     };
 
     use super::*;
 
+    #[test]
+    fn overflowing_outputs_are_invalid() {
+        let output = |value| TransactionOutput {
+            value,
+            script_pubkey: vec![],
+        };
+        let spent = output(10);
+        let tx = Transaction {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![output(u64::MAX), output(11)],
+            lock_time: 0,
+        };
+        assert!(matches!(
+            transaction_fee(&tx, &[Some(&spent)]),
+            Err(CustomError::InvalidBalance)
+        ));
+
+        let tx = Transaction {
+            outputs: vec![output(MAX_MONEY), output(1)],
+            ..tx
+        };
+        assert!(matches!(
+            transaction_fee(&tx, &[Some(&output(u64::MAX))]),
+            Err(CustomError::InvalidBalance)
+        ));
+        assert_eq!(sum_money([MAX_MONEY - 1, 1]).unwrap(), MAX_MONEY);
+        assert!(sum_money([u64::MAX - 1, 2]).is_err());
+    }
+
     #[test]
     fn test_save_restore() {
         let filename = format!("{}", Local::now());
@@ -318,10 +591,10 @@ mod tests {
         let mut utxo_set = UTXO::new(store_path.clone(), filename.clone()).unwrap();
 
         let key1 = OutPoint {
-            hash: vec![
+            hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             index: 1,
         };
         let value1 = UTXOValue {
@@ -332,17 +605,19 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![
+            block_hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             block_timestamp: 1680000000,
+            height: 0,
+            is_coinbase: false,
         };
         let key2 = OutPoint {
-            hash: vec![
+            hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             index: 2,
         };
         let value2 = UTXOValue {
@@ -353,17 +628,19 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![
+            block_hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             block_timestamp: 1680000001,
+            height: 0,
+            is_coinbase: false,
         };
         let key3 = OutPoint {
-            hash: vec![
+            hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             index: 3,
         };
         let value3 = UTXOValue {
@@ -374,11 +651,13 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![
+            block_hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             block_timestamp: 1680000002,
+            height: 0,
+            is_coinbase: false,
         };
         utxo_set.tx_set.insert(key1, value1);
         utxo_set.tx_set.insert(key2, value2);
@@ -387,59 +666,72 @@ mod tests {
         assert_eq!(utxo_set.tx_set.len(), 3);
 
         utxo_set
-            .save(vec![
+            .save(Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ])
+            ]))
             .unwrap();
 
         let mut utxo_set2 = UTXO::new(store_path.clone(), filename.clone()).unwrap();
-        utxo_set2.restore_utxo().unwrap();
+        utxo_set2.restore_utxo(&mpsc::channel().0).unwrap();
 
         assert_eq!(utxo_set2.tx_set.len(), 3);
         assert_eq!(utxo_set2.tx_set, utxo_set.tx_set);
 
-        fs::remove_file(format!("{}/{}", store_path, filename)).unwrap();
+        // un archivo corrupto se descarta para volver a generar las UTXO
+        let path = format!("{}/{}", store_path, filename);
+        let mut buffer = fs::read(&path).unwrap();
+        let last = buffer.len() - 1;
+        buffer[last] ^= 0xff;
+        fs::write(&path, buffer).unwrap();
+        let mut utxo_set3 = UTXO::new(store_path.clone(), filename.clone()).unwrap();
+        assert_eq!(utxo_set3.restore_utxo(&mpsc::channel().0).unwrap(), None);
+        assert!(utxo_set3.tx_set.is_empty());
+
+        // un archivo de otro tipo no se sobreescribe
+        fs::write(&path, seal_store_file(StoreFileKind::Wallets, &[])).unwrap();
+        assert!(matches!(
+            utxo_set3.restore_utxo(&mpsc::channel().0),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
     fn starting_index_calculation() {
         let header1 = BlockHeader {
             version: 536870912,
-            prev_block_hash: [
+            prev_block_hash: Hash32::new([
                 37, 167, 68, 172, 119, 180, 173, 121, 130, 113, 230, 183, 81, 26, 52, 142, 31, 52,
                 247, 233, 68, 123, 190, 78, 10, 195, 189, 99, 0, 0, 0, 0,
-            ]
-            .to_vec(),
-            merkle_root: [
+            ]),
+            merkle_root: Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 248, 125, 43, 136, 39, 116, 186, 43,
                 114, 204, 35, 144, 47, 194, 229, 44, 97, 83, 110, 112, 229, 230,
-            ]
-            .to_vec(),
+            ]),
             timestamp: 1,
             bits: 486604799,
             nonce: 409655068,
-            hash: vec![],
+            hash: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         };
 
         let header2 = BlockHeader {
             version: 536870912,
-            prev_block_hash: [
+            prev_block_hash: Hash32::new([
                 37, 167, 68, 172, 119, 180, 173, 121, 130, 113, 230, 183, 81, 26, 52, 142, 31, 52,
                 247, 233, 68, 123, 190, 78, 10, 195, 189, 99, 0, 0, 0, 0,
-            ]
-            .to_vec(),
-            merkle_root: [
+            ]),
+            merkle_root: Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 248, 125, 43, 136, 39, 116, 186, 43,
                 114, 204, 35, 144, 47, 194, 229, 44, 97, 83, 110, 112, 229, 230,
-            ]
-            .to_vec(),
+            ]),
             timestamp: 3,
             bits: 486604799,
             nonce: 409655068,
-            hash: vec![],
+            hash: Hash32::default(),
             block_downloaded: true,
             broadcasted: true,
         };
@@ -467,17 +759,16 @@ mod tests {
         let filename = String::from("test_utxo.bin");
         let store_path = String::from("tests");
         let mut utxo_set = UTXO::new(store_path, filename.clone()).unwrap();
-        let block_hash = vec![
+        let block_hash = Hash32::new([
             127, 47, 239, 163, 175, 36, 146, 56, 212, 168, 146, 23, 101, 29, 205, 186, 7, 67, 240,
             23, 75, 32, 175, 14, 221, 106, 150, 247, 21, 243, 205, 109,
-        ];
+        ]);
 
         let key: OutPoint = OutPoint {
-            hash: [
+            hash: Hash32::new([
                 252, 47, 239, 163, 175, 36, 146, 56, 212, 168, 146, 23, 101, 29, 205, 186, 7, 67,
                 240, 23, 75, 32, 175, 14, 221, 106, 150, 247, 21, 243, 205, 109,
-            ]
-            .to_vec(),
+            ]),
             index: 0,
         };
         let value = UTXOValue {
@@ -488,12 +779,14 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: block_hash.clone(),
+            block_hash,
             block_timestamp: 1680000000,
+            height: 0,
+            is_coinbase: false,
         };
         utxo_set.tx_set.insert(key, value);
 
-        let buffer = utxo_set.serialize(block_hash.clone());
+        let buffer = utxo_set.serialize(block_hash);
         let (last_block_hash, parsed_tx_set) = UTXO::parse(buffer).unwrap();
         assert_eq!(last_block_hash, block_hash);
         assert_eq!(utxo_set.tx_set, parsed_tx_set);
@@ -501,7 +794,7 @@ mod tests {
 
     #[test]
     fn utxo_generation() {
-        let (gui_sender, _gui_receiver) = glib::MainContext::channel(Priority::default());
+        let (gui_sender, _gui_receiver) = mpsc::channel();
 
         let logger = Logger::new(&String::from("tests/test_log.txt"), gui_sender).unwrap();
         let logger_sender = logger.get_sender();
@@ -513,24 +806,25 @@ mod tests {
                 bits: 486604799,
                 block_downloaded: true,
                 broadcasted: true,
-                hash: vec![
+                hash: Hash32::new([
                     1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6,
                     7, 8, 9, 0, 1, 2,
-                ],
-                merkle_root: vec![],
+                ]),
+                merkle_root: Hash32::default(),
                 nonce: 409655068,
-                prev_block_hash: vec![],
+                prev_block_hash: Hash32::default(),
                 timestamp: 1680000000,
                 version: 21123123,
             },
             transactions: vec![Transaction {
                 inputs: vec![TransactionInput {
                     previous_output: OutPoint {
-                        hash: vec![],
+                        hash: Hash32::default(),
                         index: 0,
                     },
                     script_sig: vec![],
                     sequence: 0,
+                    witness: vec![],
                 }],
                 outputs: vec![TransactionOutput {
                     script_pubkey: vec![1, 2],
@@ -543,8 +837,6 @@ mod tests {
 
         // bloque con 42 inputs y outputs en 20 txs
         let block = Block::restore(path).unwrap();
-        let real_path = format!("tests/blocks/{}.bin", block.header.hash_as_string());
-        block.save(real_path.clone()).unwrap();
 
         if Path::new("tests/test_utxo.bin").exists() {
             fs::remove_file("tests/test_utxo.bin").unwrap();
@@ -553,18 +845,39 @@ mod tests {
         let store_path = String::from("tests");
         let mut utxo_set = UTXO::new(store_path, filename.clone()).unwrap();
 
+        // los bloques recorridos y sus filtros se guardan en un store aparte
+        fs::remove_dir_all("tests/utxo_generation").ok();
+        fs::create_dir_all("tests/utxo_generation/filters").unwrap();
+        let mut block_store = BlockStore::open(
+            String::from("tests/utxo_generation/blocks"),
+            Network::Testnet,
+        )
+        .unwrap();
+        block_store.append(&block).unwrap();
+        let pending_blocks_ref = PendingBlocks::new(&block_store, &vec![], 0);
+        let blocks_state = BlocksState::new(
+            String::from("tests/utxo_generation"),
+            block_store,
+            logger_sender.clone(),
+            pending_blocks_ref,
+        );
+
         let headers = vec![block_old.header.clone(), block.header.clone()];
         utxo_set
-            .generate(&headers, &mut logger_sender.clone())
+            .generate(&headers, &blocks_state, &mut logger_sender.clone())
             .unwrap();
 
         // // solo tienen que estar los utxo del segundo bloque
         assert_eq!(utxo_set.tx_set.len(), 42);
         assert_eq!(utxo_set.is_synced(), true);
+        assert!(blocks_state
+            .get_filter(block.header.hash())
+            .unwrap()
+            .is_some());
+        fs::remove_dir_all("tests/utxo_generation").unwrap();
 
         fs::remove_file("tests/test_log.txt").unwrap();
         fs::remove_file("tests/test_utxo.bin").unwrap();
-        fs::remove_file(real_path).unwrap();
     }
 
     #[test]
@@ -581,7 +894,7 @@ mod tests {
         .unwrap();
 
         let key1 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 1,
         };
         let tx_out1 = TransactionOutput {
@@ -591,11 +904,13 @@ mod tests {
         };
         let value1 = UTXOValue {
             tx_out: tx_out1.clone(),
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000000,
+            height: 0,
+            is_coinbase: false,
         };
         let key2 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 2,
         };
         let tx_out2 = TransactionOutput {
@@ -605,8 +920,10 @@ mod tests {
         };
         let value2 = UTXOValue {
             tx_out: tx_out2.clone(),
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000001,
+            height: 0,
+            is_coinbase: false,
         };
         let tx_out3 = TransactionOutput {
             value: 100,
@@ -615,13 +932,15 @@ mod tests {
         };
 
         let key3 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 3,
         };
         let value3 = UTXOValue {
             tx_out: tx_out3.clone(),
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000002,
+            height: 0,
+            is_coinbase: false,
         };
         utxo_set.tx_set.insert(key1.clone(), value1.clone());
         utxo_set.tx_set.insert(key2.clone(), value2.clone());
@@ -637,6 +956,194 @@ mod tests {
             .contains(&(key2, value2)));
     }
 
+    #[test]
+    fn immature_coinbase_is_not_spendable() {
+        let mut utxo_set = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
+        let wallet = Wallet::new(
+            String::from("test_wallet"),
+            String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"),
+            String::from("privkey"),
+            &utxo_set,
+        )
+        .unwrap();
+        let tx_out = TransactionOutput {
+            value: 5000,
+            script_pubkey: get_script_pubkey(String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"))
+                .unwrap(),
+        };
+        let coinbase = UTXOValue {
+            tx_out,
+            block_hash: Hash32::default(),
+            block_timestamp: 1680000000,
+            height: 50,
+            is_coinbase: true,
+        };
+        utxo_set.tx_set.insert(
+            OutPoint {
+                hash: Hash32::default(),
+                index: 0,
+            },
+            coinbase,
+        );
+
+        utxo_set.tip_height = 148;
+        assert_eq!(utxo_set.wallet_balance(&wallet).unwrap(), 0);
+        assert!(utxo_set.generate_wallet_utxo(&wallet).unwrap().is_empty());
+
+        utxo_set.tip_height = 149;
+        assert_eq!(utxo_set.wallet_balance(&wallet).unwrap(), 5000);
+        assert_eq!(utxo_set.generate_wallet_utxo(&wallet).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn block_balances_are_validated() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let mut utxo_set = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
+        let spent = OutPoint {
+            hash: Hash32::new([1; 32]),
+            index: 0,
+        };
+        utxo_set.tx_set.insert(
+            spent.clone(),
+            UTXOValue {
+                tx_out: TransactionOutput {
+                    value: 1000,
+                    script_pubkey: vec![0x51],
+                },
+                block_hash: Hash32::default(),
+                block_timestamp: 1680000000,
+                height: 0,
+                is_coinbase: false,
+            },
+        );
+        let transaction =
+            |previous_output: OutPoint, script_sig: Vec<u8>, value: u64| Transaction {
+                version: 1,
+                inputs: vec![TransactionInput {
+                    previous_output,
+                    script_sig,
+                    sequence: 0xffffffff,
+                    witness: vec![],
+                }],
+                outputs: vec![TransactionOutput {
+                    value,
+                    script_pubkey: vec![0x51],
+                }],
+                lock_time: 0,
+            };
+        // Coinbase de la altura 420000, con un subsidio de 12.5 BTC
+        let block = |coinbase_value: u64, spend_value: u64| Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
+                timestamp: 1680000000,
+                bits: 0,
+                nonce: 0,
+                hash: Hash32::default(),
+                block_downloaded: true,
+                broadcasted: true,
+            },
+            transactions: vec![
+                transaction(
+                    OutPoint {
+                        hash: Hash32::default(),
+                        index: 0xffffffff,
+                    },
+                    vec![3, 0xa0, 0x68, 0x06],
+                    coinbase_value,
+                ),
+                transaction(spent.clone(), vec![], spend_value),
+            ],
+        };
+
+        assert!(utxo_set
+            .validate_block(&block(1_250_000_000 + 100, 900), &headers)
+            .is_ok());
+        assert!(utxo_set
+            .validate_block(&block(1_250_000_000 + 101, 900), &headers)
+            .is_err());
+        assert!(utxo_set
+            .validate_block(&block(1_250_000_000, 1001), &headers)
+            .is_err());
+        assert_eq!(block_subsidy(0, Network::Testnet), 5_000_000_000);
+        assert_eq!(block_subsidy(420_000, Network::Testnet), 1_250_000_000);
+        assert_eq!(block_subsidy(150, Network::Regtest), 2_500_000_000);
+    }
+
+    #[test]
+    fn block_sigops_cost_is_limited() {
+        let (logger_sender, _) = mpsc::channel();
+        let headers = HeadersState::new(
+            "tests/test_headers.bin".to_string(),
+            logger_sender,
+            Network::Testnet,
+        )
+        .unwrap();
+        let mut utxo_set = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
+        let spent = OutPoint {
+            hash: Hash32::new([1; 32]),
+            index: 0,
+        };
+        let mut p2wsh = vec![0x00, 32];
+        p2wsh.extend([1; 32]);
+        utxo_set.tx_set.insert(
+            spent.clone(),
+            UTXOValue {
+                tx_out: TransactionOutput {
+                    value: 1000,
+                    script_pubkey: p2wsh,
+                },
+                block_hash: Hash32::default(),
+                block_timestamp: 1680000000,
+                height: 0,
+                is_coinbase: false,
+            },
+        );
+        let block = |witness_sigops: usize| Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
+                timestamp: 1680000000,
+                bits: 0,
+                nonce: 0,
+                hash: Hash32::default(),
+                block_downloaded: true,
+                broadcasted: true,
+            },
+            transactions: vec![Transaction {
+                version: 1,
+                inputs: vec![TransactionInput {
+                    previous_output: spent.clone(),
+                    script_sig: vec![],
+                    sequence: 0xffffffff,
+                    witness: vec![vec![0xac; witness_sigops]],
+                }],
+                outputs: vec![],
+                lock_time: 0,
+            }],
+        };
+        // La primera transaccion es la coinbase, por lo que sus entradas no se buscan
+        let mut valid = block(MAX_BLOCK_SIGOPS_COST);
+        valid.transactions.insert(0, valid.transactions[0].clone());
+        assert!(valid.check_limits().is_ok());
+        assert!(utxo_set.validate_block(&valid, &headers).is_ok());
+
+        let mut invalid = block(MAX_BLOCK_SIGOPS_COST + 1);
+        invalid
+            .transactions
+            .insert(0, invalid.transactions[0].clone());
+        assert!(invalid.check_limits().is_ok());
+        assert!(utxo_set.validate_block(&invalid, &headers).is_err());
+    }
+
     #[test]
     fn correct_wallet_balance() {
         let filename = String::from("test_utxo.bin");
@@ -652,7 +1159,7 @@ mod tests {
         .unwrap();
 
         let key1 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 1,
         };
         let value1 = UTXOValue {
@@ -663,11 +1170,13 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000000,
+            height: 0,
+            is_coinbase: false,
         };
         let key2 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 2,
         };
         let value2 = UTXOValue {
@@ -678,11 +1187,13 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000001,
+            height: 0,
+            is_coinbase: false,
         };
         let key3 = OutPoint {
-            hash: vec![],
+            hash: Hash32::default(),
             index: 3,
         };
         let value3 = UTXOValue {
@@ -693,8 +1204,10 @@ mod tests {
                 ))
                 .unwrap(),
             },
-            block_hash: vec![],
+            block_hash: Hash32::default(),
             block_timestamp: 1680000002,
+            height: 0,
+            is_coinbase: false,
         };
         utxo_set.tx_set.insert(key1, value1);
         utxo_set.tx_set.insert(key2, value2);
@@ -0,0 +1,279 @@
+use std::fs;
+
+use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+
+use crate::{
+    error::CustomError,
+    parser::BufferParser,
+    structs::hash32::Hash32,
+    utils::{get_random_bytes, write_file_atomically},
+    wallet::Wallet,
+};
+
+use super::{
+    address_book_state::{append_label, extract_label},
+    store_file::{read_store_file_header, store_file_header, StoreFileKind, LEGACY_VERSION},
+};
+
+/// Iteraciones de PBKDF2 con las que se deriva la clave de los backups, para encarecer probar passphrases.
+const BACKUP_ITERATIONS: u32 = 100_000;
+/// Tamaño del salt de la derivacion de la clave y del nonce del cifrado, que se generan al azar en cada backup.
+const BACKUP_SALT_SIZE: usize = 16;
+/// Tamaño del codigo de autenticacion (HMAC-SHA256) que cierra el archivo.
+const BACKUP_MAC_SIZE: usize = 32;
+
+type HmacSha256 = hmac::Hmac<sha256::Hash>;
+type HmacSha256Engine = hmac::HmacEngine<sha256::Hash>;
+
+#[derive(Debug, Clone, Default)]
+/// WalletsBackup es el contenido de un backup de las wallets, que se guarda cifrado con una passphrase
+/// en un unico archivo independiente de wallets.bin, para poder restaurarlo en otro nodo o despues de perder el store.
+/// El archivo tiene el encabezado de los archivos del store, las iteraciones, el salt y el nonce, el contenido cifrado
+/// con HMAC-SHA256 en modo contador y un HMAC-SHA256 de todo lo anterior, con claves derivadas de la passphrase con PBKDF2.
+/// Los elementos son:
+/// - wallets: Wallets, con sus claves, nombres e historiales.
+/// - contacts: Etiquetas y direcciones de la libreta de direcciones.
+/// - movement_labels: Etiquetas de los movimientos, por el hash de su transaccion.
+pub struct WalletsBackup {
+    pub wallets: Vec<Wallet>,
+    pub contacts: Vec<(String, String)>,
+    pub movement_labels: Vec<(Hash32, String)>,
+}
+
+impl WalletsBackup {
+    /// Cifra el backup con la passphrase y lo guarda en el path recibido, reemplazando el archivo si ya existia.
+    /// Devuelve CustomError::Validation si la passphrase esta vacia.
+    pub fn export(&self, path: &str, passphrase: &str) -> Result<(), CustomError> {
+        write_file_atomically(path, &self.encrypt(passphrase)?)
+    }
+
+    /// Lee y descifra el backup guardado en el path recibido.
+    /// Devuelve CustomError::Validation si la passphrase es incorrecta o el archivo fue modificado,
+    /// y CustomError::StoreFileIncompatible si no es un backup de wallets, es de una version mas nueva
+    /// o no usa BACKUP_ITERATIONS iteraciones.
+    pub fn import(path: &str, passphrase: &str) -> Result<Self, CustomError> {
+        Self::decrypt(fs::read(path)?, passphrase)
+    }
+
+    fn encrypt(&self, passphrase: &str) -> Result<Vec<u8>, CustomError> {
+        if passphrase.is_empty() {
+            return Err(CustomError::Validation(String::from(
+                "The passphrase must not be empty",
+            )));
+        }
+        let salt = get_random_bytes(BACKUP_SALT_SIZE)?;
+        let nonce = get_random_bytes(BACKUP_SALT_SIZE)?;
+        let (encryption_key, mac_key) = derive_keys(passphrase, &salt, BACKUP_ITERATIONS);
+
+        let mut buffer = store_file_header(StoreFileKind::WalletsBackup);
+        buffer.extend(BACKUP_ITERATIONS.to_le_bytes());
+        buffer.extend(&salt);
+        buffer.extend(&nonce);
+        let mut contents = self.serialize();
+        apply_keystream(&encryption_key, &nonce, &mut contents);
+        buffer.extend(contents);
+        let mac = authenticate(&mac_key, &buffer);
+        buffer.extend(mac);
+        Ok(buffer)
+    }
+
+    fn decrypt(buffer: Vec<u8>, passphrase: &str) -> Result<Self, CustomError> {
+        let wrong_passphrase = || {
+            CustomError::Validation(String::from(
+                "Wrong passphrase or the backup file was modified",
+            ))
+        };
+        let Some(mac_start) = buffer.len().checked_sub(BACKUP_MAC_SIZE) else {
+            return Err(CustomError::StoreFileCorrupt);
+        };
+        let (authenticated, mac) = buffer.split_at(mac_start);
+
+        let mut parser = BufferParser::new(authenticated.to_vec());
+        if read_store_file_header(StoreFileKind::WalletsBackup, &mut parser)? == LEGACY_VERSION {
+            return Err(CustomError::StoreFileIncompatible);
+        }
+        // las iteraciones se leen antes de verificar el archivo, por lo que no se aceptan otras que las que se escriben
+        if parser.extract_u32()? != BACKUP_ITERATIONS {
+            return Err(CustomError::StoreFileIncompatible);
+        }
+        let salt = parser.extract_buffer(BACKUP_SALT_SIZE)?.to_vec();
+        let nonce = parser.extract_buffer(BACKUP_SALT_SIZE)?.to_vec();
+        let (encryption_key, mac_key) = derive_keys(passphrase, &salt, BACKUP_ITERATIONS);
+        if !equal_macs(&authenticate(&mac_key, authenticated), mac) {
+            return Err(wrong_passphrase());
+        }
+
+        let remaining = parser.len();
+        let mut contents = parser.extract_buffer(remaining)?.to_vec();
+        apply_keystream(&encryption_key, &nonce, &mut contents);
+        Self::parse(contents).map_err(|_| wrong_passphrase())
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        buffer.extend((self.wallets.len() as u32).to_le_bytes());
+        for wallet in &self.wallets {
+            buffer.extend(wallet.serialize());
+        }
+        buffer.extend((self.contacts.len() as u32).to_le_bytes());
+        for (label, address) in &self.contacts {
+            append_label(&mut buffer, label);
+            append_label(&mut buffer, address);
+        }
+        buffer.extend((self.movement_labels.len() as u32).to_le_bytes());
+        for (tx_hash, label) in &self.movement_labels {
+            buffer.extend(tx_hash.as_bytes());
+            append_label(&mut buffer, label);
+        }
+        buffer
+    }
+
+    fn parse(contents: Vec<u8>) -> Result<Self, CustomError> {
+        let mut parser = BufferParser::new(contents);
+        let mut backup = Self::default();
+        for _ in 0..parser.extract_u32()? {
+            backup.wallets.push(Wallet::parse(&mut parser)?);
+        }
+        for _ in 0..parser.extract_u32()? {
+            let label = extract_label(&mut parser)?;
+            backup.contacts.push((label, extract_label(&mut parser)?));
+        }
+        for _ in 0..parser.extract_u32()? {
+            let tx_hash = parser.extract_hash()?;
+            backup
+                .movement_labels
+                .push((tx_hash, extract_label(&mut parser)?));
+        }
+        if !parser.is_empty() {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Ok(backup)
+    }
+}
+
+/// Deriva de la passphrase la clave de cifrado y la de autenticacion, con PBKDF2-HMAC-SHA256 (RFC 8018).
+fn derive_keys(passphrase: &str, salt: &[u8], iterations: u32) -> ([u8; 32], [u8; 32]) {
+    let keyed_engine = HmacSha256Engine::new(passphrase.as_bytes());
+    let derive_block = |index: u32| {
+        let mut engine = keyed_engine.clone();
+        engine.input(salt);
+        engine.input(&index.to_be_bytes());
+        let mut block = HmacSha256::from_engine(engine).to_byte_array();
+        let mut result = block;
+        for _ in 1..iterations {
+            let mut engine = keyed_engine.clone();
+            engine.input(&block);
+            block = HmacSha256::from_engine(engine).to_byte_array();
+            result
+                .iter_mut()
+                .zip(block)
+                .for_each(|(byte, other)| *byte ^= other);
+        }
+        result
+    };
+    (derive_block(1), derive_block(2))
+}
+
+/// Cifra o descifra el contenido en el lugar, haciendo XOR con los bloques HMAC(clave, nonce || contador).
+fn apply_keystream(key: &[u8], nonce: &[u8], contents: &mut [u8]) {
+    for (counter, chunk) in contents.chunks_mut(32).enumerate() {
+        let mut engine = HmacSha256Engine::new(key);
+        engine.input(nonce);
+        engine.input(&(counter as u64).to_le_bytes());
+        let keystream = HmacSha256::from_engine(engine).to_byte_array();
+        chunk
+            .iter_mut()
+            .zip(keystream)
+            .for_each(|(byte, key_byte)| *byte ^= key_byte);
+    }
+}
+
+/// Calcula el codigo de autenticacion del archivo.
+fn authenticate(key: &[u8], buffer: &[u8]) -> [u8; BACKUP_MAC_SIZE] {
+    let mut engine = HmacSha256Engine::new(key);
+    engine.input(buffer);
+    HmacSha256::from_engine(engine).to_byte_array()
+}
+
+/// Compara los codigos de autenticacion recorriendolos completos, para no revelar cuantos bytes coinciden.
+fn equal_macs(expected: &[u8], received: &[u8]) -> bool {
+    expected.len() == received.len()
+        && expected
+            .iter()
+            .zip(received)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use crate::structs::movement::Movement;
+
+    use super::*;
+
+    fn backup() -> WalletsBackup {
+        WalletsBackup {
+            wallets: vec![Wallet {
+                name: String::from("wallet 1"),
+                pubkey: String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"),
+                privkey: String::from("cVK6pF1sfsvvmF9vGyq4wFeMywy1SMFHNpXa3d4Hi2evKHRQyTbn"),
+                history: vec![Movement {
+                    tx_hash: Hash32::new([3; 32]),
+                    value: 1000,
+                    block_hash: None,
+                    conflicted: false,
+                }],
+            }],
+            contacts: vec![(
+                String::from("Exchange"),
+                String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"),
+            )],
+            movement_labels: vec![(Hash32::new([3; 32]), String::from("Salary"))],
+        }
+    }
+
+    #[test]
+    fn pbkdf2_matches_the_rfc_test_vector() {
+        // RFC 7914, seccion 11: PBKDF2-HMAC-SHA256 de "passwd" con salt "salt" y 1 iteracion.
+        let (first_block, _) = derive_keys("passwd", b"salt", 1);
+        assert_eq!(
+            crate::utils::bytes_to_hex(&first_block),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+    }
+
+    #[test]
+    fn backup_is_restored_with_its_passphrase() -> Result<(), CustomError> {
+        let path = "tests/test_wallets_backup.bin";
+        backup().export(path, "correct horse")?;
+        let file = fs::read(path)?;
+        let restored = WalletsBackup::import(path, "correct horse");
+        let wrong_passphrase = WalletsBackup::import(path, "wrong horse");
+        remove_file(path)?;
+
+        let restored = restored?;
+        assert_eq!(restored.wallets.len(), 1);
+        assert_eq!(restored.wallets[0].privkey, backup().wallets[0].privkey);
+        assert_eq!(restored.wallets[0].history[0].tx_hash, Hash32::new([3; 32]));
+        assert_eq!(restored.contacts, backup().contacts);
+        assert_eq!(restored.movement_labels, backup().movement_labels);
+        assert!(matches!(wrong_passphrase, Err(CustomError::Validation(_))));
+        assert!(!file.windows(8).any(|window| window == b"cVK6pF1s"));
+
+        let mut tampered = file.clone();
+        tampered[60] ^= 1;
+        assert!(WalletsBackup::decrypt(tampered, "correct horse").is_err());
+
+        // un archivo con otras iteraciones se rechaza antes de derivar la clave
+        let iterations_start = store_file_header(StoreFileKind::WalletsBackup).len();
+        let mut tampered = file;
+        tampered[iterations_start..iterations_start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            WalletsBackup::decrypt(tampered, "correct horse"),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+        Ok(())
+    }
+}
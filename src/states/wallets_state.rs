@@ -1,65 +1,132 @@
-use std::io::{Read, Write};
+use std::{fs, io::Read, sync::mpsc::Sender};
 
 use crate::{
-    error::CustomError, messages::block::Block, parser::BufferParser, utils::open_new_file,
+    error::CustomError,
+    logger::{send_log, Log, LogTarget},
+    messages::{block::Block, transaction::Transaction},
+    parser::BufferParser,
+    structs::{
+        hash32::Hash32,
+        history_query::{HistoryPage, HistoryQuery},
+        movement::Movement,
+    },
+    utils::{open_new_file, write_file_atomically},
     wallet::Wallet,
 };
 
-use super::utxo_state::UTXO;
+use super::{
+    store_file::{seal_store_file, StoreFile, StoreFileKind},
+    utxo_state::UTXO,
+};
 
 /// Wallets es una estructura que contiene los elementos necesarios para manejar los wallets.
 /// Los elementos son:
 /// - wallets: Vector de wallets.
 /// - active_pubkey: Public key del wallet activo.
 /// - path: Path del archivo donde se guardan los wallets.
+/// - dirty: Indica si el historial de los wallets cambio desde la ultima vez que se guardaron.
 pub struct WalletsState {
     wallets: Vec<Wallet>,
     active_pubkey: Option<String>,
     path: String,
+    dirty: bool,
 }
 
 impl WalletsState {
     /// Inicializa los wallets del nodo.
     /// Si el archivo donde se guardan los wallets no existe, se crea.
     /// Si el archivo existe, se restauran los wallets.
-    pub fn new(path: String) -> Result<Self, CustomError> {
+    pub fn new(path: String, logger_sender: Sender<Log>) -> Result<Self, CustomError> {
         let mut wallets = Self {
             wallets: Vec::new(),
             active_pubkey: None,
             path,
+            dirty: false,
         };
-        wallets.restore()?;
+        wallets.restore(&logger_sender)?;
         Ok(wallets)
     }
 
-    fn restore(&mut self) -> Result<(), CustomError> {
+    /// Restaura los wallets guardados en el archivo.
+    /// Si el archivo esta corrupto (su checksum no coincide o no se puede parsear) se restauran los wallets
+    /// anteriores al error, y se conserva una copia del archivo original con la extension .corrupt para no perder las claves del resto.
+    /// Un archivo de una version anterior del formato se migra y se reescribe con el formato actual.
+    /// Devuelve CustomError::StoreFileIncompatible si el archivo es de otro tipo o de una version mas nueva.
+    fn restore(&mut self, logger_sender: &Sender<Log>) -> Result<(), CustomError> {
         let mut file = open_new_file(self.path.clone(), false)?;
         let mut buffer = vec![];
         file.read_to_end(&mut buffer)?;
-        let mut parser = BufferParser::new(buffer);
+        let is_empty = buffer.is_empty();
+        let saved_wallets = StoreFile::parse(StoreFileKind::Wallets, buffer)?;
+        let version = saved_wallets.version;
+        let outdated = saved_wallets.is_outdated(StoreFileKind::Wallets);
+        let mut parser = BufferParser::new(saved_wallets.contents);
 
-        let mut wallets = vec![];
+        let mut intact = saved_wallets.intact;
         while !parser.is_empty() {
-            let wallet = Wallet::parse(&mut parser)?;
-            wallets.push(wallet);
+            let Ok(wallet) = Wallet::parse(&mut parser) else {
+                intact = false;
+                break;
+            };
+            self.wallets.push(wallet);
         }
 
-        self.wallets = wallets;
+        if !intact {
+            let backup_path = format!("{}.corrupt", self.path);
+            fs::copy(&self.path, &backup_path)?;
+            send_log(
+                logger_sender,
+                Log::Warn(
+                    LogTarget::Wallet,
+                    format!(
+                        "Wallets file is corrupt, restored {} wallets and saved a copy to {backup_path}",
+                        self.wallets.len()
+                    ),
+                ),
+            );
+            return self.save();
+        }
+        if outdated {
+            self.save()?;
+            if !is_empty {
+                send_log(
+                    logger_sender,
+                    Log::Info(
+                        LogTarget::Wallet,
+                        format!(
+                            "Wallets file migrated from format version {version} to {}",
+                            StoreFileKind::Wallets.version()
+                        ),
+                    ),
+                );
+            }
+        }
         Ok(())
     }
 
-    fn save(&self) -> Result<(), CustomError> {
-        let mut file = open_new_file(self.path.clone(), false)?;
-
+    fn save(&mut self) -> Result<(), CustomError> {
         let mut buffer = vec![];
         for wallet in &self.wallets {
             buffer.append(&mut wallet.serialize());
         }
 
-        file.write_all(&buffer)?;
+        write_file_atomically(
+            &self.path,
+            &seal_store_file(StoreFileKind::Wallets, &buffer),
+        )?;
+        self.dirty = false;
         Ok(())
     }
 
+    /// Guarda las wallets, lo que fuerza su escritura en disco.
+    /// Si no cambiaron desde la ultima vez que se guardaron no hace nada.
+    pub fn flush(&mut self) -> Result<(), CustomError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.save()
+    }
+
     /// Establece la wallet activa.
     pub fn set_active(&mut self, public_key: &str) -> Result<(), CustomError> {
         self.active_pubkey = self
@@ -91,6 +158,78 @@ impl WalletsState {
         Ok(())
     }
 
+    /// Cambia el nombre de la wallet con la public key recibida.
+    /// Devuelve CustomError::Validation si el nombre esta vacio o supera los 255 bytes, ya que su largo se guarda en un byte.
+    pub fn rename(&mut self, public_key: &str, name: &str) -> Result<(), CustomError> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > u8::MAX as usize {
+            return Err(CustomError::Validation(
+                "Wallet names must have between 1 and 255 bytes".to_string(),
+            ));
+        }
+        let wallet = self
+            .wallets
+            .iter_mut()
+            .find(|wallet| wallet.pubkey == public_key)
+            .ok_or(CustomError::WalletNotFound)?;
+        wallet.name = name.to_string();
+        self.save()
+    }
+
+    /// Mueve la wallet con la public key recibida a la posicion indicada de la lista, corriendo las que estaban entre medio.
+    /// Devuelve CustomError::Validation si la posicion no existe.
+    pub fn move_to(&mut self, public_key: &str, index: usize) -> Result<(), CustomError> {
+        if index >= self.wallets.len() {
+            return Err(CustomError::Validation(format!(
+                "Invalid wallet position, there are {} wallets",
+                self.wallets.len()
+            )));
+        }
+        let wallet = self.remove_from_list(public_key)?;
+        self.wallets.insert(index, wallet);
+        self.save()
+    }
+
+    /// Quita la wallet con la public key recibida y la devuelve. Si era la activa, no queda ninguna wallet activa.
+    pub fn remove(&mut self, public_key: &str) -> Result<Wallet, CustomError> {
+        let wallet = self.remove_from_list(public_key)?;
+        if self.active_pubkey.as_deref() == Some(public_key) {
+            self.active_pubkey = None;
+        }
+        self.save()?;
+        Ok(wallet)
+    }
+
+    fn remove_from_list(&mut self, public_key: &str) -> Result<Wallet, CustomError> {
+        let index = self
+            .wallets
+            .iter()
+            .position(|wallet| wallet.pubkey == public_key)
+            .ok_or(CustomError::WalletNotFound)?;
+        Ok(self.wallets.remove(index))
+    }
+
+    /// Agrega las wallets recibidas, por ejemplo de un backup. Las que ya estaban conservan su nombre
+    /// y solo se les agregan los movimientos que no tenian. Devuelve la cantidad de wallets nuevas.
+    pub fn merge(&mut self, wallets: Vec<Wallet>) -> Result<usize, CustomError> {
+        let mut added = 0;
+        for wallet in wallets {
+            match self
+                .wallets
+                .iter_mut()
+                .find(|known| known.pubkey == wallet.pubkey)
+            {
+                Some(known) => known.merge_history(wallet.history),
+                None => {
+                    self.wallets.push(wallet);
+                    added += 1;
+                }
+            }
+        }
+        self.save()?;
+        Ok(added)
+    }
+
     /// Devuelve la wallet activa.
     pub fn get_active(&self) -> Option<&Wallet> {
         match self.active_pubkey {
@@ -102,36 +241,119 @@ impl WalletsState {
         }
     }
 
+    /// Devuelve la pagina del historial de la wallet activa que pide la consulta, del movimiento mas reciente al mas viejo.
+    /// Los movimientos pendientes recibidos se consideran los mas recientes, y para el resto se obtiene el timestamp
+    /// de su bloque con block_timestamp. Solo se clonan los movimientos de la pagina.
+    /// Devuelve CustomError::WalletNotFound si no hay una wallet activa.
+    pub fn query_active_history(
+        &self,
+        query: &HistoryQuery,
+        pending: Vec<Movement>,
+        block_timestamp: impl Fn(&Hash32) -> Option<u32>,
+    ) -> Result<HistoryPage, CustomError> {
+        let Some(active_wallet) = self.get_active() else {
+            return Err(CustomError::WalletNotFound);
+        };
+
+        let mut page = HistoryPage::default();
+        let history = active_wallet.history.iter().rev().map(|movement| {
+            let timestamp = movement.block_hash.as_ref().and_then(&block_timestamp);
+            (movement, timestamp)
+        });
+        let movements = pending
+            .iter()
+            .map(|movement| (movement, None))
+            .chain(history)
+            .filter(|(movement, timestamp)| query.matches(movement, *timestamp));
+
+        for (movement, timestamp) in movements {
+            if page.total >= query.offset && page.movements.len() < query.limit {
+                page.movements.push((movement.clone(), timestamp));
+            }
+            page.total += 1;
+        }
+        Ok(page)
+    }
+
+    /// Indica si la transaccion mueve fondos de alguna de las wallets.
+    pub fn involves(&self, transaction: &Transaction, utxo: &UTXO) -> Result<bool, CustomError> {
+        for wallet in &self.wallets {
+            if transaction
+                .get_movement(&wallet.get_pubkey_hash()?, utxo)?
+                .is_some()
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     /// Actualiza las wallets con la informacion del nuevo bloque.
-    pub fn update(&mut self, block: &Block, utxo: &UTXO) -> Result<bool, CustomError> {
-        let mut wallets_updated = false;
+    /// Devuelve los hashes de las transacciones del bloque que mueven fondos de alguna de las wallets.
+    /// Los cambios se guardan en disco en el proximo flush.
+    pub fn update(&mut self, block: &Block, utxo: &UTXO) -> Result<Vec<Hash32>, CustomError> {
+        let mut wallet_txs = vec![];
 
         for tx in &block.transactions {
+            let mut involved = false;
             for wallet in &mut self.wallets {
                 let movement = tx.get_movement(&wallet.get_pubkey_hash()?, utxo)?;
                 if let Some(mut movement) = movement {
-                    movement.block_hash = Some(block.header.hash().clone());
+                    movement.block_hash = Some(*block.header.hash());
                     wallet.update_history(movement);
-                    wallets_updated = true;
+                    involved = true;
                 }
             }
+            if involved {
+                wallet_txs.push(tx.hash());
+            }
         }
-        if wallets_updated {
-            self.save()?;
+        self.dirty |= !wallet_txs.is_empty();
+        Ok(wallet_txs)
+    }
+
+    /// Indica si hay una wallet activa.
+    pub fn has_active(&self) -> bool {
+        self.active_pubkey.is_some()
+    }
+
+    /// Agrega al historial de las wallets los movimientos de las transacciones pendientes que entraron en conflicto
+    /// con un bloque, marcados como conflicted, para que no se muestren como pendientes para siempre.
+    /// Devuelve true si se actualizo alguna wallet y hay una wallet activa.
+    /// Los cambios se guardan en disco en el proximo flush.
+    pub fn mark_conflicted(
+        &mut self,
+        transactions: &[Transaction],
+        utxo: &UTXO,
+    ) -> Result<bool, CustomError> {
+        let mut wallets_updated = false;
+
+        for tx in transactions {
+            for wallet in &mut self.wallets {
+                if let Some(mut movement) = tx.get_movement(&wallet.get_pubkey_hash()?, utxo)? {
+                    movement.conflicted = true;
+                    wallet.update_history(movement);
+                    wallets_updated = true;
+                }
+            }
         }
+        self.dirty |= wallets_updated;
         Ok(wallets_updated && self.active_pubkey.is_some())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs::{self, remove_file};
+    use std::{
+        fs::{self, remove_file},
+        sync::mpsc,
+    };
 
     use crate::{
         messages::transaction::Transaction,
         structs::{
-            block_header::BlockHeader, outpoint::OutPoint, tx_input::TransactionInput,
-            tx_output::TransactionOutput,
+            block_header::BlockHeader, history_query::MovementFilter, outpoint::OutPoint,
+            tx_input::TransactionInput, tx_output::TransactionOutput,
         },
     };
 
@@ -139,7 +361,8 @@ mod tests {
 
     #[test]
     fn create_wallets_empty() {
-        let wallets = WalletsState::new("tests/wallets_empty.bin".to_string()).unwrap();
+        let wallets =
+            WalletsState::new("tests/wallets_empty.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.wallets.len(), 0);
         assert_eq!(wallets.active_pubkey, None);
 
@@ -148,11 +371,66 @@ mod tests {
 
     #[test]
     fn create_wallets_restoring_a_wallet() {
-        let wallets = WalletsState::new("tests/test_wallets.bin".to_string()).unwrap();
+        let wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.wallets.len(), 1);
         assert_eq!(wallets.active_pubkey, None);
     }
 
+    #[test]
+    fn create_wallets_restoring_a_corrupt_file() {
+        let path = "tests/test_wallets_corrupt.bin";
+        let mut buffer = fs::read("tests/test_wallets.bin").unwrap();
+        let wallets_len = buffer.len();
+        buffer.extend([0xff; 3]);
+        fs::write(path, &buffer).unwrap();
+
+        let wallets = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        assert_eq!(wallets.wallets.len(), 1);
+        assert_eq!(fs::read(path).unwrap().len(), wallets_len);
+        assert_eq!(fs::read(format!("{path}.corrupt")).unwrap(), buffer);
+
+        remove_file(path).unwrap();
+        remove_file(format!("{path}.corrupt")).unwrap();
+    }
+
+    #[test]
+    fn create_wallets_with_a_wrong_checksum() {
+        let path = "tests/test_wallets_checksum.bin";
+        let mut buffer = fs::read("tests/test_wallets.bin").unwrap();
+        let last = buffer.len() - 1;
+        buffer[last] ^= 0xff;
+        fs::write(path, &buffer).unwrap();
+
+        WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        assert_eq!(fs::read(format!("{path}.corrupt")).unwrap(), buffer);
+
+        remove_file(path).unwrap();
+        remove_file(format!("{path}.corrupt")).unwrap();
+    }
+
+    #[test]
+    fn create_wallets_from_legacy_and_incompatible_files() {
+        let path = "tests/test_wallets_legacy.bin";
+        let buffer = fs::read("tests/test_wallets.bin").unwrap();
+        let contents = StoreFile::parse(StoreFileKind::Wallets, buffer.clone())
+            .unwrap()
+            .contents;
+        fs::write(path, contents).unwrap();
+
+        let wallets = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        assert_eq!(wallets.wallets.len(), 1);
+        assert_eq!(fs::read(path).unwrap(), buffer);
+
+        fs::write(path, seal_store_file(StoreFileKind::Utxo, &[])).unwrap();
+        assert!(matches!(
+            WalletsState::new(path.to_string(), mpsc::channel().0),
+            Err(CustomError::StoreFileIncompatible)
+        ));
+
+        remove_file(path).unwrap();
+    }
+
     #[test]
     fn append_wallet() {
         fs::copy(
@@ -161,7 +439,11 @@ mod tests {
         )
         .unwrap();
 
-        let mut wallets = WalletsState::new("tests/test_wallets_append.bin".to_string()).unwrap();
+        let mut wallets = WalletsState::new(
+            "tests/test_wallets_append.bin".to_string(),
+            mpsc::channel().0,
+        )
+        .unwrap();
         assert_eq!(wallets.wallets.len(), 1);
 
         let new_wallet = Wallet::new(
@@ -186,8 +468,11 @@ mod tests {
         )
         .unwrap();
 
-        let mut wallets =
-            WalletsState::new("tests/test_wallets_append_duplicated.bin".to_string()).unwrap();
+        let mut wallets = WalletsState::new(
+            "tests/test_wallets_append_duplicated.bin".to_string(),
+            mpsc::channel().0,
+        )
+        .unwrap();
         assert_eq!(wallets.wallets.len(), 1);
 
         let new_wallet = Wallet::new(
@@ -204,9 +489,85 @@ mod tests {
         remove_file("tests/test_wallets_append_duplicated.bin".to_string()).unwrap();
     }
 
+    #[test]
+    fn rename_move_and_remove_wallets() {
+        let path = "tests/test_wallets_manage.bin";
+        fs::copy("tests/test_wallets.bin", path).unwrap();
+        let mut wallets = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        let mut second = wallets.wallets[0].clone();
+        second.pubkey = String::from("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3");
+        wallets.append(second).unwrap();
+        wallets
+            .set_active("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm")
+            .unwrap();
+
+        wallets
+            .rename("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3", " savings ")
+            .unwrap();
+        assert!(wallets
+            .rename("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3", " ")
+            .is_err());
+        wallets
+            .move_to("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3", 0)
+            .unwrap();
+        assert!(wallets
+            .move_to("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3", 2)
+            .is_err());
+        let restored = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        let names: Vec<&str> = restored
+            .get_all()
+            .iter()
+            .map(|wallet| wallet.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["savings", "wallet 1"]);
+
+        let removed = wallets
+            .remove("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm")
+            .unwrap();
+        assert_eq!(removed.name, "wallet 1");
+        assert!(!wallets.has_active());
+        assert!(matches!(
+            wallets.remove("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"),
+            Err(CustomError::WalletNotFound)
+        ));
+        let restored = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        remove_file(path).unwrap();
+        assert_eq!(restored.get_all().len(), 1);
+    }
+
+    #[test]
+    fn merge_wallets() {
+        let path = "tests/test_wallets_merge.bin";
+        fs::copy("tests/test_wallets.bin", path).unwrap();
+        let mut wallets = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        let movement = |byte| Movement {
+            tx_hash: Hash32::new([byte; 32]),
+            value: 100,
+            block_hash: None,
+            conflicted: false,
+        };
+
+        let mut known = wallets.wallets[0].clone();
+        known.name = String::from("renamed");
+        known.history = vec![movement(1)];
+        let mut new_wallet = known.clone();
+        new_wallet.pubkey = String::from("mxz3drZtkg4R3u1RDL7zRPLsizvhmGWfr3");
+
+        assert_eq!(wallets.merge(vec![known.clone(), new_wallet]).unwrap(), 1);
+        assert_eq!(wallets.merge(vec![known]).unwrap(), 0);
+        let restored = WalletsState::new(path.to_string(), mpsc::channel().0).unwrap();
+        remove_file(path).unwrap();
+
+        assert_eq!(restored.wallets.len(), 2);
+        assert_eq!(restored.wallets[0].name, "wallet 1");
+        assert_eq!(restored.wallets[0].history.len(), 1);
+        assert_eq!(restored.wallets[1].history.len(), 1);
+    }
+
     #[test]
     fn save_wallets() {
-        let mut wallets = WalletsState::new("tests/save_wallets.bin".to_string()).unwrap();
+        let mut wallets =
+            WalletsState::new("tests/save_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.wallets.len(), 0);
 
         let new_wallet = Wallet::new(
@@ -220,7 +581,8 @@ mod tests {
         wallets.append(new_wallet).unwrap();
         assert_eq!(wallets.wallets.len(), 1);
 
-        let wallets2 = WalletsState::new("tests/save_wallets.bin".to_string()).unwrap();
+        let wallets2 =
+            WalletsState::new("tests/save_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets2.wallets.len(), 1);
 
         remove_file("tests/save_wallets.bin".to_string()).unwrap();
@@ -228,7 +590,8 @@ mod tests {
 
     #[test]
     fn get_wallets() {
-        let wallets = WalletsState::new("tests/test_wallets.bin".to_string()).unwrap();
+        let wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.active_pubkey, None);
 
         let all_wallets = wallets.get_all();
@@ -240,7 +603,8 @@ mod tests {
 
     #[test]
     fn set_active_wallet() {
-        let mut wallets = WalletsState::new("tests/test_wallets.bin".to_string()).unwrap();
+        let mut wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.active_pubkey, None);
 
         wallets
@@ -254,7 +618,8 @@ mod tests {
 
     #[test]
     fn get_active_wallet() {
-        let mut wallets = WalletsState::new("tests/test_wallets.bin".to_string()).unwrap();
+        let mut wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
         assert_eq!(wallets.active_pubkey, None);
 
         assert!(wallets.get_active().is_none());
@@ -280,7 +645,11 @@ mod tests {
         )
         .unwrap();
 
-        let mut wallets = WalletsState::new("tests/test_wallets_update.bin".to_string()).unwrap();
+        let mut wallets = WalletsState::new(
+            "tests/test_wallets_update.bin".to_string(),
+            mpsc::channel().0,
+        )
+        .unwrap();
         assert_eq!(wallets.active_pubkey, None);
 
         wallets
@@ -292,12 +661,12 @@ mod tests {
         let block = Block {
             header: BlockHeader {
                 version: 536887296,
-                prev_block_hash: vec![],
-                merkle_root: vec![],
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
                 timestamp: 1686626483,
                 bits: 421617023,
                 nonce: 3878826733,
-                hash: vec![],
+                hash: Hash32::default(),
                 block_downloaded: true,
                 broadcasted: true,
             },
@@ -305,11 +674,12 @@ mod tests {
                 version: 1,
                 inputs: vec![TransactionInput {
                     previous_output: OutPoint {
-                        hash: vec![],
+                        hash: Hash32::default(),
                         index: 4294967295,
                     },
                     script_sig: vec![],
                     sequence: 4294967295,
+                    witness: vec![],
                 }],
                 outputs: vec![TransactionOutput {
                     value: 2366975,
@@ -324,11 +694,70 @@ mod tests {
 
         let utxo = UTXO::new(String::from("tests"), String::from("test_utxo.bin")).unwrap();
 
-        let updated = wallets.update(&block, &utxo).unwrap();
+        let wallet_txs = wallets.update(&block, &utxo).unwrap();
 
-        assert_eq!(updated, true);
+        assert_eq!(wallet_txs, vec![block.transactions[0].hash()]);
         assert_eq!(wallets.get_active().unwrap().history.len(), 1);
 
         remove_file("tests/test_wallets_update.bin".to_string()).unwrap();
     }
+
+    #[test]
+    fn query_active_history_pages_and_filters() {
+        let mut wallets =
+            WalletsState::new("tests/test_wallets.bin".to_string(), mpsc::channel().0).unwrap();
+        wallets
+            .set_active("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm")
+            .unwrap();
+        for i in 0..10_u8 {
+            wallets.wallets[0].history.push(Movement {
+                tx_hash: Hash32::new([i; 32]),
+                value: if i % 2 == 0 { 100 } else { -100 },
+                block_hash: Some(Hash32::new([i; 32])),
+                conflicted: false,
+            });
+        }
+        let pending = vec![Movement {
+            tx_hash: Hash32::new([0xff; 32]),
+            value: -50,
+            block_hash: None,
+            conflicted: false,
+        }];
+        let block_timestamp = |hash: &Hash32| Some(hash.as_bytes()[0] as u32 * 100);
+
+        let query = HistoryQuery {
+            offset: 0,
+            limit: 4,
+            ..Default::default()
+        };
+        let page = wallets
+            .query_active_history(&query, pending.clone(), block_timestamp)
+            .unwrap();
+        assert_eq!(page.total, 11);
+        assert_eq!(page.movements.len(), 4);
+        assert_eq!(page.movements[0].0.tx_hash, Hash32::new([0xff; 32]));
+        assert_eq!(page.movements[1].0.tx_hash, Hash32::new([9; 32]));
+        assert_eq!(page.movements[1].1, Some(900));
+
+        let query = HistoryQuery {
+            filter: MovementFilter::Received,
+            from: Some(200),
+            offset: 1,
+            limit: 10,
+            ..Default::default()
+        };
+        let page = wallets
+            .query_active_history(&query, pending, block_timestamp)
+            .unwrap();
+        assert_eq!(page.total, 4);
+        let hashes: Vec<Hash32> = page.movements.iter().map(|(m, _)| m.tx_hash).collect();
+        assert_eq!(
+            hashes,
+            vec![
+                Hash32::new([6; 32]),
+                Hash32::new([4; 32]),
+                Hash32::new([2; 32])
+            ]
+        );
+    }
 }
@@ -0,0 +1,94 @@
+use super::hash32::Hash32;
+
+#[derive(Debug, Clone, PartialEq)]
+/// AccelerationPreview resume una transaccion hija que gasta las salidas de la wallet activa de una pending tx
+/// con un fee alto, para que los mineros incluyan a las dos juntas (child pays for parent), y se muestra al usuario
+/// para que la confirme antes de enviarla.
+/// Los elementos son:
+/// - parent_hash: Hash de la pending tx que se quiere acelerar.
+/// - parent_fee: Fee en satoshis de la pending tx, None si no se conoce.
+/// - parent_vsize: Tamaño virtual de la pending tx.
+/// - address: Direccion de la wallet activa, que recibe la salida de la transaccion hija.
+/// - value: Valor en satoshis de la salida de la transaccion hija.
+/// - fee: Fee en satoshis de la transaccion hija.
+/// - vsize: Tamaño virtual de la transaccion hija.
+pub struct AccelerationPreview {
+    pub parent_hash: Hash32,
+    pub parent_fee: Option<u64>,
+    pub parent_vsize: usize,
+    pub address: String,
+    pub value: u64,
+    pub fee: u64,
+    pub vsize: usize,
+}
+
+impl AccelerationPreview {
+    /// Devuelve el fee rate en satoshis por byte virtual de las dos transacciones juntas,
+    /// contando como 0 el fee de la pending tx si no se conoce.
+    pub fn package_fee_rate(&self) -> f64 {
+        match self.parent_vsize + self.vsize {
+            0 => 0.0,
+            vsize => (self.parent_fee.unwrap_or(0) + self.fee) as f64 / vsize as f64,
+        }
+    }
+
+    /// Describe las transacciones en lineas, con los montos formateados con la funcion recibida.
+    pub fn describe(&self, format_amount: impl Fn(i64) -> String) -> String {
+        let parent_fee = match self.parent_fee {
+            Some(fee) => format!(
+                "{} ({:.1} sat/vB, {} vB)",
+                format_amount(fee as i64),
+                fee as f64 / self.parent_vsize as f64,
+                self.parent_vsize
+            ),
+            None => format!("Unknown ({} vB)", self.parent_vsize),
+        };
+        [
+            format!("Accelerate {}", self.parent_hash),
+            format!("Parent fee: {parent_fee}"),
+            format!(
+                "Child fee: {} ({:.1} sat/vB, {} vB)",
+                format_amount(self.fee as i64),
+                self.fee as f64 / self.vsize as f64,
+                self.vsize
+            ),
+            format!(
+                "Receive {} at {}",
+                format_amount(self.value as i64),
+                self.address
+            ),
+            format!("Package fee rate: {:.1} sat/vB", self.package_fee_rate()),
+        ]
+        .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acceleration_preview_description() {
+        let hash = Hash32::default();
+        let preview = AccelerationPreview {
+            parent_hash: hash,
+            parent_fee: Some(200),
+            parent_vsize: 200,
+            address: String::from("address"),
+            value: 5000,
+            fee: 1800,
+            vsize: 200,
+        };
+        assert_eq!(preview.package_fee_rate(), 5.0);
+        assert_eq!(
+            preview.describe(|value| format!("{value} sats")),
+            format!("Accelerate {hash}\nParent fee: 200 sats (1.0 sat/vB, 200 vB)\nChild fee: 1800 sats (9.0 sat/vB, 200 vB)\nReceive 5000 sats at address\nPackage fee rate: 5.0 sat/vB")
+        );
+
+        let unknown_fee = AccelerationPreview {
+            parent_fee: None,
+            ..preview
+        };
+        assert_eq!(unknown_fee.package_fee_rate(), 4.5);
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::CustomError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Unidad en la que se muestran e ingresan los montos:
+/// - Btc: 100.000.000 satoshis, con 8 decimales.
+/// - MilliBtc: 100.000 satoshis, con 5 decimales.
+/// - Sats: satoshis, sin decimales.
+pub enum AmountUnit {
+    #[default]
+    Btc,
+    MilliBtc,
+    Sats,
+}
+
+impl AmountUnit {
+    /// Todas las unidades, en el orden en que se ofrecen al usuario.
+    pub const ALL: [AmountUnit; 3] = [Self::Btc, Self::MilliBtc, Self::Sats];
+
+    /// Cantidad de decimales de la unidad, es decir la potencia de 10 de satoshis que equivale a una unidad.
+    pub fn decimals(&self) -> u32 {
+        match self {
+            Self::Btc => 8,
+            Self::MilliBtc => 5,
+            Self::Sats => 0,
+        }
+    }
+
+    /// Formatea un valor en satoshis en la unidad, con todos sus decimales y el nombre de la unidad.
+    /// Los valores negativos se muestran con signo.
+    pub fn format(&self, value: i64) -> String {
+        format!("{} {self}", self.format_value(value))
+    }
+
+    /// Formatea un valor en satoshis en la unidad, con todos sus decimales y sin el nombre de la unidad,
+    /// de forma que se pueda volver a parsear con parse.
+    pub fn format_value(&self, value: i64) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.unsigned_abs();
+        let decimals = self.decimals();
+        if decimals == 0 {
+            return format!("{sign}{value}");
+        }
+        let satoshis_per_unit = 10_u64.pow(decimals);
+        format!(
+            "{sign}{}.{:0width$}",
+            value / satoshis_per_unit,
+            value % satoshis_per_unit,
+            width = decimals as usize
+        )
+    }
+
+    /// Parsea un monto ingresado en la unidad y lo devuelve en satoshis, sin pasar por punto flotante.
+    /// Acepta punto o coma como separador decimal.
+    /// Devuelve CustomError::InvalidValue si no es un numero positivo, si tiene mas decimales que la unidad o si es demasiado grande.
+    pub fn parse(&self, value: &str) -> Result<u64, CustomError> {
+        let value = value.trim().replace(',', ".");
+        let (integer, fraction) = value.split_once('.').unwrap_or((&value, ""));
+        let decimals = self.decimals() as usize;
+        if (integer.is_empty() && fraction.is_empty())
+            || fraction.len() > decimals
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(CustomError::InvalidValue);
+        }
+
+        let integer: u64 = match integer {
+            "" => 0,
+            integer => integer.parse().map_err(|_| CustomError::InvalidValue)?,
+        };
+        let fraction: u64 = match fraction {
+            "" => 0,
+            fraction => {
+                fraction
+                    .parse::<u64>()
+                    .map_err(|_| CustomError::InvalidValue)?
+                    * 10_u64.pow((decimals - fraction.len()) as u32)
+            }
+        };
+        integer
+            .checked_mul(10_u64.pow(decimals as u32))
+            .and_then(|satoshis| satoshis.checked_add(fraction))
+            .ok_or(CustomError::InvalidValue)
+    }
+}
+
+impl fmt::Display for AmountUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Btc => "BTC",
+            Self::MilliBtc => "mBTC",
+            Self::Sats => "sats",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for AmountUnit {
+    type Err = CustomError;
+
+    /// Parsea el nombre de la unidad, como lo devuelve Display.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|unit| unit.to_string() == value)
+            .ok_or(CustomError::InvalidValue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_in_each_unit() {
+        assert_eq!(AmountUnit::Btc.format(123_456_789), "1.23456789 BTC");
+        assert_eq!(AmountUnit::MilliBtc.format(123_456_789), "1234.56789 mBTC");
+        assert_eq!(AmountUnit::Sats.format(123_456_789), "123456789 sats");
+        assert_eq!(AmountUnit::Btc.format(-5_000), "-0.00005000 BTC");
+    }
+
+    #[test]
+    fn formatted_values_parse_back() -> Result<(), CustomError> {
+        for unit in AmountUnit::ALL {
+            assert_eq!(unit.parse(&unit.format_value(123_456_789))?, 123_456_789);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_in_each_unit() -> Result<(), CustomError> {
+        assert_eq!(AmountUnit::Btc.parse("1.5")?, 150_000_000);
+        assert_eq!(AmountUnit::Btc.parse("0,00000001")?, 1);
+        assert_eq!(AmountUnit::Btc.parse(".1")?, 10_000_000);
+        assert_eq!(AmountUnit::MilliBtc.parse("2.5")?, 250_000);
+        assert_eq!(AmountUnit::Sats.parse(" 1000 ")?, 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_invalid_amounts() {
+        assert!(AmountUnit::Btc.parse("").is_err());
+        assert!(AmountUnit::Btc.parse(".").is_err());
+        assert!(AmountUnit::Btc.parse("-1").is_err());
+        assert!(AmountUnit::Btc.parse("0.000000001").is_err());
+        assert!(AmountUnit::Sats.parse("1.5").is_err());
+        assert!(AmountUnit::Btc.parse("1e3").is_err());
+        assert!(AmountUnit::Btc.parse("999999999999").is_err());
+    }
+
+    #[test]
+    fn unit_names_roundtrip() {
+        for unit in AmountUnit::ALL {
+            assert_eq!(unit.to_string().parse::<AmountUnit>().unwrap(), unit);
+        }
+    }
+}
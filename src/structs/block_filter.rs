@@ -0,0 +1,247 @@
+use bitcoin_hashes::siphash24;
+
+use crate::{
+    error::CustomError,
+    messages::block::Block,
+    parser::{BufferParser, VarIntSerialize},
+};
+
+use super::{hash32::Hash32, script::OP_RETURN};
+
+/// Tipo del filtro basico de BIP158, el unico que se construye y se sirve.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+/// Cantidad de bits del resto en la codificacion Golomb-Rice.
+const GOLOMB_P: u8 = 19;
+/// Inversa de la tasa de falsos positivos del filtro basico.
+const GOLOMB_M: u64 = 784931;
+
+#[derive(Debug, Clone, PartialEq)]
+/// BlockFilter es el filtro basico de un bloque (BIP158), un Golomb-coded set con los scripts de las salidas
+/// creadas en el bloque y los de las salidas que gastan sus transacciones.
+/// Permite a un cliente liviano saber si un bloque le interesa sin revelar sus direcciones.
+/// - data: Filtro serializado, la cantidad de elementos seguida de las diferencias codificadas.
+pub struct BlockFilter {
+    pub data: Vec<u8>,
+}
+
+impl BlockFilter {
+    /// Crea el filtro del bloque con los elementos recibidos.
+    /// Las claves del SipHash se derivan del hash del bloque, por lo que cada bloque mapea los elementos distinto.
+    pub fn new(block_hash: &Hash32, elements: &[Vec<u8>]) -> Self {
+        let mut elements: Vec<&Vec<u8>> = elements.iter().collect();
+        elements.sort();
+        elements.dedup();
+
+        let range = elements.len() as u64 * GOLOMB_M;
+        let mut values: Vec<u64> = elements
+            .iter()
+            .map(|element| hash_to_range(block_hash, element, range))
+            .collect();
+        values.sort_unstable();
+
+        let mut data = elements.len().to_varint_bytes();
+        let mut writer = BitWriter::default();
+        let mut last_value = 0;
+        for value in values {
+            writer.write_golomb_rice(value - last_value);
+            last_value = value;
+        }
+        data.extend(writer.finish());
+        Self { data }
+    }
+
+    /// Crea el filtro de un bloque a partir de sus salidas y de los scripts de las salidas que gasta.
+    /// Se excluyen los scripts vacios y los OP_RETURN, como indica BIP158.
+    pub fn from_block(block: &Block, spent_scripts: &[Vec<u8>]) -> Self {
+        let mut elements = vec![];
+        for transaction in &block.transactions {
+            for output in &transaction.outputs {
+                if output
+                    .script_pubkey
+                    .first()
+                    .is_some_and(|op| *op != OP_RETURN)
+                {
+                    elements.push(output.script_pubkey.clone());
+                }
+            }
+        }
+        elements.extend(
+            spent_scripts
+                .iter()
+                .filter(|script| !script.is_empty())
+                .cloned(),
+        );
+        Self::new(block.header.hash(), &elements)
+    }
+
+    /// Indica si el elemento puede estar en el filtro del bloque, puede devolver falsos positivos.
+    /// Devuelve CustomError si el filtro esta mal formado.
+    pub fn contains(&self, block_hash: &Hash32, element: &[u8]) -> Result<bool, CustomError> {
+        let mut parser = BufferParser::new(self.data.clone());
+        let count = parser.extract_varint()?;
+        let target = hash_to_range(block_hash, element, count * GOLOMB_M);
+
+        let remaining = parser.len();
+        let mut reader = BitReader::new(parser.extract_buffer(remaining)?.to_vec());
+        let mut value = 0;
+        for _ in 0..count {
+            value += reader.read_golomb_rice()?;
+            if value == target {
+                return Ok(true);
+            }
+            if value > target {
+                break;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Devuelve el hash del filtro.
+    pub fn hash(&self) -> Hash32 {
+        Hash32::sha256d(&self.data)
+    }
+
+    /// Devuelve el header del filtro (BIP157), que lo encadena con el header del filtro del bloque anterior.
+    pub fn header(&self, previous_header: &Hash32) -> Hash32 {
+        let mut buffer = self.hash().to_vec();
+        buffer.extend(previous_header.as_bytes());
+        Hash32::sha256d(&buffer)
+    }
+}
+
+/// Mapea el elemento a un numero uniforme en [0, range) usando SipHash con las claves del bloque.
+fn hash_to_range(block_hash: &Hash32, element: &[u8], range: u64) -> u64 {
+    let bytes = block_hash.as_bytes();
+    let mut k0 = [0; 8];
+    let mut k1 = [0; 8];
+    k0.copy_from_slice(&bytes[0..8]);
+    k1.copy_from_slice(&bytes[8..16]);
+    let hash = siphash24::Hash::hash_to_u64_with_keys(
+        u64::from_le_bytes(k0),
+        u64::from_le_bytes(k1),
+        element,
+    );
+    ((hash as u128 * range as u128) >> 64) as u64
+}
+
+#[derive(Default)]
+/// Escribe bits de a uno, del mas significativo al menos significativo de cada byte.
+struct BitWriter {
+    buffer: Vec<u8>,
+    bits_used: usize,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.bits_used.is_multiple_of(8) {
+            self.buffer.push(0);
+        }
+        if bit {
+            if let Some(byte) = self.buffer.last_mut() {
+                *byte |= 0x80 >> (self.bits_used % 8);
+            }
+        }
+        self.bits_used += 1;
+    }
+
+    /// Escribe el cociente en unario seguido de los GOLOMB_P bits del resto.
+    fn write_golomb_rice(&mut self, value: u64) {
+        for _ in 0..(value >> GOLOMB_P) {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for bit in (0..GOLOMB_P).rev() {
+            self.write_bit(value & (1 << bit) != 0);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+/// Lee bits de a uno, en el mismo orden en que los escribe BitWriter.
+struct BitReader {
+    buffer: Vec<u8>,
+    bits_read: usize,
+}
+
+impl BitReader {
+    fn new(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer,
+            bits_read: 0,
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, CustomError> {
+        let Some(byte) = self.buffer.get(self.bits_read / 8) else {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        };
+        let bit = byte & (0x80 >> (self.bits_read % 8)) != 0;
+        self.bits_read += 1;
+        Ok(bit)
+    }
+
+    fn read_golomb_rice(&mut self) -> Result<u64, CustomError> {
+        let mut quotient = 0;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut remainder = 0;
+        for _ in 0..GOLOMB_P {
+            remainder = (remainder << 1) | self.read_bit()? as u64;
+        }
+        Ok((quotient << GOLOMB_P) | remainder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin_hashes::hex::FromHex;
+
+    use super::*;
+
+    /// Hash y script de la unica salida del bloque genesis de testnet, vector de prueba de BIP158.
+    fn testnet_genesis() -> (Hash32, Vec<u8>) {
+        let block_hash =
+            Hash32::from_str("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943")
+                .unwrap();
+        let script = Vec::from_hex("4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac").unwrap();
+        (block_hash, script)
+    }
+
+    #[test]
+    fn basic_filter_of_testnet_genesis() {
+        let (block_hash, script) = testnet_genesis();
+        let filter = BlockFilter::new(&block_hash, std::slice::from_ref(&script));
+        assert_eq!(filter.data, Vec::from_hex("019dfca8").unwrap());
+        assert_eq!(
+            filter.header(&Hash32::default()),
+            Hash32::from_str("21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750")
+                .unwrap()
+        );
+        assert!(filter.contains(&block_hash, &script).unwrap());
+    }
+
+    #[test]
+    fn filter_contains_its_elements() {
+        let (block_hash, _) = testnet_genesis();
+        let elements: Vec<Vec<u8>> = (0..50_u8).map(|i| vec![i; 25]).collect();
+        let filter = BlockFilter::new(&block_hash, &elements);
+
+        for element in &elements {
+            assert!(filter.contains(&block_hash, element).unwrap());
+        }
+        assert!(!filter.contains(&block_hash, &[100; 25]).unwrap());
+    }
+
+    #[test]
+    fn empty_filter() {
+        let filter = BlockFilter::new(&Hash32::default(), &[]);
+        assert_eq!(filter.data, vec![0]);
+        assert!(!filter.contains(&Hash32::default(), &[1, 2, 3]).unwrap());
+    }
+}
@@ -1,6 +1,9 @@
-use bitcoin_hashes::{sha256d, Hash};
+use crate::{
+    error::CustomError,
+    parser::{BtcEncode, BufferParser},
+};
 
-use crate::{error::CustomError, parser::BufferParser};
+use super::hash32::Hash32;
 
 #[derive(Debug, Clone)]
 ///Esta estructura representa el header de un bloque, el cual contiene la siguiente información:
@@ -12,57 +15,38 @@ use crate::{error::CustomError, parser::BufferParser};
 /// - Nonce: Número aleatorio que se utiliza para generar el hash del bloque
 pub struct BlockHeader {
     pub version: i32,
-    pub prev_block_hash: Vec<u8>,
-    pub merkle_root: Vec<u8>,
+    pub prev_block_hash: Hash32,
+    pub merkle_root: Hash32,
     pub timestamp: u32,
     pub bits: u32,
     pub nonce: u32,
-    pub hash: Vec<u8>,
+    pub hash: Hash32,
     pub broadcasted: bool,
     pub block_downloaded: bool,
 }
 
-impl BlockHeader {
-    ///Esta funcion se encarga de dado un BlockHeader, serializarlo en un vector de bytes
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&self.version.to_le_bytes());
-        buffer.extend(&self.prev_block_hash);
-        buffer.extend(&self.merkle_root);
-        buffer.extend(&self.timestamp.to_le_bytes());
-        buffer.extend(&self.bits.to_le_bytes());
-        buffer.extend(&self.nonce.to_le_bytes());
-
-        buffer
+/// Implementa el trait BtcEncode para el header de un bloque, con sus 80 bytes.
+impl BtcEncode for BlockHeader {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.version.encode(buffer);
+        self.prev_block_hash.encode(buffer);
+        self.merkle_root.encode(buffer);
+        self.timestamp.encode(buffer);
+        self.bits.encode(buffer);
+        self.nonce.encode(buffer);
     }
 
-    pub fn serialize_for_backup(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&self.version.to_le_bytes());
-        buffer.extend(&self.prev_block_hash);
-        buffer.extend(&self.merkle_root);
-        buffer.extend(&self.timestamp.to_le_bytes());
-        buffer.extend(&self.bits.to_le_bytes());
-        buffer.extend(&self.nonce.to_le_bytes());
-        buffer.extend(&self.hash);
-
-        buffer
-    }
-
-    ///Esta funcion se encarga de dado un vector de bytes, parsearlo a un BlockHeader con todos sus campos correspondientes
-    /// Tambien se encarga de validar que el header sea valido, es decir, que cumpla con la proof of work, esto solo lo hace si el parametro validate es true.
-    pub fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let hash = sha256d::Hash::hash(&buffer).to_byte_array().to_vec();
-
-        let mut parser = BufferParser::new(buffer);
-        if parser.len() < 80 {
-            return Err(CustomError::SerializedBufferIsInvalid);
-        }
+    ///Esta funcion se encarga de parsear los 80 bytes de un BlockHeader con todos sus campos correspondientes
+    /// Tambien se encarga de validar que el header sea valido, es decir, que cumpla con la proof of work.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let buffer = parser.extract_buffer(80)?;
+        let hash = Hash32::sha256d(buffer);
 
+        let mut parser = BufferParser::new(buffer.to_vec());
         let block_header = BlockHeader {
             version: parser.extract_i32()?,
-            prev_block_hash: parser.extract_buffer(32)?.to_vec(),
-            merkle_root: parser.extract_buffer(32)?.to_vec(),
+            prev_block_hash: parser.extract_hash()?,
+            merkle_root: parser.extract_hash()?,
             timestamp: parser.extract_u32()?,
             bits: parser.extract_u32()?,
             nonce: parser.extract_u32()?,
@@ -77,38 +61,68 @@ impl BlockHeader {
 
         Ok(block_header)
     }
+}
+
+impl BlockHeader {
+    pub fn serialize_for_backup(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![];
+        buffer.extend(&self.version.to_le_bytes());
+        buffer.extend(self.prev_block_hash.as_bytes());
+        buffer.extend(self.merkle_root.as_bytes());
+        buffer.extend(&self.timestamp.to_le_bytes());
+        buffer.extend(&self.bits.to_le_bytes());
+        buffer.extend(&self.nonce.to_le_bytes());
+        buffer.extend(self.hash.as_bytes());
+
+        buffer
+    }
 
+    /// Parsea un header guardado con serialize_for_backup.
+    /// El hash guardado se compara con el de los campos del header, por lo que funciona como checksum del registro.
     pub fn parse_from_backup(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let mut parser = BufferParser::new(buffer);
-        if parser.len() < 112 {
+        if buffer.len() < 112 {
             return Err(CustomError::SerializedBufferIsInvalid);
         }
+        let hash = Hash32::sha256d(&buffer[..80]);
+        let block_header = Self::parse_backup_record(buffer)?;
 
-        let block_header = BlockHeader {
+        if block_header.hash != hash {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        if !(block_header.validate()) {
+            return Err(CustomError::HeaderInvalidPoW);
+        }
+
+        Ok(block_header)
+    }
+
+    /// Parsea un header guardado con serialize_for_backup sin verificar su hash ni su proof of work,
+    /// para leer headers de un archivo que ya se verifico.
+    pub fn parse_backup_record(buffer: Vec<u8>) -> Result<Self, CustomError> {
+        let mut parser = BufferParser::new(buffer);
+        Ok(BlockHeader {
             version: parser.extract_i32()?,
-            prev_block_hash: parser.extract_buffer(32)?.to_vec(),
-            merkle_root: parser.extract_buffer(32)?.to_vec(),
+            prev_block_hash: parser.extract_hash()?,
+            merkle_root: parser.extract_hash()?,
             timestamp: parser.extract_u32()?,
             bits: parser.extract_u32()?,
             nonce: parser.extract_u32()?,
-            hash: parser.extract_buffer(32)?.to_vec(),
+            hash: parser.extract_hash()?,
             block_downloaded: true,
             broadcasted: true,
-        };
-
-        if !(block_header.validate()) {
-            return Err(CustomError::HeaderInvalidPoW);
-        }
-
-        Ok(block_header)
+        })
     }
 
     ///Esta funcion se encarga de validar la proof of work de un bloque.
     fn validate(&self) -> bool {
-        let hash = self.hash();
+        let hash = self.hash().as_bytes();
         let bits_vec = self.bits.to_be_bytes().to_vec();
 
         let leading_zeros_start = bits_vec[0] as usize;
+        // con otro exponente el target no entra en 32 bytes o no tiene los 3 bytes de la mantisa
+        if !(3..=32).contains(&leading_zeros_start) {
+            return false;
+        }
         let leading_zeros = hash[leading_zeros_start..32].to_vec();
 
         if leading_zeros.iter().any(|zero| *zero != 0_u8) {
@@ -128,29 +142,30 @@ impl BlockHeader {
         false
     }
 
-    /// Esta funcion se encarga de calcular el hash del header de un bloque
-    pub fn hash(&self) -> &Vec<u8> {
-        &self.hash
+    /// Trabajo aproximado que representa el header, 2^256 / target, a partir de sus bits de dificultad.
+    /// Permite comparar cadenas por su trabajo acumulado sin aritmetica de 256 bits.
+    pub fn work(&self) -> u128 {
+        let exponent = (self.bits >> 24) as i32;
+        let mantissa = (self.bits & 0x00ff_ffff) as u128;
+        if mantissa == 0 {
+            return 0;
+        }
+        let shift = (256 - 8 * (exponent - 3)).clamp(0, 127);
+        (1_u128 << shift) / mantissa
     }
 
-    /// Esta funcion se encarga de calcular el hash del header de un bloque y devolverlo como un string
-    pub fn hash_as_string(&self) -> String {
-        hash_as_string(self.hash().clone())
-    }
-}
-
-/// Esta funcion se encarga de convertir un vector de bytes en hexa que forma un hash a un string
-pub fn hash_as_string(hash: Vec<u8>) -> String {
-    let mut filename = String::with_capacity(2 * hash.len());
-    for byte in hash {
-        filename.push_str(format!("{:02X}", byte).as_str());
+    /// Esta funcion se encarga de calcular el hash del header de un bloque
+    pub fn hash(&self) -> &Hash32 {
+        &self.hash
     }
-    filename
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::structs::block_header::BlockHeader;
+    use crate::{
+        parser::BtcEncode,
+        structs::{block_header::BlockHeader, hash32::Hash32},
+    };
 
     #[test]
     fn blockheader_serialize_and_parse() {
@@ -197,21 +212,21 @@ mod tests {
     fn valid_pow_header() {
         let valid_header = BlockHeader {
             version: 2,
-            prev_block_hash: vec![
+            prev_block_hash: Hash32::new([
                 61, 8, 52, 163, 234, 98, 255, 92, 186, 170, 164, 90, 56, 131, 46, 171, 52, 239,
                 104, 223, 166, 65, 183, 217, 36, 6, 53, 63, 0, 0, 0, 0,
-            ],
-            merkle_root: vec![
+            ]),
+            merkle_root: Hash32::new([
                 45, 107, 6, 225, 181, 124, 4, 88, 86, 174, 58, 59, 113, 215, 174, 42, 209, 149,
                 142, 110, 166, 53, 244, 88, 6, 76, 228, 77, 7, 10, 189, 126,
-            ],
+            ]),
             timestamp: 1347149007,
             bits: 476726600,
             nonce: 240236131,
-            hash: vec![
+            hash: Hash32::new([
                 10, 110, 89, 244, 38, 172, 240, 48, 75, 251, 139, 33, 16, 164, 179, 154, 22, 123,
                 120, 81, 209, 213, 111, 183, 7, 9, 162, 49, 0, 0, 0, 0,
-            ],
+            ]),
             block_downloaded: false,
             broadcasted: false,
         };
@@ -224,25 +239,62 @@ mod tests {
     fn invalid_pow_header() {
         let valid_header = BlockHeader {
             version: 2,
-            prev_block_hash: vec![
+            prev_block_hash: Hash32::new([
                 61, 8, 52, 163, 234, 98, 255, 92, 186, 170, 164, 90, 56, 131, 46, 171, 52, 239,
                 104, 223, 166, 65, 183, 217, 36, 6, 53, 63, 0, 0, 0, 0,
-            ],
-            merkle_root: vec![
+            ]),
+            merkle_root: Hash32::new([
                 45, 107, 6, 225, 181, 124, 4, 88, 86, 174, 58, 59, 113, 215, 174, 42, 209, 149,
                 142, 110, 166, 53, 244, 88, 6, 76, 228, 77, 7, 10, 189, 126,
-            ],
+            ]),
             timestamp: 1347149007,
             bits: 476726600,
             nonce: 123123,
-            hash: vec![
+            hash: Hash32::new([
                 116, 18, 66, 212, 76, 145, 158, 131, 46, 212, 244, 136, 96, 84, 11, 220, 121, 121,
                 78, 50, 3, 197, 235, 49, 172, 32, 11, 104, 118, 114, 161, 104,
-            ],
+            ]),
             block_downloaded: false,
             broadcasted: false,
         };
 
         assert!(!valid_header.validate());
     }
+
+    #[test]
+    fn out_of_range_bits_are_invalid() {
+        for bits in [0x2100_ffff, 0xff00_ffff, 0x0200_ffff, 0] {
+            let header = BlockHeader {
+                version: 1,
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
+                timestamp: 0,
+                bits,
+                nonce: 0,
+                hash: Hash32::default(),
+                block_downloaded: false,
+                broadcasted: false,
+            };
+            assert!(!header.validate());
+        }
+    }
+
+    #[test]
+    fn header_work_grows_with_difficulty() {
+        let header_with_bits = |bits| BlockHeader {
+            version: 1,
+            prev_block_hash: Hash32::default(),
+            merkle_root: Hash32::default(),
+            timestamp: 0,
+            bits,
+            nonce: 0,
+            hash: Hash32::default(),
+            block_downloaded: false,
+            broadcasted: false,
+        };
+        let easy = header_with_bits(0x1d00ffff);
+        let hard = header_with_bits(0x1c00ffff);
+        assert_eq!(easy.work(), (1 << 32) + (1 << 16) + 1);
+        assert_eq!(hard.work(), easy.work() * 256);
+    }
 }
@@ -0,0 +1,361 @@
+use crate::{
+    messages::{
+        block::{
+            merkle_root, witness_commitment_script, Block, MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT,
+        },
+        transaction::Transaction,
+    },
+    parser::BtcEncode,
+    utils::bytes_to_hex,
+};
+
+use super::{
+    block_header::BlockHeader, hash32::Hash32, outpoint::OutPoint, tx_input::TransactionInput,
+    tx_output::TransactionOutput,
+};
+
+/// Version de los bloques que se arman, con los bits superiores que indican BIP9 y sin señalizar ningun deployment.
+pub const BLOCK_TEMPLATE_VERSION: i32 = 0x2000_0000;
+/// Peso que se reserva en el bloque para la coinbase, que la arma el minero.
+pub const COINBASE_RESERVED_WEIGHT: usize = 4_000;
+/// Costo en sigops que se reserva en el bloque para la coinbase.
+pub const COINBASE_RESERVED_SIGOPS_COST: usize = 400;
+
+#[derive(Debug, Clone)]
+/// Transaccion de la mempool seleccionada para un bloque, junto a los datos que necesita el minero:
+/// - transaction: Transaccion seleccionada.
+/// - fee: Fee de la transaccion en satoshis.
+/// - sigops_cost: Costo en sigops de la transaccion (BIP141).
+pub struct TemplateTransaction {
+    pub transaction: Transaction,
+    pub fee: u64,
+    pub sigops_cost: usize,
+}
+
+#[derive(Debug, Clone)]
+/// BlockTemplate es un bloque candidato para que lo mine un minero externo (BIP22/BIP23), sin coinbase ni nonce.
+/// Los elementos son:
+/// - version: Version del bloque.
+/// - previous_block_hash: Hash del ultimo bloque de la cadena, sobre el que se arma el nuevo.
+/// - height: Altura del nuevo bloque, que la coinbase debe indicar (BIP34).
+/// - bits: Bits de dificultad que debe tener el nuevo bloque.
+/// - cur_time: Timestamp sugerido para el nuevo bloque.
+/// - min_time: Timestamp minimo del nuevo bloque, el median time past mas uno.
+/// - transactions: Transacciones seleccionadas de la mempool, los padres antes que los hijos.
+/// - coinbase_value: Valor maximo que puede cobrar la coinbase, el subsidio mas los fees de las transacciones.
+pub struct BlockTemplate {
+    pub version: i32,
+    pub previous_block_hash: Hash32,
+    pub height: u32,
+    pub bits: u32,
+    pub cur_time: u32,
+    pub min_time: u32,
+    pub transactions: Vec<TemplateTransaction>,
+    pub coinbase_value: u64,
+}
+
+impl BlockTemplate {
+    /// Devuelve el script de la salida de la coinbase con el commitment de los witness de las transacciones,
+    /// o None si ninguna tiene witness y no es necesario.
+    pub fn witness_commitment(&self) -> Option<Vec<u8>> {
+        if !self
+            .transactions
+            .iter()
+            .any(|template_tx| template_tx.transaction.has_witness())
+        {
+            return None;
+        }
+        let transactions: Vec<Transaction> = self
+            .transactions
+            .iter()
+            .map(|template_tx| template_tx.transaction.clone())
+            .collect();
+        Some(witness_commitment_script(&transactions))
+    }
+
+    /// Arma la coinbase del template, que cobra coinbase_value en el script recibido e indica la altura (BIP34).
+    /// Si alguna transaccion tiene witness se agrega la salida con su commitment, y el nonce en el witness de la coinbase.
+    pub fn coinbase(&self, script_pubkey: Vec<u8>) -> Transaction {
+        let mut script_sig = height_script(self.height);
+        // OP_0 como extra nonce, el script de la coinbase debe tener al menos 2 bytes
+        script_sig.push(0x00);
+
+        let mut outputs = vec![TransactionOutput {
+            value: self.coinbase_value,
+            script_pubkey,
+        }];
+        let mut witness = vec![];
+        if let Some(commitment) = self.witness_commitment() {
+            outputs.push(TransactionOutput {
+                value: 0,
+                script_pubkey: commitment,
+            });
+            witness.push(vec![0; 32]);
+        }
+
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    hash: Hash32::default(),
+                    index: 0xffffffff,
+                },
+                script_sig,
+                sequence: 0xffffffff,
+                witness,
+            }],
+            outputs,
+            lock_time: 0,
+        }
+    }
+
+    /// Arma el bloque del template con la coinbase recibida, con el timestamp cur_time y el nonce en cero.
+    pub fn block(&self, coinbase: Transaction) -> Block {
+        let mut transactions = vec![coinbase];
+        transactions.extend(
+            self.transactions
+                .iter()
+                .map(|template_tx| template_tx.transaction.clone()),
+        );
+        let hashes = transactions.iter().map(Transaction::hash).collect();
+
+        let mut header = BlockHeader {
+            version: self.version,
+            prev_block_hash: self.previous_block_hash,
+            merkle_root: merkle_root(hashes).unwrap_or_default(),
+            timestamp: self.cur_time,
+            bits: self.bits,
+            nonce: 0,
+            hash: Hash32::default(),
+            broadcasted: false,
+            block_downloaded: false,
+        };
+        header.hash = Hash32::sha256d(&header.serialize());
+        Block::new(header, transactions)
+    }
+
+    /// Devuelve el template en JSON, con los campos de la respuesta de getblocktemplate de Bitcoin Core.
+    /// En depends se indican las posiciones, desde 1, de las transacciones del template de las que gasta cada una.
+    pub fn to_json(&self) -> String {
+        let transactions: Vec<String> = self
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, template_tx)| {
+                let depends: Vec<String> = self.transactions[..index]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, parent)| {
+                        let parent_hash = parent.transaction.hash();
+                        template_tx
+                            .transaction
+                            .inputs
+                            .iter()
+                            .any(|input| input.previous_output.hash == parent_hash)
+                    })
+                    .map(|(parent_index, _)| (parent_index + 1).to_string())
+                    .collect();
+                format!(
+                    "{{\"data\":\"{}\",\"txid\":\"{}\",\"hash\":\"{}\",\"depends\":[{}],\"fee\":{},\"sigops\":{},\"weight\":{}}}",
+                    bytes_to_hex(&template_tx.transaction.serialize()),
+                    template_tx.transaction.hash(),
+                    template_tx.transaction.wtxid(),
+                    depends.join(","),
+                    template_tx.fee,
+                    template_tx.sigops_cost,
+                    template_tx.transaction.weight()
+                )
+            })
+            .collect();
+        let witness_commitment = match self.witness_commitment() {
+            Some(script) => format!(
+                ",\"default_witness_commitment\":\"{}\"",
+                bytes_to_hex(&script)
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "{{\"version\":{},\"rules\":[\"segwit\"],\"previousblockhash\":\"{}\",\"transactions\":[{}],\"coinbasevalue\":{},\"target\":\"{}\",\"mintime\":{},\"mutable\":[\"time\",\"transactions\",\"prevblock\"],\"noncerange\":\"00000000ffffffff\",\"sigoplimit\":{},\"weightlimit\":{},\"curtime\":{},\"bits\":\"{:08x}\",\"height\":{}{}}}",
+            self.version,
+            self.previous_block_hash,
+            transactions.join(","),
+            self.coinbase_value,
+            bytes_to_hex(&target_from_bits(self.bits)),
+            self.min_time,
+            MAX_BLOCK_SIGOPS_COST,
+            MAX_BLOCK_WEIGHT,
+            self.cur_time,
+            self.bits,
+            self.height,
+            witness_commitment
+        )
+    }
+}
+
+/// Busca un nonce con el que el hash del header cumpla con su dificultad, recalculando el hash con cada uno.
+/// Solo es viable con la dificultad minima de la regtest. Devuelve false si se agotaron los nonce sin encontrarlo.
+pub fn solve_header(header: &mut BlockHeader) -> bool {
+    let target = target_from_bits(header.bits);
+    for nonce in 0..=u32::MAX {
+        header.nonce = nonce;
+        header.hash = Hash32::sha256d(&header.serialize());
+        // el target esta en big endian, como el hash en el orden de RPC
+        if header.hash.to_rpc_bytes() <= target {
+            return true;
+        }
+    }
+    false
+}
+
+/// Devuelve el script con el que la coinbase indica la altura del bloque (BIP34), como lo lee Block::coinbase_height:
+/// OP_1 a OP_16 para las alturas chicas, y si no los bytes de la altura en little endian precedidos de su cantidad.
+fn height_script(height: u32) -> Vec<u8> {
+    if (1..=16).contains(&height) {
+        return vec![0x50 + height as u8];
+    }
+    let mut bytes = height.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    // el bit mas alto del ultimo byte indicaria un numero negativo
+    if bytes.last().is_some_and(|byte| byte & 0x80 != 0) {
+        bytes.push(0);
+    }
+    let mut script = vec![bytes.len() as u8];
+    script.extend(bytes);
+    script
+}
+
+/// Expande los bits de dificultad compactos al target de 32 bytes que representan, en big endian.
+pub fn target_from_bits(bits: u32) -> [u8; 32] {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = (bits & 0x007f_ffff).to_be_bytes();
+    let mut target = [0; 32];
+    for (offset, byte) in mantissa[1..].iter().enumerate() {
+        // el byte mas significativo de la mantisa ocupa la posicion exponent contando desde el final
+        if let Some(position) = (32 + offset).checked_sub(exponent) {
+            if let Some(target_byte) = target.get_mut(position) {
+                *target_byte = *byte;
+            }
+        }
+    }
+    target
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::{
+        outpoint::OutPoint, tx_input::TransactionInput, tx_output::TransactionOutput,
+    };
+
+    use super::*;
+
+    fn transaction(previous_output: OutPoint) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn target_is_expanded_from_bits() {
+        let target = target_from_bits(0x1d00ffff);
+        assert_eq!(target[..4], [0, 0, 0, 0]);
+        assert_eq!(target[4..6], [0xff, 0xff]);
+        assert!(target[6..].iter().all(|byte| *byte == 0));
+
+        let target = target_from_bits(0x207fffff);
+        assert_eq!(target[..3], [0x7f, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn template_json_lists_dependencies() {
+        let parent = transaction(OutPoint {
+            hash: Hash32::new([1; 32]),
+            index: 0,
+        });
+        let child = transaction(OutPoint {
+            hash: parent.hash(),
+            index: 0,
+        });
+        let template = BlockTemplate {
+            version: BLOCK_TEMPLATE_VERSION,
+            previous_block_hash: Hash32::default(),
+            height: 101,
+            bits: 0x207fffff,
+            cur_time: 1_700_000_000,
+            min_time: 1_699_999_000,
+            transactions: vec![
+                TemplateTransaction {
+                    transaction: parent,
+                    fee: 500,
+                    sigops_cost: 0,
+                },
+                TemplateTransaction {
+                    transaction: child,
+                    fee: 700,
+                    sigops_cost: 0,
+                },
+            ],
+            coinbase_value: 5_000_001_200,
+        };
+
+        assert!(template.witness_commitment().is_none());
+        let json = template.to_json();
+        assert!(json.contains("\"depends\":[],\"fee\":500"));
+        assert!(json.contains("\"depends\":[1],\"fee\":700"));
+        assert!(json.contains("\"coinbasevalue\":5000001200"));
+        assert!(json.contains("\"bits\":\"207fffff\",\"height\":101}"));
+    }
+
+    #[test]
+    fn template_block_is_mined() {
+        let template = BlockTemplate {
+            version: BLOCK_TEMPLATE_VERSION,
+            previous_block_hash: Hash32::new([7; 32]),
+            height: 200,
+            bits: 0x207fffff,
+            cur_time: 1_700_000_000,
+            min_time: 1_699_999_000,
+            transactions: vec![TemplateTransaction {
+                transaction: transaction(OutPoint {
+                    hash: Hash32::new([1; 32]),
+                    index: 0,
+                }),
+                fee: 500,
+                sigops_cost: 0,
+            }],
+            coinbase_value: 5_000_000_500,
+        };
+        let coinbase = template.coinbase(vec![0x51]);
+        assert_eq!(coinbase.outputs[0].value, 5_000_000_500);
+        assert!(coinbase.inputs[0].witness.is_empty());
+
+        let mut block = template.block(coinbase);
+        assert!(solve_header(&mut block.header));
+        assert_eq!(block.coinbase_height(), Some(200));
+        assert_eq!(block.transactions.len(), 2);
+        assert!(block.create_merkle_root().is_ok());
+        assert_eq!(block.header.hash().as_bytes()[31] & 0x80, 0);
+    }
+
+    #[test]
+    fn height_is_encoded_in_coinbase_script() {
+        assert_eq!(height_script(1), vec![0x51]);
+        assert_eq!(height_script(16), vec![0x60]);
+        assert_eq!(height_script(17), vec![1, 17]);
+        assert_eq!(height_script(128), vec![2, 0x80, 0]);
+        assert_eq!(height_script(800_000), vec![3, 0x00, 0x35, 0x0c]);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    error::CustomError,
+    messages::transaction::Transaction,
+    parser::{BtcEncode, BufferParser},
+};
+
+/// Tamaño maximo en bytes de un bloom filter (BIP37).
+const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+/// Cantidad maxima de funciones de hash de un bloom filter (BIP37).
+const MAX_HASH_FUNCS: u32 = 50;
+/// Constante que se suma a la semilla de cada funcion de hash.
+const HASH_SEED_MULTIPLIER: u32 = 0xFBA4C795;
+const LN2: f64 = std::f64::consts::LN_2;
+/// Opcodes de los scripts que agregan datos al stack.
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta estructura representa un bloom filter (BIP37), con el que un cliente SPV le indica a un peer que transacciones le interesan
+/// sin revelar exactamente cuales son, a costa de recibir algunos falsos positivos.
+/// - data: Bits del filtro.
+/// - hash_funcs: Cantidad de funciones de hash que se aplican a cada elemento.
+/// - tweak: Valor aleatorio que se suma a la semilla de las funciones de hash.
+pub struct BloomFilter {
+    pub data: Vec<u8>,
+    pub hash_funcs: u32,
+    pub tweak: u32,
+}
+
+impl BloomFilter {
+    /// Crea un bloom filter vacio dimensionado para elements elementos con una tasa de falsos positivos fp_rate.
+    pub fn new(elements: usize, fp_rate: f64, tweak: u32) -> Self {
+        let elements = elements.max(1);
+        let bits = (-1.0 / (LN2 * LN2) * elements as f64 * fp_rate.ln()) as usize;
+        let size = (bits.min(MAX_BLOOM_FILTER_SIZE * 8) / 8).max(1);
+        let hash_funcs = ((size * 8 / elements) as f64 * LN2) as u32;
+
+        Self {
+            data: vec![0; size],
+            hash_funcs: hash_funcs.clamp(1, MAX_HASH_FUNCS),
+            tweak,
+        }
+    }
+
+    /// Agrega un elemento al filtro.
+    pub fn insert(&mut self, element: &[u8]) {
+        for hash_num in 0..self.hash_funcs {
+            let index = self.bit_index(hash_num, element);
+            self.data[index >> 3] |= 1 << (index & 7);
+        }
+    }
+
+    /// Indica si el elemento puede estar en el filtro, puede devolver falsos positivos.
+    pub fn contains(&self, element: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        (0..self.hash_funcs).all(|hash_num| {
+            let index = self.bit_index(hash_num, element);
+            self.data[index >> 3] & (1 << (index & 7)) != 0
+        })
+    }
+
+    /// Indica si la transaccion coincide con el filtro segun las reglas de BIP37: si esta su hash,
+    /// alguno de los datos de los scripts de sus salidas o de sus entradas, o alguno de los outpoints que gasta.
+    pub fn matches_transaction(&self, transaction: &Transaction) -> bool {
+        if self.contains(transaction.hash().as_bytes()) {
+            return true;
+        }
+        let output_matches = transaction.outputs.iter().any(|output| {
+            script_pushes(&output.script_pubkey)
+                .iter()
+                .any(|data| self.contains(data))
+        });
+        output_matches
+            || transaction.inputs.iter().any(|input| {
+                self.contains(&input.previous_output.serialize())
+                    || script_pushes(&input.script_sig)
+                        .iter()
+                        .any(|data| self.contains(data))
+            })
+    }
+
+    fn bit_index(&self, hash_num: u32, element: &[u8]) -> usize {
+        let seed = hash_num
+            .wrapping_mul(HASH_SEED_MULTIPLIER)
+            .wrapping_add(self.tweak);
+        murmur3(element, seed) as usize % (self.data.len() * 8)
+    }
+}
+
+/// Implementa el trait BtcEncode para el bloom filter.
+impl BtcEncode for BloomFilter {
+    /// Serializa el filtro como en el mensaje filterload, sin los flags.
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.data.encode(buffer);
+        self.hash_funcs.encode(buffer);
+        self.tweak.encode(buffer);
+    }
+
+    /// Parsea un filtro serializado como en el mensaje filterload, sin los flags.
+    /// Devuelve CustomError si el filtro supera los tamaños maximos de BIP37.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let size = parser.extract_varint()? as usize;
+        if size > MAX_BLOOM_FILTER_SIZE {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        let data = parser.extract_buffer(size)?.to_vec();
+        let hash_funcs = parser.extract_u32()?;
+        if hash_funcs > MAX_HASH_FUNCS {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        let tweak = parser.extract_u32()?;
+        Ok(Self {
+            data,
+            hash_funcs,
+            tweak,
+        })
+    }
+}
+
+/// Devuelve los datos que agrega al stack el script, ignorando el resto de los opcodes.
+/// Si el script esta mal formado se devuelven los datos leidos hasta ese punto.
+fn script_pushes(script: &[u8]) -> Vec<&[u8]> {
+    let mut pushes = vec![];
+    let mut position = 0;
+    while let Some(opcode) = script.get(position) {
+        position += 1;
+        let size = match *opcode {
+            1..=0x4b => *opcode as usize,
+            OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4 => {
+                let size_len = match *opcode {
+                    OP_PUSHDATA1 => 1,
+                    OP_PUSHDATA2 => 2,
+                    _ => 4,
+                };
+                let Some(bytes) = script.get(position..position + size_len) else {
+                    break;
+                };
+                position += size_len;
+                bytes
+                    .iter()
+                    .rev()
+                    .fold(0, |length, byte| (length << 8) | *byte as usize)
+            }
+            _ => continue,
+        };
+        let Some(data) = script.get(position..position + size) else {
+            break;
+        };
+        pushes.push(data);
+        position += size;
+    }
+    pushes
+}
+
+/// Funcion de hash MurmurHash3 (x86, 32 bits) que usan los bloom filters de BIP37.
+fn murmur3(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mut h1 = seed;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+
+    for chunk in chunks {
+        let k1 = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        h1 ^= k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    if !tail.is_empty() {
+        let mut k1 = 0_u32;
+        for (i, byte) in tail.iter().enumerate() {
+            k1 ^= (*byte as u32) << (8 * i);
+        }
+        h1 ^= k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85ebca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2ae35);
+    h1 ^= h1 >> 16;
+    h1
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_hashes::hex::FromHex;
+
+    use crate::structs::{
+        hash32::Hash32, outpoint::OutPoint, tx_input::TransactionInput,
+        tx_output::TransactionOutput,
+    };
+
+    use super::*;
+
+    fn hex(value: &str) -> Vec<u8> {
+        Vec::from_hex(value).unwrap()
+    }
+
+    #[test]
+    fn murmur3_test_vectors() {
+        assert_eq!(murmur3(&[], 0), 0);
+        assert_eq!(murmur3(&[], 0xFBA4C795), 0x6a396f08);
+        assert_eq!(murmur3(&hex("00"), 0), 0x514e28b7);
+        assert_eq!(murmur3(&hex("00"), 0xFBA4C795), 0xea3f0b17);
+        assert_eq!(murmur3(&hex("ff"), 0), 0xfd6cf10d);
+        assert_eq!(murmur3(&hex("0011"), 0), 0x16c6b7ab);
+        assert_eq!(murmur3(&hex("001122"), 0), 0x8eb51c3d);
+        assert_eq!(murmur3(&hex("00112233"), 0), 0xb4471bf8);
+        assert_eq!(murmur3(&hex("0011223344"), 0), 0xe2301fa8);
+    }
+
+    #[test]
+    fn bloom_filter_insert_and_serialize() {
+        let mut filter = BloomFilter::new(3, 0.01, 0);
+        filter.insert(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        assert!(filter.contains(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+        assert!(!filter.contains(&hex("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+
+        filter.insert(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+        filter.insert(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+        assert!(filter.contains(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+        assert_eq!(filter.serialize(), hex("03614e9b0500000000000000"));
+
+        let mut parser = BufferParser::new(filter.serialize());
+        assert_eq!(BloomFilter::decode(&mut parser).unwrap(), filter);
+    }
+
+    #[test]
+    fn bloom_filter_with_tweak() {
+        let mut filter = BloomFilter::new(3, 0.01, 2147483649);
+        filter.insert(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+        filter.insert(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+        filter.insert(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+        assert_eq!(filter.serialize(), hex("03ce42990500000001000080"));
+    }
+
+    #[test]
+    fn script_pushes_extracts_data() {
+        let pubkey_hash = hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8");
+        let mut script = hex("76a914");
+        script.extend(&pubkey_hash);
+        script.extend(hex("88ac"));
+        assert_eq!(script_pushes(&script), vec![pubkey_hash.as_slice()]);
+
+        assert_eq!(
+            script_pushes(&hex("4c0201024d01000300")),
+            vec![&[1, 2][..], &[3]]
+        );
+        assert!(script_pushes(&hex("4c05010203")).is_empty());
+    }
+
+    #[test]
+    fn bloom_filter_matches_transaction() {
+        let pubkey_hash = hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8");
+        let mut script_pubkey = hex("76a914");
+        script_pubkey.extend(&pubkey_hash);
+        script_pubkey.extend(hex("88ac"));
+        let previous_output = OutPoint {
+            hash: Hash32::new([7; 32]),
+            index: 1,
+        };
+        let transaction = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: previous_output.clone(),
+                script_sig: vec![],
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1000,
+                script_pubkey,
+            }],
+            lock_time: 0,
+        };
+
+        let mut filter = BloomFilter::new(1, 0.0001, 0);
+        assert!(!filter.matches_transaction(&transaction));
+        filter.insert(&pubkey_hash);
+        assert!(filter.matches_transaction(&transaction));
+
+        let mut filter = BloomFilter::new(1, 0.0001, 0);
+        filter.insert(&previous_output.serialize());
+        assert!(filter.matches_transaction(&transaction));
+
+        let mut filter = BloomFilter::new(1, 0.0001, 0);
+        filter.insert(transaction.hash().as_bytes());
+        assert!(filter.matches_transaction(&transaction));
+    }
+}
@@ -0,0 +1,90 @@
+use super::outpoint::OutPoint;
+
+#[derive(Debug, Clone, PartialEq)]
+/// ConsolidationPreview resume una transaccion que junta los UTXO chicos de la wallet activa en una sola salida,
+/// para que el usuario la confirme antes de enviarla.
+/// Los elementos son:
+/// - inputs: Salidas que se gastan y su valor en satoshis, de menor a mayor valor.
+/// - address: Direccion de la wallet activa, que recibe la salida.
+/// - value: Valor en satoshis de la salida, lo que suman las entradas menos el fee.
+/// - fee: Fee en satoshis que se paga a los mineros.
+/// - vsize: Tamaño virtual de la transaccion, que crece con cada input.
+pub struct ConsolidationPreview {
+    pub inputs: Vec<(OutPoint, u64)>,
+    pub address: String,
+    pub value: u64,
+    pub fee: u64,
+    pub vsize: usize,
+}
+
+impl ConsolidationPreview {
+    /// Devuelve lo que suman las salidas que se gastan.
+    pub fn input_value(&self) -> u64 {
+        self.inputs.iter().map(|(_, value)| value).sum()
+    }
+
+    /// Devuelve el fee rate en satoshis por byte virtual.
+    pub fn fee_rate(&self) -> f64 {
+        match self.vsize {
+            0 => 0.0,
+            vsize => self.fee as f64 / vsize as f64,
+        }
+    }
+
+    /// Describe la transaccion en lineas, con los montos formateados con la funcion recibida.
+    pub fn describe(&self, format_amount: impl Fn(i64) -> String) -> String {
+        let mut lines = vec![format!(
+            "Consolidate {} outputs worth {}:",
+            self.inputs.len(),
+            format_amount(self.input_value() as i64)
+        )];
+        lines.extend(self.inputs.iter().map(|(out_point, value)| {
+            format!(
+                "  {}:{} {}",
+                out_point.hash,
+                out_point.index,
+                format_amount(*value as i64)
+            )
+        }));
+        lines.push(format!(
+            "Receive {} at {}",
+            format_amount(self.value as i64),
+            self.address
+        ));
+        lines.push(format!(
+            "Fee: {} ({:.1} sat/vB, {} vB)",
+            format_amount(self.fee as i64),
+            self.fee_rate(),
+            self.vsize
+        ));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::hash32::Hash32;
+
+    use super::*;
+
+    #[test]
+    fn consolidation_preview_description() {
+        let hash = Hash32::default();
+        let preview = ConsolidationPreview {
+            inputs: vec![
+                (OutPoint { hash, index: 0 }, 1000),
+                (OutPoint { hash, index: 3 }, 2000),
+            ],
+            address: String::from("address"),
+            value: 2700,
+            fee: 300,
+            vsize: 200,
+        };
+        assert_eq!(preview.input_value(), 3000);
+        assert_eq!(preview.fee_rate(), 1.5);
+        assert_eq!(
+            preview.describe(|value| format!("{value} sats")),
+            format!("Consolidate 2 outputs worth 3000 sats:\n  {hash}:0 1000 sats\n  {hash}:3 2000 sats\nReceive 2700 sats at address\nFee: 300 sats (1.5 sat/vB, 200 vB)")
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fmt, str::FromStr};
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use crate::{
+    error::CustomError,
+    utils::{bytes_to_hex, hex_to_bytes},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+/// Hash32 representa un hash de 32 bytes (bloques, transacciones, merkle roots) en el orden en que viaja por la red.
+/// Se muestra y se parsea en hexa con los bytes invertidos, como lo muestran los exploradores.
+pub struct Hash32(pub [u8; 32]);
+
+impl Hash32 {
+    /// Crea un Hash32 a partir de sus 32 bytes.
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Crea un Hash32 a partir de un slice.
+    /// Devuelve CustomError si el slice no tiene 32 bytes.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, CustomError> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| CustomError::SerializedBufferIsInvalid)?;
+        Ok(Self(bytes))
+    }
+
+    /// Calcula el doble sha256 del buffer recibido.
+    pub fn sha256d(buffer: &[u8]) -> Self {
+        Self(sha256d::Hash::hash(buffer).to_byte_array())
+    }
+
+    /// Devuelve los bytes del hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Devuelve los bytes del hash en un vector.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    /// Devuelve los bytes del hash invertidos, en el orden en que se muestra en RPC y en los exploradores.
+    pub fn to_rpc_bytes(&self) -> [u8; 32] {
+        let mut bytes = self.0;
+        bytes.reverse();
+        bytes
+    }
+
+    /// Crea un Hash32 a partir de sus bytes en el orden de RPC, invirtiendolos al orden de la red.
+    pub fn from_rpc_bytes(mut bytes: [u8; 32]) -> Self {
+        bytes.reverse();
+        Self(bytes)
+    }
+}
+
+impl From<[u8; 32]> for Hash32 {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Hash32 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Hash32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bytes_to_hex(&self.to_rpc_bytes()))
+    }
+}
+
+impl fmt::Debug for Hash32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash32({})", self)
+    }
+}
+
+impl FromStr for Hash32 {
+    type Err = CustomError;
+
+    /// Parsea un hash en hexa con los bytes invertidos, como lo devuelve Display.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let bytes = hex_to_bytes(value).map_err(|_| CustomError::SerializedBufferIsInvalid)?;
+        Ok(Self::from_rpc_bytes(Self::from_slice(&bytes)?.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash32_display_and_from_str() {
+        let mut bytes = [0; 32];
+        bytes[0] = 0xab;
+        bytes[31] = 0x01;
+        let hash = Hash32::new(bytes);
+
+        let string = hash.to_string();
+        assert_eq!(
+            string,
+            "01000000000000000000000000000000000000000000000000000000000000ab"
+        );
+        assert_eq!(Hash32::from_str(&string).unwrap(), hash);
+        assert_eq!(Hash32::from_str(&string.to_uppercase()).unwrap(), hash);
+        assert_eq!(hash.to_rpc_bytes()[0], 0x01);
+        assert_eq!(Hash32::from_rpc_bytes(hash.to_rpc_bytes()), hash);
+    }
+
+    #[test]
+    fn hash32_invalid_values() {
+        assert!(Hash32::from_slice(&[0; 31]).is_err());
+        assert!(Hash32::from_str("abcd").is_err());
+        assert!(Hash32::from_str(&"zz".repeat(32)).is_err());
+    }
+}
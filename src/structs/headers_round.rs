@@ -0,0 +1,202 @@
+use std::net::SocketAddrV6;
+
+use crate::messages::headers::Headers;
+
+use super::block_header::BlockHeader;
+
+/// Cantidad de peers a los que se les pide en simultaneo cada lote de headers, para compararlos entre si.
+pub const HEADERS_CROSS_CHECK_PEERS: usize = 3;
+/// Cantidad maxima de headers al final de un lote que pueden diferir del elegido sin que el peer se señale,
+/// ya que dos peers honestos pueden tener distintos bloques en la punta de la cadena hasta que se resuelve la carrera.
+pub const MAX_TIP_DIVERGENCE: usize = 6;
+
+#[derive(Debug)]
+/// HeadersRound es un pedido de un mismo lote de headers hecho en simultaneo a varios peers.
+/// Una vez que responden todos se elige el lote con mas trabajo acumulado, y ante un empate el que respaldan mas peers.
+/// Los peers cuyo lote diverge del elegido se señalan, lo que evita que un unico peer mentiroso desvie la IBD.
+/// Un lote que es prefijo del elegido no diverge, el peer solo esta atrasado, y tampoco uno que solo difiere
+/// en sus ultimos MAX_TIP_DIVERGENCE headers, ya que el peer puede estar en otra rama de la punta de la cadena.
+/// - pending: Peers a los que se les pidio el lote y todavia no respondieron.
+/// - responses: Lotes recibidos, junto al peer que lo envio.
+pub struct HeadersRound {
+    pending: Vec<SocketAddrV6>,
+    responses: Vec<(SocketAddrV6, Headers)>,
+}
+
+impl HeadersRound {
+    /// Crea la ronda con los peers a los que se les pidio el lote.
+    pub fn new(peers: Vec<SocketAddrV6>) -> Self {
+        Self {
+            pending: peers,
+            responses: vec![],
+        }
+    }
+
+    /// Indica si se espera la respuesta del peer.
+    pub fn is_pending(&self, address: SocketAddrV6) -> bool {
+        self.pending.contains(&address)
+    }
+
+    /// Guarda el lote enviado por el peer. Devuelve true si ya respondieron todos los peers.
+    pub fn add_response(&mut self, address: SocketAddrV6, headers: Headers) -> bool {
+        if self.is_pending(address) {
+            self.pending.retain(|peer| *peer != address);
+            self.responses.push((address, headers));
+        }
+        self.pending.is_empty()
+    }
+
+    /// Deja de esperar a un peer que no va a responder (ej: se desconecto).
+    /// Devuelve true si ya respondieron todos los peers restantes.
+    pub fn remove_peer(&mut self, address: SocketAddrV6) -> bool {
+        self.pending.retain(|peer| *peer != address);
+        self.pending.is_empty()
+    }
+
+    /// Elige el lote de la ronda y devuelve los peers cuyo lote diverge de el a mas de MAX_TIP_DIVERGENCE headers de su final.
+    /// Devuelve None si ningun peer respondio.
+    pub fn resolve(self) -> Option<(Headers, Vec<SocketAddrV6>)> {
+        let mut best: Option<(&Headers, (u128, usize))> = None;
+        for (_, headers) in &self.responses {
+            let work = headers.headers.iter().map(BlockHeader::work).sum::<u128>();
+            let supporters = self
+                .responses
+                .iter()
+                .filter(|(_, other)| is_prefix(other, headers))
+                .count();
+            if best.is_none_or(|(_, score)| (work, supporters) > score) {
+                best = Some((headers, (work, supporters)));
+            }
+        }
+        let (best, _) = best?;
+
+        let divergent = self
+            .responses
+            .iter()
+            .filter(|(_, headers)| {
+                headers.headers.len() - common_prefix_len(headers, best) > MAX_TIP_DIVERGENCE
+            })
+            .map(|(address, _)| *address)
+            .collect();
+        Some((best.clone(), divergent))
+    }
+}
+
+/// Indica si los headers de prefix coinciden con los primeros headers de headers.
+fn is_prefix(prefix: &Headers, headers: &Headers) -> bool {
+    prefix.headers.len() <= headers.headers.len()
+        && prefix
+            .headers
+            .iter()
+            .zip(&headers.headers)
+            .all(|(a, b)| a.hash() == b.hash())
+}
+
+/// Devuelve la cantidad de headers iniciales en los que coinciden los dos lotes.
+fn common_prefix_len(headers: &Headers, other: &Headers) -> usize {
+    headers
+        .headers
+        .iter()
+        .zip(&other.headers)
+        .take_while(|(a, b)| a.hash() == b.hash())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use crate::structs::hash32::Hash32;
+
+    use super::*;
+
+    fn address(port: u16) -> SocketAddrV6 {
+        SocketAddrV6::new(Ipv6Addr::LOCALHOST, port, 0, 0)
+    }
+
+    /// Crea una cadena de headers con los bits recibidos, distinguida por el seed de sus hashes.
+    fn chain(seed: u8, bits: &[u32]) -> Headers {
+        let headers = bits
+            .iter()
+            .enumerate()
+            .map(|(i, bits)| BlockHeader {
+                version: 1,
+                prev_block_hash: Hash32::default(),
+                merkle_root: Hash32::default(),
+                timestamp: 0,
+                bits: *bits,
+                nonce: 0,
+                hash: Hash32::new([seed.wrapping_add(i as u8); 32]),
+                block_downloaded: false,
+                broadcasted: false,
+            })
+            .collect();
+        Headers { headers }
+    }
+
+    #[test]
+    fn majority_wins_among_equal_work() {
+        let honest = chain(1, &[0x1d00ffff; 10]);
+        let lying = chain(100, &[0x1d00ffff; 10]);
+        let mut round = HeadersRound::new(vec![address(1), address(2), address(3)]);
+
+        assert!(!round.add_response(address(1), lying));
+        assert!(!round.add_response(address(2), honest.clone()));
+        assert!(round.add_response(address(3), honest.clone()));
+
+        let (headers, divergent) = round.resolve().unwrap();
+        assert_eq!(headers.headers[9].hash(), honest.headers[9].hash());
+        assert_eq!(divergent, vec![address(1)]);
+    }
+
+    #[test]
+    fn most_work_chain_wins_and_lagging_peers_are_not_divergent() {
+        let weak = chain(100, &[0x1d00ffff; 10]);
+        let strong = chain(1, &[0x1c00ffff; 10]);
+        let lagging = chain(1, &[0x1c00ffff; 8]);
+        let mut round = HeadersRound::new(vec![address(1), address(2), address(3), address(4)]);
+
+        round.add_response(address(1), weak.clone());
+        round.add_response(address(2), weak);
+        round.add_response(address(3), lagging);
+        assert!(round.is_pending(address(4)));
+        assert!(round.add_response(address(4), strong.clone()));
+
+        let (headers, divergent) = round.resolve().unwrap();
+        assert_eq!(headers.headers.len(), strong.headers.len());
+        assert_eq!(headers.headers[0].hash(), strong.headers[0].hash());
+        assert_eq!(divergent, vec![address(1), address(2)]);
+    }
+
+    #[test]
+    fn tip_races_are_not_divergent() {
+        let mut best = chain(1, &[0x1d00ffff; 20]);
+        best.headers[19].bits = 0x1c00ffff;
+        let mut other_tip = best.clone();
+        other_tip.headers[19].bits = 0x1d00ffff;
+        other_tip.headers[19].hash = Hash32::new([200; 32]);
+        let mut deep_fork = best.clone();
+        for header in deep_fork.headers.iter_mut().skip(10) {
+            header.hash = Hash32::new([header.hash.as_bytes()[0].wrapping_add(100); 32]);
+        }
+        deep_fork.headers[19].bits = 0x1d00ffff;
+        let mut round = HeadersRound::new(vec![address(1), address(2), address(3)]);
+
+        round.add_response(address(1), best.clone());
+        round.add_response(address(2), other_tip);
+        assert!(round.add_response(address(3), deep_fork));
+
+        let (headers, divergent) = round.resolve().unwrap();
+        assert_eq!(headers.headers[19].hash(), best.headers[19].hash());
+        assert_eq!(divergent, vec![address(3)]);
+    }
+
+    #[test]
+    fn round_without_responses() {
+        let mut round = HeadersRound::new(vec![address(1), address(2)]);
+        assert!(!round.add_response(address(3), chain(1, &[0x1d00ffff])));
+        assert!(!round.remove_peer(address(1)));
+        assert!(round.remove_peer(address(2)));
+        assert!(round.resolve().is_none());
+    }
+}
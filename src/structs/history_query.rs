@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use crate::error::CustomError;
+
+use super::movement::Movement;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Filtro por tipo de los movimientos del historial:
+/// - All: Todos los movimientos.
+/// - Sent: Movimientos que envian fondos de la wallet.
+/// - Received: Movimientos que reciben fondos en la wallet.
+/// - Pending: Movimientos de transacciones que todavia no estan en un bloque.
+pub enum MovementFilter {
+    #[default]
+    All,
+    Sent,
+    Received,
+    Pending,
+}
+
+impl MovementFilter {
+    /// Indica si el movimiento pasa el filtro.
+    pub fn matches(&self, movement: &Movement) -> bool {
+        match self {
+            Self::All => true,
+            Self::Sent => movement.value < 0,
+            Self::Received => movement.value > 0,
+            Self::Pending => movement.block_hash.is_none() && !movement.conflicted,
+        }
+    }
+}
+
+impl FromStr for MovementFilter {
+    type Err = CustomError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "all" => Ok(Self::All),
+            "sent" => Ok(Self::Sent),
+            "received" => Ok(Self::Received),
+            "pending" => Ok(Self::Pending),
+            _ => Err(CustomError::InvalidValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Consulta paginada del historial de una wallet, del movimiento mas reciente al mas viejo.
+/// Los elementos son:
+/// - search: Texto a buscar en el hash de la transaccion, sin distinguir mayusculas. Si esta vacio no se filtra.
+/// - amount: Monto en satoshis a buscar, el valor absoluto del movimiento debe coincidir.
+///   Si hay search y amount, el movimiento debe coincidir con alguno de los dos.
+/// - filter: Filtro por tipo de movimiento.
+/// - from: Timestamp minimo del bloque del movimiento, inclusive.
+/// - to: Timestamp maximo del bloque del movimiento, inclusive.
+///   Si hay un rango de fechas se excluyen los movimientos sin bloque.
+/// - offset: Cantidad de movimientos que cumplen la consulta a saltear.
+/// - limit: Cantidad maxima de movimientos a devolver.
+pub struct HistoryQuery {
+    pub search: String,
+    pub amount: Option<u64>,
+    pub filter: MovementFilter,
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    /// Indica si el movimiento, cuyo bloque tiene el timestamp recibido, cumple la consulta.
+    pub fn matches(&self, movement: &Movement, timestamp: Option<u32>) -> bool {
+        self.filter.matches(movement)
+            && self.matches_search(movement)
+            && self.matches_dates(timestamp)
+    }
+
+    fn matches_search(&self, movement: &Movement) -> bool {
+        let search = self.search.trim().to_lowercase();
+        if search.is_empty() && self.amount.is_none() {
+            return true;
+        }
+        let matches_hash = !search.is_empty() && movement.tx_hash.to_string().contains(&search);
+        let matches_amount = self.amount == Some(movement.value.unsigned_abs());
+        matches_hash || matches_amount
+    }
+
+    fn matches_dates(&self, timestamp: Option<u32>) -> bool {
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Some(timestamp) = timestamp else {
+            return false;
+        };
+        self.from.is_none_or(|from| timestamp >= from)
+            && self.to.is_none_or(|to| timestamp <= to)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Pagina del historial devuelta por una HistoryQuery:
+/// - movements: Movimientos de la pagina junto al timestamp de su bloque, None si no estan en un bloque.
+/// - total: Cantidad total de movimientos que cumplen la consulta, sin paginar.
+pub struct HistoryPage {
+    pub movements: Vec<(Movement, Option<u32>)>,
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::hash32::Hash32;
+
+    use super::*;
+
+    fn movement(value: i64, block_hash: Option<Hash32>) -> Movement {
+        Movement {
+            tx_hash: Hash32::new([0xab; 32]),
+            value,
+            block_hash,
+            conflicted: false,
+        }
+    }
+
+    #[test]
+    fn filter_by_movement_type() {
+        let received = movement(500, Some(Hash32::default()));
+        let sent = movement(-500, Some(Hash32::default()));
+        let pending = movement(-500, None);
+
+        assert!(MovementFilter::Received.matches(&received));
+        assert!(!MovementFilter::Received.matches(&sent));
+        assert!(MovementFilter::Sent.matches(&sent));
+        assert!(MovementFilter::Pending.matches(&pending));
+        assert!(!MovementFilter::Pending.matches(&sent));
+        assert!(MovementFilter::All.matches(&pending));
+    }
+
+    #[test]
+    fn search_by_hash_or_amount() {
+        let received = movement(500, Some(Hash32::default()));
+        let mut query = HistoryQuery {
+            search: String::from("ABAB"),
+            ..Default::default()
+        };
+        assert!(query.matches(&received, None));
+
+        query.search = String::from("cd");
+        assert!(!query.matches(&received, None));
+
+        query.amount = Some(500);
+        assert!(query.matches(&received, None));
+        assert!(query.matches(&movement(-500, None), None));
+    }
+
+    #[test]
+    fn filter_by_date_range() {
+        let received = movement(500, Some(Hash32::default()));
+        let query = HistoryQuery {
+            from: Some(1_000),
+            to: Some(2_000),
+            ..Default::default()
+        };
+        assert!(query.matches(&received, Some(1_000)));
+        assert!(query.matches(&received, Some(2_000)));
+        assert!(!query.matches(&received, Some(2_001)));
+        assert!(!query.matches(&movement(500, None), None));
+    }
+}
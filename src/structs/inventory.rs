@@ -1,4 +1,9 @@
-use crate::{error::CustomError, parser::BufferParser};
+use crate::{
+    error::CustomError,
+    parser::{BtcEncode, BufferParser},
+};
+
+use super::hash32::Hash32;
 
 #[derive(Debug, Clone, PartialEq)]
 /// Este enum contiene los tipos de inventarios que se pueden enviar:
@@ -6,14 +11,16 @@ use crate::{error::CustomError, parser::BufferParser};
 /// - Block = 2
 /// - FilteredBlock = 3
 /// - CompactBlock = 4
-/// - WitnessTx = 5
-/// - WitnessBlock = 6
-/// - FilteredWitnessBlock = 7
+/// - Wtx = 5, transaccion identificada por su wtxid (BIP339)
+/// - WitnessTx = 0x40000001
+/// - WitnessBlock = 0x40000002
+/// - FilteredWitnessBlock = 0x40000003
 pub enum InventoryType {
     Tx,
     Block,
     FilteredBlock,
     CompactBlock,
+    Wtx,
     WitnessTx,
     WitnessBlock,
     FilteredWitnessBlock,
@@ -23,50 +30,47 @@ pub enum InventoryType {
 ///Esta es la estructura de un inventario, la cual contiene un tipo de inventario y un hash del inventario en si.
 pub struct Inventory {
     pub inventory_type: InventoryType,
-    pub hash: Vec<u8>,
+    pub hash: Hash32,
 }
 
 impl Inventory {
     /// Esta funcion se encarga de crear un nuevo inventario, dado un tipo de inventario y un hash recibidos como parametros.
-    pub fn new(inventory_type: InventoryType, hash: Vec<u8>) -> Self {
+    pub fn new(inventory_type: InventoryType, hash: Hash32) -> Self {
         Self {
             inventory_type,
             hash,
         }
     }
+}
 
-    /// Esta funcion se encarga de serializar un inventario en un vector de bytes.
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
+/// Implementa el trait BtcEncode para un inventario, con su tipo y su hash.
+impl BtcEncode for Inventory {
+    fn encode(&self, buffer: &mut Vec<u8>) {
         let inventory_type = match self.inventory_type {
             InventoryType::Tx => 1_u32,
             InventoryType::Block => 2_u32,
             InventoryType::FilteredBlock => 3_u32,
             InventoryType::CompactBlock => 4_u32,
+            InventoryType::Wtx => 5_u32,
             InventoryType::WitnessTx => 0x40000001,
             InventoryType::WitnessBlock => 0x40000002,
             InventoryType::FilteredWitnessBlock => 0x40000003,
         };
-        buffer.extend(inventory_type.to_le_bytes());
-        buffer.extend(&self.hash);
-        buffer
+        inventory_type.encode(buffer);
+        self.hash.encode(buffer);
     }
 
-    /// Esta funcion se encarga de parsear un vector de bytes a un inventario.
+    /// Esta funcion se encarga de parsear un inventario.
     /// Devuelve CustomError si:
-    /// - El vector de bytes no tiene 36 bytes de longitud
+    /// - Quedan menos de 36 bytes para leer
     /// - El tipo de inventario no es valido
-    /// - El hash no tiene 32 bytes de longitud
-    pub fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let mut parser = BufferParser::new(buffer);
-        if parser.len() != 36 {
-            return Err(CustomError::SerializedBufferIsInvalid);
-        }
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         let inventory_type = match parser.extract_u32()? {
             1_u32 => InventoryType::Tx,
             2_u32 => InventoryType::Block,
             3_u32 => InventoryType::FilteredBlock,
             4_u32 => InventoryType::CompactBlock,
+            5_u32 => InventoryType::Wtx,
             0x40000001 => InventoryType::WitnessTx,
             0x40000002 => InventoryType::WitnessBlock,
             0x40000003 => InventoryType::FilteredWitnessBlock,
@@ -76,24 +80,37 @@ impl Inventory {
         };
         Ok(Self {
             inventory_type,
-            hash: parser.extract_buffer(32)?.to_vec(),
+            hash: parser.extract_hash()?,
         })
     }
+
+    /// Parsea un inventario de un vector de bytes, que debe tener exactamente 36 bytes.
+    fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
+        if buffer.len() != 36 {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        Self::decode(&mut BufferParser::new(buffer))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::structs::inventory::{Inventory, InventoryType};
+    use crate::{
+        parser::BtcEncode,
+        structs::{
+            hash32::Hash32,
+            inventory::{Inventory, InventoryType},
+        },
+    };
 
     #[test]
     fn inventory_block_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::Block,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -117,11 +134,10 @@ mod tests {
     fn inventory_tx_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::Tx,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -132,11 +148,10 @@ mod tests {
     fn inventory_filtered_block_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::FilteredBlock,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -147,26 +162,33 @@ mod tests {
     fn inventory_compact_block_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::CompactBlock,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
         assert_eq!(inventory, parsed_inventory);
     }
 
+    #[test]
+    fn inventory_wtx_serialize_and_parse() {
+        let inventory = Inventory::new(InventoryType::Wtx, Hash32::new([7; 32]));
+        let buffer = inventory.serialize();
+        assert_eq!(buffer[..4], [5, 0, 0, 0]);
+        let parsed_inventory = Inventory::parse(buffer).unwrap();
+        assert_eq!(inventory, parsed_inventory);
+    }
+
     #[test]
     fn inventory_witness_tx_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::WitnessTx,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -177,11 +199,10 @@ mod tests {
     fn inventory_witness_block_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::WitnessBlock,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -192,11 +213,10 @@ mod tests {
     fn inventory_filtered_witness_block_serialize_and_parse() {
         let inventory = Inventory::new(
             InventoryType::FilteredWitnessBlock,
-            [
+            Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
                 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
-            ]
-            .to_vec(),
+            ]),
         );
         let buffer = inventory.serialize();
         let parsed_inventory = Inventory::parse(buffer).unwrap();
@@ -207,14 +227,13 @@ mod tests {
     fn inventory_invalid_buffer() {
         let inventory = Inventory {
             inventory_type: InventoryType::Block,
-            hash: [
+            hash: Hash32::new([
                 220, 9, 210, 68, 121, 44, 33, 165, 243, 235, 28, 125, 43, 136, 29, 116, 190, 43,
-                124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220, 100, 200, 129,
-                233, 45, 56, 82, 56, 124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 46,
-            ]
-            .to_vec(),
+                124, 200, 30, 144, 40, 190, 229, 44, 93, 83, 110, 112, 225, 220,
+            ]),
         };
-        let buffer = inventory.serialize();
+        let mut buffer = inventory.serialize();
+        buffer.extend([100, 200, 129, 233, 45, 56, 82, 56]);
         let parsed_inventory = Inventory::parse(buffer);
         assert!(parsed_inventory.is_err());
     }
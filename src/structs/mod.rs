@@ -1,6 +1,22 @@
+pub mod acceleration_preview;
+pub mod amount_unit;
+pub mod block_filter;
 pub mod block_header;
+pub mod block_template;
+pub mod bloom_filter;
+pub mod consolidation_preview;
+pub mod hash32;
+pub mod headers_round;
+pub mod history_query;
 pub mod inventory;
 pub mod movement;
+pub mod net_address;
+pub mod node_status;
 pub mod outpoint;
+pub mod peer_info;
+pub mod script;
+pub mod sync_progress;
+pub mod transaction_details;
+pub mod transfer_summary;
 pub mod tx_input;
 pub mod tx_output;
@@ -1,32 +1,40 @@
 use crate::{error::CustomError, parser::BufferParser};
 
+use super::hash32::Hash32;
+
 #[derive(Clone, Debug)]
 
 /// Esta estructura representa un movimiento de fondos, la cual contiene:
 /// - tx_hash: Hash de la transaccion
 /// - value: Valor de la transaccion
 /// - block_hash: Hash del bloque en el que se encuentra la transaccion, en caso de una transaccion pendiente, no validada, este campo es None
+/// - conflicted: Indica si la transaccion quedo invalidada porque un bloque incluyo otra que gasta sus mismas salidas
 pub struct Movement {
-    pub tx_hash: Vec<u8>,
+    pub tx_hash: Hash32,
     pub value: i64,
-    pub block_hash: Option<Vec<u8>>,
+    pub block_hash: Option<Hash32>,
+    pub conflicted: bool,
 }
 
 impl Movement {
     /// Esta funcion se encarga de serializar un movement en un vector de bytes.
+    /// Los movements en conflicto no tienen bloque, se marcan con un 2 en lugar del indicador de presencia del block hash.
     pub fn serialize(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
-        buffer.push(self.tx_hash.len() as u8);
-        buffer.extend(self.tx_hash.clone());
+        buffer.push(self.tx_hash.as_bytes().len() as u8);
+        buffer.extend(self.tx_hash.as_bytes());
         buffer.extend(self.value.to_le_bytes());
-        match self.block_hash.clone() {
+        match self.block_hash {
             Some(block_hash) => {
                 buffer.push(1);
-                buffer.push(block_hash.len() as u8);
-                buffer.extend(block_hash);
+                buffer.push(block_hash.as_bytes().len() as u8);
+                buffer.extend(block_hash.as_bytes());
             }
             None => {
-                buffer.push(0);
+                buffer.push(match self.conflicted {
+                    true => 2,
+                    false => 0,
+                });
             }
         }
         buffer
@@ -37,14 +45,14 @@ impl Movement {
     /// - Falla alguna extraccion del BufferParser
     pub fn parse(parser: &mut BufferParser) -> Result<Self, CustomError> {
         let tx_hash_len = parser.extract_u8()? as usize;
-        let tx_hash = parser.extract_buffer(tx_hash_len)?.to_vec();
+        let tx_hash = Hash32::from_slice(parser.extract_buffer(tx_hash_len)?)?;
         let value = parser.extract_i64()?;
         let block_hash_present = parser.extract_u8()?;
         let block_hash = match block_hash_present {
-            0 => None,
+            0 | 2 => None,
             1 => {
                 let block_hash_len = parser.extract_u8()? as usize;
-                Some(parser.extract_buffer(block_hash_len)?.to_vec())
+                Some(Hash32::from_slice(parser.extract_buffer(block_hash_len)?)?)
             }
             _ => {
                 return Err(CustomError::Validation(String::from(
@@ -57,68 +65,89 @@ impl Movement {
             tx_hash,
             value,
             block_hash,
+            conflicted: block_hash_present == 2,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parser::BufferParser, structs::movement::Movement};
+    use crate::{
+        parser::BufferParser,
+        structs::{hash32::Hash32, movement::Movement},
+    };
 
     #[test]
     fn movement_serialization() {
         let movement = Movement {
-            tx_hash: vec![
+            tx_hash: Hash32::new([
                 158, 58, 146, 241, 218, 207, 194, 196, 103, 192, 89, 27, 56, 110, 195, 138, 29,
                 177, 167, 47, 144, 191, 102, 68, 45, 70, 88, 237, 140, 224, 130, 115,
-            ],
+            ]),
             value: 500,
-            block_hash: Some(vec![
+            block_hash: Some(Hash32::new([
                 167, 131, 118, 190, 70, 199, 31, 2, 255, 135, 123, 36, 232, 182, 60, 178, 165, 110,
                 47, 11, 50, 1, 133, 106, 59, 195, 153, 210, 59, 21, 163, 41,
-            ]),
+            ])),
+            conflicted: false,
         };
         let serialized_movement = movement.serialize();
         let mut parser = BufferParser::new(serialized_movement);
         let parsed_movement = Movement::parse(&mut parser).unwrap();
         assert_eq!(
             parsed_movement.tx_hash,
-            vec![
+            Hash32::new([
                 158, 58, 146, 241, 218, 207, 194, 196, 103, 192, 89, 27, 56, 110, 195, 138, 29,
                 177, 167, 47, 144, 191, 102, 68, 45, 70, 88, 237, 140, 224, 130, 115
-            ]
+            ])
         );
         assert_eq!(parsed_movement.value, 500);
         assert_eq!(
             parsed_movement.block_hash,
-            Some(vec![
+            Some(Hash32::new([
                 167, 131, 118, 190, 70, 199, 31, 2, 255, 135, 123, 36, 232, 182, 60, 178, 165, 110,
                 47, 11, 50, 1, 133, 106, 59, 195, 153, 210, 59, 21, 163, 41
-            ])
+            ]))
         );
     }
 
     #[test]
     fn movement_without_block_hash() {
         let movement = Movement {
-            tx_hash: vec![
+            tx_hash: Hash32::new([
                 158, 58, 146, 241, 218, 207, 194, 196, 103, 192, 89, 27, 56, 110, 195, 138, 29,
                 177, 167, 47, 144, 191, 102, 68, 45, 70, 88, 237, 140, 224, 130, 115,
-            ],
+            ]),
             value: 500,
             block_hash: None,
+            conflicted: false,
         };
         let serialized_movement = movement.serialize();
         let mut parser = BufferParser::new(serialized_movement);
         let parsed_movement = Movement::parse(&mut parser).unwrap();
         assert_eq!(
             parsed_movement.tx_hash,
-            vec![
+            Hash32::new([
                 158, 58, 146, 241, 218, 207, 194, 196, 103, 192, 89, 27, 56, 110, 195, 138, 29,
                 177, 167, 47, 144, 191, 102, 68, 45, 70, 88, 237, 140, 224, 130, 115
-            ]
+            ])
         );
         assert_eq!(parsed_movement.value, 500);
         assert_eq!(parsed_movement.block_hash, None);
     }
+
+    #[test]
+    fn conflicted_movement_serialization() {
+        let movement = Movement {
+            tx_hash: Hash32::new([1; 32]),
+            value: -500,
+            block_hash: None,
+            conflicted: true,
+        };
+        let mut parser = BufferParser::new(movement.serialize());
+        let parsed_movement = Movement::parse(&mut parser).unwrap();
+        assert!(parsed_movement.conflicted);
+        assert_eq!(parsed_movement.block_hash, None);
+        assert_eq!(parsed_movement.value, -500);
+    }
 }
@@ -0,0 +1,236 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV6};
+
+use crate::{
+    error::CustomError,
+    parser::{BtcEncode, BufferParser, VarIntSerialize},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta es la estructura de una direccion de red anunciada en un mensaje addr.
+/// Contiene:
+/// - timestamp: ultima vez que se supo del nodo, en segundos.
+/// - services: servicios que ofrece el nodo.
+/// - address: direccion IPv6 y puerto del nodo, las direcciones IPv4 se mapean a IPv6.
+pub struct NetAddress {
+    pub timestamp: u32,
+    pub services: u64,
+    pub address: SocketAddrV6,
+}
+
+/// Implementa el trait BtcEncode para una direccion de un mensaje addr.
+impl BtcEncode for NetAddress {
+    /// Esta funcion se encarga de serializar la direccion en sus 30 bytes: timestamp, servicios, IP y puerto.
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.timestamp.encode(buffer);
+        self.services.encode(buffer);
+        buffer.extend(self.address.ip().octets());
+        buffer.extend(self.address.port().to_be_bytes());
+    }
+
+    /// Esta funcion se encarga de parsear una direccion del parser.
+    /// Devuelve CustomError si no quedan 30 bytes para leer.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        Ok(Self {
+            timestamp: parser.extract_u32()?,
+            services: parser.extract_u64()?,
+            address: parser.extract_address()?,
+        })
+    }
+}
+
+/// Tamaño maximo de una direccion en un mensaje addrv2 (BIP155).
+pub const MAX_ADDR_V2_SIZE: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Este enum contiene las redes de las direcciones del formato addrv2 (BIP155), con su id de red:
+/// - IPv4 = 1 (4 bytes)
+/// - IPv6 = 2 (16 bytes)
+/// - TorV2 = 3 (10 bytes, en desuso)
+/// - TorV3 = 4 (32 bytes, clave publica ed25519 del servicio .onion)
+/// - I2P = 5 (32 bytes, hash SHA256 del destino .b32.i2p)
+/// - Cjdns = 6 (16 bytes)
+/// - Unknown: redes que no conocemos, se conservan su id y bytes para poder ignorarlas.
+pub enum NetworkAddress {
+    IPv4(Ipv4Addr),
+    IPv6(Ipv6Addr),
+    TorV2([u8; 10]),
+    TorV3([u8; 32]),
+    I2P([u8; 32]),
+    Cjdns(Ipv6Addr),
+    Unknown(u8, Vec<u8>),
+}
+
+impl NetworkAddress {
+    /// Devuelve el id de red y los bytes de la direccion.
+    fn to_parts(&self) -> (u8, Vec<u8>) {
+        match self {
+            Self::IPv4(ip) => (1, ip.octets().to_vec()),
+            Self::IPv6(ip) => (2, ip.octets().to_vec()),
+            Self::TorV2(bytes) => (3, bytes.to_vec()),
+            Self::TorV3(bytes) => (4, bytes.to_vec()),
+            Self::I2P(bytes) => (5, bytes.to_vec()),
+            Self::Cjdns(ip) => (6, ip.octets().to_vec()),
+            Self::Unknown(network_id, bytes) => (*network_id, bytes.clone()),
+        }
+    }
+
+    /// Crea la direccion a partir de su id de red y sus bytes.
+    /// Devuelve CustomError si el largo de los bytes no corresponde a la red.
+    fn from_parts(network_id: u8, bytes: &[u8]) -> Result<Self, CustomError> {
+        let invalid = |_| CustomError::SerializedBufferIsInvalid;
+        Ok(match network_id {
+            1 => Self::IPv4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).map_err(invalid)?)),
+            2 => Self::IPv6(Ipv6Addr::from(
+                <[u8; 16]>::try_from(bytes).map_err(invalid)?,
+            )),
+            3 => Self::TorV2(bytes.try_into().map_err(invalid)?),
+            4 => Self::TorV3(bytes.try_into().map_err(invalid)?),
+            5 => Self::I2P(bytes.try_into().map_err(invalid)?),
+            6 => Self::Cjdns(Ipv6Addr::from(
+                <[u8; 16]>::try_from(bytes).map_err(invalid)?,
+            )),
+            _ => Self::Unknown(network_id, bytes.to_vec()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta es la estructura de una direccion de red anunciada en un mensaje addrv2 (BIP155).
+/// Contiene:
+/// - timestamp: ultima vez que se supo del nodo, en segundos.
+/// - services: servicios que ofrece el nodo.
+/// - address: direccion del nodo en alguna de las redes soportadas.
+/// - port: puerto del nodo.
+pub struct NetAddressV2 {
+    pub timestamp: u32,
+    pub services: u64,
+    pub address: NetworkAddress,
+    pub port: u16,
+}
+
+/// Implementa el trait BtcEncode para una direccion de un mensaje addrv2.
+impl BtcEncode for NetAddressV2 {
+    /// Esta funcion se encarga de serializar la direccion: timestamp, servicios (varint), id de red,
+    /// largo de la direccion (varint), direccion y puerto.
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let (network_id, address) = self.address.to_parts();
+        self.timestamp.encode(buffer);
+        buffer.extend((self.services as usize).to_varint_bytes());
+        buffer.push(network_id);
+        address.encode(buffer);
+        buffer.extend(self.port.to_be_bytes());
+    }
+
+    /// Esta funcion se encarga de parsear una direccion del parser.
+    /// Devuelve CustomError si la direccion supera los 512 bytes, si su largo no corresponde a su red
+    /// o si faltan bytes para leer.
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let timestamp = parser.extract_u32()?;
+        let services = parser.extract_varint()?;
+        let network_id = parser.extract_u8()?;
+        let address_len = parser.extract_varint()? as usize;
+        if address_len > MAX_ADDR_V2_SIZE {
+            return Err(CustomError::SerializedBufferIsInvalid);
+        }
+        let address = NetworkAddress::from_parts(network_id, parser.extract_buffer(address_len)?)?;
+        let port = u16::from_be_bytes([parser.extract_u8()?, parser.extract_u8()?]);
+
+        Ok(Self {
+            timestamp,
+            services,
+            address,
+            port,
+        })
+    }
+}
+
+impl NetAddressV2 {
+    /// Devuelve la direccion IPv6 del nodo, con las IPv4 mapeadas a IPv6, como la usa el address manager.
+    /// Las direcciones de redes a las que no nos podemos conectar directamente devuelven None.
+    pub fn to_net_address(&self) -> Option<NetAddress> {
+        let ip = match &self.address {
+            NetworkAddress::IPv4(ip) => ip.to_ipv6_mapped(),
+            NetworkAddress::IPv6(ip) => *ip,
+            _ => return None,
+        };
+        Some(NetAddress {
+            timestamp: self.timestamp,
+            services: self.services,
+            address: SocketAddrV6::new(ip, self.port, 0, 0),
+        })
+    }
+}
+
+impl From<NetAddress> for NetAddressV2 {
+    fn from(net_address: NetAddress) -> Self {
+        let ip = net_address.address.ip();
+        let address = match ip.to_ipv4_mapped() {
+            Some(ip_v4) => NetworkAddress::IPv4(ip_v4),
+            None => NetworkAddress::IPv6(*ip),
+        };
+        Self {
+            timestamp: net_address.timestamp,
+            services: net_address.services,
+            address,
+            port: net_address.address.port(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn net_address_v2_serialize_and_parse() {
+        let addresses = vec![
+            NetworkAddress::IPv4(Ipv4Addr::new(10, 0, 0, 1)),
+            NetworkAddress::IPv6("2001:db8::1".parse().unwrap()),
+            NetworkAddress::TorV3([7; 32]),
+            NetworkAddress::I2P([9; 32]),
+            NetworkAddress::Unknown(42, vec![1, 2, 3]),
+        ];
+        for address in addresses {
+            let net_address = NetAddressV2 {
+                timestamp: 1681095630,
+                services: 1033,
+                address,
+                port: 18333,
+            };
+            let mut parser = BufferParser::new(net_address.serialize());
+            assert_eq!(NetAddressV2::decode(&mut parser).unwrap(), net_address);
+            assert!(parser.is_empty());
+        }
+    }
+
+    #[test]
+    fn net_address_v2_invalid_length() {
+        let net_address = NetAddressV2 {
+            timestamp: 0,
+            services: 0,
+            address: NetworkAddress::Unknown(4, vec![1; 16]),
+            port: 0,
+        };
+        let mut parser = BufferParser::new(net_address.serialize());
+        assert!(NetAddressV2::decode(&mut parser).is_err());
+    }
+
+    #[test]
+    fn net_address_v2_to_net_address() {
+        let net_address = NetAddressV2 {
+            timestamp: 1,
+            services: 1,
+            address: NetworkAddress::IPv4(Ipv4Addr::new(10, 0, 0, 1)),
+            port: 18333,
+        };
+        let legacy = net_address.to_net_address().unwrap();
+        assert_eq!(legacy.address, "[::ffff:10.0.0.1]:18333".parse().unwrap());
+        assert_eq!(NetAddressV2::from(legacy), net_address);
+
+        let onion = NetAddressV2 {
+            address: NetworkAddress::TorV3([7; 32]),
+            ..net_address
+        };
+        assert!(onion.to_net_address().is_none());
+    }
+}
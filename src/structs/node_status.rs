@@ -0,0 +1,66 @@
+use super::hash32::Hash32;
+
+#[derive(Debug, Clone, PartialEq)]
+/// NodeStatus es un resumen del estado del nodo, como el getblockchaininfo de Bitcoin Core.
+/// Los elementos son:
+/// - tip_hash: Hash del ultimo bloque procesado por las UTXO.
+/// - tip_height: Altura del ultimo bloque procesado por las UTXO.
+/// - header_height: Altura del ultimo header descargado.
+/// - sync_percentage: Porcentaje (entre 0 y 100) de la etapa actual de la sincronizacion, 100 si ya se sincronizo.
+/// - utxo_size: Cantidad de salidas sin gastar.
+/// - mempool_size: Cantidad de pending txs.
+/// - peers: Cantidad de peers conectados.
+/// - disk_usage: Bytes que ocupan los archivos del nodo en el directorio store.
+pub struct NodeStatus {
+    pub tip_hash: Hash32,
+    pub tip_height: u32,
+    pub header_height: usize,
+    pub sync_percentage: f64,
+    pub utxo_size: usize,
+    pub mempool_size: usize,
+    pub peers: usize,
+    pub disk_usage: u64,
+}
+
+impl NodeStatus {
+    /// Devuelve el estado en JSON, con los nombres de los campos de getblockchaininfo cuando existen.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"bestblockhash\":\"{}\",\"blocks\":{},\"headers\":{},\"syncpercentage\":{:.2},\"utxos\":{},\"mempool\":{},\"connections\":{},\"size_on_disk\":{}}}",
+            self.tip_hash,
+            self.tip_height,
+            self.header_height,
+            self.sync_percentage,
+            self.utxo_size,
+            self.mempool_size,
+            self.peers,
+            self.disk_usage
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_status_as_json() {
+        let status = NodeStatus {
+            tip_hash: Hash32::default(),
+            tip_height: 10,
+            header_height: 12,
+            sync_percentage: 50.0,
+            utxo_size: 30,
+            mempool_size: 2,
+            peers: 8,
+            disk_usage: 4096,
+        };
+        assert_eq!(
+            status.to_json(),
+            format!(
+                "{{\"bestblockhash\":\"{}\",\"blocks\":10,\"headers\":12,\"syncpercentage\":50.00,\"utxos\":30,\"mempool\":2,\"connections\":8,\"size_on_disk\":4096}}",
+                Hash32::default()
+            )
+        );
+    }
+}
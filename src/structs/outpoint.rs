@@ -1,4 +1,9 @@
-use crate::{error::CustomError, parser::BufferParser};
+use crate::{
+    error::CustomError,
+    parser::{BtcEncode, BufferParser},
+};
+
+use super::hash32::Hash32;
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 
@@ -6,23 +11,19 @@ use crate::{error::CustomError, parser::BufferParser};
 /// - hash: Hash de la transaccion
 /// - index: Indice de la transaccion
 pub struct OutPoint {
-    pub hash: Vec<u8>,
+    pub hash: Hash32,
     pub index: u32,
 }
 
-impl OutPoint {
-    /// Esta funcion se encarga de serializar un outpoint en un vector de bytes.
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.hash.clone());
-        buffer.extend(self.index.to_le_bytes());
-        buffer
+/// Implementa el trait BtcEncode para un outpoint, con el hash de la transaccion y el indice de la salida.
+impl BtcEncode for OutPoint {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.hash.encode(buffer);
+        self.index.encode(buffer);
     }
 
-    /// Esta funcion se encarga de parsear un outpoint a partir de un vector de bytes.
-    pub fn parse(buffer: Vec<u8>) -> Result<Self, CustomError> {
-        let mut parser = BufferParser::new(buffer);
-        let hash = parser.extract_buffer(32)?.to_vec();
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let hash = parser.extract_hash()?;
         let index = parser.extract_u32()?;
         Ok(Self { hash, index })
     }
@@ -30,15 +31,18 @@ impl OutPoint {
 
 #[cfg(test)]
 mod tests {
-    use crate::structs::outpoint::OutPoint;
+    use crate::{
+        parser::BtcEncode,
+        structs::{hash32::Hash32, outpoint::OutPoint},
+    };
 
     #[test]
     fn serialize_and_parse() {
         let outpoint = OutPoint {
-            hash: vec![
+            hash: Hash32::new([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, 6, 7,
                 8, 9, 10, 1, 2,
-            ],
+            ]),
             index: 0,
         };
         let serialized = outpoint.serialize();
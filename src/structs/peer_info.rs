@@ -0,0 +1,42 @@
+use std::net::SocketAddrV6;
+
+use crate::peer::Peer;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta estructura contiene los datos de un peer conectado que se muestran al listar los peers:
+/// - address: Direccion del peer.
+/// - user_agent: User agent del peer, indica el software que utiliza.
+/// - version: Version del protocolo del peer.
+/// - latency: Latencia en milisegundos con el peer.
+/// - bytes_received: Bytes recibidos del peer.
+/// - bytes_sent: Bytes enviados al peer.
+/// - inbound: Indica si la conexion la inicio el peer (entrante) o el nodo (saliente).
+/// - misbehavior: Puntaje de mal comportamiento acumulado por el peer.
+/// - connected_at: Timestamp en segundos en el que se establecio la conexion.
+pub struct PeerInfo {
+    pub address: SocketAddrV6,
+    pub user_agent: String,
+    pub version: i32,
+    pub latency: i64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub inbound: bool,
+    pub misbehavior: u32,
+    pub connected_at: u64,
+}
+
+impl From<&Peer> for PeerInfo {
+    fn from(peer: &Peer) -> Self {
+        Self {
+            address: peer.address,
+            user_agent: peer.user_agent.clone(),
+            version: peer.version,
+            latency: peer.latency,
+            bytes_received: peer.traffic.get_bytes_received(),
+            bytes_sent: peer.traffic.get_bytes_sent(),
+            inbound: peer.inbound,
+            misbehavior: peer.misbehavior,
+            connected_at: peer.connected_at,
+        }
+    }
+}
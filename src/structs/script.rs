@@ -0,0 +1,422 @@
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_PUSHDATA4: u8 = 0x4e;
+pub const OP_1NEGATE: u8 = 0x4f;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_IF: u8 = 0x63;
+pub const OP_NOTIF: u8 = 0x64;
+pub const OP_ENDIF: u8 = 0x68;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+pub const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+
+/// Cantidad de sigops que cuenta un OP_CHECKMULTISIG sin conocer la cantidad de claves.
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Tipos de script pubkey que reconoce el nodo.
+/// - NullData: Salida OP_RETURN seguida solo de datos, que no se puede gastar.
+/// - Multisig: Multisig sin P2SH, con la cantidad de claves.
+/// - NonStandard: Cualquier otro script.
+pub enum ScriptType {
+    P2PK,
+    P2PKH,
+    P2SH,
+    P2WPKH,
+    P2WSH,
+    P2TR,
+    Multisig(usize),
+    NullData,
+    NonStandard,
+}
+
+impl ScriptType {
+    /// Clasifica un script pubkey segun su forma.
+    pub fn classify(script: &[u8]) -> Self {
+        match script {
+            [OP_DUP, OP_HASH160, 20, .., OP_EQUALVERIFY, OP_CHECKSIG] if script.len() == 25 => {
+                Self::P2PKH
+            }
+            [OP_HASH160, 20, .., OP_EQUAL] if script.len() == 23 => Self::P2SH,
+            [OP_0, 20, ..] if script.len() == 22 => Self::P2WPKH,
+            [OP_0, 32, ..] if script.len() == 34 => Self::P2WSH,
+            [OP_1, 32, ..] if script.len() == 34 => Self::P2TR,
+            [33, .., OP_CHECKSIG] if script.len() == 35 => Self::P2PK,
+            [65, .., OP_CHECKSIG] if script.len() == 67 => Self::P2PK,
+            [OP_RETURN, data @ ..] if is_push_only(data) => Self::NullData,
+            _ => classify_multisig(script),
+        }
+    }
+
+    /// Indica si el script es de un tipo que se acepta y retransmite.
+    pub fn is_standard(&self) -> bool {
+        !matches!(self, Self::NonStandard)
+    }
+
+    /// Indica si el script es de una version de witness, cuyas salidas se gastan con witness en lugar de script sig.
+    pub fn is_witness(&self) -> bool {
+        matches!(self, Self::P2WPKH | Self::P2WSH | Self::P2TR)
+    }
+}
+
+/// Reconoce un multisig sin P2SH: OP_m <claves> OP_n OP_CHECKMULTISIG, con 1 <= m <= n <= 3.
+fn classify_multisig(script: &[u8]) -> ScriptType {
+    let Some(ops) = parse_ops(script) else {
+        return ScriptType::NonStandard;
+    };
+    let [(required, _), keys @ .., (total, _), (OP_CHECKMULTISIG, _)] = ops.as_slice() else {
+        return ScriptType::NonStandard;
+    };
+    let (Some(required), Some(total)) = (small_int(*required), small_int(*total)) else {
+        return ScriptType::NonStandard;
+    };
+    let valid_keys = keys
+        .iter()
+        .all(|(_, key)| matches!(key.map(<[u8]>::len), Some(33) | Some(65)));
+    match required >= 1 && required <= total && total <= 3 && keys.len() == total && valid_keys {
+        true => ScriptType::Multisig(total),
+        false => ScriptType::NonStandard,
+    }
+}
+
+/// Devuelve el numero de un opcode OP_1 a OP_16, o None si es otro opcode.
+fn small_int(opcode: u8) -> Option<usize> {
+    match opcode {
+        OP_1..=OP_16 => Some((opcode - OP_1 + 1) as usize),
+        _ => None,
+    }
+}
+
+/// Opcode de un script, junto a los datos que agrega al stack si es un push.
+pub type ScriptOp<'a> = (u8, Option<&'a [u8]>);
+
+/// Recorre los opcodes de un script, junto a los datos que agrega al stack si es un push.
+/// Termina al llegar al final del script o a un push que se pasa del final, en cuyo caso queda marcado como malformed.
+struct ScriptOps<'a> {
+    script: &'a [u8],
+    position: usize,
+    malformed: bool,
+}
+
+impl<'a> ScriptOps<'a> {
+    fn new(script: &'a [u8]) -> Self {
+        Self {
+            script,
+            position: 0,
+            malformed: false,
+        }
+    }
+
+    /// Lee el largo little endian de bytes bytes de un OP_PUSHDATA y avanza la posicion.
+    fn read_len(&mut self, bytes: usize) -> Option<usize> {
+        let len_bytes = self.script.get(self.position..self.position + bytes)?;
+        self.position += bytes;
+        Some(
+            len_bytes
+                .iter()
+                .rev()
+                .fold(0, |len, byte| (len << 8) | *byte as usize),
+        )
+    }
+
+    fn read_push(&mut self, opcode: u8) -> Option<&'a [u8]> {
+        let data_len = match opcode {
+            OP_PUSHDATA1 => self.read_len(1)?,
+            OP_PUSHDATA2 => self.read_len(2)?,
+            OP_PUSHDATA4 => self.read_len(4)?,
+            _ => opcode as usize,
+        };
+        let data = self
+            .script
+            .get(self.position..self.position.checked_add(data_len)?)?;
+        self.position += data_len;
+        Some(data)
+    }
+}
+
+impl<'a> Iterator for ScriptOps<'a> {
+    type Item = ScriptOp<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.malformed {
+            return None;
+        }
+        let opcode = *self.script.get(self.position)?;
+        self.position += 1;
+        if !(1..=OP_PUSHDATA4).contains(&opcode) {
+            return Some((opcode, None));
+        }
+        match self.read_push(opcode) {
+            Some(data) => Some((opcode, Some(data))),
+            None => {
+                self.malformed = true;
+                None
+            }
+        }
+    }
+}
+
+/// Separa el script en sus opcodes, junto a los datos que agrega al stack si es un push.
+/// Devuelve None si algun push se pasa del final del script.
+pub fn parse_ops(script: &[u8]) -> Option<Vec<ScriptOp<'_>>> {
+    let mut script_ops = ScriptOps::new(script);
+    let ops: Vec<ScriptOp> = script_ops.by_ref().collect();
+    match script_ops.malformed {
+        true => None,
+        false => Some(ops),
+    }
+}
+
+/// Indica si el script solo agrega datos al stack, como deben hacerlo los script sig estandar.
+/// OP_RESERVED (0x50) no se considera un push.
+pub fn is_push_only(script: &[u8]) -> bool {
+    parse_ops(script).is_some_and(|ops| {
+        ops.iter()
+            .all(|(opcode, _)| *opcode <= OP_16 && *opcode != 0x50)
+    })
+}
+
+/// Cuenta las sigops de un script sin conocer el script que ejecutan, como lo hacen los nodos anteriores a P2SH.
+/// Cada OP_CHECKMULTISIG cuenta como MAX_PUBKEYS_PER_MULTISIG sigops. Si el script esta mal formado se cuentan
+/// las sigops hasta el push invalido.
+pub fn count_legacy_sigops(script: &[u8]) -> usize {
+    count_sigops(script, false)
+}
+
+/// Cuenta las sigops de un redeem script o witness script, donde un OP_CHECKMULTISIG precedido por OP_n cuenta n sigops.
+pub fn count_accurate_sigops(script: &[u8]) -> usize {
+    count_sigops(script, true)
+}
+
+fn count_sigops(script: &[u8], accurate: bool) -> usize {
+    let mut sigops = 0;
+    let mut last_opcode = None;
+    for (opcode, _) in ScriptOps::new(script) {
+        sigops += match opcode {
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => 1,
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                match last_opcode.and_then(small_int).filter(|_| accurate) {
+                    Some(keys) => keys,
+                    None => MAX_PUBKEYS_PER_MULTISIG,
+                }
+            }
+            _ => 0,
+        };
+        last_opcode = Some(opcode);
+    }
+    sigops
+}
+
+/// Devuelve el redeem script de una entrada que gasta una salida P2SH: el ultimo push de su script sig.
+/// Devuelve None si la salida no es P2SH o si el script sig no solo agrega datos.
+fn redeem_script<'a>(script_pubkey: &[u8], script_sig: &'a [u8]) -> Option<&'a [u8]> {
+    if ScriptType::classify(script_pubkey) != ScriptType::P2SH {
+        return None;
+    }
+    if !is_push_only(script_sig) {
+        return None;
+    }
+    parse_ops(script_sig)?.last()?.1
+}
+
+/// Cuenta las sigops del redeem script de una entrada que gasta una salida P2SH, o 0 si la salida es de otro tipo.
+pub fn count_p2sh_sigops(script_pubkey: &[u8], script_sig: &[u8]) -> usize {
+    redeem_script(script_pubkey, script_sig).map_or(0, count_accurate_sigops)
+}
+
+/// Devuelve los scripts que se ejecutan al gastar una salida: su script pubkey, el redeem script si es P2SH
+/// y el witness script si es P2WSH, directamente o dentro de P2SH.
+pub fn executed_scripts<'a>(
+    script_pubkey: &'a [u8],
+    script_sig: &'a [u8],
+    witness: &'a [Vec<u8>],
+) -> Vec<&'a [u8]> {
+    let mut scripts = vec![script_pubkey];
+    let redeem_script = redeem_script(script_pubkey, script_sig);
+    if let Some(redeem_script) = redeem_script {
+        scripts.push(redeem_script);
+    }
+    let program = redeem_script.unwrap_or(script_pubkey);
+    if ScriptType::classify(program) == ScriptType::P2WSH {
+        if let Some(witness_script) = witness.last() {
+            scripts.push(witness_script);
+        }
+    }
+    scripts
+}
+
+/// Devuelve los OP_CHECKLOCKTIMEVERIFY y OP_CHECKSEQUENCEVERIFY del script junto al numero que verifican,
+/// el elemento que se agrega al stack justo antes.
+/// Solo se devuelven los que estan fuera de todo OP_IF, que se ejecutan siempre que el script llegue hasta ellos.
+/// Si el numero no se puede decodificar se devuelve None, lo que hace fallar la verificacion.
+pub fn unconditional_lock_checks(script: &[u8]) -> Vec<(u8, Option<i64>)> {
+    let mut checks = vec![];
+    let mut depth = 0;
+    let mut last_op: Option<ScriptOp> = None;
+    for (opcode, data) in ScriptOps::new(script) {
+        match opcode {
+            OP_IF | OP_NOTIF => depth += 1,
+            OP_ENDIF => depth -= 1,
+            OP_CHECKLOCKTIMEVERIFY | OP_CHECKSEQUENCEVERIFY if depth == 0 => {
+                checks.push((opcode, last_op.and_then(script_number)));
+            }
+            _ => (),
+        }
+        last_op = Some((opcode, data));
+    }
+    checks
+}
+
+/// Decodifica el numero que agrega al stack un opcode, de hasta 5 bytes en little endian con el signo en el ultimo bit.
+fn script_number((opcode, data): ScriptOp) -> Option<i64> {
+    match opcode {
+        OP_0 => return Some(0),
+        OP_1NEGATE => return Some(-1),
+        OP_1..=OP_16 => return small_int(opcode).map(|number| number as i64),
+        _ => (),
+    }
+    let data = data?;
+    if data.len() > 5 {
+        return None;
+    }
+    let Some((last, _)) = data.split_last() else {
+        return Some(0);
+    };
+    let magnitude = data
+        .iter()
+        .rev()
+        .fold(0_i64, |number, byte| (number << 8) | *byte as i64);
+    match last & 0x80 != 0 {
+        true => Some(-(magnitude & !(0x80 << (8 * (data.len() - 1))))),
+        false => Some(magnitude),
+    }
+}
+
+/// Cuenta las sigops de los witness de una entrada (BIP141), que gasta una salida witness directamente o dentro de P2SH.
+/// Una salida P2WPKH cuenta una sigop y una P2WSH las de su witness script, el ultimo elemento del witness.
+/// Las salidas taproot y las de versiones desconocidas no cuentan sigops.
+pub fn count_witness_sigops(script_pubkey: &[u8], script_sig: &[u8], witness: &[Vec<u8>]) -> usize {
+    let program = redeem_script(script_pubkey, script_sig).unwrap_or(script_pubkey);
+    match ScriptType::classify(program) {
+        ScriptType::P2WPKH => 1,
+        ScriptType::P2WSH => witness
+            .last()
+            .map_or(0, |witness_script| count_accurate_sigops(witness_script)),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_standard_scripts() {
+        let mut p2pkh = vec![OP_DUP, OP_HASH160, 20];
+        p2pkh.extend([1; 20]);
+        p2pkh.extend([OP_EQUALVERIFY, OP_CHECKSIG]);
+        assert_eq!(ScriptType::classify(&p2pkh), ScriptType::P2PKH);
+
+        let mut p2wpkh = vec![OP_0, 20];
+        p2wpkh.extend([1; 20]);
+        assert_eq!(ScriptType::classify(&p2wpkh), ScriptType::P2WPKH);
+
+        let mut multisig = vec![OP_1, 33];
+        multisig.extend([2; 33]);
+        multisig.extend([OP_1, OP_CHECKMULTISIG]);
+        assert_eq!(ScriptType::classify(&multisig), ScriptType::Multisig(1));
+
+        assert_eq!(
+            ScriptType::classify(&[OP_RETURN, 3, 1, 2, 3]),
+            ScriptType::NullData
+        );
+        assert_eq!(ScriptType::classify(&[OP_1]), ScriptType::NonStandard);
+        assert_eq!(
+            ScriptType::classify(&[OP_RETURN, OP_CHECKSIG]),
+            ScriptType::NonStandard
+        );
+    }
+
+    #[test]
+    fn push_only_and_sigops() {
+        assert!(is_push_only(&[2, 1, 2, OP_PUSHDATA1, 1, 3, OP_0, OP_1]));
+        assert!(!is_push_only(&[2, 1, 2, OP_CHECKSIG]));
+        assert!(!is_push_only(&[5, 1, 2]));
+
+        assert_eq!(count_legacy_sigops(&[OP_CHECKSIG, 1, OP_CHECKSIG]), 1);
+        assert_eq!(count_legacy_sigops(&[OP_CHECKSIG, OP_PUSHDATA1, 5, 1]), 1);
+        assert_eq!(count_accurate_sigops(&[OP_1 + 1, OP_CHECKMULTISIG]), 2);
+        assert_eq!(
+            count_accurate_sigops(&[OP_CHECKMULTISIG]),
+            MAX_PUBKEYS_PER_MULTISIG
+        );
+    }
+
+    #[test]
+    fn lock_checks_outside_conditionals() {
+        let script = [
+            3,
+            0x40,
+            0x0d,
+            0x03,
+            OP_CHECKLOCKTIMEVERIFY,
+            0x75,
+            OP_IF,
+            OP_1,
+            OP_CHECKSEQUENCEVERIFY,
+            OP_ENDIF,
+            1,
+            0x81,
+            OP_CHECKSEQUENCEVERIFY,
+            OP_CHECKLOCKTIMEVERIFY,
+        ];
+        assert_eq!(
+            unconditional_lock_checks(&script),
+            vec![
+                (OP_CHECKLOCKTIMEVERIFY, Some(200_000)),
+                (OP_CHECKSEQUENCEVERIFY, Some(-1)),
+                (OP_CHECKLOCKTIMEVERIFY, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn p2sh_and_witness_sigops() {
+        let witness_script = vec![OP_1 + 1, OP_1 + 2, OP_CHECKMULTISIG];
+        let mut p2wsh = vec![OP_0, 32];
+        p2wsh.extend([1; 32]);
+        let mut p2wpkh = vec![OP_0, 20];
+        p2wpkh.extend([1; 20]);
+        let mut p2sh = vec![OP_HASH160, 20];
+        p2sh.extend([1; 20]);
+        p2sh.push(OP_EQUAL);
+
+        let witness = vec![vec![1; 72], witness_script.clone()];
+        assert_eq!(count_witness_sigops(&p2wsh, &[], &witness), 3);
+        assert_eq!(count_witness_sigops(&p2wpkh, &[], &witness), 1);
+
+        let mut nested_script_sig = vec![p2wpkh.len() as u8];
+        nested_script_sig.extend(&p2wpkh);
+        assert_eq!(count_witness_sigops(&p2sh, &nested_script_sig, &witness), 1);
+        assert_eq!(count_p2sh_sigops(&p2sh, &nested_script_sig), 0);
+
+        let mut script_sig = vec![0, witness_script.len() as u8];
+        script_sig.extend(&witness_script);
+        assert_eq!(count_p2sh_sigops(&p2sh, &script_sig), 3);
+        assert_eq!(count_p2sh_sigops(&p2wpkh, &script_sig), 0);
+        assert_eq!(
+            count_legacy_sigops(&[OP_CHECKSIGVERIFY, OP_CHECKMULTISIG]),
+            1 + MAX_PUBKEYS_PER_MULTISIG
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+/// Segundos promedio entre bloques, para estimar la altura de la red a partir del timestamp del ultimo header.
+const TARGET_BLOCK_SPACING: u64 = 10 * 60;
+/// Ventana de tiempo en milisegundos sobre la que se mide la velocidad de descarga de bloques.
+const RATE_WINDOW: u128 = 30_000;
+/// Tiempo minimo en milisegundos entre dos reportes de progreso, para no saturar la interfaz grafica.
+const REPORT_INTERVAL: u128 = 250;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Esta estructura contiene el progreso de la sincronizacion del nodo con la red:
+/// - header_height: Altura del ultimo header descargado.
+/// - network_height: Altura estimada de la red.
+/// - headers_synced: Indica si ya se descargaron todos los headers.
+/// - blocks_downloaded: Bloques posteriores al inicio del IBD ya descargados.
+/// - blocks_total: Bloques posteriores al inicio del IBD segun los headers descargados.
+/// - blocks_per_second: Velocidad de descarga de bloques en los ultimos RATE_WINDOW milisegundos.
+/// - eta: Segundos estimados para terminar de descargar los bloques, None si todavia no se puede estimar.
+pub struct SyncProgress {
+    pub header_height: usize,
+    pub network_height: usize,
+    pub headers_synced: bool,
+    pub blocks_downloaded: usize,
+    pub blocks_total: usize,
+    pub blocks_per_second: f64,
+    pub eta: Option<u64>,
+}
+
+impl SyncProgress {
+    /// Crea el progreso de la sincronizacion, sin velocidad de descarga ni ETA.
+    pub fn new(
+        header_height: usize,
+        network_height: usize,
+        headers_synced: bool,
+        blocks_downloaded: usize,
+        blocks_total: usize,
+    ) -> Self {
+        Self {
+            header_height,
+            network_height: network_height.max(header_height),
+            headers_synced,
+            blocks_downloaded: blocks_downloaded.min(blocks_total),
+            blocks_total,
+            blocks_per_second: 0.0,
+            eta: None,
+        }
+    }
+
+    /// Devuelve la fraccion (entre 0 y 1) de la etapa actual de la sincronizacion:
+    /// los headers descargados hasta que se sincronizan, y despues los bloques descargados.
+    pub fn fraction(&self) -> f64 {
+        let (done, total) = match self.headers_synced {
+            false => (self.header_height, self.network_height),
+            true => (self.blocks_downloaded, self.blocks_total),
+        };
+        match total {
+            0 => 0.0,
+            _ => done as f64 / total as f64,
+        }
+    }
+}
+
+/// Estima la altura de la red sumandole a la del ultimo header los bloques que se esperan desde su timestamp.
+pub fn estimate_network_height(header_height: usize, last_timestamp: u32, now: u64) -> usize {
+    let elapsed = now.saturating_sub(last_timestamp as u64);
+    header_height + (elapsed / TARGET_BLOCK_SPACING) as usize
+}
+
+#[derive(Debug, Default)]
+/// SyncProgressTracker mide la velocidad de descarga de bloques para completar el progreso de la sincronizacion.
+/// Los elementos son:
+/// - samples: Timestamps en milisegundos y bloques descargados en ese momento, de los ultimos RATE_WINDOW milisegundos.
+/// - last_report: Timestamp en milisegundos del ultimo progreso reportado.
+pub struct SyncProgressTracker {
+    samples: VecDeque<(u128, usize)>,
+    last_report: u128,
+}
+
+impl SyncProgressTracker {
+    /// Registra los bloques descargados del progreso y le completa la velocidad de descarga y el ETA.
+    /// Devuelve None si no pasaron REPORT_INTERVAL milisegundos desde el ultimo progreso devuelto.
+    pub fn report(&mut self, now: u128, mut progress: SyncProgress) -> Option<SyncProgress> {
+        self.samples.push_back((now, progress.blocks_downloaded));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(timestamp, _)| now - timestamp > RATE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+
+        if now.saturating_sub(self.last_report) < REPORT_INTERVAL {
+            return None;
+        }
+        self.last_report = now;
+
+        if let Some((first_timestamp, first_downloaded)) = self.samples.front() {
+            let elapsed = now - first_timestamp;
+            if elapsed > 0 {
+                let downloaded = progress.blocks_downloaded.saturating_sub(*first_downloaded);
+                progress.blocks_per_second = downloaded as f64 * 1000.0 / elapsed as f64;
+            }
+        }
+        if progress.headers_synced && progress.blocks_per_second > 0.0 {
+            let remaining = progress.blocks_total - progress.blocks_downloaded;
+            progress.eta = Some((remaining as f64 / progress.blocks_per_second).ceil() as u64);
+        }
+        Some(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_height_is_estimated_from_last_timestamp() {
+        assert_eq!(estimate_network_height(100, 1_000, 1_000), 100);
+        assert_eq!(
+            estimate_network_height(100, 1_000, 1_000 + 3 * 600 + 10),
+            103
+        );
+        assert_eq!(estimate_network_height(100, 2_000, 1_000), 100);
+    }
+
+    #[test]
+    fn fraction_follows_the_current_stage() {
+        let headers = SyncProgress::new(50, 200, false, 0, 50);
+        assert_eq!(headers.fraction(), 0.25);
+
+        let blocks = SyncProgress::new(200, 200, true, 30, 40);
+        assert_eq!(blocks.fraction(), 0.75);
+    }
+
+    #[test]
+    fn tracker_measures_rate_and_eta() {
+        let mut tracker = SyncProgressTracker::default();
+        assert!(tracker
+            .report(1_000, SyncProgress::new(200, 200, true, 0, 100))
+            .is_some());
+        assert!(tracker
+            .report(1_100, SyncProgress::new(200, 200, true, 5, 100))
+            .is_none());
+
+        let progress = tracker
+            .report(3_000, SyncProgress::new(200, 200, true, 20, 100))
+            .unwrap();
+        assert_eq!(progress.blocks_per_second, 10.0);
+        assert_eq!(progress.eta, Some(8));
+    }
+
+    #[test]
+    fn tracker_has_no_eta_while_downloading_headers() {
+        let mut tracker = SyncProgressTracker::default();
+        tracker.report(1_000, SyncProgress::new(50, 200, false, 0, 50));
+        let progress = tracker
+            .report(2_000, SyncProgress::new(100, 200, false, 10, 100))
+            .unwrap();
+        assert_eq!(progress.eta, None);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::messages::transaction::Transaction;
+
+use super::{hash32::Hash32, tx_output::TransactionOutput};
+
+#[derive(Debug, Clone)]
+/// Esta estructura contiene los detalles de una transaccion que se muestran al consultarla:
+/// - transaction: Transaccion consultada.
+/// - block_hash: Hash del bloque que la incluyo, None si esta pendiente.
+/// - confirmations: Cantidad de bloques que la confirman, contando el que la incluyo. 0 si esta pendiente.
+/// - spent_outputs: Salidas que gastan sus entradas, en el mismo orden que las entradas. None si no se conoce la salida.
+pub struct TransactionDetails {
+    pub transaction: Transaction,
+    pub block_hash: Option<Hash32>,
+    pub confirmations: usize,
+    pub spent_outputs: Vec<Option<TransactionOutput>>,
+}
+
+impl TransactionDetails {
+    /// Devuelve el fee de la transaccion en satoshis: lo que reciben sus entradas menos lo que envian sus salidas.
+    /// Devuelve None si no se conoce alguna de las salidas que gasta, como en las coinbase.
+    pub fn fee(&self) -> Option<u64> {
+        let mut inputs_value = 0;
+        for spent_output in &self.spent_outputs {
+            inputs_value += spent_output.as_ref()?.value;
+        }
+        let outputs_value: u64 = self
+            .transaction
+            .outputs
+            .iter()
+            .map(|output| output.value)
+            .sum();
+        inputs_value.checked_sub(outputs_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::{outpoint::OutPoint, tx_input::TransactionInput};
+
+    use super::*;
+
+    #[test]
+    fn fee_from_spent_outputs() {
+        let output = |value| TransactionOutput {
+            value,
+            script_pubkey: vec![],
+        };
+        let input = TransactionInput {
+            previous_output: OutPoint {
+                hash: Hash32::new([1; 32]),
+                index: 0,
+            },
+            script_sig: vec![],
+            sequence: 0xffffffff,
+            witness: vec![],
+        };
+        let mut details = TransactionDetails {
+            transaction: Transaction {
+                version: 1,
+                inputs: vec![input.clone(), input],
+                outputs: vec![output(700), output(200)],
+                lock_time: 0,
+            },
+            block_hash: None,
+            confirmations: 0,
+            spent_outputs: vec![Some(output(500)), Some(output(600))],
+        };
+        assert_eq!(details.fee(), Some(200));
+
+        details.spent_outputs[1] = None;
+        assert_eq!(details.fee(), None);
+    }
+}
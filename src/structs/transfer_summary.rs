@@ -0,0 +1,89 @@
+use crate::messages::transaction::LOCKTIME_THRESHOLD;
+
+#[derive(Debug, Clone, PartialEq)]
+/// TransferSummary resume una transaccion armada por la wallet activa, para que el usuario la confirme antes de enviarla.
+/// Los elementos son:
+/// - recipients: Direcciones a las que se envia y el monto en satoshis que recibe cada una, ordenadas por direccion.
+/// - change: Cambio en satoshis que vuelve a la wallet, 0 si no hay o si era dust y se sumo al fee.
+/// - fee: Fee en satoshis que se paga a los mineros.
+/// - vsize: Tamaño virtual de la transaccion, que crece con cada input y output.
+/// - lock_time: Altura de bloque o timestamp antes del cual no se puede minar, None si no tiene.
+pub struct TransferSummary {
+    pub recipients: Vec<(String, u64)>,
+    pub change: u64,
+    pub fee: u64,
+    pub vsize: usize,
+    pub lock_time: Option<u32>,
+}
+
+impl TransferSummary {
+    /// Devuelve el total que sale de la wallet: los montos de los destinatarios mas el fee.
+    pub fn total(&self) -> u64 {
+        self.recipients.iter().map(|(_, value)| value).sum::<u64>() + self.fee
+    }
+
+    /// Devuelve el fee rate en satoshis por byte virtual.
+    pub fn fee_rate(&self) -> f64 {
+        match self.vsize {
+            0 => 0.0,
+            vsize => self.fee as f64 / vsize as f64,
+        }
+    }
+
+    /// Describe la transaccion en lineas, con los montos formateados con la funcion recibida.
+    pub fn describe(&self, format_amount: impl Fn(i64) -> String) -> String {
+        let mut lines: Vec<String> = self
+            .recipients
+            .iter()
+            .map(|(address, value)| format!("Send {} to {address}", format_amount(*value as i64)))
+            .collect();
+        if self.change > 0 {
+            lines.push(format!("Change: {}", format_amount(self.change as i64)));
+        }
+        lines.push(format!(
+            "Fee: {} ({:.1} sat/vB, {} vB)",
+            format_amount(self.fee as i64),
+            self.fee_rate(),
+            self.vsize
+        ));
+        lines.push(format!("Total: {}", format_amount(self.total() as i64)));
+        match self.lock_time {
+            Some(height) if height < LOCKTIME_THRESHOLD => {
+                lines.push(format!("Locked until block {height}"))
+            }
+            Some(timestamp) => lines.push(format!("Locked until timestamp {timestamp}")),
+            None => {}
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_summary_description() {
+        let summary = TransferSummary {
+            recipients: vec![(String::from("first"), 1000), (String::from("second"), 500)],
+            change: 200,
+            fee: 450,
+            vsize: 300,
+            lock_time: None,
+        };
+        assert_eq!(summary.total(), 1950);
+        assert_eq!(summary.fee_rate(), 1.5);
+        assert_eq!(
+            summary.describe(|value| format!("{value} sats")),
+            "Send 1000 sats to first\nSend 500 sats to second\nChange: 200 sats\nFee: 450 sats (1.5 sat/vB, 300 vB)\nTotal: 1950 sats"
+        );
+
+        let locked = TransferSummary {
+            lock_time: Some(800_000),
+            ..summary
+        };
+        assert!(locked
+            .describe(|value| format!("{value} sats"))
+            .ends_with("Total: 1950 sats\nLocked until block 800000"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     error::CustomError,
-    parser::{BufferParser, VarIntSerialize},
+    parser::{BtcEncode, BufferParser},
 };
 
 use super::outpoint::OutPoint;
@@ -11,35 +11,35 @@ use super::outpoint::OutPoint;
 /// - previous_output: Outpoint de la transaccion que genero el input
 /// - script_sig: Script que se debe ejecutar para firmar transacciones
 /// - sequence: Numero de version definido por el usuario
+/// - witness: Elementos del witness del input (BIP144), vacio si la transaccion no es segwit.
+///   Se serializa aparte, al final de la transaccion.
 pub struct TransactionInput {
     pub previous_output: OutPoint,
     pub script_sig: Vec<u8>,
     pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
 }
 
-impl TransactionInput {
-    /// Esta funcion se encarga de serializar un input en un vector de bytes.
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.previous_output.serialize());
-        buffer.extend(self.script_sig.len().to_varint_bytes());
-        buffer.extend(self.script_sig.clone());
-        buffer.extend(self.sequence.to_le_bytes());
-        buffer
+/// Implementa el trait BtcEncode para un input, sin sus witness que se serializan al final de la transaccion.
+impl BtcEncode for TransactionInput {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.previous_output.encode(buffer);
+        self.script_sig.encode(buffer);
+        self.sequence.encode(buffer);
     }
 
     /// Esta funcion se encarga de parsear un input a partir de un BufferParser.
     /// Devuelve CustomError si:
     /// - Falla alguna de las extracciones del BufferParser
-    pub fn parse(parser: &mut BufferParser) -> Result<Self, CustomError> {
-        let previous_output = OutPoint::parse(parser.extract_buffer(36)?.to_vec())?;
-        let script_sig_length = parser.extract_varint()? as usize;
-        let script_sig = parser.extract_buffer(script_sig_length)?.to_vec();
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
+        let previous_output = OutPoint::decode(parser)?;
+        let script_sig = Vec::decode(parser)?;
         let sequence = parser.extract_u32()?;
         Ok(Self {
             previous_output,
             script_sig,
             sequence,
+            witness: vec![],
         })
     }
 }
@@ -47,24 +47,25 @@ impl TransactionInput {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::BufferParser;
+    use crate::{parser::BufferParser, structs::hash32::Hash32};
 
     #[test]
     fn serialize_and_parse() {
         let input = TransactionInput {
             previous_output: OutPoint {
-                hash: vec![
+                hash: Hash32::new([
                     1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 1, 2, 3, 4, 5, 6,
                     7, 8, 9, 10, 1, 2,
-                ],
+                ]),
                 index: 0,
             },
             script_sig: vec![4, 5, 6],
             sequence: 0xffffffff,
+            witness: vec![],
         };
         let serialize = input.serialize();
         let mut parser = BufferParser::new(serialize);
-        let parsed_input = TransactionInput::parse(&mut parser).unwrap();
+        let parsed_input = TransactionInput::decode(&mut parser).unwrap();
         assert_eq!(input, parsed_input);
     }
 }
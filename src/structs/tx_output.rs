@@ -1,8 +1,17 @@
 use crate::{
     error::CustomError,
-    parser::{BufferParser, VarIntSerialize},
+    network::Network,
+    parser::{BtcEncode, BufferParser},
 };
 
+use super::{hash32::Hash32, script::ScriptType};
+
+/// Fee rate en satoshis por kilobyte virtual con el que se calcula si una salida es dust.
+const DUST_RELAY_FEE: u64 = 3000;
+/// Tamaño virtual estimado de la entrada que gasta una salida P2PKH, y de la que gasta una salida witness.
+const LEGACY_SPEND_VSIZE: u64 = 148;
+const WITNESS_SPEND_VSIZE: u64 = 67;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 
 /// Esta estructura representa un output de una transaccion, la cual contiene:
@@ -13,26 +22,52 @@ pub struct TransactionOutput {
     pub script_pubkey: Vec<u8>,
 }
 
-impl TransactionOutput {
-    /// Esta funcion se encarga de serializar un output en un vector de bytes.
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(self.value.to_le_bytes());
-        buffer.extend(self.script_pubkey.len().to_varint_bytes());
-        buffer.extend(self.script_pubkey.clone());
-        buffer
+/// Implementa el trait BtcEncode para un output, con su valor y su script pubkey.
+impl BtcEncode for TransactionOutput {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.value.encode(buffer);
+        self.script_pubkey.encode(buffer);
     }
 
     /// Esta funcion se encarga de parsear un output a partir de un BufferParser.
-    pub fn parse(parser: &mut BufferParser) -> Result<Self, CustomError> {
-        let value = parser.extract_u64()?;
-        let script_pk_length = parser.extract_varint()? as usize;
-        let script_pubkey = parser.extract_buffer(script_pk_length)?.to_vec();
+    fn decode(parser: &mut BufferParser) -> Result<Self, CustomError> {
         Ok(Self {
-            value,
-            script_pubkey,
+            value: parser.extract_u64()?,
+            script_pubkey: Vec::decode(parser)?,
         })
     }
+}
+
+impl TransactionOutput {
+    /// Indica si el output es dust: gastarlo costaria, al fee rate DUST_RELAY_FEE, mas de lo que vale.
+    /// Las salidas OP_RETURN no se pueden gastar, por lo que nunca son dust.
+    pub fn is_dust(&self) -> bool {
+        let script_type = ScriptType::classify(&self.script_pubkey);
+        if script_type == ScriptType::NullData {
+            return false;
+        }
+        let spend_vsize = match script_type.is_witness() {
+            true => WITNESS_SPEND_VSIZE,
+            false => LEGACY_SPEND_VSIZE,
+        };
+        let vsize = self.serialize().len() as u64 + spend_vsize;
+        self.value < vsize * DUST_RELAY_FEE / 1000
+    }
+
+    /// Devuelve la direccion en Base58Check de la red recibida a la que se envia el output.
+    /// Devuelve None si el script no es P2PKH ni P2SH.
+    pub fn address(&self, network: Network) -> Option<String> {
+        let (prefix, hash) = match ScriptType::classify(&self.script_pubkey) {
+            ScriptType::P2PKH => (network.p2pkh_prefix(), &self.script_pubkey[3..23]),
+            ScriptType::P2SH => (network.p2sh_prefix(), &self.script_pubkey[2..22]),
+            _ => return None,
+        };
+        let mut payload = vec![prefix];
+        payload.extend(hash);
+        let checksum = Hash32::sha256d(&payload);
+        payload.extend(&checksum.as_bytes()[..4]);
+        Some(bs58::encode(payload).into_string())
+    }
 
     /// Esta funcion se encarga de verificar si un output esta enviado a una clave publica del tipo P2PKH.
     pub fn is_sent_to_key(&self, public_key_hash: &Vec<u8>) -> Result<bool, CustomError> {
@@ -65,8 +100,12 @@ fn compare_p2pkh(
 #[cfg(test)]
 mod tests {
     use crate::{
-        messages::transaction::Transaction, parser::BufferParser, states::utxo_state::UTXO,
-        structs::tx_output::TransactionOutput, wallet::Wallet,
+        messages::transaction::Transaction,
+        network::Network,
+        parser::{BtcEncode, BufferParser},
+        states::utxo_state::UTXO,
+        structs::tx_output::TransactionOutput,
+        wallet::{get_script_pubkey, Wallet},
     };
 
     #[test]
@@ -77,10 +116,47 @@ mod tests {
         };
         let serialized = output.serialize();
         let mut parser = BufferParser::new(serialized);
-        let parsed_output = TransactionOutput::parse(&mut parser).unwrap();
+        let parsed_output = TransactionOutput::decode(&mut parser).unwrap();
         assert_eq!(output, parsed_output);
     }
 
+    #[test]
+    fn dust_threshold() {
+        let mut p2pkh = vec![0x76, 0xa9, 20];
+        p2pkh.extend([1; 20]);
+        p2pkh.extend([0x88, 0xac]);
+        let mut p2wpkh = vec![0x00, 20];
+        p2wpkh.extend([1; 20]);
+        let output = |value, script_pubkey: &Vec<u8>| TransactionOutput {
+            value,
+            script_pubkey: script_pubkey.clone(),
+        };
+
+        assert!(output(545, &p2pkh).is_dust());
+        assert!(!output(546, &p2pkh).is_dust());
+        assert!(output(293, &p2wpkh).is_dust());
+        assert!(!output(294, &p2wpkh).is_dust());
+        assert!(!output(0, &vec![0x6a]).is_dust());
+    }
+
+    #[test]
+    fn output_address() {
+        let address = "mscatccDgq7azndWHFTzvEuZuywCsUvTRu";
+        let output = TransactionOutput {
+            value: 100,
+            script_pubkey: get_script_pubkey(address.to_string()).unwrap(),
+        };
+        assert_eq!(output.address(Network::Testnet), Some(address.to_string()));
+
+        let mut p2wpkh = vec![0x00, 20];
+        p2wpkh.extend([1; 20]);
+        let output = TransactionOutput {
+            value: 100,
+            script_pubkey: p2wpkh,
+        };
+        assert_eq!(output.address(Network::Testnet), None);
+    }
+
     #[test]
     fn is_sent_to_key() {
         let mut found = false;
@@ -115,7 +191,7 @@ mod tests {
         let public_key_hash = wallet.get_pubkey_hash().unwrap();
 
         let mut parser = BufferParser::new(buffer);
-        let tx = Transaction::parse_from_parser(&mut parser).unwrap();
+        let tx = Transaction::decode(&mut parser).unwrap();
         let tx_outputs = tx.outputs.clone();
         for output in tx_outputs {
             found = output.is_sent_to_key(&public_key_hash).unwrap();
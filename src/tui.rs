@@ -0,0 +1,899 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+
+use crate::{
+    error::CustomError,
+    events::GUIEvents,
+    logger::{send_log, Log},
+    loops::node_action_loop::{NodeAction, NodeActionSender},
+    node_state::NodeState,
+    rpc::{error_message, RpcHandler},
+    structs::{
+        amount_unit::AmountUnit, hash32::Hash32, history_query::HistoryQuery,
+        sync_progress::SyncProgress,
+    },
+    wallet::{merge_recipients, Wallet},
+};
+
+/// Cantidad de logs que se muestran en pantalla.
+const TUI_LOG_LINES: usize = 8;
+/// Cantidad de lineas de la salida de los comandos que se muestran en pantalla.
+const TUI_OUTPUT_LINES: usize = 20;
+/// Cantidad de movimientos que muestra el comando history si no se indica otra.
+const DEFAULT_HISTORY_COUNT: usize = 10;
+/// Tiempo minimo entre dos redibujos provocados por eventos del nodo, para no redibujar con cada log.
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+/// Cada cuanto se redibuja la pantalla aunque no haya eventos, ya que los peers y la mempool cambian sin emitir GUIEvents.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+/// Secuencia ANSI que limpia la pantalla y mueve el cursor al inicio.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+/// Secuencias ANSI que guardan y restauran la posicion del cursor, y que lo mueven al inicio de la pantalla.
+const SAVE_CURSOR: &str = "\x1b7";
+const RESTORE_CURSOR: &str = "\x1b8";
+const CURSOR_HOME: &str = "\x1b[H";
+/// Secuencia ANSI que borra el resto de la linea.
+const CLEAR_LINE: &str = "\x1b[K";
+/// Cantidad maxima de caracteres de las lineas del estado, que se recortan para que cada una ocupe una sola fila
+/// y el estado tenga siempre la misma altura.
+const TUI_STATUS_WIDTH: usize = 100;
+/// Comandos cuyos parametros a partir de la posicion indicada son secretos (claves privadas y passphrases),
+/// por lo que no se repiten en la salida.
+const SECRET_PARAMS: [(&str, usize); 4] = [
+    ("addwallet", 3),
+    ("backupwallets", 2),
+    ("restorewallets", 2),
+    ("removewallet", 3),
+];
+
+/// Comandos propios de la interfaz de terminal, como se muestran en la ayuda.
+const TUI_HELP: [&str; 18] = [
+    "wallets",
+    "wallet <index>",
+    "addwallet <name> <pubkey> <privkey>",
+    "renamewallet <index> <name>",
+    "movewallet <index> <new index>",
+    "removewallet <index> confirm|<backup path> <passphrase>",
+    "backupwallets <path> <passphrase>",
+    "restorewallets <path> <passphrase>",
+    "history [count]",
+    "send <address|label> <amount BTC> [<address|label> <amount BTC> ...] <fee BTC> [locktime <height|timestamp>]",
+    "consolidate <fee rate sat/vB> [max amount BTC]",
+    "accelerate <txid> <fee rate sat/vB>",
+    "contacts",
+    "addcontact <address> <label>",
+    "removecontact <address>",
+    "label <txid> [label]",
+    "clear",
+    "quit",
+];
+
+#[derive(Debug, PartialEq)]
+/// TuiCommand representa los comandos que se ingresan en la interfaz de terminal:
+/// - Help: Lista los comandos de la terminal y los comandos RPC.
+/// - Quit: Cierra la aplicacion.
+/// - Clear: Limpia la salida de los comandos.
+/// - Wallets: Lista las wallets, marcando la activa.
+/// - Wallet: Cambia la wallet activa por la del indice recibido.
+/// - AddWallet: Agrega una wallet con su nombre, clave publica y clave privada.
+/// - RenameWallet: Cambia el nombre de la wallet del indice recibido.
+/// - MoveWallet: Mueve la wallet del primer indice a la posicion del segundo.
+/// - RemoveWallet: Quita la wallet del indice recibido, guardando antes sus claves en un backup cifrado si se indica el path y la passphrase.
+/// - BackupWallets: Guarda las wallets y la libreta de direcciones en un backup cifrado con la passphrase.
+/// - RestoreWallets: Restaura un backup cifrado, agregando las wallets, movimientos y etiquetas que falten.
+/// - History: Muestra los ultimos movimientos de la wallet activa.
+/// - Send: Envia montos a una o mas direcciones (o etiquetas de la libreta) desde la wallet activa, con el fee
+///   y opcionalmente el lock time. Los montos y el fee se ingresan en BTC y se guardan en satoshis.
+/// - Consolidate: Junta los UTXO de la wallet activa de hasta un monto (todos si no se indica) en una salida, con el fee rate en satoshis por byte virtual.
+///   El monto se ingresa en BTC y se guarda en satoshis.
+/// - Accelerate: Acelera una pending tx que le envia fondos a la wallet activa gastandolos con una transaccion hija,
+///   para que las dos juntas paguen el fee rate en satoshis por byte virtual (CPFP).
+/// - Contacts: Lista las direcciones de la libreta de direcciones con sus etiquetas.
+/// - AddContact: Agrega una direccion a la libreta con su etiqueta, o le cambia la etiqueta.
+/// - RemoveContact: Quita una direccion de la libreta.
+/// - Label: Etiqueta el movimiento de una transaccion, o le quita la etiqueta si no se indica ninguna.
+/// - Rpc: Cualquier otra linea, que se ejecuta como un comando RPC (getblock, getpeerinfo, etc.).
+pub enum TuiCommand {
+    Help,
+    Quit,
+    Clear,
+    Wallets,
+    Wallet(usize),
+    AddWallet(String, String, String),
+    RenameWallet(usize, String),
+    MoveWallet(usize, usize),
+    RemoveWallet(usize, Option<(String, String)>),
+    BackupWallets(String, String),
+    RestoreWallets(String, String),
+    History(usize),
+    Send(Vec<(String, u64)>, u64, Option<u32>),
+    Consolidate(u64, Option<u64>),
+    Accelerate(Hash32, u64),
+    Contacts,
+    AddContact(String, String),
+    RemoveContact(String),
+    Label(Hash32, String),
+    Rpc(String),
+}
+
+impl FromStr for TuiCommand {
+    type Err = CustomError;
+
+    /// Parsea una linea ingresada: el nombre del comando seguido de sus parametros separados por espacios.
+    /// Los montos de send se ingresan en BTC.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.as_slice() {
+            ["help"] => Self::Help,
+            ["quit"] | ["exit"] => Self::Quit,
+            ["clear"] => Self::Clear,
+            ["wallets"] => Self::Wallets,
+            ["wallet", index] => Self::Wallet(parse_param(index)?),
+            ["addwallet", name, pubkey, privkey] => {
+                Self::AddWallet(name.to_string(), pubkey.to_string(), privkey.to_string())
+            }
+            ["renamewallet", index, name @ ..] if !name.is_empty() => {
+                Self::RenameWallet(parse_param(index)?, name.join(" "))
+            }
+            ["movewallet", index, new_index] => {
+                Self::MoveWallet(parse_param(index)?, parse_param(new_index)?)
+            }
+            ["removewallet", index, "confirm"] => Self::RemoveWallet(parse_param(index)?, None),
+            ["removewallet", index, path, passphrase] => Self::RemoveWallet(
+                parse_param(index)?,
+                Some((path.to_string(), passphrase.to_string())),
+            ),
+            ["backupwallets", path, passphrase] => {
+                Self::BackupWallets(path.to_string(), passphrase.to_string())
+            }
+            ["restorewallets", path, passphrase] => {
+                Self::RestoreWallets(path.to_string(), passphrase.to_string())
+            }
+            ["history"] => Self::History(DEFAULT_HISTORY_COUNT),
+            ["history", count] => Self::History(parse_param(count)?),
+            ["send", recipients @ .., fee, "locktime", lock_time]
+                if recipients.len() >= 2 && recipients.len() % 2 == 0 =>
+            {
+                parse_send(recipients, fee, Some(parse_param(lock_time)?))?
+            }
+            ["send", recipients @ .., fee]
+                if recipients.len() >= 2 && recipients.len() % 2 == 0 =>
+            {
+                parse_send(recipients, fee, None)?
+            }
+            ["consolidate", fee_rate] => Self::Consolidate(parse_param(fee_rate)?, None),
+            ["consolidate", fee_rate, max_value] => Self::Consolidate(
+                parse_param(fee_rate)?,
+                Some(AmountUnit::Btc.parse(max_value)?),
+            ),
+            ["accelerate", txid, fee_rate] => {
+                Self::Accelerate(parse_param(txid)?, parse_param(fee_rate)?)
+            }
+            ["contacts"] => Self::Contacts,
+            ["addcontact", address, label @ ..] if !label.is_empty() => {
+                Self::AddContact(address.to_string(), label.join(" "))
+            }
+            ["removecontact", address] => Self::RemoveContact(address.to_string()),
+            ["label", txid, label @ ..] => Self::Label(parse_param(txid)?, label.join(" ")),
+            [command, ..] => match TUI_HELP
+                .iter()
+                .find(|usage| usage.split(' ').next() == Some(command))
+            {
+                Some(usage) => {
+                    return Err(CustomError::Validation(format!(
+                        "Invalid parameters, usage: {usage}"
+                    )))
+                }
+                None => Self::Rpc(line.to_string()),
+            },
+            [] => return Err(CustomError::Validation(String::from("Empty command"))),
+        };
+        Ok(command)
+    }
+}
+
+/// Devuelve la wallet del indice recibido, como se listan con el comando wallets.
+fn wallet_at(node_state: &NodeState, index: usize) -> Result<Wallet, CustomError> {
+    node_state
+        .get_wallets()?
+        .into_iter()
+        .nth(index)
+        .ok_or(CustomError::WalletNotFound)
+}
+
+fn parse_param<T: FromStr>(value: &str) -> Result<T, CustomError> {
+    value.parse().map_err(|_| CustomError::InvalidValue)
+}
+
+/// Parsea los pares de direccion y monto en BTC y el fee en BTC del comando send.
+fn parse_send(
+    recipients: &[&str],
+    fee: &str,
+    lock_time: Option<u32>,
+) -> Result<TuiCommand, CustomError> {
+    let recipients = recipients
+        .chunks(2)
+        .map(|pair| Ok((pair[0].to_string(), AmountUnit::Btc.parse(pair[1])?)))
+        .collect::<Result<_, CustomError>>()?;
+    Ok(TuiCommand::Send(
+        recipients,
+        AmountUnit::Btc.parse(fee)?,
+        lock_time,
+    ))
+}
+
+/// TuiInput son las entradas que recibe el loop de la interfaz de terminal:
+/// - Event: GUIEvents emitido por el nodo.
+/// - Command: Linea ingresada por el usuario.
+/// - StdinClosed: Se cerro la entrada estandar (por ejemplo con Ctrl-D).
+enum TuiInput {
+    Event(GUIEvents),
+    Command(String),
+    StdinClosed,
+}
+
+/// Tui es la interfaz de terminal del nodo, alternativa a la interfaz grafica para usarla en servidores por SSH.
+/// Consume los mismos GUIEvents que la interfaz grafica y muestra el estado de la sincronizacion, los peers,
+/// la wallet activa y los ultimos logs, redibujando la pantalla con secuencias ANSI.
+/// Los comandos se ingresan por linea: los de la terminal (wallets, send, etc.) y los comandos RPC de la consola de depuracion.
+/// No usa un crate de interfaces de terminal (como ratatui o ncurses), que agregaria dependencias nativas o de compilacion
+/// para una pantalla de solo lectura: la terminal queda en modo canonico y la edicion de la linea la hace la propia terminal.
+/// La pantalla completa solo se redibuja al ingresar un comando; los eventos del nodo reescriben unicamente el estado,
+/// que ocupa las primeras filas con una altura fija, guardando y restaurando el cursor para no borrar la linea que se esta escribiendo.
+/// Los elementos son:
+/// - node_state_ref: Referencia al estado del nodo.
+/// - logger_sender: Sender para enviar logs al logger.
+/// - node_action_sender: Sender para enviar acciones al nodo.
+/// - rpc_handler: Capa de comandos RPC que ejecuta los comandos que no son propios de la terminal.
+/// - sync_progress: Ultimo progreso de la sincronizacion recibido.
+/// - node_state_ready: Indica si el nodo ya se sincronizo.
+/// - logs: Ultimos TUI_LOG_LINES logs recibidos.
+/// - output: Ultimas TUI_OUTPUT_LINES lineas de la salida de los comandos.
+pub struct Tui {
+    node_state_ref: Arc<NodeState>,
+    logger_sender: mpsc::Sender<Log>,
+    node_action_sender: NodeActionSender,
+    rpc_handler: RpcHandler,
+    sync_progress: Option<SyncProgress>,
+    node_state_ready: bool,
+    logs: VecDeque<String>,
+    output: VecDeque<String>,
+}
+
+impl Tui {
+    /// Inicia la interfaz de terminal y se bloquea hasta que el usuario la cierra o se recibe GUIEvents::Terminate.
+    /// Lee los GUIEvents del receiver recibido y los comandos de la entrada estandar, cada uno en su propio thread.
+    pub fn start(
+        events_receiver: mpsc::Receiver<GUIEvents>,
+        node_state_ref: Arc<NodeState>,
+        logger_sender: mpsc::Sender<Log>,
+        node_action_sender: NodeActionSender,
+    ) -> Result<(), CustomError> {
+        let (input_sender, input_receiver) = mpsc::channel();
+
+        let events_sender = input_sender.clone();
+        thread::spawn(move || {
+            while let Ok(event) = events_receiver.recv() {
+                if events_sender.send(TuiInput::Event(event)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // El thread queda bloqueado leyendo la entrada estandar hasta que termina el proceso
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if input_sender.send(TuiInput::Command(line)).is_err() {
+                    return;
+                }
+            }
+            input_sender.send(TuiInput::StdinClosed).ok();
+        });
+
+        let mut tui = Self {
+            rpc_handler: RpcHandler {
+                node_state_ref: node_state_ref.clone(),
+                node_action_sender: node_action_sender.clone(),
+            },
+            node_state_ref,
+            logger_sender,
+            node_action_sender,
+            sync_progress: None,
+            node_state_ready: false,
+            logs: VecDeque::new(),
+            output: VecDeque::new(),
+        };
+        tui.event_loop(input_receiver)
+    }
+
+    /// Redibuja la pantalla despues de cada comando, y actualiza el estado a lo sumo una vez por REDRAW_INTERVAL
+    /// cuando llegan eventos y cada REFRESH_INTERVAL si no llega ninguno.
+    fn event_loop(&mut self, input_receiver: mpsc::Receiver<TuiInput>) -> Result<(), CustomError> {
+        self.draw()?;
+        let mut last_draw = Instant::now();
+        let mut pending_draw = false;
+
+        loop {
+            let timeout = match pending_draw {
+                true => REDRAW_INTERVAL.saturating_sub(last_draw.elapsed()),
+                false => REFRESH_INTERVAL,
+            };
+            match input_receiver.recv_timeout(timeout) {
+                Ok(TuiInput::Event(event)) => {
+                    if !self.handle_events(&event) {
+                        break;
+                    }
+                    pending_draw = true;
+                }
+                Ok(TuiInput::Command(line)) => {
+                    if !self.handle_command(&line) {
+                        break;
+                    }
+                    self.draw()?;
+                    last_draw = Instant::now();
+                    pending_draw = false;
+                    continue;
+                }
+                Ok(TuiInput::StdinClosed) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => pending_draw = true,
+            }
+
+            if pending_draw && last_draw.elapsed() >= REDRAW_INTERVAL {
+                self.refresh_status()?;
+                last_draw = Instant::now();
+                pending_draw = false;
+            }
+        }
+
+        print!("{CLEAR_SCREEN}");
+        io::stdout().flush()?;
+        Ok(())
+    }
+
+    /// Maneja los GUIEvents recibidos y hace las acciones acorde a cada evento.
+    /// Para Log: Guarda el log para mostrarlo.
+    /// Para SyncProgress: Guarda el progreso de la sincronizacion.
+    /// Para NodeStateReady: Indica que el nodo ya se sincronizo.
+    /// Para TransactionSent: Informa en la salida que se envio la transaccion.
+    /// Para Terminate: Devuelve false para cerrar la interfaz.
+    /// El resto de los eventos solo provocan que se redibuje la pantalla.
+    pub fn handle_events(&mut self, message: &GUIEvents) -> bool {
+        match message {
+            GUIEvents::Log(log) => {
+                let formatted_time = Local::now().format("%Y-%m-%d %H:%M:%S");
+                push_limited(
+                    &mut self.logs,
+                    format!("[{formatted_time}] {log}"),
+                    TUI_LOG_LINES,
+                );
+            }
+            GUIEvents::SyncProgress(progress) => self.sync_progress = Some(progress.clone()),
+            GUIEvents::NodeStateReady => self.node_state_ready = true,
+            GUIEvents::TransactionSent => self.print(String::from("Transaction sent")),
+            GUIEvents::Terminate => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Ejecuta la linea ingresada y muestra su resultado en la salida.
+    /// Devuelve false si se pidio cerrar la interfaz.
+    fn handle_command(&mut self, line: &str) -> bool {
+        if line.trim().is_empty() {
+            return true;
+        }
+        self.print(format!("> {}", redact_secrets(line)));
+
+        let result = line
+            .parse::<TuiCommand>()
+            .and_then(|command| self.execute(command));
+        match result {
+            Ok(Some(output)) => self.print(output),
+            Ok(None) => return false,
+            Err(error) => self.print(error_message(&error)),
+        }
+        true
+    }
+
+    /// Ejecuta el comando y devuelve el texto a mostrar, o None si se pidio cerrar la interfaz.
+    fn execute(&mut self, command: TuiCommand) -> Result<Option<String>, CustomError> {
+        let node_state = &self.node_state_ref;
+
+        let output = match command {
+            TuiCommand::Help => format!(
+                "Terminal commands:\n  {}\nRPC commands:\n  {}",
+                TUI_HELP.join("\n  "),
+                self.rpc_handler.execute_line("help")?.replace('\n', "\n  ")
+            ),
+            TuiCommand::Quit => return Ok(None),
+            TuiCommand::Clear => {
+                self.output.clear();
+                return Ok(Some(String::new()));
+            }
+            TuiCommand::Wallets => {
+                let active = node_state.get_active_wallet()?.map(|wallet| wallet.pubkey);
+                let wallets: Vec<String> = node_state
+                    .get_wallets()?
+                    .iter()
+                    .enumerate()
+                    .map(|(index, wallet)| {
+                        let marker = match Some(&wallet.pubkey) == active.as_ref() {
+                            true => "*",
+                            false => " ",
+                        };
+                        format!("{marker} {index}: {} ({})", wallet.name, wallet.pubkey)
+                    })
+                    .collect();
+                match wallets.is_empty() {
+                    true => String::from("No wallets, add one with addwallet"),
+                    false => wallets.join("\n"),
+                }
+            }
+            TuiCommand::Wallet(index) => {
+                let wallet = wallet_at(node_state, index)?;
+                node_state.change_wallet(wallet.pubkey)?;
+                format!("Active wallet: {}", wallet.name)
+            }
+            TuiCommand::AddWallet(name, pubkey, privkey) => {
+                node_state.append_wallet(name.clone(), pubkey, privkey)?;
+                format!("Wallet {name} added")
+            }
+            TuiCommand::RenameWallet(index, name) => {
+                let wallet = wallet_at(node_state, index)?;
+                node_state.rename_wallet(&wallet.pubkey, &name)?;
+                format!("Wallet {} renamed to {}", wallet.name, name.trim())
+            }
+            TuiCommand::MoveWallet(index, new_index) => {
+                let wallet = wallet_at(node_state, index)?;
+                node_state.move_wallet(&wallet.pubkey, new_index)?;
+                format!("Wallet {} moved to position {new_index}", wallet.name)
+            }
+            TuiCommand::RemoveWallet(index, key_backup) => {
+                let wallet = wallet_at(node_state, index)?;
+                let key_backup = key_backup
+                    .as_ref()
+                    .map(|(path, passphrase)| (path.as_str(), passphrase.as_str()));
+                node_state.remove_wallet(&wallet.pubkey, key_backup)?;
+                match key_backup {
+                    Some((path, _)) => {
+                        format!(
+                            "Wallet {} removed, its keys were saved to {path}",
+                            wallet.name
+                        )
+                    }
+                    None => format!("Wallet {} removed", wallet.name),
+                }
+            }
+            TuiCommand::BackupWallets(path, passphrase) => {
+                let count = node_state.export_wallets_backup(&path, &passphrase)?;
+                format!("Backup of {count} wallets saved to {path}")
+            }
+            TuiCommand::RestoreWallets(path, passphrase) => {
+                let added = node_state.restore_wallets_backup(&path, &passphrase)?;
+                format!("Backup restored from {path}, {added} wallets added")
+            }
+            TuiCommand::History(count) => {
+                let page = node_state.query_active_wallet_history(&HistoryQuery {
+                    limit: count,
+                    ..Default::default()
+                })?;
+                let movements: Vec<String> = page
+                    .movements
+                    .iter()
+                    .map(|(movement, _)| {
+                        let status = match (movement.block_hash.is_some(), movement.conflicted) {
+                            (true, _) => "confirmed",
+                            (false, false) => "pending",
+                            (false, true) => "conflicted",
+                        };
+                        let label = node_state
+                            .get_movement_label(&movement.tx_hash)
+                            .ok()
+                            .flatten()
+                            .unwrap_or_else(|| movement.tx_hash.to_string());
+                        format!(
+                            "{label} {:>20} {status}",
+                            AmountUnit::Btc.format(movement.value)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{}\nShowing {} of {} movements",
+                    movements.join("\n"),
+                    page.movements.len(),
+                    page.total
+                )
+            }
+            TuiCommand::Send(recipients, fee, lock_time) => {
+                let outputs = merge_recipients(&node_state.resolve_recipients(recipients)?)?;
+                let summary = node_state.summarize_transaction(outputs.clone(), fee, lock_time)?;
+                self.node_action_sender
+                    .try_send(NodeAction::MakeTransaction((outputs, fee, lock_time)))?;
+                format!(
+                    "{}\nCreating transaction...",
+                    summary.describe(|value| AmountUnit::Btc.format(value))
+                )
+            }
+            TuiCommand::Consolidate(fee_rate, max_value) => {
+                let preview = node_state.preview_consolidation(max_value, fee_rate)?;
+                self.node_action_sender
+                    .try_send(NodeAction::ConsolidateUtxos(max_value, fee_rate))?;
+                format!(
+                    "{}\nCreating transaction...",
+                    preview.describe(|value| AmountUnit::Btc.format(value))
+                )
+            }
+            TuiCommand::Accelerate(tx_hash, fee_rate) => {
+                let preview = node_state.preview_acceleration(tx_hash, fee_rate)?;
+                self.node_action_sender
+                    .try_send(NodeAction::AccelerateTransaction(tx_hash, fee_rate))?;
+                format!(
+                    "{}\nCreating transaction...",
+                    preview.describe(|value| AmountUnit::Btc.format(value))
+                )
+            }
+            TuiCommand::Contacts => {
+                let contacts: Vec<String> = node_state
+                    .get_contacts()?
+                    .iter()
+                    .map(|(label, address)| format!("{label}: {address}"))
+                    .collect();
+                match contacts.is_empty() {
+                    true => String::from("No contacts, add one with addcontact"),
+                    false => contacts.join("\n"),
+                }
+            }
+            TuiCommand::AddContact(address, label) => {
+                node_state.set_contact(address.clone(), label.clone())?;
+                format!("Contact {label} saved for {address}")
+            }
+            TuiCommand::RemoveContact(address) => match node_state.remove_contact(&address)? {
+                true => format!("Contact {address} removed"),
+                false => format!("{address} is not in the address book"),
+            },
+            TuiCommand::Label(tx_hash, label) => {
+                node_state.set_movement_label(tx_hash, label)?;
+                format!("Label of {tx_hash} updated")
+            }
+            TuiCommand::Rpc(line) => self.rpc_handler.execute_line(&line)?,
+        };
+
+        Ok(Some(output))
+    }
+
+    fn print(&mut self, text: String) {
+        for line in text.lines() {
+            push_limited(&mut self.output, line.to_string(), TUI_OUTPUT_LINES);
+        }
+    }
+
+    /// Limpia la pantalla y la dibuja completa, dejando el cursor en el prompt.
+    fn draw(&self) -> Result<(), CustomError> {
+        let status = match self.render_status() {
+            Ok(status) => status,
+            Err(error) => {
+                send_log(&self.logger_sender, Log::Error(error));
+                return Ok(());
+            }
+        };
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{CLEAR_SCREEN}")?;
+        for line in status {
+            writeln!(stdout, "{line}")?;
+        }
+        writeln!(stdout, "\n--- Output ---")?;
+        for line in &self.output {
+            writeln!(stdout, "{line}")?;
+        }
+        write!(stdout, "> ")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Reescribe solo las filas del estado, restaurando despues el cursor a donde estaba,
+    /// por lo que la linea que el usuario esta escribiendo no se borra.
+    fn refresh_status(&self) -> Result<(), CustomError> {
+        let status = match self.render_status() {
+            Ok(status) => status,
+            Err(error) => {
+                send_log(&self.logger_sender, Log::Error(error));
+                return Ok(());
+            }
+        };
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{SAVE_CURSOR}{CURSOR_HOME}")?;
+        for line in status {
+            writeln!(stdout, "{line}{CLEAR_LINE}")?;
+        }
+        write!(stdout, "{RESTORE_CURSOR}")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Genera las filas del estado: el estado del nodo, la wallet activa y los ultimos logs.
+    /// Siempre devuelve la misma cantidad de filas, de a lo sumo TUI_STATUS_WIDTH caracteres.
+    fn render_status(&self) -> Result<Vec<String>, CustomError> {
+        let node_state = &self.node_state_ref;
+        let mut status = vec![
+            format!(
+                "Rust-eze ({:?})  -  help lists the commands",
+                node_state.get_network()
+            ),
+            String::new(),
+            format!(
+                "Sync:    {}",
+                sync_status(self.sync_progress.as_ref(), self.node_state_ready)
+            ),
+        ];
+        let node_status = node_state.get_status()?;
+        status.push(format!(
+            "Blocks:  {}    Headers: {}    UTXOs: {}    Disk: {:.1} MB",
+            node_status.tip_height,
+            node_status.header_height,
+            node_status.utxo_size,
+            node_status.disk_usage as f64 / 1_000_000.0
+        ));
+        status.push(format!(
+            "Peers:   {}    Mempool: {} txs",
+            node_status.peers, node_status.mempool_size
+        ));
+
+        match node_state.get_active_wallet()? {
+            Some(wallet) => {
+                let available = node_state.get_active_wallet_balance()?;
+                let pending: i64 = node_state
+                    .get_active_wallet_pending_txs()?
+                    .iter()
+                    .map(|movement| movement.value)
+                    .sum();
+                status.push(format!(
+                    "Wallet:  {}    Available: {}    Pending: {}",
+                    wallet.name,
+                    AmountUnit::Btc.format(available as i64),
+                    AmountUnit::Btc.format(pending)
+                ));
+            }
+            None => status.push(String::from(
+                "Wallet:  none, select one with wallet <index>",
+            )),
+        }
+
+        status.push(String::new());
+        status.push(String::from("--- Logs ---"));
+        status.extend(self.logs.iter().cloned());
+        status.resize(
+            status.len() + TUI_LOG_LINES - self.logs.len(),
+            String::new(),
+        );
+        Ok(status
+            .into_iter()
+            .map(|line| {
+                line.replace('\n', " ")
+                    .chars()
+                    .take(TUI_STATUS_WIDTH)
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/// Devuelve la linea ingresada reemplazando los parametros secretos de SECRET_PARAMS por asteriscos.
+fn redact_secrets(line: &str) -> String {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let secret_start = SECRET_PARAMS
+        .iter()
+        .find(|(command, _)| words.first() == Some(command))
+        .map(|(_, position)| *position);
+    words
+        .iter()
+        .enumerate()
+        .map(|(position, word)| match secret_start {
+            Some(start) if position >= start => "****",
+            _ => word,
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Describe el estado de la sincronizacion: el progreso de los headers o de los bloques, o si ya se sincronizo.
+fn sync_status(progress: Option<&SyncProgress>, node_state_ready: bool) -> String {
+    match (progress, node_state_ready) {
+        (_, true) => String::from("synced"),
+        (None, false) => String::from("connecting to peers..."),
+        (Some(progress), false) if !progress.headers_synced => format!(
+            "downloading headers {} / ~{} ({:.1}%)",
+            progress.header_height,
+            progress.network_height,
+            progress.fraction() * 100.0
+        ),
+        (Some(progress), false) => format!(
+            "downloading blocks {} / {} ({:.1}%, {:.1} blocks/s)",
+            progress.blocks_downloaded,
+            progress.blocks_total,
+            progress.fraction() * 100.0,
+            progress.blocks_per_second
+        ),
+    }
+}
+
+/// Agrega una linea al final, descartando las mas viejas si se supera el limite.
+fn push_limited(lines: &mut VecDeque<String>, line: String, limit: usize) {
+    lines.push_back(line);
+    while lines.len() > limit {
+        lines.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_terminal_commands() {
+        assert_eq!("quit".parse::<TuiCommand>().unwrap(), TuiCommand::Quit);
+        assert_eq!(
+            "wallet 2".parse::<TuiCommand>().unwrap(),
+            TuiCommand::Wallet(2)
+        );
+        assert_eq!(
+            "renamewallet 1 Cold storage".parse::<TuiCommand>().unwrap(),
+            TuiCommand::RenameWallet(1, String::from("Cold storage"))
+        );
+        assert_eq!(
+            "removewallet 0 confirm".parse::<TuiCommand>().unwrap(),
+            TuiCommand::RemoveWallet(0, None)
+        );
+        assert_eq!(
+            "removewallet 0 keys.backup secret"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::RemoveWallet(
+                0,
+                Some((String::from("keys.backup"), String::from("secret")))
+            )
+        );
+        assert_eq!(
+            "restorewallets wallets.backup secret"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::RestoreWallets(String::from("wallets.backup"), String::from("secret"))
+        );
+        assert_eq!(
+            "history".parse::<TuiCommand>().unwrap(),
+            TuiCommand::History(DEFAULT_HISTORY_COUNT)
+        );
+        assert_eq!(
+            "send mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm 0.5 0.0001"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::Send(
+                vec![(
+                    String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"),
+                    50_000_000
+                )],
+                10_000,
+                None
+            )
+        );
+        assert_eq!(
+            "send mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm 0.5 mscatccDgq7azndWHFTzvEuZuywCsUvTRu 1 0.0001"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::Send(
+                vec![
+                    (String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"), 50_000_000),
+                    (String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"), 100_000_000)
+                ],
+                10_000,
+                None
+            )
+        );
+        assert_eq!(
+            "send mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm 0.5 0.0001 locktime 800000"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::Send(
+                vec![(
+                    String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"),
+                    50_000_000
+                )],
+                10_000,
+                Some(800_000)
+            )
+        );
+        assert_eq!(
+            "consolidate 2 0.001".parse::<TuiCommand>().unwrap(),
+            TuiCommand::Consolidate(2, Some(100_000))
+        );
+        assert_eq!(
+            format!("accelerate {} 20", Hash32::default())
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::Accelerate(Hash32::default(), 20)
+        );
+        assert_eq!(
+            "addcontact mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm My exchange"
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::AddContact(
+                String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm"),
+                String::from("My exchange")
+            )
+        );
+        assert_eq!(
+            format!("label {} Exchange withdrawal", Hash32::default())
+                .parse::<TuiCommand>()
+                .unwrap(),
+            TuiCommand::Label(Hash32::default(), String::from("Exchange withdrawal"))
+        );
+    }
+
+    #[test]
+    fn secrets_are_not_echoed() {
+        assert_eq!(
+            redact_secrets("addwallet savings mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm cVK6pF1sfsvvm"),
+            "addwallet savings mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm ****"
+        );
+        assert_eq!(
+            redact_secrets("backupwallets /tmp/wallets.bak  correct horse"),
+            "backupwallets /tmp/wallets.bak **** ****"
+        );
+        assert_eq!(
+            redact_secrets("restorewallets /tmp/wallets.bak secret"),
+            "restorewallets /tmp/wallets.bak ****"
+        );
+        assert_eq!(
+            redact_secrets("removewallet 1 /tmp/wallet.bak secret"),
+            "removewallet 1 /tmp/wallet.bak ****"
+        );
+        assert_eq!(
+            redact_secrets("removewallet 1 confirm"),
+            "removewallet 1 confirm"
+        );
+        assert_eq!(redact_secrets(" getblockcount "), "getblockcount");
+    }
+
+    #[test]
+    fn unknown_commands_are_rpc_commands() {
+        assert_eq!(
+            "getpeerinfo".parse::<TuiCommand>().unwrap(),
+            TuiCommand::Rpc(String::from("getpeerinfo"))
+        );
+        assert!("wallet".parse::<TuiCommand>().is_err());
+        assert!("wallet x".parse::<TuiCommand>().is_err());
+        assert!("send address -1 0".parse::<TuiCommand>().is_err());
+        assert!("send address 1 address 0".parse::<TuiCommand>().is_err());
+        assert!("addcontact address".parse::<TuiCommand>().is_err());
+        assert!("backupwallets wallets.backup"
+            .parse::<TuiCommand>()
+            .is_err());
+        assert!("removewallet 0".parse::<TuiCommand>().is_err());
+        assert!("movewallet 0".parse::<TuiCommand>().is_err());
+        assert!("consolidate 0.5".parse::<TuiCommand>().is_err());
+        assert!("accelerate txid 5".parse::<TuiCommand>().is_err());
+        assert!("label txid Savings".parse::<TuiCommand>().is_err());
+        assert!("  ".parse::<TuiCommand>().is_err());
+    }
+
+    #[test]
+    fn describe_sync_status() {
+        assert_eq!(sync_status(None, false), "connecting to peers...");
+        let progress = SyncProgress::new(50, 100, false, 0, 0);
+        assert_eq!(
+            sync_status(Some(&progress), false),
+            "downloading headers 50 / ~100 (50.0%)"
+        );
+        assert_eq!(sync_status(Some(&progress), true), "synced");
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
-    fs::OpenOptions,
-    net::{SocketAddr, SocketAddrV6, TcpStream, ToSocketAddrs},
+    collections::hash_map::RandomState,
+    fs::{self, File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::{Read, Write},
+    net::{IpAddr, SocketAddr, SocketAddrV6, TcpStream, ToSocketAddrs},
+    thread,
     time::{Duration, SystemTime},
     vec::IntoIter,
 };
 
-use crate::{error::CustomError, structs::block_header::BlockHeader};
+use crate::{error::CustomError, proxy::Proxy, structs::block_header::BlockHeader};
 
 /// get_addresses resuelve la direccion del seed y devuelve un iterador de direcciones.
 pub fn get_addresses(seed: String, port: u16) -> Result<IntoIter<SocketAddr>, CustomError> {
@@ -14,11 +18,82 @@ pub fn get_addresses(seed: String, port: u16) -> Result<IntoIter<SocketAddr>, Cu
         .map_err(|_| CustomError::CannotResolveSeedAddress)
 }
 
-/// open_stream abre un stream a la direccion recibida.
+/// get_seed_addresses consulta en paralelo las semillas DNS recibidas y devuelve sus direcciones sin repetir,
+/// manteniendo el orden de las semillas: si una semilla falla o devuelve pocas direcciones, se usan las de la siguiente.
+/// Si entre todas no se obtienen min_addresses direcciones, se consultan tambien las fallback_seeds.
+/// Devuelve CustomError si no se obtuvo ninguna direccion.
+pub fn get_seed_addresses(
+    seeds: &[String],
+    fallback_seeds: &[&str],
+    port: u16,
+    min_addresses: usize,
+) -> Result<IntoIter<SocketAddr>, CustomError> {
+    let fallback_seeds = fallback_seeds.iter().map(|seed| seed.to_string()).collect();
+    let mut addresses: Vec<SocketAddr> = vec![];
+
+    for seeds in [seeds.to_vec(), fallback_seeds] {
+        if addresses.len() >= min_addresses {
+            break;
+        }
+        for seed_addresses in resolve_seeds(seeds, port) {
+            for address in seed_addresses {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+    }
+
+    if addresses.is_empty() {
+        return Err(CustomError::CannotResolveSeedAddress);
+    }
+    Ok(addresses.into_iter())
+}
+
+/// get_literal_seed_addresses devuelve las semillas que ya son direcciones IP, descartando las que son nombres de dominio.
+/// Se usa cuando hay un proxy configurado, ya que resolver las semillas DNS revela fuera del proxy que se corre un nodo;
+/// en ese caso el nodo se conecta con las direcciones conocidas de ejecuciones anteriores y con estas semillas.
+pub fn get_literal_seed_addresses(seeds: &[String], port: u16) -> IntoIter<SocketAddr> {
+    let mut addresses: Vec<SocketAddr> = vec![];
+    for seed in seeds {
+        let address = match seed.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, port),
+            Err(_) => match seed.parse::<SocketAddr>() {
+                Ok(address) => address,
+                Err(_) => continue,
+            },
+        };
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    addresses.into_iter()
+}
+
+/// Resuelve cada semilla en su propio thread, devolviendo las direcciones de las que se pudieron resolver en el orden recibido.
+fn resolve_seeds(seeds: Vec<String>, port: u16) -> Vec<IntoIter<SocketAddr>> {
+    let threads: Vec<_> = seeds
+        .into_iter()
+        .map(|seed| thread::spawn(move || get_addresses(seed, port)))
+        .collect();
+    threads
+        .into_iter()
+        .filter_map(|thread| thread.join().ok()?.ok())
+        .collect()
+}
+
+/// open_stream abre un stream a la direccion recibida, esperando como maximo el timeout recibido.
+/// Si se recibe un proxy SOCKS5, la conexion se abre a traves de el.
 /// Devuelve un error si no se puede conectar.
-pub fn open_stream(address: SocketAddr) -> Result<TcpStream, CustomError> {
-    TcpStream::connect_timeout(&address, Duration::from_millis(500))
-        .map_err(|_| CustomError::CannotConnectToNode)
+pub fn open_stream(
+    address: SocketAddr,
+    timeout: Duration,
+    proxy: Option<&Proxy>,
+) -> Result<TcpStream, CustomError> {
+    if let Some(proxy) = proxy {
+        return proxy.connect(address, timeout);
+    }
+    TcpStream::connect_timeout(&address, timeout).map_err(|_| CustomError::CannotConnectToNode)
 }
 
 /// get_address_v6 devuelve una direccion ipv6 desde un address.
@@ -32,6 +107,15 @@ pub fn get_address_v6(address: SocketAddr) -> SocketAddrV6 {
     SocketAddrV6::new(ip_v6, address.port(), 0, 0)
 }
 
+/// get_socket_address devuelve un address desde una direccion ipv6.
+/// Si la direccion es una ipv4 mapeada a ipv6, devuelve la direccion ipv4.
+pub fn get_socket_address(address: SocketAddrV6) -> SocketAddr {
+    match address.ip().to_ipv4_mapped() {
+        Some(ip_v4) => SocketAddr::new(ip_v4.into(), address.port()),
+        None => SocketAddr::V6(address),
+    }
+}
+
 /// open_new_file abre un archivo en la ubicacion recibida.
 /// Si el archivo no existe, lo crea.
 /// Si el archivo existe, lo abre.
@@ -47,6 +131,36 @@ pub fn open_new_file(path_to_file: String, append: bool) -> Result<std::fs::File
     Ok(file)
 }
 
+/// write_file_atomically reemplaza el contenido del archivo en la ubicacion recibida.
+/// Escribe el contenido en un archivo temporal, fuerza su escritura en disco y lo renombra sobre el original,
+/// de forma que un cierre inesperado deja el archivo anterior o el nuevo, pero nunca uno a medio escribir.
+pub fn write_file_atomically(path: &str, buffer: &[u8]) -> Result<(), CustomError> {
+    let temp_path = format!("{path}.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(buffer)?;
+    file.sync_all()?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// dir_size devuelve la suma de los tamaños de los archivos del directorio recibido y de sus subdirectorios.
+/// Devuelve 0 si el directorio no existe.
+pub fn dir_size(path: &str) -> Result<u64, CustomError> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Ok(0);
+    };
+    let mut size = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += match metadata.is_dir() {
+            true => dir_size(&entry.path().to_string_lossy())?,
+            false => metadata.len(),
+        };
+    }
+    Ok(size)
+}
+
 /// get_current_timestamp devuelve el timestamp actual.
 pub fn get_current_timestamp() -> Result<u64, CustomError> {
     Ok(SystemTime::now()
@@ -61,6 +175,40 @@ pub fn get_current_timestamp_millis() -> Result<u128, CustomError> {
         .as_millis())
 }
 
+/// get_random_nonce devuelve un numero aleatorio de 64 bits.
+/// Utiliza las claves aleatorias de RandomState, que son distintas en cada llamado.
+pub fn get_random_nonce() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(get_current_timestamp_millis().unwrap_or_default());
+    hasher.finish()
+}
+
+/// get_random_bytes devuelve la cantidad recibida de bytes aleatorios, leidos del generador del sistema operativo.
+/// A diferencia de get_random_nonce sirven para generar claves, como el salt de los backups cifrados.
+pub fn get_random_bytes(size: usize) -> Result<Vec<u8>, CustomError> {
+    let mut bytes = vec![0; size];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// bytes_to_hex devuelve los bytes en hexadecimal en minusculas, en el mismo orden en que estan.
+/// Para mostrar un hash en el orden de los exploradores se usa el Display de Hash32.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// hex_to_bytes convierte un string hexadecimal en los bytes que representa, en el mismo orden.
+/// Devuelve CustomError::InvalidValue si no tiene una cantidad par de digitos hexadecimales.
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, CustomError> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(CustomError::InvalidValue);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| CustomError::InvalidValue))
+        .collect()
+}
+
 /// calculate_index_from_timestamp devuelve el indice del ultimo bloque anterior o igual a un timestamp dado.
 pub fn calculate_index_from_timestamp(headers: &Vec<BlockHeader>, last_timestamp: u32) -> usize {
     let new_headers_len = headers
@@ -84,6 +232,24 @@ mod tests {
     };
 
     use super::*;
+    use crate::peer::PeerTimeouts;
+
+    #[test]
+    fn hex_conversions() {
+        assert_eq!(bytes_to_hex(&[0x00, 0xff, 0x1a]), "00ff1a");
+        assert_eq!(hex_to_bytes("00ff1A").unwrap(), vec![0x00, 0xff, 0x1a]);
+        assert!(hex_to_bytes("0").is_err());
+        assert!(hex_to_bytes("0g").is_err());
+        assert!(hex_to_bytes("ñ0").is_err());
+    }
+
+    #[test]
+    fn dir_size_adds_the_files_of_subdirectories() {
+        let block_size = fs::metadata("tests/blocks/test_block.bin").unwrap().len();
+        assert!(dir_size("tests").unwrap() >= block_size);
+        assert_eq!(dir_size("tests/blocks").unwrap(), block_size);
+        assert_eq!(dir_size("tests/does_not_exist").unwrap(), 0);
+    }
 
     #[test]
     fn get_addresses_returns_an_iterator_of_addresses_if_given_a_seed() {
@@ -95,10 +261,54 @@ mod tests {
         assert!(get_addresses("invalid.seed".to_string(), 4321).is_err());
     }
 
+    #[test]
+    fn get_seed_addresses_deduplicates_and_falls_back() {
+        let seeds = vec![
+            String::from("invalid.seed"),
+            String::from("127.0.0.1"),
+            String::from("127.0.0.1"),
+        ];
+        let addresses: Vec<SocketAddr> = get_seed_addresses(&seeds, &["::1"], 4321, 1)
+            .unwrap()
+            .collect();
+        assert_eq!(addresses, vec![SocketAddr::from(([127, 0, 0, 1], 4321))]);
+
+        let addresses: Vec<SocketAddr> = get_seed_addresses(&seeds, &["::1"], 4321, 2)
+            .unwrap()
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                SocketAddr::from(([127, 0, 0, 1], 4321)),
+                SocketAddr::from((Ipv6Addr::LOCALHOST, 4321)),
+            ]
+        );
+
+        assert!(get_seed_addresses(&[String::from("invalid.seed")], &[], 4321, 1).is_err());
+    }
+
+    #[test]
+    fn get_literal_seed_addresses_skips_domains() {
+        let seeds = vec![
+            String::from("seed.testnet.bitcoin.sprovoost.nl"),
+            String::from("127.0.0.1"),
+            String::from("[::1]:18444"),
+            String::from("127.0.0.1"),
+        ];
+        let addresses: Vec<SocketAddr> = get_literal_seed_addresses(&seeds, 4321).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                SocketAddr::from(([127, 0, 0, 1], 4321)),
+                SocketAddr::from((Ipv6Addr::LOCALHOST, 18444)),
+            ]
+        );
+    }
+
     #[test]
     fn open_stream_returns_a_tcp_stream_if_given_a_valid_address() {
         let address = "google.com:80".to_socket_addrs().unwrap().next().unwrap();
-        let stream = open_stream(address);
+        let stream = open_stream(address, PeerTimeouts::default().connect, None);
         assert!(stream.is_ok());
     }
 
@@ -1,5 +1,12 @@
+use std::collections::HashMap;
+
+use secp256k1::Secp256k1;
+
 use crate::{
-    error::CustomError, parser::BufferParser, states::utxo_state::UTXO, structs::movement::Movement,
+    error::CustomError,
+    parser::BufferParser,
+    states::utxo_state::UTXO,
+    structs::{movement::Movement, tx_output::TransactionOutput},
 };
 
 #[derive(Clone, Debug)]
@@ -41,12 +48,13 @@ impl Wallet {
             privkey,
             history: vec![],
         };
-        for (outpoint, value) in &utxo_set.tx_set {
+        for (outpoint, value) in utxo_set.tx_set.iter() {
             if value.tx_out.is_sent_to_key(&wallet.get_pubkey_hash()?)? {
                 wallet.history.push(Movement {
-                    tx_hash: outpoint.hash.clone(),
+                    tx_hash: outpoint.hash,
                     value: value.tx_out.value as i64,
-                    block_hash: Some(value.block_hash.clone()),
+                    block_hash: Some(value.block_hash),
+                    conflicted: false,
                 });
             }
         }
@@ -104,6 +112,17 @@ impl Wallet {
         get_privkey_hash(self.privkey.clone())
     }
 
+    /// Devuelve la public key comprimida de la wallet, derivada de su private key.
+    pub fn get_public_key(&self) -> Result<Vec<u8>, CustomError> {
+        let secp = Secp256k1::new();
+        let key = secp256k1::SecretKey::from_slice(&self.get_privkey_hash()?).map_err(|_| {
+            CustomError::Validation(String::from("User PrivKey incorrectly formatted"))
+        })?;
+        Ok(secp256k1::PublicKey::from_secret_key(&secp, &key)
+            .serialize()
+            .to_vec())
+    }
+
     /// Devuelve el script pubkey de la wallet.
     pub fn get_script_pubkey(&self) -> Result<Vec<u8>, CustomError> {
         get_script_pubkey(self.pubkey.clone())
@@ -114,6 +133,19 @@ impl Wallet {
         self.history.push(movement);
     }
 
+    /// Agrega al final del historial los movimientos recibidos de transacciones que no estaban en el.
+    pub fn merge_history(&mut self, history: Vec<Movement>) {
+        for movement in history {
+            if !self
+                .history
+                .iter()
+                .any(|known| known.tx_hash == movement.tx_hash)
+            {
+                self.history.push(movement);
+            }
+        }
+    }
+
     /// Devuelve el historial de la wallet.
     pub fn get_history(&self) -> Vec<Movement> {
         self.history.clone()
@@ -160,10 +192,39 @@ pub fn get_script_pubkey(pubkey: String) -> Result<Vec<u8>, CustomError> {
     Ok(script_pubkey)
 }
 
+/// Agrupa los destinatarios de un envio por direccion, sumando los montos de las direcciones repetidas.
+/// Devuelve CustomError::Validation, indicando el numero del destinatario (empezando en 1), si no hay destinatarios,
+/// si una direccion es invalida o si un monto es dust y no se retransmitiria.
+pub fn merge_recipients(recipients: &[(String, u64)]) -> Result<HashMap<String, u64>, CustomError> {
+    if recipients.is_empty() {
+        return Err(CustomError::Validation(String::from(
+            "There must be at least one recipient",
+        )));
+    }
+
+    let mut merged = HashMap::new();
+    for (index, (address, value)) in recipients.iter().enumerate() {
+        let invalid =
+            |reason: &str| CustomError::Validation(format!("Recipient {}: {reason}", index + 1));
+        let output = TransactionOutput {
+            value: *value,
+            script_pubkey: get_script_pubkey(address.clone())
+                .map_err(|_| invalid("invalid address"))?,
+        };
+        if output.is_dust() {
+            return Err(invalid("the amount is too small to be relayed"));
+        }
+        *merged.entry(address.clone()).or_insert(0) += value;
+    }
+    Ok(merged)
+}
+
 #[cfg(test)]
 
 mod tests {
-    use crate::structs::movement::Movement;
+    use bitcoin_hashes::{hash160, Hash};
+
+    use crate::structs::{hash32::Hash32, movement::Movement};
 
     use super::*;
 
@@ -259,25 +320,26 @@ mod tests {
             history: vec![],
         };
         wallet.update_history(Movement {
-            tx_hash: vec![
+            tx_hash: Hash32::new([
                 158, 58, 146, 241, 218, 207, 194, 196, 103, 192, 89, 27, 56, 110, 195, 138, 29,
                 177, 167, 47, 144, 191, 102, 68, 45, 70, 88, 237, 140, 224, 130, 115,
-            ],
+            ]),
             value: 500,
-            block_hash: Some(vec![
+            block_hash: Some(Hash32::new([
                 167, 131, 118, 190, 70, 199, 31, 2, 255, 135, 123, 36, 232, 182, 60, 178, 98, 181,
                 242, 112, 111, 183, 22, 128, 11, 0, 0, 0, 0, 0, 0, 0,
-            ]),
+            ])),
+            conflicted: false,
         });
         let serialized_wallet = wallet.serialize();
         let mut parser = BufferParser::new(serialized_wallet);
         let parsed_wallet = Wallet::parse(&mut parser).unwrap();
         assert_eq!(
             parsed_wallet.history[0].block_hash,
-            Some(vec![
+            Some(Hash32::new([
                 167, 131, 118, 190, 70, 199, 31, 2, 255, 135, 123, 36, 232, 182, 60, 178, 98, 181,
                 242, 112, 111, 183, 22, 128, 11, 0, 0, 0, 0, 0, 0, 0
-            ])
+            ]))
         );
     }
 
@@ -335,6 +397,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn wallet_public_key_matches_pubkey_hash() {
+        let wallet = Wallet {
+            name: String::from("test"),
+            pubkey: String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu"),
+            privkey: String::from("cNpwEsaVLhju18SJowLtdCNaJtvMvqL4jtFLm2FXw7vZjg4sRWvH"),
+            history: vec![],
+        };
+        let public_key = wallet.get_public_key().unwrap();
+        assert_eq!(public_key.len(), 33);
+        assert_eq!(
+            hash160::Hash::hash(&public_key).to_byte_array().to_vec(),
+            wallet.get_pubkey_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn recipients_are_merged_by_address() {
+        let first = String::from("mscatccDgq7azndWHFTzvEuZuywCsUvTRu");
+        let second = String::from("mhzZUxRkPzNpCsQHemTakuJa5xhCajxyVm");
+        let merged = merge_recipients(&[
+            (first.clone(), 1000),
+            (second.clone(), 2000),
+            (first.clone(), 3000),
+        ])
+        .unwrap();
+        assert_eq!(
+            merged,
+            HashMap::from([(first.clone(), 4000), (second, 2000)])
+        );
+
+        assert!(merge_recipients(&[]).is_err());
+        assert!(merge_recipients(&[(first.clone(), 100)]).is_err());
+        assert!(matches!(
+            merge_recipients(&[(first, 1000), (String::from("0"), 1000)]),
+            Err(CustomError::Validation(reason)) if reason == "Recipient 2: invalid address"
+        ));
+    }
+
     #[test]
     fn wallet_incorrect_privkey_hash() {
         let wallet = Wallet {
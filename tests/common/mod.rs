@@ -0,0 +1,391 @@
+//! Herramientas para probar el nodo de punta a punta contra un peer guionado que escucha en un socket local,
+//! sin conectarse a una red real.
+//! Cada binario de tests usa solo algunas, por lo que se permite que el resto no se use.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::Write,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    config::Config,
+    error::CustomError,
+    events::GUIEvents,
+    logger::{Log, LogFilter, LogFormat, Logger},
+    loops::node_action_loop::{NodeAction, NodeActionSender},
+    message::{Message, MessageHeader},
+    messages::{
+        block::Block,
+        get_data::GetData,
+        get_headers::GetHeaders,
+        headers::Headers,
+        not_found::NotFound,
+        ping_pong::{Ping, Pong},
+        ver_ack::VerAck,
+        version::Version,
+    },
+    network::Network,
+    node::Node,
+    node_state::NodeState,
+    parser::BtcEncode,
+    services::{NODE_NETWORK, NODE_WITNESS},
+    states::utxo_state::block_subsidy,
+    structs::{
+        block_header::BlockHeader,
+        block_template::{solve_header, BlockTemplate, BLOCK_TEMPLATE_VERSION},
+        inventory::{Inventory, InventoryType},
+    },
+    utils::{get_address_v6, get_current_timestamp},
+};
+
+/// Version del protocolo que anuncia el FakePeer.
+pub const FAKE_PEER_VERSION: i32 = 70015;
+
+/// User agent que anuncia el FakePeer.
+pub const FAKE_PEER_USER_AGENT: &str = "/fake-peer:0.1.0/";
+
+/// Cantidad maxima de headers que envia el FakePeer en un mensaje, como un nodo real.
+const MAX_HEADERS: usize = 2000;
+
+/// Cada cuanto se vuelve a evaluar una condicion en wait_until.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Behavior indica como responde el FakePeer, para ejercitar los caminos de error del nodo.
+pub enum Behavior {
+    /// Completa el handshake y sirve los headers y bloques de su cadena.
+    Honest,
+    /// Responde el version pero nunca envia el verack, por lo que el handshake no termina.
+    NoVerAck,
+    /// Sirve los bloques con la coinbase alterada, por lo que no coinciden con su merkle root.
+    CorruptBlocks,
+    /// Sirve los headers con el checksum del mensaje alterado.
+    BadChecksum,
+}
+
+/// FakePeer es un peer que escucha en un puerto local y responde a los mensajes que recibe segun su Behavior,
+/// sirviendo una cadena fija de bloques. Atiende cada conexion en un thread propio hasta que se cierra.
+/// Los elementos son:
+/// - address: Direccion en la que escucha.
+/// - received: Comandos recibidos de todas las conexiones, en el orden en que llegaron.
+pub struct FakePeer {
+    pub address: SocketAddr,
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakePeer {
+    /// Comienza a escuchar en un puerto libre de 127.0.0.1 y devuelve el peer con la direccion elegida.
+    pub fn spawn(chain: Vec<Block>, behavior: Behavior) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let received = Arc::new(Mutex::new(vec![]));
+
+        let chain = Arc::new(chain);
+        let listener_received = received.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let chain = chain.clone();
+                let received = listener_received.clone();
+                thread::spawn(move || serve(stream, &chain, behavior, &received));
+            }
+        });
+
+        Self { address, received }
+    }
+
+    /// Devuelve los comandos recibidos hasta el momento.
+    pub fn received(&self) -> Vec<String> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Espera a recibir el comando, devolviendo false si no llega antes del timeout.
+    pub fn wait_for(&self, command: &str, timeout: Duration) -> bool {
+        wait_until(timeout, || self.received().iter().any(|c| c == command))
+    }
+}
+
+/// Responde los mensajes de una conexion hasta que se cierra o llega un mensaje invalido.
+fn serve(
+    mut stream: TcpStream,
+    chain: &[Block],
+    behavior: Behavior,
+    received: &Mutex<Vec<String>>,
+) -> Result<(), CustomError> {
+    loop {
+        let header = MessageHeader::read(&mut stream)?;
+        let payload = header.read_payload(&mut stream, Network::Regtest)?;
+        received.lock()?.push(header.command.clone());
+
+        match header.command.as_str() {
+            "version" => {
+                let version = Version::parse(payload)?;
+                let mut response = Version::new(
+                    version.sender_address,
+                    version.receiver_address,
+                    FAKE_PEER_VERSION,
+                    NODE_NETWORK | NODE_WITNESS,
+                    FAKE_PEER_USER_AGENT,
+                    true,
+                );
+                response.start_height = chain.len() as i32;
+                response.send(&mut stream, Network::Regtest)?;
+                if behavior != Behavior::NoVerAck {
+                    VerAck::new().send(&mut stream, Network::Regtest)?;
+                }
+            }
+            "getheaders" => {
+                let mut headers = Headers::new();
+                headers.headers = headers_after(chain, &GetHeaders::parse(payload)?);
+                match behavior {
+                    Behavior::BadChecksum => send_with_bad_checksum(&headers, &mut stream)?,
+                    _ => {
+                        headers.send(&mut stream, Network::Regtest)?;
+                    }
+                }
+            }
+            "getdata" => {
+                for inventory in GetData::parse(payload)?.get_inventories() {
+                    serve_inventory(chain, behavior, inventory, &mut stream)?;
+                }
+            }
+            "ping" => {
+                let nonce = Ping::parse(payload)?.nonce;
+                Pong { nonce }.send(&mut stream, Network::Regtest)?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Se conecta al nodo en la direccion recibida como lo haria un peer remoto y completa el handshake,
+/// devolviendo la conexion abierta y el version con el que respondio el nodo.
+/// El version se envia con un nonce propio, por lo que el nodo no lo confunde con una conexion a si mismo.
+pub fn dial(
+    address: SocketAddr,
+    version: i32,
+    services: u64,
+) -> Result<(TcpStream, Version), CustomError> {
+    let mut stream = TcpStream::connect_timeout(&address, Duration::from_secs(5))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let local_address = get_address_v6(stream.local_addr()?);
+    Version::new(
+        get_address_v6(address),
+        local_address,
+        version,
+        services,
+        FAKE_PEER_USER_AGENT,
+        true,
+    )
+    .send(&mut stream, Network::Regtest)?;
+
+    let mut response = None;
+    loop {
+        let header = MessageHeader::read(&mut stream)?;
+        let payload = header.read_payload(&mut stream, Network::Regtest)?;
+        match header.command.as_str() {
+            "version" => response = Some(Version::parse(payload)?),
+            "verack" => break,
+            _ => {}
+        }
+    }
+    VerAck::new().send(&mut stream, Network::Regtest)?;
+    let response = response.ok_or(CustomError::CannotHandshakeNode)?;
+    Ok((stream, response))
+}
+
+/// Devuelve los headers de la cadena que siguen al primer hash conocido del locator, o desde el genesis.
+fn headers_after(chain: &[Block], get_headers: &GetHeaders) -> Vec<BlockHeader> {
+    let start = get_headers
+        .block_locator_hashes
+        .iter()
+        .find_map(|hash| {
+            chain
+                .iter()
+                .position(|block| block.header.hash() == hash)
+                .map(|position| position + 1)
+        })
+        .unwrap_or(0);
+    chain[start..]
+        .iter()
+        .take(MAX_HEADERS)
+        .map(|block| block.header.clone())
+        .collect()
+}
+
+/// Envia el bloque pedido, o un 'notfound' si no es un bloque de la cadena.
+fn serve_inventory(
+    chain: &[Block],
+    behavior: Behavior,
+    inventory: &Inventory,
+    stream: &mut TcpStream,
+) -> Result<(), CustomError> {
+    let block = match inventory.inventory_type {
+        InventoryType::Block | InventoryType::WitnessBlock => chain
+            .iter()
+            .find(|block| *block.header.hash() == inventory.hash),
+        _ => None,
+    };
+    let Some(block) = block else {
+        NotFound::new(vec![inventory.clone()]).send(stream, Network::Regtest)?;
+        return Ok(());
+    };
+    let mut block = block.clone();
+    if behavior == Behavior::CorruptBlocks {
+        block.transactions[0].inputs[0].script_sig.push(0x00);
+    }
+    block.send(stream, Network::Regtest)?;
+    Ok(())
+}
+
+/// Envia el mensaje con el ultimo byte de su checksum alterado.
+fn send_with_bad_checksum(
+    message: &impl Message,
+    stream: &mut TcpStream,
+) -> Result<(), CustomError> {
+    let mut header = MessageHeader::new(message, Network::Regtest).serialize();
+    header[23] ^= 0xff;
+    stream.write_all(&header)?;
+    stream.write_all(&message.serialize())?;
+    Ok(())
+}
+
+/// Mina una cadena de regtest de length bloques sobre el genesis, cada uno con solo su coinbase.
+pub fn regtest_chain(length: u32) -> Vec<Block> {
+    let start_time = get_current_timestamp().unwrap() as u32 - length;
+    let mut previous_block_hash = Network::Regtest.genesis();
+    (1..=length)
+        .map(|height| {
+            let template = BlockTemplate {
+                version: BLOCK_TEMPLATE_VERSION,
+                previous_block_hash,
+                height,
+                bits: Network::Regtest.pow_limit_bits(),
+                cur_time: start_time + height,
+                min_time: 0,
+                transactions: vec![],
+                coinbase_value: block_subsidy(height, Network::Regtest),
+            };
+            // OP_TRUE, cualquiera puede gastar la coinbase
+            let mut block = template.block(template.coinbase(vec![0x51]));
+            assert!(solve_header(&mut block.header));
+            previous_block_hash = *block.header.hash();
+            block
+        })
+        .collect()
+}
+
+/// Espera a que se cumpla la condicion, devolviendo false si no se cumple antes del timeout.
+pub fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    condition()
+}
+
+/// TestNode es un nodo de regtest completo que solo se conecta a la direccion recibida y no atiende conexiones entrantes.
+/// Guarda su estado y su log en tests/, y los borra al detenerse.
+/// Los elementos son:
+/// - node_state_ref: Estado del nodo, para verificar lo que descargo.
+/// - node_action_sender: Sender con el que se le indica al nodo que termine.
+/// - gui_receiver: Receiver de los eventos del nodo, que deja de funcionar si se cierra.
+/// - thread: Thread del nodo.
+/// - logger: Logger del nodo, se detiene junto al nodo.
+/// - log_file: Archivo de log del nodo.
+/// - store_path: Directorio donde el nodo guarda su estado.
+pub struct TestNode {
+    pub node_state_ref: Arc<NodeState>,
+    node_action_sender: NodeActionSender,
+    gui_receiver: mpsc::Receiver<GUIEvents>,
+    thread: JoinHandle<Result<(), CustomError>>,
+    logger: Logger,
+    log_file: String,
+    store_path: String,
+}
+
+impl TestNode {
+    /// Inicia el nodo conectandose al peer de la direccion recibida. El nombre identifica los archivos del test.
+    pub fn start(name: &str, peer_address: SocketAddr) -> Self {
+        Self::start_with_overrides(name, peer_address, vec![])
+    }
+
+    /// Inicia el nodo como start, pisando su configuracion con los valores recibidos.
+    pub fn start_with_overrides(
+        name: &str,
+        peer_address: SocketAddr,
+        overrides: Vec<(&str, String)>,
+    ) -> Self {
+        let log_file = format!("tests/test_log_{name}.txt");
+        let store_path = format!("tests/store_{name}");
+        fs::remove_dir_all(&store_path).ok();
+
+        let mut settings = vec![
+            ("SEED", peer_address.ip().to_string()),
+            ("PROTOCOL_VERSION", FAKE_PEER_VERSION.to_string()),
+            ("PORT", peer_address.port().to_string()),
+            ("LOG", log_file.clone()),
+            ("NPEERS", String::from("1")),
+            ("NETWORK", String::from("regtest")),
+            ("STORE_PATH", store_path.clone()),
+            ("CLIENT_ONLY", String::from("true")),
+        ];
+        settings.extend(overrides);
+        let config = Config::from_file_with_overrides(None, settings).unwrap();
+
+        let (gui_sender, gui_receiver) = mpsc::channel();
+        let logger = Logger::with_options(
+            &log_file,
+            gui_sender.clone(),
+            LogFilter::default(),
+            LogFormat::Text,
+            false,
+        )
+        .unwrap();
+        let node_state_ref = NodeState::new(
+            logger.get_sender(),
+            gui_sender.clone(),
+            &store_path,
+            Network::Regtest,
+        )
+        .unwrap();
+        let node = Node::new(&config, &logger, node_state_ref.clone()).unwrap();
+        let node_action_sender = node.node_action_sender.clone();
+        let thread = node
+            .spawn(vec![peer_address].into_iter(), gui_sender)
+            .unwrap();
+
+        Self {
+            node_state_ref,
+            node_action_sender,
+            gui_receiver,
+            thread,
+            logger,
+            log_file,
+            store_path,
+        }
+    }
+
+    /// Termina el nodo y el logger, y borra sus archivos.
+    pub fn stop(self) {
+        let store_path = self.shutdown();
+        fs::remove_dir_all(store_path).unwrap();
+    }
+
+    /// Termina el nodo y el logger, borrando solo el log. Devuelve el directorio donde quedo guardado el estado.
+    pub fn shutdown(self) -> String {
+        self.node_action_sender.send(NodeAction::Terminate).unwrap();
+        self.thread.join().unwrap().unwrap();
+        self.logger.tx.send(Log::Terminate).unwrap();
+        self.logger.thread.join().unwrap().unwrap();
+        fs::remove_file(self.log_file).unwrap();
+        self.store_path
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::{
+    net::{Ipv6Addr, SocketAddrV6},
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use bitcoin::{
+    logger::{LogFilter, LogFormat, Logger},
+    loops::node_action_loop::{node_action_channel, NODE_ACTION_QUEUE_SIZE},
+    network::Network,
+    peer::{Peer, PeerConfig},
+    utils::get_address_v6,
+};
+use common::{
+    regtest_chain, wait_until, Behavior, FakePeer, TestNode, FAKE_PEER_USER_AGENT,
+    FAKE_PEER_VERSION,
+};
+
+/// Tiempo maximo que se espera a que el nodo sincronice con el FakePeer.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Llama al peer como lo hace el nodo, con channels que no atiende nadie.
+fn call_peer(fake_peer: &FakePeer, log_file: &str) -> Result<Peer, bitcoin::error::CustomError> {
+    let (gui_sender, _gui_receiver) = mpsc::channel();
+    let logger = Logger::with_options(
+        &String::from(log_file),
+        gui_sender,
+        LogFilter::default(),
+        LogFormat::Text,
+        false,
+    )
+    .unwrap();
+    let (_peer_action_sender, receiver) = mpsc::channel();
+    let (node_action_sender, _node_action_receiver) = node_action_channel(NODE_ACTION_QUEUE_SIZE);
+
+    let peer = Peer::call(
+        fake_peer.address,
+        SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 18444, 0, 0),
+        FAKE_PEER_VERSION,
+        &PeerConfig {
+            network: Network::Regtest,
+            ..Default::default()
+        },
+        Arc::new(Mutex::new(receiver)),
+        logger.get_sender(),
+        node_action_sender,
+    );
+    std::fs::remove_file(log_file).unwrap();
+    peer
+}
+
+#[test]
+fn handshake_with_fake_peer() {
+    let fake_peer = FakePeer::spawn(regtest_chain(3), Behavior::Honest);
+
+    let peer = call_peer(&fake_peer, "tests/test_log_fake_handshake.txt").unwrap();
+    assert_eq!(peer.version, FAKE_PEER_VERSION);
+    assert_eq!(peer.user_agent, FAKE_PEER_USER_AGENT);
+    assert_eq!(peer.start_height, 3);
+    assert!(fake_peer.wait_for("verack", Duration::from_secs(5)));
+    peer.disconnect();
+}
+
+#[test]
+fn handshake_fails_without_verack() {
+    let fake_peer = FakePeer::spawn(vec![], Behavior::NoVerAck);
+
+    assert!(call_peer(&fake_peer, "tests/test_log_fake_no_verack.txt").is_err());
+    assert_eq!(fake_peer.received()[0], "version");
+}
+
+#[test]
+fn node_syncs_headers_and_blocks_from_fake_peer() {
+    let chain = regtest_chain(20);
+    let tip = *chain.last().unwrap().header.hash();
+    let fake_peer = FakePeer::spawn(chain, Behavior::Honest);
+
+    let node = TestNode::start("fake_sync", fake_peer.address);
+    let synced = wait_until(SYNC_TIMEOUT, || {
+        node.node_state_ref.get_last_header_hash().unwrap() == Some(tip)
+            && node.node_state_ref.has_block(&tip).unwrap()
+    });
+    let received = fake_peer.received();
+    node.stop();
+
+    assert!(synced);
+    assert!(received.iter().any(|command| command == "getheaders"));
+    assert!(received.iter().any(|command| command == "getdata"));
+}
+
+#[test]
+fn node_bans_fake_peer_serving_corrupt_blocks() {
+    let chain = regtest_chain(5);
+    let first_block = *chain[0].header.hash();
+    let fake_peer = FakePeer::spawn(chain, Behavior::CorruptBlocks);
+
+    let node = TestNode::start("fake_corrupt", fake_peer.address);
+    let address = get_address_v6(fake_peer.address);
+    let banned = wait_until(SYNC_TIMEOUT, || {
+        node.node_state_ref.is_banned(address).unwrap()
+    });
+    let has_block = node.node_state_ref.has_block(&first_block).unwrap();
+    node.stop();
+
+    assert!(banned);
+    assert!(!has_block);
+}
+
+#[test]
+fn node_rejects_headers_with_bad_checksum() {
+    let chain = regtest_chain(5);
+    let fake_peer = FakePeer::spawn(chain, Behavior::BadChecksum);
+
+    let node = TestNode::start("fake_checksum", fake_peer.address);
+    let rejected = fake_peer.wait_for("reject", SYNC_TIMEOUT);
+    let last_header = node.node_state_ref.get_last_header_hash().unwrap();
+    node.stop();
+
+    assert!(rejected);
+    assert_eq!(last_header, None);
+}
@@ -1,9 +1,11 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     use std::{
         fs::{self, File},
         io::{BufRead, BufReader},
-        net::{Ipv6Addr, SocketAddrV6},
+        net::{Ipv6Addr, SocketAddr, SocketAddrV6},
         sync::{mpsc, Arc, Mutex},
         thread,
         time::Duration,
@@ -11,52 +13,125 @@ mod tests {
 
     use bitcoin::{
         config::Config,
-        logger::Logger,
+        error::CustomError,
+        logger::{Log, Logger},
         loops::{
-            peer_action_loop::PeerAction, pending_blocks_loop::pending_blocks_loop,
+            node_action_loop::{node_action_channel, NodeActionSender, NODE_ACTION_QUEUE_SIZE},
+            peer_action_loop::PeerAction,
+            pending_blocks_loop::pending_blocks_loop,
             tcp_listener_loop::TcpListenerLoop,
         },
+        network::Network,
         node::Node,
         node_state::NodeState,
-        peer::Peer,
+        peer::{Peer, PeerConfig},
+        structs::hash32::Hash32,
         utils::get_addresses,
     };
-    use gtk::glib::{self, Priority};
+
+    use crate::common::{dial, wait_until, Behavior, FakePeer};
+
+    /// Configuracion de los peers en la regtest, la red en la que se comunican los FakePeer.
+    fn regtest_config() -> PeerConfig {
+        PeerConfig {
+            network: Network::Regtest,
+            ..Default::default()
+        }
+    }
+
+    type TcpListenerHandle = (
+        thread::JoinHandle<Result<(), CustomError>>,
+        mpsc::Sender<()>,
+    );
 
     #[test]
     fn node_and_state_creation() {
-        let (gui_sender, _gui_receiver) = glib::MainContext::channel(Priority::default());
+        let (gui_sender, _gui_receiver) = mpsc::channel();
 
         let logger = Logger::new(&String::from("tests/test_log.txt"), gui_sender.clone()).unwrap();
         let logger_sender = logger.get_sender();
 
+        let config = Config::from_file("example-config.txt").unwrap();
         let store_path = String::from("tests/store");
-        let node_state_ref =
-            NodeState::new(logger_sender.clone(), gui_sender, &store_path).unwrap();
+        let node_state_ref = NodeState::new(
+            logger_sender.clone(),
+            gui_sender,
+            &store_path,
+            config.network,
+        )
+        .unwrap();
 
-        let config = Config::from_file("example-config.txt").unwrap();
         let node = Node::new(&config, &logger, node_state_ref.clone());
         assert!(node.is_ok());
         fs::remove_file("tests/test_log.txt").unwrap();
         fs::remove_dir_all("tests/store").unwrap();
     }
 
+    #[test]
+    fn nodes_with_different_configs_in_the_same_process() {
+        let (gui_sender, _gui_receiver) = mpsc::channel();
+        let logger = Logger::new(&String::from("tests/test_log6.txt"), gui_sender.clone()).unwrap();
+        let logger_sender = logger.get_sender();
+
+        let config = Config::from_file("example-config.txt").unwrap();
+        let mut regtest_config = Config::from_file("example-config.txt").unwrap();
+        regtest_config.network = Network::Regtest;
+        regtest_config.spv = true;
+
+        let store_path = String::from("tests/store_configs");
+        let regtest_store_path = String::from("tests/store_configs_regtest");
+        let node_state_ref = NodeState::new(
+            logger_sender.clone(),
+            gui_sender.clone(),
+            &store_path,
+            config.network,
+        )
+        .unwrap();
+        let regtest_node_state_ref = NodeState::new(
+            logger_sender,
+            gui_sender,
+            &regtest_store_path,
+            Network::Regtest,
+        )
+        .unwrap();
+
+        let node = Node::new(&config, &logger, node_state_ref.clone()).unwrap();
+        let regtest_node =
+            Node::new(&regtest_config, &logger, regtest_node_state_ref.clone()).unwrap();
+        assert_ne!(node.services, 0);
+        assert_eq!(regtest_node.services, 0);
+        assert!(!node_state_ref.is_spv_mode());
+        assert!(regtest_node_state_ref.is_spv_mode());
+        assert!(matches!(
+            Node::new(&config, &logger, regtest_node_state_ref),
+            Err(CustomError::ConfigInvalid)
+        ));
+
+        fs::remove_file("tests/test_log6.txt").unwrap();
+        fs::remove_dir_all(store_path).unwrap();
+        fs::remove_dir_all(regtest_store_path).unwrap();
+    }
+
     #[test]
     fn handshake_peers() {
-        let (gui_sender, _gui_receiver) = glib::MainContext::channel(Priority::default());
-        let mut addresses =
-            get_addresses("seed.testnet.bitcoin.sprovoost.nl".to_string(), 18333).unwrap();
+        let (gui_sender, _gui_receiver) = mpsc::channel();
+        let fake_peers = [
+            FakePeer::spawn(vec![], Behavior::Honest),
+            FakePeer::spawn(vec![], Behavior::Honest),
+        ];
+        let mut addresses = fake_peers.iter().map(|fake_peer| fake_peer.address);
         let (_peer_action_sender, receiver) = mpsc::channel();
         let peer_action_receiver = Arc::new(Mutex::new(receiver));
-        let (node_action_sender, _node_action_receiver) = mpsc::channel();
+        let (node_action_sender, _node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
         let logger = Logger::new(&String::from("tests/test_log2.txt"), gui_sender.clone()).unwrap();
         let logger_sender = logger.get_sender();
 
         let peer = Peer::call(
             addresses.next().unwrap(),
             SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 18333, 0, 0),
-            0,
             70012,
+            &regtest_config(),
             peer_action_receiver.clone(),
             logger_sender.clone(),
             node_action_sender.clone(),
@@ -68,8 +143,8 @@ mod tests {
         let peer2 = Peer::call(
             addresses.next().unwrap(),
             SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 18333, 0, 0),
-            0,
             70012,
+            &regtest_config(),
             peer_action_receiver,
             logger_sender,
             node_action_sender,
@@ -84,7 +159,7 @@ mod tests {
         let mut handshakes = 0;
         for line in reader.lines() {
             let line = line.unwrap();
-            if line.contains("Successful handshake with") {
+            if line.contains("Successful handshake") {
                 handshakes += 1;
             }
         }
@@ -94,24 +169,38 @@ mod tests {
 
     #[test]
     fn node_state_pending_blocks_get_updated() {
-        let (gui_sender, _gui_receiver) = glib::MainContext::channel(Priority::default());
+        let (gui_sender, _gui_receiver) = mpsc::channel();
 
         let logger = Logger::new(&String::from("tests/test_log3.txt"), gui_sender.clone()).unwrap();
         let logger_sender = logger.get_sender();
 
         let (peer_action_sender, receiver) = mpsc::channel();
         let peer_action_receiver = Arc::new(Mutex::new(receiver));
+        let (node_action_sender, _node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
 
-        let store_path = String::from("tests");
-        let node_state_ref =
-            NodeState::new(logger_sender.clone(), gui_sender, &store_path).unwrap();
-        let node_state = node_state_ref.clone();
-        let mut node_state = node_state.lock().unwrap();
-        node_state.append_pending_block(vec![1, 2, 3]).unwrap();
-        assert_eq!(node_state.is_pending_blocks_empty().unwrap(), false);
-        drop(node_state);
+        let store_path = String::from("tests/store_pending_blocks");
+        let node_state_ref = NodeState::new(
+            logger_sender.clone(),
+            gui_sender,
+            &store_path,
+            Network::Regtest,
+        )
+        .unwrap();
+        node_state_ref
+            .append_pending_block(Hash32::new([1; 32]))
+            .unwrap();
+        assert_eq!(node_state_ref.is_pending_blocks_empty().unwrap(), false);
 
-        pending_blocks_loop(node_state_ref, peer_action_sender, logger_sender);
+        let (_terminate_sender, terminate_receiver) = mpsc::channel();
+        pending_blocks_loop(
+            node_state_ref,
+            peer_action_sender,
+            node_action_sender,
+            logger_sender,
+            3,
+            terminate_receiver,
+        );
 
         thread::sleep(Duration::from_secs(5));
 
@@ -122,11 +211,45 @@ mod tests {
             assert!(false);
         }
         fs::remove_file("tests/test_log3.txt").unwrap();
+        fs::remove_dir_all(store_path).unwrap();
+    }
+
+    /// Comienza a escuchar conexiones en el puerto recibido, respondiendolas con Peer::answer.
+    /// El listener termina cuando se descarta el handle devuelto.
+    fn spawn_answerer(
+        port: u16,
+        logger_sender: mpsc::Sender<Log>,
+        node_state_ref: Arc<NodeState>,
+        peer_action_receiver: Arc<Mutex<mpsc::Receiver<PeerAction>>>,
+        node_action_sender: NodeActionSender,
+    ) -> TcpListenerHandle {
+        let listener_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), port, 0, 0);
+        let answerer_logger = logger_sender.clone();
+        TcpListenerLoop::spawn(
+            logger_sender,
+            node_state_ref,
+            SocketAddr::V6(listener_address),
+            Box::new(move |stream| {
+                Peer::answer(
+                    stream,
+                    listener_address,
+                    70015,
+                    &PeerConfig {
+                        services: 123,
+                        ..regtest_config()
+                    },
+                    peer_action_receiver.clone(),
+                    answerer_logger.clone(),
+                    node_action_sender.clone(),
+                )
+            }),
+            8,
+        )
     }
 
     #[test]
     fn node_anwsers_handshakes() {
-        let (gui_sender, _gui_receiver) = glib::MainContext::channel(Priority::default());
+        let (gui_sender, _gui_receiver) = mpsc::channel();
 
         let logger = Logger::new(&String::from("tests/test_log4.txt"), gui_sender.clone()).unwrap();
         let logger_sender = logger.get_sender();
@@ -134,47 +257,100 @@ mod tests {
         let (_peer_action_sender, receiver) = mpsc::channel();
         let peer_action_receiver = Arc::new(Mutex::new(receiver));
 
-        let (node_action_sender, _node_action_receiver) = mpsc::channel();
+        let (node_action_sender, _node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
+
+        let store_path = String::from("tests/store_answers");
+        let node_state_ref = NodeState::new(
+            logger_sender.clone(),
+            gui_sender,
+            &store_path,
+            Network::Regtest,
+        )
+        .unwrap();
+
+        let _tcp_listener = spawn_answerer(
+            18334,
+            logger_sender,
+            node_state_ref.clone(),
+            peer_action_receiver,
+            node_action_sender,
+        );
+        let address = get_addresses("127.0.0.1".to_string(), 18334)
+            .unwrap()
+            .next()
+            .unwrap();
+        thread::sleep(Duration::from_secs(1));
+
+        let (_stream, version) = dial(address, 13, 1).unwrap();
+        assert_eq!(version.version, 70015);
+        assert_eq!(version.services, 123);
+
+        assert!(wait_until(Duration::from_secs(5), || {
+            node_state_ref.get_peers().unwrap().len() == 1
+        }));
+        let peers = node_state_ref.get_peers().unwrap();
+        assert_eq!(peers[0].version, 13);
+        assert_eq!(peers[0].services, 1);
+        drop(peers);
+
+        fs::remove_file("tests/test_log4.txt").unwrap();
+        fs::remove_dir_all(store_path).unwrap();
+    }
+
+    #[test]
+    fn self_connections_are_dropped() {
+        let (gui_sender, _gui_receiver) = mpsc::channel();
+
+        let logger = Logger::new(&String::from("tests/test_log5.txt"), gui_sender.clone()).unwrap();
+        let logger_sender = logger.get_sender();
+
+        let (_peer_action_sender, receiver) = mpsc::channel();
+        let peer_action_receiver = Arc::new(Mutex::new(receiver));
+
+        let (node_action_sender, _node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
 
-        let store_path = String::from("tests");
-        let node_state_ref =
-            NodeState::new(logger_sender.clone(), gui_sender, &store_path).unwrap();
+        let store_path = String::from("tests/store_self_connection");
+        let node_state_ref = NodeState::new(
+            logger_sender.clone(),
+            gui_sender,
+            &store_path,
+            Network::Regtest,
+        )
+        .unwrap();
 
-        let _tcp_listener = TcpListenerLoop::spawn(
+        let _tcp_listener = spawn_answerer(
+            18335,
             logger_sender.clone(),
             node_state_ref.clone(),
-            SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 18334, 0, 0),
-            123,
-            70015,
             peer_action_receiver.clone(),
             node_action_sender.clone(),
         );
-        let mut addresses = get_addresses("127.0.0.1".to_string(), 18334).unwrap();
-        thread::sleep(Duration::from_secs(5));
+        let address = get_addresses("127.0.0.1".to_string(), 18335)
+            .unwrap()
+            .next()
+            .unwrap();
+        thread::sleep(Duration::from_secs(1));
 
         let peer = Peer::call(
-            addresses.next().unwrap(),
-            SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2), 18334, 0, 0),
-            1,
+            address,
+            SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2), 18335, 0, 0),
             13,
-            peer_action_receiver.clone(),
-            logger_sender.clone(),
-            node_action_sender.clone(),
-        )
-        .unwrap();
-
-        assert_eq!(peer.version, 70015);
-        assert_eq!(peer.services, 123);
+            &PeerConfig {
+                services: 1,
+                ..regtest_config()
+            },
+            peer_action_receiver,
+            logger_sender,
+            node_action_sender,
+        );
+        assert!(peer.is_err());
 
         thread::sleep(Duration::from_secs(1));
+        assert!(node_state_ref.get_peers().unwrap().is_empty());
 
-        let mut node_state = node_state_ref.lock().unwrap();
-        let peers = node_state.get_peers();
-        assert_eq!(peers.len(), 1);
-        assert_eq!(peers[0].version, 13);
-        assert_eq!(peers[0].services, 1);
-        drop(node_state);
-
-        fs::remove_file("tests/test_log4.txt").unwrap();
+        fs::remove_file("tests/test_log5.txt").unwrap();
+        fs::remove_dir_all(store_path).unwrap();
     }
 }
@@ -0,0 +1,48 @@
+mod common;
+
+use std::{fs, sync::mpsc, time::Duration};
+
+use bitcoin::{capture::read_capture, loops::replay_loop::ReplayLoop, network::Network};
+use common::{regtest_chain, wait_until, Behavior, FakePeer, TestNode};
+
+/// Archivo donde se capturan los mensajes.
+const CAPTURE_PATH: &str = "tests/test_capture_replay.bin";
+
+/// Tiempo maximo que se espera a que el nodo sincronice.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[test]
+fn node_syncs_from_a_replayed_capture() {
+    fs::remove_file(CAPTURE_PATH).ok();
+    let capture = vec![("CAPTURE", String::from(CAPTURE_PATH))];
+    let chain = regtest_chain(10);
+    let tip = *chain.last().unwrap().header.hash();
+    let fake_peer = FakePeer::spawn(chain, Behavior::Honest);
+
+    let node = TestNode::start_with_overrides("capture", fake_peer.address, capture);
+    let synced = wait_until(SYNC_TIMEOUT, || {
+        node.node_state_ref.has_block(&tip).unwrap()
+    });
+    node.stop();
+    assert!(synced);
+
+    let messages = read_capture(CAPTURE_PATH).unwrap();
+    assert!(messages
+        .iter()
+        .any(|message| message.header().unwrap().command == "headers"));
+    assert!(messages
+        .iter()
+        .any(|message| message.header().unwrap().command == "block"));
+
+    let (logger_sender, _logger_receiver) = mpsc::channel();
+    let addresses = ReplayLoop::spawn(logger_sender, messages, Network::Regtest).unwrap();
+    assert_eq!(addresses.len(), 1);
+    let node = TestNode::start_with_overrides("replay", addresses[0], vec![]);
+    let replayed = wait_until(SYNC_TIMEOUT, || {
+        node.node_state_ref.has_block(&tip).unwrap()
+    });
+    node.stop();
+    fs::remove_file(CAPTURE_PATH).unwrap();
+
+    assert!(replayed);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::{fs, path::Path, sync::mpsc, thread, time::Duration};
+
+use bitcoin::{network::Network, node_state::NodeState};
+use common::{regtest_chain, wait_until, Behavior, FakePeer, TestNode};
+
+/// Tiempo maximo que se espera a que el nodo sincronice con el FakePeer.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tiempo maximo que se espera a que terminen los threads del nodo al cerrarlo.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[test]
+fn node_shuts_down_and_keeps_its_store() {
+    let chain = regtest_chain(10);
+    let tip = *chain.last().unwrap().header.hash();
+    let fake_peer = FakePeer::spawn(chain, Behavior::Honest);
+
+    let node = TestNode::start("shutdown", fake_peer.address);
+    let synced = wait_until(SYNC_TIMEOUT, || {
+        node.node_state_ref.has_block(&tip).unwrap()
+    });
+
+    // el join se hace en otro thread para no bloquear el test si el nodo no termina
+    let (store_sender, store_receiver) = mpsc::channel();
+    thread::spawn(move || store_sender.send(node.shutdown()));
+    let store_path = store_receiver.recv_timeout(SHUTDOWN_TIMEOUT).unwrap();
+    assert!(synced);
+
+    for file in ["headers.bin", "wallets.bin", "blocks/blk00000.dat"] {
+        assert!(Path::new(&format!("{store_path}/{file}")).exists());
+    }
+
+    // el estado guardado al cerrar se restaura con la cadena descargada
+    let (logger_sender, _logger_receiver) = mpsc::channel();
+    let (gui_sender, _gui_receiver) = mpsc::channel();
+    let node_state_ref =
+        NodeState::new(logger_sender, gui_sender, &store_path, Network::Regtest).unwrap();
+    let restored_tip = node_state_ref.get_last_header_hash().unwrap();
+    let restored_block = node_state_ref.has_block(&tip).unwrap();
+    drop(node_state_ref);
+    fs::remove_dir_all(&store_path).unwrap();
+
+    assert_eq!(restored_tip, Some(tip));
+    assert!(restored_block);
+}
@@ -0,0 +1,150 @@
+use std::{fs, sync::mpsc};
+
+use bitcoin::{
+    events::GUIEvents,
+    logger::Log,
+    loops::node_action_loop::{
+        node_action_channel, NodeAction, NodeActionReceiver, NODE_ACTION_QUEUE_SIZE,
+    },
+    messages::block::Block,
+    network::Network,
+    node_state::NodeState,
+    rpc::{RpcCommand, RpcHandler},
+    structs::{
+        block_template::{solve_header, BlockTemplate},
+        hash32::Hash32,
+    },
+};
+
+/// SubmitBlockTest es un estado de regtest sin bloques sobre el que se ejecuta submitblock como lo hace la RPC.
+/// Guarda su estado en tests/ y lo borra al descartarse.
+/// Los elementos son:
+/// - rpc_handler: Handler con el que se envian los bloques.
+/// - node_action_receiver: Receiver de los bloques aceptados que se anuncian a los peers.
+/// - _logger_receiver: Receiver de los logs del estado, que falla al loguear si se cierra.
+/// - _gui_receiver: Receiver de los eventos del estado, que falla al notificarlos si se cierra.
+/// - store_path: Directorio donde se guarda el estado.
+struct SubmitBlockTest {
+    rpc_handler: RpcHandler,
+    node_action_receiver: NodeActionReceiver,
+    _logger_receiver: mpsc::Receiver<Log>,
+    _gui_receiver: mpsc::Receiver<GUIEvents>,
+    store_path: String,
+}
+
+impl SubmitBlockTest {
+    /// Crea el estado sin bloques. El nombre identifica el directorio del test.
+    fn new(name: &str) -> Self {
+        let store_path = format!("tests/store_submit_{name}");
+        fs::remove_dir_all(&store_path).ok();
+
+        let (logger_sender, logger_receiver) = mpsc::channel();
+        let (gui_sender, gui_receiver) = mpsc::channel();
+        let node_state_ref =
+            NodeState::new(logger_sender, gui_sender, &store_path, Network::Regtest).unwrap();
+        let (node_action_sender, node_action_receiver) =
+            node_action_channel(NODE_ACTION_QUEUE_SIZE);
+
+        Self {
+            rpc_handler: RpcHandler {
+                node_state_ref,
+                node_action_sender,
+            },
+            node_action_receiver,
+            _logger_receiver: logger_receiver,
+            _gui_receiver: gui_receiver,
+            store_path,
+        }
+    }
+
+    /// Devuelve el template del siguiente bloque.
+    fn template(&self) -> BlockTemplate {
+        self.rpc_handler
+            .node_state_ref
+            .get_block_template()
+            .unwrap()
+    }
+
+    /// Devuelve el resultado de submitblock con el bloque recibido.
+    fn submit(&self, block: &Block) -> String {
+        self.rpc_handler
+            .execute(RpcCommand::SubmitBlock(block.clone()))
+            .unwrap()
+    }
+}
+
+impl Drop for SubmitBlockTest {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.store_path).ok();
+    }
+}
+
+/// Mina el bloque del template con la coinbase pagando a OP_TRUE.
+fn mine(template: &BlockTemplate) -> Block {
+    let mut block = template.block(template.coinbase(vec![0x51]));
+    assert!(solve_header(&mut block.header));
+    block
+}
+
+#[test]
+fn submit_block_accepts_the_next_block() {
+    let test = SubmitBlockTest::new("accepted");
+    let block = mine(&test.template());
+
+    assert_eq!(test.submit(&block), "null");
+    let node_state = &test.rpc_handler.node_state_ref;
+    assert_eq!(
+        node_state.get_last_header_hash().unwrap(),
+        Some(*block.header.hash())
+    );
+    assert!(node_state.has_block(block.header.hash()).unwrap());
+    assert!(matches!(
+        test.node_action_receiver.recv(),
+        Ok(NodeAction::LocalBlock(local_block)) if local_block.header.hash() == block.header.hash()
+    ));
+}
+
+#[test]
+fn submit_block_rejects_a_duplicate() {
+    let test = SubmitBlockTest::new("duplicate");
+    let block = mine(&test.template());
+
+    assert_eq!(test.submit(&block), "null");
+    assert_eq!(test.submit(&block), "\"duplicate\"");
+}
+
+#[test]
+fn submit_block_rejects_an_unknown_previous_block() {
+    let test = SubmitBlockTest::new("prev_not_found");
+    let mut template = test.template();
+    template.previous_block_hash = Hash32::new([1; 32]);
+
+    assert_eq!(test.submit(&mine(&template)), "\"prev-blk-not-found\"");
+}
+
+#[test]
+fn submit_block_rejects_unexpected_bits() {
+    let test = SubmitBlockTest::new("bad_diffbits");
+    let mut template = test.template();
+    template.bits -= 1;
+
+    assert_eq!(test.submit(&mine(&template)), "\"bad-diffbits\"");
+}
+
+#[test]
+fn submit_block_rejects_a_timestamp_at_the_median_time_past() {
+    let test = SubmitBlockTest::new("time_too_old");
+    let mut template = test.template();
+    template.cur_time = template.min_time - 1;
+
+    assert_eq!(test.submit(&mine(&template)), "\"time-too-old\"");
+}
+
+#[test]
+fn submit_block_rejects_a_coinbase_with_the_wrong_height() {
+    let test = SubmitBlockTest::new("bad_cb_height");
+    let mut template = test.template();
+    template.height += 1;
+
+    assert_eq!(test.submit(&mine(&template)), "\"bad-cb-height\"");
+}